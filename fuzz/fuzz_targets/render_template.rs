@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use netgen::template::render;
+
+// Properties of the templating, on top of it never panicking:
+// - a file without template syntax renders to itself;
+// - a loop over `range(n)` repeats its body n times.
+fuzz_target!(|input: (&str, u8)| {
+    let (source, count) = input;
+    let rendered = render(source);
+
+    if source.contains("{{") || source.contains("{%") {
+        return;
+    }
+    assert_eq!(rendered.ok().as_deref(), Some(source));
+
+    // `lines` would split it, or drop a trailing '\r'.
+    if source.contains(['\n', '\r']) {
+        return;
+    }
+    let looped =
+        format!("{{% for i in range({count}) %}}\n{source}\n{{% endfor %}}\n");
+    let expected = format!("{source}\n").repeat(usize::from(count));
+    assert_eq!(render(&looped).ok(), Some(expected));
+});
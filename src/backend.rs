@@ -0,0 +1,165 @@
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Output};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::error::NamespaceError;
+use crate::{NamespaceHandle, NetResult, destroy_ns, mount_device};
+
+// Name the main namespace is known by, as for `NamespaceHandle`.
+const MAIN_NAMESPACE: &str = "main";
+
+/// Where the namespaces of a topology live and how commands get run in
+/// them.
+///
+/// `device` is the router owning the namespaces, or None for the main
+/// namespace, the switches and the ends of the links attached to them living
+/// there.
+pub trait Backend: Debug + Send + Sync {
+    /// Creates the namespaces of `device`. Namespaces which exist already
+    /// are reused as they are.
+    fn create_namespace(&self, device: Option<&str>) -> NetResult<()>;
+
+    /// Deletes the namespaces of `device`, along with everything running in
+    /// them.
+    fn destroy_namespace(&self, device: Option<&str>) -> NetResult<()>;
+
+    /// Whether the namespaces of `device` exist and are alive.
+    fn namespace_exists(&self, device: Option<&str>) -> bool;
+
+    /// Runs `cmd` with `args` in the network namespace of `device` and waits
+    /// for it to complete, capturing its output.
+    fn exec(
+        &self,
+        device: Option<&str>,
+        cmd: &str,
+        args: &[&str],
+    ) -> NetResult<Output>;
+}
+
+// ==== struct LinuxBackend ====
+
+/// Network and PID namespaces mounted under `ns_dir()`, the backend netgen runs
+/// topologies on. Needs root.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LinuxBackend;
+
+impl Backend for LinuxBackend {
+    fn create_namespace(&self, device: Option<&str>) -> NetResult<()> {
+        mount_device(device.map(str::to_string))?;
+        Ok(())
+    }
+
+    fn destroy_namespace(&self, device: Option<&str>) -> NetResult<()> {
+        destroy_ns(device.map(str::to_string))
+    }
+
+    fn namespace_exists(&self, device: Option<&str>) -> bool {
+        NamespaceHandle::new(device).is_active()
+    }
+
+    fn exec(
+        &self,
+        device: Option<&str>,
+        cmd: &str,
+        args: &[&str],
+    ) -> NetResult<Output> {
+        NamespaceHandle::new(device).exec(cmd, args)
+    }
+}
+
+// ==== struct MockBackend ====
+
+/// In-memory backend, keeping track of the namespaces and of the commands
+/// run in them without touching the host.
+///
+/// Lets a topology be powered on and off, partitioned or checked for its
+/// commands without privileges, e.g. in unit tests. The crate still only
+/// builds on Linux: the mock does away with root, not with the Linux-only
+/// dependencies (nix, rtnetlink) the rest of netgen is written against.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    namespaces: Mutex<BTreeSet<String>>,
+    commands: Mutex<Vec<MockCommand>>,
+}
+
+/// A command run through `MockBackend`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockCommand {
+    pub device: Option<String>,
+    pub cmd: String,
+    pub args: Vec<String>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names of the namespaces currently created, "main" standing for the
+    /// main namespace.
+    pub fn namespaces(&self) -> Vec<String> {
+        self.lock_namespaces().iter().cloned().collect()
+    }
+
+    /// Commands run so far, in order.
+    pub fn commands(&self) -> Vec<MockCommand> {
+        self.lock_commands().clone()
+    }
+
+    fn lock_namespaces(&self) -> MutexGuard<'_, BTreeSet<String>> {
+        self.namespaces
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn lock_commands(&self) -> MutexGuard<'_, Vec<MockCommand>> {
+        self.commands.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Backend for MockBackend {
+    fn create_namespace(&self, device: Option<&str>) -> NetResult<()> {
+        self.lock_namespaces()
+            .insert(device.unwrap_or(MAIN_NAMESPACE).to_string());
+        Ok(())
+    }
+
+    fn destroy_namespace(&self, device: Option<&str>) -> NetResult<()> {
+        self.lock_namespaces()
+            .remove(device.unwrap_or(MAIN_NAMESPACE));
+        Ok(())
+    }
+
+    fn namespace_exists(&self, device: Option<&str>) -> bool {
+        self.lock_namespaces()
+            .contains(device.unwrap_or(MAIN_NAMESPACE))
+    }
+
+    /// Records the command and reports it as successful, without output.
+    fn exec(
+        &self,
+        device: Option<&str>,
+        cmd: &str,
+        args: &[&str],
+    ) -> NetResult<Output> {
+        if !self.namespace_exists(device) {
+            return Err(NamespaceError::NotFound {
+                device: device.unwrap_or(MAIN_NAMESPACE).to_string(),
+            }
+            .into());
+        }
+
+        self.lock_commands().push(MockCommand {
+            device: device.map(str::to_string),
+            cmd: cmd.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        });
+        Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+}
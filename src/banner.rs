@@ -0,0 +1,113 @@
+use std::fmt::Write as _;
+
+use crate::NetResult;
+use crate::error::ConfigError;
+use crate::node::{Device, Node};
+use crate::topology::Topology;
+
+/// Summary of a node printed when logging into it: its interfaces and their
+/// addresses, what they are linked to, and the processes netgen started in
+/// it.
+///
+/// ```text
+/// rt1 (router, kind holo, router-id 1.1.1.1)
+///
+/// interfaces:
+///   eth-sw1  10.0.1.1/24  -> sw1:eth-rt1
+///   lo       1.1.1.1/32
+///
+/// daemons:
+///   ./holo.sh  pid 1234  running
+/// ```
+pub fn banner(topology: &Topology, node_name: &str) -> NetResult<String> {
+    let node = topology
+        .get_node(node_name)
+        .ok_or_else(|| ConfigError::UnknownNode(node_name.to_string()))?;
+
+    let mut details = vec![];
+    match node {
+        Node::Router(router) => {
+            details.push("router".to_string());
+            if let Some(kind) = &router.kind {
+                details.push(format!("kind {kind}"));
+            }
+            if let Some(router_id) = router.router_id() {
+                details.push(format!("router-id {router_id}"));
+            }
+            if router.is_dut() {
+                details.push("DUT".to_string());
+            }
+        }
+        Node::Switch(switch) => {
+            details.push(format!("switch, bridge {}", switch.bridge()));
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{node_name} ({})", details.join(", "));
+
+    let rows: Vec<[String; 3]> = node
+        .interfaces()
+        .iter()
+        .map(|iface| {
+            let addresses = iface
+                .addresses
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" ");
+            let peer = topology
+                .links_of(node_name)
+                .find_map(|link| {
+                    let [src, dst] = link.ends();
+                    match src.is(node_name, &iface.name) {
+                        true => Some(dst),
+                        false => dst.is(node_name, &iface.name).then_some(src),
+                    }
+                })
+                .map_or_else(String::new, |peer| format!("-> {peer}"));
+            [iface.name.clone(), addresses, peer]
+        })
+        .collect();
+    if !rows.is_empty() {
+        let width = |column: usize| {
+            rows.iter()
+                .map(|row| row[column].len())
+                .max()
+                .unwrap_or_default()
+        };
+        let (name_width, addr_width) = (width(0), width(1));
+        let _ = writeln!(out, "\ninterfaces:");
+        for [name, addresses, peer] in &rows {
+            let line = format!(
+                "  {name:name_width$}  {addresses:addr_width$}  {peer}"
+            );
+            let _ = writeln!(out, "{}", line.trim_end());
+        }
+    }
+
+    let daemons = node.status()?;
+    if !daemons.is_empty() {
+        let name_width = daemons
+            .iter()
+            .map(|daemon| daemon.name.len())
+            .max()
+            .unwrap_or_default();
+        let _ = writeln!(out, "\ndaemons:");
+        for daemon in &daemons {
+            let state = match (daemon.running, &daemon.crash) {
+                (true, _) => "running".to_string(),
+                (false, Some(crash)) => {
+                    format!("crashed, see {}", crash.display())
+                }
+                (false, None) => "dead".to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "  {:name_width$}  pid {}  {state}",
+                daemon.name, daemon.pid
+            );
+        }
+    }
+    Ok(out)
+}
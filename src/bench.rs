@@ -0,0 +1,399 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+use ipnetwork::IpNetwork;
+use tracing::{debug, warn};
+
+use crate::error::{ConfigError, NetError};
+use crate::link::LinkEndpoint;
+use crate::snapshot::Counters;
+use crate::topology::{Topology, parse_end};
+use crate::{NamespaceHandle, NetResult, with_run_dir};
+
+pub const DEFAULT_FRAME_SIZE: u16 = 64;
+
+// Destination of the frames when the receiving interface has no IPv4
+// address (TEST-NET-2).
+const FALLBACK_DST: &str = "198.51.100.1";
+
+// Where the XDP program is pinned for `ip` to attach it.
+const PIN_DIR: &str = "/sys/fs/bpf";
+
+// From linux/bpf.h.
+const BPF_PROG_LOAD: libc::c_long = 5;
+const BPF_OBJ_PIN: libc::c_long = 6;
+const BPF_PROG_TYPE_XDP: u32 = 6;
+
+// ==== struct BenchOptions ====
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchOptions {
+    pub duration: Duration,
+    // Size of the frames sent, Ethernet header included.
+    pub frame_size: u16,
+    // Interfaces ("<node>:<iface>") given a pass-through XDP program for the
+    // run.
+    pub xdp: Vec<String>,
+}
+
+// ==== struct BenchResult ====
+
+/// Packets blasted from one interface towards another, and how many of them
+/// made it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub from: String,
+    pub to: String,
+    pub frame_size: u16,
+    pub xdp: Vec<String>,
+    pub sent: u64,
+    pub received: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn tx_pps(&self) -> f64 {
+        self.sent as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn rx_pps(&self) -> f64 {
+        self.received as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let xdp = match self.xdp.is_empty() {
+            true => "no XDP".to_string(),
+            false => format!("XDP on {}", self.xdp.join(", ")),
+        };
+        let _ = writeln!(
+            out,
+            "{} -> {} ({}-byte frames, {xdp})",
+            self.from, self.to, self.frame_size
+        );
+        let _ = writeln!(out, "  sent      {:>12.0} pps", self.tx_pps());
+        let share = match self.sent {
+            0 => 0.0,
+            sent => self.received as f64 / sent as f64 * 100.0,
+        };
+        let _ = writeln!(
+            out,
+            "  received  {:>12.0} pps ({share:.1}%)",
+            self.rx_pps()
+        );
+        out
+    }
+}
+
+/// Sends frames as fast as the kernel's pktgen can from the `src` interface
+/// ("<node>:<iface>") to the `dst` one for the duration of the run, and
+/// counts those received.
+///
+/// The frames are addressed to the MAC address of `dst`, which has to be on
+/// the same segment as `src`, directly or through switches. This measures
+/// what the emulated fabric itself can carry, optionally with XDP attached
+/// to some of its veths.
+pub fn run(
+    topology: &Topology,
+    src: &str,
+    dst: &str,
+    options: &BenchOptions,
+) -> NetResult<BenchResult> {
+    let (src, dst) = (parse_end(src)?, parse_end(dst)?);
+    for end in [&src, &dst] {
+        router_ns(topology, end)?;
+    }
+    let xdp = options
+        .xdp
+        .iter()
+        .map(|end| parse_end(end))
+        .collect::<NetResult<Vec<_>>>()?;
+
+    let pin = format!("{PIN_DIR}/netgen-xdp-pass-{}", std::process::id());
+    let prog = load_xdp_pass()?;
+    pin_prog(&prog, &pin)?;
+    let mut attached = vec![];
+    let result = xdp
+        .iter()
+        .try_for_each(|end| {
+            set_xdp(topology, end, &["pinned", &pin])?;
+            attached.push(end);
+            Ok(())
+        })
+        .and_then(|()| blast(topology, &src, &dst, options));
+
+    for end in attached {
+        if let Err(err) = set_xdp(topology, end, &["off"]) {
+            warn!(%end, %err, "unable to detach XDP");
+        }
+    }
+    let _ = fs::remove_file(&pin);
+    result
+}
+
+fn blast(
+    topology: &Topology,
+    src: &LinkEndpoint,
+    dst: &LinkEndpoint,
+    options: &BenchOptions,
+) -> NetResult<BenchResult> {
+    let (src_ns, dst_ns) =
+        (router_ns(topology, src)?, router_ns(topology, dst)?);
+    let (src_node, src_iface) = (src.node.as_str(), src.iface.as_str());
+    let (dst_node, dst_iface) = (dst.node.as_str(), dst.iface.as_str());
+
+    // pktgen has a directory of its own in every network namespace, once
+    // the module is loaded.
+    let _ = std::process::Command::new("modprobe")
+        .arg("pktgen")
+        .output();
+
+    let dst_mac = mac_address(&dst_ns, dst_iface)?;
+    let dst_ip = topology
+        .get_node(dst_node)
+        .into_iter()
+        .flat_map(|node| node.interfaces())
+        .filter(|iface| iface.name == dst_iface)
+        .flat_map(|iface| iface.addresses.iter())
+        .find_map(|address| match address {
+            IpNetwork::V4(address) => Some(address.ip().to_string()),
+            IpNetwork::V6(_) => None,
+        })
+        .unwrap_or_else(|| FALLBACK_DST.to_string());
+    pktgen(
+        &src_ns,
+        &format!(
+            "pg() {{ echo \"$2\" > /proc/net/pktgen/$1; }}
+            pg kpktgend_0 rem_device_all
+            pg kpktgend_0 'add_device {src_iface}'
+            pg {src_iface} 'count 0'
+            pg {src_iface} 'delay 0'
+            pg {src_iface} 'pkt_size {}'
+            pg {src_iface} 'dst {dst_ip}'
+            pg {src_iface} 'dst_mac {dst_mac}'",
+            options.frame_size
+        ),
+    )?;
+
+    let tx = |counters: &Counters| counters.tx_packets;
+    let rx = |counters: &Counters| counters.rx_packets;
+    let before = (
+        packets(src_node, src_iface, tx)?,
+        packets(dst_node, dst_iface, rx)?,
+    );
+    let started = Instant::now();
+
+    // Writing "start" only returns once pktgen is stopped.
+    debug!(src = %src, dst = %dst, "starting pktgen");
+    let sender_ns = src_ns.clone();
+    let sender = std::thread::spawn(with_run_dir(move || {
+        pktgen(&sender_ns, "echo start > /proc/net/pktgen/pgctrl")
+    }));
+    std::thread::sleep(options.duration);
+    let after = packets(src_node, src_iface, tx)
+        .and_then(|sent| Ok((sent, packets(dst_node, dst_iface, rx)?)));
+    let elapsed = started.elapsed();
+
+    // Stopped whatever happened, it would otherwise keep going.
+    let stopped = pktgen(&src_ns, "echo stop > /proc/net/pktgen/pgctrl");
+    let _ = sender.join();
+    stopped?;
+    let after = after?;
+
+    Ok(BenchResult {
+        from: src.to_string(),
+        to: dst.to_string(),
+        frame_size: options.frame_size,
+        xdp: options.xdp.clone(),
+        sent: after.0.saturating_sub(before.0),
+        received: after.1.saturating_sub(before.1),
+        elapsed,
+    })
+}
+
+// The namespace of the router the end is on. pktgen is only set up in
+// routers.
+fn router_ns(
+    topology: &Topology,
+    end: &LinkEndpoint,
+) -> NetResult<NamespaceHandle> {
+    let node = end.node.as_str();
+    match topology.get_node(node) {
+        Some(node) if node.as_router().is_some() => {}
+        Some(_) => {
+            return Err(NetError::BasicError(format!(
+                "{node} is a switch, frames are sent and counted on routers"
+            )));
+        }
+        None => return Err(ConfigError::UnknownNode(node.to_string()).into()),
+    }
+    Ok(NamespaceHandle::new(Some(node)))
+}
+
+fn packets(
+    node: &str,
+    iface: &str,
+    count: impl Fn(&Counters) -> u64,
+) -> NetResult<u64> {
+    Counters::read(node)?.get(iface).map(count).ok_or_else(|| {
+        NetError::BasicError(format!("No interface {iface} on {node}"))
+    })
+}
+
+fn mac_address(namespace: &NamespaceHandle, iface: &str) -> NetResult<String> {
+    let output =
+        namespace.exec("ip", &["-br", "link", "show", "dev", iface])?;
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .nth(2)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            NetError::BasicError(format!(
+                "Unable to read the MAC address of {iface}"
+            ))
+        })
+}
+
+fn pktgen(namespace: &NamespaceHandle, script: &str) -> NetResult<()> {
+    let output = namespace.exec("sh", &["-c", script])?;
+    if !output.status.success() {
+        return Err(NetError::BasicError(format!(
+            "Unable to drive pktgen: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+// Attaches the pinned program to the end in native mode, or detaches it.
+fn set_xdp(
+    topology: &Topology,
+    end: &LinkEndpoint,
+    args: &[&str],
+) -> NetResult<()> {
+    let node = end.node.as_str();
+    let router = topology
+        .get_node(node)
+        .is_some_and(|node| node.as_router().is_some());
+    // Switch ends live in the main namespace.
+    let namespace = NamespaceHandle::new(router.then_some(node));
+    let mut ip_args = vec!["link", "set", "dev", end.iface.as_str(), "xdpdrv"];
+    ip_args.extend(args);
+    let output = namespace.exec("ip", &ip_args)?;
+    if !output.status.success() {
+        return Err(NetError::BasicError(format!(
+            "Unable to set XDP on {end}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+// `r0 = XDP_PASS; exit`, which needs no compiler to build.
+fn load_xdp_pass() -> NetResult<OwnedFd> {
+    #[repr(C)]
+    struct Insn {
+        code: u8,
+        regs: u8,
+        off: i16,
+        imm: i32,
+    }
+    #[repr(C)]
+    #[derive(Default)]
+    struct ProgLoadAttr {
+        prog_type: u32,
+        insn_cnt: u32,
+        insns: u64,
+        license: u64,
+        log_level: u32,
+        log_size: u32,
+        log_buf: u64,
+        kern_version: u32,
+        prog_flags: u32,
+        prog_name: [u8; 16],
+    }
+
+    let insns = [
+        // BPF_ALU64 | BPF_MOV | BPF_K, r0 = 2 (XDP_PASS)
+        Insn {
+            code: 0xb7,
+            regs: 0,
+            off: 0,
+            imm: 2,
+        },
+        // BPF_JMP | BPF_EXIT
+        Insn {
+            code: 0x95,
+            regs: 0,
+            off: 0,
+            imm: 0,
+        },
+    ];
+    let license = c"GPL";
+    let mut attr = ProgLoadAttr {
+        prog_type: BPF_PROG_TYPE_XDP,
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: license.as_ptr() as u64,
+        ..Default::default()
+    };
+    attr.prog_name[..8].copy_from_slice(b"xdp_pass");
+
+    // SAFETY: `attr` is a valid bpf_attr prefix for BPF_PROG_LOAD, pointing
+    // to instructions and a license which outlive the call.
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_PROG_LOAD,
+            &mut attr as *mut ProgLoadAttr,
+            size_of::<ProgLoadAttr>(),
+        )
+    };
+    if fd < 0 {
+        return Err(NetError::BasicError(format!(
+            "Unable to load the XDP program: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    // SAFETY: `fd` was just returned by the kernel and is owned by no one
+    // else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+fn pin_prog(prog: &OwnedFd, path: &str) -> NetResult<()> {
+    #[repr(C)]
+    struct ObjPinAttr {
+        pathname: u64,
+        bpf_fd: u32,
+        file_flags: u32,
+    }
+
+    let pathname = std::ffi::CString::new(path).map_err(|_| {
+        NetError::BasicError(format!("Invalid pin path {path}"))
+    })?;
+    let mut attr = ObjPinAttr {
+        pathname: pathname.as_ptr() as u64,
+        bpf_fd: prog.as_raw_fd() as u32,
+        file_flags: 0,
+    };
+    // SAFETY: `attr` is a valid bpf_attr prefix for BPF_OBJ_PIN, pointing to
+    // a path which outlives the call.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_OBJ_PIN,
+            &mut attr as *mut ObjPinAttr,
+            size_of::<ObjPinAttr>(),
+        )
+    };
+    if ret < 0 {
+        return Err(NetError::BasicError(format!(
+            "Unable to pin the XDP program to {path} (is bpffs mounted on \
+                {PIN_DIR}?): {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
@@ -1,22 +1,59 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::CString;
+use std::fmt;
 use std::fs::{self, File};
+use std::io::{IsTerminal, Write};
+use std::net::IpAddr;
+use std::os::unix::process::ExitStatusExt;
 use std::path::Path;
+use std::process::ExitStatus;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use clap::{Arg, ArgMatches, command};
+use clap::{Arg, ArgAction, ArgMatches, command};
+use ipnetwork::IpNetwork;
+use netgen::bench::BenchOptions;
+use netgen::bundle::Bundle;
+use netgen::calibrate::{Calibration, REFERENCE_TOPOLOGY};
+use netgen::cancel::CancelToken;
+use netgen::churn::ChurnRun;
+use netgen::diff::TopologyDiff;
 use netgen::error::{ConfigError, NamespaceError, NetError};
+use netgen::exec_history::ExecHistory;
+use netgen::fib::StaticRoute;
+use netgen::init::{Addressing, CONFIGURED_DAEMONS, Shape, Skeleton};
+use netgen::kernel::Kernel;
+use netgen::lab::{Lab, LabRegistry};
 use netgen::node::Router;
+use netgen::notify::{Notification, Trigger};
+use netgen::plan::Plan;
+use netgen::provenance::Provenance;
+use netgen::report::{ReportFormat, TestCase, TestReport};
+use netgen::runs::{RunHistory, RunRecord};
+use netgen::schedule::Schedule;
+use netgen::snapshot::TopologySnapshot;
+use netgen::state::TopologyState;
+use netgen::suite::{Suite, SuiteParser};
+use netgen::sweep::{self, SweepReport, SweepVar};
 use netgen::topology::{Topology, TopologyParser};
+use netgen::watch::Watcher;
 use netgen::{
-    DEVICES_NS_DIR, MAIN_NS_DIR, NetResult, mount_device, mount_router_volumes,
+    NamespaceHandle, NetResult, devices_ns_dir, mount_device,
+    mount_router_volumes,
 };
+use netgen::{draw, lldp, ps, runs, sockets};
 use nix::mount::{MsFlags, mount};
 use nix::sched::{CloneFlags, unshare};
-use nix::sys::wait::waitpid;
-use nix::unistd::{ForkResult, Pid, execvp, fork};
-use tracing::{Level, debug, error};
-use tracing_subscriber::filter::LevelFilter;
+use nix::sys::wait::{WaitStatus, waitpid};
+use nix::unistd::{ForkResult, Pid, execvpe, fork};
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::filter::{LevelFilter, Targets};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::registry::Registry;
 
+// Checkpoint taken and restored when none is named.
+const DEFAULT_CHECKPOINT: &str = "latest";
+
 fn main() {
     if let Err(err) = ngen_main() {
         error!(%err);
@@ -25,16 +62,52 @@ fn main() {
 }
 
 fn ngen_main() -> NetResult<()> {
-    init_tracing();
     let app_match = command!("netgen")
+        .arg(
+            Arg::new("Verbose")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::Count)
+                .global(true)
+                .help("show more of what netgen does, -vv down to netlink"),
+        )
+        .arg(
+            Arg::new("Quiet")
+                .short('q')
+                .long("quiet")
+                .action(ArgAction::Count)
+                .global(true)
+                .conflicts_with("Verbose")
+                .help("only show errors, -qq for nothing but the output"),
+        )
+        .subcommand(
+            command!("init")
+                .args(init_args())
+                .about("writes a topology and startup configs, guided"),
+        )
+        .subcommand(
+            command!("quick")
+                .args(quick_args())
+                .arg(parallelism_arg())
+                .arg(force_arg())
+                .about("starts a ring or chain of routers, no YAML needed"),
+        )
         .subcommand(
             command!("start")
                 .args(config_args())
+                .args(subset_args())
+                .arg(parallelism_arg())
+                .arg(force_arg())
+                .arg(strictness_arg())
+                .arg(tune_arg())
                 .about("starts the netgen setup"),
         )
+        .subcommand(command!("apply").args(apply_args()).about(
+            "brings the running topology in line with the topology file",
+        ))
         .subcommand(
             command!("stop")
-                .args(config_args())
+                .args(stop_args())
                 .about("stops the running netgen setup"),
         )
         .subcommand(
@@ -42,59 +115,446 @@ fn ngen_main() -> NetResult<()> {
                 .args(login_args())
                 .about("logs into device"),
         )
+        .subcommand(
+            command!("exec")
+                .args(exec_args())
+                .about("runs a command in a router of the running topology"),
+        )
+        .subcommand(
+            command!("lldp")
+                .args(lldp_args())
+                .about("lists the LLDP neighbors of a router"),
+        )
+        .subcommand(
+            command!("ps")
+                .args(ps_args())
+                .about("lists the processes running in a router"),
+        )
+        .subcommand(
+            command!("sockets")
+                .args(sockets_args())
+                .about("lists the sockets a router listens and talks on"),
+        )
+        .subcommand(
+            command!("shell")
+                .args(shell_args())
+                .about("opens a shell in a router of the running topology"),
+        )
+        .subcommand(
+            command!("cp")
+                .args(cp_args())
+                .about("copies files between the host and a router"),
+        )
         .subcommand(
             command!("ls")
                 .args(ls_args())
                 .about("lists all the running devices"),
         )
+        .subcommand(
+            command!("stats")
+                .args(stats_args())
+                .about("shows the traffic rates of the router interfaces"),
+        )
+        .subcommand(
+            command!("watch")
+                .args(watch_args())
+                .about("streams the changes of the running topology"),
+        )
+        .subcommand(
+            command!("events")
+                .args(events_args())
+                .about("prints the event log, from a sequence number on"),
+        )
+        .subcommand(
+            command!("status")
+                .args(status_args())
+                .about("compares the running topology with the topology file"),
+        )
+        .subcommand(
+            command!("graph")
+                .args(graph_args())
+                .about("draws the topology, in DOT by default"),
+        )
+        .subcommand(
+            command!("partition")
+                .args(partition_args())
+                .about("splits the running topology into isolated groups"),
+        )
+        .subcommand(
+            command!("heal")
+                .args(config_args())
+                .about("restores the links cut by partition"),
+        )
+        .subcommand(
+            command!("node")
+                .about("changes nodes of the running topology")
+                .subcommand(
+                    command!("clone")
+                        .args(node_clone_args())
+                        .about("adds a copy of a router and brings it up"),
+                )
+                .subcommand(
+                    command!("remove")
+                        .args(node_remove_args())
+                        .about("removes a node along with its links"),
+                )
+                .subcommand(
+                    command!("checkpoint")
+                        .args(node_checkpoint_args())
+                        .about("checkpoints the daemons of a router with CRIU"),
+                )
+                .subcommand(
+                    command!("restore").args(node_restore_args()).about(
+                        "restores the daemons of a router from a checkpoint",
+                    ),
+                ),
+        )
+        .subcommand(
+            command!("session")
+                .about("manages the session of the running topology")
+                .subcommand(
+                    command!("rename")
+                        .args(session_rename_args())
+                        .about("renames the session without a teardown"),
+                ),
+        )
+        .subcommand(
+            command!("link")
+                .about("changes links of the running topology")
+                .subcommand(
+                    command!("add")
+                        .args(link_args())
+                        .about("links two interfaces and brings them up"),
+                )
+                .subcommand(
+                    command!("remove")
+                        .args(link_args())
+                        .about("removes the link between two interfaces"),
+                )
+                .subcommand(
+                    command!("enable")
+                        .args(link_ends_args())
+                        .about("brings a link back up, and keeps it up"),
+                )
+                .subcommand(
+                    command!("disable")
+                        .args(link_ends_args())
+                        .about("brings a link down, and keeps it down"),
+                )
+                .subcommand(
+                    command!("latency").args(link_latency_csv_args()).about(
+                        "measures the one-way latency and drops of a link",
+                    ),
+                )
+                .subcommand(command!("bench").args(link_bench_args()).about(
+                    "measures the packet rate the emulated fabric carries",
+                ))
+                .subcommand(
+                    command!("export")
+                        .about("prints the impairments the links run with"),
+                )
+                .subcommand(
+                    command!("import")
+                        .args(link_import_args())
+                        .about("reapplies impairments printed by export"),
+                ),
+        )
+        .subcommand(
+            command!("route")
+                .about("changes the FIBs of the running routers")
+                .subcommand(
+                    command!("add")
+                        .args(route_add_args())
+                        .about("programs a route without a routing daemon"),
+                ),
+        )
+        .subcommand(
+            command!("address")
+                .about("changes the addresses of the running routers")
+                .subcommand(
+                    command!("add")
+                        .args(address_add_args())
+                        .about("adds addresses to interfaces, in bulk"),
+                ),
+        )
+        .subcommand(
+            command!("iface")
+                .about("changes the interfaces of the running routers")
+                .subcommand(
+                    command!("move")
+                        .args(iface_move_args())
+                        .about("moves a link end to another router, live"),
+                ),
+        )
+        .subcommand(
+            command!("mgmt")
+                .about("cuts routers off from their controllers, or not")
+                .subcommand(
+                    command!("down")
+                        .args(mgmt_args())
+                        .about("blocks the management plane of routers"),
+                )
+                .subcommand(
+                    command!("up")
+                        .args(mgmt_args())
+                        .about("unblocks the management plane of routers"),
+                ),
+        )
+        .subcommand(
+            command!("tools")
+                .args(tools_args())
+                .about("lists the programs netgen runs, installed or not"),
+        )
+        .subcommand(
+            command!("bundle")
+                .args(bundle_args())
+                .about("archives the artifacts of a run for bug reports"),
+        )
+        .subcommand(
+            command!("check")
+                .about("checks properties of the running topology")
+                .subcommand(
+                    command!("pmtu")
+                        .args(check_pmtu_args())
+                        .about("discovers the path MTU between two nodes"),
+                )
+                .subcommand(
+                    command!("path")
+                        .args(check_path_args())
+                        .about("traces the paths between two nodes both ways"),
+                )
+                .subcommand(
+                    command!("route")
+                        .args(check_route_args())
+                        .about("waits for a node to have a route"),
+                ),
+        )
+        .subcommand(command!("verify").args(verify_args()).about(
+            "checks the invariants and validators of the running topology",
+        ))
+        .subcommand(
+            command!("lab")
+                .about("manages the labs registered by name")
+                .subcommand(
+                    command!("add")
+                        .args(lab_add_args())
+                        .about("registers a topology file under a name"),
+                )
+                .subcommand(
+                    command!("list")
+                        .args(lab_list_args())
+                        .about("lists the registered labs"),
+                )
+                .subcommand(
+                    command!("start")
+                        .args(lab_start_args())
+                        .about("starts a registered lab"),
+                ),
+        )
+        .subcommand(command!("import").args(import_args()).about(
+            "generates a topology file from existing namespaces or configs",
+        ))
+        .subcommand(
+            command!("fmt")
+                .args(fmt_args())
+                .about("rewrites a topology file in canonical form"),
+        )
+        .subcommand(
+            command!("diff").args(diff_args()).about(
+                "shows the nodes and links two topology files differ by",
+            ),
+        )
+        .subcommand(
+            command!("history")
+                .args(history_args())
+                .about("lists who started, changed and stopped topologies"),
+        )
+        .subcommand(
+            command!("schedule")
+                .args(schedule_args())
+                .about("takes measurements of the running topology on a timer"),
+        )
+        .subcommand(
+            command!("sweep")
+                .args(sweep_args())
+                .about("runs a test suite across a grid of parameters"),
+        )
+        .subcommand(
+            command!("calibrate")
+                .args(calibrate_args())
+                .about("measures what a veth pair carries on this host"),
+        )
+        .subcommand(
+            command!("test")
+                .about("runs test suites against topologies")
+                .subcommand(
+                    command!("run")
+                        .args(test_run_args())
+                        .about("runs a test suite end to end"),
+                ),
+        )
         .get_matches();
+    init_tracing(verbosity(&app_match));
+
+    let Some(command) = recorded_command(&app_match) else {
+        return run_command(&app_match);
+    };
+    // The forked processes bringing devices up return from the command too,
+    // only the original one records the run.
+    let pid = std::process::id();
+    let before = Provenance::running().ok().flatten();
+    let started = SystemTime::now();
+    let result = run_command(&app_match);
+    if std::process::id() == pid {
+        record_run(command, before, started, &result);
+    }
+    result
+}
 
+/// Runs the subcommand `app_match` holds.
+fn run_command(app_match: &ArgMatches) -> NetResult<()> {
     match app_match.subcommand() {
+        Some(("init", init_args)) => init_topology(init_args)?,
+        Some(("quick", quick_args)) => quick_topology(quick_args)?,
         Some(("ls", ls_args)) => {
             let (topology, _config_file_name) = parse_config_args(ls_args)?;
             println!("{:#?}", topology.print_nodes());
         }
         Some(("start", start_args)) => {
-            let (mut topology, config_file_name) =
-                parse_config_args(start_args)?;
-
-            if instance_running() {
-                let err = NetError::BasicError(format!(
-                    "Topology is currently running. \
-                        Consider running 'netgen stop -t {config_file_name}' \
-                        then try again.",
-                ));
-                error!(%err);
-                std::process::exit(1);
+            let (topology, config_file_name) = parse_config_args(start_args)?;
+            let mut topology = select_subset(topology, start_args)?;
+            set_parallelism(&mut topology, start_args);
+            check_strictness(&topology, start_args)?;
+            check_resources(&topology, start_args)?;
+            check_kernel(&topology, start_args)?;
+            check_container(
+                start_args
+                    .get_one::<String>("Strictness")
+                    .is_some_and(|strictness| strictness == "strict"),
+            )?;
+            if start_args.get_flag("Force") && !instance_running() {
+                netgen::clean_stale_run()?;
             }
-
-            let pid = Pid::this();
-
-            // Create the directory storing our namespaces if it doesn't exists.
-            let _ = fs::create_dir_all(DEVICES_NS_DIR);
-
-            create_routers(&mut topology).map_err(|err| {
-                error!(%err);
-                std::process::exit(1);
-            });
-
-            // Check if this is the child process.
-            if Pid::this() == pid {
-                return Ok(());
+            if start_topology(&mut topology, &config_file_name)? {
+                report_issues(&topology);
+            }
+        }
+        Some(("apply", apply_args)) => {
+            apply_topology(apply_args)?;
+        }
+        Some(("stats", stats_args)) => show_stats(stats_args)?,
+        Some(("watch", watch_args)) => watch_topology(watch_args)?,
+        Some(("events", events_args)) => show_events(events_args)?,
+        Some(("status", status_args)) => {
+            show_status(status_args)?;
+        }
+        Some(("graph", graph_args)) => show_graph(graph_args)?,
+        Some(("partition", partition_args)) => {
+            partition_topology(partition_args)?;
+        }
+        Some(("heal", heal_args)) => {
+            let (topology, _config_file_name) = parse_config_args(heal_args)?;
+            for link in topology.heal()? {
+                info!("healed {} <-> {}", link.src, link.dst);
+            }
+        }
+        Some(("node", node_args)) => match node_args.subcommand() {
+            Some(("clone", clone_args)) => clone_node(clone_args)?,
+            Some(("remove", remove_args)) => remove_node(remove_args)?,
+            Some(("checkpoint", checkpoint_args)) => {
+                checkpoint_node(checkpoint_args)?
+            }
+            Some(("restore", restore_args)) => restore_node(restore_args)?,
+            _ => {}
+        },
+        Some(("session", session_args)) => {
+            if let Some(("rename", rename_args)) = session_args.subcommand() {
+                rename_session(rename_args)?;
+            }
+        }
+        Some(("link", link_args)) => match link_args.subcommand() {
+            Some(("add", add_args)) => change_link(add_args, true)?,
+            Some(("remove", remove_args)) => change_link(remove_args, false)?,
+            Some(("enable", enable_args)) => toggle_link(enable_args, true)?,
+            Some(("disable", disable_args)) => {
+                toggle_link(disable_args, false)?
+            }
+            Some(("latency", latency_args)) => show_latency(latency_args)?,
+            Some(("bench", bench_args)) => run_bench(bench_args)?,
+            Some(("export", _)) => {
+                print!("{}", running_topology()?.export_impairments()?)
+            }
+            Some(("import", import_args)) => import_impairments(import_args)?,
+            _ => {}
+        },
+        Some(("route", route_args)) => {
+            if let Some(("add", add_args)) = route_args.subcommand() {
+                add_route(add_args)?;
+            }
+        }
+        Some(("address", address_args)) => {
+            if let Some(("add", add_args)) = address_args.subcommand() {
+                add_addresses(add_args)?;
+            }
+        }
+        Some(("iface", iface_args)) => {
+            if let Some(("move", move_args)) = iface_args.subcommand() {
+                move_iface(move_args)?;
+            }
+        }
+        Some(("mgmt", mgmt_args)) => match mgmt_args.subcommand() {
+            Some(("down", down_args)) => set_mgmt(down_args, false)?,
+            Some(("up", up_args)) => set_mgmt(up_args, true)?,
+            _ => {}
+        },
+        Some(("bundle", bundle_args)) => write_bundle(bundle_args)?,
+        Some(("tools", tools_args)) => show_tools(tools_args)?,
+        Some(("exec", exec_args)) => run_in_node(exec_args)?,
+        Some(("shell", shell_args)) => open_shell(shell_args)?,
+        Some(("cp", cp_args)) => copy_files(cp_args)?,
+        Some(("lldp", lldp_args)) => show_lldp(lldp_args)?,
+        Some(("ps", ps_args)) => show_processes(ps_args)?,
+        Some(("sockets", sockets_args)) => show_sockets(sockets_args)?,
+        Some(("check", check_args)) => match check_args.subcommand() {
+            Some(("pmtu", pmtu_args)) => check_pmtu(pmtu_args)?,
+            Some(("path", path_args)) => check_path(path_args)?,
+            Some(("route", route_args)) => check_route(route_args)?,
+            _ => {}
+        },
+        Some(("verify", verify_args)) => verify(verify_args)?,
+        Some(("import", import_args)) => import_topology(import_args)?,
+        Some(("fmt", fmt_args)) => format_topology(fmt_args)?,
+        Some(("diff", diff_args)) => diff_topologies(diff_args)?,
+        Some(("lab", lab_args)) => match lab_args.subcommand() {
+            Some(("add", add_args)) => add_lab(add_args)?,
+            Some(("list", list_args)) => list_labs(list_args)?,
+            Some(("start", start_args)) => start_lab(start_args)?,
+            _ => {}
+        },
+        Some(("history", history_args)) => show_history(history_args)?,
+        Some(("schedule", schedule_args)) => run_schedule(schedule_args)?,
+        Some(("sweep", sweep_args)) => run_sweep(sweep_args)?,
+        Some(("calibrate", calibrate_args)) => calibrate(calibrate_args)?,
+        Some(("test", test_args)) => {
+            if let Some(("run", run_args)) = test_args.subcommand() {
+                run_suite(run_args)?;
             }
-
-            // For for setting vEth and bridges up for the devices.
-            add_switches_and_links(&mut topology).map_err(|err| {
-                error!(%err);
-                std::process::exit(1);
-            });
         }
         Some(("stop", stop_args)) => {
-            let (topology, _config_file_name) = parse_config_args(stop_args)?;
-            topology.power_off()?;
+            // What runs is recorded, the topology file is only needed for
+            // topologies started before it was.
+            let recorded = match stop_args.contains_id("Topo File") {
+                true => None,
+                false => Topology::recorded()?.filter(|_| instance_running()),
+            };
+            let topology = match recorded {
+                Some(topology) => topology,
+                None => parse_config_args(stop_args)?.0,
+            };
+            stop_topology(&topology, stop_args)?;
         }
         Some(("login", login_args)) => {
-            let router = parse_login_args(login_args)?;
+            let (topology, router) = parse_login_args(login_args)?;
 
             // Check if topology instance is running.
             if !instance_running() {
@@ -114,6 +574,15 @@ fn ngen_main() -> NetResult<()> {
                 error!(%err);
                 std::process::exit(1);
             }
+            topology.start_lazy_daemons(&router.name)?;
+
+            // Read while the state file is still in view.
+            let banner = match login_args.get_flag("No Banner") {
+                true => None,
+                false => netgen::banner::banner(&topology, &router.name)
+                    .map_err(|err| debug!(%err, "no banner"))
+                    .ok(),
+            };
 
             // Enter into the device's PID and network namespaces.
             netgen::enter_ns(Some(router.name.clone()))?;
@@ -161,10 +630,23 @@ fn ngen_main() -> NetResult<()> {
             mount_router_volumes(&router)?;
 
             debug!("successfully logged in");
+            if let Some(banner) = banner {
+                println!("{banner}");
+            }
 
-            let shell = std::ffi::CString::new("/bin/bash").unwrap();
-            execvp(&shell, &[&shell]).map_err(|err| {
-                NetError::BasicError(format!("execvp failed: {err}"))
+            // The shell gets the router's environment, as its daemons do.
+            let mut env: BTreeMap<String, String> = std::env::vars().collect();
+            env.extend(router.env().clone());
+            let env: Vec<CString> = env
+                .into_iter()
+                .filter_map(|(var, value)| {
+                    CString::new(format!("{var}={value}")).ok()
+                })
+                .collect();
+
+            let shell = CString::new("/bin/bash").unwrap();
+            execvpe(&shell, &[&shell], &env).map_err(|err| {
+                NetError::BasicError(format!("execvpe failed: {err}"))
             })?;
         }
         _ => {
@@ -174,111 +656,3140 @@ fn ngen_main() -> NetResult<()> {
     Ok(())
 }
 
-/// Checks if the main directory exists indicating if there is an instance
-/// running.
-fn instance_running() -> bool {
-    Path::new(MAIN_NS_DIR).exists()
+// Name of the subcommand, if its runs are kept in the run history.
+fn recorded_command(app_match: &ArgMatches) -> Option<&'static str> {
+    match app_match.subcommand()? {
+        ("start", _) => Some("start"),
+        ("stop", _) => Some("stop"),
+        ("apply", _) => Some("apply"),
+        ("lab", lab_args) => match lab_args.subcommand()? {
+            ("start", _) => Some("lab start"),
+            _ => None,
+        },
+        ("test", test_args) => match test_args.subcommand()? {
+            ("run", _) => Some("test run"),
+            _ => None,
+        },
+        _ => None,
+    }
 }
 
-/// Make sure both the net and pid files are present.
-fn device_running(router_name: &str) -> bool {
-    let net_path = format!("{DEVICES_NS_DIR}/{router_name}/net");
-    let pid_path = format!("{DEVICES_NS_DIR}/{router_name}/pid");
+// Records the run of `command` in the run history, along with the topology
+// running once done, or before for those it tore down. Failing to is only
+// worth a warning, the run itself went through.
+fn record_run(
+    command: &str,
+    before: Option<Provenance>,
+    started: SystemTime,
+    result: &NetResult<()>,
+) {
+    let topology = Provenance::running().ok().flatten().or(before);
+    let run = RunRecord {
+        command: command.to_string(),
+        user: runs::current_user(),
+        topology: topology.as_ref().map(|topology| topology.topology.clone()),
+        hash: topology.map(|topology| topology.hash),
+        started: started
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis()),
+        duration: started.elapsed().unwrap_or_default(),
+        failure: result.as_ref().err().map(|err| err.to_string()),
+    };
+    if let Err(err) = RunHistory::append(run) {
+        warn!(%err, "unable to record the run");
+    }
+}
+
+/// Brings up the whole topology.
+///
+/// Bringing up the devices happens across a chain of forked processes.
+/// Returns `true` in the original process, once the whole chain is done, and
+/// `false` in the forked processes, which should exit straight away.
+fn start_topology(
+    topology: &mut Topology,
+    config_file_name: &str,
+) -> NetResult<bool> {
+    // Rather than finding out halfway through bringing the topology up.
+    topology.check_files()?;
+    TopologyState::clean_stale()?;
+    if instance_running() {
+        let err = NetError::BasicError(format!(
+            "Topology is currently running. \
+                Consider running 'netgen stop -t {config_file_name}' \
+                then try again.",
+        ));
+        return Err(err);
+    }
+    if NamespaceHandle::new(None).is_mounted() {
+        let err = NetError::BasicError(
+            "A previous topology was not stopped cleanly and no netgen \
+                process runs it anymore. Consider running 'netgen start \
+                --force' to take over its leftovers."
+                .to_string(),
+        );
+        return Err(err);
+    }
+
+    // Rate limits above what the host carries make for misleading results.
+    if let Ok(Some(calibration)) = Calibration::load() {
+        for warning in calibration.check(topology) {
+            warn!("{warning}");
+        }
+    }
+
+    // Ctrl-C stops the setup between two of its steps rather than halfway
+    // through one.
+    let cancel = CancelToken::on_interrupt()?;
+    topology.set_cancel(cancel.clone());
+    let pid = Pid::this();
+
+    // Create the directory storing our namespaces if it doesn't exists.
+    let _ = fs::create_dir_all(devices_ns_dir());
+
+    create_routers(topology).map_err(|err| {
+        error!(%err);
+        std::process::exit(1);
+    });
+
+    // Check if this is the child process.
+    if Pid::this() == pid {
+        if cancel.is_cancelled() {
+            error!(
+                "start cancelled, run 'netgen stop' to tear down what was \
+                    brought up"
+            );
+            return Err(NetError::Cancelled);
+        }
+        // Back in the host namespace, once the routers are fully set up.
+        topology.expose_ports()?;
+        return Ok(true);
+    }
 
-    Path::new(&net_path).exists() && Path::new(&pid_path).exists()
+    // For for setting vEth and bridges up for the devices.
+    add_switches_and_links(topology).map_err(|err| {
+        error!(%err);
+        std::process::exit(1);
+    });
+    Ok(false)
 }
 
-// Powers on all the devices in the topology.
-fn create_routers(topology: &mut Topology) -> NetResult<()> {
-    let fork = unsafe { fork() };
+/// The part of `topology` picked with `--only` and `--skip`, the links to
+/// the nodes left out being reported as pending.
+fn select_subset(topology: Topology, args: &ArgMatches) -> NetResult<Topology> {
+    let nodes = |id: &str| -> Vec<String> {
+        args.get_many::<String>(id)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    };
+    let (only, skip) = (nodes("Only"), nodes("Skip"));
+    if only.is_empty() && skip.is_empty() {
+        return Ok(topology);
+    }
 
-    // Fork for creating the devices.
-    match fork {
-        Ok(ForkResult::Child) => {
-            let _ = mount_device(None).map_err(|err| {
-                error!(%err);
-                std::process::exit(1);
-            });
+    let subset = topology.subset(&only, &skip)?;
+    for link in topology.links() {
+        if link
+            .ends()
+            .iter()
+            .any(|end| subset.get_node(end.node.as_str()).is_none())
+        {
+            warn!(src = %link.src, dst = %link.dst, "link left pending");
+        }
+    }
+    Ok(subset)
+}
 
-            // Creates required namespaces for the routing devices.
-            topology.power_routers_on().map_err(|err| {
+/// Fails with the issues of the topology under `--strictness strict`, before
+/// anything is brought up.
+fn check_strictness(topology: &Topology, args: &ArgMatches) -> NetResult<()> {
+    let strict = args
+        .get_one::<String>("Strictness")
+        .is_some_and(|strictness| strictness == "strict");
+    if !strict || topology.issues().is_empty() {
+        return Ok(());
+    }
+    Err(NetError::BasicError(format!(
+        "The topology has {} issue(s):\n  {}",
+        topology.issues().len(),
+        topology.issues().join("\n  ")
+    )))
+}
+
+/// Checks the host limits the topology would run into before bringing it
+/// up, raising those it can with `--tune`. Those left are warned about, or
+/// fatal with `--strictness strict`.
+fn check_resources(topology: &Topology, args: &ArgMatches) -> NetResult<()> {
+    let mut shortfalls = netgen::resources::check(topology);
+    if args.get_flag("Tune") {
+        shortfalls.retain(|shortfall| {
+            if !shortfall.is_tunable() {
+                return true;
+            }
+            match shortfall.tune() {
+                Ok(()) => {
+                    info!("raised {}", shortfall.limit);
+                    false
+                }
+                Err(err) => {
+                    warn!(%err);
+                    true
+                }
+            }
+        });
+    }
+    if shortfalls.is_empty() {
+        return Ok(());
+    }
+
+    let strict = args
+        .get_one::<String>("Strictness")
+        .is_some_and(|strictness| strictness == "strict");
+    if strict {
+        return Err(NetError::BasicError(format!(
+            "The host is short of resources for the topology:\n  {}",
+            shortfalls
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        )));
+    }
+    for shortfall in &shortfalls {
+        match shortfall.is_tunable() && !args.get_flag("Tune") {
+            true => warn!("{shortfall}, '--tune' raises it"),
+            false => warn!("{shortfall}"),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the kernel has the features the topology relies on, e.g.
+/// netem for impaired links, before bringing it up. Those it lacks are
+/// warned about, or fatal with `--strictness strict`.
+fn check_kernel(topology: &Topology, args: &ArgMatches) -> NetResult<()> {
+    let kernel = Kernel::running();
+    let missing = netgen::kernel::check(topology, &kernel);
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let strict = args
+        .get_one::<String>("Strictness")
+        .is_some_and(|strictness| strictness == "strict");
+    if strict {
+        return Err(NetError::BasicError(format!(
+            "Kernel {} lacks features the topology relies on:\n  {}",
+            kernel.release,
+            missing
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        )));
+    }
+    for missing in &missing {
+        warn!(kernel = %kernel.release, "{missing}");
+    }
+    Ok(())
+}
+
+/// Checks that the container netgen runs in, if any, lets it bring up
+/// topologies: lacking capabilities is fatal, the rest only when `strict`.
+fn check_container(strict: bool) -> NetResult<()> {
+    let Some(container) = netgen::container::detect() else {
+        return Ok(());
+    };
+    debug!(runtime = %container.runtime, run_dir = %netgen::default_run_dir(),
+        "running in a container");
+    let problems = netgen::container::preflight(container);
+    if problems.iter().any(|problem| problem.fatal || strict) {
+        return Err(NetError::BasicError(format!(
+            "Unable to run topologies in this container:\n  {}",
+            problems
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        )));
+    }
+    for problem in &problems {
+        warn!("{problem}");
+    }
+    Ok(())
+}
+
+/// Reports what netgen worked around while bringing up the topology, once
+/// it is up.
+fn report_issues(topology: &Topology) {
+    if topology.issues().is_empty() {
+        return;
+    }
+    warn!(
+        "topology brought up with {} issue(s), which '--strictness strict' \
+            makes fatal:",
+        topology.issues().len()
+    );
+    for issue in topology.issues() {
+        warn!("  {issue}");
+    }
+}
+
+/// Brings the running topology in line with the topology file, once the
+/// plan has been shown and confirmed.
+fn apply_topology(apply_args: &ArgMatches) -> NetResult<()> {
+    let (mut topology, config_file_name) = parse_config_args(apply_args)?;
+    set_parallelism(&mut topology, apply_args);
+
+    let running = match instance_running() {
+        true => Some(Topology::recorded()?.ok_or_else(|| {
+            NetError::BasicError(
+                "The running topology was not recorded. Consider running \
+                    'netgen stop' then 'netgen start' with it first."
+                    .to_string(),
+            )
+        })?),
+        false => None,
+    };
+    let plan = topology.plan(running.as_ref());
+
+    if apply_args.get_flag("JSON") {
+        print!("{}", plan.to_json());
+    } else {
+        print!("{}", plan.render(use_color()));
+    }
+    if apply_args.get_flag("Plan") {
+        return Ok(());
+    }
+    if plan.is_empty() {
+        // Metadata changes need no action, but are still recorded.
+        if running.is_some() {
+            topology.record()?;
+        }
+        return Ok(());
+    }
+    if !apply_args.get_flag("Yes") && !confirm_apply()? {
+        info!("apply cancelled");
+        return Ok(());
+    }
+
+    match running {
+        None => {
+            start_topology(&mut topology, &config_file_name)?;
+        }
+        Some(running) => {
+            topology.check_files()?;
+            running.tear_down(&plan)?;
+            build_changes(&mut topology, &plan)?;
+            topology.expose_changed_ports(&plan)?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds what the plan creates or updates. This happens from the main
+/// namespace, in a child process.
+fn build_changes(topology: &mut Topology, plan: &Plan) -> NetResult<()> {
+    match unsafe { fork() } {
+        Ok(ForkResult::Child) => {
+            let result =
+                netgen::enter_ns(None).and_then(|()| topology.build(plan));
+            if let Err(err) = result {
                 error!(%err);
                 std::process::exit(1);
-            });
-            debug!("Devices powered on");
+            }
+            std::process::exit(0);
         }
         Ok(ForkResult::Parent { child }) => {
-            waitpid(child, None).map_err(|err| {
+            let status = waitpid(child, None).map_err(|err| {
                 NetError::NamespaceError(NamespaceError::Fork {
-                    fork_function: String::from("create_routers"),
+                    fork_function: String::from("build_changes"),
                     source: err,
                 })
             })?;
-        }
-        Err(err) => {
-            return Err(NamespaceError::Fork {
-                fork_function: String::from("create_routers"),
-                source: err,
+            match status {
+                WaitStatus::Exited(_, 0) => Ok(()),
+                _ => Err(NetError::BasicError(
+                    "Unable to apply all the changes.".to_string(),
+                )),
             }
-            .into());
         }
+        Err(err) => Err(NamespaceError::Fork {
+            fork_function: String::from("build_changes"),
+            source: err,
+        }
+        .into()),
     }
-    Ok(())
 }
 
-fn add_switches_and_links(topology: &mut Topology) -> NetResult<()> {
-    netgen::enter_ns(None)?;
-    topology.power_switches_on()?;
-    topology.setup_links()?;
+/// Asks for a confirmation before mutating the topology.
+fn confirm_apply() -> NetResult<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(NetError::BasicError(
+            "Refusing to apply changes without confirmation, pass --yes."
+                .to_string(),
+        ));
+    }
+    println!();
+    println!("Do you want to apply these changes? Only 'yes' is accepted: ");
+    let mut buf = String::new();
+    std::io::stdin().read_line(&mut buf).map_err(|err| {
+        NetError::BasicError(format!("Unable to read confirmation: {err:?}"))
+    })?;
+    Ok(buf.trim() == "yes")
+}
 
+/// Registers a topology file in the lab registry.
+fn add_lab(add_args: &ArgMatches) -> NetResult<()> {
+    let Some(name) = add_args.get_one::<String>("Name") else {
+        return Err(NetError::BasicError(
+            "The name of the lab is missing.".to_string(),
+        ));
+    };
+    let Some(path) = add_args.get_one::<String>("Topo File") else {
+        return Err(NetError::BasicError(
+            "The topology file of the lab is missing, see --topo.".to_string(),
+        ));
+    };
+    // Catch mistakes now rather than when the lab gets started.
+    let mut topo_file =
+        File::open(path).map_err(|err| NamespaceError::FileOpen {
+            path: path.clone(),
+            source: err,
+        })?;
+    TopologyParser::from_yaml_file(&mut topo_file)?;
+
+    let mut registry = LabRegistry::load()?;
+    registry.add(Lab {
+        name: name.clone(),
+        path: path.into(),
+        description: add_args.get_one::<String>("Description").cloned(),
+        tags: add_args
+            .get_many::<String>("Tag")
+            .map(|tags| tags.cloned().collect())
+            .unwrap_or_default(),
+    })?;
+    registry.save()?;
+    info!("registered {name}");
     Ok(())
 }
 
-fn init_tracing() {
-    let level_filter = LevelFilter::from_level(Level::TRACE);
-    let layer = tracing_subscriber::fmt::layer().with_target(false);
-    let layer = layer.with_filter(level_filter);
-    let subscriber = Registry::default().with(layer);
-    let _ = tracing::subscriber::set_global_default(subscriber).map_err(|_| {
-        eprintln!("unable to initialize tracing");
-    });
-}
+fn list_labs(list_args: &ArgMatches) -> NetResult<()> {
+    let registry = LabRegistry::load()?;
+    let tag = list_args.get_one::<String>("Tag").map(String::as_str);
+    let labs: Vec<&Lab> = registry.list(tag).collect();
+    if labs.is_empty() {
+        println!("no labs registered, see `netgen lab add`");
+        return Ok(());
+    }
 
-fn config_args() -> Vec<Arg> {
-    vec![
-        Arg::new("Topo File")
-            .short('t')
-            .long("topo")
-            .value_name("yaml-file")
-            .help("file with the topology"),
-    ]
+    let name_width = labs
+        .iter()
+        .map(|lab| lab.name.len())
+        .chain(["NAME".len()])
+        .max()
+        .unwrap_or_default();
+    let tags: Vec<String> = labs.iter().map(|lab| lab.tags.join(",")).collect();
+    let tags_width = tags
+        .iter()
+        .map(String::len)
+        .chain(["TAGS".len()])
+        .max()
+        .unwrap_or_default();
+    println!(
+        "{:name_width$}  {:tags_width$}  DESCRIPTION",
+        "NAME", "TAGS"
+    );
+    for (lab, tags) in labs.iter().zip(&tags) {
+        let description = lab.description.as_deref().unwrap_or_default();
+        let line = format!(
+            "{:name_width$}  {tags:tags_width$}  {description}",
+            lab.name
+        );
+        println!("{}", line.trim_end());
+        println!("{:name_width$}  {}", "", lab.path.display());
+    }
+    Ok(())
 }
 
-fn login_args() -> Vec<Arg> {
-    vec![
-        Arg::new("Device Name")
-            .short('d')
-            .long("device")
-            .value_name("device-name")
-            .help("name of device"),
-        Arg::new("Topo File")
-            .short('t')
-            .long("topo")
-            .value_name("yaml-file")
-            .help("file with the topology"),
-    ]
+fn start_lab(start_args: &ArgMatches) -> NetResult<()> {
+    let Some(name) = start_args.get_one::<String>("Name") else {
+        return Err(NetError::BasicError(
+            "The name of the lab to start is missing.".to_string(),
+        ));
+    };
+    let registry = LabRegistry::load()?;
+    let path = registry.get(name)?.path.to_string_lossy().to_string();
+
+    let mut topo_file =
+        File::open(&path).map_err(|err| NamespaceError::FileOpen {
+            path: path.clone(),
+            source: err,
+        })?;
+    let mut topology = TopologyParser::from_yaml_file(&mut topo_file)?;
+    set_parallelism(&mut topology, start_args);
+    if start_args.get_flag("Force") && !instance_running() {
+        netgen::clean_stale_run()?;
+    }
+    start_topology(&mut topology, &path)?;
+    Ok(())
 }
 
-fn ls_args() -> Vec<Arg> {
+/// Runs a test suite end to end: brings up its topology, plays the scenario,
+/// checks the assertions and tears everything down.
+fn run_suite(run_args: &ArgMatches) -> NetResult<()> {
+    let suite_file = match run_args.get_one::<String>("Suite File") {
+        Some(suite_file) => suite_file.to_string(),
+        None => prompt_suite()?,
+    };
+    let suite = SuiteParser::from_yaml_file(&suite_file)?;
+
+    let topo_yml_file = suite.topology.to_string_lossy().to_string();
+    let mut topo_file = File::open(&suite.topology).map_err(|err| {
+        NamespaceError::FileOpen {
+            path: topo_yml_file.clone(),
+            source: err,
+        }
+    })?;
+    let mut topology = TopologyParser::from_yaml_file(&mut topo_file)?;
+    set_parallelism(&mut topology, run_args);
+
+    let diff_dir = run_args.get_one::<String>("Diff Report");
+    let csv_file = run_args.get_one::<String>("CSV");
+    let cancel = CancelToken::on_interrupt()?;
+    let Some(report) = play_suite(
+        &suite,
+        &mut topology,
+        &topo_yml_file,
+        diff_dir,
+        csv_file,
+        &cancel,
+    )?
+    else {
+        return Ok(());
+    };
+
+    for case in &report.cases {
+        match &case.failure {
+            None => println!("PASS {}", case.name),
+            Some(reason) => {
+                println!("FAIL {} ({reason})", case.name);
+                topology.notify(&Notification {
+                    trigger: Trigger::TestFailed,
+                    subject: suite.name.clone(),
+                    summary: format!("{} failed: {reason}", case.name),
+                });
+            }
+        }
+    }
+
+    write_reports(&report, run_args)?;
+
+    // An error rather than exiting, for the failure to be recorded in the
+    // run history.
+    if report.failures() > 0 {
+        return Err(NetError::BasicError(format!(
+            "{} of {} assertions failed.",
+            report.failures(),
+            report.cases.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Brings up `topology`, plays the scenario of `suite` against it, checks
+/// the assertions and tears everything down.
+///
+/// Once `cancel` is cancelled, the scenario stops and the assertions left
+/// fail, the topology being torn down all the same.
+///
+/// Returns None in the processes forked to bring the topology up, which
+/// should exit straight away.
+fn play_suite(
+    suite: &Suite,
+    topology: &mut Topology,
+    topo_yml_file: &str,
+    diff_dir: Option<&String>,
+    csv_file: Option<&String>,
+    cancel: &CancelToken,
+) -> NetResult<Option<TestReport>> {
+    match start_topology(topology, topo_yml_file) {
+        Ok(true) => {}
+        Ok(false) => return Ok(None),
+        Err(NetError::Cancelled) => {
+            topology.power_off()?;
+            return Err(NetError::Cancelled);
+        }
+        Err(err) => return Err(err),
+    }
+
+    let _ = cancel.sleep(suite.settle);
+    let result = (|| -> NetResult<_> {
+        let routers = topology.router_names();
+        let before = match diff_dir {
+            Some(_) => Some(TopologySnapshot::take(&routers)?),
+            None => None,
+        };
+        let measurements = suite.run_scenario(topology, cancel)?;
+        if let (Some(dir), Some(before)) = (diff_dir, before) {
+            let after = TopologySnapshot::take(&routers)?;
+            let mut diff = before.diff(&after);
+            diff.metadata = topology.metadata();
+            diff.provenance = Provenance::running()?;
+            diff.write(dir)?;
+        }
+        if let Some(csv_file) = csv_file {
+            netgen::csv::write(
+                csv_file,
+                netgen::churn::CSV_COLUMNS,
+                &netgen::churn::to_csv(&measurements),
+                false,
+            )?;
+        }
+        let mut report = suite.check_assertions(cancel);
+        report
+            .cases
+            .splice(0..0, measurements.iter().map(ChurnRun::to_case));
+        report.lead_with(&topology.duts());
+        // Read before the state file goes away with the topology.
+        report.provenance = Provenance::running()?;
+        Ok(report)
+    })();
+
+    // Tear down regardless of how the run went. The report is what the run
+    // is for, so a teardown failing is only logged, `netgen stop` cleaning
+    // up what is left.
+    if let Err(err) = topology.power_off() {
+        error!(%err, "topology not torn down");
+    }
+    result.map(Some)
+}
+
+/// Measures what a veth pair carries on this host on a reference topology,
+/// and keeps the results for later runs to warn about link rates above it.
+fn calibrate(calibrate_args: &ArgMatches) -> NetResult<()> {
+    TopologyState::clean_stale()?;
+    if instance_running() {
+        return Err(NetError::BasicError(
+            "A topology is running, stop it before calibrating.".to_string(),
+        ));
+    }
+    let seconds = calibrate_args.get_one::<f64>("Duration").copied();
+    let duration = Duration::try_from_secs_f64(seconds.unwrap_or(5.0))
+        .map_err(|_| {
+            NetError::BasicError(
+                "The duration has to be a positive number of seconds"
+                    .to_string(),
+            )
+        })?;
+
+    let mut topology = TopologyParser::from_yaml_str(REFERENCE_TOPOLOGY)?;
+    if !start_topology(&mut topology, "calibration")? {
+        return Ok(());
+    }
+    let result = Calibration::measure(&topology, duration);
+    // Tear down regardless of how the measurement went.
+    topology.power_off()?;
+
+    let calibration = result?;
+    calibration.save()?;
+    print!("{}", calibration.render());
+    info!("saved to {}", Calibration::path()?.display());
+    Ok(())
+}
+
+/// Lists the latest runs of the run history, oldest first.
+fn show_history(history_args: &ArgMatches) -> NetResult<()> {
+    let user = history_args.get_one::<String>("User");
+    let command = history_args.get_one::<String>("Command");
+    let last = history_args.get_one::<usize>("Last").copied().unwrap_or(20);
+    let history = RunHistory::load()?;
+    let runs: Vec<RunRecord> = history
+        .runs
+        .into_iter()
+        .filter(|run| user.is_none_or(|user| run.user == *user))
+        .filter(|run| command.is_none_or(|command| run.command == *command))
+        .collect();
+    let runs = &runs[runs.len().saturating_sub(last)..];
+    match history_args.get_flag("JSON") {
+        true => println!(
+            "[{}]",
+            runs.iter()
+                .map(RunRecord::to_json)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        false => print!("{}", runs::render(runs)),
+    }
+    Ok(())
+}
+
+/// Takes the measurements of a schedule file until Ctrl-C.
+fn run_schedule(schedule_args: &ArgMatches) -> NetResult<()> {
+    let topology = running_topology()?;
+    let Some(path) = schedule_args.get_one::<String>("Schedule File") else {
+        return Err(NetError::BasicError(
+            "The schedule file is missing.".to_string(),
+        ));
+    };
+    let schedule = Schedule::from_yaml_file(path)?;
+    if schedule.jobs.is_empty() {
+        return Err(NetError::BasicError(format!("No jobs in {path}.")));
+    }
+    let cancel = CancelToken::on_interrupt()?;
+    schedule.run(&topology, &cancel);
+    Ok(())
+}
+
+/// Runs a test suite against its topology once per combination of the
+/// values of the `--var`s, the topology file seeing them as template
+/// variables.
+fn run_sweep(sweep_args: &ArgMatches) -> NetResult<()> {
+    let vars: Vec<SweepVar> = sweep_args
+        .get_many::<SweepVar>("Var")
+        .map(|vars| vars.cloned().collect())
+        .unwrap_or_default();
+    if vars.is_empty() {
+        return Err(NetError::BasicError(
+            "Nothing to sweep, see --var.".to_string(),
+        ));
+    }
+    let Some(suite_file) = sweep_args.get_one::<String>("Test") else {
+        return Err(NetError::BasicError(
+            "The test suite to run is missing, see --test.".to_string(),
+        ));
+    };
+    let suite = SuiteParser::from_yaml_file(suite_file)?;
+
+    // The topology of the suite, unless given.
+    let topo_yml_file = sweep_args
+        .get_one::<String>("Topo File")
+        .cloned()
+        .unwrap_or_else(|| suite.topology.to_string_lossy().to_string());
+    let source = fs::read_to_string(&topo_yml_file).map_err(|err| {
+        NamespaceError::FileOpen {
+            path: topo_yml_file.clone(),
+            source: err,
+        }
+    })?;
+
+    let cancel = CancelToken::on_interrupt()?;
+    let mut sweep = SweepReport::default();
+    for combination in sweep::grid(&vars) {
+        if cancel.is_cancelled() {
+            warn!("sweep cancelled, reporting the combinations run so far");
+            break;
+        }
+        let label = sweep::describe(&combination);
+        println!("== {label}");
+
+        let parsed = TopologyParser::from_yaml_str_with(
+            &source,
+            &sweep::template_vars(&combination),
+            &BTreeSet::new(),
+        );
+        // A combination failing to parse or to run is its result, the
+        // others may still be valid.
+        let failed = |name: &str, err: NetError| {
+            let mut report = TestReport::new(&suite.name);
+            report.cases.push(TestCase {
+                name: name.to_string(),
+                duration: Duration::ZERO,
+                failure: Some(err.to_string()),
+                node: None,
+            });
+            report
+        };
+        let report = match parsed {
+            Ok(mut topology) => {
+                set_parallelism(&mut topology, sweep_args);
+                match play_suite(
+                    &suite,
+                    &mut topology,
+                    &topo_yml_file,
+                    None,
+                    None,
+                    &cancel,
+                ) {
+                    Ok(Some(report)) => report,
+                    Ok(None) => return Ok(()),
+                    Err(err) => {
+                        error!(combination = %label, %err, "run failed");
+                        failed("run", err)
+                    }
+                }
+            }
+            Err(err) => failed("topology", err),
+        };
+
+        for case in &report.cases {
+            match &case.failure {
+                None => println!("PASS {}", case.name),
+                Some(reason) => println!("FAIL {} ({reason})", case.name),
+            }
+        }
+        sweep.runs.push((combination, report));
+    }
+
+    if let Some(output) = sweep_args.get_one::<String>("Output") {
+        sweep.write_json(output)?;
+    }
+    write_reports(&sweep.to_report(&suite.name), sweep_args)?;
+
+    if sweep.failures() > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Prints the programs netgen runs for some of its features, and whether
+/// they are installed. With `--missing`, only prints those which are not,
+/// exiting with a non-zero status if any.
+fn show_tools(tools_args: &ArgMatches) -> NetResult<()> {
+    let missing_only = tools_args.get_flag("Missing");
+    let tools = netgen::tools::audit();
+    let width = tools
+        .iter()
+        .map(|(tool, _)| tool.program.len())
+        .max()
+        .unwrap_or(0);
+    let mut missing = 0;
+    for (tool, installed) in tools {
+        if !installed {
+            missing += 1;
+        } else if missing_only {
+            continue;
+        }
+        let state = match installed {
+            true => "installed",
+            false => "missing",
+        };
+        println!("{:width$}  {state:9}  {}", tool.program, tool.needed_by);
+    }
+    if missing_only && missing > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Archives the state file, event log, device files and, if a topology is
+/// running, a snapshot of every router, along with the files given.
+fn write_bundle(bundle_args: &ArgMatches) -> NetResult<()> {
+    let output = match bundle_args.get_one::<String>("Output") {
+        Some(output) => output.clone(),
+        None => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            format!("netgen-bundle-{now}.tar.gz")
+        }
+    };
+
+    let mut bundle = Bundle::new()?;
+    bundle.add_run_files()?;
+    if instance_running() {
+        match Topology::recorded() {
+            Ok(Some(topology)) => bundle.add_node_state(&topology)?,
+            Ok(None) => {}
+            Err(err) => warn!(%err, "unable to snapshot the routers"),
+        }
+    }
+    if let Some(includes) = bundle_args.get_many::<String>("Include") {
+        for include in includes {
+            bundle.add_extra(Path::new(include))?;
+        }
+    }
+
+    let entries = bundle.write(Path::new(&output))?;
+    info!("wrote {output} ({} files)", entries.len());
+    Ok(())
+}
+
+/// Tears the topology down, or only stops its daemons with `--daemons-only`.
+/// With `--keep-artifacts`, what netgen wrote for the nodes is gathered into
+/// a directory first, as `netgen bundle` would.
+fn stop_topology(topology: &Topology, stop_args: &ArgMatches) -> NetResult<()> {
+    if stop_args.get_flag("Daemons Only") {
+        return topology.stop_daemons();
+    }
+
+    if let Some(dir) = stop_args.get_one::<String>("Keep Artifacts") {
+        let mut bundle = Bundle::in_dir(Path::new(dir))?;
+        // Routes and neighbors are captured while the daemons still run,
+        // the logs and cores once they exited.
+        if instance_running() {
+            bundle.add_node_state(topology)?;
+        }
+        topology.stop_daemons()?;
+        bundle.add_run_files()?;
+        let entries = bundle.keep()?;
+        info!("kept {} files in {dir}", entries.len());
+    }
+    topology.power_off()?;
+    if stop_args.get_flag("Purge") {
+        topology.purge_persisted()?;
+    }
+    Ok(())
+}
+
+/// Cuts the links between the groups of nodes given, e.g.
+/// `--groups "r1,r2 / r3,r4"`.
+fn partition_topology(partition_args: &ArgMatches) -> NetResult<()> {
+    let (topology, _config_file_name) = parse_config_args(partition_args)?;
+    if !instance_running() {
+        return Err(NetError::BasicError(
+            "No topology instance currently running.".to_string(),
+        ));
+    }
+
+    let groups = partition_args
+        .get_one::<String>("Groups")
+        .map(|groups| parse_groups(groups))
+        .ok_or_else(|| {
+            NetError::BasicError("No groups given, pass --groups.".to_string())
+        })??;
+    for link in topology.partition(&groups)? {
+        info!("cut {} <-> {}", link.src, link.dst);
+    }
+    Ok(())
+}
+
+/// Runs a command in a router of the running topology, exiting with its
+/// status.
+fn run_in_node(exec_args: &ArgMatches) -> NetResult<()> {
+    if exec_args.get_flag("All") {
+        return run_in_routers(exec_args);
+    }
+    let node = exec_args
+        .get_one::<String>("Node")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let history = ExecHistory::load()?;
+    if exec_args.get_flag("History") {
+        for (index, command) in history.of(&node).iter().enumerate() {
+            println!("{:>5}  {}", index + 1, shell_words(command));
+        }
+        return Ok(());
+    }
+
+    let topology = running_topology()?;
+    // Before recording anything, for typos to stay out of the history.
+    if topology.get_node(&node).is_none() {
+        return Err(ConfigError::UnknownNode(node).into());
+    }
+    let command: Vec<String> = match exec_args.get_flag("Last") {
+        true => {
+            let last = history.last(&node).ok_or_else(|| {
+                NetError::BasicError(format!("No command was run in {node}."))
+            })?;
+            // As shells do, so that what runs is not a surprise.
+            info!("{}", shell_words(last));
+            last.to_vec()
+        }
+        false => exec_args
+            .get_many::<String>("Command")
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect(),
+    };
+    let command: Vec<&str> = command.iter().map(String::as_str).collect();
+    let Some((cmd, args)) = command.split_first() else {
+        return Err(NetError::BasicError("No command given.".to_string()));
+    };
+
+    // Recorded before it runs, in case it is interrupted.
+    if let Err(err) =
+        ExecHistory::update(|history| history.record(&node, &command))
+    {
+        warn!(%err, "unable to record the command");
+    }
+    let status = topology.exec_in_node(&node, cmd, args)?;
+    std::process::exit(exit_code(status));
+}
+
+/// Runs a command in every router of the running topology, then prints
+/// what each output under its name, in order, and which failed.
+fn run_in_routers(exec_args: &ArgMatches) -> NetResult<()> {
+    let topology = running_topology()?;
+    let command: Vec<String> = exec_args
+        .get_many::<String>("Command")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    let command: Vec<&str> = command.iter().map(String::as_str).collect();
+    let Some((cmd, args)) = command.split_first() else {
+        return Err(NetError::BasicError("No command given.".to_string()));
+    };
+    let parallel = exec_args
+        .get_one::<usize>("Parallel")
+        .copied()
+        .unwrap_or(usize::MAX);
+
+    let recorded = ExecHistory::update(|history| {
+        for router in topology.router_names() {
+            history.record(&router, &command);
+        }
+    });
+    if let Err(err) = recorded {
+        warn!(%err, "unable to record the command");
+    }
+
+    let outputs = topology.exec_in_routers(cmd, args, parallel);
+    let mut failures = vec![];
+    let mut stdout = std::io::stdout().lock();
+    for (router, output) in &outputs {
+        let status = match output {
+            Ok(output) if output.status.success() => "ok".to_string(),
+            Ok(output) => {
+                let code = exit_code(output.status);
+                failures.push(format!("{router} (exit {code})"));
+                format!("exit {code}")
+            }
+            Err(err) => {
+                failures.push(format!("{router} ({err})"));
+                "not run".to_string()
+            }
+        };
+        let _ = writeln!(stdout, "==== {router}: {status} ====");
+        match output {
+            Ok(output) => {
+                let _ = stdout.write_all(&output.stdout);
+                let _ = stdout.write_all(&output.stderr);
+            }
+            Err(err) => {
+                let _ = writeln!(stdout, "{err}");
+            }
+        }
+    }
+    let _ = stdout.flush();
+
+    match failures.is_empty() {
+        true => {
+            info!("succeeded in all {} routers", outputs.len());
+            Ok(())
+        }
+        false => Err(NetError::BasicError(format!(
+            "Failed in {} of {} routers: {}",
+            failures.len(),
+            outputs.len(),
+            failures.join(", ")
+        ))),
+    }
+}
+
+// The command as it would be typed in a shell, its arguments quoted where
+// needed.
+fn shell_words(command: &[String]) -> String {
+    let safe = |arg: &str| {
+        !arg.is_empty()
+            && arg.chars().all(|ch| {
+                ch.is_ascii_alphanumeric() || "-_./:=@,+%".contains(ch)
+            })
+    };
+    command
+        .iter()
+        .map(|arg| match safe(arg.as_str()) {
+            true => arg.clone(),
+            false => format!("'{}'", arg.replace('\'', "'\\''")),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Copies files between the host and the routers of the running topology,
+/// as their processes see them.
+fn copy_files(cp_args: &ArgMatches) -> NetResult<()> {
+    let topology = running_topology()?;
+    let arg = |name: &str| {
+        cp_args
+            .get_one::<String>(name)
+            .map(String::as_str)
+            .unwrap_or_default()
+    };
+    let copied = topology.copy_files(arg("Source"), arg("Destination"))?;
+    info!("copied {} to {}", arg("Source"), copied.display());
+    Ok(())
+}
+
+/// Opens an interactive shell in a router of the running topology.
+fn open_shell(shell_args: &ArgMatches) -> NetResult<()> {
+    let topology = running_topology()?;
+    let node = shell_args
+        .get_one::<String>("Node")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let shell =
+        std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+
+    let status = topology.exec_in_node(&node, &shell, &[])?;
+    std::process::exit(exit_code(status));
+}
+
+/// Lists what the interfaces of a router of the running topology heard
+/// over LLDP.
+fn show_lldp(lldp_args: &ArgMatches) -> NetResult<()> {
+    let topology = running_topology()?;
+    let node = lldp_args
+        .get_one::<String>("Node")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let router = topology
+        .get_router(&node)
+        .ok_or_else(|| ConfigError::UnknownNode(node.clone()))?;
+    if !router.lldp_enabled() {
+        return Err(NetError::BasicError(format!(
+            "LLDP is not enabled on {node}, see 'lldp' in the topology file."
+        )));
+    }
+    print!("{}", lldp::render(&lldp::neighbors(&node)));
+    Ok(())
+}
+
+fn show_processes(ps_args: &ArgMatches) -> NetResult<()> {
+    let topology = running_topology()?;
+    let node = ps_args
+        .get_one::<String>("Node")
+        .map_or_else(prompt_device, |v| v.to_string());
+    if topology.get_router(&node).is_none() {
+        return Err(ConfigError::UnknownNode(node).into());
+    }
+    print!("{}", ps::render(&ps::list(&node)?));
+    Ok(())
+}
+
+fn show_sockets(sockets_args: &ArgMatches) -> NetResult<()> {
+    let topology = running_topology()?;
+    let node = sockets_args
+        .get_one::<String>("Node")
+        .map_or_else(prompt_device, |v| v.to_string());
+    if topology.get_router(&node).is_none() {
+        return Err(ConfigError::UnknownNode(node).into());
+    }
+    let sockets = sockets::list(&node, sockets_args.get_flag("Listening"))?;
+    print!("{}", sockets::render(&sockets));
+    Ok(())
+}
+
+// The exit code of a shell running the command: 128 plus the signal when
+// killed by one.
+fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+/// The topology currently running, as recorded when it was started.
+fn running_topology() -> NetResult<Topology> {
+    if !instance_running() {
+        return Err(NetError::BasicError(
+            "No topology instance currently running.".to_string(),
+        ));
+    }
+    Topology::recorded()?.ok_or_else(|| {
+        NetError::BasicError(
+            "The running topology was not recorded. Consider running \
+                'netgen stop' then 'netgen start' with it first."
+                .to_string(),
+        )
+    })
+}
+
+/// Adds a copy of a router to the running topology, and brings it up along
+/// with its links.
+fn clone_node(clone_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+
+    let original = clone_args
+        .get_one::<String>("Router")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let name = clone_args.get_one::<String>("Name").ok_or_else(|| {
+        NetError::BasicError("No name given for the copy.".to_string())
+    })?;
+    let link_to: Vec<String> = clone_args
+        .get_many::<String>("Link To")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+
+    let topology = running.clone_router(&original, name, &link_to)?;
+    change_running(&running, topology, clone_args)
+}
+
+/// Removes a node from the running topology, tearing down its links.
+fn remove_node(remove_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+    let name = remove_args
+        .get_one::<String>("Node")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let topology = running.remove_node(&name)?;
+    change_running(&running, topology, remove_args)
+}
+
+/// Renames the session of the running topology, which has to be `<old>` so
+/// that the wrong one is never renamed.
+fn rename_session(rename_args: &ArgMatches) -> NetResult<()> {
+    let mut running = running_topology()?;
+    let (Some(old), Some(new)) = (
+        rename_args.get_one::<String>("Old"),
+        rename_args.get_one::<String>("New"),
+    ) else {
+        return Err(NetError::BasicError(
+            "Both the current and the new session names are needed."
+                .to_string(),
+        ));
+    };
+    if running.session() != old {
+        return Err(NetError::BasicError(format!(
+            "The running session is {}, not {old}.",
+            running.session()
+        )));
+    }
+    running.rename_session(new)?;
+    info!("renamed session {old} to {new}");
+    Ok(())
+}
+
+/// Checkpoints the daemons of a router of the running topology.
+fn checkpoint_node(checkpoint_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+    let node = checkpoint_args
+        .get_one::<String>("Node")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let name = checkpoint_name(checkpoint_args);
+    let leave_running = checkpoint_args.get_flag("Leave Running");
+
+    let daemons = running.checkpoint_node(&node, name, leave_running)?;
+    info!("checkpointed {} of {node} as {name}", daemons.join(", "));
+    Ok(())
+}
+
+/// Restores the daemons of a router of the running topology from one of
+/// its checkpoints.
+fn restore_node(restore_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+    let node = restore_args
+        .get_one::<String>("Node")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let name = checkpoint_name(restore_args);
+
+    let daemons = running.restore_node(&node, name)?;
+    info!("restored {} of {node} from {name}", daemons.join(", "));
+    Ok(())
+}
+
+fn checkpoint_name(args: &ArgMatches) -> &str {
+    args.get_one::<String>("Name")
+        .map_or(DEFAULT_CHECKPOINT, String::as_str)
+}
+
+/// Adds a link to the running topology and brings it up, or removes one.
+fn change_link(link_args: &ArgMatches, add: bool) -> NetResult<()> {
+    let running = running_topology()?;
+    let end = |name: &str| {
+        link_args.get_one::<String>(name).cloned().ok_or_else(|| {
+            NetError::BasicError(format!("No {} given.", name.to_lowercase()))
+        })
+    };
+    let (src, dst) = (end("Source")?, end("Destination")?);
+    let topology = match add {
+        true => running.add_link(&src, &dst)?,
+        false => running.remove_link(&src, &dst)?,
+    };
+    change_running(&running, topology, link_args)
+}
+
+/// Programs a route into the FIB of a router of the running topology.
+fn add_route(route_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+    let (Some(node), Some(prefix), Some(via)) = (
+        route_args.get_one::<String>("Node"),
+        route_args.get_one::<IpNetwork>("Prefix"),
+        route_args.get_one::<IpAddr>("Next Hop"),
+    ) else {
+        return Err(NetError::BasicError(
+            "Usage: netgen route add <node> <prefix> via <address>".to_string(),
+        ));
+    };
+    let route = StaticRoute {
+        prefix: *prefix,
+        via: *via,
+        table: route_args.get_one::<u32>("Table").copied(),
+    };
+    running.add_route(node, &route)?;
+    println!("{node}: {route}");
+    Ok(())
+}
+
+/// Adds addresses to the interfaces of a router of the running topology,
+/// given as "<iface> <prefix>" pairs on the command line or lines of
+/// `--file`.
+fn add_addresses(address_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+    let Some(node) = address_args.get_one::<String>("Node") else {
+        return Err(NetError::BasicError("No node given.".to_string()));
+    };
+
+    let mut words: Vec<String> = address_args
+        .get_many::<String>("Addresses")
+        .map(|words| words.cloned().collect())
+        .unwrap_or_default();
+    if let Some(path) = address_args.get_one::<String>("File") {
+        let contents = match path.as_str() {
+            "-" => {
+                std::io::read_to_string(std::io::stdin()).map_err(|err| {
+                    NetError::BasicError(format!(
+                        "Unable to read stdin: {err:?}"
+                    ))
+                })?
+            }
+            path => fs::read_to_string(path).map_err(|err| {
+                NamespaceError::FileOpen {
+                    path: path.to_string(),
+                    source: err,
+                }
+            })?,
+        };
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            words.extend(line.split_whitespace().map(str::to_string));
+        }
+    }
+
+    if words.is_empty() || words.len() % 2 != 0 {
+        return Err(NetError::BasicError(
+            "Usage: netgen address add <node> [<iface> <prefix>]... \
+                [--file <file>]"
+                .to_string(),
+        ));
+    }
+    let addresses = words
+        .chunks(2)
+        .map(|pair| {
+            let address = pair[1].parse::<IpNetwork>().map_err(|_| {
+                NetError::BasicError(format!(
+                    "Invalid address '{}' for {}",
+                    pair[1], pair[0]
+                ))
+            })?;
+            Ok((pair[0].clone(), address))
+        })
+        .collect::<NetResult<Vec<_>>>()?;
+
+    let count = addresses.len();
+    running.add_addresses_bulk(node, addresses)?;
+    info!("{node}: added {count} addresses");
+    Ok(())
+}
+
+/// Moves an interface of the running topology to another router, link and
+/// addresses included, then records the topology as changed.
+fn move_iface(move_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+    let (Some(end), Some(to)) = (
+        move_args.get_one::<String>("End"),
+        move_args.get_one::<String>("To"),
+    ) else {
+        return Err(NetError::BasicError(
+            "Usage: netgen iface move <node>:<iface> <other-node>".to_string(),
+        ));
+    };
+    running.move_iface(end, to)?.record()?;
+    info!("moved {end} to {to}");
+    Ok(())
+}
+
+/// Blocks or unblocks the management plane of routers of the running
+/// topology, their data plane being left alone.
+fn set_mgmt(mgmt_args: &ArgMatches, reachable: bool) -> NetResult<()> {
+    let running = running_topology()?;
+    let strings = |name: &str| -> Vec<String> {
+        mgmt_args
+            .get_many::<String>(name)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    };
+    let nodes = strings("Nodes");
+    running.set_mgmt_reachable(&nodes, &strings("Ifaces"), reachable)?;
+    match reachable {
+        true => info!("management of {} restored", nodes.join(", ")),
+        false => info!("management of {} cut off", nodes.join(", ")),
+    }
+    Ok(())
+}
+
+/// Measures the one-way latency and drops of a link of the running
+/// topology, in both directions.
+fn show_latency(latency_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+    let end = |name: &str| {
+        latency_args
+            .get_one::<String>(name)
+            .cloned()
+            .ok_or_else(|| {
+                NetError::BasicError(format!(
+                    "No {} given.",
+                    name.to_lowercase()
+                ))
+            })
+    };
+    let (src, dst) = (end("Source")?, end("Destination")?);
+    let seconds = latency_args.get_one::<f64>("Duration").copied();
+    let duration = Duration::try_from_secs_f64(seconds.unwrap_or(10.0))
+        .map_err(|_| {
+            NetError::BasicError(
+                "The duration has to be a positive number of seconds"
+                    .to_string(),
+            )
+        })?;
+
+    let latencies = netgen::latency::measure(&running, &src, &dst, duration)?;
+    print!("{}", netgen::latency::render(&latencies));
+    if let Some(csv_file) = latency_args.get_one::<String>("CSV") {
+        netgen::csv::write(
+            csv_file,
+            netgen::latency::CSV_COLUMNS,
+            &netgen::latency::to_csv(&latencies),
+            false,
+        )?;
+    }
+    Ok(())
+}
+
+/// Applies the impairments of a file written by `link export` to the
+/// running topology.
+fn import_impairments(import_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+    let Some(path) = import_args.get_one::<String>("Impairments File") else {
+        return Err(NetError::BasicError(
+            "No impairments file given.".to_string(),
+        ));
+    };
+    let source =
+        fs::read_to_string(path).map_err(|err| NamespaceError::FileOpen {
+            path: path.to_string(),
+            source: err,
+        })?;
+    for end in running.import_impairments(&source)? {
+        info!("impaired {end}");
+    }
+    Ok(())
+}
+
+/// Blasts frames between two interfaces of the running topology to measure
+/// what the emulated fabric carries, optionally with XDP on some veths.
+fn run_bench(bench_args: &ArgMatches) -> NetResult<()> {
+    let running = running_topology()?;
+    let end = |name: &str| {
+        bench_args.get_one::<String>(name).cloned().ok_or_else(|| {
+            NetError::BasicError(format!("No {} given.", name.to_lowercase()))
+        })
+    };
+    let (src, dst) = (end("Source")?, end("Destination")?);
+    let seconds = bench_args.get_one::<f64>("Duration").copied();
+    let duration = Duration::try_from_secs_f64(seconds.unwrap_or(10.0))
+        .map_err(|_| {
+            NetError::BasicError(
+                "The duration has to be a positive number of seconds"
+                    .to_string(),
+            )
+        })?;
+    let options = BenchOptions {
+        duration,
+        frame_size: bench_args
+            .get_one::<u16>("Frame Size")
+            .copied()
+            .unwrap_or(netgen::bench::DEFAULT_FRAME_SIZE),
+        xdp: bench_args
+            .get_many::<String>("XDP")
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect(),
+    };
+
+    let result = netgen::bench::run(&running, &src, &dst, &options)?;
+    print!("{}", result.render());
+    Ok(())
+}
+
+/// Turns the running topology into `topology`, which is then recorded as
+/// the running one.
+fn change_running(
+    running: &Topology,
+    mut topology: Topology,
+    args: &ArgMatches,
+) -> NetResult<()> {
+    set_parallelism(&mut topology, args);
+    let plan = topology.plan(Some(running));
+    print!("{}", plan.render(use_color()));
+
+    running.tear_down(&plan)?;
+    build_changes(&mut topology, &plan)?;
+    topology.expose_changed_ports(&plan)
+}
+
+/// Asks for the shape of a topology, then writes it along with example
+/// startup configs for its routers.
+fn init_topology(init_args: &ArgMatches) -> NetResult<()> {
+    let output = init_args
+        .get_one::<String>("Output")
+        .map_or("topology.yml", String::as_str);
+    let configs_dir = Path::new(output)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map_or_else(
+            || "configs".to_string(),
+            |dir| format!("{}/configs", dir.display()),
+        );
+
+    println!("Answer the questions below, or press Enter for the default.");
+    let skeleton = loop {
+        let routers = ask("Number of routers", "3")?;
+        let shape =
+            ask(&format!("Shape ({})", Shape::NAMES.join(", ")), "ring")?;
+        let duts: String =
+            ask("Routers under test, e.g. rt1,rt2 (none by default)", "")?;
+        let addressing = ask("Addressing (pools or static)", "pools")?;
+        let link_prefix = ask("Prefix of the links", "10.0.0.0/16")?;
+        let loopback_prefix = ask("Prefix of the loopbacks", "10.255.0.0/24")?;
+        let daemons: String = ask(
+            &format!(
+                "FRR daemons, e.g. {} (none for bare routers)",
+                CONFIGURED_DAEMONS.join(",")
+            ),
+            "ospfd",
+        )?;
+        let list = |value: &str| -> Vec<String> {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty() && *item != "none")
+                .map(str::to_string)
+                .collect()
+        };
+        let skeleton = Skeleton {
+            routers,
+            shape,
+            duts: list(&duts),
+            addressing,
+            link_prefix,
+            loopback_prefix,
+            daemons: list(&daemons),
+            configs_dir: configs_dir.clone(),
+        };
+        match skeleton.check() {
+            Ok(()) => break skeleton,
+            Err(err) => println!("{err} Let's start over."),
+        }
+    };
+
+    let configs = skeleton.startup_configs();
+    let mut files = vec![(output.to_string(), skeleton.topology_yaml())];
+    files.extend(configs.into_iter().map(|(router, config)| {
+        (format!("{configs_dir}/{router}.conf"), config)
+    }));
+    let overwrite = init_args.get_flag("Overwrite");
+    if let Some((path, _)) = files
+        .iter()
+        .find(|(path, _)| !overwrite && Path::new(path).exists())
+    {
+        return Err(NetError::BasicError(format!(
+            "{path} already exists, use --overwrite to replace it."
+        )));
+    }
+    if files.len() > 1 {
+        fs::create_dir_all(&configs_dir).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to create {configs_dir}: {err:?}"
+            ))
+        })?;
+    }
+    for (path, contents) in &files {
+        fs::write(path, contents).map_err(|err| {
+            NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+        })?;
+        info!("wrote {path}");
+    }
+    info!("start it with: netgen start --topo {output}");
+    Ok(())
+}
+
+/// Starts a lab of routers in a ring or a chain, written to the quick lab
+/// directory first so that it can be stopped, inspected and changed like
+/// any other.
+fn quick_topology(quick_args: &ArgMatches) -> NetResult<()> {
+    let routers = quick_args.get_one::<usize>("Routers").copied().unwrap_or(3);
+    let shape = match quick_args.get_one::<String>("Shape") {
+        Some(shape) => shape.parse().map_err(NetError::BasicError)?,
+        None if routers >= 3 => Shape::Ring,
+        None => Shape::Chain,
+    };
+    let daemons: Vec<String> = quick_args
+        .get_one::<String>("Daemons")
+        .map_or("ospfd", String::as_str)
+        .split(',')
+        .map(str::trim)
+        .filter(|daemon| !daemon.is_empty() && *daemon != "none")
+        .map(str::to_string)
+        .collect();
+
+    let dir = netgen::init::quick_dir()?;
+    let configs_dir = dir.join("configs").to_string_lossy().to_string();
+    let skeleton = Skeleton::quick(routers, shape, daemons, &configs_dir);
+    check_container(false)?;
+    let generated = netgen::generate::regular(&skeleton)?;
+
+    // Left over from the previous quick lab, if any.
+    let _ = fs::remove_dir_all(&configs_dir);
+    let path = dir.join("topology.yml").to_string_lossy().to_string();
+    generated.write(&path)?;
+
+    let mut topology = generated.topology;
+    set_parallelism(&mut topology, quick_args);
+    if quick_args.get_flag("Force") && !instance_running() {
+        netgen::clean_stale_run()?;
+    }
+    if start_topology(&mut topology, &path)? {
+        info!("started {} from {path}", skeleton.router_names().join(", "));
+        report_issues(&topology);
+    }
+    Ok(())
+}
+
+/// Asks `question` until the answer parses, `default` standing for an
+/// empty one.
+fn ask<T: FromStr<Err: fmt::Display>>(
+    question: &str,
+    default: &str,
+) -> NetResult<T> {
+    loop {
+        match default.is_empty() {
+            true => print!("{question}: "),
+            false => print!("{question} [{default}]: "),
+        }
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        let read = std::io::stdin().read_line(&mut answer).map_err(|err| {
+            NetError::BasicError(format!("Unable to read the answer: {err}"))
+        })?;
+        if read == 0 {
+            return Err(NetError::BasicError(
+                "No answer, the input ended.".to_string(),
+            ));
+        }
+        let answer = match answer.trim() {
+            "" => default,
+            answer => answer,
+        };
+        match answer.parse() {
+            Ok(value) => return Ok(value),
+            Err(err) => println!("  {answer}: {err}"),
+        }
+    }
+}
+
+/// Prints the topology file in canonical form, or rewrites it.
+fn format_topology(fmt_args: &ArgMatches) -> NetResult<()> {
+    let topo_yml_file = fmt_args
+        .get_one::<String>("Topo File")
+        .map_or_else(prompt_topo, |v| v.to_string());
+    let source = fs::read_to_string(&topo_yml_file).map_err(|err| {
+        NamespaceError::FileOpen {
+            path: topo_yml_file.clone(),
+            source: err,
+        }
+    })?;
+    let formatted = TopologyParser::format(&source)?;
+
+    if fmt_args.get_flag("Check") {
+        if formatted != source {
+            return Err(NetError::BasicError(format!(
+                "{topo_yml_file} is not in canonical form, see netgen fmt."
+            )));
+        }
+    } else if fmt_args.get_flag("Write") {
+        if formatted != source {
+            fs::write(&topo_yml_file, &formatted).map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to write {topo_yml_file}: {err:?}"
+                ))
+            })?;
+            info!("formatted {topo_yml_file}");
+        }
+    } else {
+        print!("{formatted}");
+    }
+    Ok(())
+}
+
+/// Shows what differs between two topology files once parsed, with the
+/// same `--profile`s enabled in both.
+fn diff_topologies(diff_args: &ArgMatches) -> NetResult<()> {
+    let profiles: BTreeSet<String> = diff_args
+        .get_many::<String>("Profile")
+        .map(|profiles| profiles.cloned().collect())
+        .unwrap_or_default();
+    let parse = |arg: &str| -> NetResult<Topology> {
+        let Some(path) = diff_args.get_one::<String>(arg) else {
+            return Err(NetError::BasicError(format!(
+                "The {} topology file is missing.",
+                arg.to_lowercase()
+            )));
+        };
+        let source = fs::read_to_string(path).map_err(|err| {
+            NamespaceError::FileOpen {
+                path: path.clone(),
+                source: err,
+            }
+        })?;
+        TopologyParser::from_yaml_str_with(&source, &BTreeMap::new(), &profiles)
+    };
+    let diff = TopologyDiff::between(&parse("Old")?, &parse("New")?);
+
+    if diff_args.get_flag("JSON") {
+        print!("{}", diff.to_json());
+    } else {
+        print!("{}", diff.render(use_color()));
+    }
+    Ok(())
+}
+
+/// Emits a topology approximating the namespaces of the host, or a
+/// directory of router configurations.
+fn import_topology(import_args: &ArgMatches) -> NetResult<()> {
+    let yaml = match import_args.get_one::<String>("Configs") {
+        Some(dir) => netgen::import::import_from_configs(Path::new(dir))?,
+        None if import_args.get_flag("From Host") => {
+            netgen::import::import_from_host()?
+        }
+        None => {
+            return Err(NetError::BasicError(
+                "Nothing to import, use --from-host or --configs.".to_string(),
+            ));
+        }
+    };
+    match import_args.get_one::<String>("Output") {
+        Some(output) => fs::write(output, yaml).map_err(|err| {
+            NetError::BasicError(format!("Unable to write {output}: {err:?}"))
+        })?,
+        None => print!("{yaml}"),
+    }
+    Ok(())
+}
+
+/// Checks the invariants declared by the running topology and runs the
+/// custom validators, exiting with 1 if any of them fails.
+fn verify(verify_args: &ArgMatches) -> NetResult<()> {
+    let topology = running_topology()?;
+    let timeout = verify_args
+        .get_one::<u64>("Timeout")
+        .map_or(Duration::ZERO, |secs| Duration::from_secs(*secs));
+
+    let cancel = CancelToken::on_interrupt()?;
+    let report = topology.verify(timeout, &cancel)?;
+    if report.cases.is_empty() {
+        return Err(NetError::BasicError(format!(
+            "Nothing to verify: the running topology declares no invariants \
+                nor link expectations, and no validators are in {}.",
+            netgen::validator::validators_dir()?.display()
+        )));
+    }
+    for case in &report.cases {
+        match &case.failure {
+            None => println!("PASS {}", case.name),
+            Some(reason) => println!("FAIL {} ({reason})", case.name),
+        }
+    }
+    write_reports(&report, verify_args)?;
+
+    if report.failures() > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Brings a link of the running topology up or down, for good.
+fn toggle_link(link_args: &ArgMatches, enabled: bool) -> NetResult<()> {
+    let running = running_topology()?;
+    let end = |name: &str| {
+        link_args.get_one::<String>(name).cloned().ok_or_else(|| {
+            NetError::BasicError(format!("No {} given.", name.to_lowercase()))
+        })
+    };
+    let link = running.set_link_enabled(
+        &end("Source")?,
+        &end("Destination")?,
+        enabled,
+    )?;
+    let state = if enabled { "enabled" } else { "disabled" };
+    info!("{state} {} <-> {}", link.src, link.dst);
+    Ok(())
+}
+
+/// Parses groups of node names, separated by '/', themselves separated by
+/// ','.
+fn parse_groups(groups: &str) -> NetResult<Vec<Vec<String>>> {
+    groups
+        .split('/')
+        .map(|group| {
+            group
+                .split(',')
+                .map(|name| match name.trim() {
+                    "" => Err(NetError::BasicError(format!(
+                        "Empty node name in groups '{groups}'."
+                    ))),
+                    name => Ok(name.to_string()),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Discovers the path MTU between two nodes of the running topology, and
+/// optionally compares it against the expected value.
+fn check_pmtu(pmtu_args: &ArgMatches) -> NetResult<()> {
+    let (topology, _config_file_name) = parse_config_args(pmtu_args)?;
+    if !instance_running() {
+        return Err(NetError::BasicError(
+            "No topology instance currently running.".to_string(),
+        ));
+    }
+
+    let src = pmtu_args
+        .get_one::<String>("Source")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let dst = pmtu_args
+        .get_one::<String>("Destination")
+        .map_or_else(prompt_device, |v| v.to_string());
+
+    // The destination is either an address or a node, in which case the
+    // first of its addresses is used.
+    let dst_addr = match dst.parse::<IpAddr>() {
+        Ok(addr) => addr,
+        Err(_) => *topology.addresses_of(&dst).first().ok_or_else(|| {
+            NetError::BasicError(format!("Node {dst} has no addresses."))
+        })?,
+    };
+    let max_mtu = *pmtu_args.get_one::<u32>("Max").unwrap_or(&9216);
+
+    let start = Instant::now();
+    let pmtu = netgen::check::discover_pmtu(&src, dst_addr, max_mtu)?;
+    match pmtu {
+        Some(pmtu) => println!("PMTU {src} -> {dst} ({dst_addr}): {pmtu}"),
+        None => println!("PMTU {src} -> {dst} ({dst_addr}): unreachable"),
+    }
+
+    if let Some(expected) = pmtu_args.get_one::<u32>("Expect") {
+        let failure = match pmtu {
+            Some(pmtu) if pmtu == *expected => None,
+            Some(pmtu) => Some(format!("expected {expected}, found {pmtu}")),
+            None => Some(format!("{dst_addr} unreachable from {src}")),
+        };
+        let failed = failure.is_some();
+
+        let mut report = TestReport::new("check-pmtu");
+        report.cases.push(TestCase {
+            name: format!("pmtu {src} -> {dst}"),
+            duration: start.elapsed(),
+            failure,
+            node: Some(src.clone()),
+        });
+        write_reports(&report, pmtu_args)?;
+
+        if failed {
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+/// Traces the forward and reverse paths between two nodes of the running
+/// topology, and tells whether routing between them is symmetric.
+fn check_path(path_args: &ArgMatches) -> NetResult<()> {
+    let (topology, _config_file_name) = parse_config_args(path_args)?;
+    if !instance_running() {
+        return Err(NetError::BasicError(
+            "No topology instance currently running.".to_string(),
+        ));
+    }
+
+    let src = path_args
+        .get_one::<String>("Source")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let dst = path_args
+        .get_one::<String>("Destination")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let first_address = |node: &str| {
+        topology.addresses_of(node).first().copied().ok_or_else(|| {
+            NetError::BasicError(format!("Node {node} has no addresses."))
+        })
+    };
+    let (src_addr, dst_addr) = (first_address(&src)?, first_address(&dst)?);
+    let max_hops = *path_args.get_one::<u8>("Max Hops").unwrap_or(&30);
+
+    let start = Instant::now();
+    let forward = netgen::check::trace_path(&src, dst_addr, max_hops)?;
+    let reverse = netgen::check::trace_path(&dst, src_addr, max_hops)?;
+
+    // Hops are told apart by node, as each direction reports the address
+    // of the interface the probe came in through.
+    let nodes = |hops: &[Option<IpAddr>]| -> Vec<Option<String>> {
+        hops.iter()
+            .map(|hop| {
+                hop.and_then(|hop| topology.node_with_address(hop))
+                    .map(str::to_string)
+            })
+            .collect()
+    };
+    let (forward_nodes, reverse_nodes) = (nodes(&forward), nodes(&reverse));
+    for (label, from, to, hops, hop_nodes) in [
+        ("forward", &src, &dst, &forward, &forward_nodes),
+        ("reverse", &dst, &src, &reverse, &reverse_nodes),
+    ] {
+        println!("{label} {from} -> {to}:");
+        for (ttl, (hop, node)) in hops.iter().zip(hop_nodes).enumerate() {
+            match (hop, node) {
+                (Some(hop), Some(node)) => {
+                    println!("  {:>2} {hop} ({node})", ttl + 1)
+                }
+                (Some(hop), None) => println!("  {:>2} {hop}", ttl + 1),
+                (None, _) => println!("  {:>2} *", ttl + 1),
+            }
+        }
+    }
+
+    let as_str = |nodes: &[Option<String>]| -> Vec<Option<&str>> {
+        nodes.iter().map(Option::as_deref).collect()
+    };
+    let symmetric = netgen::check::is_symmetric(
+        &as_str(&forward_nodes),
+        &as_str(&reverse_nodes),
+    );
+    match symmetric {
+        true => println!("routing is symmetric"),
+        false => println!("routing is asymmetric"),
+    }
+
+    if path_args.get_flag("Symmetric") {
+        let failure = match symmetric {
+            true => None,
+            false => Some(format!("asymmetric path {src} <-> {dst}")),
+        };
+        let failed = failure.is_some();
+
+        let mut report = TestReport::new("check-path");
+        report.cases.push(TestCase {
+            name: format!("path {src} <-> {dst}"),
+            duration: start.elapsed(),
+            failure,
+            node: Some(src.clone()),
+        });
+        write_reports(&report, path_args)?;
+
+        if failed {
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+/// Waits for a node of the running topology to have a route for a prefix,
+/// optionally through a given next-hop.
+fn check_route(route_args: &ArgMatches) -> NetResult<()> {
+    if !instance_running() {
+        return Err(NetError::BasicError(
+            "No topology instance currently running.".to_string(),
+        ));
+    }
+
+    let node = route_args
+        .get_one::<String>("Node")
+        .map_or_else(prompt_device, |v| v.to_string());
+    let Some(prefix) = route_args.get_one::<IpNetwork>("Prefix") else {
+        return Err(NetError::BasicError(
+            "The prefix to look the route up for is missing.".to_string(),
+        ));
+    };
+    let via = route_args.get_one::<IpAddr>("Via").copied();
+    let timeout = route_args
+        .get_one::<u64>("Timeout")
+        .map_or(Duration::ZERO, |secs| Duration::from_secs(*secs));
+
+    let start = Instant::now();
+    let route = netgen::check::wait_for_route(&node, *prefix, via, timeout);
+    let failure = match &route {
+        Ok(route) => {
+            println!("{node}: {route}");
+            None
+        }
+        Err(err) => {
+            error!(%err, %node, %prefix, "route check failed");
+            Some(err.to_string())
+        }
+    };
+    let failed = failure.is_some();
+
+    let mut report = TestReport::new("check-route");
+    report.cases.push(TestCase {
+        name: match via {
+            Some(via) => format!("route {node} {prefix} via {via}"),
+            None => format!("route {node} {prefix}"),
+        },
+        duration: start.elapsed(),
+        failure,
+        node: Some(node.clone()),
+    });
+    write_reports(&report, route_args)?;
+
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Shows the rates of the router interfaces, sampled over `--interval`.
+/// With `--watch`, keeps refreshing them until interrupted.
+fn show_stats(stats_args: &ArgMatches) -> NetResult<()> {
+    let (topology, _config_file_name) = parse_config_args(stats_args)?;
+    if !instance_running() {
+        return Err(NetError::BasicError(
+            "No topology instance currently running.".to_string(),
+        ));
+    }
+    let seconds = stats_args.get_one::<f64>("Interval").copied();
+    let interval = Duration::try_from_secs_f64(seconds.unwrap_or(1.0))
+        .map_err(|_| {
+            NetError::BasicError(
+                "The interval has to be a positive number of seconds"
+                    .to_string(),
+            )
+        })?;
+    let color = use_color();
+    let csv_file = stats_args.get_one::<String>("CSV");
+    // The devices under test, if any, unless asked for all the routers.
+    let routers = match topology.duts() {
+        duts if duts.is_empty() || stats_args.get_flag("All") => {
+            topology.router_names()
+        }
+        duts => duts,
+    };
+
+    for sample in 0u64.. {
+        let rates = netgen::stats::sample(&topology, &routers, interval)?;
+        if let Some(csv_file) = csv_file {
+            let time_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis());
+            netgen::csv::write(
+                csv_file,
+                netgen::stats::CSV_COLUMNS,
+                &netgen::stats::to_csv(&rates, time_ms),
+                sample > 0,
+            )?;
+        }
+        if stats_args.get_flag("Watch") && color {
+            // Redraw in place, like top.
+            print!("\x1b[2J\x1b[H");
+        }
+        print!("{}", netgen::stats::render(&rates, color));
+        if !stats_args.get_flag("Watch") {
+            return Ok(());
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// Prints the events recorded after `--since`, so that a client can resume
+/// from the last one it saw. Warns when some of those were rotated out of
+/// the log already.
+fn show_events(events_args: &ArgMatches) -> NetResult<()> {
+    let since = events_args.get_one::<u64>("Since").copied().unwrap_or(0);
+    let events = netgen::events::since(since)?;
+    if let Some(first) = events.first()
+        && first.seq > since + 1
+    {
+        warn!(
+            missing = first.seq - since - 1,
+            "events rotated out of the log"
+        );
+    }
+    for event in events {
+        println!("{event}");
+    }
+    Ok(())
+}
+
+/// Prints the changes of the running topology as they happen, until
+/// interrupted.
+fn watch_topology(watch_args: &ArgMatches) -> NetResult<()> {
+    let topology = running_topology()?;
+    let nodes: Vec<&String> = watch_args
+        .get_many::<String>("Node")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    let watcher = Watcher::start(&topology)?;
+    let color = use_color();
+
+    while let Some(event) = watcher.next() {
+        if nodes.is_empty() || nodes.contains(&&event.node) {
+            println!("{}", watcher.render(&event, color));
+        }
+        // Whatever is shown, the `notify` rules see every event.
+        if let Some(notification) = event.notification() {
+            topology.notify(&notification);
+        }
+    }
+    Ok(())
+}
+
+/// Shows how the running topology differs from the topology file. With
+/// `--check`, exits with a non-zero status if anything diverges.
+fn show_status(status_args: &ArgMatches) -> NetResult<()> {
+    TopologyState::clean_stale()?;
+    // Without a topology file, the running topology is checked against
+    // itself, as recorded.
+    let recorded = match status_args.contains_id("Topo File") {
+        true => None,
+        false => Topology::recorded()?.filter(|_| instance_running()),
+    };
+    let topology = match recorded {
+        Some(topology) => topology,
+        None => parse_config_args(status_args)?.0,
+    };
+    let status = topology.status()?;
+
+    match status_args.get_flag("JSON") {
+        true => print!("{}", status.to_json()),
+        false => print!("{}", status.render(use_color())),
+    }
+    write_reports(&status.to_report(), status_args)?;
+
+    if status_args.get_flag("Check") && status.diverges() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Prints the topology file, or the running topology without one, in DOT
+/// for Graphviz or, with `--ascii`, drawn with characters.
+fn show_graph(graph_args: &ArgMatches) -> NetResult<()> {
+    let recorded = match graph_args.contains_id("Topo File") {
+        true => None,
+        false => Topology::recorded()?.filter(|_| instance_running()),
+    };
+    let (topology, provenance) = match recorded {
+        Some(topology) => {
+            let provenance = Provenance::running()?
+                .unwrap_or_else(|| Provenance::of(&topology));
+            (topology, provenance)
+        }
+        None => {
+            let topology = parse_config_args(graph_args)?.0;
+            let provenance = Provenance::of(&topology);
+            (topology, provenance)
+        }
+    };
+    match graph_args.get_flag("ASCII") {
+        true => print!("{}", draw::ascii(&topology)),
+        false => print!("{}", draw::dot(&topology, &provenance)),
+    }
+    Ok(())
+}
+
+/// Writes `report` out in every format requested through `report_args`,
+/// along with the provenance of the running topology if it has none.
+fn write_reports(report: &TestReport, args: &ArgMatches) -> NetResult<()> {
+    let mut report = report.clone();
+    if report.provenance.is_none() && instance_running() {
+        report.provenance = Provenance::running()?;
+        if let Some(topology) = Topology::recorded()? {
+            report.lead_with(&topology.duts());
+        }
+    }
+    if let Some(junit_file) = args.get_one::<String>("JUnit") {
+        report.write(ReportFormat::Junit, junit_file)?;
+    }
+    if let Some(tap_file) = args.get_one::<String>("TAP") {
+        report.write(ReportFormat::Tap, tap_file)?;
+    }
+    Ok(())
+}
+
+/// Whether to color the output: only on terminals, unless NO_COLOR is set.
+fn use_color() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Checks if the main namespace is mounted and still held by netgen,
+/// indicating if there is an instance running.
+fn instance_running() -> bool {
+    NamespaceHandle::new(None).is_running()
+}
+
+/// Make sure both the net and pid namespaces are mounted.
+fn device_running(router_name: &str) -> bool {
+    NamespaceHandle::new(Some(router_name)).is_mounted()
+}
+
+// Powers on all the devices in the topology.
+fn create_routers(topology: &mut Topology) -> NetResult<()> {
+    let fork = unsafe { fork() };
+
+    // Fork for creating the devices.
+    match fork {
+        Ok(ForkResult::Child) => {
+            let _ = mount_device(None).map_err(|err| {
+                error!(%err);
+                std::process::exit(1);
+            });
+
+            // Creates required namespaces for the routing devices.
+            topology.power_routers_on().map_err(|err| {
+                error!(%err);
+                std::process::exit(1);
+            });
+            debug!("Devices powered on");
+        }
+        Ok(ForkResult::Parent { child }) => {
+            waitpid(child, None).map_err(|err| {
+                NetError::NamespaceError(NamespaceError::Fork {
+                    fork_function: String::from("create_routers"),
+                    source: err,
+                })
+            })?;
+        }
+        Err(err) => {
+            return Err(NamespaceError::Fork {
+                fork_function: String::from("create_routers"),
+                source: err,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn add_switches_and_links(topology: &mut Topology) -> NetResult<()> {
+    netgen::enter_ns(None)?;
+    topology.power_switches_on()?;
+    topology.setup_links()?;
+
+    Ok(())
+}
+
+/// Verbosity asked for, -v counting up and -q down, as given to any
+/// subcommand.
+fn verbosity(app_match: &ArgMatches) -> i8 {
+    let mut matches = app_match;
+    let (mut verbose, mut quiet) = (0, 0);
+    loop {
+        verbose = verbose.max(matches.get_count("Verbose"));
+        quiet = quiet.max(matches.get_count("Quiet"));
+        match matches.subcommand() {
+            Some((_, sub_matches)) => matches = sub_matches,
+            None => break,
+        }
+    }
+    i8::try_from(verbose).unwrap_or(i8::MAX)
+        - i8::try_from(quiet).unwrap_or(i8::MAX)
+}
+
+/// Logs to stderr, leaving stdout to what commands output. By verbosity:
+/// errors only (-q), nothing (-qq), what netgen does (default), its
+/// details (-v) and down to the netlink and runtime crates it builds on
+/// (-vv), which otherwise only log their warnings.
+fn init_tracing(verbosity: i8) {
+    let (level, dependencies) = match verbosity {
+        i8::MIN..=-2 => (LevelFilter::OFF, LevelFilter::OFF),
+        -1 => (LevelFilter::ERROR, LevelFilter::ERROR),
+        0 => (LevelFilter::INFO, LevelFilter::WARN),
+        1 => (LevelFilter::DEBUG, LevelFilter::WARN),
+        2.. => (LevelFilter::TRACE, LevelFilter::TRACE),
+    };
+    let filter = Targets::new()
+        .with_default(dependencies)
+        .with_target("netgen", level);
+    let layer = tracing_subscriber::fmt::layer()
+        .with_target(verbosity >= 2)
+        .with_writer(std::io::stderr)
+        .with_filter(filter);
+    let subscriber = Registry::default().with(layer);
+    let _ = tracing::subscriber::set_global_default(subscriber).map_err(|_| {
+        eprintln!("unable to initialize tracing");
+    });
+}
+
+fn config_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Topo File")
+            .short('t')
+            .long("topo")
+            .value_name("yaml-file")
+            .help("file with the topology"),
+        profile_arg(),
+    ]
+}
+
+fn profile_arg() -> Arg {
+    Arg::new("Profile")
+        .long("profile")
+        .value_name("profile")
+        .action(ArgAction::Append)
+        .help("include the nodes and links of this profile")
+}
+
+fn subset_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Only")
+            .long("only")
+            .value_name("nodes")
+            .value_delimiter(',')
+            .action(ArgAction::Append)
+            .help("bring up these nodes only (comma-separated)"),
+        Arg::new("Skip")
+            .long("skip")
+            .value_name("nodes")
+            .value_delimiter(',')
+            .action(ArgAction::Append)
+            .help("bring up all the nodes but these (comma-separated)"),
+    ]
+}
+
+fn parallelism_arg() -> Arg {
+    Arg::new("Parallelism")
+        .long("parallelism")
+        .value_name("n")
+        .value_parser(clap::value_parser!(u64).range(1..))
+        .help("maximum concurrent netlink operations while setting up links")
+}
+
+fn strictness_arg() -> Arg {
+    Arg::new("Strictness")
+        .long("strictness")
+        .value_name("level")
+        .value_parser(["strict", "warn"])
+        .help(
+            "whether issues netgen can work around abort the start (strict) \
+                or are reported once up (warn, default)",
+        )
+}
+
+fn init_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Output")
+            .short('o')
+            .long("output")
+            .value_name("yaml-file")
+            .help("file to write the topology to (default: topology.yml)"),
+        Arg::new("Overwrite")
+            .long("overwrite")
+            .action(ArgAction::SetTrue)
+            .help("replace the topology and startup configs if they exist"),
+    ]
+}
+
+fn quick_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Routers")
+            .value_name("routers")
+            .value_parser(clap::value_parser!(usize))
+            .help("number of routers (default: 3)"),
+        Arg::new("Shape")
+            .long("shape")
+            .value_name("shape")
+            .value_parser(["ring", "chain"])
+            .help("how the routers are linked (default: ring, from 3 on)"),
+        Arg::new("Daemons")
+            .long("daemons")
+            .value_name("daemons")
+            .help("FRR daemons, e.g. ospfd,isisd, or none (default: ospfd)"),
+    ]
+}
+
+fn tune_arg() -> Arg {
+    Arg::new("Tune")
+        .long("tune")
+        .action(ArgAction::SetTrue)
+        .help("raise the host limits the topology would run into")
+}
+
+fn force_arg() -> Arg {
+    Arg::new("Force")
+        .long("force")
+        .action(ArgAction::SetTrue)
+        .help("take over what a topology which crashed left behind")
+}
+
+fn login_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Device Name")
+            .short('d')
+            .long("device")
+            .value_name("device-name")
+            .help("name of device"),
+        Arg::new("Topo File")
+            .short('t')
+            .long("topo")
+            .value_name("yaml-file")
+            .help("file with the topology"),
+        profile_arg(),
+        Arg::new("No Banner")
+            .long("no-banner")
+            .action(ArgAction::SetTrue)
+            .help("do not print the summary of the device"),
+    ]
+}
+
+fn exec_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node")
+            .value_name("node")
+            .help("router to run it in"),
+        // After `--`, for `--all` not to take the command for a node.
+        Arg::new("Command")
+            .value_name("command")
+            .num_args(1..)
+            .last(true)
+            .allow_hyphen_values(true)
+            .help("command to run, along with its arguments"),
+        Arg::new("Last")
+            .long("last")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all(["Command", "History"])
+            .help("run the last command run in the router again"),
+        Arg::new("History")
+            .long("history")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("Command")
+            .help("list the commands run in the router, oldest first"),
+        Arg::new("All")
+            .long("all")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all(["Node", "Last", "History"])
+            .help("run the command in every router, concurrently"),
+        Arg::new("Parallel")
+            .long("parallel")
+            .value_name("n")
+            .value_parser(clap::value_parser!(usize).range(1..))
+            .requires("All")
+            .help("run it in at most this many routers at once"),
+    ]
+}
+
+fn lldp_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node")
+            .value_name("node")
+            .help("router to list them for"),
+    ]
+}
+
+fn ps_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node")
+            .value_name("node")
+            .help("router to list them for"),
+    ]
+}
+
+fn sockets_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node")
+            .value_name("node")
+            .help("router to list them for"),
+        Arg::new("Listening")
+            .long("listening")
+            .short('l')
+            .action(ArgAction::SetTrue)
+            .help("only list the listening sockets"),
+    ]
+}
+
+fn shell_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node")
+            .value_name("node")
+            .help("router to open it in"),
+    ]
+}
+
+fn cp_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Source")
+            .value_name("src")
+            .required(true)
+            .help("file or directory to copy, a host path or <node>:<path>"),
+        Arg::new("Destination")
+            .value_name("dst")
+            .required(true)
+            .help("where to copy it, a host path or <node>:<path>"),
+    ]
+}
+
+fn ls_args() -> Vec<Arg> {
+    config_args()
+}
+
+fn apply_args() -> Vec<Arg> {
+    let mut args = config_args();
+    args.extend([
+        Arg::new("Plan")
+            .long("plan")
+            .action(ArgAction::SetTrue)
+            .help("only show the changes, without applying them"),
+        Arg::new("Yes")
+            .short('y')
+            .long("yes")
+            .action(ArgAction::SetTrue)
+            .help("apply the changes without asking for confirmation"),
+        Arg::new("JSON")
+            .long("json")
+            .action(ArgAction::SetTrue)
+            .help("show the plan as JSON"),
+        parallelism_arg(),
+    ]);
+    args
+}
+
+fn stats_args() -> Vec<Arg> {
+    let mut args = config_args();
+    args.extend([
+        Arg::new("Interval")
+            .short('i')
+            .long("interval")
+            .value_name("seconds")
+            .value_parser(clap::value_parser!(f64))
+            .help("how long to sample the counters for (default: 1)"),
+        Arg::new("Watch")
+            .short('w')
+            .long("watch")
+            .action(ArgAction::SetTrue)
+            .help("keep refreshing the rates"),
+        Arg::new("All")
+            .short('a')
+            .long("all")
+            .action(ArgAction::SetTrue)
+            .help("sample every router, not only the devices under test"),
+        csv_arg(),
+    ]);
+    args
+}
+
+fn watch_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node")
+            .short('n')
+            .long("node")
+            .value_name("node")
+            .action(ArgAction::Append)
+            .help("only show the changes of the node, can be repeated"),
+    ]
+}
+
+fn events_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Since")
+            .long("since")
+            .value_name("seq")
+            .value_parser(clap::value_parser!(u64))
+            .help("only print the events numbered after this one"),
+    ]
+}
+
+fn status_args() -> Vec<Arg> {
+    let mut args = config_args();
+    args.push(
+        Arg::new("Check")
+            .long("check")
+            .action(ArgAction::SetTrue)
+            .help("exit with a non-zero status if anything diverges"),
+    );
+    args.push(
+        Arg::new("JSON")
+            .long("json")
+            .action(ArgAction::SetTrue)
+            .help("show the status as JSON"),
+    );
+    args.extend(report_args());
+    args
+}
+
+fn graph_args() -> Vec<Arg> {
+    let mut args = config_args();
+    args.push(
+        Arg::new("ASCII")
+            .long("ascii")
+            .action(ArgAction::SetTrue)
+            .help("draw the topology in the terminal rather than in DOT"),
+    );
+    args
+}
+
+fn fmt_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Topo File")
+            .value_name("yaml-file")
+            .help("file with the topology"),
+        Arg::new("Write")
+            .short('w')
+            .long("write")
+            .action(ArgAction::SetTrue)
+            .help("rewrite the file rather than print it"),
+        Arg::new("Check")
+            .long("check")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("Write")
+            .help("only fail if the file is not in canonical form"),
+    ]
+}
+
+fn diff_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Old")
+            .value_name("old-yaml-file")
+            .help("file with the topology to compare from"),
+        Arg::new("New")
+            .value_name("new-yaml-file")
+            .help("file with the topology to compare to"),
+        profile_arg(),
+        Arg::new("JSON")
+            .long("json")
+            .action(ArgAction::SetTrue)
+            .help("show the differences as JSON"),
+    ]
+}
+
+fn import_args() -> Vec<Arg> {
+    vec![
+        Arg::new("From Host")
+            .long("from-host")
+            .action(ArgAction::SetTrue)
+            .help("import the namespaces under /var/run/netns"),
+        Arg::new("Configs")
+            .long("configs")
+            .value_name("dir")
+            .conflicts_with("From Host")
+            .help("import a directory of FRR or Holo router configurations"),
+        Arg::new("Output")
+            .short('o')
+            .long("output")
+            .value_name("yaml-file")
+            .help("write the topology to this file instead of stdout"),
+    ]
+}
+
+fn lab_add_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Name")
+            .value_name("name")
+            .help("name to register the lab as"),
+        Arg::new("Topo File")
+            .short('t')
+            .long("topo")
+            .value_name("yaml-file")
+            .help("file with the topology"),
+        Arg::new("Description")
+            .long("description")
+            .value_name("text")
+            .help("what the lab is about"),
+        Arg::new("Tag")
+            .long("tag")
+            .value_name("tag")
+            .action(ArgAction::Append)
+            .help("tag the lab, e.g. with the protocols it exercises"),
+    ]
+}
+
+fn lab_list_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Tag")
+            .long("tag")
+            .value_name("tag")
+            .help("only list the labs carrying this tag"),
+    ]
+}
+
+fn lab_start_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Name").value_name("name").help("name of the lab"),
+        parallelism_arg(),
+        force_arg(),
+    ]
+}
+
+fn calibrate_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Duration")
+            .short('d')
+            .long("duration")
+            .value_name("seconds")
+            .value_parser(clap::value_parser!(f64))
+            .help("how long to blast frames of each size for (default: 5)"),
+    ]
+}
+
+fn test_run_args() -> Vec<Arg> {
+    let mut args = vec![
+        Arg::new("Suite File")
+            .value_name("suite-file")
+            .help("file with the test suite"),
+        Arg::new("Diff Report")
+            .long("diff-report")
+            .value_name("dir")
+            .help("write what the scenario changed on each node to this directory"),
+        csv_arg(),
+    ];
+    args.push(parallelism_arg());
+    args.extend(report_args());
+    args
+}
+
+fn history_args() -> Vec<Arg> {
+    vec![
+        Arg::new("User")
+            .long("user")
+            .value_name("user")
+            .help("only list the runs of this user"),
+        Arg::new("Command")
+            .long("command")
+            .value_name("command")
+            .help("only list the runs of this command, e.g. start"),
+        Arg::new("Last")
+            .short('n')
+            .long("last")
+            .value_name("count")
+            .value_parser(clap::value_parser!(usize))
+            .help("how many of the latest runs to list (default: 20)"),
+        Arg::new("JSON")
+            .long("json")
+            .action(ArgAction::SetTrue)
+            .help("print the runs as JSON"),
+    ]
+}
+
+fn schedule_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Schedule File")
+            .value_name("schedule-file")
+            .help("file with the measurement jobs"),
+    ]
+}
+
+fn sweep_args() -> Vec<Arg> {
+    let mut args = vec![
+        Arg::new("Var")
+            .long("var")
+            .value_name("name=value,..")
+            .value_parser(clap::value_parser!(SweepVar))
+            .action(ArgAction::Append)
+            .help(
+                "template variable and the values it takes, e.g. delay=10,50",
+            ),
+        Arg::new("Topo File")
+            .short('t')
+            .long("topo")
+            .value_name("yaml-file")
+            .help("file with the topology (default: the suite's)"),
+        Arg::new("Test")
+            .long("test")
+            .value_name("suite-file")
+            .help("file with the test suite"),
+        Arg::new("Output")
+            .short('o')
+            .long("output")
+            .value_name("json-file")
+            .help(
+                "write the results of every combination as JSON to this file",
+            ),
+    ];
+    args.push(parallelism_arg());
+    args.extend(report_args());
+    args
+}
+
+fn tools_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Missing")
+            .long("missing")
+            .action(ArgAction::SetTrue)
+            .help("only list the programs which are not installed"),
+    ]
+}
+
+fn bundle_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Output")
+            .short('o')
+            .long("output")
+            .value_name("tar-file")
+            .help("write the bundle to this file (default: netgen-bundle-*)"),
+        Arg::new("Include")
+            .long("include")
+            .value_name("path")
+            .action(ArgAction::Append)
+            .help("also bundle this file or directory, e.g. a report or pcap"),
+    ]
+}
+
+fn stop_args() -> Vec<Arg> {
+    let mut args = config_args();
+    args.extend([
+        Arg::new("Daemons Only")
+            .long("daemons-only")
+            .visible_alias("keep-ns")
+            .action(ArgAction::SetTrue)
+            .help("only stop the daemons, keeping the nodes and links up"),
+        Arg::new("Keep Artifacts")
+            .long("keep-artifacts")
+            .value_name("dir")
+            .conflicts_with("Daemons Only")
+            .help("keep the logs, cores and captures of the nodes in this dir"),
+        Arg::new("Purge")
+            .long("purge")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("Daemons Only")
+            .help("also remove what the routers keep of their persist: dirs"),
+    ]);
+    args
+}
+
+fn partition_args() -> Vec<Arg> {
+    let mut args = config_args();
+    args.push(
+        Arg::new("Groups")
+            .long("groups")
+            .value_name("groups")
+            .help("nodes of each group, e.g. \"r1,r2 / r3,r4\""),
+    );
+    args
+}
+
+fn check_pmtu_args() -> Vec<Arg> {
+    let mut args = vec![
+        Arg::new("Source")
+            .value_name("src-node")
+            .help("node the probes are sent from"),
+        Arg::new("Destination")
+            .value_name("dst")
+            .help("node or address the probes are sent to"),
+        Arg::new("Topo File")
+            .short('t')
+            .long("topo")
+            .value_name("yaml-file")
+            .help("file with the topology"),
+        Arg::new("Expect")
+            .long("expect")
+            .value_name("mtu")
+            .value_parser(clap::value_parser!(u32))
+            .help("fail unless the discovered path MTU is exactly this"),
+        Arg::new("Max")
+            .long("max")
+            .value_name("mtu")
+            .value_parser(clap::value_parser!(u32))
+            .help("largest MTU probed (default 9216)"),
+    ];
+    args.extend(report_args());
+    args
+}
+
+fn check_route_args() -> Vec<Arg> {
+    let mut args = vec![
+        Arg::new("Node")
+            .value_name("node")
+            .help("node whose routing table is checked"),
+        Arg::new("Prefix")
+            .value_name("prefix")
+            .value_parser(clap::value_parser!(IpNetwork))
+            .help("prefix the route has to be for, exactly"),
+        Arg::new("Via")
+            .long("via")
+            .value_name("address")
+            .value_parser(clap::value_parser!(IpAddr))
+            .help("fail unless the route goes through this next-hop"),
+        Arg::new("Timeout")
+            .long("timeout")
+            .value_name("seconds")
+            .value_parser(clap::value_parser!(u64))
+            .help("how long to wait for the route (default 0)"),
+    ];
+    args.extend(report_args());
+    args
+}
+
+fn verify_args() -> Vec<Arg> {
+    let mut args = vec![
+        Arg::new("Timeout")
+            .long("timeout")
+            .value_name("seconds")
+            .value_parser(clap::value_parser!(u64))
+            .help("how long each check is retried for (default 0)"),
+    ];
+    args.extend(report_args());
+    args
+}
+
+fn node_clone_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Router")
+            .value_name("router")
+            .help("router to copy"),
+        Arg::new("Name").value_name("name").help("name of the copy"),
+        Arg::new("Link To")
+            .long("link-to")
+            .value_name("node:iface")
+            .action(ArgAction::Append)
+            .help(
+                "link the copy to this interface, through the interface \
+                of the router linked to the same node",
+            ),
+        parallelism_arg(),
+    ]
+}
+
+fn node_remove_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node").value_name("node").help("node to remove"),
+        parallelism_arg(),
+    ]
+}
+
+fn session_rename_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Old")
+            .value_name("old")
+            .help("current name of the session"),
+        Arg::new("New")
+            .value_name("new")
+            .help("name to give the session"),
+    ]
+}
+
+fn node_checkpoint_args() -> Vec<Arg> {
+    let mut args = node_restore_args();
+    args.push(
+        Arg::new("Leave Running")
+            .long("leave-running")
+            .action(ArgAction::SetTrue)
+            .help("keep the daemons running once checkpointed"),
+    );
+    args
+}
+
+fn node_restore_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node")
+            .value_name("router")
+            .help("router of the daemons"),
+        Arg::new("Name")
+            .long("name")
+            .value_name("name")
+            .help(format!(
+                "name of the checkpoint, {DEFAULT_CHECKPOINT} by default"
+            )),
+    ]
+}
+
+fn link_args() -> Vec<Arg> {
+    let mut args = link_ends_args();
+    args.push(parallelism_arg());
+    args
+}
+
+fn link_ends_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Source")
+            .value_name("node:iface")
+            .required(true)
+            .help("one end of the link"),
+        Arg::new("Destination")
+            .value_name("node:iface")
+            .required(true)
+            .help("the other end of the link"),
+    ]
+}
+
+fn route_add_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node")
+            .value_name("node")
+            .required(true)
+            .help("router whose FIB the route is programmed into"),
+        Arg::new("Prefix")
+            .value_name("prefix")
+            .required(true)
+            .value_parser(clap::value_parser!(IpNetwork))
+            .help("prefix the route is for"),
+        Arg::new("Via")
+            .value_name("via")
+            .required(true)
+            .value_parser(["via"])
+            .hide_possible_values(true),
+        Arg::new("Next Hop")
+            .value_name("address")
+            .required(true)
+            .value_parser(clap::value_parser!(IpAddr))
+            .help("next-hop of the route"),
+        Arg::new("Table")
+            .long("table")
+            .value_name("table")
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .help("routing table of the route, the main one by default"),
+    ]
+}
+
+fn address_add_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Node")
+            .value_name("node")
+            .required(true)
+            .help("router the addresses are added to"),
+        Arg::new("Addresses")
+            .value_names(["iface", "prefix"])
+            .num_args(1..)
+            .help("interface and address, e.g. 'eth0.100 10.100.0.1/24'"),
+        Arg::new("File")
+            .short('f')
+            .long("file")
+            .value_name("file")
+            .help("file with an '<iface> <prefix>' pair per line, - for stdin"),
+    ]
+}
+
+fn iface_move_args() -> Vec<Arg> {
+    vec![
+        Arg::new("End")
+            .value_name("node:iface")
+            .required(true)
+            .help("linked interface to move"),
+        Arg::new("To")
+            .value_name("other-node")
+            .required(true)
+            .help("router the interface is moved to"),
+    ]
+}
+
+fn mgmt_args() -> Vec<Arg> {
+    vec![
+        Arg::new("Nodes")
+            .value_name("node")
+            .num_args(1..)
+            .required(true)
+            .help("routers to cut off, or to make reachable again"),
+        Arg::new("Ifaces")
+            .long("iface")
+            .value_name("iface")
+            .action(ArgAction::Append)
+            .help("management interface of the routers, blocked as a whole"),
+    ]
+}
+
+fn link_import_args() -> Vec<Arg> {
     vec![
+        Arg::new("Impairments File")
+            .value_name("yaml-file")
+            .required(true)
+            .help("file written by 'netgen link export'"),
+    ]
+}
+
+fn link_latency_args() -> Vec<Arg> {
+    let mut args = link_args();
+    // Nothing is brought up.
+    args.pop();
+    args.push(
+        Arg::new("Duration")
+            .short('d')
+            .long("duration")
+            .value_name("seconds")
+            .value_parser(clap::value_parser!(f64))
+            .help("how long to measure for (default: 10)"),
+    );
+    args
+}
+
+fn link_latency_csv_args() -> Vec<Arg> {
+    let mut args = link_latency_args();
+    args.push(csv_arg());
+    args
+}
+
+fn link_bench_args() -> Vec<Arg> {
+    let mut args = link_latency_args();
+    args.extend([
+        Arg::new("Frame Size")
+            .short('s')
+            .long("size")
+            .value_name("bytes")
+            .value_parser(clap::value_parser!(u16).range(60..))
+            .help("size of the frames sent (default: 64)"),
+        Arg::new("XDP")
+            .long("xdp")
+            .value_name("node:iface")
+            .action(ArgAction::Append)
+            .help("attach a pass-through XDP program to this interface"),
+    ]);
+    args
+}
+
+fn check_path_args() -> Vec<Arg> {
+    let mut args = vec![
+        Arg::new("Source")
+            .value_name("src-node")
+            .help("node the forward path starts from"),
+        Arg::new("Destination")
+            .value_name("dst-node")
+            .help("node the reverse path starts from"),
         Arg::new("Topo File")
             .short('t')
             .long("topo")
             .value_name("yaml-file")
             .help("file with the topology"),
+        Arg::new("Symmetric")
+            .long("symmetric")
+            .action(ArgAction::SetTrue)
+            .help("fail unless both paths cross the same nodes"),
+        Arg::new("Max Hops")
+            .long("max-hops")
+            .value_name("n")
+            .value_parser(clap::value_parser!(u8).range(1..))
+            .help("largest TTL probed (default 30)"),
+    ];
+    args.extend(report_args());
+    args
+}
+
+/// Reporter options shared by every command producing a `TestReport`.
+fn csv_arg() -> Arg {
+    Arg::new("CSV")
+        .long("csv")
+        .value_name("csv-file")
+        .help("write the measurements as CSV to this file")
+}
+
+fn report_args() -> Vec<Arg> {
+    vec![
+        Arg::new("JUnit")
+            .long("junit")
+            .value_name("xml-file")
+            .help("write the results as JUnit XML to this file"),
+        Arg::new("TAP")
+            .long("tap")
+            .value_name("tap-file")
+            .help("write the results as TAP to this file"),
     ]
 }
 
+fn set_parallelism(topology: &mut Topology, args: &ArgMatches) {
+    if let Some(parallelism) = args.get_one::<u64>("Parallelism") {
+        topology.set_parallelism(*parallelism as usize);
+    }
+}
+
 /// Returns Result<(topology_object, config_file_path)>
 fn parse_config_args(
     config_args: &ArgMatches,
@@ -287,17 +3798,35 @@ fn parse_config_args(
         .get_one::<String>("Topo File")
         .map_or_else(prompt_topo, |v| v.to_string());
 
-    let mut topo_file =
-        File::open(&topo_yml_file).map_err(|err| NamespaceError::FileOpen {
-            path: topo_yml_file.clone(),
+    let topology = parse_topology_file(&topo_yml_file, config_args)?;
+    Ok((topology, topo_yml_file))
+}
+
+/// Parses the topology file with the profiles given by `--profile`, or by
+/// default those the running topology was started with.
+fn parse_topology_file(
+    topo_yml_file: &str,
+    config_args: &ArgMatches,
+) -> NetResult<Topology> {
+    let source = fs::read_to_string(topo_yml_file).map_err(|err| {
+        NamespaceError::FileOpen {
+            path: topo_yml_file.to_string(),
             source: err,
-        })?;
+        }
+    })?;
 
-    let topology = TopologyParser::from_yaml_file(&mut topo_file)?;
-    Ok((topology, topo_yml_file))
+    let profiles: BTreeSet<String> =
+        match config_args.get_many::<String>("Profile") {
+            Some(profiles) => profiles.cloned().collect(),
+            None => TopologyState::load()?
+                .filter(|_| instance_running())
+                .map(|state| state.profiles.into_iter().collect())
+                .unwrap_or_default(),
+        };
+    TopologyParser::from_yaml_str_with(&source, &BTreeMap::new(), &profiles)
 }
 
-fn parse_login_args(config_args: &ArgMatches) -> NetResult<Router> {
+fn parse_login_args(config_args: &ArgMatches) -> NetResult<(Topology, Router)> {
     let topo_yml_file = config_args
         .get_one::<String>("Topo File")
         .map_or_else(prompt_topo, |v| v.to_string());
@@ -307,19 +3836,14 @@ fn parse_login_args(config_args: &ArgMatches) -> NetResult<Router> {
         .map_or_else(prompt_device, |v| v.to_string());
 
     // Generate Topology.
-    let mut topo_file =
-        File::open(&topo_yml_file).map_err(|err| NamespaceError::FileOpen {
-            path: topo_yml_file.clone(),
-            source: err,
-        })?;
-    let topology = TopologyParser::from_yaml_file(&mut topo_file)?;
+    let topology = parse_topology_file(&topo_yml_file, config_args)?;
 
     // Fetch device.
     let router = topology
         .get_router(&router_name)
         .ok_or(ConfigError::UnknownNode(router_name))?;
 
-    Ok(router)
+    Ok((topology, router))
 }
 
 fn prompt_topo() -> String {
@@ -332,6 +3856,17 @@ fn prompt_topo() -> String {
     buf.trim().to_string()
 }
 
+fn prompt_suite() -> NetResult<String> {
+    println!("Test suite YAML file name: ");
+    let mut buf = String::new();
+    std::io::stdin().read_line(&mut buf).map_err(|err| {
+        NetError::BasicError(format!(
+            "Unable to read the suite file name: {err:?}"
+        ))
+    })?;
+    Ok(buf.trim().to_string())
+}
+
 fn prompt_device() -> String {
     println!("Device Name: ");
     let mut buf = String::new();
@@ -0,0 +1,315 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{debug, warn};
+
+use crate::error::NetError;
+use crate::events::rotated_events_file;
+use crate::report::json_string;
+use crate::topology::Topology;
+use crate::{NetResult, devices_ns_dir, events_file, exec_in_ns, state_file};
+
+// Entries of the device directories left out of bundles: the namespace
+// mounts, the host directories mounted into the routers, which can be
+// anything, and private keys.
+const SKIPPED_DEVICE_ENTRIES: &[&str] =
+    &["net", "pid", "anchor", "vols", "ssh_host_ed25519_key"];
+
+// Commands whose output is captured from every running router.
+const NODE_COMMANDS: &[(&str, &[&str])] = &[
+    ("ip-addr.txt", &["ip", "addr", "show"]),
+    ("ip-route.txt", &["ip", "route", "show", "table", "all"]),
+    (
+        "ip-6-route.txt",
+        &["ip", "-6", "route", "show", "table", "all"],
+    ),
+    ("ip-neigh.txt", &["ip", "neigh", "show"]),
+    ("tc-qdisc.txt", &["tc", "qdisc", "show"]),
+    ("ps.txt", &["ps", "-ef"]),
+];
+
+// Further commands whose output is captured from the devices under test.
+const DUT_COMMANDS: &[(&str, &[&str])] = &[
+    (
+        "ip-link-stats.txt",
+        &["ip", "-s", "-s", "-d", "link", "show"],
+    ),
+    ("ip-rule.txt", &["ip", "rule", "show"]),
+    ("tc-stats.txt", &["tc", "-s", "qdisc", "show"]),
+    ("ss.txt", &["ss", "-tuanpi"]),
+    ("nstat.txt", &["nstat", "-az"]),
+];
+
+// ==== struct Bundle ====
+
+/// Artifacts of a run gathered into a single archive, e.g. to attach to a
+/// bug report against a routing daemon:
+///
+/// ```text
+/// manifest.json        what is in the bundle, and where it comes from
+/// state.yml            the state file, with the topology that ran
+/// events.log           probe transitions and other events
+/// events.log.1         older events, once the log was rotated
+/// devices/<node>/      the files netgen wrote for each node
+/// nodes/<node>/*.txt   addresses, routes, neighbors, qdiscs and processes
+///                      of each router, if the topology is running, along
+///                      with link, socket and qdisc statistics for DUTs
+/// extra/               files and directories given on the command line,
+///                      e.g. reports, pcaps or diff reports
+/// ```
+#[derive(Debug)]
+pub struct Bundle {
+    // Where the artifacts are gathered before being archived.
+    staging: PathBuf,
+    entries: Vec<BundleEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BundleEntry {
+    // Relative to the root of the bundle.
+    pub path: String,
+    // Where it was copied from, or the command it is the output of.
+    pub source: String,
+    pub size: u64,
+}
+
+impl Bundle {
+    pub fn new() -> NetResult<Self> {
+        let staging = std::env::temp_dir()
+            .join(format!("netgen-bundle-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&staging);
+        fs::create_dir_all(&staging).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to create {}: {err:?}",
+                staging.display()
+            ))
+        })?;
+        Ok(Self {
+            staging,
+            entries: vec![],
+        })
+    }
+
+    /// A bundle gathered into `dir` and left there rather than archived,
+    /// see `keep`.
+    pub fn in_dir(dir: &Path) -> NetResult<Self> {
+        fs::create_dir_all(dir).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to create {}: {err:?}",
+                dir.display()
+            ))
+        })?;
+        Ok(Self {
+            staging: dir.to_path_buf(),
+            entries: vec![],
+        })
+    }
+
+    /// Gathers the state file, the event log and the device directories.
+    pub fn add_run_files(&mut self) -> NetResult<()> {
+        for (file, name) in [
+            (state_file(), "state.yml"),
+            (rotated_events_file(), "events.log.1"),
+            (events_file(), "events.log"),
+        ] {
+            if Path::new(&file).exists() {
+                self.add_path(Path::new(&file), name)?;
+            }
+        }
+
+        let Ok(devices) = fs::read_dir(devices_ns_dir()) else {
+            return Ok(());
+        };
+        for device in devices.flatten() {
+            let name = device.file_name().to_string_lossy().to_string();
+            self.copy_tree(&device.path(), &format!("devices/{name}"), true)?;
+        }
+        Ok(())
+    }
+
+    /// Captures the state of every router of the running `topology`.
+    pub fn add_node_state(&mut self, topology: &Topology) -> NetResult<()> {
+        let duts = topology.duts();
+        for node in topology.router_names() {
+            let commands = match duts.contains(&node) {
+                true => [NODE_COMMANDS, DUT_COMMANDS].concat(),
+                false => NODE_COMMANDS.to_vec(),
+            };
+            for (file, command) in commands {
+                let (cmd, args) = (command[0], &command[1..]);
+                let output = match exec_in_ns(&node, cmd, args) {
+                    Ok(output) => output,
+                    Err(err) => {
+                        warn!(%node, %cmd, %err, "unable to capture");
+                        continue;
+                    }
+                };
+                let mut contents = output.stdout;
+                contents.extend(output.stderr);
+                self.write(
+                    &format!("nodes/{node}/{file}"),
+                    &contents,
+                    &format!("{} (in {node})", command.join(" ")),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a file or directory under `extra/`.
+    pub fn add_extra(&mut self, path: &Path) -> NetResult<()> {
+        let name = path
+            .file_name()
+            .map_or_else(|| "extra".into(), |name| name.to_string_lossy());
+        self.add_path(path, &format!("extra/{name}"))
+    }
+
+    /// Writes the manifest and archives the bundle as a gzipped tarball at
+    /// `output`, returning what it holds.
+    pub fn write(self, output: &Path) -> NetResult<Vec<BundleEntry>> {
+        self.write_manifest()?;
+
+        let result = Command::new("tar")
+            .arg("-czf")
+            .arg(output)
+            .arg("-C")
+            .arg(&self.staging)
+            .arg(".")
+            .output();
+        let _ = fs::remove_dir_all(&self.staging);
+        match result {
+            Ok(tar) if tar.status.success() => Ok(self.entries),
+            Ok(tar) => Err(NetError::BasicError(format!(
+                "Unable to write {}: {}",
+                output.display(),
+                String::from_utf8_lossy(&tar.stderr).trim()
+            ))),
+            Err(err) => {
+                Err(NetError::BasicError(format!("Unable to run tar: {err:?}")))
+            }
+        }
+    }
+
+    /// Writes the manifest and leaves the bundle where it was gathered,
+    /// returning what it holds.
+    pub fn keep(self) -> NetResult<Vec<BundleEntry>> {
+        self.write_manifest()?;
+        Ok(self.entries)
+    }
+
+    fn write_manifest(&self) -> NetResult<()> {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let files: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    r#"    {{"path": {}, "source": {}, "size": {}}}"#,
+                    json_string(&entry.path),
+                    json_string(&entry.source),
+                    entry.size
+                )
+            })
+            .collect();
+        let manifest = format!(
+            "{{\n  \"netgen\": {},\n  \"created\": {created},\n  \"files\": [\n{}\n  ]\n}}\n",
+            json_string(env!("CARGO_PKG_VERSION")),
+            files.join(",\n")
+        );
+        write_file(&self.staging.join("manifest.json"), manifest.as_bytes())
+    }
+
+    fn add_path(&mut self, path: &Path, dst: &str) -> NetResult<()> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => {
+                self.copy_tree(path, dst, false)
+            }
+            Ok(_) => self.copy_file(path, dst),
+            Err(err) => Err(NetError::BasicError(format!(
+                "Unable to read {}: {err:?}",
+                path.display()
+            ))),
+        }
+    }
+
+    // Copies the regular files under `src`, symbolic links and special
+    // files being skipped.
+    fn copy_tree(
+        &mut self,
+        src: &Path,
+        dst: &str,
+        device: bool,
+    ) -> NetResult<()> {
+        let entries = fs::read_dir(src).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to read {}: {err:?}",
+                src.display()
+            ))
+        })?;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if device && SKIPPED_DEVICE_ENTRIES.contains(&name.as_str()) {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let dst = format!("{dst}/{name}");
+            if file_type.is_dir() {
+                self.copy_tree(&entry.path(), &dst, false)?;
+            } else if file_type.is_file() {
+                self.copy_file(&entry.path(), &dst)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_file(&mut self, src: &Path, dst: &str) -> NetResult<()> {
+        let contents = match fs::read(src) {
+            Ok(contents) => contents,
+            // Files of a running topology may come and go.
+            Err(err) => {
+                debug!(path = %src.display(), ?err, "skipped");
+                return Ok(());
+            }
+        };
+        self.write(dst, &contents, &src.display().to_string())
+    }
+
+    fn write(
+        &mut self,
+        dst: &str,
+        contents: &[u8],
+        source: &str,
+    ) -> NetResult<()> {
+        let path = self.staging.join(dst);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to create {}: {err:?}",
+                    dir.display()
+                ))
+            })?;
+        }
+        write_file(&path, contents)?;
+        self.entries.push(BundleEntry {
+            path: dst.to_string(),
+            source: source.to_string(),
+            size: contents.len() as u64,
+        });
+        Ok(())
+    }
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> NetResult<()> {
+    fs::write(path, contents).map_err(|err| {
+        NetError::BasicError(format!(
+            "Unable to write {}: {err:?}",
+            path.display()
+        ))
+    })
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use yaml_rust2::yaml::{Hash, Yaml};
+use yaml_rust2::{YamlEmitter, YamlLoader};
+
+use crate::bench::{self, BenchOptions};
+use crate::error::NetError;
+use crate::lab::config_dir;
+use crate::topology::Topology;
+use crate::{NetResult, exec_in_ns};
+
+const CALIBRATION_FILE: &str = "calibration.yml";
+
+/// Two routers linked back to back, measured by `netgen calibrate`.
+pub const REFERENCE_TOPOLOGY: &str = "
+routers:
+  calib-a:
+    interfaces:
+      eth0:
+        - 10.255.255.1/30
+  calib-b:
+    interfaces:
+      eth0:
+        - 10.255.255.2/30
+
+links:
+  - src-device: calib-a
+    src-iface: eth0
+    dst-device: calib-b
+    dst-iface: eth0
+";
+
+// Frames of the smallest and the largest size on a 1500-byte MTU link.
+const SMALL_FRAME: u16 = 64;
+const LARGE_FRAME: u16 = 1514;
+
+// ==== struct Calibration ====
+
+/// What a veth pair carries on this host, as measured on the reference
+/// topology, persisted to `calibration.yml` in the netgen config directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    // Small frames per second.
+    pub max_pps: f64,
+    // With large frames, in kbit/s like link rate limits.
+    pub max_rate: u64,
+    // Average round trip time of a ping, in microseconds.
+    pub latency_us: f64,
+    // Seconds since the epoch.
+    pub measured_at: u64,
+}
+
+impl Calibration {
+    /// Measures the reference topology, which has to be running, blasting
+    /// frames over its link for `duration` per frame size.
+    pub fn measure(topology: &Topology, duration: Duration) -> NetResult<Self> {
+        let blast = |frame_size| {
+            let options = BenchOptions {
+                duration,
+                frame_size,
+                xdp: vec![],
+            };
+            bench::run(topology, "calib-a:eth0", "calib-b:eth0", &options)
+        };
+        let small = blast(SMALL_FRAME)?;
+        let large = blast(LARGE_FRAME)?;
+
+        Ok(Self {
+            max_pps: small.rx_pps(),
+            max_rate: (large.rx_pps() * f64::from(LARGE_FRAME) * 8.0 / 1000.0)
+                as u64,
+            latency_us: ping("calib-a", "10.255.255.2")?,
+            measured_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Rate limits of `topology` above what this host measured, as
+    /// warnings to show before running it.
+    pub fn check(&self, topology: &Topology) -> Vec<String> {
+        topology
+            .rate_limited_links()
+            .into_iter()
+            .filter(|(_, rate)| u64::from(*rate) > self.max_rate)
+            .map(|(link, rate)| {
+                format!(
+                    "{} - {} is limited to {rate} kbit/s, but this host only \
+                        carried {} kbit/s over a veth pair when calibrated",
+                    link.src, link.dst, self.max_rate
+                )
+            })
+            .collect()
+    }
+
+    /// Where the calibration is kept, next to the lab registry.
+    pub fn path() -> NetResult<PathBuf> {
+        Ok(config_dir("the calibration")?.join(CALIBRATION_FILE))
+    }
+
+    /// The last calibration of this host, if any.
+    pub fn load() -> NetResult<Option<Self>> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let invalid = || {
+            NetError::BasicError(format!(
+                "Calibration {} is invalid, run 'netgen calibrate' again",
+                path.display()
+            ))
+        };
+        let contents = fs::read_to_string(&path).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to read {}: {err:?}",
+                path.display()
+            ))
+        })?;
+        let docs =
+            YamlLoader::load_from_str(&contents).map_err(|_| invalid())?;
+        let doc = docs.first().ok_or_else(invalid)?;
+        let float = |key: &str| match &doc[key] {
+            Yaml::Real(_) => doc[key].as_f64(),
+            Yaml::Integer(value) => Some(*value as f64),
+            _ => None,
+        };
+        let integer = |key: &str| {
+            doc[key]
+                .as_i64()
+                .and_then(|value| u64::try_from(value).ok())
+        };
+        match (
+            float("max-pps"),
+            integer("max-rate"),
+            float("latency-us"),
+            integer("measured-at"),
+        ) {
+            (
+                Some(max_pps),
+                Some(max_rate),
+                Some(latency_us),
+                Some(measured_at),
+            ) => Ok(Some(Self {
+                max_pps,
+                max_rate,
+                latency_us,
+                measured_at,
+            })),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn save(&self) -> NetResult<()> {
+        let mut doc = Hash::new();
+        for (key, value) in [
+            ("max-pps", Yaml::Real(format!("{:.0}", self.max_pps))),
+            ("max-rate", Yaml::Integer(self.max_rate as i64)),
+            ("latency-us", Yaml::Real(format!("{:.1}", self.latency_us))),
+            ("measured-at", Yaml::Integer(self.measured_at as i64)),
+        ] {
+            doc.insert(Yaml::String(key.to_string()), value);
+        }
+
+        let mut contents = String::new();
+        let mut emitter = YamlEmitter::new(&mut contents);
+        emitter.dump(&Yaml::Hash(doc)).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to write the calibration: {err:?}"
+            ))
+        })?;
+        contents.push('\n');
+
+        let path = Self::path()?;
+        let write_err = |err: std::io::Error| {
+            NetError::BasicError(format!(
+                "Unable to write {}: {err:?}",
+                path.display()
+            ))
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(write_err)?;
+        }
+        fs::write(&path, contents).map_err(write_err)
+    }
+
+    pub fn render(&self) -> String {
+        format!(
+            "small frames  {:>12.0} pps\nlarge frames  {:>12} kbit/s\n\
+                latency       {:>12.1} us\n",
+            self.max_pps, self.max_rate, self.latency_us
+        )
+    }
+}
+
+// Average round trip time of pings from `node` to `address`, in
+// microseconds.
+fn ping(node: &str, address: &str) -> NetResult<f64> {
+    let output = exec_in_ns(
+        node,
+        "ping",
+        &["-q", "-c", "50", "-i", "0.01", "-W", "1", address],
+    )?;
+    // rtt min/avg/max/mdev = 0.031/0.046/0.101/0.012 ms
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.split_once(" = "))
+        .and_then(|(_, rtts)| rtts.split('/').nth(1)?.parse::<f64>().ok())
+        .map(|avg| avg * 1000.0)
+        .ok_or_else(|| {
+            NetError::BasicError(format!(
+                "Unable to ping {address} from {node}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        })
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use nix::sys::signal::{
+    SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction,
+};
+
+use crate::NetResult;
+use crate::error::NetError;
+
+// Longest a sleep goes on for once cancelled.
+const SLEEP_SLICE: Duration = Duration::from_millis(100);
+
+// Set by the handler of SIGINT and SIGTERM, once installed.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn interrupted(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Gives SIGINT and SIGTERM their default action back in a forked process
+/// which outlives the one bringing the topology up, e.g. an LLDP agent, so
+/// that `stop` terminating it does not merely cancel a token.
+pub(crate) fn reset_interrupts() {
+    let action =
+        SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+    for signal in [Signal::SIGINT, Signal::SIGTERM] {
+        // Safe as no handler is installed.
+        let _ = unsafe { sigaction(signal, &action) };
+    }
+}
+
+// ==== struct CancelToken ====
+
+/// Asks long-running operations to stop, e.g. setting up a topology or
+/// playing a scenario. They check it between their steps, so that a step
+/// is never left halfway: a daemon is started and recorded or not at all,
+/// and a churn withdraws what it announced.
+///
+/// Clones share their state, cancelling one cancels all of them.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    // Also cancelled by SIGINT and SIGTERM, see `on_interrupt`.
+    interrupts: bool,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token cancelled by Ctrl-C or SIGTERM, which no longer kill the
+    /// process. The handlers are inherited by the processes it forks, which
+    /// get cancelled along with it, but for the daemons and namespace
+    /// holders outliving it, see `reset_interrupts`.
+    pub fn on_interrupt() -> NetResult<Self> {
+        // Restarted, so that waiting on a child is not cut short.
+        let action = SigAction::new(
+            SigHandler::Handler(interrupted),
+            SaFlags::SA_RESTART,
+            SigSet::empty(),
+        );
+        for signal in [Signal::SIGINT, Signal::SIGTERM] {
+            // Safe as the handler only stores to an atomic.
+            unsafe { sigaction(signal, &action) }.map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to handle {signal}: {err:?}"
+                ))
+            })?;
+        }
+        Ok(Self {
+            interrupts: true,
+            ..Self::default()
+        })
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || (self.interrupts && INTERRUPTED.load(Ordering::SeqCst))
+    }
+
+    /// Fails with `NetError::Cancelled` once cancelled.
+    pub fn check(&self) -> NetResult<()> {
+        match self.is_cancelled() {
+            true => Err(NetError::Cancelled),
+            false => Ok(()),
+        }
+    }
+
+    /// Sleeps for `duration`, or until cancelled.
+    pub fn sleep(&self, duration: Duration) -> NetResult<()> {
+        let deadline = Instant::now() + duration;
+        loop {
+            self.check()?;
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(());
+            }
+            std::thread::sleep(left.min(SLEEP_SLICE));
+        }
+    }
+}
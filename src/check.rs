@@ -0,0 +1,251 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use ipnetwork::IpNetwork;
+use tracing::debug;
+
+use crate::error::NetError;
+use crate::{NetResult, exec_in_ns};
+
+/// Smallest MTU an IPv4 host must accept (RFC 791).
+const MIN_IPV4_MTU: u32 = 68;
+/// Smallest MTU an IPv6 link can have (RFC 8200).
+const MIN_IPV6_MTU: u32 = 1280;
+/// How often the routing table is read while waiting for a route.
+const ROUTE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Finds the path MTU from `node` towards `dst` by sending pings with the
+/// "Don't Fragment" bit set and binary searching for the largest packet that
+/// makes it through.
+///
+/// Returns `None` if not even a minimum-sized packet gets an answer.
+pub fn discover_pmtu(
+    node: &str,
+    dst: IpAddr,
+    max_mtu: u32,
+) -> NetResult<Option<u32>> {
+    let (family, header_len, min_mtu) = match dst {
+        IpAddr::V4(_) => ("-4", 28, MIN_IPV4_MTU),
+        IpAddr::V6(_) => ("-6", 48, MIN_IPV6_MTU),
+    };
+    let dst = dst.to_string();
+
+    let probe = |mtu: u32| -> NetResult<bool> {
+        let payload = (mtu - header_len).to_string();
+        let output = exec_in_ns(
+            node,
+            "ping",
+            &[
+                family, "-M", "do", "-c", "1", "-W", "1", "-s", &payload, &dst,
+            ],
+        )?;
+        debug!(%node, %dst, mtu, success = output.status.success(), "probe");
+        Ok(output.status.success())
+    };
+
+    if !probe(min_mtu)? {
+        return Ok(None);
+    }
+
+    // Invariant: `low` gets through, anything above `high` does not.
+    let (mut low, mut high) = (min_mtu, max_mtu.max(min_mtu));
+    while low < high {
+        let mid = low + (high - low).div_ceil(2);
+        if probe(mid)? {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(Some(low))
+}
+
+/// Hops from `node` towards `dst`, found by sending pings with increasing
+/// TTLs and collecting who reports them expired, `dst` itself being last.
+///
+/// Hops that do not answer are `None`. The trace stops at `dst`, or after
+/// `max_hops`.
+pub fn trace_path(
+    node: &str,
+    dst: IpAddr,
+    max_hops: u8,
+) -> NetResult<Vec<Option<IpAddr>>> {
+    let family = match dst {
+        IpAddr::V4(_) => "-4",
+        IpAddr::V6(_) => "-6",
+    };
+    let dst_str = dst.to_string();
+
+    let mut hops = vec![];
+    for ttl in 1..=max_hops {
+        let ttl_str = ttl.to_string();
+        let output = exec_in_ns(
+            node,
+            "ping",
+            &[family, "-n", "-c", "1", "-W", "1", "-t", &ttl_str, &dst_str],
+        )?;
+        if output.status.success() {
+            debug!(%node, dst = %dst_str, ttl, "reached");
+            hops.push(Some(dst));
+            return Ok(hops);
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let hop = expired_at(&stdout);
+        debug!(%node, dst = %dst_str, ttl, ?hop, "probe");
+        hops.push(hop);
+    }
+    Ok(hops)
+}
+
+/// Whether the reverse path crosses the same nodes as the forward path,
+/// in the opposite order. Both end with the node they were traced to,
+/// which is left out of the comparison.
+///
+/// Paths with hops that did not answer are never symmetric.
+pub fn is_symmetric(
+    forward: &[Option<&str>],
+    reverse: &[Option<&str>],
+) -> bool {
+    let transit = |path: &[Option<&str>]| -> Option<Vec<&str>> {
+        let (_dst, transit) = path.split_last()?;
+        transit.iter().copied().collect()
+    };
+    match (transit(forward), transit(reverse)) {
+        (Some(forward), Some(mut reverse)) => {
+            reverse.reverse();
+            forward == reverse
+        }
+        _ => false,
+    }
+}
+
+/// A route of the main routing table of a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub prefix: IpNetwork,
+    // Gateways of the route, several for ECMP. Empty for routes to
+    // directly connected networks.
+    pub nexthops: Vec<IpAddr>,
+    pub devices: Vec<String>,
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.prefix)?;
+        for nexthop in &self.nexthops {
+            write!(f, " via {nexthop}")?;
+        }
+        for device in &self.devices {
+            write!(f, " dev {device}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The route of `node` for exactly `prefix`, if any.
+pub fn lookup_route(node: &str, prefix: IpNetwork) -> NetResult<Option<Route>> {
+    let family = match prefix {
+        IpNetwork::V4(_) => "-4",
+        IpNetwork::V6(_) => "-6",
+    };
+    let prefix_str = prefix.to_string();
+    let output = exec_in_ns(
+        node,
+        "ip",
+        &[family, "route", "show", "exact", &prefix_str],
+    )?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_route(prefix, &stdout))
+}
+
+/// Waits up to `timeout` for `node` to have a route for exactly `prefix`,
+/// through `via` if set, and returns it.
+pub fn wait_for_route(
+    node: &str,
+    prefix: IpNetwork,
+    via: Option<IpAddr>,
+    timeout: Duration,
+) -> NetResult<Route> {
+    let start = Instant::now();
+    loop {
+        let route = lookup_route(node, prefix)?;
+        debug!(%node, %prefix, ?route, "route lookup");
+        let last_seen = match route {
+            Some(route)
+                if via.is_none_or(|via| route.nexthops.contains(&via)) =>
+            {
+                return Ok(route);
+            }
+            Some(route) => format!("found {route}"),
+            None => "no route found".to_string(),
+        };
+        if start.elapsed() >= timeout {
+            let expected = match via {
+                Some(via) => format!("{prefix} via {via}"),
+                None => prefix.to_string(),
+            };
+            return Err(NetError::BasicError(format!(
+                "{node} has no route for {expected} after {timeout:?}, \
+                    {last_seen}"
+            )));
+        }
+        std::thread::sleep(ROUTE_POLL_INTERVAL);
+    }
+}
+
+// Parses the output of `ip route show exact`, e.g.
+// "10.0.2.0/24 via 10.0.1.2 dev eth0 proto ospf metric 20", or for ECMP
+// routes:
+//
+// 10.0.2.0/24 proto ospf metric 20
+//         nexthop via 10.0.1.2 dev eth0 weight 1
+//         nexthop via 10.0.3.2 dev eth1 weight 1
+fn parse_route(prefix: IpNetwork, output: &str) -> Option<Route> {
+    if output.trim().is_empty() {
+        return None;
+    }
+
+    let mut route = Route {
+        prefix,
+        nexthops: vec![],
+        devices: vec![],
+    };
+    let mut words = output.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "via" => {
+                // IPv4 routes can have IPv6 gateways, "via inet6 fe80::1".
+                let mut gateway = words.next();
+                if matches!(gateway, Some("inet" | "inet6")) {
+                    gateway = words.next();
+                }
+                if let Some(gateway) = gateway.and_then(|gw| gw.parse().ok()) {
+                    route.nexthops.push(gateway);
+                }
+            }
+            "dev" => {
+                if let Some(device) = words.next()
+                    && !route.devices.iter().any(|dev| dev == device)
+                {
+                    route.devices.push(device.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(route)
+}
+
+// Address reporting the TTL expired in ping's output, e.g.
+// "From 10.0.1.1 icmp_seq=1 Time to live exceeded".
+fn expired_at(output: &str) -> Option<IpAddr> {
+    output.lines().find_map(|line| {
+        let (_, rest) = line.split_once("From ")?;
+        let address = rest.split_whitespace().next()?.trim_end_matches(':');
+        // Link-local IPv6 addresses come with their interface.
+        let address = address.split('%').next()?;
+        address.parse().ok()
+    })
+}
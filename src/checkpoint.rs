@@ -0,0 +1,154 @@
+use std::fs;
+use std::process::Command;
+
+use crate::error::NetError;
+use crate::state::ProcessRecord;
+use crate::{NetResult, run_dir};
+
+/// Where checkpoints are kept on the host, across runs, for every
+/// iteration of an experiment to restore them.
+pub(crate) fn checkpoint_root() -> String {
+    format!("{}/checkpoints", run_dir())
+}
+
+// Daemons of a checkpoint, in the order they were started, which is the
+// one they are restored in.
+const DAEMONS_FILE: &str = "daemons";
+
+// TCP sessions (BGP...) are kept established, unix sockets connected to
+// other daemons are left for them to reconnect.
+const CRIU_ARGS: &[&str] = &[
+    "--shell-job",
+    "--tcp-established",
+    "--ext-unix-sk",
+    "--file-locks",
+];
+
+/// Directory of the checkpoint `name` of `node`.
+pub(crate) fn checkpoint_dir(node: &str, name: &str) -> String {
+    format!("{}/{node}/{name}", checkpoint_root())
+}
+
+/// Whether the process runs in a mount namespace other than ours, i.e. was
+/// started in the router's namespaces (FRR, the SSH server...) rather than
+/// forked by netgen. Only those are checkpointed.
+pub(crate) fn checkpointable(record: &ProcessRecord) -> bool {
+    let mnt_ns = |pid: &str| fs::read_link(format!("/proc/{pid}/ns/mnt")).ok();
+    let theirs = mnt_ns(&record.pid.to_string());
+    theirs.is_some() && theirs != mnt_ns("self")
+}
+
+/// Dumps the processes with CRIU into `dir`, one images directory per
+/// daemon, replacing any previous checkpoint there. CRIU kills them once
+/// dumped, unless `leave_running` is set.
+///
+/// CRIU is run within the namespaces of each process, so that only the
+/// process is dumped, not the router's namespaces.
+pub(crate) fn dump(
+    records: &[&ProcessRecord],
+    dir: &str,
+    leave_running: bool,
+) -> NetResult<()> {
+    let _ = fs::remove_dir_all(dir);
+    for record in records {
+        let what = format!("{} of {}", record.name, record.node);
+        let images = format!("{dir}/{}", record.name);
+        fs::create_dir_all(&images).map_err(|err| {
+            NetError::BasicError(format!("Unable to create {images}: {err:?}"))
+        })?;
+        let tree = ns_pid(record.pid).ok_or_else(|| {
+            NetError::BasicError(format!("{what} is no longer running"))
+        })?;
+
+        let (target, tree) = (record.pid.to_string(), tree.to_string());
+        let mut args = vec![
+            "--target",
+            &target,
+            "--mount",
+            "--net",
+            "--pid",
+            "criu",
+            "dump",
+            "--tree",
+            &tree,
+            "--images-dir",
+            &images,
+        ];
+        args.extend(CRIU_ARGS);
+        if leave_running {
+            args.push("--leave-running");
+        }
+        criu("nsenter", &args, &format!("checkpoint {what}"))?;
+    }
+
+    let daemons: Vec<&str> =
+        records.iter().map(|record| record.name.as_str()).collect();
+    let path = format!("{dir}/{DAEMONS_FILE}");
+    fs::write(&path, daemons.join("\n") + "\n").map_err(|err| {
+        NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+    })
+}
+
+/// Daemons of the checkpoint in `dir`, in the order to restore them in.
+pub(crate) fn daemons(dir: &str) -> NetResult<Vec<String>> {
+    let path = format!("{dir}/{DAEMONS_FILE}");
+    let daemons = fs::read_to_string(&path).map_err(|err| {
+        NetError::BasicError(format!("No checkpoint in {dir}: {err:?}"))
+    })?;
+    Ok(daemons
+        .lines()
+        .map(str::trim)
+        .filter(|daemon| !daemon.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Restores `daemon` from the checkpoint in `dir`, returning its PID as
+/// seen through `/proc`. Has to be called from within the router's
+/// namespaces, the daemon being restored in the ones CRIU runs in.
+pub(crate) fn restore(dir: &str, daemon: &str) -> NetResult<i32> {
+    let images = format!("{dir}/{daemon}");
+    let pidfile = format!("{images}/restored.pid");
+    let mut args = vec![
+        "restore",
+        "--images-dir",
+        &images,
+        "--restore-detached",
+        "--pidfile",
+        &pidfile,
+    ];
+    args.extend(CRIU_ARGS);
+    criu("criu", &args, &format!("restore {daemon}"))?;
+
+    fs::read_to_string(&pidfile)
+        .ok()
+        .and_then(|pid| pid.trim().parse().ok())
+        .ok_or_else(|| {
+            NetError::BasicError(format!(
+                "Unable to read the PID of the restored {daemon}"
+            ))
+        })
+}
+
+// PID of the process within its own PID namespace, the last one listed
+// by NSpid.
+fn ns_pid(pid: i32) -> Option<i32> {
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let pids = status
+        .lines()
+        .find_map(|line| line.strip_prefix("NSpid:"))?;
+    pids.split_whitespace().last()?.parse().ok()
+}
+
+fn criu(cmd: &str, args: &[&str], what: &str) -> NetResult<()> {
+    let output = Command::new(cmd).args(args).output().map_err(|err| {
+        NetError::BasicError(format!("Unable to run {cmd} to {what}: {err:?}"))
+    })?;
+    if !output.status.success() {
+        return Err(NetError::BasicError(format!(
+            "Unable to {what}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use ipnetwork::IpNetwork;
+use tracing::{debug, debug_span};
+
+use crate::cancel::CancelToken;
+use crate::csv;
+use crate::error::NetError;
+use crate::injector::{mrt_dump, prefix_range};
+use crate::report::TestCase;
+use crate::{NetResult, devices_ns_dir, exec_in_ns};
+
+// How often the routing table of the device under test is read while
+// waiting for it to catch up.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+// Routes of the batch being sent, in the injector's directory.
+const BATCH_FILE: &str = "churn-batch.mrt";
+
+// ==== struct Churn ====
+
+/// Scenario step announcing and withdrawing prefixes through a route
+/// injector at a steady rate, measuring how long the device under test
+/// takes to install and remove them:
+///
+/// ```yaml
+/// scenario:
+///   - churn:
+///       injector: inj1
+///       dut: rt1
+///       prefixes: { start: 200.0.0.0/24, count: 1000 }
+///       rate: 100       # updates per second
+///       duration: 60
+///       timeout: 5      # per batch, 5 by default
+/// ```
+///
+/// The prefixes are announced in batches of a tenth of `rate`, then
+/// withdrawn, over and over until `duration` expires, each batch in a
+/// single call to the injector, which must be an `injector` router of the
+/// topology. An update is processed once the prefix appears in (or
+/// disappears from) the kernel routing table of `dut`, timed from the end
+/// of its batch's call. Batches wait for the previous one to be processed,
+/// so a device falling behind lowers the rate.
+#[derive(Debug, Clone)]
+pub struct Churn {
+    pub injector: String,
+    pub dut: String,
+    pub start: IpNetwork,
+    pub count: u64,
+    pub rate: u32,
+    pub duration: Duration,
+    pub timeout: Duration,
+}
+
+// ==== struct ChurnStats ====
+
+/// Processing latencies measured by a `Churn` step.
+#[derive(Debug, Clone, Default)]
+pub struct ChurnStats {
+    // Latency of every update processed, sorted.
+    pub latencies: Vec<Duration>,
+    // Updates the device did not process within the timeout.
+    pub lost: usize,
+}
+
+impl Churn {
+    pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Plays the churn against the running topology, until its duration
+    /// elapsed or `cancel` is cancelled.
+    pub fn run(&self, cancel: &CancelToken) -> NetResult<ChurnStats> {
+        let churn_span =
+            debug_span!("churn", injector = %self.injector, dut = %self.dut);
+        let _span_guard = churn_span.enter();
+
+        let prefixes: Vec<IpNetwork> =
+            prefix_range(self.start, self.count).collect();
+        if prefixes.is_empty() {
+            return Err(NetError::BasicError(
+                "Churn has no prefixes to announce".to_string(),
+            ));
+        }
+        let batch_size = (self.rate as usize / 10).max(1);
+        let batch_period = Duration::from_secs_f64(
+            batch_size as f64 / f64::from(self.rate.max(1)),
+        );
+
+        let mut stats = ChurnStats::default();
+        let start = Instant::now();
+        let mut announce = true;
+        'churn: loop {
+            for batch in prefixes.chunks(batch_size) {
+                if start.elapsed() >= self.duration || cancel.is_cancelled() {
+                    break 'churn;
+                }
+                let started = Instant::now();
+                self.send(batch, announce)?;
+                // Latencies count from the time the whole batch was handed
+                // to the injector, not from when gobgp started.
+                let sent = Instant::now();
+                self.wait_processed(batch, announce, sent, &mut stats)?;
+                debug!(size = batch.len(), announce,
+                    send = ?sent - started, elapsed = ?started.elapsed(),
+                    "batch processed");
+                let elapsed = started.elapsed();
+                if let Some(idle) = batch_period.checked_sub(elapsed) {
+                    std::thread::sleep(idle);
+                }
+            }
+            announce = !announce;
+        }
+
+        // Leave the device as it was found, whichever pass got interrupted.
+        let _ = self.send(&prefixes, false);
+        stats.latencies.sort();
+        Ok(stats)
+    }
+
+    // Announces or withdraws the batch in a single gobgp call, injecting it
+    // as an MRT dump. Withdrawn prefixes are injected anew with the
+    // community the injector's export policy rejects, see `mrt_dump`.
+    fn send(&self, batch: &[IpNetwork], announce: bool) -> NetResult<()> {
+        let action = if announce { "announce" } else { "withdraw" };
+        let path =
+            format!("{}/{}/{BATCH_FILE}", devices_ns_dir(), self.injector);
+        let dump = mrt_dump(0, None, batch.iter().copied(), !announce);
+        fs::write(&path, dump).map_err(|err| {
+            NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+        })?;
+
+        let output = exec_in_ns(
+            &self.injector,
+            "gobgp",
+            &["mrt", "inject", "global", &path],
+        )?;
+        if !output.status.success() {
+            return Err(NetError::BasicError(format!(
+                "Unable to {action} prefixes on {}: {}",
+                self.injector,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    // Polls the routing table of the device under test until every update
+    // of the batch shows up, or the timeout expires.
+    fn wait_processed(
+        &self,
+        batch: &[IpNetwork],
+        announce: bool,
+        sent: Instant,
+        stats: &mut ChurnStats,
+    ) -> NetResult<()> {
+        let mut pending: BTreeSet<IpNetwork> = batch.iter().copied().collect();
+        while !pending.is_empty() && sent.elapsed() < self.timeout {
+            let routes = self.routes()?;
+            let now = sent.elapsed();
+            let before = pending.len();
+            pending.retain(|prefix| routes.contains(prefix) != announce);
+            stats
+                .latencies
+                .extend(std::iter::repeat_n(now, before - pending.len()));
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        stats.lost += pending.len();
+        Ok(())
+    }
+
+    // Prefixes in the main routing table of the device under test.
+    fn routes(&self) -> NetResult<BTreeSet<IpNetwork>> {
+        let family = match self.start {
+            IpNetwork::V4(_) => "-4",
+            IpNetwork::V6(_) => "-6",
+        };
+        let output = exec_in_ns(&self.dut, "ip", &[family, "route", "show"])?;
+
+        // e.g. "200.0.0.0/24 via 10.0.0.2 dev eth0 proto bgp metric 20", host
+        // routes coming without their prefix length.
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_whitespace().next()?.parse().ok())
+            .collect())
+    }
+
+    /// Name of the step in reports.
+    pub fn describe(&self) -> String {
+        format!("churn {} -> {}", self.injector, self.dut)
+    }
+}
+
+impl ChurnStats {
+    /// Latency below which `percent` of the processed updates fall.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let last = self.latencies.len().checked_sub(1)?;
+        let index = (last as f64 * percent / 100.0).round() as usize;
+        self.latencies.get(index.min(last)).copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.latencies.len()).ok()?;
+        (count > 0).then(|| self.latencies.iter().sum::<Duration>() / count)
+    }
+
+    /// One test case for the whole step, failing if any update was lost.
+    pub fn to_case(&self, churn: &Churn, duration: Duration) -> TestCase {
+        TestCase {
+            name: format!("{} ({self})", churn.describe()),
+            duration,
+            failure: (self.lost > 0).then(|| {
+                format!(
+                    "{} updates not processed within {:?}",
+                    self.lost, churn.timeout
+                )
+            }),
+            node: Some(churn.dut.clone()),
+        }
+    }
+}
+
+impl fmt::Display for ChurnStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} updates", self.latencies.len() + self.lost)?;
+        let latencies = (
+            self.latencies.first(),
+            self.mean(),
+            self.percentile(99.0),
+            self.latencies.last(),
+        );
+        if let (Some(min), Some(mean), Some(p99), Some(max)) = latencies {
+            write!(
+                f,
+                ", latency min {min:?} avg {mean:?} p99 {p99:?} max {max:?}"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// ==== struct ChurnRun ====
+
+/// A `Churn` step of a scenario, along with what it measured.
+#[derive(Debug, Clone)]
+pub struct ChurnRun {
+    // Position of the step in the scenario, from 0.
+    pub step: usize,
+    pub churn: Churn,
+    pub stats: ChurnStats,
+    pub duration: Duration,
+}
+
+/// Columns of `to_csv`, one row per churn step: its position in the
+/// scenario, what it churned, the updates sent, those lost, and the
+/// processing latencies of the others in microseconds.
+pub const CSV_COLUMNS: &[&str] = &[
+    "step",
+    "injector",
+    "dut",
+    "rate",
+    "duration_s",
+    "updates",
+    "lost",
+    "min_us",
+    "mean_us",
+    "p50_us",
+    "p99_us",
+    "max_us",
+];
+
+impl ChurnRun {
+    pub fn to_case(&self) -> TestCase {
+        self.stats.to_case(&self.churn, self.duration)
+    }
+}
+
+/// The churn steps as CSV rows, see `CSV_COLUMNS`.
+pub fn to_csv(runs: &[ChurnRun]) -> String {
+    let micros = |latency: Option<Duration>| {
+        latency.map_or(String::new(), |latency| latency.as_micros().to_string())
+    };
+    runs.iter()
+        .map(|run| {
+            let stats = &run.stats;
+            csv::line(&[
+                run.step.to_string(),
+                run.churn.injector.clone(),
+                run.churn.dut.clone(),
+                run.churn.rate.to_string(),
+                format!("{:.3}", run.duration.as_secs_f64()),
+                (stats.latencies.len() + stats.lost).to_string(),
+                stats.lost.to_string(),
+                micros(stats.latencies.first().copied()),
+                micros(stats.mean()),
+                micros(stats.percentile(50.0)),
+                micros(stats.percentile(99.0)),
+                micros(stats.latencies.last().copied()),
+            ])
+        })
+        .collect()
+}
@@ -0,0 +1,161 @@
+//! Rewriting of a topology file to add a copy of one of its routers.
+
+use yaml_rust2::yaml::{Hash, Yaml};
+use yaml_rust2::{YamlEmitter, YamlLoader};
+
+use crate::NetResult;
+use crate::error::{ConfigError, NetError};
+use crate::link::LinkEndpoint;
+
+/// A router to add to a topology file, copied from `original`.
+pub(crate) struct RouterCopy<'a> {
+    pub(crate) original: &'a str,
+    pub(crate) name: &'a str,
+    // Interfaces of the copy and their addresses, replacing the original's.
+    pub(crate) interfaces: Vec<(String, Vec<String>)>,
+    // The copy's interface, and the end it is linked to.
+    pub(crate) links: Vec<(String, LinkEndpoint)>,
+    // Values of the original, e.g. its addresses, and those of the copy.
+    pub(crate) replacements: Vec<(String, String)>,
+}
+
+/// Adds `copy` to the (rendered) topology file `source`.
+///
+/// The copy keeps the configuration of the original, strings mentioning
+/// its name or addresses being rewritten for the copy. Its exposed ports
+/// are dropped, as they would clash on the host.
+pub(crate) fn add_router_copy(
+    source: &str,
+    copy: &RouterCopy,
+) -> NetResult<String> {
+    let mut docs = YamlLoader::load_from_str(source)
+        .map_err(|err| NetError::ConfigError(ConfigError::YamlSyntax(err)))?;
+    let original = docs
+        .iter()
+        .find_map(|doc| doc["routers"][copy.original].as_hash())
+        .ok_or_else(|| ConfigError::UnknownNode(copy.original.to_string()))?;
+
+    let mut router = Hash::new();
+    for (key, value) in original {
+        match key.as_str() {
+            Some("interfaces" | "expose") => {}
+            _ => {
+                router.insert(
+                    retemplate(key, &copy.replacements),
+                    retemplate(value, &copy.replacements),
+                );
+            }
+        }
+    }
+    let interfaces = copy
+        .interfaces
+        .iter()
+        .map(|(iface, addresses)| {
+            let addresses = addresses
+                .iter()
+                .map(|addr| Yaml::String(addr.clone()))
+                .collect();
+            (key(iface), Yaml::Array(addresses))
+        })
+        .collect();
+    router.insert(key("interfaces"), Yaml::Hash(interfaces));
+
+    // The copy goes along its original.
+    let Some(Yaml::Hash(doc)) = docs
+        .iter_mut()
+        .find(|doc| doc["routers"][copy.original].as_hash().is_some())
+    else {
+        return Err(ConfigError::UnknownNode(copy.original.to_string()).into());
+    };
+    if let Some(Yaml::Hash(routers)) = doc.get_mut(&key("routers")) {
+        routers.insert(key(copy.name), Yaml::Hash(router));
+    }
+
+    let links = copy.links.iter().map(|(iface, end)| {
+        Yaml::Hash(Hash::from_iter([
+            (key("src-device"), key(copy.name)),
+            (key("src-iface"), key(iface)),
+            (key("dst-device"), key(end.node.as_str())),
+            (key("dst-iface"), key(end.iface.as_str())),
+        ]))
+    });
+    match doc.get_mut(&key("links")) {
+        Some(Yaml::Array(existing)) => existing.extend(links),
+        _ => {
+            doc.insert(key("links"), Yaml::Array(links.collect()));
+        }
+    }
+
+    let mut rendered = String::new();
+    for doc in &docs {
+        let mut emitter = YamlEmitter::new(&mut rendered);
+        emitter.multiline_strings(true);
+        emitter.dump(doc).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to write the topology with {}: {err:?}",
+                copy.name
+            ))
+        })?;
+        rendered.push('\n');
+    }
+    Ok(rendered)
+}
+
+pub(crate) fn key(value: &str) -> Yaml {
+    Yaml::String(value.to_string())
+}
+
+// Rewrites the strings in `yaml`, keys included.
+fn retemplate(yaml: &Yaml, replacements: &[(String, String)]) -> Yaml {
+    match yaml {
+        Yaml::String(value) => Yaml::String(
+            replacements
+                .iter()
+                .fold(value.clone(), |value, (from, to)| {
+                    replace_word(&value, from, to)
+                }),
+        ),
+        Yaml::Array(values) => Yaml::Array(
+            values
+                .iter()
+                .map(|value| retemplate(value, replacements))
+                .collect(),
+        ),
+        Yaml::Hash(hash) => Yaml::Hash(
+            hash.iter()
+                .map(|(key, value)| {
+                    (
+                        retemplate(key, replacements),
+                        retemplate(value, replacements),
+                    )
+                })
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+// Replaces the occurrences of `from` which are not part of a longer name or
+// address: "rt1" in "rt1.conf", but neither in "rt10" nor in "rt1-b".
+fn replace_word(value: &str, from: &str, to: &str) -> String {
+    if from.is_empty() {
+        return value.to_string();
+    }
+
+    let part_of_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let mut replaced = String::with_capacity(value.len());
+    let mut copied = 0;
+    for (start, _) in value.match_indices(from) {
+        let end = start + from.len();
+        let before = value[..start].chars().next_back();
+        let after = value[end..].chars().next();
+        if before.is_some_and(part_of_word) || after.is_some_and(part_of_word) {
+            continue;
+        }
+        replaced.push_str(&value[copied..start]);
+        replaced.push_str(to);
+        copied = end;
+    }
+    replaced.push_str(&value[copied..]);
+    replaced
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use nix::sys::statfs::{OVERLAYFS_SUPER_MAGIC, TMPFS_MAGIC, statfs};
+use nix::sys::statvfs::{FsFlags, statvfs};
+use nix::unistd::{AccessFlags, access};
+use tracing::debug;
+
+use crate::DEFAULT_RUN_DIR;
+
+// Capabilities netgen cannot do without, as (bit, name).
+const REQUIRED_CAPS: &[(u32, &str)] =
+    &[(21, "CAP_SYS_ADMIN"), (12, "CAP_NET_ADMIN")];
+
+// Directories the runtime files go under when /tmp is an overlay, tried in
+// order, each only when on a tmpfs.
+const TMPFS_RUN_DIRS: &[&str] = &["/run", "/dev/shm"];
+
+// ==== struct Container ====
+
+/// The container netgen runs in, e.g. the Docker container of a CI job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    // "docker", "podman", "kubernetes", "lxc", or what the `container`
+    // environment variable says.
+    pub runtime: String,
+    // /tmp is on the container's overlay filesystem rather than a tmpfs.
+    pub overlay_tmp: bool,
+}
+
+/// The container netgen runs in, None on a host.
+pub fn detect() -> Option<&'static Container> {
+    static DETECTED: OnceLock<Option<Container>> = OnceLock::new();
+    DETECTED
+        .get_or_init(|| {
+            let container = runtime().map(|runtime| Container {
+                runtime,
+                overlay_tmp: statfs("/tmp").is_ok_and(|stat| {
+                    stat.filesystem_type() == OVERLAYFS_SUPER_MAGIC
+                }),
+            });
+            debug!(?container, "container detection");
+            container
+        })
+        .as_ref()
+}
+
+fn runtime() -> Option<String> {
+    if Path::new("/.dockerenv").exists() {
+        return Some("docker".to_string());
+    }
+    if Path::new("/run/.containerenv").exists() {
+        return Some("podman".to_string());
+    }
+    if std::env::var_os("KUBERNETES_SERVICE_HOST").is_some() {
+        return Some("kubernetes".to_string());
+    }
+    // Set by systemd-nspawn, LXC and podman among others.
+    if let Ok(runtime) = std::env::var("container")
+        && !runtime.is_empty()
+    {
+        return Some(runtime);
+    }
+    let cgroup = fs::read_to_string("/proc/1/cgroup").unwrap_or_default();
+    ["docker", "kubepods", "lxc"]
+        .into_iter()
+        .find(|runtime| cgroup.contains(runtime))
+        .map(|runtime| match runtime {
+            "kubepods" => "kubernetes".to_string(),
+            runtime => runtime.to_string(),
+        })
+}
+
+/// Where the namespaces and runtime files go by default: DEFAULT_RUN_DIR,
+/// but on a tmpfs when /tmp is the overlay of a container, so that they
+/// neither land in its writable layer nor outlive a restart of it.
+pub(crate) fn run_dir() -> String {
+    if detect().is_some_and(|container| container.overlay_tmp)
+        && let Some(dir) = TMPFS_RUN_DIRS.iter().find(|dir| {
+            statfs(**dir)
+                .is_ok_and(|stat| stat.filesystem_type() == TMPFS_MAGIC)
+                && access(**dir, AccessFlags::W_OK).is_ok()
+        })
+    {
+        return format!("{dir}/netgen-rs");
+    }
+    DEFAULT_RUN_DIR.to_string()
+}
+
+// ==== struct Problem ====
+
+/// Something of the container which keeps topologies from coming up, or
+/// from coming up entirely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    // Whether nothing can be brought up at all.
+    pub fatal: bool,
+    pub reason: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+/// What `container` lacks for netgen to run topologies in it, e.g.
+/// capabilities it was not granted.
+pub fn preflight(container: &Container) -> Vec<Problem> {
+    let mut problems = vec![];
+    let effective = effective_caps();
+    for (bit, name) in REQUIRED_CAPS {
+        if effective.is_some_and(|caps| caps & (1 << bit) == 0) {
+            problems.push(Problem {
+                fatal: true,
+                reason: format!(
+                    "the {} container lacks {name}, run it with \
+                        --privileged or --cap-add {}",
+                    container.runtime,
+                    name.trim_start_matches("CAP_")
+                ),
+            });
+        }
+    }
+    if statvfs("/proc/sys")
+        .is_ok_and(|stat| stat.flags().contains(FsFlags::ST_RDONLY))
+    {
+        problems.push(Problem {
+            fatal: false,
+            reason: "/proc/sys is read-only, sysctls such as forwarding \
+                cannot be set in the routers, run the container with \
+                --privileged"
+                .to_string(),
+        });
+    }
+    problems
+}
+
+// The effective capabilities of the process, from /proc/self/status.
+fn effective_caps() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let caps = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))?
+        .trim();
+    u64::from_str_radix(caps, 16).ok()
+}
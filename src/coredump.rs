@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use tracing::debug;
+
+use crate::devices_ns_dir;
+use crate::state::ProcessRecord;
+
+// Clock ticks per second in /proc, fixed by the kernel ABI.
+pub(crate) const USER_HZ: u64 = 100;
+
+/// Directory the crash artifacts of the daemons of `node` are collected
+/// into.
+pub(crate) fn cores_dir(node: &str) -> PathBuf {
+    PathBuf::from(format!("{}/{node}/cores", devices_ns_dir()))
+}
+
+/// Lets the calling process, and the daemons it execs, dump cores up to
+/// the hard limit, the soft one often being 0.
+pub(crate) fn enable_cores() {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    unsafe {
+        if libc::getrlimit(libc::RLIMIT_CORE, &mut limit) == 0 {
+            limit.rlim_cur = limit.rlim_max;
+            libc::setrlimit(libc::RLIMIT_CORE, &limit);
+        }
+    }
+}
+
+/// Collects what systemd-coredump captured of `process` crashing: its
+/// report, backtrace included, to `<name>.<pid>.txt` and its core to
+/// `<name>.<pid>.core` in the node's cores directory.
+///
+/// `kernel.core_pattern` is global to the host, so rather than setting it
+/// the cores are looked up with coredumpctl, which reads them back from the
+/// journal without ptracing anything. Returns the path of the report, or
+/// None if the process did not crash, or its core was not captured.
+pub(crate) fn collect(process: &ProcessRecord) -> Option<PathBuf> {
+    let dir = cores_dir(&process.node);
+    let base = format!("{}.{}", process.name, process.pid);
+    let report = dir.join(format!("{base}.txt"));
+    if report.exists() {
+        return Some(report);
+    }
+
+    // Only crashes since the process started, in case its PID was used by
+    // another one before.
+    let since = format!("--since=@{}", process_start_epoch(process)?);
+    let pid = process.pid.to_string();
+    let info = Command::new("coredumpctl")
+        .args(["--no-pager", "--quiet", &since, "info", &pid])
+        .output()
+        .inspect_err(|err| debug!(?err, "coredumpctl unavailable"))
+        .ok()?;
+    if !info.status.success() || info.stdout.is_empty() {
+        return None;
+    }
+
+    fs::create_dir_all(&dir).ok()?;
+    fs::write(&report, &info.stdout).ok()?;
+    let core = dir.join(format!("{base}.core"));
+    let output = format!("--output={}", core.display());
+    // The core itself may be missing, e.g. when above the size limits of
+    // systemd-coredump, the report still has the backtrace.
+    let _ = Command::new("coredumpctl")
+        .args(["--no-pager", "--quiet", &since, &output, "dump", &pid])
+        .output();
+    debug!(node = %process.node, name = %process.name, pid = process.pid,
+        report = %report.display(), "crash collected");
+    Some(report)
+}
+
+// When `process` started, in seconds since the epoch.
+fn process_start_epoch(process: &ProcessRecord) -> Option<u64> {
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+    let boot_time: u64 = stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(boot_time + process.start_time / USER_HZ)
+}
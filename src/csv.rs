@@ -0,0 +1,59 @@
+//! CSV export of the measurements, for analysis in pandas, polars or a
+//! spreadsheet.
+//!
+//! Each measurement has its own columns, documented with the `CSV_COLUMNS`
+//! of its module. Columns are only ever added at the end, so that scripts
+//! reading them by position keep working. Durations are in microseconds,
+//! rates in units per second, and values which do not apply are left empty.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::NetResult;
+use crate::error::NetError;
+
+/// A CSV line of `fields`, quoted where needed as RFC 4180 has it.
+pub fn line<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let field = field.as_ref();
+            match field.contains([',', '"', '\n', '\r']) {
+                true => format!("\"{}\"", field.replace('"', "\"\"")),
+                false => field.to_string(),
+            }
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+/// Writes `rows`, as rendered by the `to_csv` functions, to `path` under a
+/// header of `columns`. With `append`, the rows are added to those of an
+/// existing file instead, the header only being written to an empty one.
+pub fn write(
+    path: &str,
+    columns: &[&str],
+    rows: &str,
+    append: bool,
+) -> NetResult<()> {
+    let write_err = |err: std::io::Error| {
+        NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+    };
+    let empty = !append
+        || fs::metadata(Path::new(path))
+            .map(|meta| meta.len() == 0)
+            .unwrap_or(true);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .map_err(write_err)?;
+    if empty {
+        file.write_all(line(columns).as_bytes())
+            .map_err(write_err)?;
+    }
+    file.write_all(rows.as_bytes()).map_err(write_err)
+}
@@ -0,0 +1,280 @@
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+use ipnetwork::IpNetwork;
+
+use crate::link::Link;
+use crate::node::Node;
+use crate::plan::{Action, RESET, link_name};
+use crate::report::json_string;
+use crate::topology::Topology;
+
+// ==== struct Difference ====
+
+/// A node or link added, changed or removed from one topology to the other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    // Create when added, Update when changed and Delete when removed.
+    pub action: Action,
+    // "router", "switch" or "link".
+    pub kind: String,
+    pub name: String,
+    // What changed, e.g. "+ address 10.0.0.1/24 on eth0", for changes only.
+    pub details: Vec<String>,
+}
+
+// ==== struct TopologyDiff ====
+
+/// What differs between two topologies, as parsed rather than as written:
+/// nodes by name, then links.
+#[derive(Debug, Clone, Default)]
+pub struct TopologyDiff {
+    pub differences: Vec<Difference>,
+}
+
+impl TopologyDiff {
+    /// What it takes to go from `old` to `new`. Addresses are compared
+    /// once allocated, those from pools included.
+    pub fn between(old: &Topology, new: &Topology) -> Self {
+        let mut diff = Self::default();
+        let names: BTreeSet<&str> =
+            old.nodes().chain(new.nodes()).map(Node::name).collect();
+        for name in names {
+            match (old.get_node(name), new.get_node(name)) {
+                (None, Some(node)) => {
+                    diff.push(Action::Create, kind(node), name, vec![])
+                }
+                (Some(node), None) => {
+                    diff.push(Action::Delete, kind(node), name, vec![])
+                }
+                (Some(old_node), Some(new_node)) if old_node != new_node => {
+                    let details = node_details(old_node, new_node);
+                    if !details.is_empty() {
+                        diff.push(Action::Update, kind(new_node), name, details)
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for link in new.links() {
+            let name = link_name(link);
+            match old.links().iter().find(|old| link_name(old) == name) {
+                None => diff.push(Action::Create, "link", &name, vec![]),
+                Some(old_link) => {
+                    let details = link_details(old_link, link);
+                    if !details.is_empty() {
+                        diff.push(Action::Update, "link", &name, details);
+                    }
+                }
+            }
+        }
+        for link in old.links() {
+            let name = link_name(link);
+            if !new.links().iter().any(|new| link_name(new) == name) {
+                diff.push(Action::Delete, "link", &name, vec![]);
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    pub fn count(&self, action: Action) -> usize {
+        self.differences
+            .iter()
+            .filter(|difference| difference.action == action)
+            .count()
+    }
+
+    fn push(
+        &mut self,
+        action: Action,
+        kind: &str,
+        name: &str,
+        details: Vec<String>,
+    ) {
+        self.differences.push(Difference {
+            action,
+            kind: kind.to_string(),
+            name: name.to_string(),
+            details,
+        });
+    }
+
+    /// Renders the differences followed by a summary, colored when `color`
+    /// is set.
+    pub fn render(&self, color: bool) -> String {
+        let mut out = String::new();
+        if self.is_empty() {
+            let _ = writeln!(out, "No differences.");
+            return out;
+        }
+
+        for difference in &self.differences {
+            let (start, end) = match color {
+                true => (difference.action.color(), RESET),
+                false => ("", ""),
+            };
+            let _ = writeln!(
+                out,
+                "{start}{} {} {}{end}",
+                difference.action.symbol(),
+                difference.kind,
+                difference.name
+            );
+            for detail in &difference.details {
+                let _ = writeln!(out, "    {detail}");
+            }
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "{} added, {} changed, {} removed.",
+            self.count(Action::Create),
+            self.count(Action::Update),
+            self.count(Action::Delete)
+        );
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let differences: Vec<String> = self
+            .differences
+            .iter()
+            .map(|difference| {
+                let details: Vec<String> = difference
+                    .details
+                    .iter()
+                    .map(|detail| json_string(detail))
+                    .collect();
+                format!(
+                    "    {{\"change\": \"{}\", \"kind\": {}, \"name\": {}, \
+                        \"details\": [{}]}}",
+                    change(difference.action),
+                    json_string(&difference.kind),
+                    json_string(&difference.name),
+                    details.join(", ")
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"differences\": [\n{}\n  ]\n}}\n",
+            differences.join(",\n")
+        )
+    }
+}
+
+fn kind(node: &Node) -> &'static str {
+    match node {
+        Node::Router(_) => "router",
+        Node::Switch(_) => "switch",
+    }
+}
+
+fn change(action: Action) -> &'static str {
+    match action {
+        Action::Create => "added",
+        Action::Update => "changed",
+        Action::Delete => "removed",
+    }
+}
+
+// What changed on a node: its interfaces and their addresses, then the rest
+// of its configuration as a whole.
+fn node_details(old: &Node, new: &Node) -> Vec<String> {
+    let mut details = vec![];
+    if kind(old) != kind(new) {
+        details.push(format!("~ {} turned into a {}", kind(old), kind(new)));
+    }
+
+    let ifaces = |node: &Node| -> BTreeSet<String> {
+        node.interfaces()
+            .iter()
+            .map(|iface| iface.name.clone())
+            .collect()
+    };
+    let (old_ifaces, new_ifaces) = (ifaces(old), ifaces(new));
+    for iface in new_ifaces.difference(&old_ifaces) {
+        details.push(format!("+ interface {iface}"));
+    }
+    for iface in old_ifaces.difference(&new_ifaces) {
+        details.push(format!("- interface {iface}"));
+    }
+
+    let addresses = |node: &Node| -> BTreeSet<(String, IpNetwork)> {
+        node.interfaces()
+            .iter()
+            .flat_map(|iface| {
+                iface
+                    .addresses
+                    .iter()
+                    .map(|address| (iface.name.clone(), *address))
+            })
+            .collect()
+    };
+    let (old_addresses, new_addresses) = (addresses(old), addresses(new));
+    for (iface, address) in new_addresses.difference(&old_addresses) {
+        details.push(format!("+ address {address} on {iface}"));
+    }
+    for (iface, address) in old_addresses.difference(&new_addresses) {
+        details.push(format!("- address {address} on {iface}"));
+    }
+
+    // Interfaces on both sides which changed other than by their addresses,
+    // e.g. their families.
+    for name in old_ifaces.intersection(&new_ifaces) {
+        let iface = |node: &Node| {
+            node.interfaces()
+                .iter()
+                .find(|iface| iface.name == *name)
+                .map(|iface| {
+                    let mut iface = iface.clone();
+                    iface.addresses.clear();
+                    iface.pools.clear();
+                    iface
+                })
+        };
+        if iface(old) != iface(new) {
+            details.push(format!("~ interface {name}"));
+        }
+    }
+
+    if old.metadata() != new.metadata() {
+        details.push("~ metadata".to_string());
+    }
+    let rest = |node: &Node| {
+        let mut node = node.clone();
+        node.metadata_mut().clear();
+        node.interfaces_mut().clear();
+        node
+    };
+    if kind(old) == kind(new) && rest(old) != rest(new) {
+        details.push("~ configuration".to_string());
+    }
+    details
+}
+
+fn link_details(old: &Link, new: &Link) -> Vec<String> {
+    let mut details = vec![];
+    let mut changed = |field: &str, differs: bool| {
+        if differs {
+            details.push(format!("~ {field}"));
+        }
+    };
+    changed("profile", old.profile != new.profile);
+    changed("impairment", old.impairment != new.impairment);
+    changed("flows", old.flows != new.flows);
+    changed("mtu", old.mtu != new.mtu);
+    changed("metadata", old.metadata != new.metadata);
+    changed("expectations", old.expect != new.expect);
+    if old.enabled != new.enabled {
+        details.push(match new.enabled {
+            true => "~ enabled".to_string(),
+            false => "~ disabled".to_string(),
+        });
+    }
+    details
+}
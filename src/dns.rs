@@ -0,0 +1,48 @@
+use std::fmt::Write as _;
+use std::net::IpAddr;
+
+use crate::node::{FileDrop, FileSource};
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+// ==== struct DnsConfig ====
+
+/// Resolver configuration of a router, written to its `/etc/resolv.conf`:
+///
+/// ```yaml
+/// routers:
+///   rt1:
+///     dns:
+///       servers: [ 10.0.0.53, 2001:db8::53 ]
+///       search: [ lab.example ]
+///       options: [ "timeout:1" ]
+/// ```
+///
+/// `dns: [ 10.0.0.53 ]` is short for only listing servers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct DnsConfig {
+    pub(crate) servers: Vec<IpAddr>,
+    pub(crate) search: Vec<String>,
+    pub(crate) options: Vec<String>,
+}
+
+impl DnsConfig {
+    /// The resolv.conf, as a file to materialize in the router.
+    pub(crate) fn resolv_conf(&self) -> FileDrop {
+        let mut contents = String::from("# Written by netgen\n");
+        for server in &self.servers {
+            let _ = writeln!(contents, "nameserver {server}");
+        }
+        if !self.search.is_empty() {
+            let _ = writeln!(contents, "search {}", self.search.join(" "));
+        }
+        if !self.options.is_empty() {
+            let _ = writeln!(contents, "options {}", self.options.join(" "));
+        }
+        FileDrop {
+            dst: RESOLV_CONF.to_string(),
+            source: FileSource::Inline(contents),
+            mode: Some(0o644),
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+
+use crate::node::Node;
+use crate::provenance::Provenance;
+use crate::topology::Topology;
+
+// Blank rows between the boxes of a column.
+const BOX_SPACING: usize = 1;
+// Between the vertical tracks links run along, in the gutter between two
+// columns of boxes.
+const TRACK_SPACING: usize = 2;
+
+/// The topology in the DOT language, for Graphviz to lay out: nodes list
+/// their interfaces and addresses, links are labeled with their interfaces
+/// on both ends. The `provenance` of the topology heads it as a comment.
+pub fn dot(topology: &Topology, provenance: &Provenance) -> String {
+    let mut out = format!("// {provenance}\n");
+    out.push_str("graph netgen {\n    node [shape=box];\n");
+    for node in topology.nodes() {
+        let label = node_lines(node).join("\\n");
+        let shape = match node.as_switch() {
+            Some(_) => ", shape=ellipse",
+            None => "",
+        };
+        let _ = writeln!(
+            out,
+            "    \"{}\" [label=\"{label}\"{shape}];",
+            node.name()
+        );
+    }
+    for link in topology.links() {
+        let _ = writeln!(
+            out,
+            "    \"{}\" -- \"{}\" [taillabel=\"{}\", headlabel=\"{}\"];",
+            link.src.node, link.dst.node, link.src.iface, link.dst.iface
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// The topology drawn with characters, for terminals without Graphviz:
+/// nodes are boxes listing their interfaces and addresses, links are lines
+/// between the rows of their interfaces.
+///
+/// Nodes are laid out in columns by their distance in hops from the first
+/// node of their segment, so that links only join boxes of the same or of
+/// neighboring columns. Lines may still cross, which is fine on small
+/// topologies only.
+pub fn ascii(topology: &Topology) -> String {
+    // Columns, breadth first from the first node of every segment.
+    let mut columns: Vec<Vec<&Node>> = vec![];
+    let mut column_of: BTreeMap<&str, usize> = BTreeMap::new();
+    for root in topology.nodes() {
+        if column_of.contains_key(root.name()) {
+            continue;
+        }
+        column_of.insert(root.name(), 0);
+        let mut queue = VecDeque::from([root]);
+        while let Some(node) = queue.pop_front() {
+            let column = column_of[node.name()];
+            if columns.len() <= column {
+                columns.push(vec![]);
+            }
+            columns[column].push(node);
+            for neighbor in topology.neighbors(node.name()) {
+                if column_of.contains_key(neighbor) {
+                    continue;
+                }
+                if let Some(neighbor) = topology.get_node(neighbor) {
+                    column_of.insert(neighbor.name(), column + 1);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+
+    let mut boxes: BTreeMap<&str, NodeBox> = BTreeMap::new();
+    let mut widths = vec![];
+    let mut height = 0;
+    for (column, nodes) in columns.iter().enumerate() {
+        let mut y = 0;
+        let mut width = 0;
+        for node in nodes {
+            let node_box = NodeBox::new(node, column, y);
+            y += node_box.height() + BOX_SPACING;
+            width = width.max(node_box.width());
+            boxes.insert(node.name(), node_box);
+        }
+        height = height.max(y);
+        widths.push(width);
+    }
+
+    // Links are routed through the gutter to the right of the leftmost of
+    // their ends.
+    let mut gutters: Vec<Vec<Route>> = vec![vec![]; columns.len()];
+    for link in topology.links() {
+        let [a, b] = link.ends();
+        let (Some(a_box), Some(b_box)) =
+            (boxes.get(a.node.as_str()), boxes.get(b.node.as_str()))
+        else {
+            continue;
+        };
+        let (a_row, b_row) =
+            (a_box.row(a.iface.as_str()), b_box.row(b.iface.as_str()));
+        let route = match a_box.column.cmp(&b_box.column) {
+            Ordering::Less => Route::new(a_row, b_row, true),
+            Ordering::Greater => Route::new(b_row, a_row, true),
+            Ordering::Equal => Route::new(a_row, b_row, false),
+        };
+        gutters[a_box.column.min(b_box.column)].push(route);
+    }
+
+    let mut xs = vec![];
+    let mut width = 0;
+    for (column, routes) in gutters.iter().enumerate() {
+        xs.push(width);
+        width += widths[column] + TRACK_SPACING * (routes.len() + 1);
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    for node_box in boxes.values() {
+        let x = xs[node_box.column];
+        canvas.draw_box(x, node_box.y, widths[node_box.column], node_box);
+    }
+    for (column, routes) in gutters.iter().enumerate() {
+        let start = xs[column] + widths[column];
+        for (track, index) in track_order(routes).into_iter().enumerate() {
+            let route = &routes[index];
+            let x = start + TRACK_SPACING * (track + 1) - 1;
+            canvas.hline(start, x, route.from);
+            match route.crosses {
+                true => canvas.hline(x, xs[column + 1] - 1, route.to),
+                false => canvas.hline(start, x, route.to),
+            }
+            if route.from != route.to {
+                canvas.vline(x, route.from, route.to);
+            }
+        }
+    }
+    canvas.render()
+}
+
+// Title of the node followed by its interfaces, along with their addresses.
+fn node_lines(node: &Node) -> Vec<String> {
+    let title = match node.as_switch() {
+        Some(_) => format!("{} (switch)", node.name()),
+        None => node.name().to_string(),
+    };
+    let ifaces = node.interfaces().iter().map(|iface| {
+        let addresses: Vec<String> =
+            iface.addresses().iter().map(ToString::to_string).collect();
+        match addresses.is_empty() {
+            true => iface.name().to_string(),
+            false => format!("{}  {}", iface.name(), addresses.join(", ")),
+        }
+    });
+    std::iter::once(title).chain(ifaces).collect()
+}
+
+// ==== struct NodeBox ====
+
+// A node drawn as a box, its title on the first row followed by one row
+// per interface.
+struct NodeBox {
+    column: usize,
+    // Of the top border.
+    y: usize,
+    lines: Vec<String>,
+    ifaces: Vec<String>,
+}
+
+impl NodeBox {
+    fn new(node: &Node, column: usize, y: usize) -> Self {
+        Self {
+            column,
+            y,
+            lines: node_lines(node),
+            ifaces: node
+                .interfaces()
+                .iter()
+                .map(|iface| iface.name().to_string())
+                .collect(),
+        }
+    }
+
+    fn width(&self) -> usize {
+        let content = self.lines.iter().map(|line| line.chars().count());
+        content.max().unwrap_or_default() + 4
+    }
+
+    fn height(&self) -> usize {
+        self.lines.len() + 2
+    }
+
+    // Row links to the interface attach to, the title's if it is unknown.
+    fn row(&self, iface: &str) -> usize {
+        let line = self
+            .ifaces
+            .iter()
+            .position(|name| name == iface)
+            .map_or(0, |index| index + 1);
+        self.y + 1 + line
+    }
+}
+
+// ==== struct Route ====
+
+// A link through a gutter, from the row of its end on the left, either to
+// the row of its end on the right or, when both ends are in the same
+// column, back to the left.
+#[derive(Clone)]
+struct Route {
+    from: usize,
+    to: usize,
+    crosses: bool,
+}
+
+impl Route {
+    fn new(from: usize, to: usize, crosses: bool) -> Self {
+        Self { from, to, crosses }
+    }
+
+    // Rows the route leaves the boxes on the left from.
+    fn left_rows(&self) -> Vec<usize> {
+        match self.crosses {
+            true => vec![self.from],
+            false => vec![self.from, self.to],
+        }
+    }
+}
+
+// Order in which the routes of a gutter get their track, left to right.
+// Lines from the left run up to the track of their route and lines to the
+// right from it, so a route has to come after those leaving from the row
+// it arrives at for their lines not to overlap. They overlap whichever way
+// round when routes wait on each other.
+fn track_order(routes: &[Route]) -> Vec<usize> {
+    let waits_on: Vec<Vec<usize>> = routes
+        .iter()
+        .enumerate()
+        .map(|(index, route)| {
+            routes
+                .iter()
+                .enumerate()
+                .filter(|(other, other_route)| {
+                    *other != index
+                        && route.crosses
+                        && other_route.left_rows().contains(&route.to)
+                })
+                .map(|(other, _)| other)
+                .collect()
+        })
+        .collect();
+
+    let mut order: Vec<usize> = vec![];
+    while order.len() < routes.len() {
+        let mut pending =
+            (0..routes.len()).filter(|index| !order.contains(index));
+        let next = pending
+            .clone()
+            .find(|index| {
+                waits_on[*index].iter().all(|other| order.contains(other))
+            })
+            .or_else(|| pending.next());
+        order.extend(next);
+    }
+    order
+}
+
+// ==== struct Canvas ====
+
+struct Canvas {
+    cells: Vec<Vec<char>>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            cells: vec![vec![' '; width]; height],
+        }
+    }
+
+    // Lines crossing each other make a '+'.
+    fn put(&mut self, x: usize, y: usize, c: char) {
+        let Some(cell) = self.cells.get_mut(y).and_then(|row| row.get_mut(x))
+        else {
+            return;
+        };
+        *cell = match *cell {
+            ' ' => c,
+            existing if existing == c => c,
+            _ => '+',
+        };
+    }
+
+    fn hline(&mut self, x1: usize, x2: usize, y: usize) {
+        for x in x1.min(x2)..=x1.max(x2) {
+            self.put(x, y, '-');
+        }
+    }
+
+    fn vline(&mut self, x: usize, y1: usize, y2: usize) {
+        for y in y1.min(y2)..=y1.max(y2) {
+            self.put(x, y, '|');
+        }
+    }
+
+    fn draw_box(&mut self, x: usize, y: usize, width: usize, node: &NodeBox) {
+        let bottom = y + node.height() - 1;
+        self.hline(x, x + width - 1, y);
+        self.hline(x, x + width - 1, bottom);
+        self.vline(x, y, bottom);
+        self.vline(x + width - 1, y, bottom);
+        for (row, line) in node.lines.iter().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                self.cells[y + 1 + row][x + 2 + column] = c;
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut lines: Vec<String> = self
+            .cells
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_string())
+            .collect();
+        while lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+}
@@ -0,0 +1,213 @@
+//! Rewriting of a topology file to add or remove links and nodes, for
+//! changing a running topology without going through its file.
+
+use yaml_rust2::yaml::{Hash, Yaml};
+use yaml_rust2::{YamlEmitter, YamlLoader};
+
+use crate::NetResult;
+use crate::clone::key;
+use crate::error::{ConfigError, NetError};
+use crate::link::LinkEndpoint;
+
+/// Adds a link between `src` and `dst` to the (rendered) topology file
+/// `source`.
+pub(crate) fn add_link(
+    source: &str,
+    src: &LinkEndpoint,
+    dst: &LinkEndpoint,
+) -> NetResult<String> {
+    let mut docs = load(source)?;
+    let link = Yaml::Hash(Hash::from_iter([
+        (key("src-device"), key(src.node.as_str())),
+        (key("src-iface"), key(src.iface.as_str())),
+        (key("dst-device"), key(dst.node.as_str())),
+        (key("dst-iface"), key(dst.iface.as_str())),
+    ]));
+
+    // Along the other links, if any.
+    let index = docs
+        .iter()
+        .position(|doc| doc["links"].as_vec().is_some())
+        .unwrap_or_default();
+    let Some(Yaml::Hash(doc)) = docs.get_mut(index) else {
+        return Err(NetError::BasicError(
+            "The topology file has no document to add links to.".to_string(),
+        ));
+    };
+    match doc.get_mut(&key("links")) {
+        Some(Yaml::Array(links)) => links.push(link),
+        _ => {
+            doc.insert(key("links"), Yaml::Array(vec![link]));
+        }
+    }
+    dump(&docs, &format!("the link {src} - {dst}"))
+}
+
+/// Removes the link between `a` and `b` from the (rendered) topology file
+/// `source`. Links whose interfaces are named after `iface-naming` are
+/// matched on their nodes only.
+pub(crate) fn remove_link(
+    source: &str,
+    a: &LinkEndpoint,
+    b: &LinkEndpoint,
+) -> NetResult<String> {
+    let mut docs = load(source)?;
+    let mut removed = false;
+    for doc in &mut docs {
+        let Some(Yaml::Array(links)) =
+            doc.as_mut_hash().and_then(|doc| doc.get_mut(&key("links")))
+        else {
+            continue;
+        };
+        if let Some(index) = links.iter().position(|link| {
+            let end = |side: &str| {
+                let node = link[format!("{side}-device").as_str()].as_str();
+                let iface = link[format!("{side}-iface").as_str()].as_str();
+                (node, iface)
+            };
+            let matches = |(node, iface): (Option<&str>, Option<&str>),
+                           end: &LinkEndpoint| {
+                node == Some(end.node.as_str())
+                    && iface.is_none_or(|iface| iface == end.iface.as_str())
+            };
+            let (src, dst) = (end("src"), end("dst"));
+            (matches(src, a) && matches(dst, b))
+                || (matches(src, b) && matches(dst, a))
+        }) {
+            links.remove(index);
+            removed = true;
+            break;
+        }
+    }
+    if !removed {
+        return Err(NetError::BasicError(format!(
+            "No link between {a} and {b} in the topology."
+        )));
+    }
+    dump(&docs, &format!("the link {a} - {b} removed"))
+}
+
+/// Removes the node `name` from the (rendered) topology file `source`,
+/// along with its links and the probe sessions it takes part in.
+pub(crate) fn remove_node(source: &str, name: &str) -> NetResult<String> {
+    let mut docs = load(source)?;
+    let mut removed = false;
+    for doc in &mut docs {
+        let Yaml::Hash(doc) = doc else {
+            continue;
+        };
+        for group in ["routers", "switches"] {
+            if let Some(Yaml::Hash(nodes)) = doc.get_mut(&key(group)) {
+                removed |= nodes.remove(&key(name)).is_some();
+            }
+        }
+        if let Some(Yaml::Array(links)) = doc.get_mut(&key("links")) {
+            links.retain(|link| {
+                link["src-device"].as_str() != Some(name)
+                    && link["dst-device"].as_str() != Some(name)
+            });
+        }
+        if let Some(Yaml::Array(probes)) = doc.get_mut(&key("probes")) {
+            probes.retain(|probe| {
+                probe["between"]
+                    .as_vec()
+                    .is_none_or(|nodes| !nodes.contains(&key(name)))
+            });
+        }
+    }
+    if !removed {
+        return Err(ConfigError::UnknownNode(name.to_string()).into());
+    }
+    dump(&docs, &format!("{name} removed"))
+}
+
+/// Moves the link end `end` to the router `to` in the (rendered) topology
+/// file `source`, along with the addresses of its interface.
+pub(crate) fn move_iface(
+    source: &str,
+    end: &LinkEndpoint,
+    to: &str,
+) -> NetResult<String> {
+    let (node, iface) = (end.node.as_str(), end.iface.as_str());
+    let mut docs = load(source)?;
+    let mut moved = false;
+    for doc in &mut docs {
+        let Yaml::Hash(doc) = doc else {
+            continue;
+        };
+        if let Some(Yaml::Array(links)) = doc.get_mut(&key("links")) {
+            for link in links.iter_mut() {
+                let Yaml::Hash(link) = link else {
+                    continue;
+                };
+                for side in ["src", "dst"] {
+                    let (device, side_iface) = (
+                        key(&format!("{side}-device")),
+                        format!("{side}-iface"),
+                    );
+                    if link.get(&device).and_then(Yaml::as_str) == Some(node)
+                        && link.get(&key(&side_iface)).and_then(Yaml::as_str)
+                            == Some(iface)
+                    {
+                        link.insert(device, key(to));
+                        moved = true;
+                    }
+                }
+            }
+        }
+
+        // The addresses of the interface, if it has any.
+        let Some(Yaml::Hash(routers)) = doc.get_mut(&key("routers")) else {
+            continue;
+        };
+        let config = routers
+            .get_mut(&key(node))
+            .and_then(|router| router.as_mut_hash())
+            .and_then(|router| router.get_mut(&key("interfaces")))
+            .and_then(|ifaces| ifaces.as_mut_hash())
+            .and_then(|ifaces| ifaces.remove(&key(iface)));
+        if let Some(config) = config
+            && let Some(Yaml::Hash(router)) = routers.get_mut(&key(to))
+        {
+            match router.get_mut(&key("interfaces")) {
+                Some(Yaml::Hash(ifaces)) => {
+                    ifaces.insert(key(iface), config);
+                }
+                _ => {
+                    router.insert(
+                        key("interfaces"),
+                        Yaml::Hash(Hash::from_iter([(key(iface), config)])),
+                    );
+                }
+            }
+        }
+    }
+    // Ends whose interface is named after `iface-naming` would be named
+    // anew on the other router.
+    if !moved {
+        return Err(NetError::BasicError(format!(
+            "No link in the topology names {end} explicitly."
+        )));
+    }
+    dump(&docs, &format!("{end} moved to {to}"))
+}
+
+fn load(source: &str) -> NetResult<Vec<Yaml>> {
+    YamlLoader::load_from_str(source)
+        .map_err(|err| NetError::ConfigError(ConfigError::YamlSyntax(err)))
+}
+
+fn dump(docs: &[Yaml], what: &str) -> NetResult<String> {
+    let mut rendered = String::new();
+    for doc in docs {
+        let mut emitter = YamlEmitter::new(&mut rendered);
+        emitter.multiline_strings(true);
+        emitter.dump(doc).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to write the topology with {what}: {err:?}"
+            ))
+        })?;
+        rendered.push('\n');
+    }
+    Ok(rendered)
+}
@@ -20,6 +20,12 @@ pub enum NetError {
 
     #[error(transparent)]
     LinkError(#[from] LinkError),
+
+    #[error(transparent)]
+    StateError(#[from] StateError),
+
+    #[error("Cancelled")]
+    Cancelled,
 }
 
 // TODO: Look into customizing the LoginErrors. Currently mushed
@@ -62,6 +68,53 @@ pub enum ConfigError {
     #[error("Invalid YAML Syntax {0}.")]
     YamlSyntax(#[from] ScanError),
 
+    // Positions of `scan` are in the rendered file, `line` is the line of
+    // the template it comes from.
+    #[error(
+        "Invalid YAML Syntax {scan} of the rendered template, from its line \
+         {line}."
+    )]
+    RenderedYamlSyntax { scan: ScanError, line: usize },
+
+    #[error("Template error on line {line}: {reason}.")]
+    Template { line: usize, reason: String },
+
+    #[error("Interface name '{name}' is longer than 15 characters.")]
+    InterfaceNameTooLong { name: String },
+
+    #[error(
+        "Interface name '{name}' is invalid, it may not contain '/', ':' or \
+        whitespace."
+    )]
+    InvalidInterfaceName { name: String },
+
+    #[error(
+        "Interface {node}:{iface} named by iface-naming is already linked."
+    )]
+    InterfaceNameClash { node: String, iface: String },
+
+    #[error("Address pool '{0}' does not exist.")]
+    UnknownPool(String),
+
+    #[error("Link impairment profile '{0}' does not exist.")]
+    UnknownProfile(String),
+
+    #[error("Address pool '{0}' has no free addresses left.")]
+    PoolExhausted(String),
+
+    #[error("Invalid address pool '{name}': {reason}.")]
+    InvalidPool { name: String, reason: String },
+
+    #[error(
+        "Address {address} of {node}:{iface} belongs to a disabled address \
+        family."
+    )]
+    DisabledFamily {
+        node: String,
+        iface: String,
+        address: IpNetwork,
+    },
+
     #[error("Invalid address '{address}' for interface:\n{path}")]
     InvalidAddress {
         address: String,
@@ -69,6 +122,30 @@ pub enum ConfigError {
         #[source]
         source: ipnetwork::IpNetworkError,
     },
+
+    #[error("Files of the topology cannot be read:\n  {}", .0.join("\n  "))]
+    UnreadableFiles(Vec<String>),
+}
+
+impl ConfigError {
+    /// Path of the field at fault, for the errors which have one.
+    pub fn path(&self) -> Option<&YamlPath> {
+        match self {
+            ConfigError::IncorrectType { path, .. }
+            | ConfigError::MissingField { path }
+            | ConfigError::InvalidAddress { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn path_mut(&mut self) -> Option<&mut YamlPath> {
+        match self {
+            ConfigError::IncorrectType { path, .. }
+            | ConfigError::MissingField { path }
+            | ConfigError::InvalidAddress { path, .. } => Some(path),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, ThisError)]
@@ -95,6 +172,9 @@ pub enum NamespaceError {
         source: nix::Error,
     },
 
+    #[error("Failed to set up namespaces for device '{device}': {reason}")]
+    Setup { device: String, reason: String },
+
     #[error("Failed to unmount namespace at '{path}': {source}")]
     Unmount {
         path: String,
@@ -187,11 +267,43 @@ pub enum LinkError {
         #[source]
         source: rtnetlink::Error,
     },
+
+    #[error("Packet socket {operation} failed on {device}:{iface}: {source}")]
+    RawSocket {
+        device: String,
+        iface: String,
+        operation: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+#[derive(Debug, ThisError)]
+pub enum StateError {
+    #[error("Unable to read state file '{path}': {source}")]
+    Read {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Unable to write state file '{path}': {source}")]
+    Write {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("State file '{path}' is corrupted: {reason}")]
+    Corrupted { path: String, reason: String },
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct YamlPath {
     segments: Vec<PathSegment>,
+    // Line and column of the deepest key of the path found in the file,
+    // both starting from 1.
+    location: Option<(usize, usize)>,
 }
 
 #[derive(Debug, Clone)]
@@ -216,10 +328,33 @@ impl YamlPath {
         self.segments.push(PathSegment::Unknown);
         self.clone()
     }
+
+    /// Line and column in the file the path leads to, if known.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.location
+    }
+
+    pub(crate) fn set_location(&mut self, line: usize, column: usize) {
+        self.location = Some((line, column));
+    }
+
+    /// The known keys of the path, from the root.
+    pub(crate) fn keys(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                PathSegment::Key(k) => Some(k.as_str()),
+                PathSegment::Unknown => None,
+            })
+            .collect()
+    }
 }
 
 impl fmt::Display for YamlPath {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, column)) = self.location {
+            writeln!(f, "(line {line}, column {column})")?;
+        }
         for (depth, segment) in self.segments.iter().enumerate() {
             let indent = "  ".repeat(depth);
             match segment {
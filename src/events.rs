@@ -0,0 +1,160 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nix::fcntl::{Flock, FlockArg};
+use tracing::warn;
+
+use crate::error::NetError;
+use crate::{NetResult, events_file};
+
+/// Size past which the event log is rotated to `rotated_events_file()`,
+/// replacing the events rotated before, so that the log of a long-lived lab
+/// never takes more than twice as much.
+pub const EVENTS_MAX_SIZE: u64 = 8 * 1024 * 1024;
+
+// Read back from the end of the log to find its last sequence number, more
+// than any line takes.
+const TAIL_SIZE: u64 = 4096;
+
+/// Where the events rotated out of `events_file()` are.
+pub fn rotated_events_file() -> String {
+    format!("{}.1", events_file())
+}
+
+// ==== struct Event ====
+
+/// A line of the event log, timestamped with the Unix time in milliseconds
+/// and numbered from 1, e.g.:
+///
+/// ```text
+/// 1760612345123 42 probe rt1 -> rt2 down
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub seq: u64,
+    pub time: u128,
+    pub kind: String,
+    // What happened, starting with what it happened to.
+    pub text: String,
+}
+
+impl Event {
+    /// Parses a line of the log. Lines written before events were numbered
+    /// get 0 as their sequence number.
+    pub fn parse(line: &str) -> Option<Self> {
+        let (time, rest) = line.split_once(' ')?;
+        let (seq, rest) = match rest.split_once(' ') {
+            Some((seq, rest)) if seq.bytes().all(|b| b.is_ascii_digit()) => {
+                (seq.parse().ok()?, rest)
+            }
+            _ => (0, rest),
+        };
+        let (kind, text) = rest.split_once(' ')?;
+        Some(Self {
+            seq,
+            time: time.parse().ok()?,
+            kind: kind.to_string(),
+            text: text.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.time, self.seq, self.kind, self.text)
+    }
+}
+
+/// Appends an event to `events_file()`, numbered after the last one.
+///
+/// Events are a best-effort log, failing to record one is not an error.
+pub(crate) fn record(kind: &str, subject: &str, event: &str) {
+    if let Err(err) = append(kind, &format!("{subject} {event}")) {
+        warn!(%kind, %subject, %event, error = %err, "unable to record event");
+    }
+}
+
+fn append(kind: &str, text: &str) -> io::Result<()> {
+    let path = events_file();
+    let rotated = rotated_events_file();
+    // Numbering and rotating are serialized between the processes recording
+    // events through a lock of their own, the log being replaced.
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(format!("{path}.lock"))?;
+    let _lock = Flock::lock(lock, FlockArg::LockExclusive)
+        .map_err(|(_, errno)| io::Error::from(errno))?;
+
+    let last = match last_seq(&path)? {
+        Some(seq) => seq,
+        None => last_seq(&rotated)?.unwrap_or_default(),
+    };
+    if fs::metadata(&path).is_ok_and(|meta| meta.len() >= EVENTS_MAX_SIZE) {
+        fs::rename(&path, &rotated)?;
+    }
+
+    let event = Event {
+        seq: last + 1,
+        time: unix_millis(),
+        kind: kind.to_string(),
+        text: text.to_string(),
+    };
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(format!("{event}\n").as_bytes())
+}
+
+// Sequence number of the last event of the log at `path`, if any.
+fn last_seq(path: &str) -> io::Result<Option<u64>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_SIZE)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    Ok(String::from_utf8_lossy(&tail)
+        .lines()
+        .rev()
+        .find_map(Event::parse)
+        .map(|event| event.seq))
+}
+
+/// The events recorded after the one numbered `cursor`, oldest first, as
+/// far back as the log goes. With 0, all of them.
+pub fn since(cursor: u64) -> NetResult<Vec<Event>> {
+    let mut events = vec![];
+    for path in [rotated_events_file(), events_file()] {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(NetError::BasicError(format!(
+                    "Unable to read {path}: {err:?}"
+                )));
+            }
+        };
+        events.extend(
+            contents
+                .lines()
+                .filter_map(Event::parse)
+                .filter(|event| event.seq > cursor),
+        );
+    }
+    Ok(events)
+}
+
+/// Milliseconds since the Unix epoch.
+pub(crate) fn unix_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis())
+}
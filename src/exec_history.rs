@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use yaml_rust2::YamlLoader;
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::NetResult;
+use crate::error::NetError;
+use crate::lab::{config_dir, lock_file, write_yaml};
+
+const HISTORY_FILE: &str = "history.yml";
+
+/// Commands kept per node, the oldest being dropped first.
+pub const HISTORY_LEN: usize = 100;
+
+// ==== struct ExecHistory ====
+
+/// The commands run with `netgen exec`, by node, oldest first, persisted to
+/// `history.yml` in the netgen config directory so that they outlive the
+/// topologies:
+///
+/// ```yaml
+/// rt1:
+///   - [ ip, route ]
+///   - [ vtysh, -c, show bgp summary ]
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ExecHistory {
+    pub commands: BTreeMap<String, Vec<Vec<String>>>,
+}
+
+impl ExecHistory {
+    /// Where the history is kept: `$XDG_CONFIG_HOME/netgen`, or
+    /// `~/.config/netgen`.
+    pub fn path() -> NetResult<PathBuf> {
+        Ok(config_dir("the command history")?.join(HISTORY_FILE))
+    }
+
+    /// Loads the history, empty if no command was run yet.
+    pub fn load() -> NetResult<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to read {}: {err:?}",
+                path.display()
+            ))
+        })?;
+        let invalid = |reason: &str| {
+            NetError::BasicError(format!(
+                "Command history {} is invalid: {reason}",
+                path.display()
+            ))
+        };
+        let docs = YamlLoader::load_from_str(&contents)
+            .map_err(|err| invalid(&err.to_string()))?;
+
+        let mut history = Self::default();
+        let nodes = match docs.first() {
+            Some(Yaml::Hash(nodes)) => nodes,
+            Some(Yaml::Null) | None => return Ok(history),
+            Some(_) => return Err(invalid("expected a hash")),
+        };
+        for (node, commands) in nodes {
+            let (Yaml::String(node), Yaml::Array(commands)) = (node, commands)
+            else {
+                return Err(invalid("expected a list of commands by node"));
+            };
+            let commands = commands
+                .iter()
+                .map(|command| match command {
+                    Yaml::Array(args) => args
+                        .iter()
+                        .map(|arg| arg.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>(),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("commands are lists of strings"))?;
+            history.commands.insert(node.clone(), commands);
+        }
+        Ok(history)
+    }
+
+    /// Loads the history, hands it to `change` and saves it back, other
+    /// processes waiting in between.
+    pub fn update(change: impl FnOnce(&mut Self)) -> NetResult<()> {
+        let _lock = lock_file(&Self::path()?)?;
+        let mut history = Self::load()?;
+        change(&mut history);
+        history.save()
+    }
+
+    fn save(&self) -> NetResult<()> {
+        let mut doc = Hash::new();
+        for (node, commands) in &self.commands {
+            let commands = commands
+                .iter()
+                .map(|args| {
+                    Yaml::Array(
+                        args.iter().cloned().map(Yaml::String).collect(),
+                    )
+                })
+                .collect();
+            doc.insert(Yaml::String(node.clone()), Yaml::Array(commands));
+        }
+        write_yaml(&Self::path()?, &Yaml::Hash(doc), "the command history")
+    }
+
+    /// Appends `command` to the history of `node`, unless it is the last
+    /// one run there already.
+    pub fn record(&mut self, node: &str, command: &[&str]) {
+        let command: Vec<String> =
+            command.iter().map(|arg| arg.to_string()).collect();
+        let commands = self.commands.entry(node.to_string()).or_default();
+        if commands.last() == Some(&command) {
+            return;
+        }
+        commands.push(command);
+        let excess = commands.len().saturating_sub(HISTORY_LEN);
+        commands.drain(..excess);
+    }
+
+    /// The commands run in `node`, oldest first.
+    pub fn of(&self, node: &str) -> &[Vec<String>] {
+        self.commands.get(node).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn last(&self, node: &str) -> Option<&[String]> {
+        self.of(node).last().map(Vec::as_slice)
+    }
+}
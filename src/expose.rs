@@ -0,0 +1,93 @@
+use std::net::{SocketAddr, TcpListener};
+
+use tokio::io::copy_bidirectional;
+use tokio::net::TcpStream;
+use tracing::debug;
+
+use crate::NetResult;
+use crate::error::NetError;
+
+// ==== struct GrpcProxy ====
+
+/// The gRPC northbound of the routers of a kind, Holo's by default, made
+/// reachable from the host on a port per router, for automation to
+/// configure them all without entering their namespaces:
+///
+/// ```yaml
+/// grpc:
+///   kind: holo        # the default
+///   port: 50051       # the daemon's, the default
+///   host-port: 50100  # the first router's, the default, the next ones
+///                     # following in name order
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GrpcProxy {
+    pub(crate) kind: String,
+    pub(crate) port: u16,
+    pub(crate) host_port: u16,
+}
+
+impl Default for GrpcProxy {
+    fn default() -> Self {
+        Self {
+            kind: "holo".to_string(),
+            port: 50051,
+            host_port: 50100,
+        }
+    }
+}
+
+/// Accepts connections on every listener and pipes each of them to its
+/// target, until the process is killed.
+///
+/// Connections to the targets are opened from the caller's network
+/// namespace, which need not be the one the listeners were bound in.
+pub(crate) fn forward(
+    listeners: Vec<(TcpListener, SocketAddr)>,
+) -> NetResult<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| {
+            NetError::BasicError(format!(
+                "Failed to create tokio runtime: {err:?}"
+            ))
+        })?;
+
+    runtime.block_on(async {
+        let mut tasks = vec![];
+        for (listener, target) in listeners {
+            listener.set_nonblocking(true).map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to set up listener for {target}: {err:?}"
+                ))
+            })?;
+            let listener = tokio::net::TcpListener::from_std(listener)
+                .map_err(|err| {
+                    NetError::BasicError(format!(
+                        "Unable to set up listener for {target}: {err:?}"
+                    ))
+                })?;
+            tasks.push(tokio::spawn(accept_loop(listener, target)));
+        }
+        for task in tasks {
+            let _ = task.await;
+        }
+        Ok(())
+    })
+}
+
+async fn accept_loop(listener: tokio::net::TcpListener, target: SocketAddr) {
+    loop {
+        let Ok((mut inbound, peer)) = listener.accept().await else {
+            continue;
+        };
+        tokio::spawn(async move {
+            let Ok(mut outbound) = TcpStream::connect(target).await else {
+                debug!(%peer, %target, "unable to reach exposed port");
+                return;
+            };
+            let _ = copy_bidirectional(&mut inbound, &mut outbound).await;
+        });
+    }
+}
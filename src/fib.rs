@@ -0,0 +1,59 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+use rtnetlink::{Handle, RouteMessageBuilder};
+
+use crate::NetResult;
+use crate::error::NetError;
+
+// ==== struct StaticRoute ====
+
+/// A route programmed into the FIB of a router over netlink, for data-plane
+/// experiments which do without a routing daemon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticRoute {
+    pub prefix: IpNetwork,
+    pub via: IpAddr,
+    // The main table unless set, e.g. for policy routing rules to send
+    // packets to.
+    pub table: Option<u32>,
+}
+
+impl StaticRoute {
+    // Adds the route, replacing any to the same prefix. Has to be called
+    // from within the router's network namespace.
+    pub(crate) async fn add(&self, handle: &Handle) -> NetResult<()> {
+        let mut builder = RouteMessageBuilder::<IpAddr>::new()
+            .destination_prefix(self.prefix.network(), self.prefix.prefix())
+            .and_then(|builder| builder.gateway(self.via))
+            .map_err(|err| {
+                NetError::BasicError(format!("Invalid route {self}: {err}"))
+            })?;
+        if let Some(table) = self.table {
+            builder = builder.table_id(table);
+        }
+        let message = builder.build();
+        handle
+            .route()
+            .add(message)
+            .replace()
+            .execute()
+            .await
+            .map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to add route {self}: {err}"
+                ))
+            })
+    }
+}
+
+impl fmt::Display for StaticRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} via {}", self.prefix, self.via)?;
+        if let Some(table) = self.table {
+            write!(f, " table {table}")?;
+        }
+        Ok(())
+    }
+}
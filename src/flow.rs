@@ -0,0 +1,158 @@
+use std::fs::{self, File};
+use std::net::SocketAddr;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+
+use ipnetwork::IpNetwork;
+use tracing::debug;
+
+use crate::error::NetError;
+use crate::limits::ProcessLimits;
+use crate::state::{ProcessRecord, TopologyState};
+use crate::{NetResult, devices_ns_dir, process_start_time};
+
+const CONFIG_FILE: &str = "pmacctd.conf";
+const LOG_FILE: &str = "pmacctd.log";
+
+// ==== enum FlowProtocol ====
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FlowProtocol {
+    // NetFlow v9.
+    Netflow,
+    // NetFlow v10.
+    Ipfix,
+    Sflow,
+}
+
+impl FlowProtocol {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "netflow" => Some(Self::Netflow),
+            "ipfix" => Some(Self::Ipfix),
+            "sflow" => Some(Self::Sflow),
+            _ => None,
+        }
+    }
+}
+
+// ==== struct FlowExport ====
+
+/// Flow records exported from a switch, backed by pmacctd capturing on its
+/// bridge:
+///
+/// ```yaml
+/// switches:
+///   sw1:
+///     flow-export:
+///       protocol: ipfix
+///       collector: 192.168.100.50:4739
+///       sampling: 100
+///       source: 192.168.100.254/24
+/// ```
+///
+/// One packet out of `sampling` is accounted for. The bridge lives in
+/// netgen's main namespace, which has no address of its own: `source` is
+/// given to the bridge for the exporter to reach a collector running on a
+/// router attached to the switch.
+///
+/// `pmacctd` needs to be installed on the host.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FlowExport {
+    pub(crate) protocol: FlowProtocol,
+    pub(crate) collector: SocketAddr,
+    pub(crate) sampling: u32,
+    pub(crate) source: Option<IpNetwork>,
+}
+
+impl FlowExport {
+    /// pmacctd configuration exporting what goes through `bridge`.
+    pub(crate) fn config(&self, bridge: &str) -> String {
+        let mut config = vec![
+            "daemonize: false".to_string(),
+            format!("pcap_interface: {bridge}"),
+            format!("sampling_rate: {}", self.sampling),
+        ];
+        match self.protocol {
+            FlowProtocol::Netflow | FlowProtocol::Ipfix => {
+                let version = match self.protocol {
+                    FlowProtocol::Ipfix => 10,
+                    _ => 9,
+                };
+                config.push("plugins: nfprobe".to_string());
+                config.push(format!("nfprobe_receiver: {}", self.collector));
+                config.push(format!("nfprobe_version: {version}"));
+                if let Some(source) = self.source {
+                    config.push(format!("nfprobe_source_ip: {}", source.ip()));
+                }
+            }
+            FlowProtocol::Sflow => {
+                config.push("plugins: sfprobe".to_string());
+                config.push(format!("sfprobe_receiver: {}", self.collector));
+                if let Some(source) = self.source {
+                    config.push(format!("sfprobe_agentip: {}", source.ip()));
+                }
+            }
+        }
+        config.join("\n") + "\n"
+    }
+
+    /// Starts exporting the flows of `switch_name`'s bridge, the exporter
+    /// being recorded in the topology state so that `stop` can terminate
+    /// it. Has to be called from the main namespace.
+    pub(crate) fn start(
+        &self,
+        switch_name: &str,
+        bridge: &str,
+        limits: ProcessLimits,
+    ) -> NetResult<()> {
+        let dir = format!("{}/{switch_name}", devices_ns_dir());
+        let io_err = |path: &str, err: std::io::Error| {
+            NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+        };
+        fs::create_dir_all(&dir).map_err(|err| io_err(&dir, err))?;
+        let config_path = format!("{dir}/{CONFIG_FILE}");
+        fs::write(&config_path, self.config(bridge))
+            .map_err(|err| io_err(&config_path, err))?;
+        let log_path = format!("{dir}/{LOG_FILE}");
+        let log =
+            File::create(&log_path).map_err(|err| io_err(&log_path, err))?;
+
+        // In its own process group, so that it outlives the terminal netgen
+        // was started from.
+        let mut command = Command::new("pmacctd");
+        command
+            .args(["-f", &config_path])
+            .stdin(Stdio::null())
+            .stdout(log.try_clone().map_err(|err| io_err(&log_path, err))?)
+            .stderr(log)
+            .process_group(0);
+        // SAFETY: only applies the limits, through async-signal-safe calls
+        // but for the oom_score_adj write, as the daemons of routers do.
+        unsafe {
+            command.pre_exec(move || {
+                limits.apply().map_err(std::io::Error::other)
+            });
+        }
+        let child = command.spawn().map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to run pmacctd for {switch_name}: {err:?}"
+            ))
+        })?;
+        let pid = child.id() as i32;
+        debug!(switch = %switch_name, pid, collector = %self.collector,
+            "exporting flows");
+
+        // It may already have exited, e.g. on an invalid configuration, in
+        // which case there is nothing to stop later on.
+        let Some(start_time) = process_start_time(pid) else {
+            return Ok(());
+        };
+        TopologyState::record_process(ProcessRecord {
+            node: switch_name.to_string(),
+            name: "pmacctd".to_string(),
+            pid,
+            start_time,
+        })
+    }
+}
@@ -0,0 +1,538 @@
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use nix::fcntl::{Flock, FlockArg};
+use nix::mount::{MsFlags, mount};
+use tracing::{debug, warn};
+
+use crate::error::{NamespaceError, NetError};
+use crate::events;
+use crate::node::{FileDrop, FileSource};
+use crate::state::{BinaryRecord, TopologyState};
+use crate::{NetResult, devices_ns_dir};
+
+pub(crate) const DEFAULT_PATH: &str = "/usr/lib/frr";
+// Where distributions install the daemons, searched before PATH.
+const SEARCH_DIRS: &[&str] = &[
+    DEFAULT_PATH,
+    "/usr/libexec/frr",
+    "/usr/local/lib/frr",
+    "/usr/local/libexec/frr",
+];
+
+/// Routing daemons which can be listed under `daemons`.
+pub(crate) const DAEMONS: &[&str] = &[
+    "bgpd", "ospfd", "ospf6d", "ripd", "ripngd", "isisd", "pimd", "pim6d",
+    "ldpd", "nhrpd", "eigrpd", "babeld", "sharpd", "pbrd", "bfdd", "fabricd",
+    "vrrpd", "pathd",
+];
+
+// The daemons run in the foreground as root, which owns the runtime
+// directory of the router.
+pub(crate) const DAEMON_ARGS: &[&str] = &["-u", "root", "-g", "root"];
+// Added for the devices under test, whose logs are the ones read.
+pub(crate) const DUT_ARGS: &[&str] = &["--log-level", "debugging"];
+
+pub(crate) const RUN_DIR: &str = "/var/run/frr";
+const DAEMONS_FILE: &str = "/etc/frr/daemons";
+const VTYSH_CONF: &str = "/etc/frr/vtysh.conf";
+const STARTUP_CONFIG: &str = "/etc/frr/frr.conf";
+// How long the daemons get to open their vty sockets before the startup
+// config is pushed to them.
+const VTY_TIMEOUT: Duration = Duration::from_secs(10);
+// Between attempts at loading the startup config.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+pub(crate) const DEFAULT_RETRIES: u32 = 3;
+const REJECTED_FILE: &str = "rejected.conf";
+const COMMANDS_LOG: &str = "commands.log";
+// Written by FRR in running configs, not to be found in startup ones.
+const IGNORED_LINES: &[&str] = &[
+    "Building configuration",
+    "Current configuration",
+    "frr version",
+    "frr defaults",
+    "exit-address-family",
+    "exit-vrf",
+];
+
+// ==== struct Frr ====
+
+/// FRR routing suite running in a router, zebra and staticd along with the
+/// listed daemons:
+///
+/// ```yaml
+/// routers:
+///   rt1:
+///     frr:
+///       daemons: [ ospfd, bgpd ]
+///       startup-config: ./configs/rt1.conf
+/// ```
+///
+/// Every router gets its own `/var/run/frr`, kept under its device
+/// directory, so that `vtysh` run inside it talks to its own daemons.
+/// `path` is where the daemons are installed, found by `default_dir` when
+/// not set.
+/// A startup config vtysh fails to load is retried `startup-retries` times,
+/// 3 by default, backing off from 1 up to 8 seconds.
+///
+/// With `lazy: true`, the daemons are not started with the topology but
+/// once the router is first used: logged into, or a command run in it.
+///
+/// `commands` are run through vtysh after the startup config, in a single
+/// session so that they can enter configuration modes, e.g. to clear
+/// neighbors. What each prints is logged to `commands.log`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Frr {
+    pub(crate) daemons: Vec<String>,
+    pub(crate) startup_config: Option<FileSource>,
+    // Further attempts at loading the startup config when vtysh fails.
+    pub(crate) retries: u32,
+    // Directory of the daemons, if set in the topology.
+    pub(crate) path: Option<String>,
+    // Whether the daemons wait for the router to be first used.
+    pub(crate) lazy: bool,
+    // vtysh commands run once the startup config is loaded.
+    pub(crate) commands: Vec<String>,
+}
+
+impl Default for Frr {
+    fn default() -> Self {
+        Self {
+            daemons: vec![],
+            startup_config: None,
+            retries: DEFAULT_RETRIES,
+            path: None,
+            lazy: false,
+            commands: vec![],
+        }
+    }
+}
+
+impl Frr {
+    /// The configuration files of FRR, as files to materialize in the
+    /// router.
+    pub(crate) fn files(&self) -> Vec<FileDrop> {
+        let mut daemons = String::from("# Written by netgen\n");
+        for daemon in self.daemons() {
+            let _ = writeln!(daemons, "{daemon}=yes");
+        }
+        let _ = writeln!(daemons, "vtysh_enable=yes");
+
+        let mut files = vec![
+            FileDrop {
+                dst: DAEMONS_FILE.to_string(),
+                source: FileSource::Inline(daemons),
+                mode: Some(0o644),
+            },
+            FileDrop {
+                dst: VTYSH_CONF.to_string(),
+                source: FileSource::Inline(
+                    "service integrated-vtysh-config\n".to_string(),
+                ),
+                mode: Some(0o644),
+            },
+        ];
+        if let Some(source) = &self.startup_config {
+            files.push(FileDrop {
+                dst: STARTUP_CONFIG.to_string(),
+                source: source.clone(),
+                mode: Some(0o640),
+            });
+        }
+        files
+    }
+
+    /// Directory the daemons are installed in.
+    pub(crate) fn dir(&self) -> String {
+        self.path.clone().unwrap_or_else(default_dir)
+    }
+
+    /// Daemons to start, in order. mgmtd only exists as of FRR 9.0.
+    pub(crate) fn daemons(&self) -> Vec<&str> {
+        let mgmtd = Path::new(&self.dir()).join("mgmtd").exists();
+        let base = ["mgmtd", "zebra", "staticd"]
+            .into_iter()
+            .filter(|daemon| *daemon != "mgmtd" || mgmtd);
+        base.chain(self.daemons.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Path of the binary of `daemon`.
+    pub(crate) fn binary(&self, daemon: &str) -> String {
+        format!("{}/{daemon}", self.dir())
+    }
+
+    /// Pushes the startup config (if any) to the daemons through vtysh,
+    /// once they all listen, retrying up to `retries` times, then looks for
+    /// lines the daemons dropped. Has to be called from within the router's
+    /// namespaces.
+    ///
+    /// Whether vtysh loaded the config is recorded in the topology state
+    /// for `status` to show, a config which does not load leaving the
+    /// daemons running. Dropped lines are only warned about: they are told
+    /// by comparing text, which FRR may rewrite.
+    pub(crate) fn load_startup_config(
+        &self,
+        router_name: &str,
+    ) -> NetResult<()> {
+        if self.startup_config.is_none() {
+            return Ok(());
+        }
+
+        let result = self.push_startup_config(router_name);
+        match &result {
+            Ok(()) => {
+                if let Err(err) = check_running_config(router_name) {
+                    warn!(router = %router_name, %err,
+                        "running config not checked");
+                }
+            }
+            Err(err) => {
+                warn!(router = %router_name, %err, "startup config not loaded")
+            }
+        }
+        TopologyState::record_config(
+            router_name,
+            result.err().map(|err| err.to_string()),
+        )
+    }
+
+    /// Runs `commands` through vtysh in a single session, logging what each
+    /// of them prints to the router's `commands.log`. Has to be called from
+    /// within the router's namespaces, once the daemons are started.
+    ///
+    /// Commands failing leave the router running, as a startup config which
+    /// does not load does.
+    pub(crate) fn run_commands(&self, router_name: &str) -> NetResult<()> {
+        if self.commands.is_empty() {
+            return Ok(());
+        }
+        self.wait_for_daemons(router_name)?;
+
+        // Commands are echoed after the prompt, which tells where the
+        // output of each starts.
+        let mut vtysh = Command::new("vtysh");
+        vtysh.arg("-E");
+        for command in &self.commands {
+            vtysh.args(["-c", command]);
+        }
+        let output = vtysh.output().map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to run vtysh on {router_name}: {err:?}"
+            ))
+        })?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        let mut log = String::new();
+        let mut commands = self.commands.iter().peekable();
+        for line in stdout.lines() {
+            if let Some(command) = commands.peek()
+                && is_echo(line, command)
+            {
+                debug!(router = %router_name, %command, "vtysh");
+                let _ = writeln!(log, "# {command}");
+                commands.next();
+                continue;
+            }
+            let _ = writeln!(log, "{line}");
+        }
+        log.push_str(&String::from_utf8_lossy(&output.stderr));
+
+        let path =
+            format!("{}/{router_name}/frr/{COMMANDS_LOG}", devices_ns_dir());
+        fs::write(&path, log).map_err(|err| {
+            NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+        })?;
+        if !output.status.success() {
+            warn!(router = %router_name, status = %output.status, %path,
+                "commands failed");
+        }
+        Ok(())
+    }
+
+    // Waits for all the daemons to listen on their vty sockets.
+    fn wait_for_daemons(&self, router_name: &str) -> NetResult<()> {
+        let deadline = Instant::now() + VTY_TIMEOUT;
+        for daemon in self.daemons() {
+            let socket = format!("{RUN_DIR}/{daemon}.vty");
+            while !Path::new(&socket).exists() {
+                if Instant::now() >= deadline {
+                    return Err(NetError::BasicError(format!(
+                        "{daemon} of {router_name} did not start within {}s",
+                        VTY_TIMEOUT.as_secs()
+                    )));
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+        Ok(())
+    }
+
+    fn push_startup_config(&self, router_name: &str) -> NetResult<()> {
+        self.wait_for_daemons(router_name)?;
+
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            let output = Command::new("vtysh")
+                .args(["-f", STARTUP_CONFIG])
+                .output()
+                .map_err(|err| {
+                    NetError::BasicError(format!(
+                        "Unable to run vtysh on {router_name}: {err:?}"
+                    ))
+                })?;
+            if output.status.success() {
+                return Ok(());
+            }
+
+            // vtysh reports the lines it rejects on stdout.
+            let reason = [output.stdout, output.stderr]
+                .iter()
+                .map(|out| String::from_utf8_lossy(out).trim().to_string())
+                .filter(|out| !out.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            if attempt == self.retries {
+                return Err(NetError::BasicError(format!(
+                    "Startup config of {router_name} failed to load after \
+                        {} attempts ({}): {reason}",
+                    attempt + 1,
+                    output.status
+                )));
+            }
+            attempt += 1;
+            debug!(router = %router_name, attempt, ?backoff, %reason,
+                "startup config failed to load, retrying");
+            std::thread::sleep(backoff);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+// Whether `line` is vtysh echoing `command` after its prompt, e.g.
+// "rt1(config)# router bgp 65001".
+fn is_echo(line: &str, command: &str) -> bool {
+    line.strip_suffix(command)
+        .and_then(|prompt| prompt.strip_suffix(' '))
+        .is_some_and(|prompt| prompt.ends_with('#') || prompt.ends_with('>'))
+}
+
+// Path of the lines of the startup config of `router_name` missing from
+// its running config, if any were.
+fn rejected_path(router_name: &str) -> String {
+    format!("{}/{router_name}/frr/{REJECTED_FILE}", devices_ns_dir())
+}
+
+// Compares the running config of the daemons with the startup config, the
+// lines they seemingly dropped being written to `rejected_path` and warned
+// about. The comparison is textual, so FRR rewriting a line its own way,
+// e.g. expanding an abbreviation, is reported as well: vtysh's exit status,
+// not this, tells whether the config loaded. Errors are those reading the
+// configs.
+fn check_running_config(router_name: &str) -> NetResult<()> {
+    let intended = fs::read_to_string(STARTUP_CONFIG).map_err(|err| {
+        NetError::BasicError(format!(
+            "Unable to read {STARTUP_CONFIG} of {router_name}: {err:?}"
+        ))
+    })?;
+    let output = Command::new("vtysh")
+        .args(["-c", "show running-config"])
+        .output()
+        .map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to run vtysh on {router_name}: {err:?}"
+            ))
+        })?;
+    if !output.status.success() {
+        return Err(NetError::BasicError(format!(
+            "Unable to read the running config of {router_name} ({})",
+            output.status
+        )));
+    }
+    let running = String::from_utf8_lossy(&output.stdout);
+
+    let path = rejected_path(router_name);
+    let rejected = rejected_lines(&intended, &running);
+    if rejected.is_empty() {
+        let _ = fs::remove_file(&path);
+        return Ok(());
+    }
+    for line in &rejected {
+        warn!(router = %router_name, %line, "line not in running config");
+    }
+    fs::write(&path, rejected.join("\n") + "\n").map_err(|err| {
+        NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+    })?;
+    events::record(
+        "frr",
+        router_name,
+        &format!(
+            "{} startup config line(s) not in the running config, see {path}",
+            rejected.len()
+        ),
+    );
+    Ok(())
+}
+
+// Lines of the `intended` config missing from the `running` one, those
+// within a section (e.g. `router ospf`) being prefixed by it, as in
+// "router ospf > network 10.0.0.0/24 area 0".
+//
+// Comments and the lines FRR writes on its own are not compared.
+fn rejected_lines(intended: &str, running: &str) -> Vec<String> {
+    let running = config_lines(running);
+    config_lines(intended)
+        .into_iter()
+        .filter(|line| !running.contains(line))
+        .collect()
+}
+
+fn config_lines(config: &str) -> Vec<String> {
+    let mut section = None;
+    let mut lines = vec![];
+    for line in config.lines() {
+        let trimmed = line.trim();
+        let ignored = trimmed.is_empty()
+            || trimmed.starts_with('!')
+            || trimmed.starts_with('#')
+            || IGNORED_LINES
+                .iter()
+                .any(|ignored| trimmed.starts_with(ignored));
+        if ignored {
+            continue;
+        }
+        // Sections end with "exit" or the next unindented line.
+        if trimmed == "exit" || trimmed == "end" {
+            section = None;
+            continue;
+        }
+        let line = match line.starts_with(char::is_whitespace) {
+            true => match &section {
+                Some(section) => format!("{section} > {trimmed}"),
+                None => trimmed.to_string(),
+            },
+            false => {
+                section = Some(trimmed.to_string());
+                trimmed.to_string()
+            }
+        };
+        lines.push(line);
+    }
+    lines
+}
+
+/// Bind-mounts the router's runtime directory onto `/var/run/frr`. Called
+/// from within the router's private mount namespace.
+pub(crate) fn mount_run_dir(router_name: &str) -> NetResult<()> {
+    let run_dir = format!("{}/{router_name}/frr", devices_ns_dir());
+    for dir in [run_dir.as_str(), RUN_DIR] {
+        fs::create_dir_all(dir).map_err(|err| {
+            NetError::BasicError(format!("Unable to create {dir}: {err:?}"))
+        })?;
+    }
+    mount(
+        Some(run_dir.as_str()),
+        RUN_DIR,
+        None::<&str>,
+        MsFlags::MS_BIND,
+        None::<&str>,
+    )
+    .map_err(|err| {
+        NetError::NamespaceError(NamespaceError::Mount {
+            ns_type: "frr runtime directory".to_string(),
+            device: router_name.to_string(),
+            source: err,
+        })
+    })
+}
+
+/// Locks the starting of the daemons of `router_name` by other processes,
+/// until dropped.
+pub(crate) fn start_lock(router_name: &str) -> NetResult<Flock<File>> {
+    let run_dir = format!("{}/{router_name}/frr", devices_ns_dir());
+    let path = format!("{run_dir}/start.lock");
+    let file = fs::create_dir_all(&run_dir)
+        .and_then(|()| {
+            OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&path)
+        })
+        .map_err(|err| {
+            NetError::BasicError(format!("Unable to open {path}: {err:?}"))
+        })?;
+    Flock::lock(file, FlockArg::LockExclusive).map_err(|(_, err)| {
+        NetError::BasicError(format!("Unable to lock {path}: {err:?}"))
+    })
+}
+
+/// Directory the daemons are installed in when `path` is not set: the one
+/// pinned in the state when the running topology was started, else the
+/// first of the usual ones or of PATH holding zebra, else `DEFAULT_PATH`.
+pub(crate) fn default_dir() -> String {
+    if let Ok(Some(state)) = TopologyState::load()
+        && let Some(dir) = state.frr_dir
+    {
+        return dir;
+    }
+    discover().unwrap_or_else(|| DEFAULT_PATH.to_string())
+}
+
+// The first directory zebra is found in, the usual ones before PATH.
+fn discover() -> Option<String> {
+    let path = std::env::var("PATH").unwrap_or_default();
+    SEARCH_DIRS
+        .iter()
+        .map(|dir| dir.to_string())
+        .chain(path.split(':').map(str::to_string))
+        .find(|dir| !dir.is_empty() && Path::new(dir).join("zebra").is_file())
+}
+
+/// zebra of `dir` as found now, to pin in the state.
+pub(crate) fn pin(dir: &str) -> BinaryRecord {
+    let path = format!("{dir}/zebra");
+    BinaryRecord {
+        version: version(&path),
+        modified: modified(&path),
+        path,
+    }
+}
+
+/// Warns about the binaries pinned in the state which changed since the
+/// topology was started, the daemons started from then on not running the
+/// same version as the others.
+pub(crate) fn check_pinned(state: &TopologyState) {
+    for pinned in &state.binaries {
+        if modified(&pinned.path) == pinned.modified {
+            continue;
+        }
+        let now = version(&pinned.path);
+        warn!(
+            path = %pinned.path,
+            pinned = pinned.version.as_deref().unwrap_or("unknown"),
+            now = now.as_deref().unwrap_or("missing"),
+            "binary changed since the topology was started"
+        );
+    }
+}
+
+// First line `<binary> --version` prints, e.g. "zebra version 9.1".
+fn version(path: &str) -> Option<String> {
+    let output = Command::new(path).arg("--version").output().ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+}
+
+// Modification time of `path` in seconds since the Unix epoch.
+fn modified(path: &str) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
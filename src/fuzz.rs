@@ -0,0 +1,22 @@
+//! Entry points for fuzzing the topology parser, behind the `fuzz` feature.
+//!
+//! Parsing never touches the host: no namespace, link or process is created
+//! until the topology is started.
+
+use crate::NetResult;
+use crate::error::NetError;
+use crate::topology::{Topology, TopologyParser};
+
+/// Topology files known to parse, to seed a fuzzing corpus with.
+pub const SEEDS: &[&str] = &[include_str!("../assets/sample-topo.yml")];
+
+/// Parses `data` as a topology file, templates included, the way
+/// `netgen start` does.
+///
+/// Malformed input is reported as an error, any panic being a bug.
+pub fn parse_topology(data: &[u8]) -> NetResult<Topology> {
+    let yaml = std::str::from_utf8(data).map_err(|err| {
+        NetError::BasicError(format!("Topology file is not UTF-8: {err}"))
+    })?;
+    TopologyParser::from_yaml_str(yaml)
+}
@@ -0,0 +1,128 @@
+//! Generating topologies from code, for the test frameworks built on netgen
+//! to synthesize labs without shelling out to `netgen init` or `quick`.
+//!
+//! Regular topologies come from a `Skeleton`, others from a template and
+//! its variables, either way both as a parsed `Topology` and as the YAML it
+//! was parsed from:
+//!
+//! ```no_run
+//! use netgen::generate::{self, Shape, Skeleton};
+//! use netgen::testing::OwnedTopology;
+//!
+//! # fn main() -> netgen::NetResult<()> {
+//! let skeleton =
+//!     Skeleton::quick(4, Shape::Ring, vec!["ospfd".into()], "/tmp/lab");
+//! let generated = generate::regular(&skeleton)?;
+//! generated.write("/tmp/lab/topology.yml")?;
+//! let lab = OwnedTopology::start(generated.topology)?;
+//! # drop(lab);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Parsing never touches the host, nor do the generated startup configs
+//! need to be written for it to succeed.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::NetResult;
+use crate::error::NetError;
+use crate::template::{self, Value};
+use crate::topology::{Topology, TopologyParser};
+
+pub use crate::init::{Addressing, Shape, Skeleton};
+
+// ==== struct Generated ====
+
+/// A generated topology, parsed and as YAML, with the files it refers to.
+#[derive(Debug)]
+pub struct Generated {
+    pub topology: Topology,
+    // The topology file, templates rendered.
+    pub yaml: String,
+    // Startup configs of the routers, as (path, contents).
+    pub files: Vec<(String, String)>,
+}
+
+impl Generated {
+    // Parses `yaml`, rendered already.
+    fn parse(yaml: String, files: Vec<(String, String)>) -> NetResult<Self> {
+        let topology = TopologyParser::from_yaml_str(&yaml)?;
+        Ok(Self {
+            topology,
+            yaml,
+            files,
+        })
+    }
+
+    /// Writes the topology file to `path` and the startup configs where it
+    /// expects them, creating their directories.
+    pub fn write(&self, path: impl AsRef<Path>) -> NetResult<()> {
+        let path = path.as_ref();
+        let files = self
+            .files
+            .iter()
+            .map(|(path, contents)| (Path::new(path), contents))
+            .chain([(path, &self.yaml)]);
+        for (path, contents) in files {
+            if let Some(dir) = path.parent()
+                && !dir.as_os_str().is_empty()
+            {
+                fs::create_dir_all(dir).map_err(|err| {
+                    NetError::BasicError(format!(
+                        "Unable to create {}: {err:?}",
+                        dir.display()
+                    ))
+                })?;
+            }
+            fs::write(path, contents).map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to write {}: {err:?}",
+                    path.display()
+                ))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// The regular topology `skeleton` describes, as `netgen init` writes it,
+/// its startup configs under the skeleton's `configs_dir`.
+pub fn regular(skeleton: &Skeleton) -> NetResult<Generated> {
+    skeleton.check()?;
+    let files = skeleton
+        .startup_configs()
+        .into_iter()
+        .map(|(router, config)| {
+            (format!("{}/{router}.conf", skeleton.configs_dir), config)
+        })
+        .collect();
+    Generated::parse(skeleton.topology_yaml(), files)
+}
+
+/// The topology the template `source` renders to with `vars` predefined.
+pub fn from_template(
+    source: &str,
+    vars: &BTreeMap<String, Value>,
+) -> NetResult<Generated> {
+    Generated::parse(template::render_with(source, vars)?, vec![])
+}
+
+/// The topologies the template `source` renders to with each of
+/// `var_sets`, in order, e.g. the same fabric at growing sizes.
+pub fn batch(
+    source: &str,
+    var_sets: &[BTreeMap<String, Value>],
+) -> NetResult<Vec<Generated>> {
+    var_sets
+        .iter()
+        .map(|vars| from_template(source, vars))
+        .collect()
+}
+
+/// The topologies of `skeletons`, in order.
+pub fn regular_batch(skeletons: &[Skeleton]) -> NetResult<Vec<Generated>> {
+    skeletons.iter().map(regular).collect()
+}
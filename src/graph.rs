@@ -0,0 +1,207 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use petgraph::algo::has_path_connecting;
+use petgraph::graph::{EdgeIndex, NodeIndex, UnGraph};
+use petgraph::unionfind::UnionFind;
+use petgraph::visit::{EdgeFiltered, EdgeRef};
+
+use crate::link::Link;
+
+// ==== struct TopologyGraph ====
+
+/// The topology as an undirected graph, with one edge per link.
+///
+/// Switches are nodes like routers are, so nodes on the same switch are
+/// two hops away from each other.
+pub(crate) struct TopologyGraph<'a> {
+    graph: UnGraph<&'a str, &'a Link>,
+    indexes: BTreeMap<&'a str, NodeIndex>,
+}
+
+impl<'a> TopologyGraph<'a> {
+    pub(crate) fn new(
+        nodes: impl IntoIterator<Item = &'a str>,
+        links: &'a [Link],
+    ) -> Self {
+        let mut graph = UnGraph::default();
+        let indexes: BTreeMap<&str, NodeIndex> = nodes
+            .into_iter()
+            .map(|name| (name, graph.add_node(name)))
+            .collect();
+        for link in links {
+            // Link ends are resolved when parsing, both nodes exist.
+            if let (Some(&src), Some(&dst)) = (
+                indexes.get(link.src.node.as_str()),
+                indexes.get(link.dst.node.as_str()),
+            ) {
+                graph.add_edge(src, dst, link);
+            }
+        }
+        Self { graph, indexes }
+    }
+
+    /// Nodes sharing a link with `node`, by name.
+    pub(crate) fn neighbors(&self, node: &str) -> Vec<&'a str> {
+        let Some(&index) = self.indexes.get(node) else {
+            return vec![];
+        };
+        let mut neighbors: Vec<&str> = self
+            .graph
+            .neighbors(index)
+            .filter(|&neighbor| neighbor != index)
+            .map(|neighbor| self.graph[neighbor])
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    pub(crate) fn path_exists(&self, from: &str, to: &str) -> bool {
+        match (self.indexes.get(from), self.indexes.get(to)) {
+            (Some(&from), Some(&to)) => {
+                has_path_connecting(&self.graph, from, to, None)
+            }
+            _ => false,
+        }
+    }
+
+    /// Groups of nodes connected to one another, each ordered by name and
+    /// ordered by their first node.
+    pub(crate) fn segments(&self) -> Vec<Vec<&'a str>> {
+        let mut segments = UnionFind::new(self.graph.node_count());
+        for edge in self.graph.edge_references() {
+            segments.union(edge.source().index(), edge.target().index());
+        }
+
+        let mut grouped: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+        for (&name, index) in &self.indexes {
+            grouped
+                .entry(segments.find(index.index()))
+                .or_default()
+                .push(name);
+        }
+        // Nodes are visited by name, each group is ordered already.
+        let mut segments: Vec<Vec<&str>> = grouped.into_values().collect();
+        segments.sort_unstable();
+        segments
+    }
+
+    /// Whether the ends of `link` would no longer be connected without it,
+    /// e.g. because it is the last link to one of them.
+    pub(crate) fn disconnects(&self, link: &Link) -> bool {
+        let (Some(&src), Some(&dst)) = (
+            self.indexes.get(link.src.node.as_str()),
+            self.indexes.get(link.dst.node.as_str()),
+        ) else {
+            return false;
+        };
+        let without_link =
+            EdgeFiltered::from_fn(&self.graph, |edge| *edge.weight() != link);
+        !has_path_connecting(&without_link, src, dst, None)
+    }
+
+    /// Links to cut so that no node of a group can reach a node of another
+    /// group. Nodes in none of the groups, e.g. switches, end up on
+    /// whichever side needs the fewest links cut.
+    ///
+    /// Each group is separated from the groups after it with a minimum
+    /// cut, so the result is minimal when there are two groups.
+    pub(crate) fn cut_set(&self, groups: &[Vec<&str>]) -> Vec<&'a Link> {
+        let indexes = |group: &[&str]| -> BTreeSet<NodeIndex> {
+            group
+                .iter()
+                .filter_map(|name| self.indexes.get(name).copied())
+                .collect()
+        };
+
+        let mut cut = BTreeSet::new();
+        for (position, group) in groups.iter().enumerate() {
+            let sources = indexes(group);
+            let sinks: BTreeSet<NodeIndex> = groups[position + 1..]
+                .iter()
+                .flat_map(|group| indexes(group))
+                .collect();
+            if sinks.is_empty() {
+                break;
+            }
+            cut.extend(self.min_cut(&sources, &sinks, &cut));
+        }
+        cut.into_iter().map(|edge| self.graph[edge]).collect()
+    }
+
+    // Minimum set of edges, beyond the `removed` ones, separating `sources`
+    // from `sinks`. Every link carries a single unit of flow, in either
+    // direction.
+    fn min_cut(
+        &self,
+        sources: &BTreeSet<NodeIndex>,
+        sinks: &BTreeSet<NodeIndex>,
+        removed: &BTreeSet<EdgeIndex>,
+    ) -> Vec<EdgeIndex> {
+        // Flow on each edge, positive going from its source to its target.
+        let mut flow = vec![0i8; self.graph.edge_count()];
+        loop {
+            let reached = self.augmenting_search(sources, &flow, removed);
+            let Some(&sink) =
+                sinks.iter().find(|sink| reached.contains_key(sink))
+            else {
+                // The nodes still reachable are the source side of the cut.
+                return self
+                    .graph
+                    .edge_references()
+                    .filter(|edge| {
+                        !removed.contains(&edge.id())
+                            && reached.contains_key(&edge.source())
+                                != reached.contains_key(&edge.target())
+                    })
+                    .map(|edge| edge.id())
+                    .collect();
+            };
+
+            // Push one more unit of flow along the path found.
+            let mut node = sink;
+            while let Some(&Some((edge, from))) = reached.get(&node) {
+                match self.graph.edge_endpoints(edge) {
+                    Some((source, _)) if source == from => {
+                        flow[edge.index()] += 1
+                    }
+                    _ => flow[edge.index()] -= 1,
+                }
+                node = from;
+            }
+        }
+    }
+
+    // Nodes reachable from `sources` through edges with spare capacity,
+    // along with the edge and node each was reached through.
+    fn augmenting_search(
+        &self,
+        sources: &BTreeSet<NodeIndex>,
+        flow: &[i8],
+        removed: &BTreeSet<EdgeIndex>,
+    ) -> BTreeMap<NodeIndex, Option<(EdgeIndex, NodeIndex)>> {
+        let mut reached: BTreeMap<NodeIndex, Option<(EdgeIndex, NodeIndex)>> =
+            sources.iter().map(|&source| (source, None)).collect();
+        let mut queue: VecDeque<NodeIndex> = sources.iter().copied().collect();
+        while let Some(node) = queue.pop_front() {
+            for edge in self.graph.edges(node) {
+                if removed.contains(&edge.id()) {
+                    continue;
+                }
+                // Flow is counted in the direction the edge was added in.
+                let (next, spare) = match self.graph.edge_endpoints(edge.id()) {
+                    Some((source, target)) if source == node => {
+                        (target, flow[edge.id().index()] < 1)
+                    }
+                    Some((source, _)) => (source, flow[edge.id().index()] > -1),
+                    None => continue,
+                };
+                if spare && !reached.contains_key(&next) {
+                    reached.insert(next, Some((edge.id(), node)));
+                    queue.push_back(next);
+                }
+            }
+        }
+        reached
+    }
+}
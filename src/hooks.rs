@@ -0,0 +1,61 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::link::Link;
+
+type NodeHook = Arc<dyn Fn(&str) + Send + Sync>;
+type LinkHook = Arc<dyn Fn(&Link) + Send + Sync>;
+type DaemonHook = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+// ==== struct Hooks ====
+
+// Callbacks registered on a topology by the application embedding netgen,
+// run in the order they were registered.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    node_up: Vec<NodeHook>,
+    link_up: Vec<LinkHook>,
+    daemon_ready: Vec<DaemonHook>,
+}
+
+impl Hooks {
+    pub(crate) fn add_node_up(&mut self, hook: NodeHook) {
+        self.node_up.push(hook);
+    }
+
+    pub(crate) fn add_link_up(&mut self, hook: LinkHook) {
+        self.link_up.push(hook);
+    }
+
+    pub(crate) fn add_daemon_ready(&mut self, hook: DaemonHook) {
+        self.daemon_ready.push(hook);
+    }
+
+    pub(crate) fn node_up(&self, node: &str) {
+        for hook in &self.node_up {
+            hook(node);
+        }
+    }
+
+    pub(crate) fn link_up(&self, link: &Link) {
+        for hook in &self.link_up {
+            hook(link);
+        }
+    }
+
+    pub(crate) fn daemon_ready(&self, node: &str, daemon: &str) {
+        for hook in &self.daemon_ready {
+            hook(node, daemon);
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("node_up", &self.node_up.len())
+            .field("link_up", &self.link_up.len())
+            .field("daemon_ready", &self.daemon_ready.len())
+            .finish()
+    }
+}
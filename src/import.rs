@@ -0,0 +1,704 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd};
+use std::path::{Path, PathBuf};
+
+use futures_util::{StreamExt, TryStreamExt};
+use ipnetwork::IpNetwork;
+use netlink_packet_route::RouteNetlinkMessage;
+use netlink_packet_route::address::{
+    AddressAttribute, AddressMessage, AddressScope,
+};
+use netlink_packet_route::link::{
+    InfoKind, LinkAttribute, LinkInfo, LinkMessage,
+};
+use netlink_packet_route::nsid::{NsidAttribute, NsidMessage};
+use nix::sched::{CloneFlags, setns};
+use rtnetlink::packet_core::{NLM_F_REQUEST, NetlinkMessage, NetlinkPayload};
+use rtnetlink::{Handle, new_connection};
+use tracing::{debug, warn};
+use yaml_rust2::YamlEmitter;
+use yaml_rust2::yaml::{Array, Hash, Yaml};
+
+use crate::error::{LinkError, NamespaceError, NetError};
+use crate::topology::check_iface_name;
+use crate::{NetResult, with_run_dir};
+
+/// Where `ip netns add` bind-mounts the named namespaces.
+pub const NETNS_RUN_DIR: &str = "/var/run/netns";
+
+// Name given to the namespace the command runs in.
+const HOST_NS: &str = "";
+// Namespace of the calling thread.
+const HOST_NS_PATH: &str = "/proc/thread-self/ns/net";
+
+#[derive(Debug, Clone)]
+struct NsInterface {
+    index: u32,
+    name: String,
+    is_veth: bool,
+    is_bridge: bool,
+    // Index of the veth peer, relative to the peer's namespace.
+    peer: Option<u32>,
+    // Id the peer's namespace is known by in this one, see `Snapshot`, None
+    // when the peer is in the same namespace.
+    peer_netnsid: Option<i32>,
+    controller: Option<u32>,
+    addresses: Vec<IpNetwork>,
+}
+
+// ==== struct Snapshot ====
+
+// The interfaces of a namespace, and the ids it knows the other namespaces
+// by, which the veths name the namespace of their peer with.
+#[derive(Debug, Default)]
+struct Snapshot {
+    interfaces: Vec<NsInterface>,
+    // Namespace id -> name of the namespace.
+    nsids: BTreeMap<i32, String>,
+}
+
+/// Inspects the named namespaces under `NETNS_RUN_DIR` and emits a topology
+/// YAML approximating them.
+///
+/// Every namespace becomes a router. Bridges in the host namespace become
+/// switches, and veth pairs become links. Since interface indexes are only
+/// unique within a namespace, the peer of a veth is looked up in the
+/// namespace its link-netnsid names.
+pub fn import_from_host() -> NetResult<String> {
+    // Opened from the calling thread, for it to be the host namespace.
+    let mut paths = vec![(HOST_NS.to_string(), HOST_NS_PATH.to_string())];
+    if let Ok(entries) = fs::read_dir(NETNS_RUN_DIR) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path().to_string_lossy().to_string();
+            paths.push((name, path));
+        }
+    }
+    let files = paths
+        .into_iter()
+        .map(|(name, path)| {
+            let file =
+                File::open(&path).map_err(|err| NamespaceError::FileOpen {
+                    path: path.clone(),
+                    source: err,
+                })?;
+            Ok((name, path, file))
+        })
+        .collect::<NetResult<Vec<(String, String, File)>>>()?;
+
+    let mut namespaces: BTreeMap<String, Snapshot> = BTreeMap::new();
+    for (name, path, file) in &files {
+        let snapshot = if name == HOST_NS {
+            snapshot(&files)?
+        } else {
+            debug!(namespace = %name, "importing");
+            snapshot_ns(path, file, &files)?
+        };
+        namespaces.insert(name.clone(), snapshot);
+    }
+
+    Ok(to_yaml(&namespaces))
+}
+
+// Takes a snapshot of the namespace of `file` from a separate thread, so
+// that the calling thread never leaves its own namespace.
+fn snapshot_ns(
+    path: &str,
+    file: &File,
+    namespaces: &[(String, String, File)],
+) -> NetResult<Snapshot> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(with_run_dir(move || {
+                setns(file.as_fd(), CloneFlags::CLONE_NEWNET).map_err(
+                    |err| NamespaceError::Entry {
+                        device: path.to_string(),
+                        source: err,
+                    },
+                )?;
+                snapshot(namespaces)
+            }))
+            .join()
+            .map_err(|_| {
+                NetError::BasicError(
+                    "namespace snapshot thread panicked".to_string(),
+                )
+            })?
+    })
+}
+
+// Id the current namespace knows the namespace of `file` by, if it gave it
+// one, as it does to the namespaces of the peers of its veths.
+async fn nsid_of(handle: &mut Handle, file: &File) -> NetResult<Option<i32>> {
+    let mut message = NsidMessage::default();
+    message
+        .attributes
+        .push(NsidAttribute::Fd(file.as_raw_fd() as u32));
+    let mut request =
+        NetlinkMessage::from(RouteNetlinkMessage::GetNsId(message));
+    request.header.flags = NLM_F_REQUEST;
+
+    let mut response =
+        handle
+            .request(request)
+            .map_err(|err| LinkError::ExecuteFailed {
+                operation: "import->get-nsid".to_string(),
+                source: err,
+            })?;
+    while let Some(message) = response.next().await {
+        if let NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewNsId(
+            message,
+        )) = message.payload
+        {
+            return Ok(message.attributes.iter().find_map(|attr| match attr {
+                NsidAttribute::Id(id) if *id >= 0 => Some(*id),
+                _ => None,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+// Dumps the interfaces and addresses of the current namespace, along with
+// the ids it knows `namespaces` by.
+fn snapshot(namespaces: &[(String, String, File)]) -> NetResult<Snapshot> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| {
+            NetError::BasicError(format!(
+                "Failed to create tokio runtime: {err:?}"
+            ))
+        })?;
+
+    runtime.block_on(async {
+        let (connection, mut handle, _) = new_connection()
+            .map_err(|err| LinkError::ConnectionFailed { source: err })?;
+        tokio::spawn(connection);
+
+        let mut nsids = BTreeMap::new();
+        for (name, _, file) in namespaces {
+            if let Some(id) = nsid_of(&mut handle, file).await? {
+                nsids.insert(id, name.clone());
+            }
+        }
+
+        let links: Vec<LinkMessage> =
+            handle.link().get().execute().try_collect().await.map_err(
+                |err| LinkError::ExecuteFailed {
+                    operation: "import->dump-links".to_string(),
+                    source: err,
+                },
+            )?;
+        let addresses: Vec<AddressMessage> = handle
+            .address()
+            .get()
+            .execute()
+            .try_collect()
+            .await
+            .map_err(|err| LinkError::ExecuteFailed {
+                operation: "import->dump-addresses".to_string(),
+                source: err,
+            })?;
+
+        let mut interfaces: Vec<NsInterface> =
+            links.iter().map(to_interface).collect();
+
+        for message in &addresses {
+            // Link-local and host addresses are created by the kernel.
+            if message.header.scope != AddressScope::Universe {
+                continue;
+            }
+            let Some(addr) =
+                message.attributes.iter().find_map(|attr| match attr {
+                    AddressAttribute::Address(addr) => Some(*addr),
+                    _ => None,
+                })
+            else {
+                continue;
+            };
+            if let Some(iface) = interfaces
+                .iter_mut()
+                .find(|iface| iface.index == message.header.index)
+                && let Ok(network) =
+                    IpNetwork::new(addr, message.header.prefix_len)
+            {
+                iface.addresses.push(network);
+            }
+        }
+        Ok(Snapshot { interfaces, nsids })
+    })
+}
+
+fn to_interface(message: &LinkMessage) -> NsInterface {
+    let mut iface = NsInterface {
+        index: message.header.index,
+        name: String::new(),
+        is_veth: false,
+        is_bridge: false,
+        peer: None,
+        peer_netnsid: None,
+        controller: None,
+        addresses: vec![],
+    };
+
+    for attr in &message.attributes {
+        match attr {
+            LinkAttribute::IfName(name) => iface.name = name.clone(),
+            LinkAttribute::Link(peer) => iface.peer = Some(*peer),
+            LinkAttribute::LinkNetNsId(id) => iface.peer_netnsid = Some(*id),
+            LinkAttribute::Controller(index) => iface.controller = Some(*index),
+            LinkAttribute::LinkInfo(infos) => {
+                for info in infos {
+                    match info {
+                        LinkInfo::Kind(InfoKind::Veth) => iface.is_veth = true,
+                        LinkInfo::Kind(InfoKind::Bridge) => {
+                            iface.is_bridge = true
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    iface
+}
+
+fn to_yaml(namespaces: &BTreeMap<String, Snapshot>) -> String {
+    let host = &namespaces[HOST_NS].interfaces;
+
+    // Routers.
+    let mut routers = Hash::new();
+    for (ns_name, snapshot) in namespaces {
+        if ns_name == HOST_NS {
+            continue;
+        }
+        let mut ifaces = Hash::new();
+        for iface in &snapshot.interfaces {
+            if iface.name == "lo" && iface.addresses.is_empty() {
+                continue;
+            }
+            ifaces.insert(str_yaml(&iface.name), addresses_yaml(iface));
+        }
+        let mut router = Hash::new();
+        router.insert(str_yaml("interfaces"), Yaml::Hash(ifaces));
+        routers.insert(str_yaml(ns_name), Yaml::Hash(router));
+    }
+
+    // Switches, from the bridges in the host namespace.
+    let mut switches = Hash::new();
+    for bridge in host.iter().filter(|iface| iface.is_bridge) {
+        let mut ports = Hash::new();
+        for port in host
+            .iter()
+            .filter(|iface| iface.controller == Some(bridge.index))
+        {
+            ports.insert(str_yaml(&port.name), addresses_yaml(port));
+        }
+        let mut switch = Hash::new();
+        switch.insert(str_yaml("interfaces"), Yaml::Hash(ports));
+        switches.insert(str_yaml(&bridge.name), Yaml::Hash(switch));
+    }
+
+    // Links, from the veth pairs. A veth names the namespace of its peer by
+    // an id of its own namespace, and the peer by its index there; ends in
+    // the same namespace as their peer, which have no such id, or whose peer
+    // is in a namespace left out are skipped.
+    let mut links = Array::new();
+    for (src_ns, snapshot) in namespaces {
+        for src in snapshot.interfaces.iter().filter(|iface| iface.is_veth) {
+            let Some(dst_ns) =
+                src.peer_netnsid.and_then(|id| snapshot.nsids.get(&id))
+            else {
+                continue;
+            };
+            // Each pair once, from its first end.
+            if (dst_ns, src.peer) < (src_ns, Some(src.index)) {
+                continue;
+            }
+            let Some(dst) = namespaces.get(dst_ns).and_then(|peer_ns| {
+                peer_ns
+                    .interfaces
+                    .iter()
+                    .find(|iface| Some(iface.index) == src.peer)
+            }) else {
+                continue;
+            };
+
+            // Ends in the host namespace are only representable when they
+            // are bridge ports.
+            let endpoint = |ns: &str, iface: &NsInterface| {
+                if ns != HOST_NS {
+                    return Some(ns.to_string());
+                }
+                let controller = iface.controller?;
+                host.iter()
+                    .find(|bridge| bridge.index == controller)
+                    .map(|bridge| bridge.name.clone())
+            };
+            let (Some(src_device), Some(dst_device)) =
+                (endpoint(src_ns, src), endpoint(dst_ns, dst))
+            else {
+                continue;
+            };
+
+            let mut link = Hash::new();
+            link.insert(str_yaml("src-device"), str_yaml(&src_device));
+            link.insert(str_yaml("src-iface"), str_yaml(&src.name));
+            link.insert(str_yaml("dst-device"), str_yaml(&dst_device));
+            link.insert(str_yaml("dst-iface"), str_yaml(&dst.name));
+            links.push(Yaml::Hash(link));
+        }
+    }
+
+    let mut topology = Hash::new();
+    topology.insert(str_yaml("routers"), Yaml::Hash(routers));
+    if !switches.is_empty() {
+        topology.insert(str_yaml("switches"), Yaml::Hash(switches));
+    }
+    topology.insert(str_yaml("links"), Yaml::Array(links));
+
+    let mut output = String::new();
+    let _ = YamlEmitter::new(&mut output).dump(&Yaml::Hash(topology));
+    output.push('\n');
+    output
+}
+
+fn str_yaml(value: &str) -> Yaml {
+    Yaml::String(value.to_string())
+}
+
+fn addresses_yaml(iface: &NsInterface) -> Yaml {
+    if iface.addresses.is_empty() {
+        return Yaml::Null;
+    }
+    Yaml::Array(
+        iface
+            .addresses
+            .iter()
+            .map(|addr| Yaml::String(addr.to_string()))
+            .collect(),
+    )
+}
+
+// ==== struct ConfigRouter ====
+
+// A router as read from its configuration file.
+#[derive(Debug, Default)]
+struct ConfigRouter {
+    name: String,
+    // The configuration file, loaded at startup when it is FRR's.
+    path: PathBuf,
+    // FRR daemons the configuration needs, None if it is not FRR's.
+    daemons: Option<BTreeSet<&'static str>>,
+    interfaces: BTreeMap<String, Vec<IpNetwork>>,
+}
+
+// FRR daemons, by the first words of the statements configuring them.
+const FRR_DAEMONS: &[(&str, &str)] = &[
+    ("router bgp", "bgpd"),
+    ("router ospf", "ospfd"),
+    ("router ospf6", "ospf6d"),
+    ("router isis", "isisd"),
+    ("router rip", "ripd"),
+    ("router ripng", "ripngd"),
+    ("router eigrp", "eigrpd"),
+    ("router babel", "babeld"),
+    ("router openfabric", "fabricd"),
+    ("router pim", "pimd"),
+    ("mpls ldp", "ldpd"),
+    ("bfd", "bfdd"),
+];
+
+/// Reads a directory of router configurations, FRR's or Holo's, and emits
+/// a draft topology YAML with their interfaces, addresses and the links
+/// between them.
+///
+/// Every file of `dir` is a router, as is every subdirectory holding a
+/// `frr.conf`, named after its `hostname` or else the file. Interfaces
+/// sharing a subnet are linked, through a switch when there are more than
+/// two of them. The FRR daemons configured are enabled, with the file as
+/// the startup config.
+pub fn import_from_configs(dir: &Path) -> NetResult<String> {
+    let read_err = |path: &Path, err: io::Error| {
+        NetError::BasicError(format!(
+            "Unable to read {}: {err:?}",
+            path.display()
+        ))
+    };
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|err| read_err(dir, err))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            !path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .filter_map(|path| match path.is_dir() {
+            true => Some(path.join("frr.conf")).filter(|path| path.is_file()),
+            false => Some(path),
+        })
+        .collect();
+    paths.sort();
+
+    let mut routers: BTreeMap<String, ConfigRouter> = BTreeMap::new();
+    for path in paths {
+        let contents =
+            fs::read_to_string(&path).map_err(|err| read_err(&path, err))?;
+        // "rt1.conf" or "rt1/frr.conf".
+        let file = match path.file_name() {
+            Some(name) if name == "frr.conf" => path.parent(),
+            _ => Some(path.as_path()),
+        };
+        let default_name = file
+            .and_then(Path::file_stem)
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let router = parse_config(&default_name, &path, &contents);
+        debug!(router = %router.name, path = %path.display(), "imported");
+        if routers.contains_key(&router.name) {
+            warn!(router = %router.name, path = %path.display(),
+                "router configured twice, skipped");
+            continue;
+        }
+        routers.insert(router.name.clone(), router);
+    }
+    for router in routers.values_mut() {
+        rename_interfaces(router);
+    }
+
+    Ok(configs_to_yaml(&routers))
+}
+
+/// The addresses a configuration, FRR's or Holo's, sets on the interfaces,
+/// by interface name.
+pub(crate) fn config_addresses(
+    contents: &str,
+) -> BTreeMap<String, Vec<IpNetwork>> {
+    parse_config("", Path::new(""), contents).interfaces
+}
+
+// Parses a configuration file, FRR's or Holo's, line by line: statements
+// are recognized by their first words whatever their nesting, addresses
+// being of the last interface entered.
+fn parse_config(
+    default_name: &str,
+    path: &Path,
+    contents: &str,
+) -> ConfigRouter {
+    let mut router = ConfigRouter {
+        name: default_name.to_string(),
+        path: path.to_path_buf(),
+        ..Default::default()
+    };
+    let mut daemons = BTreeSet::new();
+    let mut frr = false;
+    let mut iface: Option<String> = None;
+
+    for line in contents.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let nested = line.starts_with(char::is_whitespace);
+        match words.as_slice() {
+            [] => {}
+            ["hostname", name, ..] if !nested => {
+                router.name = name.to_string();
+            }
+            ["frr", "version" | "defaults", ..] => frr = true,
+            // "interface eth0" for FRR, "interfaces interface eth0" or
+            // "interface eth0" nested under "interfaces" for Holo.
+            ["interface", name, ..] | ["interfaces", "interface", name, ..] => {
+                iface = Some(name.to_string());
+                router.interfaces.entry(name.to_string()).or_default();
+            }
+            ["ip" | "ipv4" | "ipv6", "address", address, rest @ ..] => {
+                let Some(name) = &iface else {
+                    continue;
+                };
+                let network = match rest {
+                    ["prefix-length", len, ..] => {
+                        len.parse().ok().and_then(|len| {
+                            let address = address.parse().ok()?;
+                            IpNetwork::new(address, len).ok()
+                        })
+                    }
+                    _ => address.parse().ok(),
+                };
+                match network {
+                    Some(network) => router
+                        .interfaces
+                        .entry(name.clone())
+                        .or_default()
+                        .push(network),
+                    None => warn!(router = %router.name, %line,
+                        "address not understood, skipped"),
+                }
+            }
+            ["exit" | "!", ..] => iface = None,
+            _ if !nested => {
+                iface = None;
+                if let Some((_, daemon)) =
+                    FRR_DAEMONS.iter().find(|(statement, _)| {
+                        let statement: Vec<&str> =
+                            statement.split(' ').collect();
+                        words.starts_with(&statement)
+                    })
+                {
+                    daemons.insert(*daemon);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Holo's configs have nothing of the sort.
+    if frr || !daemons.is_empty() {
+        router.daemons = Some(daemons);
+    }
+    router
+}
+
+// Renames the interfaces the kernel would not accept, e.g.
+// "GigabitEthernet0/0", to "eth<n>".
+fn rename_interfaces(router: &mut ConfigRouter) {
+    let invalid: Vec<String> = router
+        .interfaces
+        .keys()
+        .filter(|name| check_iface_name(name).is_err())
+        .cloned()
+        .collect();
+    let mut index = 0;
+    for old in invalid {
+        let new = loop {
+            let new = format!("eth{index}");
+            index += 1;
+            if !router.interfaces.contains_key(&new) {
+                break new;
+            }
+        };
+        warn!(router = %router.name, %old, %new,
+            "interface renamed, the startup config still uses its old name");
+        if let Some(addresses) = router.interfaces.remove(&old) {
+            router.interfaces.insert(new, addresses);
+        }
+    }
+}
+
+fn configs_to_yaml(routers: &BTreeMap<String, ConfigRouter>) -> String {
+    // Interfaces by the subnets they are on, host routes excepted.
+    let mut subnets: BTreeMap<IpNetwork, Vec<(&str, &str)>> = BTreeMap::new();
+    for router in routers.values() {
+        for (iface, addresses) in &router.interfaces {
+            if iface == "lo" {
+                continue;
+            }
+            for address in addresses {
+                let host_len = if address.is_ipv4() { 32 } else { 128 };
+                if address.prefix() == host_len {
+                    continue;
+                }
+                let Ok(subnet) =
+                    IpNetwork::new(address.network(), address.prefix())
+                else {
+                    continue;
+                };
+                let ends = subnets.entry(subnet).or_default();
+                if !ends.contains(&(router.name.as_str(), iface.as_str())) {
+                    ends.push((&router.name, iface));
+                }
+            }
+        }
+    }
+
+    // An interface is linked once, through the first of its subnets, the
+    // other families being on the same wire.
+    let mut linked: BTreeSet<(&str, &str)> = BTreeSet::new();
+    let mut links = Array::new();
+    let mut switches = Hash::new();
+    let link_yaml = |src: (&str, &str), dst: (&str, &str)| {
+        let mut link = Hash::new();
+        link.insert(str_yaml("src-device"), str_yaml(src.0));
+        link.insert(str_yaml("src-iface"), str_yaml(src.1));
+        link.insert(str_yaml("dst-device"), str_yaml(dst.0));
+        link.insert(str_yaml("dst-iface"), str_yaml(dst.1));
+        Yaml::Hash(link)
+    };
+    for ends in subnets.values() {
+        let ends: Vec<(&str, &str)> = ends
+            .iter()
+            .filter(|end| !linked.contains(*end))
+            .copied()
+            .collect();
+        match ends.as_slice() {
+            [] | [_] => {}
+            [src, dst] => links.push(link_yaml(*src, *dst)),
+            _ => {
+                let switch = format!("sw{}", switches.len() + 1);
+                let mut ports = Hash::new();
+                for (index, end) in ends.iter().enumerate() {
+                    let port = Some(format!("eth-{}", end.0))
+                        .filter(|port| {
+                            check_iface_name(port).is_ok()
+                                && !ports.contains_key(&str_yaml(port))
+                        })
+                        .unwrap_or_else(|| format!("port{}", index + 1));
+                    links.push(link_yaml(
+                        *end,
+                        (switch.as_str(), port.as_str()),
+                    ));
+                    ports.insert(str_yaml(&port), Yaml::Null);
+                }
+                let mut config = Hash::new();
+                config.insert(str_yaml("interfaces"), Yaml::Hash(ports));
+                switches.insert(str_yaml(&switch), Yaml::Hash(config));
+            }
+        }
+        linked.extend(ends);
+    }
+
+    let mut routers_yaml = Hash::new();
+    for router in routers.values() {
+        let mut ifaces = Hash::new();
+        for (iface, addresses) in &router.interfaces {
+            let addresses = match addresses.is_empty() {
+                true => Yaml::Null,
+                false => Yaml::Array(
+                    addresses
+                        .iter()
+                        .map(|addr| Yaml::String(addr.to_string()))
+                        .collect(),
+                ),
+            };
+            ifaces.insert(str_yaml(iface), addresses);
+        }
+        let mut config = Hash::new();
+        config.insert(str_yaml("interfaces"), Yaml::Hash(ifaces));
+        if let Some(daemons) = &router.daemons {
+            let mut frr = Hash::new();
+            frr.insert(
+                str_yaml("daemons"),
+                Yaml::Array(daemons.iter().copied().map(str_yaml).collect()),
+            );
+            frr.insert(
+                str_yaml("startup-config"),
+                str_yaml(&router.path.to_string_lossy()),
+            );
+            config.insert(str_yaml("frr"), Yaml::Hash(frr));
+        }
+        routers_yaml.insert(str_yaml(&router.name), Yaml::Hash(config));
+    }
+
+    let mut topology = Hash::new();
+    topology.insert(str_yaml("routers"), Yaml::Hash(routers_yaml));
+    if !switches.is_empty() {
+        topology.insert(str_yaml("switches"), Yaml::Hash(switches));
+    }
+    topology.insert(str_yaml("links"), Yaml::Array(links));
+
+    let mut output = String::new();
+    let _ = YamlEmitter::new(&mut output).dump(&Yaml::Hash(topology));
+    output.push('\n');
+    output
+}
@@ -0,0 +1,483 @@
+use std::fmt::Write as _;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use ipnetwork::IpNetwork;
+use yaml_rust2::YamlEmitter;
+use yaml_rust2::yaml::{Array, Hash, Yaml};
+
+use crate::NetResult;
+use crate::error::NetError;
+use crate::lab;
+
+/// Daemons `netgen init` writes example startup configs for.
+pub const CONFIGURED_DAEMONS: &[&str] = &["ospfd", "isisd", "bgpd"];
+
+/// Where `netgen quick` writes the topology it starts and the startup
+/// configs of its routers: `quick` in the netgen config directory.
+pub fn quick_dir() -> NetResult<PathBuf> {
+    Ok(lab::config_dir("the quick lab")?.join("quick"))
+}
+
+// ==== enum Shape ====
+
+/// How the routers of a skeleton are linked together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    /// Each router to the next one.
+    Chain,
+    /// A chain closed back onto its first router.
+    Ring,
+    /// Every router to every other one.
+    Mesh,
+    /// The first router to every other one.
+    Star,
+    /// Every router to a single switch.
+    Lan,
+}
+
+impl Shape {
+    pub const NAMES: &'static [&'static str] =
+        &["chain", "ring", "mesh", "star", "lan"];
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "chain" => Ok(Shape::Chain),
+            "ring" => Ok(Shape::Ring),
+            "mesh" => Ok(Shape::Mesh),
+            "star" => Ok(Shape::Star),
+            "lan" => Ok(Shape::Lan),
+            _ => Err(format!("expected one of {}", Shape::NAMES.join(", "))),
+        }
+    }
+}
+
+// ==== enum Addressing ====
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Addressing {
+    /// Addresses are allocated from pools when the topology is parsed.
+    Pools,
+    /// Addresses are written out in the topology.
+    Static,
+}
+
+impl FromStr for Addressing {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "pools" => Ok(Addressing::Pools),
+            "static" => Ok(Addressing::Static),
+            _ => Err("expected pools or static".to_string()),
+        }
+    }
+}
+
+// ==== struct Skeleton ====
+
+/// The answers to `netgen init`, which a topology and the startup configs
+/// of its routers are written from. Routers are named `rt1` to `rt<n>`,
+/// and the switch of a LAN `sw1`.
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    pub routers: usize,
+    pub shape: Shape,
+    // Names of the routers under test.
+    pub duts: Vec<String>,
+    pub addressing: Addressing,
+    // Where the addresses of the links are taken from.
+    pub link_prefix: IpNetwork,
+    pub loopback_prefix: IpNetwork,
+    // FRR daemons the routers run, none for bare namespaces.
+    pub daemons: Vec<String>,
+    // Directory the startup configs are written to.
+    pub configs_dir: String,
+}
+
+// An end of a link of the skeleton, as (node, interface).
+type End = (String, String);
+
+impl Skeleton {
+    /// The skeleton `netgen quick` starts: `routers` routers linked as
+    /// `shape`, addressed from pools and running `daemons`, with the
+    /// defaults of `netgen init` otherwise.
+    pub fn quick(
+        routers: usize,
+        shape: Shape,
+        daemons: Vec<String>,
+        configs_dir: &str,
+    ) -> Self {
+        Skeleton {
+            routers,
+            shape,
+            duts: vec![],
+            addressing: Addressing::Pools,
+            link_prefix: IpNetwork::from_str("10.0.0.0/16")
+                .expect("valid prefix"),
+            loopback_prefix: IpNetwork::from_str("10.255.0.0/24")
+                .expect("valid prefix"),
+            daemons,
+            configs_dir: configs_dir.to_string(),
+        }
+    }
+
+    pub fn router_names(&self) -> Vec<String> {
+        (1..=self.routers)
+            .map(|index| format!("rt{index}"))
+            .collect()
+    }
+
+    /// Checks that the answers make a topology, e.g. that the prefixes
+    /// have room for the addresses.
+    pub fn check(&self) -> NetResult<()> {
+        let invalid = |reason: String| Err(NetError::BasicError(reason));
+        if self.routers < 2 {
+            return invalid("At least 2 routers are needed.".to_string());
+        }
+        if self.shape == Shape::Ring && self.routers < 3 {
+            return invalid("A ring needs at least 3 routers.".to_string());
+        }
+        let names = self.router_names();
+        if let Some(dut) = self.duts.iter().find(|dut| !names.contains(dut)) {
+            return invalid(format!("There is no router {dut}."));
+        }
+        if self.addressing == Addressing::Static {
+            let links = self.links().len() as u128;
+            let needed = match self.shape {
+                Shape::Lan => self.routers as u128 + 1,
+                _ => 2 * links,
+            };
+            if size(self.link_prefix) < needed {
+                return invalid(format!(
+                    "{} is too small for {needed} addresses.",
+                    self.link_prefix
+                ));
+            }
+            if size(self.loopback_prefix) <= self.routers as u128 {
+                return invalid(format!(
+                    "{} is too small for {} loopbacks.",
+                    self.loopback_prefix, self.routers
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // The links, router ends first.
+    fn links(&self) -> Vec<(End, End)> {
+        let names = self.router_names();
+        let end =
+            |node: &str, peer: &str| (node.to_string(), format!("eth-{peer}"));
+        let link = |a: &str, b: &str| (end(a, b), end(b, a));
+        match self.shape {
+            Shape::Chain => names
+                .windows(2)
+                .map(|pair| link(&pair[0], &pair[1]))
+                .collect(),
+            Shape::Ring => {
+                let mut links: Vec<_> = names
+                    .windows(2)
+                    .map(|pair| link(&pair[0], &pair[1]))
+                    .collect();
+                links.push(link(&names[names.len() - 1], &names[0]));
+                links
+            }
+            Shape::Mesh => names
+                .iter()
+                .enumerate()
+                .flat_map(|(index, a)| {
+                    names[index + 1..].iter().map(move |b| link(a, b))
+                })
+                .collect(),
+            Shape::Star => names[1..]
+                .iter()
+                .map(|leaf| link(&names[0], leaf))
+                .collect(),
+            Shape::Lan => {
+                names.iter().map(|router| link(router, "sw1")).collect()
+            }
+        }
+    }
+
+    // Addresses of the router ends of the links, when written out: a /31
+    // (/127) per link, or a host of the prefix per router on a LAN.
+    fn link_addresses(&self) -> Vec<IpNetwork> {
+        let host_len = host_len(self.link_prefix);
+        self.links()
+            .iter()
+            .enumerate()
+            .flat_map(|(index, _)| match self.shape {
+                Shape::Lan => vec![address(
+                    self.link_prefix,
+                    index as u128 + 1,
+                    self.link_prefix.prefix(),
+                )],
+                _ => (0..2)
+                    .map(|end| {
+                        address(
+                            self.link_prefix,
+                            2 * index as u128 + end,
+                            host_len - 1,
+                        )
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// The topology, as YAML.
+    pub fn topology_yaml(&self) -> String {
+        let str_yaml = |value: &str| Yaml::String(value.to_string());
+        let pool =
+            |name: &str| Yaml::Array(vec![str_yaml(&format!("pool:{name}"))]);
+        let links = self.links();
+        let link_addresses = self.link_addresses();
+
+        let mut topology = Hash::new();
+        if self.addressing == Addressing::Pools {
+            let mut pools = Hash::new();
+            for (name, prefix, length) in [
+                (
+                    "links",
+                    self.link_prefix,
+                    match self.shape {
+                        Shape::Lan => self.link_prefix.prefix(),
+                        _ => host_len(self.link_prefix) - 1,
+                    },
+                ),
+                (
+                    "loopbacks",
+                    self.loopback_prefix,
+                    host_len(self.loopback_prefix),
+                ),
+            ] {
+                let mut config = Hash::new();
+                config
+                    .insert(str_yaml("prefix"), str_yaml(&prefix.to_string()));
+                config.insert(str_yaml("length"), Yaml::Integer(length.into()));
+                pools.insert(str_yaml(name), Yaml::Hash(config));
+            }
+            topology.insert(str_yaml("pools"), Yaml::Hash(pools));
+        }
+
+        let mut routers = Hash::new();
+        for (index, name) in self.router_names().iter().enumerate() {
+            let mut ifaces = Hash::new();
+            ifaces.insert(
+                str_yaml("lo"),
+                match self.addressing {
+                    Addressing::Pools => pool("loopbacks"),
+                    Addressing::Static => Yaml::Array(vec![str_yaml(
+                        &self.loopback(index).to_string(),
+                    )]),
+                },
+            );
+            let ends = links.iter().flat_map(|(a, b)| [a, b]);
+            for (end_index, (node, iface)) in ends.enumerate() {
+                if node != name {
+                    continue;
+                }
+                let addresses = match self.addressing {
+                    Addressing::Pools => pool("links"),
+                    Addressing::Static => {
+                        // Switch ends are every other end on a LAN.
+                        let address = match self.shape {
+                            Shape::Lan => link_addresses[end_index / 2],
+                            _ => link_addresses[end_index],
+                        };
+                        Yaml::Array(vec![str_yaml(&address.to_string())])
+                    }
+                };
+                ifaces.insert(str_yaml(iface), addresses);
+            }
+
+            let mut router = Hash::new();
+            router.insert(str_yaml("interfaces"), Yaml::Hash(ifaces));
+            if self.duts.contains(name) {
+                router.insert(str_yaml("dut"), Yaml::Boolean(true));
+            }
+            if !self.daemons.is_empty() {
+                let mut frr = Hash::new();
+                frr.insert(
+                    str_yaml("daemons"),
+                    Yaml::Array(
+                        self.daemons.iter().map(|d| str_yaml(d)).collect(),
+                    ),
+                );
+                frr.insert(
+                    str_yaml("startup-config"),
+                    str_yaml(&format!("{}/{name}.conf", self.configs_dir)),
+                );
+                router.insert(str_yaml("frr"), Yaml::Hash(frr));
+            }
+            routers.insert(str_yaml(name), Yaml::Hash(router));
+        }
+        topology.insert(str_yaml("routers"), Yaml::Hash(routers));
+
+        if self.shape == Shape::Lan {
+            let ports = links
+                .iter()
+                .map(|(_, (_, port))| (str_yaml(port), Yaml::Null))
+                .collect();
+            let mut switch = Hash::new();
+            switch.insert(str_yaml("interfaces"), Yaml::Hash(ports));
+            let mut switches = Hash::new();
+            switches.insert(str_yaml("sw1"), Yaml::Hash(switch));
+            topology.insert(str_yaml("switches"), Yaml::Hash(switches));
+        }
+
+        let links: Array = links
+            .iter()
+            .map(|((src, src_iface), (dst, dst_iface))| {
+                let mut link = Hash::new();
+                link.insert(str_yaml("src-device"), str_yaml(src));
+                link.insert(str_yaml("src-iface"), str_yaml(src_iface));
+                link.insert(str_yaml("dst-device"), str_yaml(dst));
+                link.insert(str_yaml("dst-iface"), str_yaml(dst_iface));
+                Yaml::Hash(link)
+            })
+            .collect();
+        topology.insert(str_yaml("links"), Yaml::Array(links));
+        // Loopbacks are only reachable across the lab once routed.
+        let mut invariants = vec![str_yaml("adjacencies")];
+        if self.daemons.iter().any(|d| d == "ospfd" || d == "isisd") {
+            invariants.push(str_yaml("reachability"));
+        }
+        topology.insert(str_yaml("invariants"), Yaml::Array(invariants));
+
+        let mut output = String::new();
+        let _ = YamlEmitter::new(&mut output).dump(&Yaml::Hash(topology));
+        output.push('\n');
+        output
+    }
+
+    // Loopback address of the router at `index`, when written out.
+    fn loopback(&self, index: usize) -> IpNetwork {
+        let prefix = self.loopback_prefix;
+        address(prefix, index as u128 + 1, host_len(prefix))
+    }
+
+    /// Example startup configs of the routers, by router name, for the
+    /// daemons among `CONFIGURED_DAEMONS`. None without FRR.
+    pub fn startup_configs(&self) -> Vec<(String, String)> {
+        if self.daemons.is_empty() {
+            return vec![];
+        }
+        let links = self.links();
+        let runs = |daemon: &str| self.daemons.iter().any(|d| d == daemon);
+        let point_to_point = self.shape != Shape::Lan;
+
+        self.router_names()
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let mut config = String::new();
+                let _ = writeln!(config, "frr defaults traditional");
+                let _ = writeln!(config, "hostname {name}");
+                let _ = writeln!(config, "!");
+                let ifaces = links
+                    .iter()
+                    .flat_map(|(a, b)| [a, b])
+                    .filter(|(node, _)| node == name)
+                    .map(|(_, iface)| iface.as_str());
+                for iface in std::iter::once("lo").chain(ifaces) {
+                    let _ = writeln!(config, "interface {iface}");
+                    if runs("ospfd") {
+                        let _ = writeln!(config, " ip ospf area 0");
+                        if iface != "lo" && point_to_point {
+                            let _ = writeln!(
+                                config,
+                                " ip ospf network point-to-point"
+                            );
+                        }
+                    }
+                    if runs("isisd") {
+                        let _ = writeln!(config, " ip router isis lab");
+                        if iface == "lo" {
+                            let _ = writeln!(config, " isis passive");
+                        } else if point_to_point {
+                            let _ = writeln!(
+                                config,
+                                " isis network point-to-point"
+                            );
+                        }
+                    }
+                    let _ = writeln!(config, "exit");
+                    let _ = writeln!(config, "!");
+                }
+
+                let router_id = match self.addressing {
+                    Addressing::Static => Some(self.loopback(index).ip()),
+                    Addressing::Pools => None,
+                };
+                if runs("ospfd") {
+                    let _ = writeln!(config, "router ospf");
+                    if let Some(IpAddr::V4(router_id)) = router_id {
+                        let _ = writeln!(config, " ospf router-id {router_id}");
+                    }
+                    let _ = writeln!(config, "exit");
+                    let _ = writeln!(config, "!");
+                }
+                if runs("isisd") {
+                    let _ = writeln!(config, "router isis lab");
+                    let _ = writeln!(
+                        config,
+                        " net 49.0001.0000.0000.{:04}.00",
+                        index + 1
+                    );
+                    let _ = writeln!(config, "exit");
+                    let _ = writeln!(config, "!");
+                }
+                if runs("bgpd") {
+                    let _ =
+                        writeln!(config, "router bgp {}", 65000 + index + 1);
+                    if let Some(IpAddr::V4(router_id)) = router_id {
+                        let _ = writeln!(config, " bgp router-id {router_id}");
+                    }
+                    let _ = writeln!(
+                        config,
+                        " ! neighbor <address> remote-as <asn>"
+                    );
+                    let _ = writeln!(config, "exit");
+                    let _ = writeln!(config, "!");
+                }
+                (name.clone(), config)
+            })
+            .collect()
+    }
+}
+
+// Length of the prefixes of single addresses of the family of `prefix`.
+fn host_len(prefix: IpNetwork) -> u8 {
+    match prefix {
+        IpNetwork::V4(_) => 32,
+        IpNetwork::V6(_) => 128,
+    }
+}
+
+// How many addresses `prefix` holds.
+fn size(prefix: IpNetwork) -> u128 {
+    1u128
+        .checked_shl(u32::from(host_len(prefix) - prefix.prefix()))
+        .unwrap_or(u128::MAX)
+}
+
+// Address `n` of `prefix`, with a prefix length of `len`.
+fn address(prefix: IpNetwork, n: u128, len: u8) -> IpNetwork {
+    let ip = match prefix.network() {
+        IpAddr::V4(network) => {
+            IpAddr::V4((u32::from(network).wrapping_add(n as u32)).into())
+        }
+        IpAddr::V6(network) => {
+            IpAddr::V6((u128::from(network).wrapping_add(n)).into())
+        }
+    };
+    // Only fails on lengths past the family's, which are never given.
+    IpNetwork::new(ip, len).unwrap_or(prefix)
+}
@@ -0,0 +1,407 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+
+use crate::error::{ConfigError, NetError, YamlPath};
+use crate::{NetResult, devices_ns_dir};
+
+// Community of the routes the injector withdraws in bulk, see `mrt_dump`:
+// 65535:65280, in the reserved range, never seen by the peers.
+const WITHDRAWN_COMMUNITY: u32 = 0xffff_ff00;
+// Name of the gobgpd export policy and community set rejecting them.
+const WITHDRAW_POLICY: &str = "netgen-withdrawn";
+/// Most prefixes an injector or a churn step announces, twice a full IPv4
+/// table: all of them, and their MRT dump, are held in memory.
+pub(crate) const MAX_PREFIXES: u64 = 2_000_000;
+
+// ==== struct RouteInjector ====
+
+/// Built-in BGP route injector, backed by gobgpd.
+///
+/// A router carrying an `injector` block gets a gobgpd instance started in
+/// its namespace, peering with the configured neighbors and announcing
+/// `count` consecutive prefixes starting at `prefixes.start`:
+///
+/// ```yaml
+/// routers:
+///   inj1:
+///     interfaces:
+///       eth-dut: [ 10.0.0.2/24 ]
+///     injector:
+///       asn: 65001
+///       router-id: 10.0.0.2
+///       update-source: loopback
+///       neighbors:
+///         - address: 10.0.0.1
+///           remote-as: 65000
+///       prefixes:
+///         start: 100.0.0.0/24
+///         count: 1000000
+///       flap-interval: 30
+/// ```
+///
+/// `router-id` defaults to the router's loopback address, and
+/// `update-source` (either `loopback` or an address) sets the local address
+/// of the BGP sessions.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RouteInjector {
+    pub(crate) asn: u32,
+    pub(crate) router_id: Option<IpAddr>,
+    pub(crate) update_source: Option<UpdateSource>,
+    pub(crate) neighbors: Vec<InjectorPeer>,
+    pub(crate) start: IpNetwork,
+    pub(crate) count: u64,
+    // Seconds between withdrawing and re-announcing all the prefixes.
+    pub(crate) flap_interval: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct InjectorPeer {
+    pub(crate) address: IpAddr,
+    pub(crate) remote_as: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum UpdateSource {
+    Loopback,
+    Address(IpAddr),
+}
+
+impl RouteInjector {
+    pub(crate) const CONFIG_FILE: &str = "gobgpd.toml";
+    pub(crate) const ROUTES_FILE: &str = "injector-routes.mrt";
+    pub(crate) const SCRIPT_FILE: &str = "injector.sh";
+
+    /// Fills in the settings derived from the router's router-id and
+    /// loopback address.
+    pub(crate) fn resolve(
+        &mut self,
+        router_name: &str,
+        router_id: Option<IpAddr>,
+        loopback: Option<IpAddr>,
+    ) -> NetResult<()> {
+        let injector_path = YamlPath::new()
+            .key("routers")
+            .key(router_name)
+            .key("injector");
+
+        match self.router_id.or(router_id) {
+            Some(IpAddr::V4(router_id)) => {
+                self.router_id = Some(IpAddr::V4(router_id));
+            }
+            // BGP identifiers are four bytes.
+            Some(IpAddr::V6(_)) => {
+                return Err(ConfigError::IncorrectType {
+                    path: injector_path.key("router-id").unknown(),
+                    expected: "IPv4 address".to_string(),
+                }
+                .into());
+            }
+            None => {
+                return Err(ConfigError::MissingField {
+                    path: injector_path.key("router-id").unknown(),
+                }
+                .into());
+            }
+        }
+
+        if let Some(UpdateSource::Loopback) = self.update_source {
+            let loopback = loopback.ok_or_else(|| {
+                NetError::BasicError(format!(
+                    "{router_name} uses its loopback as BGP update-source \
+                        but has no loopback address"
+                ))
+            })?;
+            self.update_source = Some(UpdateSource::Address(loopback));
+        }
+        Ok(())
+    }
+
+    /// Writes the gobgpd configuration, the routes to announce as an MRT
+    /// dump and the announce/flap script into the router's device
+    /// directory.
+    ///
+    /// Returns the path of the script to be run inside the router.
+    pub(crate) fn write_files(&self, router_name: &str) -> NetResult<String> {
+        let home = format!("{}/{router_name}", devices_ns_dir());
+        let config_path = format!("{home}/{}", Self::CONFIG_FILE);
+        let routes_path = format!("{home}/{}", Self::ROUTES_FILE);
+        let script_path = format!("{home}/{}", Self::SCRIPT_FILE);
+
+        write_file(&config_path, &self.gobgpd_config())?;
+        let dump = mrt_dump(self.asn, self.router_id, self.prefixes(), false);
+        fs::write(&routes_path, dump).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to write {routes_path}: {err:?}"
+            ))
+        })?;
+        write_file(&script_path, &self.script(&config_path, &routes_path))?;
+        Ok(script_path)
+    }
+
+    fn gobgpd_config(&self) -> String {
+        let mut config = String::new();
+        let _ = writeln!(config, "[global.config]");
+        let _ = writeln!(config, "  as = {}", self.asn);
+        if let Some(router_id) = self.router_id {
+            let _ = writeln!(config, "  router-id = \"{router_id}\"");
+        }
+        let _ = writeln!(config, "[global.apply-policy.config]");
+        let _ =
+            writeln!(config, "  export-policy-list = [\"{WITHDRAW_POLICY}\"]");
+        let _ = writeln!(config, "  default-export-policy = \"accept-route\"");
+
+        for peer in &self.neighbors {
+            let _ = writeln!(config);
+            let _ = writeln!(config, "[[neighbors]]");
+            let _ = writeln!(config, "  [neighbors.config]");
+            let _ =
+                writeln!(config, "    neighbor-address = \"{}\"", peer.address);
+            let _ = writeln!(config, "    peer-as = {}", peer.remote_as);
+            if let Some(UpdateSource::Address(addr)) = self.update_source {
+                let _ = writeln!(config, "  [neighbors.transport.config]");
+                let _ = writeln!(config, "    local-address = \"{addr}\"");
+            }
+            let _ = writeln!(config, "  [[neighbors.afi-safis]]");
+            let _ = writeln!(config, "    [neighbors.afi-safis.config]");
+            let _ = writeln!(
+                config,
+                "      afi-safi-name = \"{}\"",
+                self.afi_safi_name()
+            );
+        }
+
+        // Routes injected as withdrawn are not exported: peers having them
+        // are sent withdraws instead.
+        let (high, low) =
+            (WITHDRAWN_COMMUNITY >> 16, WITHDRAWN_COMMUNITY & 0xffff);
+        let _ = writeln!(config);
+        let _ = writeln!(config, "[defined-sets.bgp-defined-sets]");
+        let _ = writeln!(
+            config,
+            "  [[defined-sets.bgp-defined-sets.community-sets]]"
+        );
+        let _ =
+            writeln!(config, "    community-set-name = \"{WITHDRAW_POLICY}\"");
+        let _ = writeln!(config, "    community-list = [\"{high}:{low}\"]");
+        let _ = writeln!(config);
+        let statement = "policy-definitions.statements";
+        let _ = writeln!(config, "[[policy-definitions]]");
+        let _ = writeln!(config, "  name = \"{WITHDRAW_POLICY}\"");
+        let _ = writeln!(config, "  [[policy-definitions.statements]]");
+        let _ = writeln!(config, "    name = \"{WITHDRAW_POLICY}\"");
+        let _ = writeln!(
+            config,
+            "    [{statement}.conditions.bgp-conditions.match-community-set]"
+        );
+        let _ = writeln!(config, "      community-set = \"{WITHDRAW_POLICY}\"");
+        let _ = writeln!(config, "      match-set-options = \"any\"");
+        let _ = writeln!(config, "    [policy-definitions.statements.actions]");
+        let _ = writeln!(config, "      route-disposition = \"reject-route\"");
+        config
+    }
+
+    fn afi_safi_name(&self) -> &'static str {
+        match self.start {
+            IpNetwork::V4(_) => "ipv4-unicast",
+            IpNetwork::V6(_) => "ipv6-unicast",
+        }
+    }
+
+    fn family(&self) -> &'static str {
+        match self.start {
+            IpNetwork::V4(_) => "ipv4",
+            IpNetwork::V6(_) => "ipv6",
+        }
+    }
+
+    pub(crate) fn prefixes(&self) -> impl Iterator<Item = IpNetwork> + '_ {
+        prefix_range(self.start, self.count)
+    }
+
+    fn script(&self, config_path: &str, routes_path: &str) -> String {
+        let family = self.family();
+        let mut script = String::new();
+        let _ = writeln!(script, "#!/bin/sh");
+        let _ = writeln!(script, "# Generated by netgen. Do not edit.");
+        let _ = writeln!(script, "gobgpd -f {config_path} &");
+        let _ = writeln!(script);
+        let _ = writeln!(script, "# Wait for the gRPC API to come up.");
+        let _ = writeln!(
+            script,
+            "until gobgp global > /dev/null 2>&1; do sleep 1; done"
+        );
+        let _ = writeln!(script);
+        let _ = writeln!(script, "announce() {{");
+        let _ = writeln!(script, "  gobgp mrt inject global {routes_path}");
+        let _ = writeln!(script, "}}");
+        let _ = writeln!(script);
+        let _ = writeln!(script, "withdraw() {{");
+        let _ = writeln!(script, "  gobgp global rib -a {family} del all");
+        let _ = writeln!(script, "}}");
+        let _ = writeln!(script);
+        let _ = writeln!(script, "announce");
+
+        if let Some(interval) = self.flap_interval {
+            let _ = writeln!(script, "while true; do");
+            let _ = writeln!(script, "  sleep {interval}");
+            let _ = writeln!(script, "  withdraw");
+            let _ = writeln!(script, "  sleep {interval}");
+            let _ = writeln!(script, "  announce");
+            let _ = writeln!(script, "done");
+        } else {
+            let _ = writeln!(script, "wait");
+        }
+        script
+    }
+}
+
+/// `prefixes` as an MRT table dump (RFC 6396), which `gobgp mrt inject`
+/// loads in one go rather than one process per prefix. Originated locally:
+/// empty AS path, unspecified next hop, which gobgpd replaces by its own
+/// address.
+///
+/// `withdrawn` routes carry `WITHDRAWN_COMMUNITY`, which the injector's
+/// export policy rejects: injected over announced ones, they withdraw them
+/// from the peers, as many as there are in a single call.
+pub(crate) fn mrt_dump(
+    asn: u32,
+    router_id: Option<IpAddr>,
+    prefixes: impl Iterator<Item = IpNetwork>,
+    withdrawn: bool,
+) -> Vec<u8> {
+    const TABLE_DUMP_V2: u16 = 13;
+    const PEER_INDEX_TABLE: u16 = 1;
+    const RIB_IPV4_UNICAST: u16 = 2;
+    const RIB_IPV6_UNICAST: u16 = 4;
+    // Peer type with 4-byte AS numbers and an IPv4 address.
+    const PEER_TYPE_AS4: u8 = 0x02;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as u32);
+    let mut dump = vec![];
+    let mut record = |subtype: u16, body: &[u8]| {
+        dump.extend(timestamp.to_be_bytes());
+        dump.extend(TABLE_DUMP_V2.to_be_bytes());
+        dump.extend(subtype.to_be_bytes());
+        dump.extend((body.len() as u32).to_be_bytes());
+        dump.extend(body);
+    };
+
+    let bgp_id = match router_id {
+        Some(IpAddr::V4(router_id)) => router_id.octets(),
+        _ => [0; 4],
+    };
+    let mut peers = vec![];
+    peers.extend(bgp_id);
+    // No view name, a single peer: ourselves.
+    peers.extend(0u16.to_be_bytes());
+    peers.extend(1u16.to_be_bytes());
+    peers.push(PEER_TYPE_AS4);
+    peers.extend(bgp_id);
+    peers.extend([0; 4]);
+    peers.extend(asn.to_be_bytes());
+    record(PEER_INDEX_TABLE, &peers);
+
+    // ORIGIN IGP and an empty AS_PATH.
+    let mut attributes = vec![0x40, 1, 1, 0, 0x40, 2, 0];
+    if withdrawn {
+        attributes.extend([0xc0, 8, 4]);
+        attributes.extend(WITHDRAWN_COMMUNITY.to_be_bytes());
+    }
+    for (sequence, prefix) in prefixes.enumerate() {
+        let (subtype, octets) = match prefix.network() {
+            IpAddr::V4(network) => {
+                (RIB_IPV4_UNICAST, network.octets().to_vec())
+            }
+            IpAddr::V6(network) => {
+                (RIB_IPV6_UNICAST, network.octets().to_vec())
+            }
+        };
+        let mut entry_attributes = attributes.clone();
+        match prefix {
+            IpNetwork::V4(_) => {
+                entry_attributes.extend([0x40, 3, 4, 0, 0, 0, 0])
+            }
+            IpNetwork::V6(_) => {
+                // MP_REACH_NLRI, abbreviated to the next hop in MRT dumps.
+                entry_attributes.extend([0x80, 14, 17, 16]);
+                entry_attributes.extend([0; 16]);
+            }
+        }
+        let mut rib = vec![];
+        rib.extend((sequence as u32).to_be_bytes());
+        rib.push(prefix.prefix());
+        rib.extend(&octets[..usize::from(prefix.prefix()).div_ceil(8)]);
+        // One entry, from peer 0.
+        rib.extend(1u16.to_be_bytes());
+        rib.extend(0u16.to_be_bytes());
+        rib.extend(timestamp.to_be_bytes());
+        rib.extend((entry_attributes.len() as u16).to_be_bytes());
+        rib.extend(&entry_attributes);
+        record(subtype, &rib);
+    }
+    dump
+}
+
+/// `count` consecutive blocks of the same length as `start`, stopping
+/// early if the address space runs out.
+pub(crate) fn prefix_range(
+    start: IpNetwork,
+    count: u64,
+) -> impl Iterator<Item = IpNetwork> {
+    let prefix_len = start.prefix();
+    let (bits, base) = match start {
+        IpNetwork::V4(net) => (32u32, u128::from(u32::from(net.network()))),
+        IpNetwork::V6(net) => (128u32, u128::from(net.network())),
+    };
+    // A /0 spans the whole family, it has no next prefix.
+    let step = 1u128.checked_shl(bits - u32::from(prefix_len));
+
+    (0..u128::from(count)).map_while(move |idx| {
+        let addr = match step {
+            Some(step) => idx.checked_mul(step)?.checked_add(base)?,
+            None if idx == 0 => base,
+            None => return None,
+        };
+        let addr = match start {
+            IpNetwork::V4(_) => {
+                IpAddr::from(u32::try_from(addr).ok()?.to_be_bytes())
+            }
+            IpNetwork::V6(_) => IpAddr::from(addr.to_be_bytes()),
+        };
+        IpNetwork::new(addr, prefix_len).ok()
+    })
+}
+
+/// Checks the `prefixes` block at `path`, of `count` prefixes from `start`:
+/// a /0 start has no next prefix to announce, and there are no more than
+/// `MAX_PREFIXES`.
+pub(crate) fn check_prefixes(
+    start: IpNetwork,
+    count: u64,
+    path: &YamlPath,
+) -> Result<(), ConfigError> {
+    if start.prefix() == 0 {
+        return Err(ConfigError::IncorrectType {
+            path: path.clone().key("start").unknown(),
+            expected: "prefix longer than /0".to_string(),
+        });
+    }
+    if count > MAX_PREFIXES {
+        return Err(ConfigError::IncorrectType {
+            path: path.clone().key("count").unknown(),
+            expected: format!("at most {MAX_PREFIXES} prefixes"),
+        });
+    }
+    Ok(())
+}
+
+fn write_file(path: &str, contents: &str) -> NetResult<()> {
+    fs::write(path, contents).map_err(|err| {
+        NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+    })
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::cancel::CancelToken;
+use crate::link::{Link, LinkEndpoint};
+use crate::node::Node;
+use crate::report::TestCase;
+use crate::suite::{Assertion, Check};
+use crate::topology::Topology;
+
+// ==== enum Invariant ====
+
+/// Properties a topology declares its labs to have once started, checked
+/// by `netgen verify`:
+///
+/// ```yaml
+/// invariants:
+///   - adjacencies
+///   - reachability
+///   - unique-router-ids
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invariant {
+    /// The ends of every enabled link are up, and routers at both ends
+    /// answer each other over it.
+    Adjacencies,
+    /// Every router reaches the loopback address of every other one.
+    Reachability,
+    /// No two routers share a router-id.
+    UniqueRouterIds,
+}
+
+impl Invariant {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "adjacencies" => Some(Invariant::Adjacencies),
+            "reachability" => Some(Invariant::Reachability),
+            "unique-router-ids" => Some(Invariant::UniqueRouterIds),
+            _ => None,
+        }
+    }
+
+    /// Checks the invariant against the running `topology`, a case per
+    /// link, pair of routers or router-id. Checks are retried until they
+    /// pass or `timeout` expires, and fail without being run once `cancel`
+    /// is cancelled.
+    pub(crate) fn evaluate(
+        &self,
+        topology: &Topology,
+        timeout: Duration,
+        cancel: &CancelToken,
+    ) -> Vec<TestCase> {
+        match self {
+            Invariant::Adjacencies => topology
+                .enabled_links()
+                .into_iter()
+                .map(|link| adjacency(topology, link, timeout))
+                .map(|assertion| assertion.evaluate(cancel))
+                .collect(),
+            Invariant::Reachability => {
+                let loopbacks: Vec<_> = topology
+                    .nodes()
+                    .filter_map(|node| match node {
+                        Node::Router(router) => Some((
+                            router.name.as_str(),
+                            router.loopback_address()?,
+                        )),
+                        Node::Switch(_) => None,
+                    })
+                    .collect();
+                let mut cases = vec![];
+                for (src, _) in &loopbacks {
+                    for (dst, address) in &loopbacks {
+                        if src == dst {
+                            continue;
+                        }
+                        let assertion = Assertion {
+                            name: format!("reachability {src} -> {dst}"),
+                            timeout,
+                            check: Check::Ping {
+                                node: src.to_string(),
+                                dst: address.to_string(),
+                            },
+                        };
+                        cases.push(assertion.evaluate(cancel));
+                    }
+                }
+                cases
+            }
+            Invariant::UniqueRouterIds => {
+                let mut routers = BTreeMap::new();
+                for node in topology.nodes() {
+                    if let Node::Router(router) = node
+                        && let Some(router_id) = router.router_id()
+                    {
+                        routers
+                            .entry(router_id)
+                            .or_insert_with(Vec::new)
+                            .push(router.name.clone());
+                    }
+                }
+                routers
+                    .into_iter()
+                    .map(|(router_id, names)| TestCase {
+                        name: format!("router-id {router_id}"),
+                        duration: Duration::ZERO,
+                        failure: (names.len() > 1)
+                            .then(|| format!("shared by {}", names.join(", "))),
+                        node: names.first().cloned(),
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Invariant::Adjacencies => write!(f, "adjacencies"),
+            Invariant::Reachability => write!(f, "reachability"),
+            Invariant::UniqueRouterIds => write!(f, "unique-router-ids"),
+        }
+    }
+}
+
+/// The checks of what `link` is expected to be, see `LinkExpectations`, run
+/// on the ends of the link on routers. An end whose interface was not
+/// created under its name fails them all. Checks are retried until
+/// `timeout`, and until `up-within` for the ends coming up.
+pub(crate) fn expectations(
+    topology: &Topology,
+    link: &Link,
+    timeout: Duration,
+) -> Vec<Assertion> {
+    let expect = &link.expect;
+    let is_router = |end: &LinkEndpoint| {
+        matches!(topology.get_node(&end.node), Some(Node::Router(_)))
+    };
+    let mut assertions = vec![];
+    for (end, peer) in [(&link.src, &link.dst), (&link.dst, &link.src)] {
+        if !is_router(end) {
+            continue;
+        }
+        let (node, iface) = (end.node.to_string(), end.iface.to_string());
+        let name = |what: &str| {
+            format!("link {} <-> {} {what} on {end}", link.src, link.dst)
+        };
+        if let Some(up_within) = expect.up_within {
+            assertions.push(Assertion {
+                name: name("up"),
+                timeout: up_within,
+                check: Check::Exec {
+                    node: node.clone(),
+                    cmd: format!(
+                        "ip -o link show dev {iface} | grep -q 'state UP'"
+                    ),
+                },
+            });
+        }
+        if expect.carrier {
+            assertions.push(Assertion {
+                name: name("carrier"),
+                timeout,
+                check: Check::Exec {
+                    node: node.clone(),
+                    cmd: format!(
+                        "ip -o link show dev {iface} | grep -qv NO-CARRIER"
+                    ),
+                },
+            });
+        }
+        // Bridges do not speak LLDP, nor forward it.
+        if expect.lldp_peer && is_router(peer) {
+            assertions.push(Assertion {
+                name: name("lldp-peer"),
+                timeout,
+                check: Check::LldpPeer {
+                    node,
+                    iface,
+                    peer: peer.clone(),
+                },
+            });
+        }
+    }
+    assertions
+}
+
+// The check of the adjacency over `link`: a ping from one end to the
+// address of the other, through the link only, when both ends are routers
+// with addresses of the same family. Otherwise, that the source end is up,
+// which a veth only is once its peer is as well.
+fn adjacency(topology: &Topology, link: &Link, timeout: Duration) -> Assertion {
+    let addresses = |end: &LinkEndpoint| match topology.get_node(&end.node) {
+        Some(node @ Node::Router(_)) => node
+            .interfaces()
+            .iter()
+            .find(|iface| iface.name == end.iface)
+            .map(|iface| iface.addresses.clone()),
+        _ => None,
+    };
+    let peer = match (addresses(&link.src), addresses(&link.dst)) {
+        (Some(src), Some(dst)) => dst
+            .into_iter()
+            .find(|dst| src.iter().any(|src| src.is_ipv4() == dst.is_ipv4())),
+        _ => None,
+    };
+
+    let iface = &link.src.iface;
+    let cmd = match peer {
+        Some(peer) => format!("ping -c 1 -W 1 -I {iface} {}", peer.ip()),
+        None => format!("ip link show dev {iface} | grep -q 'state UP'"),
+    };
+    Assertion {
+        name: format!("adjacency {} <-> {}", link.src, link.dst),
+        timeout,
+        check: Check::Exec {
+            node: link.src.node.clone(),
+            cmd,
+        },
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use tracing::debug;
+
+use crate::nat::NatKind;
+use crate::node::{FileSource, Node};
+use crate::topology::Topology;
+
+// ==== struct Feature ====
+
+/// A kernel feature a topology can rely on, the config option building it
+/// and the module it comes as when not built in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Feature {
+    pub name: &'static str,
+    pub config: &'static str,
+    // None for those which cannot be built as modules.
+    pub module: Option<&'static str>,
+}
+
+const fn feature(
+    name: &'static str,
+    config: &'static str,
+    module: Option<&'static str>,
+) -> Feature {
+    Feature {
+        name,
+        config,
+        module,
+    }
+}
+
+const VETH: Feature = feature("veth", "CONFIG_VETH", Some("veth"));
+const BRIDGE: Feature = feature("bridge", "CONFIG_BRIDGE", Some("bridge"));
+const DUMMY: Feature = feature("dummy", "CONFIG_DUMMY", Some("dummy"));
+const IPV6: Feature = feature("IPv6", "CONFIG_IPV6", Some("ipv6"));
+const NETEM: Feature =
+    feature("netem", "CONFIG_NET_SCH_NETEM", Some("sch_netem"));
+const PRIO: Feature =
+    feature("prio qdisc", "CONFIG_NET_SCH_PRIO", Some("sch_prio"));
+const FLOWER: Feature = feature(
+    "flower classifier",
+    "CONFIG_NET_CLS_FLOWER",
+    Some("cls_flower"),
+);
+const INGRESS: Feature = feature(
+    "ingress qdisc",
+    "CONFIG_NET_SCH_INGRESS",
+    Some("sch_ingress"),
+);
+const MATCHALL: Feature = feature(
+    "matchall classifier",
+    "CONFIG_NET_CLS_MATCHALL",
+    Some("cls_matchall"),
+);
+const POLICE: Feature =
+    feature("police action", "CONFIG_NET_ACT_POLICE", Some("act_police"));
+const NFT_NAT: Feature =
+    feature("nftables NAT", "CONFIG_NFT_NAT", Some("nft_nat"));
+const NFT_MASQ: Feature =
+    feature("nftables masquerade", "CONFIG_NFT_MASQ", Some("nft_masq"));
+const POLICY_ROUTING: Feature =
+    feature("policy routing", "CONFIG_IP_MULTIPLE_TABLES", None);
+const VRF: Feature = feature("VRF", "CONFIG_NET_VRF", Some("vrf"));
+const MPLS: Feature =
+    feature("MPLS", "CONFIG_MPLS_ROUTING", Some("mpls_router"));
+const MPLS_TUNNEL: Feature = feature(
+    "MPLS tunnels",
+    "CONFIG_MPLS_IPTUNNEL",
+    Some("mpls_iptunnel"),
+);
+const VXLAN: Feature = feature("VXLAN", "CONFIG_VXLAN", Some("vxlan"));
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.module {
+            Some(module) => {
+                write!(f, "{} ({}, module {module})", self.name, self.config)
+            }
+            None => write!(f, "{} ({})", self.name, self.config),
+        }
+    }
+}
+
+/// The kernel features `topology` relies on, with what needs each of them:
+/// node names, or "the links".
+pub fn required(topology: &Topology) -> BTreeMap<Feature, Vec<String>> {
+    let mut required: BTreeMap<Feature, Vec<String>> = BTreeMap::new();
+    let mut need = |feature: Feature, by: &str| {
+        let needed_by = required.entry(feature).or_default();
+        if !needed_by.iter().any(|name| name == by) {
+            needed_by.push(by.to_string());
+        }
+    };
+
+    if !topology.links().is_empty() {
+        need(VETH, "the links");
+    }
+    for link in topology.links() {
+        if link.impairment.is_some() {
+            need(NETEM, "the links");
+        }
+        if !link.flows.is_empty() {
+            for feature in [NETEM, PRIO, FLOWER] {
+                need(feature, "the links");
+            }
+        }
+    }
+
+    for node in topology.nodes() {
+        let name = node.name();
+        if node
+            .interfaces()
+            .iter()
+            .any(|iface| iface.addresses.iter().any(|addr| addr.is_ipv6()))
+        {
+            need(IPV6, name);
+        }
+        match node {
+            Node::Router(router) => {
+                if router.interfaces().iter().any(|iface| iface.dummy) {
+                    need(DUMMY, name);
+                }
+                if !router.rules.is_empty() {
+                    need(POLICY_ROUTING, name);
+                }
+                if !router.nat.is_empty() {
+                    need(NFT_NAT, name);
+                    if router
+                        .nat
+                        .iter()
+                        .any(|rule| rule.kind == NatKind::Masquerade)
+                    {
+                        need(NFT_MASQ, name);
+                    }
+                }
+                let Some(frr) = &router.frr else {
+                    continue;
+                };
+                if frr.daemons().contains(&"ldpd") {
+                    need(MPLS, name);
+                }
+                // What the startup config sets up, as far as its keywords
+                // tell.
+                let config = match &frr.startup_config {
+                    Some(FileSource::Inline(contents)) => contents.clone(),
+                    Some(FileSource::Path(path)) => {
+                        fs::read_to_string(path).unwrap_or_default()
+                    }
+                    None => String::new(),
+                };
+                for line in config.lines().map(str::trim) {
+                    if line.starts_with("vrf ") {
+                        need(VRF, name);
+                    }
+                    if line.starts_with("mpls ") {
+                        need(MPLS, name);
+                    }
+                    if line.contains("encap mpls") {
+                        need(MPLS_TUNNEL, name);
+                    }
+                    if line.starts_with("advertise-all-vni")
+                        || line.starts_with("vni ")
+                    {
+                        need(VXLAN, name);
+                    }
+                }
+            }
+            Node::Switch(switch) => {
+                need(BRIDGE, name);
+                if switch.ports.values().any(|port| port.police.is_some()) {
+                    for feature in [INGRESS, MATCHALL, FLOWER, POLICE] {
+                        need(feature, name);
+                    }
+                }
+            }
+        }
+    }
+    required
+}
+
+// ==== struct Kernel ====
+
+/// What the running kernel was built with, as far as its config and the
+/// list of its modules tell. Either may be unreadable, e.g. in containers,
+/// features being then assumed to be there.
+#[derive(Debug, Clone, Default)]
+pub struct Kernel {
+    pub release: String,
+    // The lines of its config, from /boot or /proc/config.gz.
+    config: Option<String>,
+    // modules.builtin and modules.dep, one after the other.
+    modules: Option<String>,
+}
+
+impl Kernel {
+    pub fn running() -> Self {
+        let release = fs::read_to_string("/proc/sys/kernel/osrelease")
+            .map(|release| release.trim().to_string())
+            .unwrap_or_default();
+        let config = fs::read_to_string(format!("/boot/config-{release}"))
+            .ok()
+            .or_else(|| {
+                let output = Command::new("zcat")
+                    .arg("/proc/config.gz")
+                    .output()
+                    .ok()
+                    .filter(|output| output.status.success())?;
+                Some(String::from_utf8_lossy(&output.stdout).to_string())
+            });
+        let modules_dir = format!("/lib/modules/{release}");
+        let modules = ["modules.builtin", "modules.dep"]
+            .iter()
+            .filter_map(|file| {
+                fs::read_to_string(format!("{modules_dir}/{file}")).ok()
+            })
+            .reduce(|builtin, dep| builtin + &dep);
+        debug!(%release, config = config.is_some(), modules = modules.is_some(),
+            "kernel inspected");
+        Kernel {
+            release,
+            config,
+            modules,
+        }
+    }
+
+    /// Whether the kernel has `feature`, built in, loaded or loadable. None
+    /// when it cannot be told.
+    pub fn has(&self, feature: &Feature) -> Option<bool> {
+        if let Some(module) = feature.module
+            && Path::new(&format!("/sys/module/{module}")).exists()
+        {
+            return Some(true);
+        }
+        if let Some(config) = &self.config {
+            let built = config.lines().any(|line| {
+                line.strip_prefix(feature.config)
+                    .is_some_and(|value| value == "=y" || value == "=m")
+            });
+            return Some(built);
+        }
+        let (Some(modules), Some(module)) = (&self.modules, feature.module)
+        else {
+            return None;
+        };
+        // Paths such as kernel/net/sched/sch_netem.ko.zst, dashes in file
+        // names standing for underscores in module names.
+        Some(modules.lines().any(|line| {
+            line.split(':')
+                .next()
+                .and_then(|path| path.rsplit('/').next())
+                .and_then(|file| file.split('.').next())
+                .is_some_and(|name| name.replace('-', "_") == module)
+        }))
+    }
+}
+
+// ==== struct Missing ====
+
+/// A kernel feature the topology relies on which the running kernel lacks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Missing {
+    pub feature: Feature,
+    pub needed_by: Vec<String>,
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is not available (needed by {})",
+            self.feature,
+            self.needed_by.join(", ")
+        )
+    }
+}
+
+/// The features `topology` relies on which `kernel` lacks, those which
+/// cannot be told about being assumed present.
+pub fn check(topology: &Topology, kernel: &Kernel) -> Vec<Missing> {
+    required(topology)
+        .into_iter()
+        .filter(|(feature, _)| kernel.has(feature) == Some(false))
+        .map(|(feature, needed_by)| Missing { feature, needed_by })
+        .collect()
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use nix::fcntl::{Flock, FlockArg};
+use yaml_rust2::yaml::{Hash, Yaml};
+use yaml_rust2::{YamlEmitter, YamlLoader};
+
+use crate::NetResult;
+use crate::error::NetError;
+
+const REGISTRY_FILE: &str = "labs.yml";
+
+/// The netgen config directory of the user: `$XDG_CONFIG_HOME/netgen`, or
+/// `~/.config/netgen`. `what` is what is looked for in it, for errors.
+pub(crate) fn config_dir(what: &str) -> NetResult<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match std::env::var_os("HOME") {
+            Some(home) => Path::new(&home).join(".config"),
+            None => {
+                return Err(NetError::BasicError(format!(
+                    "Neither XDG_CONFIG_HOME nor HOME is set, unable to \
+                        locate {what}"
+                )));
+            }
+        },
+    };
+    Ok(config_dir.join("netgen"))
+}
+
+/// Serializes the load-modify-save cycles of the file at `path` between
+/// the processes sharing it, until dropped, through `<path>.lock`.
+pub(crate) fn lock_file(path: &Path) -> NetResult<Flock<File>> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock_path = PathBuf::from(lock_path);
+    let lock_err = |err: String| {
+        NetError::BasicError(format!(
+            "Unable to lock {}: {err}",
+            lock_path.display()
+        ))
+    };
+    if let Some(dir) = lock_path.parent() {
+        fs::create_dir_all(dir).map_err(|err| lock_err(err.to_string()))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|err| lock_err(err.to_string()))?;
+    Flock::lock(file, FlockArg::LockExclusive)
+        .map_err(|(_, err)| lock_err(err.to_string()))
+}
+
+/// Writes `doc` to `path`, through `<path>.tmp` renamed over it, so that a
+/// crash halfway never leaves the file truncated. `what` names the file in
+/// errors.
+pub(crate) fn write_yaml(path: &Path, doc: &Yaml, what: &str) -> NetResult<()> {
+    let mut contents = String::new();
+    let mut emitter = YamlEmitter::new(&mut contents);
+    emitter.dump(doc).map_err(|err| {
+        NetError::BasicError(format!("Unable to write {what}: {err:?}"))
+    })?;
+    contents.push('\n');
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let write_err = |err: std::io::Error| {
+        NetError::BasicError(format!(
+            "Unable to write {}: {err:?}",
+            path.display()
+        ))
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(write_err)?;
+    }
+    fs::write(&tmp_path, contents).map_err(write_err)?;
+    fs::rename(&tmp_path, path).map_err(write_err)
+}
+
+// ==== struct Lab ====
+
+/// A topology file registered under a name, so that it can be started by
+/// name from anywhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lab {
+    pub name: String,
+    // Absolute, so that the lab can be started from any directory.
+    pub path: PathBuf,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+// ==== struct LabRegistry ====
+
+/// The labs registered on this host, persisted to `labs.yml` in the netgen
+/// config directory:
+///
+/// ```yaml
+/// ospf-basic:
+///   path: /home/me/labs/ospf/topology.yml
+///   description: three routers in a triangle
+///   tags: [ ospf, small ]
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LabRegistry {
+    pub labs: BTreeMap<String, Lab>,
+}
+
+impl LabRegistry {
+    /// Where the registry is kept: `$XDG_CONFIG_HOME/netgen`, or
+    /// `~/.config/netgen`.
+    pub fn path() -> NetResult<PathBuf> {
+        Ok(config_dir("the lab registry")?.join(REGISTRY_FILE))
+    }
+
+    /// Loads the registry, empty if nothing was registered yet.
+    pub fn load() -> NetResult<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to read {}: {err:?}",
+                path.display()
+            ))
+        })?;
+        let invalid = |reason: &str| {
+            NetError::BasicError(format!(
+                "Lab registry {} is invalid: {reason}",
+                path.display()
+            ))
+        };
+        let docs = YamlLoader::load_from_str(&contents)
+            .map_err(|err| invalid(&err.to_string()))?;
+
+        let mut registry = Self::default();
+        let labs = match docs.first() {
+            Some(Yaml::Hash(labs)) => labs,
+            Some(Yaml::Null) | None => return Ok(registry),
+            Some(_) => return Err(invalid("expected a hash")),
+        };
+        for (name, lab) in labs {
+            let (Yaml::String(name), Yaml::String(lab_path)) =
+                (name, &lab["path"])
+            else {
+                return Err(invalid("every lab needs a path"));
+            };
+            let tags = match &lab["tags"] {
+                Yaml::Array(tags) => tags
+                    .iter()
+                    .filter_map(|tag| tag.as_str().map(str::to_string))
+                    .collect(),
+                _ => vec![],
+            };
+            registry.labs.insert(
+                name.clone(),
+                Lab {
+                    name: name.clone(),
+                    path: PathBuf::from(lab_path),
+                    description: lab["description"]
+                        .as_str()
+                        .map(str::to_string),
+                    tags,
+                },
+            );
+        }
+        Ok(registry)
+    }
+
+    pub fn save(&self) -> NetResult<()> {
+        let mut doc = Hash::new();
+        for lab in self.labs.values() {
+            let mut entry = Hash::new();
+            entry.insert(
+                Yaml::String("path".to_string()),
+                Yaml::String(lab.path.to_string_lossy().to_string()),
+            );
+            if let Some(description) = &lab.description {
+                entry.insert(
+                    Yaml::String("description".to_string()),
+                    Yaml::String(description.clone()),
+                );
+            }
+            if !lab.tags.is_empty() {
+                entry.insert(
+                    Yaml::String("tags".to_string()),
+                    Yaml::Array(
+                        lab.tags.iter().cloned().map(Yaml::String).collect(),
+                    ),
+                );
+            }
+            doc.insert(Yaml::String(lab.name.clone()), Yaml::Hash(entry));
+        }
+
+        let mut contents = String::new();
+        let mut emitter = YamlEmitter::new(&mut contents);
+        emitter.dump(&Yaml::Hash(doc)).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to write the lab registry: {err:?}"
+            ))
+        })?;
+        contents.push('\n');
+
+        let path = Self::path()?;
+        let write_err = |err: std::io::Error| {
+            NetError::BasicError(format!(
+                "Unable to write {}: {err:?}",
+                path.display()
+            ))
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(write_err)?;
+        }
+        fs::write(&path, contents).map_err(write_err)
+    }
+
+    /// Registers `lab`, whose topology file has to exist. Fails if a lab of
+    /// the same name is registered already.
+    pub fn add(&mut self, mut lab: Lab) -> NetResult<()> {
+        if self.labs.contains_key(&lab.name) {
+            return Err(NetError::BasicError(format!(
+                "Lab {} is already registered",
+                lab.name
+            )));
+        }
+        lab.path = fs::canonicalize(&lab.path).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to find topology file {}: {err:?}",
+                lab.path.display()
+            ))
+        })?;
+        self.labs.insert(lab.name.clone(), lab);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> NetResult<&Lab> {
+        self.labs.get(name).ok_or_else(|| {
+            NetError::BasicError(format!(
+                "No lab named {name}, see `netgen lab list`"
+            ))
+        })
+    }
+
+    /// The labs carrying `tag`, or all of them if None.
+    pub fn list(&self, tag: Option<&str>) -> impl Iterator<Item = &Lab> {
+        self.labs.values().filter(move |lab| {
+            tag.is_none_or(|tag| lab.tags.iter().any(|lab_tag| lab_tag == tag))
+        })
+    }
+}
@@ -0,0 +1,303 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::csv;
+use crate::error::NetError;
+use crate::link::LinkEndpoint;
+use crate::topology::Topology;
+use crate::{NamespaceHandle, NetResult};
+
+// Width of the largest histogram bar.
+const BAR_WIDTH: u64 = 40;
+
+// ==== struct LinkLatency ====
+
+/// One-way latency of the packets sent over a link in one direction, as
+/// measured between the two ends of its veth pair.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkLatency {
+    pub from: String,
+    pub to: String,
+    // Packets queued for transmission on `from`.
+    pub sent: u64,
+    // Those of them received on `to`.
+    pub received: u64,
+    // Latency buckets in microseconds, as written by bpftrace ("[16, 32)"),
+    // along with the number of packets in them.
+    pub histogram: Vec<(String, u64)>,
+}
+
+impl LinkLatency {
+    /// Packets sent but never received, which includes those still in
+    /// flight when the measurement ended.
+    pub fn dropped(&self) -> u64 {
+        self.sent.saturating_sub(self.received)
+    }
+}
+
+/// Measures the one-way latency and drops of the packets going over the
+/// link between the `a` and `b` ends ("<node>:<iface>") for `duration`,
+/// in both directions.
+///
+/// Packets are timestamped with eBPF when queued on one end, before the
+/// impairments of the link apply, then matched when received on the other.
+/// `bpftrace` needs to be installed on the host.
+pub fn measure(
+    topology: &Topology,
+    a: &str,
+    b: &str,
+    duration: Duration,
+) -> NetResult<[LinkLatency; 2]> {
+    let link = topology.link_between(a, b)?;
+    let [a, b] = link.ends();
+    let ends = [(a, ns_inode(topology, a)?), (b, ns_inode(topology, b)?)];
+
+    let mut script = String::new();
+    for (direction, (from, to)) in [(&ends[0], &ends[1]), (&ends[1], &ends[0])]
+        .iter()
+        .enumerate()
+    {
+        let _ = write!(
+            script,
+            "tracepoint:net:net_dev_queue /{}/ {{
+                @queued{direction}[args->skbaddr] = nsecs;
+                @sent[{direction}] = count();
+            }}
+            tracepoint:net:netif_receive_skb
+            /{} && @queued{direction}[args->skbaddr]/ {{
+                @latency{direction} =
+                    hist((nsecs - @queued{direction}[args->skbaddr]) / 1000);
+                @received[{direction}] = count();
+                delete(@queued{direction}[args->skbaddr]);
+            }}\n",
+            filter(from.0, from.1),
+            filter(to.0, to.1),
+        );
+    }
+    let _ = write!(
+        script,
+        "interval:ms:{} {{ exit(); }}
+        END {{ clear(@queued0); clear(@queued1); }}\n",
+        duration.as_millis().max(1)
+    );
+
+    let output = Command::new("bpftrace")
+        .args(["-e", &script])
+        .output()
+        .map_err(|err| {
+            NetError::BasicError(format!("Unable to run bpftrace: {err:?}"))
+        })?;
+    if !output.status.success() {
+        return Err(NetError::BasicError(format!(
+            "Unable to measure the latency of {a} - {b}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let mut latencies = [
+        LinkLatency {
+            from: a.to_string(),
+            to: b.to_string(),
+            ..Default::default()
+        },
+        LinkLatency {
+            from: b.to_string(),
+            to: a.to_string(),
+            ..Default::default()
+        },
+    ];
+    parse(&String::from_utf8_lossy(&output.stdout), &mut latencies);
+    Ok(latencies)
+}
+
+/// Renders the latencies, one histogram per direction.
+pub fn render(latencies: &[LinkLatency]) -> String {
+    let mut out = String::new();
+    for latency in latencies {
+        let share = match latency.sent {
+            0 => 0.0,
+            sent => latency.dropped() as f64 / sent as f64 * 100.0,
+        };
+        let _ = writeln!(
+            out,
+            "{} -> {}: {} sent, {} dropped ({share:.2}%)",
+            latency.from,
+            latency.to,
+            latency.sent,
+            latency.dropped()
+        );
+
+        let width = latency
+            .histogram
+            .iter()
+            .map(|(range, _)| range.len())
+            .max()
+            .unwrap_or_default();
+        let max = latency
+            .histogram
+            .iter()
+            .map(|(_, count)| *count)
+            .max()
+            .unwrap_or_default();
+        for (range, count) in &latency.histogram {
+            let bar = (count * BAR_WIDTH).div_ceil(max.max(1)) as usize;
+            let _ = writeln!(
+                out,
+                "  {range:>width$} us  {count:>8}  {}",
+                "#".repeat(bar)
+            );
+        }
+    }
+    out
+}
+
+/// Columns of `to_csv`, one row per direction and latency bucket: the ends
+/// the packets went from and to, the packets sent, received and dropped
+/// that way, and the number of packets whose latency in microseconds is at
+/// least `bucket_min_us` and below `bucket_max_us`. Directions without any
+/// packet received have a single row, without bucket.
+pub const CSV_COLUMNS: &[&str] = &[
+    "from",
+    "to",
+    "sent",
+    "received",
+    "dropped",
+    "bucket_min_us",
+    "bucket_max_us",
+    "packets",
+];
+
+/// The latencies as CSV rows, see `CSV_COLUMNS`.
+pub fn to_csv(latencies: &[LinkLatency]) -> String {
+    let mut out = String::new();
+    for latency in latencies {
+        let row = |bucket: [String; 3]| {
+            let [min, max, packets] = bucket;
+            csv::line(&[
+                latency.from.clone(),
+                latency.to.clone(),
+                latency.sent.to_string(),
+                latency.received.to_string(),
+                latency.dropped().to_string(),
+                min,
+                max,
+                packets,
+            ])
+        };
+        if latency.histogram.is_empty() {
+            out += &row(Default::default());
+        }
+        for (range, count) in &latency.histogram {
+            let (min, max) = bucket_bounds(range)
+                .map_or((String::new(), String::new()), |(min, max)| {
+                    (min.to_string(), max.to_string())
+                });
+            out += &row([min, max, count.to_string()]);
+        }
+    }
+    out
+}
+
+// Bounds of a bucket as bpftrace writes them, "[16, 32)", "[1K, 2K)" or
+// "[0]" for a single value, the upper one being excluded.
+fn bucket_bounds(range: &str) -> Option<(u64, u64)> {
+    let value = |value: &str| {
+        let value = value.trim();
+        let (digits, unit) = match value.char_indices().last()? {
+            (index, 'K') => (&value[..index], 1 << 10),
+            (index, 'M') => (&value[..index], 1 << 20),
+            (index, 'G') => (&value[..index], 1 << 30),
+            _ => (value, 1),
+        };
+        digits.parse::<u64>().ok().map(|digits| digits * unit)
+    };
+    let range = range.strip_prefix('[')?;
+    if let Some(single) = range.strip_suffix(']') {
+        let single = value(single)?;
+        return Some((single, single + 1));
+    }
+    let (min, max) = range.strip_suffix(')')?.split_once(',')?;
+    Some((value(min)?, value(max)?))
+}
+
+// Inode of the network namespace the end lives in, switch ends living in
+// netgen's main namespace.
+fn ns_inode(topology: &Topology, end: &LinkEndpoint) -> NetResult<u64> {
+    let node = end.node.as_str();
+    let router = topology
+        .get_node(node)
+        .is_some_and(|node| node.as_router().is_some());
+    let namespace = NamespaceHandle::new(router.then_some(node));
+    let path = namespace.net_path();
+    let meta = fs::metadata(path)
+        .map_err(|_| NetError::BasicError(format!("{node} is not running")))?;
+    Ok(meta.ino())
+}
+
+// bpftrace predicate matching the packets of an interface of a namespace.
+fn filter(end: &LinkEndpoint, ns_inode: u64) -> String {
+    format!(
+        "str(args->name) == \"{}\" && ((struct sk_buff *)args->skbaddr)\
+            ->dev->nd_net.net->ns.inum == {ns_inode}",
+        end.iface.as_str()
+    )
+}
+
+// Reads the maps bpftrace prints on exit:
+//
+// @latency0:
+// [16, 32)              12 |@@@@@@@@@@@@                              |
+//
+// @sent[0]: 12
+fn parse(output: &str, latencies: &mut [LinkLatency; 2]) {
+    let direction = |index: &str| match index {
+        "0" => Some(0),
+        "1" => Some(1),
+        _ => None,
+    };
+    let mut histogram = None;
+    for line in output.lines().map(str::trim) {
+        if let Some(index) = line
+            .strip_prefix("@latency")
+            .and_then(|rest| rest.strip_suffix(':'))
+        {
+            histogram = direction(index);
+            continue;
+        }
+        if let Some(bucket) = line.strip_prefix('[')
+            && let Some(index) = histogram
+        {
+            let Some(end) = bucket.find([')', ']']) else {
+                continue;
+            };
+            let count = bucket[end + 1..]
+                .split_whitespace()
+                .next()
+                .and_then(|count| count.parse().ok())
+                .unwrap_or_default();
+            latencies[index]
+                .histogram
+                .push((format!("[{}", &bucket[..=end]), count));
+            continue;
+        }
+        histogram = None;
+
+        let Some((name, value)) = line.split_once("]: ") else {
+            continue;
+        };
+        let Some((map, index)) = name.split_once('[') else {
+            continue;
+        };
+        let (Some(index), Ok(value)) = (direction(index), value.parse()) else {
+            continue;
+        };
+        match map {
+            "@sent" => latencies[index].sent = value,
+            "@received" => latencies[index].received = value,
+            _ => {}
+        }
+    }
+}
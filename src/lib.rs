@@ -1,32 +1,207 @@
+pub mod backend;
+pub mod banner;
+pub mod bench;
+pub mod bundle;
+pub mod calibrate;
+pub mod cancel;
+pub mod check;
+mod checkpoint;
+pub mod churn;
+mod clone;
+pub mod container;
+mod coredump;
+pub mod csv;
+pub mod diff;
+mod dns;
+pub mod draw;
+mod edit;
 pub mod error;
-mod link;
+pub mod events;
+pub mod exec_history;
+mod expose;
+pub mod fib;
+mod flow;
+mod frr;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod generate;
+mod graph;
+mod hooks;
+pub mod import;
+pub mod init;
+mod injector;
+pub mod invariant;
+pub mod kernel;
+pub mod lab;
+pub mod latency;
+mod limits;
+pub mod link;
+pub mod lldp;
+pub mod mgmt;
+mod nat;
+pub mod netem;
 pub mod node;
+pub mod notify;
+mod ntp;
+pub mod packet;
 mod parser;
+mod pidfd;
+mod pki;
+pub mod plan;
+mod pool;
+pub mod probe;
+pub mod provenance;
+pub mod ps;
+pub mod ramp;
+pub mod raw;
+pub mod report;
+pub mod resources;
+mod rule;
+pub mod runs;
+pub mod schedule;
+mod service;
+pub mod snapshot;
+pub mod sockets;
+mod ssh;
+pub mod state;
+pub mod stats;
+pub mod status;
+pub mod suite;
+pub mod sweep;
+mod table;
+pub mod template;
+pub mod testing;
+mod toggles;
+pub mod tools;
 pub mod topology;
+pub mod validator;
+pub mod watch;
 
+use std::cell::RefCell;
 use std::fs::{File, create_dir_all, remove_dir_all};
-use std::os::fd::AsFd;
-use std::os::unix::fs::MetadataExt;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::OnceLock;
 use std::{fs, io};
 
 use error::{NamespaceError, NetError};
-use nix::mount::{MsFlags, mount, umount};
+use nix::fcntl::OFlag;
+use nix::mount::{MntFlags, MsFlags, mount, umount, umount2};
 use nix::sched::{CloneFlags, setns, unshare};
-use nix::sys::signal::{Signal, kill};
-use nix::unistd::{ForkResult, Pid, fork, pause};
+use nix::sys::signal::{SigSet, Signal};
+use nix::sys::statfs::{NSFS_MAGIC, statfs};
+use nix::sys::wait::{WaitPidFlag, WaitStatus, waitpid};
+use nix::unistd::{ForkResult, fork, pause, pipe2};
+use pidfd::PidFd;
 use tracing::{debug, error};
 
 pub type NetResult<T> = std::result::Result<T, error::NetError>;
 
-pub const NS_DIR: &str = "/tmp/netgen-rs/ns";
-pub const DEVICES_NS_DIR: &str = "/tmp/netgen-rs/ns/devices";
-pub const MAIN_NS_DIR: &str = "/tmp/netgen-rs/ns/main";
+/// Where netgen keeps the namespaces and runtime files of the topology.
+pub const DEFAULT_RUN_DIR: &str = "/tmp/netgen-rs";
+
+/// Where netgen keeps them unless a thread picked another directory:
+/// `DEFAULT_RUN_DIR`, or a tmpfs in containers whose /tmp is an overlay.
+pub fn default_run_dir() -> &'static str {
+    static DIR: OnceLock<String> = OnceLock::new();
+    DIR.get_or_init(container::run_dir)
+}
+
+thread_local! {
+    // Replaces default_run_dir() for the topology this thread works on, e.g.
+    // one test case out of several running in parallel. Processes forked
+    // from the thread keep it, threads it spawns only through
+    // `with_run_dir`.
+    static RUN_DIR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub fn run_dir() -> String {
+    RUN_DIR.with_borrow(|dir| {
+        dir.clone().unwrap_or_else(|| default_run_dir().to_string())
+    })
+}
+
+/// Makes the calling thread work on the topology under `dir`, or under
+/// `default_run_dir()` again when `None`. Returns the directory it worked on
+/// until then, if not the default one.
+pub fn set_run_dir(dir: Option<String>) -> Option<String> {
+    RUN_DIR.replace(dir)
+}
+
+/// `f`, to be run by a thread spawned from the calling one, working on the
+/// same run directory: threads do not inherit the one `set_run_dir` picked.
+pub(crate) fn with_run_dir<F, T>(f: F) -> impl FnOnce() -> T + Send
+where
+    F: FnOnce() -> T + Send,
+{
+    let dir = RUN_DIR.with_borrow(Clone::clone);
+    move || {
+        let previous = set_run_dir(dir);
+        let result = f();
+        set_run_dir(previous);
+        result
+    }
+}
+
+pub fn ns_dir() -> String {
+    format!("{}/ns", run_dir())
+}
+
+pub fn devices_ns_dir() -> String {
+    format!("{}/ns/devices", run_dir())
+}
+
+pub fn main_ns_dir() -> String {
+    format!("{}/ns/main", run_dir())
+}
+
+pub fn state_file() -> String {
+    format!("{}/state.yml", run_dir())
+}
+
+pub fn events_file() -> String {
+    format!("{}/events.log", run_dir())
+}
+
+/// Where the directories the nodes persist are kept, by node name. Unlike
+/// `ns_dir()`, it is left alone when the topology is stopped.
+pub fn persist_dir() -> String {
+    format!("{}/persist", run_dir())
+}
+
+/// Session identifier prefixed to host-visible interface names when the
+/// topology, rendered as `source`, does not set one: "ng" and a hash of the
+/// topology and of the run directory. The same lab gets the same session
+/// every time it is started, while two labs running side by side, or the
+/// same one under two run directories, do not share bridges.
+pub fn default_session(source: &str) -> String {
+    let hash = provenance::sha256_hex(format!("{}\n{source}", run_dir()));
+    format!("ng{}", &hash[..3])
+}
+
+/// How many netlink requests the bulk setup phases keep in flight when
+/// `--parallelism` is not given.
+pub const DEFAULT_PARALLELISM: usize = 4;
+
+// Sent by the namespace holder once its namespaces are mounted.
+const NS_READY: &[u8] = b"ready";
+
+// File under the device directory recording the PID and start time of the
+// process anchoring its namespaces.
+const NS_ANCHOR_FILE: &str = "anchor";
+
+/// Longest interface name the kernel accepts (IFNAMSIZ minus the NUL).
+pub const IFNAME_MAX_LEN: usize = 15;
 
 /// For a Router R, we mount the relevant namespaces to the locations.
 ///
 /// A network and PID namespaces are created, and mounted to
-/// `DEVICES_NS_DIR/{device_name}/net` / `DEVICES_NS_DIR/{device_name}/pid`
+/// `devices_ns_dir()/{device_name}/net` / `devices_ns_dir()/{device_name}/pid`
 /// if it is a Router
 ///
 ///
@@ -34,16 +209,34 @@ pub const MAIN_NS_DIR: &str = "/tmp/netgen-rs/ns/main";
 /// used as the parent to all the Router namespaces that will eventually be
 /// created. In that case, A network and PID namespaces are created, and
 /// mounted to :
-/// `MAIN_NS_DIR/net` and `MAIN_NS_DIR/pid` are
-pub fn mount_device(
-    device_name: Option<String>,
-) -> NetResult<(String, String)> {
+/// `main_ns_dir()/net` and `main_ns_dir()/pid` are
+///
+/// Namespaces that are already mounted and alive are reused as they are, so
+/// calling this twice for the same device is harmless. Either way, the
+/// calling process ends up back in the main namespace.
+pub fn mount_device(device_name: Option<String>) -> NetResult<NamespaceHandle> {
     let device = DeviceDetails::new(device_name.clone());
+    let handle = device.handle();
+
+    if handle.is_active() {
+        debug!(device = %device.name, "namespaces already mounted, reusing");
+        enter_ns(None)?;
+        return Ok(handle);
+    }
+
     let clone_flags = match device_name {
         Some(_) => CloneFlags::CLONE_NEWNET | CloneFlags::CLONE_NEWPID,
         None => CloneFlags::CLONE_NEWNET,
     };
 
+    // Where to take the caller back to should anything fail, the main
+    // namespace being the one unshared when mounting it.
+    let origin_path = "/proc/thread-self/ns/net";
+    let origin =
+        File::open(origin_path).map_err(|err| NamespaceError::FileOpen {
+            path: origin_path.to_string(),
+            source: err,
+        })?;
     unshare(clone_flags).map_err(|err| {
         NetError::NamespaceError(NamespaceError::Unshare {
             ns_name: device.name.clone(),
@@ -51,37 +244,84 @@ pub fn mount_device(
         })
     })?;
 
-    match unsafe { fork() } {
-        Ok(ForkResult::Child) => {
-            create_ns(&device)?;
-        }
-        Ok(ForkResult::Parent { .. }) => {
-            // Waiting for child
-        }
-        Err(err) => {
-            error!(
-                device = %device.name,
-                "unable to create a second child fork: {err:?}"
-            );
+    // Whatever happens, the caller is taken out of the namespace it just
+    // unshared.
+    if let Err(err) = spawn_ns_holder(&device, &handle) {
+        if let Err(restore_err) =
+            setns(origin.as_fd(), CloneFlags::CLONE_NEWNET)
+        {
+            error!(%restore_err, "unable to leave the unshared namespace");
         }
-    }
-
-    // During the creation of the main namespace, we wait for the mounting of
-    // the namespace first.
-    if device_name.is_none() {
-        debug!("Starting main namespace");
-        std::thread::sleep(std::time::Duration::from_secs(1));
+        return Err(err);
     }
 
     //Go back to main namespace
     enter_ns(None)?;
 
-    Ok((device.netns_path(), device.pidns_path()))
+    Ok(handle)
+}
+
+// Forks the process holding the namespaces the caller unshared, which
+// mounts them and then stays around, returning once they are mounted.
+fn spawn_ns_holder(
+    device: &DeviceDetails,
+    handle: &NamespaceHandle,
+) -> NetResult<()> {
+    // The child holding the namespaces reports through this pipe whether
+    // it managed to mount them.
+    let (status_reader, status_writer) =
+        pipe2(OFlag::O_CLOEXEC).map_err(|err| NamespaceError::Setup {
+            device: device.name.clone(),
+            reason: format!("unable to create status pipe: {err}"),
+        })?;
+
+    match unsafe { fork() } {
+        Ok(ForkResult::Child) => {
+            drop(status_reader);
+            cancel::reset_interrupts();
+            let mut status_writer = File::from(status_writer);
+            match create_ns(device) {
+                Ok(()) => {
+                    let _ = status_writer.write_all(NS_READY);
+                    drop(status_writer);
+                    run_anchor();
+                }
+                Err(err) => {
+                    let _ = status_writer.write_all(err.to_string().as_bytes());
+                    std::process::exit(1);
+                }
+            }
+        }
+        Ok(ForkResult::Parent { child }) => {
+            drop(status_writer);
+            let mut status = Vec::new();
+            let _ = File::from(status_reader).read_to_end(&mut status);
+            if status != NS_READY {
+                // Exiting, or already gone, once it reported.
+                let _ = waitpid(child, None);
+                let reason = match status.is_empty() {
+                    true => "namespace holder exited".to_string(),
+                    false => String::from_utf8_lossy(&status).to_string(),
+                };
+                return Err(NamespaceError::Setup {
+                    device: device.name.clone(),
+                    reason,
+                }
+                .into());
+            }
+            handle.record_anchor(child.as_raw())
+        }
+        Err(err) => Err(NamespaceError::Fork {
+            fork_function: String::from("mount_device"),
+            source: err,
+        }
+        .into()),
+    }
 }
 
 pub fn mount_router_volumes(router: &node::Router) -> NetResult<()> {
     if !router.volumes.is_empty() {
-        let vols_dir = format!("{DEVICES_NS_DIR}/{}/vols", router.name);
+        let vols_dir = format!("{}/{}/vols", devices_ns_dir(), router.name);
         fs::create_dir_all(&vols_dir).map_err(|err| {
             NetError::BasicError(format!(
                 "Unable to create {vols_dir}\n{err:?}"
@@ -92,6 +332,86 @@ pub fn mount_router_volumes(router: &node::Router) -> NetResult<()> {
     for volume in &router.volumes {
         mount_volume(&router.name, volume)?;
     }
+
+    for dir in &router.persist {
+        mount_persisted(&router.name, dir)?;
+    }
+
+    if !router.files.is_empty() {
+        let files_dir = format!("{}/{}/files", devices_ns_dir(), router.name);
+        fs::create_dir_all(&files_dir).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to create {files_dir}\n{err:?}"
+            ))
+        })?;
+    }
+
+    for file in &router.files {
+        mount_file(&router.name, file)?;
+    }
+
+    if router.frr.is_some() {
+        frr::mount_run_dir(&router.name)?;
+    }
+    Ok(())
+}
+
+/// Writes the file into the device's directory, then bind-mounts it onto
+/// its destination. Called from within the router's private mount
+/// namespace, the host only ever sees an empty placeholder at `dst`.
+fn mount_file(device_name: &str, file: &node::FileDrop) -> NetResult<()> {
+    let staging_path = file_staging_path(device_name, file);
+
+    match &file.source {
+        node::FileSource::Inline(content) => fs::write(&staging_path, content),
+        node::FileSource::Path(src) => fs::copy(src, &staging_path).map(|_| ()),
+    }
+    .map_err(|err| {
+        NetError::BasicError(format!(
+            "Unable to write {staging_path} for {}: {err:?}",
+            file.dst
+        ))
+    })?;
+
+    if let Some(mode) = file.mode {
+        fs::set_permissions(&staging_path, fs::Permissions::from_mode(mode))
+            .map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to set mode {mode:o} on {staging_path}: {err:?}"
+                ))
+            })?;
+    }
+
+    let dst_path = Path::new(&file.dst);
+    if !dst_path.exists() {
+        if let Some(parent) = dst_path.parent() {
+            fs::create_dir_all(parent).map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to create file path {parent:?}: {err:?}"
+                ))
+            })?;
+        }
+        File::create(dst_path).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to create file {dst_path:?}: {err:?}"
+            ))
+        })?;
+    }
+
+    mount(
+        Some(staging_path.as_str()),
+        dst_path,
+        None::<&str>,
+        MsFlags::MS_BIND,
+        None::<&str>,
+    )
+    .map_err(|err| {
+        NetError::NamespaceError(NamespaceError::Mount {
+            ns_type: "file mount".to_string(),
+            device: device_name.to_string(),
+            source: err,
+        })
+    })?;
     Ok(())
 }
 
@@ -106,15 +426,7 @@ fn mount_volume(device_name: &str, volume: &node::Volume) -> NetResult<()> {
         return Err(err);
     }
 
-    // Derive a stable staging path from the destination.
-    let binding = match src_path.file_name() {
-        Some(src_path_str) => PathBuf::from(format!(
-            "/tmp/netgen-rs/ns/devices/{device_name}/vols/{}",
-            src_path_str.to_string_lossy()
-        )),
-        None => src_path.to_path_buf(),
-    };
-
+    let binding = volume_staging_path(device_name, volume);
     let staging_path = binding.as_path();
 
     // Create staging path if it doesn't exist.
@@ -178,7 +490,71 @@ fn mount_volume(device_name: &str, volume: &node::Volume) -> NetResult<()> {
     Ok(())
 }
 
-fn copy_dir_all(
+// Where the directory `dir` the device persists lives on the host.
+pub(crate) fn persisted_path(device_name: &str, dir: &str) -> String {
+    format!(
+        "{}/{device_name}/{}",
+        persist_dir(),
+        dir.trim_start_matches('/').replace('/', "_")
+    )
+}
+
+// Bind-mounts what the device kept of `dir` onto it, both being created on
+// first use. Called from within the router's private mount namespace.
+fn mount_persisted(device_name: &str, dir: &str) -> NetResult<()> {
+    let persisted = persisted_path(device_name, dir);
+    for path in [persisted.as_str(), dir] {
+        fs::create_dir_all(path).map_err(|err| {
+            NetError::BasicError(format!("Unable to create {path}: {err:?}"))
+        })?;
+    }
+    mount(
+        Some(persisted.as_str()),
+        dir,
+        None::<&str>,
+        MsFlags::MS_BIND,
+        None::<&str>,
+    )
+    .map_err(|err| {
+        NetError::NamespaceError(NamespaceError::Mount {
+            ns_type: "persistent directory".to_string(),
+            device: device_name.to_string(),
+            source: err,
+        })
+    })?;
+    Ok(())
+}
+
+// Where the file dropped at `file.dst` of the device lives on the host.
+pub(crate) fn file_staging_path(
+    device_name: &str,
+    file: &node::FileDrop,
+) -> String {
+    format!(
+        "{}/{device_name}/files/{}",
+        devices_ns_dir(),
+        file.dst.trim_start_matches('/').replace('/', "_")
+    )
+}
+
+// Where the volume of the device lives on the host, a stable path derived
+// from its source.
+pub(crate) fn volume_staging_path(
+    device_name: &str,
+    volume: &node::Volume,
+) -> PathBuf {
+    let src_path = Path::new(&volume.src);
+    match src_path.file_name() {
+        Some(src_path_str) => PathBuf::from(format!(
+            "{}/{device_name}/vols/{}",
+            devices_ns_dir(),
+            src_path_str.to_string_lossy()
+        )),
+        None => src_path.to_path_buf(),
+    }
+}
+
+pub(crate) fn copy_dir_all(
     src: impl AsRef<Path>,
     dst: impl AsRef<Path>,
 ) -> io::Result<()> {
@@ -240,34 +616,43 @@ pub fn enter_ns(device_name: Option<String>) -> NetResult<()> {
         },
     )?;
 
-    let pid = Pid::this();
     match unsafe { fork() } {
         Ok(ForkResult::Child) => {
             // Just forking so we can actually enter the PID namespace.
+            Ok(())
         }
         Ok(ForkResult::Parent { child }) => {
-            nix::sys::wait::waitpid(child, None).map_err(|err| {
-                NamespaceError::Fork {
+            let status =
+                waitpid(child, None).map_err(|err| NamespaceError::Fork {
                     fork_function: String::from("enter_ns"),
                     source: err,
-                }
-            })?;
-        }
-        Err(err) => {
-            return Err(NamespaceError::Fork {
-                fork_function: String::from("enter_ns"),
-                source: err,
+                })?;
+
+            // Only continue with the child process, passing its outcome on.
+            match status {
+                WaitStatus::Exited(_, code) => std::process::exit(code),
+                _ => std::process::exit(1),
             }
-            .into());
         }
+        Err(err) => Err(NamespaceError::Fork {
+            fork_function: String::from("enter_ns"),
+            source: err,
+        }
+        .into()),
     }
+}
 
-    // Only continue with the child process.
-    if Pid::this() == pid {
-        std::process::exit(0);
-    }
-
-    Ok(())
+/// Runs `cmd` with `args` inside the network namespace of `device_name` and
+/// waits for it to complete, capturing its output.
+///
+/// Unlike `enter_ns`, the calling process stays where it is: only the
+/// spawned command is moved into the device's namespace.
+pub fn exec_in_ns(
+    device_name: &str,
+    cmd: &str,
+    args: &[&str],
+) -> NetResult<Output> {
+    NamespaceHandle::new(Some(device_name)).exec(cmd, args)
 }
 
 fn create_ns(device: &DeviceDetails) -> NetResult<()> {
@@ -278,79 +663,163 @@ fn create_ns(device: &DeviceDetails) -> NetResult<()> {
         })
     })?;
 
-    // Create net namespace.
-    File::create(device.netns_path()).map_err(|err| {
-        NetError::BasicError(format!(
-            "unable to create path {} -> {err:?}",
-            &device.netns_path()
-        ))
-    })?;
-    let proc_net_ns_path = "/proc/self/ns/net".to_string();
-    let net_path = &device.netns_path();
-    let target_net_path = Path::new(net_path);
+    bind_ns(device, "net", &device.netns_path())?;
+    bind_ns(device, "pid", &device.pidns_path())
+}
 
-    mount(
-        Some(proc_net_ns_path.as_str()),
-        target_net_path.as_os_str(),
-        None::<&str>,
-        MsFlags::MS_BIND,
-        None::<&str>,
-    )
-    .map_err(|err| {
-        NetError::NamespaceError(NamespaceError::Mount {
-            ns_type: String::from("network"),
-            device: device.name.clone(),
-            source: err,
-        })
-    })?;
+// Bind-mounts our own `ns_type` namespace onto `target`, replacing any stale
+// mount left behind by a previous run.
+fn bind_ns(
+    device: &DeviceDetails,
+    ns_type: &str,
+    target: &str,
+) -> NetResult<()> {
+    if is_ns_mount(target) {
+        umount2(target, MntFlags::MNT_DETACH).map_err(|err| {
+            NamespaceError::Unmount {
+                path: target.to_string(),
+                source: err,
+            }
+        })?;
+    }
 
-    // Create PID namespace.
-    File::create(device.pidns_path()).map_err(|err| {
+    File::create(target).map_err(|err| {
         NetError::BasicError(format!(
-            "unable to create path {} -> {err:?}",
-            &device.pidns_path()
+            "unable to create path {target} -> {err:?}"
         ))
     })?;
 
-    let proc_pid_ns_path = "/proc/self/ns/pid".to_string();
-    let pid_path = &device.pidns_path();
-    let target_pid_path = Path::new(pid_path);
-
     mount(
-        Some(proc_pid_ns_path.as_str()),
-        target_pid_path.as_os_str(),
+        Some(format!("/proc/self/ns/{ns_type}").as_str()),
+        target,
         None::<&str>,
         MsFlags::MS_BIND,
         None::<&str>,
     )
     .map_err(|err| {
         NetError::NamespaceError(NamespaceError::Mount {
-            ns_type: String::from("pid"),
+            ns_type: ns_type.to_string(),
             device: device.name.clone(),
             source: err,
         })
     })?;
-
-    pause();
     Ok(())
 }
 
-/// Kills the PIDs the device namespaces are running on,
-/// then unmounts their mountpoints.
+// Whether a namespace is bind-mounted at `path`.
+fn is_ns_mount(path: &str) -> bool {
+    statfs(path)
+        .map(|stat| stat.filesystem_type() == NSFS_MAGIC)
+        .unwrap_or(false)
+}
+
+// Body of the process anchoring a device's namespaces.
+//
+// For routers it is the init of the PID namespace: daemons double-forked by
+// the router's scripts are re-parented to it, so it reaps them. Killing it
+// makes the kernel kill everything else in the namespace.
+fn run_anchor() -> ! {
+    let mut sigchld = SigSet::empty();
+    sigchld.add(Signal::SIGCHLD);
+    if sigchld.thread_block().is_err() {
+        // Without SIGCHLD we can't reap, only keep the namespaces alive.
+        loop {
+            pause();
+        }
+    }
+
+    loop {
+        let _ = sigchld.wait();
+        while let Ok(status) = waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+            if status == WaitStatus::StillAlive {
+                break;
+            }
+        }
+    }
+}
+
+/// Kills the process anchoring the device namespaces, and with it everything
+/// running in a router's PID namespace, then unmounts their mountpoints.
 pub(crate) fn destroy_ns(device_name: Option<String>) -> NetResult<()> {
     let device = DeviceDetails::new(device_name.clone());
+    let handle = device.handle();
 
-    if let Some(pid) = find_pid_from_mountpoint(&device.netns_path()) {
-        kill(Pid::from_raw(pid), Signal::SIGKILL).map_err(|err| {
-            NetError::BasicError(format!(
-                "Unable to kill process PID {pid} : {err:?}"
-            ))
-        })?;
+    if let Some(pid) = handle
+        .anchor()
+        .or_else(|| find_pid_from_mountpoint(&device.netns_path()))
+    {
+        PidFd::open(pid)
+            .and_then(|pidfd| pidfd.signal(Signal::SIGKILL))
+            .map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to kill process PID {pid} : {err:?}"
+                ))
+            })?;
     }
 
     umount_ns(device_name)
 }
 
+/// Tears down what a run which did not stop cleanly left behind: the
+/// processes still anchoring namespaces, the namespace mounts, the files
+/// under `ns_dir()` and the state file.
+///
+/// Only meant for when no netgen process is running the topology anymore.
+pub fn clean_stale_run() -> NetResult<()> {
+    // The processes run for switches live outside of any node's namespaces,
+    // nothing else takes them down.
+    if let Ok(Some(state)) = state::TopologyState::load() {
+        for process in &state.processes {
+            if let Err(err) = process.stop() {
+                error!(%err, "process left running");
+            }
+        }
+    }
+
+    let mut devices = vec![None];
+    if let Ok(entries) = fs::read_dir(devices_ns_dir()) {
+        devices.extend(
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .map(Some),
+        );
+    }
+
+    for device in devices {
+        let handle = NamespaceHandle::new(device.as_deref());
+        // Killing a router's init takes down the daemons it runs.
+        if let Some(pid) = handle.anchor() {
+            debug!(device = %handle.name, pid, "killing stale anchor");
+            let _ = PidFd::open(pid)
+                .and_then(|pidfd| pidfd.signal(Signal::SIGKILL));
+        }
+        for path in [handle.net_path(), handle.pid_path()] {
+            if !is_ns_mount(path) {
+                continue;
+            }
+            umount2(path, MntFlags::MNT_DETACH).map_err(|err| {
+                NamespaceError::Unmount {
+                    path: path.to_string(),
+                    source: err,
+                }
+            })?;
+        }
+    }
+
+    let ns_dir = ns_dir();
+    match remove_dir_all(&ns_dir) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => {
+            return Err(NetError::BasicError(format!(
+                "Unable to remove {ns_dir}: {err:?}"
+            )));
+        }
+    }
+    state::TopologyState::remove()
+}
+
 /// Deletes the namespace created by the Router (if it exists)
 /// If deleting the main namespace, we have device_name as None.
 fn umount_ns(device_name: Option<String>) -> NetResult<()> {
@@ -423,6 +892,204 @@ fn find_pid_from_mountpoint(mountpoint: &str) -> Option<i32> {
     None
 }
 
+// Whether the process runs the same executable as we do, according to its
+// command line.
+fn is_netgen_process(pid: i32) -> bool {
+    let Ok(cmdline) = fs::read(format!("/proc/{pid}/cmdline")) else {
+        return false;
+    };
+    let program = |arg0: &[u8]| {
+        let arg0 = Path::new(std::ffi::OsStr::from_bytes(arg0));
+        arg0.file_name().map(|name| name.to_os_string())
+    };
+    let arg0 = cmdline.split(|&byte| byte == 0).next().unwrap_or_default();
+    let own = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_name().map(|name| name.to_os_string()));
+    own.is_some() && program(arg0) == own
+}
+
+/// Whether the process runs in `device_name`'s PID namespace.
+///
+/// Switches have no namespaces of their own, the processes netgen runs for
+/// them live in the main network namespace.
+pub(crate) fn process_in_device(device_name: &str, pid: i32) -> bool {
+    let device = DeviceDetails::new(Some(device_name.to_string()));
+    let ns_inode = |path: &str| fs::metadata(path).ok().map(|meta| meta.ino());
+
+    if let Some(device_inode) = ns_inode(&device.pidns_path()) {
+        return ns_inode(&format!("/proc/{pid}/ns/pid")) == Some(device_inode);
+    }
+    let main_inode = ns_inode(&DeviceDetails::new(None).netns_path());
+    main_inode.is_some()
+        && ns_inode(&format!("/proc/{pid}/ns/net")) == main_inode
+}
+
+/// Start time of the process, in clock ticks since boot.
+pub(crate) fn process_start_time(pid: i32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+    // The command name may contain spaces, fields are counted from the
+    // closing parenthesis: starttime is the 22nd field overall.
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+// ==== struct NamespaceHandle ====
+
+/// The namespaces backing a device, or the main namespace, as mounted by
+/// `mount_device`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespaceHandle {
+    name: String,
+    net_path: String,
+    pid_path: String,
+}
+
+impl NamespaceHandle {
+    /// Handle for the device's namespaces, or for the main namespace when
+    /// `device_name` is None. They might not be mounted.
+    pub fn new(device_name: Option<&str>) -> Self {
+        DeviceDetails::new(device_name.map(str::to_string)).handle()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn net_path(&self) -> &str {
+        &self.net_path
+    }
+
+    pub fn pid_path(&self) -> &str {
+        &self.pid_path
+    }
+
+    /// Whether both namespaces are mounted.
+    pub fn is_mounted(&self) -> bool {
+        is_ns_mount(&self.net_path) && is_ns_mount(&self.pid_path)
+    }
+
+    /// Whether both namespaces are mounted and still held by a process, i.e.
+    /// usable as they are.
+    pub fn is_active(&self) -> bool {
+        self.is_mounted()
+            && (self.anchor().is_some()
+                || find_pid_from_mountpoint(&self.net_path).is_some())
+    }
+
+    /// Whether the namespaces are mounted and held by a live netgen process,
+    /// as opposed to left behind by one which crashed.
+    pub fn is_running(&self) -> bool {
+        self.is_mounted()
+            && self
+                .anchor()
+                .or_else(|| find_pid_from_mountpoint(&self.net_path))
+                .is_some_and(is_netgen_process)
+    }
+
+    /// Runs `cmd` with `args` inside the network namespace and waits for it
+    /// to complete, capturing its output.
+    pub fn exec(&self, cmd: &str, args: &[&str]) -> NetResult<Output> {
+        let (mut command, _net_file) = self.command(cmd, args)?;
+        command.output().map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to run '{cmd}' in {}: {err:?}",
+                self.name
+            ))
+        })
+    }
+
+    /// Runs `cmd` with `args` inside the network namespace and waits for it
+    /// to complete, its standard input and outputs being ours.
+    pub fn run(&self, cmd: &str, args: &[&str]) -> NetResult<ExitStatus> {
+        let (mut command, _net_file) = self.command(cmd, args)?;
+        command.status().map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to run '{cmd}' in {}: {err:?}",
+                self.name
+            ))
+        })
+    }
+
+    /// Starts `cmd` with `args` inside the network namespace without
+    /// waiting for it, its standard output being piped to us.
+    pub fn spawn(&self, cmd: &str, args: &[&str]) -> NetResult<Child> {
+        let (mut command, _net_file) = self.command(cmd, args)?;
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to run '{cmd}' in {}: {err:?}",
+                    self.name
+                ))
+            })
+    }
+
+    // The command, set to enter the network namespace, along with the file
+    // holding it, which has to outlive the command being spawned.
+    fn command(&self, cmd: &str, args: &[&str]) -> NetResult<(Command, File)> {
+        let net_file = File::open(&self.net_path).map_err(|err| {
+            NamespaceError::FileOpen {
+                path: self.net_path.clone(),
+                source: err,
+            }
+        })?;
+        let net_fd = net_file.as_raw_fd();
+
+        let mut command = Command::new(cmd);
+        command.args(args);
+
+        // SAFETY: setns is async-signal-safe, and `net_file` outlives the
+        // spawned command.
+        unsafe {
+            command.pre_exec(move || {
+                let fd = BorrowedFd::borrow_raw(net_fd);
+                setns(fd, CloneFlags::CLONE_NEWNET).map_err(io::Error::from)
+            });
+        }
+        Ok((command, net_file))
+    }
+
+    /// PID of the process anchoring the namespaces, as seen from the host.
+    ///
+    /// For a router, this is the init of its PID namespace. None when the
+    /// recorded process is gone or was replaced by an unrelated one.
+    pub fn anchor(&self) -> Option<i32> {
+        let record = fs::read_to_string(self.anchor_path()).ok()?;
+        let (pid, start_time) = record.trim().split_once(' ')?;
+        let pid: i32 = pid.parse().ok()?;
+        let start_time: u64 = start_time.parse().ok()?;
+
+        let ns_inode =
+            |path: &str| fs::metadata(path).ok().map(|meta| meta.ino());
+        let holds_ns = ns_inode(&self.net_path).is_some()
+            && ns_inode(&format!("/proc/{pid}/ns/net"))
+                == ns_inode(&self.net_path);
+        (process_start_time(pid) == Some(start_time) && holds_ns).then_some(pid)
+    }
+
+    fn record_anchor(&self, pid: i32) -> NetResult<()> {
+        let path = self.anchor_path();
+        let start_time =
+            process_start_time(pid).ok_or_else(|| NamespaceError::Setup {
+                device: self.name.clone(),
+                reason: format!("namespace holder {pid} exited"),
+            })?;
+        fs::write(&path, format!("{pid} {start_time}\n")).map_err(|err| {
+            NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+        })
+    }
+
+    fn anchor_path(&self) -> String {
+        let home = Path::new(&self.net_path).parent().unwrap_or(Path::new("/"));
+        format!("{}/{NS_ANCHOR_FILE}", home.display())
+    }
+}
+
 // ==== struct DeviceDetails ====
 
 struct DeviceDetails {
@@ -435,15 +1102,23 @@ impl DeviceDetails {
         match name {
             Some(name) => Self {
                 name: name.clone(),
-                home_path: format!("{DEVICES_NS_DIR}/{name}"),
+                home_path: format!("{}/{name}", devices_ns_dir()),
             },
             None => Self {
                 name: "main".to_string(),
-                home_path: MAIN_NS_DIR.to_string(),
+                home_path: main_ns_dir(),
             },
         }
     }
 
+    fn handle(&self) -> NamespaceHandle {
+        NamespaceHandle {
+            name: self.name.clone(),
+            net_path: self.netns_path(),
+            pid_path: self.pidns_path(),
+        }
+    }
+
     // Network namespace Path.
     fn netns_path(&self) -> String {
         format!("{}/net", self.home_path)
@@ -0,0 +1,123 @@
+use std::fs;
+
+// ==== enum IoClass ====
+
+// I/O scheduling class of a process, as ionice(1) names them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IoClass {
+    // Levels from 0, served first, to 7.
+    Realtime(u8),
+    BestEffort(u8),
+    // Only served when no other process wants the disk.
+    Idle,
+}
+
+impl IoClass {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        let (class, level) = match name.split_once(':') {
+            Some((class, level)) => (class, Some(level.parse().ok()?)),
+            None => (name, None),
+        };
+        let level = level.unwrap_or(4);
+        if level > 7 {
+            return None;
+        }
+        match class {
+            "realtime" => Some(IoClass::Realtime(level)),
+            "best-effort" => Some(IoClass::BestEffort(level)),
+            "idle" => Some(IoClass::Idle),
+            _ => None,
+        }
+    }
+
+    // The priority ioprio_set takes: the class in the upper bits, the level
+    // in the lower ones.
+    fn ioprio(&self) -> libc::c_int {
+        const CLASS_SHIFT: libc::c_int = 13;
+        let (class, level) = match self {
+            IoClass::Realtime(level) => (1, *level),
+            IoClass::BestEffort(level) => (2, *level),
+            IoClass::Idle => (3, 0),
+        };
+        (class << CLASS_SHIFT) | libc::c_int::from(level)
+    }
+}
+
+// ==== struct ProcessLimits ====
+
+// Limits of the processes started in a router, its daemons among them,
+// e.g. for route-scale tests outgrowing the default fd limit, or for a lab
+// not to starve the rest of a laptop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ProcessLimits {
+    // RLIMIT_NOFILE, both soft and hard.
+    pub(crate) nofile: Option<u64>,
+    // From -1000, never killed, to 1000, killed first when out of memory.
+    pub(crate) oom_score_adj: Option<i32>,
+    // CPU scheduling niceness, from -20 to 19, the nicest.
+    pub(crate) nice: Option<i32>,
+    pub(crate) ionice: Option<IoClass>,
+}
+
+impl ProcessLimits {
+    // These limits, those left unset taking the value of `defaults`.
+    pub(crate) fn or(self, defaults: Self) -> Self {
+        Self {
+            nofile: self.nofile.or(defaults.nofile),
+            oom_score_adj: self.oom_score_adj.or(defaults.oom_score_adj),
+            nice: self.nice.or(defaults.nice),
+            ionice: self.ionice.or(defaults.ionice),
+        }
+    }
+
+    // Applies the limits to the calling process, the programs it execs and
+    // forks inheriting them.
+    pub(crate) fn apply(&self) -> Result<(), String> {
+        if let Some(nofile) = self.nofile {
+            let limit = libc::rlimit {
+                rlim_cur: nofile,
+                rlim_max: nofile,
+            };
+            // SAFETY: `limit` is a valid rlimit.
+            if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+                return Err(format!(
+                    "unable to set RLIMIT_NOFILE to {nofile}: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+        }
+        if let Some(adj) = self.oom_score_adj {
+            fs::write("/proc/self/oom_score_adj", adj.to_string()).map_err(
+                |err| format!("unable to set oom_score_adj to {adj}: {err}"),
+            )?;
+        }
+        if let Some(nice) = self.nice {
+            // SAFETY: plain syscall on the calling process.
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+                return Err(format!(
+                    "unable to set the niceness to {nice}: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+        }
+        if let Some(class) = self.ionice {
+            const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+            // SAFETY: plain syscall on the calling process.
+            let set = unsafe {
+                libc::syscall(
+                    libc::SYS_ioprio_set,
+                    IOPRIO_WHO_PROCESS,
+                    0,
+                    class.ioprio(),
+                )
+            };
+            if set != 0 {
+                return Err(format!(
+                    "unable to set the I/O class to {class:?}: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,47 +1,197 @@
+use std::borrow::Borrow;
 use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::File;
 use std::os::fd::AsRawFd;
+use std::time::Duration;
 
+use futures_util::TryStreamExt;
+use futures_util::future::join_all;
+
+use netlink_packet_route::link::{LinkAttribute, LinkFlags};
 use nix::net::if_::if_nametoindex;
-use rand::Rng;
-use rand::distributions::Alphanumeric;
-use rtnetlink::{LinkUnspec, LinkVeth, new_connection};
+use rtnetlink::{Handle, LinkUnspec, LinkVeth, new_connection};
 use tokio::runtime::Runtime;
-use tracing::{debug, debug_span, error};
+use tracing::{debug, debug_span, warn};
 
 use crate::NetResult;
+use crate::cancel::CancelToken;
 use crate::error::{LinkError, NamespaceError, NetError};
-use crate::node::Node;
+use crate::hooks::Hooks;
+use crate::netem::{FlowImpairment, Impairment};
+use crate::node::{Device, Node};
+
+// ==== NodeId / IfaceId ====
+
+/// Name of a node of the topology.
+///
+/// Only built by the parser, once the node is known to exist.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(String);
+
+impl NodeId {
+    pub(crate) fn new(name: &str) -> Self {
+        Self(name.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Name of an interface of a node.
+///
+/// Only built by the parser, once the interface is declared on the node.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IfaceId(String);
+
+impl IfaceId {
+    pub(crate) fn new(name: &str) -> Self {
+        Self(name.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+macro_rules! impl_name {
+    ($id:ty) => {
+        impl fmt::Display for $id {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl Borrow<str> for $id {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $id {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<String> for $id {
+            fn eq(&self, other: &String) -> bool {
+                &self.0 == other
+            }
+        }
+    };
+}
+
+impl_name!(NodeId);
+impl_name!(IfaceId);
+
+// ==== LinkEndpoint ====
+
+/// One end of a link: an interface of a node.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LinkEndpoint {
+    pub node: NodeId,
+    pub iface: IfaceId,
+}
+
+impl LinkEndpoint {
+    pub fn is(&self, node: &str, iface: &str) -> bool {
+        self.node == *node && self.iface == *iface
+    }
+}
+
+impl fmt::Display for LinkEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.node, self.iface)
+    }
+}
+
+// ==== LinkExpectations ====
+
+/// What `netgen verify` expects of a link once the topology is up, declared
+/// under its `expect` key:
+///
+/// ```yaml
+/// expect:
+///   up-within: 10   # seconds both ends are given to come up
+///   carrier: true   # both ends see their peer
+///   lldp-peer: true # each end hears the other one over LLDP
+/// ```
+///
+/// Ends on switches are checked from the router end only.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkExpectations {
+    pub up_within: Option<Duration>,
+    pub carrier: bool,
+    pub lldp_peer: bool,
+}
+
+impl LinkExpectations {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
 
 // ==== Link ====
 
-#[derive(Debug, Clone)]
-pub(crate) struct Link {
-    pub src_device: String,
-    pub src_iface: String,
-    pub dst_device: String,
-    pub dst_iface: String,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub src: LinkEndpoint,
+    pub dst: LinkEndpoint,
+    pub metadata: BTreeMap<String, String>,
+    // Name of the impairment profile of the link, if any.
+    pub profile: Option<String>,
+    // The profile once resolved, or the impairment given inline.
+    pub impairment: Option<Impairment>,
+    // Set on both ends, the kernel default if None.
+    pub mtu: Option<u32>,
+    // Impairments of the traffic matching them only.
+    pub flows: Vec<FlowImpairment>,
+    // Created but held down when false.
+    pub enabled: bool,
+    // Checked by `netgen verify`.
+    pub expect: LinkExpectations,
 }
 
 impl Link {
-    pub(crate) fn src(&self) -> String {
-        format!("{}:{}", self.src_device, self.src_iface)
+    /// Both ends of the link, source first.
+    pub fn ends(&self) -> [&LinkEndpoint; 2] {
+        [&self.src, &self.dst]
     }
 
-    pub(crate) fn dst(&self) -> String {
-        format!("{}:{}", self.dst_device, self.dst_iface)
+    /// The end of the link on `node`, if it is attached to it.
+    pub fn end_on(&self, node: &str) -> Option<&LinkEndpoint> {
+        self.ends().into_iter().find(|end| end.node == *node)
     }
 }
 
 // ==== LinkManager ====
 
+const INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+// Consecutive rounds hitting ENOBUFS before giving up.
+const MAX_THROTTLED_ROUNDS: u32 = 10;
+// Attempts at renaming a veth end and bringing it up.
+const RENAME_ATTEMPTS: u32 = 3;
+// Digits of the temporary veth names, see `veth_names`.
+const VETH_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+/// Most links created at once, as many as the three digits of the temporary
+/// veth names tell apart, the prefix and dash taking up the rest of
+/// IFNAMSIZ.
+pub(crate) const MAX_LINKS: usize = VETH_DIGITS.len().pow(3);
+
 pub(crate) struct LinkManager;
 
 impl LinkManager {
     pub(crate) fn setup_all(
         runtime: &Runtime,
+        veth_prefix: &str,
         nodes: &BTreeMap<String, Node>,
         links: &[Link],
+        parallelism: usize,
+        hooks: &Hooks,
+        cancel: &CancelToken,
     ) -> NetResult<()> {
         // Bring up the Routers' loopback interfaces.
         for node in nodes.values() {
@@ -50,90 +200,242 @@ impl LinkManager {
             }
         }
 
-        for link in links {
-            Self::create_link(runtime, nodes, link)?;
-        }
+        Self::connect(runtime, veth_prefix, nodes, links, parallelism)?;
+        cancel.check()?;
 
         // Add addresses for links in the router nodes.
         for node in nodes.values() {
             if let Node::Router(router) = node {
+                router.add_dummies(runtime)?;
                 router.add_iface_addresses(runtime)?;
+                router.add_static_routes(runtime)?;
+            }
+        }
+        for link in links {
+            hooks.link_up(link);
+        }
+
+        // Certificates are issued before the scripts which may use them.
+        for node in nodes.values() {
+            if let Node::Router(router) = node {
+                router.issue_cert()?;
             }
         }
 
         // Scripts run after addresses in case any of them needs the address or
         // a running & reachable network interface.
+        for node in nodes.values() {
+            cancel.check()?;
+            node.run(runtime)?;
+        }
+
+        cancel.check()?;
+        for node in nodes.values() {
+            if let Node::Router(router) = node {
+                router.run_ssh(runtime)?;
+                router.run_ntp(runtime)?;
+            }
+        }
+
+        cancel.check()?;
         for node in nodes.values() {
             if let Node::Router(router) = node {
-                router.run_scripts(runtime)?;
+                router.run_probes()?;
+                router.run_lldp()?;
+            }
+        }
+
+        for node in nodes.values() {
+            if let Node::Router(router) = node {
+                cancel.check()?;
+                if router.frr_is_lazy() {
+                    debug!(router = %router.name, "FRR left for first use");
+                    continue;
+                }
+                router.run_frr(runtime)?;
+                for daemon in router.frr_daemons() {
+                    hooks.daemon_ready(&router.name, daemon);
+                }
+            }
+        }
+
+        // Route injectors are started last so that the sessions to their
+        // neighbors can come up straight away.
+        cancel.check()?;
+        for node in nodes.values() {
+            if let Node::Router(router) = node {
+                router.run_injector(runtime)?;
             }
         }
         Ok(())
     }
 
-    fn create_link(
+    /// Creates the veth pairs backing `links` and attaches their ends to
+    /// the nodes, which have to be powered on already.
+    pub(crate) fn connect(
         runtime: &Runtime,
+        veth_prefix: &str,
         nodes: &BTreeMap<String, Node>,
-        link: &Link,
+        links: &[Link],
+        parallelism: usize,
     ) -> NetResult<()> {
-        let src_iface = format!("{}:{}", link.src_device, link.src_iface);
-        let dst_iface = format!("{}:{}", link.dst_device, link.dst_iface);
-        let link_span = debug_span!("link-setup", %src_iface, %dst_iface);
-        let _span_guard = link_span.enter();
-        debug!("Setting up");
-
-        // generate random names for veth link
-        // we do this to avoid conflict in the
-        // parent device of interface names.
-        let mut link_name: String;
-        link_name = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(4)
-            .map(char::from)
+        if links.len() > MAX_LINKS {
+            return Err(NetError::BasicError(format!(
+                "Unable to create {} links at once, {MAX_LINKS} at most",
+                links.len()
+            )));
+        }
+        // The veth pairs all live in the main namespace until attached, so
+        // they can be created concurrently.
+        let veths: Vec<(String, String)> = (0..links.len())
+            .map(|index| Self::veth_names(veth_prefix, index))
             .collect();
+        Self::create_veths(runtime, &veths, parallelism)?;
+
+        // Attaching enters the nodes' namespaces, which has to be done one
+        // node at a time. Links failing to attach are rolled back, the others
+        // being attached still so that all failures are reported at once.
+        let mut failures = vec![];
+        for (index, (link, (node1_link, node2_link))) in
+            links.iter().zip(veths).enumerate()
+        {
+            if let Err(err) = Self::attach_veth(
+                runtime, nodes, index, link, node1_link, node2_link,
+            ) {
+                warn!(src = %link.src, dst = %link.dst, error = %err,
+                    "link failed to attach");
+                failures.push(format!("{} <-> {}: {err}", link.src, link.dst));
+            }
+        }
+        if !failures.is_empty() {
+            return Err(NetError::BasicError(format!(
+                "{} of {} links failed to attach:\n  {}",
+                failures.len(),
+                links.len(),
+                failures.join("\n  ")
+            )));
+        }
+        Ok(())
+    }
 
-        let node1_link = format!("eth-{link_name}");
+    // Temporary names of the ends of the index-th veth pair, until they are
+    // renamed in the nodes. They are prefixed with the veth prefix, the
+    // session by default, to avoid conflicts in the main namespace, and
+    // derived from the index rather than random so that two runs log the
+    // same names. Unique for indexes below MAX_LINKS.
+    fn veth_names(prefix: &str, index: usize) -> (String, String) {
+        let mut suffix = String::new();
+        let mut rest = index;
+        for _ in 0..3 {
+            suffix.insert(0, char::from(VETH_DIGITS[rest % VETH_DIGITS.len()]));
+            rest /= VETH_DIGITS.len();
+        }
+        (format!("{prefix}-{suffix}a"), format!("{prefix}-{suffix}b"))
+    }
 
-        link_name = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(4)
-            .map(char::from)
-            .collect();
-        let node2_link = format!("eth-{link_name}");
+    /// Creates the veth pairs with at most `parallelism` requests in flight.
+    ///
+    /// When the kernel answers with ENOBUFS, the throttled requests are
+    /// retried after a backoff and the window is halved. It then grows back
+    /// by one request per successful round.
+    fn create_veths(
+        runtime: &Runtime,
+        veths: &[(String, String)],
+        parallelism: usize,
+    ) -> NetResult<()> {
+        let create_span = debug_span!("veth-create", count = veths.len());
+        let _span_guard = create_span.enter();
 
         runtime.block_on(async {
             let (connection, handle, _) = new_connection()
                 .map_err(|err| LinkError::ConnectionFailed { source: err })?;
             tokio::spawn(connection);
-            let request = handle.link().add(
-                LinkVeth::new(node1_link.as_str(), node2_link.as_str()).build(),
-            );
-
-            //request.message_mut().header.flags.push(LinkFlag::Up);
-            request.execute().await.map_err(|err| {
-                LinkError::ExecuteFailed {
-                    operation: "create_link".to_string(),
-                    source: err,
+
+            let parallelism = parallelism.max(1);
+            let mut window = parallelism;
+            let mut backoff = INITIAL_BACKOFF;
+            let mut throttled_rounds = 0;
+            let mut pending: Vec<(usize, &(String, String))> =
+                veths.iter().enumerate().collect();
+
+            while !pending.is_empty() {
+                let batch: Vec<(usize, &(String, String))> =
+                    pending.drain(..window.min(pending.len())).collect();
+                let results =
+                    join_all(batch.iter().map(|(_, (node1, node2))| {
+                        handle
+                            .link()
+                            .add(LinkVeth::new(node1, node2).build())
+                            .execute()
+                    }))
+                    .await;
+
+                let mut throttled = vec![];
+                for ((index, veth), result) in batch.into_iter().zip(results) {
+                    match result {
+                        Ok(()) => {
+                            debug!(index, veth = %veth.0, peer = %veth.1,
+                                "created")
+                        }
+                        Err(err) if is_throttled(&err) => {
+                            throttled.push((index, veth))
+                        }
+                        Err(err) => {
+                            return Err(LinkError::ExecuteFailed {
+                                operation: "create_link".to_string(),
+                                source: err,
+                            }
+                            .into());
+                        }
+                    }
                 }
-            })?;
 
-            Ok::<(), NetError>(())
-        })?;
-        if let Some(src_node) = nodes.get(&link.src_device)
-            && let Some(dst_node) = nodes.get(&link.dst_device)
-        {
-            // attaches the links to their respective nodes
-            Self::attach_link(
-                runtime,
-                src_node,
-                node1_link,
-                link.src_iface.clone(),
-            )?;
+                if throttled.is_empty() {
+                    window = (window + 1).min(parallelism);
+                    backoff = INITIAL_BACKOFF;
+                    throttled_rounds = 0;
+                    continue;
+                }
+
+                throttled_rounds += 1;
+                if throttled_rounds > MAX_THROTTLED_ROUNDS {
+                    return Err(NetError::BasicError(format!(
+                        "netlink kept running out of buffer space, \
+                            {} links left to create",
+                        throttled.len() + pending.len()
+                    )));
+                }
+                window = (window / 2).max(1);
+                warn!(window, ?backoff, "netlink throttled, backing off");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                pending.splice(0..0, throttled);
+            }
+            Ok(())
+        })
+    }
+
+    fn attach_veth(
+        runtime: &Runtime,
+        nodes: &BTreeMap<String, Node>,
+        index: usize,
+        link: &Link,
+        node1_link: String,
+        node2_link: String,
+    ) -> NetResult<()> {
+        let link_span = debug_span!("link-setup", index,
+            src_iface = %link.src, dst_iface = %link.dst);
+        let _span_guard = link_span.enter();
+        debug!("Setting up");
+
+        // Endpoints are resolved when parsing, the nodes exist.
+        for (end, veth) in [(&link.src, node1_link), (&link.dst, node2_link)] {
             Self::attach_link(
                 runtime,
-                dst_node,
-                node2_link,
-                link.dst_iface.clone(),
+                &nodes[end.node.as_str()],
+                veth,
+                end.iface.to_string(),
+                link.mtu,
             )?;
         }
         debug!("Setup complete");
@@ -141,25 +443,43 @@ impl LinkManager {
         Ok(())
     }
 
+    // Moves the veth end `current_link_name` to `node`, then renames it to
+    // `new_link_name` and brings it up. Once renamed, the end is looked up
+    // again to make sure it is there under its name and up, the rename being
+    // retried otherwise. On failure, the veth pair is deleted rather than
+    // left half-configured.
     fn attach_link(
         runtime: &Runtime,
         node: &Node,
         current_link_name: String,
         new_link_name: String,
+        mtu: Option<u32>,
     ) -> NetResult<()> {
+        let index =
+            if_nametoindex(current_link_name.as_str()).map_err(|err| {
+                LinkError::NoInterface {
+                    iface: current_link_name.clone(),
+                    source: err,
+                }
+            })?;
+
         runtime.block_on(async {
             let (connection, handle, _) = new_connection()
                 .map_err(|err| LinkError::ConnectionFailed { source: err })?;
             tokio::spawn(connection);
             match node {
                 Node::Router(router) => {
-                    if let Ok(index) =
-                        if_nametoindex(current_link_name.as_str())
-                        && let Some(net_path) = &router.net_path
-                    {
+                    let result = async {
+                        let Some(namespace) = router.namespace.get() else {
+                            return Err(NetError::BasicError(format!(
+                                "{} is not powered on",
+                                router.name
+                            )));
+                        };
+                        let net_path = namespace.net_path();
                         let file = File::open(net_path).map_err(|err| {
                             NamespaceError::FileOpen {
-                                path: net_path.clone(),
+                                path: net_path.to_string(),
                                 source: err,
                             }
                         })?;
@@ -175,74 +495,173 @@ impl LinkManager {
                                 source: err,
                             },
                         )?;
-
-                        // Rename the interface to it's proper name.
-                        router
-                            .in_ns(false, move || async move {
-                                let (conn, handle, _) = new_connection()
-                                    .map_err(|err| {
-                                        LinkError::ConnectionFailed {
-                                            source: err,
-                                        }
-                                    })?;
-                                tokio::spawn(conn);
-
-                                // Rename the link from the name given to it
-                                // at create_link and bring the link up.
-                                let message = LinkUnspec::new_with_index(index)
-                                    .name(new_link_name)
-                                    .up()
-                                    .build();
-
-                                handle
-                                    .link()
-                                    .set(message)
-                                    .execute()
-                                    .await
-                                    .map_err(|err| {
-                                        LinkError::ExecuteFailed {
-                                        operation:
-                                            "attach-link->bring-interface-up"
-                                                .to_string(),
-                                        source: err,
-                                    }
-                                    })?;
-                                Ok::<(), NetError>(())
-                            })
-                            .await??;
-                        // Above: one '?' for the inner method, one for the
-                        // 'in_ns' method.
+                        Ok::<(), NetError>(())
+                    }
+                    .await;
+                    if let Err(err) = result {
+                        Self::delete_veth(&handle, index).await;
+                        return Err(err);
                     }
+
+                    // Rename the interface to its proper name, deleting it
+                    // from the router's namespace if that fails.
+                    router
+                        .in_ns(false, move || async move {
+                            let (conn, handle, _) =
+                                new_connection().map_err(|err| {
+                                    LinkError::ConnectionFailed { source: err }
+                                })?;
+                            tokio::spawn(conn);
+
+                            let result = Self::rename_link(
+                                &handle,
+                                index,
+                                &new_link_name,
+                                mtu,
+                            )
+                            .await;
+                            if result.is_err() {
+                                Self::delete_veth(&handle, index).await;
+                            }
+                            result
+                        })
+                        .await??;
+                    // Above: one '?' for the inner method, one for the
+                    // 'in_ns' method.
                 }
                 Node::Switch(switch) => {
-                    if let Ok(index) =
-                        if_nametoindex(current_link_name.as_str())
-                        && let Some(ifindex) = switch.ifindex
-                    {
-                        // Rename the link from the name given to it
-                        // at create_link and bring it up.
-                        let message = LinkUnspec::new_with_index(index)
-                            .name(new_link_name)
-                            .up()
-                            .build();
-                        if let Err(err) =
-                            handle.link().set(message).execute().await
-                        {
-                            error!(error = %err, "error changing name");
-                        }
+                    let result = async {
+                        let Some(&ifindex) = switch.ifindex.get() else {
+                            return Err(NetError::BasicError(format!(
+                                "Bridge of {} not found",
+                                switch.name
+                            )));
+                        };
+                        Self::rename_link(&handle, index, &new_link_name, mtu)
+                            .await?;
 
                         let message = LinkUnspec::new_with_index(index)
                             .controller(ifindex)
                             .build();
-                        if let Err(err) =
-                            handle.link().set(message).execute().await
-                        {
-                            error!(error = %err, "error changing controller");
-                        }
+                        handle.link().set(message).execute().await.map_err(
+                            |err| LinkError::ExecuteFailed {
+                                operation: "attach-link->set-controller"
+                                    .to_string(),
+                                source: err,
+                            },
+                        )?;
+                        Ok::<(), NetError>(())
+                    }
+                    .await;
+                    if result.is_err() {
+                        Self::delete_veth(&handle, index).await;
                     }
+                    result?;
                 }
             }
             Ok(())
         })
     }
+
+    // Renames the interface `index` of the namespace `handle` talks to and
+    // brings it up, until it shows under `name` and up or the attempts run
+    // out.
+    async fn rename_link(
+        handle: &Handle,
+        index: u32,
+        name: &str,
+        mtu: Option<u32>,
+    ) -> NetResult<()> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            // Rename the link from the name given to it at create_link and
+            // bring the link up.
+            let mut message = LinkUnspec::new_with_index(index)
+                .name(name.to_string())
+                .up();
+            if let Some(mtu) = mtu {
+                message = message.mtu(mtu);
+            }
+            let result =
+                match handle.link().set(message.build()).execute().await {
+                    Ok(()) => Self::check_link(handle, index, name).await,
+                    Err(err) => Err(LinkError::ExecuteFailed {
+                        operation: "attach-link->bring-interface-up"
+                            .to_string(),
+                        source: err,
+                    }
+                    .into()),
+                };
+            match result {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt >= RENAME_ATTEMPTS => return Err(err),
+                Err(err) => {
+                    warn!(%index, %name, attempt, error = %err,
+                        "rename failed, retrying");
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            attempt += 1;
+        }
+    }
+
+    // Whether the interface `index` is named `name` and administratively
+    // up, as the kernel reports it.
+    async fn check_link(
+        handle: &Handle,
+        index: u32,
+        name: &str,
+    ) -> NetResult<()> {
+        let messages: Vec<_> = handle
+            .link()
+            .get()
+            .match_index(index)
+            .execute()
+            .try_collect()
+            .await
+            .map_err(|err| LinkError::ExecuteFailed {
+                operation: "attach-link->check-interface".to_string(),
+                source: err,
+            })?;
+        let Some(message) = messages.first() else {
+            return Err(NetError::BasicError(format!(
+                "Interface {index} vanished"
+            )));
+        };
+        let actual = message.attributes.iter().find_map(|attr| match attr {
+            LinkAttribute::IfName(name) => Some(name.as_str()),
+            _ => None,
+        });
+        if actual != Some(name) {
+            return Err(NetError::BasicError(format!(
+                "Interface {index} is named {}, not {name}",
+                actual.unwrap_or("?")
+            )));
+        }
+        if !message.header.flags.contains(LinkFlags::Up) {
+            return Err(NetError::BasicError(format!("{name} is not up")));
+        }
+        Ok(())
+    }
+
+    // Deletes the veth pair the interface `index` is an end of, wherever
+    // its peer is.
+    async fn delete_veth(handle: &Handle, index: u32) {
+        match handle.link().del(index).execute().await {
+            Ok(()) => debug!(%index, "veth rolled back"),
+            Err(err) => warn!(%index, error = %err, "unable to roll back veth"),
+        }
+    }
+}
+
+// Whether the kernel dropped the request for lack of socket buffer space.
+fn is_throttled(err: &rtnetlink::Error) -> bool {
+    match err {
+        rtnetlink::Error::NetlinkError(message) => {
+            message.raw_code().abs() == libc::ENOBUFS
+        }
+        _ => false,
+    }
 }
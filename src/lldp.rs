@@ -0,0 +1,335 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use nix::ifaddrs::getifaddrs;
+use tracing::debug;
+
+use crate::packet::{MacAddr, Packet, PacketBuilder};
+use crate::raw::RawSocket;
+use crate::table::{self, Align};
+use crate::{NetResult, devices_ns_dir, events, with_run_dir};
+
+pub(crate) const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+const LLDP_ETHERTYPE: u16 = 0x88cc;
+// Nearest bridge group address, which bridges do not forward.
+const LLDP_MULTICAST: MacAddr = MacAddr(0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e);
+// Neighbors are forgotten after this many intervals without hearing from
+// them, as with lldpd.
+const HOLD_MULTIPLIER: u32 = 4;
+
+// TLV types, see IEEE 802.1AB.
+const TLV_END: u8 = 0;
+const TLV_CHASSIS_ID: u8 = 1;
+const TLV_PORT_ID: u8 = 2;
+const TLV_TTL: u8 = 3;
+const TLV_SYSTEM_NAME: u8 = 5;
+// Subtypes of the chassis and port IDs.
+const CHASSIS_MAC: u8 = 4;
+const CHASSIS_LOCAL: u8 = 7;
+const PORT_MAC: u8 = 3;
+const PORT_IFNAME: u8 = 5;
+
+// ==== struct LldpAgent ====
+
+/// Built-in LLDP agent, advertising the router on each of its interfaces
+/// and listening for its neighbors, without lldpd.
+///
+/// Configured either for every router at the top level of the topology, or
+/// per router, the latter taking precedence:
+///
+/// ```yaml
+/// lldp:
+///   interval: 10
+///
+/// routers:
+///   rt1:
+///     lldp: false
+/// ```
+///
+/// `interval` is in seconds, 30 by default.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct LldpAgent {
+    pub(crate) enabled: bool,
+    pub(crate) interval: Duration,
+}
+
+impl Default for LldpAgent {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+}
+
+impl LldpAgent {
+    pub(crate) fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Runs the agent on the interfaces of `node` until killed, from within
+    /// the router's network namespace, one thread per interface.
+    ///
+    /// What each interface last heard is kept in a file under the router's
+    /// directory for `netgen lldp` to read.
+    pub(crate) fn run(&self, node: &str, ifaces: &[String]) {
+        let _ = fs::remove_dir_all(neighbors_dir(node));
+        let _ = fs::create_dir_all(neighbors_dir(node));
+        std::thread::scope(|scope| {
+            for iface in ifaces {
+                scope.spawn(with_run_dir(move || {
+                    if let Err(err) = self.run_on(node, iface) {
+                        debug!(%node, %iface, %err, "LLDP agent stopped");
+                    }
+                }));
+            }
+        });
+    }
+
+    fn run_on(&self, node: &str, iface: &str) -> NetResult<()> {
+        let socket = RawSocket::open(node, iface)?;
+        // Interfaces without a MAC address, e.g. tunnels, have no
+        // neighbors to speak of.
+        let Some(mac) = iface_mac(iface) else {
+            return Ok(());
+        };
+        let ttl = u16::try_from((self.interval * HOLD_MULTIPLIER).as_secs())
+            .unwrap_or(u16::MAX);
+        let frame = PacketBuilder::ethernet(mac, LLDP_MULTICAST)
+            .ethertype(LLDP_ETHERTYPE)
+            .payload(&lldpdu(node, iface, ttl))
+            .build();
+
+        // Who the interface is connected to, as last heard.
+        let mut peer = None;
+        loop {
+            socket.send(&frame)?;
+            let deadline = Instant::now() + self.interval;
+            loop {
+                let remaining =
+                    deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                let Some(frame) = socket.recv(remaining)? else {
+                    break;
+                };
+                // Our own frames are seen as well.
+                let Some(packet) = Packet::parse(&frame) else {
+                    continue;
+                };
+                if packet.ethertype != LLDP_ETHERTYPE || packet.src_mac == mac {
+                    continue;
+                }
+                let Some(neighbor) = LldpNeighbor::parse(iface, &packet) else {
+                    continue;
+                };
+                let heard =
+                    format!("{}:{}", neighbor.chassis_id, neighbor.port_id);
+                if peer.as_ref() != Some(&heard) {
+                    events::record(
+                        "lldp",
+                        &format!("{node}:{iface}"),
+                        &format!("neighbor {heard}"),
+                    );
+                    peer = Some(heard);
+                }
+                neighbor.save(node);
+            }
+        }
+    }
+}
+
+// ==== struct LldpNeighbor ====
+
+/// What an interface last heard from its neighbor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LldpNeighbor {
+    pub iface: String,
+    pub chassis_id: String,
+    pub port_id: String,
+    pub system_name: Option<String>,
+    // How long the neighbor is to be remembered for, in seconds.
+    pub ttl: u16,
+    // Unix time in milliseconds when it was last heard.
+    pub last_seen: u128,
+}
+
+impl LldpNeighbor {
+    /// Whether the neighbor has not been heard from for longer than it
+    /// asked to be remembered.
+    pub fn expired(&self) -> bool {
+        let age = events::unix_millis().saturating_sub(self.last_seen);
+        age > u128::from(self.ttl) * 1000
+    }
+
+    // Decodes the mandatory TLVs of an LLDPDU, and the system name.
+    fn parse(iface: &str, packet: &Packet) -> Option<Self> {
+        let mut neighbor = Self {
+            iface: iface.to_string(),
+            chassis_id: String::new(),
+            port_id: String::new(),
+            system_name: None,
+            ttl: 0,
+            last_seen: events::unix_millis(),
+        };
+        let mut tlvs = packet.payload.as_slice();
+        let mut ttl = None;
+        while tlvs.len() >= 2 {
+            let header = u16::from_be_bytes([tlvs[0], tlvs[1]]);
+            let (kind, len) = ((header >> 9) as u8, (header & 0x1ff) as usize);
+            let value = tlvs.get(2..2 + len)?;
+            tlvs = &tlvs[2 + len..];
+            match kind {
+                TLV_END => break,
+                TLV_CHASSIS_ID => {
+                    neighbor.chassis_id = id_value(value, CHASSIS_MAC)?;
+                }
+                TLV_PORT_ID => neighbor.port_id = id_value(value, PORT_MAC)?,
+                TLV_TTL => {
+                    ttl = Some(u16::from_be_bytes(value.try_into().ok()?));
+                }
+                TLV_SYSTEM_NAME => {
+                    neighbor.system_name =
+                        Some(String::from_utf8_lossy(value).to_string());
+                }
+                _ => {}
+            }
+        }
+        neighbor.ttl = ttl?;
+        Some(neighbor)
+    }
+
+    fn save(&self, node: &str) {
+        let mut contents = format!(
+            "chassis-id {}\nport-id {}\nttl {}\nlast-seen {}\n",
+            self.chassis_id, self.port_id, self.ttl, self.last_seen
+        );
+        if let Some(system_name) = &self.system_name {
+            let _ = writeln!(contents, "system-name {system_name}");
+        }
+        let _ = fs::write(
+            format!("{}/{}", neighbors_dir(node), self.iface),
+            contents,
+        );
+    }
+
+    fn load(iface: &str, contents: &str) -> Option<Self> {
+        let field = |name: &str| {
+            contents.lines().find_map(|line| {
+                line.strip_prefix(name)?
+                    .strip_prefix(' ')
+                    .map(str::to_string)
+            })
+        };
+        Some(Self {
+            iface: iface.to_string(),
+            chassis_id: field("chassis-id")?,
+            port_id: field("port-id")?,
+            system_name: field("system-name"),
+            ttl: field("ttl")?.parse().ok()?,
+            last_seen: field("last-seen")?.parse().ok()?,
+        })
+    }
+}
+
+/// Neighbors last heard by the interfaces of `node`, by interface.
+pub fn neighbors(node: &str) -> Vec<LldpNeighbor> {
+    let Ok(entries) = fs::read_dir(neighbors_dir(node)) else {
+        return vec![];
+    };
+    let mut neighbors: Vec<LldpNeighbor> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let iface = entry.file_name().to_string_lossy().to_string();
+            let contents = fs::read_to_string(entry.path()).ok()?;
+            LldpNeighbor::load(&iface, &contents)
+        })
+        .collect();
+    neighbors.sort_by(|a, b| a.iface.cmp(&b.iface));
+    neighbors
+}
+
+/// Renders the neighbors as an aligned table, those which expired being
+/// marked as such.
+pub fn render(neighbors: &[LldpNeighbor]) -> String {
+    let headers = ["IFACE", "NEIGHBOR", "PORT", "LAST SEEN"];
+    let now = events::unix_millis();
+    let rows: Vec<[String; 4]> = neighbors
+        .iter()
+        .map(|neighbor| {
+            let age = now.saturating_sub(neighbor.last_seen) / 1000;
+            [
+                neighbor.iface.clone(),
+                neighbor
+                    .system_name
+                    .clone()
+                    .unwrap_or_else(|| neighbor.chassis_id.clone()),
+                neighbor.port_id.clone(),
+                match neighbor.expired() {
+                    true => format!("{age}s ago, expired"),
+                    false => format!("{age}s ago"),
+                },
+            ]
+        })
+        .collect();
+    table::render(headers, [Align::Left; 4], &rows)
+}
+
+fn neighbors_dir(node: &str) -> String {
+    format!("{}/{node}/lldp", devices_ns_dir())
+}
+
+// The LLDPDU advertising `iface` of `node`: the chassis is identified by the
+// node's name, and the port by the interface's.
+fn lldpdu(node: &str, iface: &str, ttl: u16) -> Vec<u8> {
+    let mut pdu = vec![];
+    let mut tlv = |kind: u8, value: &[u8]| {
+        // Values are at most 511 bytes long, interface and node names are
+        // far shorter.
+        let len = value.len().min(0x1ff);
+        let header = (u16::from(kind) << 9) | len as u16;
+        pdu.extend(header.to_be_bytes());
+        pdu.extend(&value[..len]);
+    };
+    tlv(
+        TLV_CHASSIS_ID,
+        &[&[CHASSIS_LOCAL][..], node.as_bytes()].concat(),
+    );
+    tlv(
+        TLV_PORT_ID,
+        &[&[PORT_IFNAME][..], iface.as_bytes()].concat(),
+    );
+    tlv(TLV_TTL, &ttl.to_be_bytes());
+    tlv(TLV_SYSTEM_NAME, node.as_bytes());
+    tlv(TLV_END, &[]);
+    pdu
+}
+
+// A chassis or port ID, formatted as a MAC address when advertised as one
+// (`mac_subtype`), as text otherwise.
+fn id_value(value: &[u8], mac_subtype: u8) -> Option<String> {
+    let (subtype, id) = value.split_first()?;
+    match (*subtype, id) {
+        (subtype, &[a, b, c, d, e, f]) if subtype == mac_subtype => {
+            Some(MacAddr::new(a, b, c, d, e, f).to_string())
+        }
+        _ => Some(String::from_utf8_lossy(id).to_string()),
+    }
+}
+
+// MAC address of an interface of the namespace we are in.
+fn iface_mac(iface: &str) -> Option<MacAddr> {
+    getifaddrs().ok()?.find_map(|ifaddr| {
+        if ifaddr.interface_name != iface {
+            return None;
+        }
+        let [a, b, c, d, e, f] = ifaddr.address?.as_link_addr()?.addr()?;
+        Some(MacAddr::new(a, b, c, d, e, f))
+    })
+}
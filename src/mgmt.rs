@@ -0,0 +1,90 @@
+use std::fmt::Write as _;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+// Table a management outage of a router is loaded into, deleted once over.
+const TABLE: &str = "netgen-mgmt";
+
+// ==== struct MgmtOutage ====
+
+/// Scenario step cutting routers off from their controllers for a while,
+/// their data plane being left alone:
+///
+/// ```yaml
+/// scenario:
+///   - mgmt-outage:
+///       nodes: [rt1, rt2]
+///       duration: 30
+///       ifaces: [mgmt0]     # management interfaces, if any
+/// ```
+///
+/// The routers are reachable again once the step is over, or when the
+/// scenario is stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MgmtOutage {
+    pub nodes: Vec<String>,
+    pub ifaces: Vec<String>,
+    pub duration: Duration,
+}
+
+impl MgmtOutage {
+    pub fn describe(&self) -> String {
+        format!(
+            "management outage of {} for {:?}",
+            self.nodes.join(", "),
+            self.duration
+        )
+    }
+}
+
+fn family(address: IpAddr) -> &'static str {
+    match address {
+        IpAddr::V4(_) => "ip",
+        IpAddr::V6(_) => "ip6",
+    }
+}
+
+/// The nftables ruleset cutting a router off from its controllers, for
+/// `nft -f`: the connections the host makes to `targets`, through the ports
+/// the router exposes, and whatever goes through its management `ifaces`.
+pub(crate) fn ruleset(targets: &[SocketAddr], ifaces: &[String]) -> String {
+    let mut output = String::new();
+    let mut input = String::new();
+    let mut forward = String::new();
+    // The forwarder of the exposed ports connects to them from within the
+    // router, through its loopback.
+    for target in targets {
+        let _ = writeln!(
+            output,
+            "        oifname \"lo\" {} daddr {} tcp dport {} drop",
+            family(target.ip()),
+            target.ip(),
+            target.port()
+        );
+    }
+    for iface in ifaces {
+        let _ = writeln!(output, "        oifname \"{iface}\" drop");
+        let _ = writeln!(input, "        iifname \"{iface}\" drop");
+        let _ = writeln!(forward, "        iifname \"{iface}\" drop");
+        let _ = writeln!(forward, "        oifname \"{iface}\" drop");
+    }
+    let chain = |name: &str, rules: &str| {
+        format!(
+            "    chain {name} {{\n        type filter hook {name} priority \
+                filter; policy accept;\n{rules}    }}\n"
+        )
+    };
+    format!(
+        "table inet {TABLE}\ndelete table inet {TABLE}\n\
+            table inet {TABLE} {{\n{}{}{}}}\n",
+        chain("output", &output),
+        chain("input", &input),
+        chain("forward", &forward),
+    )
+}
+
+/// The nftables ruleset making a router reachable again, whether or not it
+/// was cut off.
+pub(crate) fn restore() -> String {
+    format!("table inet {TABLE}\ndelete table inet {TABLE}\n")
+}
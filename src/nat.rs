@@ -0,0 +1,133 @@
+use std::fmt::Write as _;
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+
+// Table the rules of a router are loaded into, replaced as a whole.
+const TABLE: &str = "netgen-nat";
+
+// ==== enum NatKind ====
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NatKind {
+    Snat,
+    Dnat,
+    Masquerade,
+}
+
+impl NatKind {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "snat" => Some(NatKind::Snat),
+            "dnat" => Some(NatKind::Dnat),
+            "masquerade" => Some(NatKind::Masquerade),
+            _ => None,
+        }
+    }
+}
+
+// ==== struct NatRule ====
+
+// A NAT rule of a router, applied to the connections whose first packet
+// matches it, the rest following through conntrack.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NatRule {
+    pub(crate) kind: NatKind,
+    pub(crate) source: Option<IpNetwork>,
+    pub(crate) destination: Option<IpNetwork>,
+    // "tcp" or "udp", required along with the ports.
+    pub(crate) protocol: Option<String>,
+    pub(crate) port: Option<u16>,
+    // DNAT only, the interface packets come in through.
+    pub(crate) in_iface: Option<String>,
+    // SNAT and masquerade only, the interface packets go out through.
+    pub(crate) out_iface: Option<String>,
+    // Translated address, none for masquerade.
+    pub(crate) to: Option<IpAddr>,
+    pub(crate) to_port: Option<u16>,
+}
+
+impl NatRule {
+    // The rule as an nft statement.
+    fn nft(&self) -> String {
+        let mut rule = String::new();
+        if let Some(iface) = &self.in_iface {
+            let _ = write!(rule, "iifname \"{iface}\" ");
+        }
+        if let Some(iface) = &self.out_iface {
+            let _ = write!(rule, "oifname \"{iface}\" ");
+        }
+        for (field, network) in
+            [("saddr", self.source), ("daddr", self.destination)]
+        {
+            if let Some(network) = network {
+                let _ =
+                    write!(rule, "{} {field} {network} ", family(network.ip()));
+            }
+        }
+        if let Some(protocol) = &self.protocol {
+            match self.port {
+                Some(port) => {
+                    let _ = write!(rule, "{protocol} dport {port} ");
+                }
+                None => {
+                    let _ = write!(rule, "meta l4proto {protocol} ");
+                }
+            }
+        }
+
+        let to = |statement: &str, to: IpAddr| {
+            let address = match (to, self.to_port) {
+                (IpAddr::V6(to), Some(port)) => format!("[{to}]:{port}"),
+                (to, Some(port)) => format!("{to}:{port}"),
+                (to, None) => to.to_string(),
+            };
+            format!("{statement} {} to {address}", family(to))
+        };
+        match (self.kind, self.to) {
+            (NatKind::Snat, Some(address)) => rule += &to("snat", address),
+            (NatKind::Dnat, Some(address)) => rule += &to("dnat", address),
+            _ => match self.to_port {
+                Some(port) => {
+                    let _ = write!(rule, "masquerade to :{port}");
+                }
+                None => rule += "masquerade",
+            },
+        }
+        rule
+    }
+}
+
+fn family(address: IpAddr) -> &'static str {
+    match address {
+        IpAddr::V4(_) => "ip",
+        IpAddr::V6(_) => "ip6",
+    }
+}
+
+/// The nftables ruleset of a router's NAT rules, for `nft -f`. Loading it
+/// again replaces the rules loaded before.
+pub(crate) fn ruleset(rules: &[NatRule]) -> String {
+    let chain = |name: &str, hook: &str, priority: &str, kinds: &[NatKind]| {
+        let mut chain = format!(
+            "    chain {name} {{\n        type nat hook {hook} priority \
+                {priority}; policy accept;\n"
+        );
+        for rule in rules.iter().filter(|rule| kinds.contains(&rule.kind)) {
+            let _ = writeln!(chain, "        {}", rule.nft());
+        }
+        chain.push_str("    }\n");
+        chain
+    };
+    format!(
+        "table inet {TABLE}\ndelete table inet {TABLE}\n\
+            table inet {TABLE} {{\n{}{}}}\n",
+        chain("prerouting", "prerouting", "dstnat", &[NatKind::Dnat]),
+        chain(
+            "postrouting",
+            "postrouting",
+            "srcnat",
+            &[NatKind::Snat, NatKind::Masquerade]
+        ),
+    )
+}
@@ -0,0 +1,334 @@
+use std::time::Duration;
+
+use ipnetwork::IpNetwork;
+
+// Bands of a prio qdisc, the first one being left for the traffic no flow
+// matches.
+pub(crate) const MAX_FLOWS: usize = 15;
+
+// ==== struct Impairment ====
+
+/// Delay, loss and rate limit applied with netem to both ends of a link.
+///
+/// Links refer to one by name, either one of the presets netgen ships or
+/// one declared under `profiles`, which take precedence:
+///
+/// ```yaml
+/// profiles:
+///   flaky-wifi:
+///     delay: 5
+///     jitter: 10
+///     loss: 2.5
+///     rate: 30000
+///
+/// links:
+///   - src-device: rt1
+///     src-iface: eth0
+///     dst-device: rt2
+///     dst-iface: eth0
+///     profile: satellite
+/// ```
+///
+/// `delay` and `jitter` are in milliseconds, `loss` in percent and `rate`
+/// in kbit/s. Every end applies them to what it sends, so the round trip
+/// of a link takes twice its delay.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Impairment {
+    pub delay: Duration,
+    pub jitter: Duration,
+    pub loss: f64,
+    // Unlimited if None.
+    pub rate: Option<u32>,
+}
+
+impl Impairment {
+    /// The preset named `name`, among "lte", "satellite", "transatlantic"
+    /// and "dsl".
+    pub fn preset(name: &str) -> Option<Impairment> {
+        let (delay, jitter, loss, rate) = match name {
+            "lte" => (50, 15, 0.5, Some(20_000)),
+            "satellite" => (300, 20, 1.0, Some(10_000)),
+            "transatlantic" => (40, 2, 0.1, None),
+            "dsl" => (15, 5, 0.2, Some(8_000)),
+            _ => return None,
+        };
+        Some(Impairment {
+            delay: Duration::from_millis(delay),
+            jitter: Duration::from_millis(jitter),
+            loss,
+            rate,
+        })
+    }
+
+    /// The impairment of all the traffic of an interface, from what
+    /// `tc qdisc show` lists: the netem qdisc at its root or, when flows
+    /// have their own, in the band of the traffic no flow matches.
+    pub(crate) fn from_qdiscs(qdiscs: &str) -> Option<Impairment> {
+        // qdisc netem 8001: root refcnt 2 limit 1000 delay 50ms  15ms
+        //     loss 0.5% rate 20Mbit
+        let line = qdiscs.lines().find(|line| {
+            line.starts_with("qdisc netem ")
+                && (line.contains(" root ") || line.contains(" parent 1:1 "))
+        })?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let after = |keyword: &str| -> &[&str] {
+            match words.iter().position(|word| *word == keyword) {
+                Some(index) => &words[index + 1..],
+                None => &[],
+            }
+        };
+        let delay = after("delay");
+        let time = |word: Option<&&str>| {
+            word.and_then(|word| parse_time(word)).unwrap_or_default()
+        };
+        Some(Impairment {
+            delay: time(delay.first()),
+            jitter: time(delay.get(1)),
+            loss: after("loss")
+                .iter()
+                .find_map(|word| word.strip_suffix('%'))
+                .and_then(|loss| loss.parse().ok())
+                .unwrap_or(0.0),
+            rate: after("rate").first().and_then(|rate| parse_rate(rate)),
+        })
+    }
+
+    // Arguments to `tc` describing the netem qdisc.
+    fn netem_args(&self) -> Vec<String> {
+        let mut args = vec!["netem".to_string(), "delay".to_string()];
+        args.push(format!("{}ms", self.delay.as_millis()));
+        if !self.jitter.is_zero() {
+            args.push(format!("{}ms", self.jitter.as_millis()));
+        }
+        if self.loss > 0.0 {
+            args.push("loss".to_string());
+            args.push(format!("{}%", self.loss));
+        }
+        if let Some(rate) = self.rate {
+            args.push("rate".to_string());
+            args.push(format!("{rate}kbit"));
+        }
+        args
+    }
+}
+
+// A time as tc prints it, e.g. "50ms" or "1.5s".
+fn parse_time(word: &str) -> Option<Duration> {
+    let (value, unit) = ["us", "ms", "s"]
+        .into_iter()
+        .find_map(|unit| Some((word.strip_suffix(unit)?, unit)))?;
+    let value: f64 = value.parse().ok()?;
+    let seconds = match unit {
+        "us" => value / 1_000_000.0,
+        "ms" => value / 1000.0,
+        _ => value,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+// A rate as tc prints it, e.g. "20Mbit", in kbit/s.
+fn parse_rate(word: &str) -> Option<u32> {
+    let units = [
+        ("Gbit", 1_000_000.0),
+        ("Mbit", 1000.0),
+        ("Kbit", 1.0),
+        ("kbit", 1.0),
+        ("bit", 0.001),
+    ];
+    let (value, kbits) = units
+        .into_iter()
+        .find_map(|(unit, kbits)| Some((word.strip_suffix(unit)?, kbits)))?;
+    let value: f64 = value.parse().ok()?;
+    Some((value * kbits).round() as u32)
+}
+
+// ==== struct FlowImpairment ====
+
+/// An impairment profile applied to the traffic of a link matching
+/// `matching` only, e.g. to delay BGP but not the data plane:
+///
+/// ```yaml
+/// links:
+///   - src-device: rt1
+///     src-iface: eth0
+///     dst-device: rt2
+///     dst-iface: eth0
+///     flows:
+///       - match: { protocol: tcp, port: 179 }
+///         profile: satellite
+///       - match: { dscp: 48 }
+///         profile: lte
+/// ```
+///
+/// The first flow matching a packet applies, traffic matching none of them
+/// getting the profile of the whole link, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlowImpairment {
+    pub matching: FlowMatch,
+    pub profile: String,
+    // The profile, once resolved.
+    pub impairment: Option<Impairment>,
+}
+
+/// Fields of the packets a `FlowImpairment` applies to, all of those set
+/// having to match.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FlowMatch {
+    pub src: Option<IpNetwork>,
+    pub dst: Option<IpNetwork>,
+    // "tcp", "udp", "sctp", "icmp" or "icmpv6".
+    pub protocol: Option<String>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+    // Either the source or the destination port.
+    pub port: Option<u16>,
+    pub dscp: Option<u8>,
+}
+
+impl FlowMatch {
+    // Ethernet types of the packets which can match.
+    fn families(&self) -> Vec<&'static str> {
+        let address = self.src.or(self.dst);
+        match (address, self.protocol.as_deref()) {
+            (Some(IpNetwork::V4(_)), _) | (None, Some("icmp")) => vec!["ip"],
+            (Some(IpNetwork::V6(_)), _) | (None, Some("icmpv6")) => {
+                vec!["ipv6"]
+            }
+            (None, _) => vec!["ip", "ipv6"],
+        }
+    }
+
+    // Arguments to the flower classifier, one set per filter needed.
+    fn flower_args(&self) -> Vec<Vec<String>> {
+        let mut args = vec![];
+        if let Some(protocol) = &self.protocol {
+            args.extend(["ip_proto".to_string(), protocol.clone()]);
+        }
+        if let Some(src) = self.src {
+            args.extend(["src_ip".to_string(), src.to_string()]);
+        }
+        if let Some(dst) = self.dst {
+            args.extend(["dst_ip".to_string(), dst.to_string()]);
+        }
+        if let Some(port) = self.src_port {
+            args.extend(["src_port".to_string(), port.to_string()]);
+        }
+        if let Some(port) = self.dst_port {
+            args.extend(["dst_port".to_string(), port.to_string()]);
+        }
+        if let Some(dscp) = self.dscp {
+            // DSCP is the upper 6 bits of the TOS or traffic class.
+            args.extend([
+                "ip_tos".to_string(),
+                format!("{:#x}/0xfc", dscp << 2),
+            ]);
+        }
+
+        match self.port {
+            Some(port) => ["src_port", "dst_port"]
+                .into_iter()
+                .map(|field| {
+                    let mut args = args.clone();
+                    args.extend([field.to_string(), port.to_string()]);
+                    args
+                })
+                .collect(),
+            None => vec![args],
+        }
+    }
+}
+
+/// `tc` arguments changing the impairment of all the traffic of the end
+/// `iface` of a link in place: its root netem qdisc or, when the link has
+/// `flows`, that of the band of the traffic no flow matches.
+pub(crate) fn replace_args(
+    iface: &str,
+    link: &Impairment,
+    flows: bool,
+) -> Vec<String> {
+    let mut args = vec!["qdisc", "replace", "dev", iface];
+    match flows {
+        // As set up by `tc_commands`.
+        true => args.extend(["parent", "1:1", "handle", "10:"]),
+        false => args.push("root"),
+    }
+    let mut args: Vec<String> = args.into_iter().map(str::to_string).collect();
+    args.extend(link.netem_args());
+    args
+}
+
+/// `tc` commands setting up the impairments of the end `iface` of a link:
+/// `link` for all of its traffic, and `flows` for the traffic they match.
+///
+/// Flows get their own band of a prio qdisc, which flower filters steer
+/// their packets to, the rest going through the first band.
+pub(crate) fn tc_commands(
+    iface: &str,
+    link: Option<&Impairment>,
+    flows: &[FlowImpairment],
+) -> Vec<Vec<String>> {
+    let command = |args: &[&str]| -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    };
+
+    if flows.is_empty() {
+        return link
+            .map(|link| {
+                let mut args =
+                    command(&["qdisc", "replace", "dev", iface, "root"]);
+                args.extend(link.netem_args());
+                args
+            })
+            .into_iter()
+            .collect();
+    }
+
+    let mut commands = vec![];
+    let bands = (flows.len() + 1).to_string();
+    let mut root = command(&[
+        "qdisc", "replace", "dev", iface, "root", "handle", "1:", "prio",
+        "bands", &bands, "priomap",
+    ]);
+    // Everything not matched by a filter goes to the first band.
+    root.extend(std::iter::repeat_n("0".to_string(), 16));
+    commands.push(root);
+
+    if let Some(link) = link {
+        let mut args = command(&[
+            "qdisc", "add", "dev", iface, "parent", "1:1", "handle", "10:",
+        ]);
+        args.extend(link.netem_args());
+        commands.push(args);
+    }
+
+    let mut prio = 0;
+    for (index, flow) in flows.iter().enumerate() {
+        // Minor numbers and handles are hexadecimal.
+        let classid = format!("1:{:x}", index + 2);
+        if let Some(impairment) = &flow.impairment {
+            let handle = format!("{:x}:", index + 0x11);
+            let mut args = command(&[
+                "qdisc", "add", "dev", iface, "parent", &classid, "handle",
+                &handle,
+            ]);
+            args.extend(impairment.netem_args());
+            commands.push(args);
+        }
+
+        // Filters of the same priority have to share their protocol.
+        for family in flow.matching.families() {
+            prio += 1;
+            let prio = prio.to_string();
+            for flower_args in flow.matching.flower_args() {
+                let mut args = command(&[
+                    "filter", "add", "dev", iface, "parent", "1:", "protocol",
+                    family, "prio", &prio, "flower",
+                ]);
+                args.extend(flower_args);
+                args.extend(["classid".to_string(), classid.clone()]);
+                commands.push(args);
+            }
+        }
+    }
+    commands
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::net::IpAddr;
+
+// ==== enum AddressFamily ====
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    pub fn of(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(_) => AddressFamily::Ipv4,
+            IpAddr::V6(_) => AddressFamily::Ipv6,
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ipv4" => Some(AddressFamily::Ipv4),
+            "ipv6" => Some(AddressFamily::Ipv6),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressFamily::Ipv4 => write!(f, "ipv4"),
+            AddressFamily::Ipv6 => write!(f, "ipv6"),
+        }
+    }
+}
+
+// ==== struct Families ====
+
+/// The address families enabled on a router or one of its interfaces, in
+/// order of preference: helpers picking one of a node's addresses, e.g. for
+/// its router-id, pick one of the first family it has an address of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Families(Vec<AddressFamily>);
+
+impl Families {
+    // Callers make sure the list is neither empty nor has duplicates.
+    pub(crate) fn new(families: Vec<AddressFamily>) -> Self {
+        Self(families)
+    }
+
+    pub fn contains(&self, family: AddressFamily) -> bool {
+        self.0.contains(&family)
+    }
+
+    pub fn allows(&self, address: IpAddr) -> bool {
+        self.contains(AddressFamily::of(address))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = AddressFamily> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// Rank of the address' family, for sorting addresses by preference.
+    /// Addresses of disabled families come last.
+    pub fn rank(&self, address: IpAddr) -> usize {
+        let family = AddressFamily::of(address);
+        self.0
+            .iter()
+            .position(|enabled| *enabled == family)
+            .unwrap_or(self.0.len())
+    }
+
+    // The sysctls, in the form `sysctl -w` takes, keeping the kernel from
+    // using the disabled families on `iface`: no IPv6 link-local address,
+    // DAD or router solicitations, and no answers to ARP requests.
+    pub(crate) fn sysctls(&self, iface: &str) -> Vec<String> {
+        // Slashes allow for interface names with dots, e.g. VLANs.
+        let mut sysctls = vec![];
+        if !self.contains(AddressFamily::Ipv6) {
+            sysctls.push(format!("net/ipv6/conf/{iface}/disable_ipv6=1"));
+        }
+        if !self.contains(AddressFamily::Ipv4) {
+            sysctls.push(format!("net/ipv4/conf/{iface}/arp_ignore=8"));
+        }
+        sysctls
+    }
+}
+
+impl Default for Families {
+    fn default() -> Self {
+        Self(vec![AddressFamily::Ipv4, AddressFamily::Ipv6])
+    }
+}
@@ -0,0 +1,42 @@
+use ipnetwork::IpNetwork;
+
+use super::Families;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interface {
+    pub(crate) name: String,
+    pub(crate) addresses: Vec<IpNetwork>,
+    // Pools an address is to be allocated from.
+    pub(crate) pools: Vec<String>,
+    // Overrides the families of the router.
+    pub(crate) families: Option<Families>,
+    // Created as a dummy interface of the router rather than by a link.
+    pub(crate) dummy: bool,
+}
+
+// ==== impl Interface ====
+
+impl Interface {
+    pub(crate) fn new(name: String) -> Self {
+        Self {
+            name,
+            addresses: vec![],
+            pools: vec![],
+            families: None,
+            dummy: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Addresses of the interface, including those allocated from pools.
+    pub fn addresses(&self) -> &[IpNetwork] {
+        &self.addresses
+    }
+
+    pub fn is_dummy(&self) -> bool {
+        self.dummy
+    }
+}
@@ -0,0 +1,1375 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::future::Future;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use ipnetwork::IpNetwork;
+use nix::errno::Errno;
+use nix::fcntl::{OFlag, open};
+use nix::net::if_::if_nametoindex;
+use nix::sched::{CloneFlags, setns};
+use nix::sys::stat::Mode;
+use nix::sys::wait::waitpid;
+use nix::unistd::{
+    ForkResult, dup2_stderr, dup2_stdin, dup2_stdout, fork, pipe2, setsid,
+};
+use rtnetlink::{LinkDummy, LinkUnspec, new_connection};
+use tokio::runtime::Runtime;
+use tracing::{debug, error, warn};
+
+use super::{Device, Families, Interface};
+use crate::backend::Backend;
+use crate::cancel;
+use crate::checkpoint;
+use crate::coredump;
+use crate::error::{LinkError, NamespaceError, NetError};
+use crate::events;
+use crate::expose;
+use crate::fib::StaticRoute;
+use crate::frr::{self, Frr};
+use crate::injector::RouteInjector;
+use crate::limits::ProcessLimits;
+use crate::lldp::LldpAgent;
+use crate::nat::NatRule;
+use crate::ntp::NtpRole;
+use crate::packet::{Matcher, Packet};
+use crate::pki::NodeCert;
+use crate::probe::{Probe, ProbeState};
+use crate::raw::RawSocket;
+use crate::rule::PolicyRule;
+use crate::service::Service;
+use crate::ssh::SshServer;
+use crate::state::{DaemonStatus, ProcessRecord, TopologyState};
+use crate::{NamespaceHandle, NetResult, devices_ns_dir, process_start_time};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Volume {
+    pub(crate) src: String,
+    pub(crate) dst: String,
+}
+
+/// A file materialized at `dst` in the router's private mount namespace.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FileDrop {
+    pub(crate) dst: String,
+    pub(crate) source: FileSource,
+    // Permission bits, e.g. 0o600 for keys.
+    pub(crate) mode: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum FileSource {
+    Inline(String),
+    Path(String),
+}
+
+/// A TCP port of the router made reachable from the host.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PortForward {
+    pub(crate) port: u16,
+    // Address of the router the connections are forwarded to.
+    pub(crate) address: IpAddr,
+    pub(crate) host_port: u16,
+    pub(crate) host_address: IpAddr,
+}
+
+// ==== impl PortForward ====
+
+impl PortForward {
+    pub(crate) fn new(port: u16) -> Self {
+        Self {
+            port,
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            host_port: port,
+            host_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        }
+    }
+
+    pub(crate) fn host_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host_address, self.host_port)
+    }
+
+    pub(crate) fn target_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Kind {
+    pub(crate) name: String,
+    pub(crate) volumes: Vec<Volume>,
+    pub(crate) scripts: Vec<String>,
+}
+
+// ==== impl Kind ====
+
+impl Kind {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Router {
+    pub name: String,
+    pub kind: Option<String>,
+    // Set once powered on.
+    pub(crate) namespace: OnceLock<Box<NamespaceHandle>>,
+    pub(crate) interfaces: Vec<Interface>,
+    pub(crate) volumes: Vec<Volume>,
+    // Directories kept across stops, under `persist_dir()`.
+    pub(crate) persist: Vec<String>,
+    pub(crate) files: Vec<FileDrop>,
+    pub(crate) expose: Vec<PortForward>,
+    // Its gRPC northbound, when its kind is proxied.
+    pub(crate) grpc: Option<PortForward>,
+    pub(crate) scripts: Vec<String>,
+    pub(crate) injector: Option<Box<RouteInjector>>,
+    // Long-running processes beside its daemons, by name.
+    pub(crate) services: Vec<Service>,
+    pub(crate) frr: Option<Box<Frr>>,
+    pub(crate) ssh: Option<Box<SshServer>>,
+    pub(crate) lldp: Option<LldpAgent>,
+    // Certificate issued by the lab CA, if any.
+    pub(crate) cert: Option<NodeCert>,
+    // Part taken in the topology's time service, if any.
+    pub(crate) ntp: Option<NtpRole>,
+    // Environment variables of the processes started in the router (TZ,
+    // LANG...), on top of netgen's own.
+    pub(crate) env: BTreeMap<String, String>,
+    // File descriptor, OOM killer and scheduling limits of those processes.
+    pub(crate) limits: ProcessLimits,
+    // Liveness probes towards other routers.
+    pub(crate) probes: Vec<Probe>,
+    // Routes programmed into the FIB once the interfaces are addressed.
+    pub(crate) static_fib: Vec<StaticRoute>,
+    // Policy routing rules, added along with the static routes.
+    pub(crate) rules: Vec<PolicyRule>,
+    // NAT rules, loaded into nftables once the links are up.
+    pub(crate) nat: Vec<NatRule>,
+    // Address families enabled on the interfaces which do not set theirs.
+    pub(crate) families: Families,
+    // IPv4 address of the loopback interface, used as router-id.
+    pub(crate) router_id: Option<IpAddr>,
+    // User-defined attributes (pod, rack, role...), not interpreted by
+    // netgen.
+    pub(crate) metadata: BTreeMap<String, String>,
+    // Device under test, which measurements and reports focus on.
+    pub(crate) dut: bool,
+}
+
+// ==== impl Router ====
+
+impl Router {
+    /// Creates a Router object that will represent the
+    /// router
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn interfaces(&self) -> &[Interface] {
+        &self.interfaces
+    }
+
+    /// IPv4 address of the loopback interface, used as router-id.
+    pub fn router_id(&self) -> Option<IpAddr> {
+        self.router_id
+    }
+
+    /// Change interface state to up.
+    pub fn iface_up(&self, ifindex: u32, runtime: &Runtime) -> NetResult<()> {
+        let router_name = self.name.clone();
+        runtime.block_on(async {
+            self.in_ns(false, move || async move {
+                let (connection, handle, _) =
+                    new_connection().map_err(|err| {
+                        LinkError::ConnectionFailed { source: err }
+                    })?;
+
+                tokio::spawn(connection);
+
+                let message = LinkUnspec::new_with_index(ifindex).up().build();
+
+                handle.link().set(message).execute().await.map_err(|err| {
+                    error!(router=%router_name, ifindex=%ifindex,
+                        "problem bringing up"
+                    );
+                    NetError::LinkError(LinkError::ChangeStateUp {
+                        device: router_name.clone(),
+                        ifindex,
+                        source: err,
+                    })
+                })?;
+                Ok::<(), NetError>(())
+            })
+            .await?
+        })
+    }
+
+    /// Moves the interface `iface` of the router, e.g. one end of a veth,
+    /// into the namespace of `to` and brings it up there. The addresses it
+    /// had are lost on the way.
+    pub(crate) fn move_iface(
+        &self,
+        runtime: &Runtime,
+        iface: &str,
+        to: &Self,
+    ) -> NetResult<()> {
+        let Some(namespace) = to.namespace.get() else {
+            return Err(NamespaceError::NotFound {
+                device: to.name.clone(),
+            }
+            .into());
+        };
+        let net_path = namespace.net_path();
+        let netns_file =
+            File::open(net_path).map_err(|err| NamespaceError::FileOpen {
+                path: net_path.to_string(),
+                source: err,
+            })?;
+        let (moved, iface) = (iface.to_string(), iface.to_string());
+        let to_name = to.name.clone();
+
+        runtime.block_on(async {
+            self.in_ns(false, move || async move {
+                let (connection, handle, _) =
+                    new_connection().map_err(|err| {
+                        LinkError::ConnectionFailed { source: err }
+                    })?;
+                tokio::spawn(connection);
+
+                let ifindex =
+                    if_nametoindex(moved.as_str()).map_err(|err| {
+                        LinkError::NoInterface {
+                            iface: moved.clone(),
+                            source: err,
+                        }
+                    })?;
+                let message = LinkUnspec::new_with_index(ifindex)
+                    .setns_by_fd(netns_file.as_raw_fd())
+                    .build();
+                handle.link().set(message).execute().await.map_err(|err| {
+                    LinkError::ExecuteFailed {
+                        operation: "move-iface->move-link-to-router-namespace"
+                            .to_string(),
+                        source: err,
+                    }
+                })?;
+                Ok::<(), NetError>(())
+            })
+            .await??;
+
+            // The interface is down once in its new namespace, and its
+            // index may have changed.
+            to.in_ns(false, move || async move {
+                let (connection, handle, _) =
+                    new_connection().map_err(|err| {
+                        LinkError::ConnectionFailed { source: err }
+                    })?;
+                tokio::spawn(connection);
+
+                let ifindex =
+                    if_nametoindex(iface.as_str()).map_err(|err| {
+                        LinkError::NoInterface {
+                            iface: iface.clone(),
+                            source: err,
+                        }
+                    })?;
+                let message = LinkUnspec::new_with_index(ifindex).up().build();
+                handle.link().set(message).execute().await.map_err(|err| {
+                    NetError::LinkError(LinkError::ChangeStateUp {
+                        device: to_name.clone(),
+                        ifindex,
+                        source: err,
+                    })
+                })?;
+                Ok::<(), NetError>(())
+            })
+            .await?
+        })
+    }
+
+    // The IPv4 address of the loopback interface, if it has one.
+    pub(crate) fn loopback_v4(&self) -> Option<Ipv4Addr> {
+        let lo = self.interfaces.iter().find(|iface| iface.name == "lo")?;
+        lo.addresses.iter().find_map(|addr| match addr.ip() {
+            IpAddr::V4(addr) => Some(addr),
+            IpAddr::V6(_) => None,
+        })
+    }
+
+    /// First address configured on the loopback interface, of the family
+    /// the router prefers.
+    pub(crate) fn loopback_address(&self) -> Option<IpAddr> {
+        let lo = self.interfaces.iter().find(|iface| iface.name == "lo")?;
+        let mut addrs: Vec<IpAddr> =
+            lo.addresses.iter().map(|addr| addr.ip()).collect();
+        addrs.sort_by_key(|addr| self.families.rank(*addr));
+        addrs.first().copied()
+    }
+
+    /// Address families enabled on the interface, the router's unless it
+    /// sets its own.
+    pub fn families_of(&self, iface: &str) -> &Families {
+        self.interfaces
+            .iter()
+            .find(|interface| interface.name == iface)
+            .and_then(|interface| interface.families.as_ref())
+            .unwrap_or(&self.families)
+    }
+
+    /// Executes instructions inside the
+    /// router's namespace.
+    ///
+    /// ```no_run
+    /// use std::process::Command;
+    ///
+    /// use netgen::node::Router;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let router = Router::new("r1");
+    ///     let _ = router
+    ///         .in_ns(false, move || async move {
+    ///             let output = Command::new("ip").args(vec!["link"]).output();
+    ///
+    ///             // this will show you the output
+    ///             // of the `ip link` in the router.
+    ///             // If no modifications have been made
+    ///             // to the namespace, should only show
+    ///             // the loopback ("lo") interface
+    ///             println!("{:#?}", output);
+    ///         })
+    ///         .await;
+    /// }
+    /// ```
+    pub async fn in_ns<Fut, T, R>(&self, is_mount: bool, f: Fut) -> NetResult<R>
+    where
+        Fut: FnOnce() -> T + Send + 'static,
+        T: Future<Output = R> + Send,
+    {
+        match self.namespace.get() {
+            Some(namespace) => {
+                // Move into the Router namespace.
+                let netns_file =
+                    File::open(namespace.net_path()).map_err(|err| {
+                        NamespaceError::FileOpen {
+                            path: namespace.net_path().to_string(),
+                            source: err,
+                        }
+                    })?;
+
+                let pidns_file =
+                    File::open(namespace.pid_path()).map_err(|err| {
+                        NamespaceError::FileOpen {
+                            path: namespace.pid_path().to_string(),
+                            source: err,
+                        }
+                    })?;
+
+                setns(netns_file.as_fd(), CloneFlags::CLONE_NEWNET).map_err(
+                    |err| NamespaceError::Entry {
+                        device: self.name.clone(),
+                        source: err,
+                    },
+                )?;
+
+                setns(pidns_file.as_fd(), CloneFlags::CLONE_NEWPID).map_err(
+                    |err| NamespaceError::Entry {
+                        device: self.name.clone(),
+                        source: err,
+                    },
+                )?;
+
+                if is_mount {
+                    // Unshare into a new mount namespace so our mounts
+                    // don't leak to the host.
+                    nix::sched::unshare(CloneFlags::CLONE_NEWNS).map_err(
+                        |err| NamespaceError::Unshare {
+                            ns_name: self.name.clone(),
+                            source: err,
+                        },
+                    )?;
+
+                    // Make all mounts private before remounting proc,
+                    // same as what login does.
+                    nix::mount::mount(
+                        None::<&str>,
+                        "/",
+                        None::<&str>,
+                        nix::mount::MsFlags::MS_PRIVATE
+                            | nix::mount::MsFlags::MS_REC,
+                        None::<&str>,
+                    )
+                    .map_err(|err| {
+                        NamespaceError::Mount {
+                            ns_type: "private remount".to_string(),
+                            device: self.name.clone(),
+                            source: err,
+                        }
+                    })?;
+
+                    // Remount /proc so it reflects the router's PID namespace.
+                    // Containers masking parts of theirs refuse it, /proc
+                    // then showing the container's processes.
+                    match nix::mount::mount(
+                        Some("proc"),
+                        "/proc",
+                        Some("proc"),
+                        nix::mount::MsFlags::empty(),
+                        None::<&str>,
+                    ) {
+                        Ok(()) => {}
+                        Err(Errno::EPERM)
+                            if crate::container::detect().is_some() =>
+                        {
+                            debug!(router = %self.name,
+                                "/proc not remounted in the container");
+                        }
+                        Err(err) => {
+                            return Err(NamespaceError::Mount {
+                                ns_type: "proc".to_string(),
+                                device: self.name.clone(),
+                                source: err,
+                            }
+                            .into());
+                        }
+                    }
+
+                    crate::mount_router_volumes(self)?;
+                }
+
+                let result = (f)().await;
+
+                // Go back to the main namespace.
+                let main = NamespaceHandle::new(None);
+                let main_net_path = main.net_path();
+                let main_pid_path = main.pid_path();
+
+                let main_net_file =
+                    File::open(main_net_path).map_err(|err| {
+                        NetError::BasicError(format!(
+                            "Unable to open file {main_net_path}: {err:?}"
+                        ))
+                    })?;
+
+                let main_pid_file =
+                    File::open(main_pid_path).map_err(|err| {
+                        NetError::BasicError(format!(
+                            "Unable to open file {main_pid_path}: {err:?}"
+                        ))
+                    })?;
+
+                setns(main_net_file.as_fd(), CloneFlags::CLONE_NEWNET)
+                    .map_err(|err| {
+                        NetError::NamespaceError(NamespaceError::ReturnToMain {
+                            source: err,
+                        })
+                    })?;
+
+                setns(main_pid_file.as_fd(), CloneFlags::CLONE_NEWPID)
+                    .map_err(|err| {
+                        NetError::NamespaceError(NamespaceError::ReturnToMain {
+                            source: err,
+                        })
+                    })?;
+
+                Ok(result)
+            }
+            None => Err(NamespaceError::NotFound {
+                device: self.name.clone(),
+            }
+            .into()),
+        }
+    }
+
+    /// adds the addresses of the said router as
+    /// per the topology yaml file.
+    ///
+    /// Example:
+    /// ```yaml
+    ///
+    /// rt2:
+    ///   interfaces:
+    ///     lo:
+    ///       ipv4:
+    ///       - 2.2.2.2/32
+    ///     eth-sw1:
+    ///       ipv4:
+    ///       - 10.0.1.2/24
+    /// ```
+    /// Above yaml config in topo file will add the address
+    /// 10.0.1.2/24 to the eth-sw1 interface and 2.2.2.2/32
+    /// to the lo address
+    pub fn add_iface_addresses(&self, runtime: &Runtime) -> NetResult<()> {
+        let addresses: Vec<(String, IpNetwork)> = self
+            .interfaces
+            .iter()
+            .flat_map(|iface| {
+                iface
+                    .addresses
+                    .iter()
+                    .map(|addr| (iface.name.clone(), *addr))
+            })
+            .collect();
+        self.add_addresses(runtime, &addresses, true)
+    }
+
+    /// Adds `addresses` to the interfaces of the router, entering its
+    /// namespace and opening a netlink connection once for all of them, so
+    /// that nodes with hundreds of sub-interfaces come up in reasonable time.
+    ///
+    /// Addresses of interfaces which do not exist, e.g. without a link
+    /// attached, are skipped with `skip_missing`, and fail otherwise.
+    pub(crate) fn add_addresses(
+        &self,
+        runtime: &Runtime,
+        addresses: &[(String, IpNetwork)],
+        skip_missing: bool,
+    ) -> NetResult<()> {
+        if addresses.is_empty() {
+            return Ok(());
+        }
+        let addresses = addresses.to_vec();
+        let router_name = self.name.clone();
+
+        runtime.block_on(async {
+            self.in_ns(false, move || async move {
+                let (connection, handle, _) =
+                    new_connection().map_err(|err| {
+                        LinkError::ConnectionFailed { source: err }
+                    })?;
+                tokio::spawn(connection);
+
+                // Interface indexes, looked up once per interface.
+                let mut ifindexes: BTreeMap<String, Option<u32>> =
+                    BTreeMap::new();
+                for (iface, addr) in &addresses {
+                    if !ifindexes.contains_key(iface) {
+                        let ifindex = match if_nametoindex(iface.as_str()) {
+                            Ok(ifindex) => Some(ifindex),
+                            Err(_) if skip_missing => {
+                                warn!(router = %router_name, %iface,
+                                    "Address not added. Interfaces without \
+                                    attached links not added.");
+                                None
+                            }
+                            Err(err) => {
+                                return Err(LinkError::NoInterface {
+                                    iface: iface.clone(),
+                                    source: err,
+                                }
+                                .into());
+                            }
+                        };
+                        ifindexes.insert(iface.clone(), ifindex);
+                    }
+                    let Some(ifindex) = ifindexes[iface] else {
+                        continue;
+                    };
+
+                    let request =
+                        handle.address().add(ifindex, addr.ip(), addr.prefix());
+                    request.execute().await.map_err(|err| {
+                        error!(router = %router_name, %iface, %addr,
+                            "Unable to add address");
+                        NetError::LinkError(LinkError::AddressAdd {
+                            iface: iface.clone(),
+                            addr: *addr,
+                            source: err,
+                        })
+                    })?;
+                }
+                Ok(())
+            })
+            .await?
+        })
+    }
+
+    /// Creates the dummy interfaces of the router, up, through a single
+    /// netlink connection.
+    pub fn add_dummies(&self, runtime: &Runtime) -> NetResult<()> {
+        let dummies: Vec<String> = self
+            .interfaces
+            .iter()
+            .filter(|iface| iface.dummy)
+            .map(|iface| iface.name.clone())
+            .collect();
+        if dummies.is_empty() {
+            return Ok(());
+        }
+        let router_name = self.name.clone();
+
+        runtime.block_on(async {
+            self.in_ns(false, move || async move {
+                let (connection, handle, _) =
+                    new_connection().map_err(|err| {
+                        LinkError::ConnectionFailed { source: err }
+                    })?;
+                tokio::spawn(connection);
+                debug!(router = %router_name, count = dummies.len(),
+                    "Adding dummy interfaces");
+                for dummy in &dummies {
+                    let message = LinkDummy::new(dummy).up().build();
+                    handle.link().add(message).execute().await.map_err(
+                        |err| {
+                            NetError::BasicError(format!(
+                                "Failed to create dummy interface {dummy} \
+                                    on {router_name}: {err}"
+                            ))
+                        },
+                    )?;
+                }
+                Ok(())
+            })
+            .await?
+        })
+    }
+
+    /// Programs the routes of the router's `static-fib` into its FIB.
+    pub fn add_static_routes(&self, runtime: &Runtime) -> NetResult<()> {
+        self.add_routes(runtime, &self.static_fib)
+    }
+
+    pub(crate) fn add_routes(
+        &self,
+        runtime: &Runtime,
+        routes: &[StaticRoute],
+    ) -> NetResult<()> {
+        if routes.is_empty() {
+            return Ok(());
+        }
+        let routes = routes.to_vec();
+        let router_name = self.name.clone();
+
+        runtime.block_on(async {
+            self.in_ns(false, move || async move {
+                let (connection, handle, _) =
+                    new_connection().map_err(|err| {
+                        LinkError::ConnectionFailed { source: err }
+                    })?;
+                tokio::spawn(connection);
+                for route in &routes {
+                    debug!(router = %router_name, %route, "Adding route");
+                    route.add(&handle).await?;
+                }
+                Ok(())
+            })
+            .await?
+        })
+    }
+
+    /// Issues the router's certificate (if it gets one) from the lab CA.
+    pub fn issue_cert(&self) -> NetResult<()> {
+        match &self.cert {
+            Some(cert) => cert.issue(&self.name),
+            None => Ok(()),
+        }
+    }
+
+    pub fn run_scripts(&self, runtime: &Runtime) -> NetResult<()> {
+        if self.scripts.is_empty() {
+            return Ok(());
+        }
+
+        let scripts = self.scripts.clone();
+        //let volumes = self.volumes.clone();
+        let router_name = self.name.clone();
+        let env = self.env.clone();
+        let limits = self.limits;
+
+        runtime.block_on(async {
+            self.in_ns(true, move || async move {
+                for script in &scripts {
+                    debug!(
+                        router = %router_name,
+                        script = %script,
+                        "Running script"
+                    );
+
+                    let mut parts: Vec<&str> =
+                        script.split_whitespace().collect();
+
+                    // Commands that are meant to run in background end with '&',
+                    // We run the commands in background by default, so no need
+                    // for that.
+                    if parts.last() == Some(&"&") {
+                        parts.pop();
+                    }
+
+                    // Check if it is meant to be a background task.
+                    if parts.is_empty() {
+                        continue;
+                    };
+                    let executable = parts[0];
+                    Self::spawn_detached(
+                        &router_name,
+                        &env,
+                        limits,
+                        executable,
+                        executable,
+                        &parts[1..],
+                    )?;
+
+                    debug!(
+                        router = %router_name,
+                        script = %script,
+                        "Script completed"
+                    );
+                }
+                Ok::<(), NetError>(())
+            })
+            .await?
+        })
+    }
+
+    /// Starts the built-in route injector (if configured) inside the
+    /// router's namespace.
+    pub fn run_injector(&self, runtime: &Runtime) -> NetResult<()> {
+        let Some(injector) = &self.injector else {
+            return Ok(());
+        };
+
+        let script_path = injector.write_files(&self.name)?;
+        let router_name = self.name.clone();
+        let env = self.env.clone();
+        let limits = self.limits;
+
+        runtime.block_on(async {
+            self.in_ns(true, move || async move {
+                debug!(
+                    router = %router_name,
+                    script = %script_path,
+                    "Starting route injector"
+                );
+                Self::spawn_detached(
+                    &router_name,
+                    &env,
+                    limits,
+                    "injector",
+                    "sh",
+                    &[script_path.as_str()],
+                )
+            })
+            .await?
+        })
+    }
+
+    /// Starts the router's services (if any), each under a supervisor
+    /// restarting it, inside the router's namespaces, then waits for them to
+    /// be ready.
+    pub fn run_services(&self, runtime: &Runtime) -> NetResult<()> {
+        if self.services.is_empty() {
+            return Ok(());
+        }
+
+        let services = self.services.clone();
+        let router_name = self.name.clone();
+        let env = self.env.clone();
+        let limits = self.limits;
+
+        runtime.block_on(async {
+            self.in_ns(true, move || async move {
+                for service in &services {
+                    debug!(router = %router_name, service = %service.name,
+                        "Starting service");
+                    Self::detach(&router_name, &service.name, limits, || {
+                        service.supervise(&router_name, &env);
+                    })?;
+                }
+                for service in &services {
+                    service
+                        .wait_ready(&router_name, &env)
+                        .map_err(NetError::BasicError)?;
+                }
+                Ok::<(), NetError>(())
+            })
+            .await?
+        })
+    }
+
+    /// Restores `daemon` from the CRIU checkpoint in `dir` inside the
+    /// router's namespaces, and records it in the topology state as
+    /// `spawn_detached` would.
+    pub(crate) fn restore_daemon(
+        &self,
+        runtime: &Runtime,
+        dir: &str,
+        daemon: &str,
+    ) -> NetResult<()> {
+        let (router_name, dir, daemon) =
+            (self.name.clone(), dir.to_string(), daemon.to_string());
+
+        runtime.block_on(async {
+            self.in_ns(true, move || async move {
+                debug!(router = %router_name, %daemon, "Restoring daemon");
+                let pid = checkpoint::restore(&dir, &daemon)?;
+                let Some(start_time) = process_start_time(pid) else {
+                    return Err(NetError::BasicError(format!(
+                        "{daemon} of {router_name} exited once restored"
+                    )));
+                };
+                TopologyState::record_process(ProcessRecord {
+                    node: router_name,
+                    name: daemon,
+                    pid,
+                    start_time,
+                })
+            })
+            .await?
+        })
+    }
+
+    /// Environment variables set for the processes started in the router.
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    /// Whether a startup config is pushed to the router's daemons.
+    pub fn has_startup_config(&self) -> bool {
+        self.frr
+            .as_ref()
+            .is_some_and(|frr| frr.startup_config.is_some())
+    }
+
+    /// Starts the FRR daemons (if configured) inside the router's
+    /// namespace, zebra first, then pushes its startup config to them.
+    pub fn run_frr(&self, runtime: &Runtime) -> NetResult<()> {
+        let Some(frr) = self.frr.as_deref().cloned() else {
+            return Ok(());
+        };
+        let router_name = self.name.clone();
+        let env = self.env.clone();
+        let limits = self.limits;
+        let args = match self.dut {
+            true => [frr::DAEMON_ARGS, frr::DUT_ARGS].concat(),
+            false => frr::DAEMON_ARGS.to_vec(),
+        };
+
+        runtime.block_on(async {
+            self.in_ns(true, move || async move {
+                for daemon in frr.daemons() {
+                    debug!(router = %router_name, %daemon, "Starting FRR");
+                    Self::spawn_detached(
+                        &router_name,
+                        &env,
+                        limits,
+                        daemon,
+                        &frr.binary(daemon),
+                        &args,
+                    )?;
+                }
+                frr.load_startup_config(&router_name)?;
+                frr.run_commands(&router_name)
+            })
+            .await?
+        })
+    }
+
+    /// Whether the router is a device under test (`dut: true`).
+    pub fn is_dut(&self) -> bool {
+        self.dut
+    }
+
+    /// Whether the FRR daemons are only started once the router is first
+    /// used, see `Topology::start_lazy_daemons`.
+    pub fn frr_is_lazy(&self) -> bool {
+        self.frr.as_ref().is_some_and(|frr| frr.lazy)
+    }
+
+    // FRR daemons the router runs, if any.
+    pub(crate) fn frr_daemons(&self) -> Vec<&str> {
+        self.frr.as_deref().map(Frr::daemons).unwrap_or_default()
+    }
+
+    /// Starts chronyd (if the router takes part in the topology's time
+    /// service) inside the router's namespace.
+    pub fn run_ntp(&self, runtime: &Runtime) -> NetResult<()> {
+        let Some(ntp) = &self.ntp else {
+            return Ok(());
+        };
+        let Some(args) = ntp.write_config(&self.name)? else {
+            return Ok(());
+        };
+        let router_name = self.name.clone();
+        let env = self.env.clone();
+        let limits = self.limits;
+
+        runtime.block_on(async {
+            self.in_ns(false, move || async move {
+                debug!(router = %router_name, "Starting chronyd");
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                Self::spawn_detached(
+                    &router_name,
+                    &env,
+                    limits,
+                    "chronyd",
+                    "chronyd",
+                    &args,
+                )
+            })
+            .await?
+        })
+    }
+
+    /// Starts the SSH server sidecar (if enabled) inside the router's
+    /// namespace.
+    pub fn run_ssh(&self, runtime: &Runtime) -> NetResult<()> {
+        let Some(ssh) = self.ssh.as_deref().filter(|ssh| ssh.enabled).cloned()
+        else {
+            return Ok(());
+        };
+
+        let key_path = ssh.ensure_host_key(&self.name)?;
+        let router_name = self.name.clone();
+        let env = self.env.clone();
+        let limits = self.limits;
+
+        runtime.block_on(async {
+            self.in_ns(true, move || async move {
+                debug!(router = %router_name, port = ssh.port,
+                    "Starting SSH server");
+                let args = ssh.args(&key_path);
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                Self::spawn_detached(
+                    &router_name,
+                    &env,
+                    limits,
+                    "ssh",
+                    "dropbear",
+                    &args,
+                )
+            })
+            .await?
+        })
+    }
+
+    /// Starts the liveness probes towards the router's peers, if any, each in
+    /// a daemon living in the router's namespaces.
+    pub fn run_probes(&self) -> NetResult<()> {
+        let namespace = NamespaceHandle::new(Some(&self.name));
+        let net_path = namespace.net_path();
+        for probe in &self.probes {
+            let netns_file = File::open(net_path).map_err(|err| {
+                NamespaceError::FileOpen {
+                    path: net_path.to_string(),
+                    source: err,
+                }
+            })?;
+            debug!(router = %self.name, peer = %probe.peer,
+                "Starting liveness probe");
+            let name = format!("probe-{}", probe.peer);
+            Self::detach(&self.name, &name, self.limits, || {
+                if setns(netns_file.as_fd(), CloneFlags::CLONE_NEWNET).is_ok()
+                    && let Err(err) = probe.run(&self.name)
+                {
+                    events::record(
+                        "probe",
+                        &format!("{} -> {}", self.name, probe.peer),
+                        &format!("failed: {err}"),
+                    );
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Whether the router runs the built-in LLDP agent.
+    pub fn lldp_enabled(&self) -> bool {
+        self.lldp.as_ref().is_some_and(|lldp| lldp.enabled)
+    }
+
+    /// Starts the LLDP agent (if enabled) on the router's interfaces, in a
+    /// daemon living in the router's namespaces.
+    pub fn run_lldp(&self) -> NetResult<()> {
+        let Some(lldp) = self.lldp.as_ref().filter(|lldp| lldp.enabled) else {
+            return Ok(());
+        };
+        let ifaces: Vec<String> = self
+            .interfaces
+            .iter()
+            .filter(|iface| iface.name != "lo")
+            .map(|iface| iface.name.clone())
+            .collect();
+
+        let net_path = NamespaceHandle::new(Some(&self.name))
+            .net_path()
+            .to_string();
+        let netns_file =
+            File::open(&net_path).map_err(|err| NamespaceError::FileOpen {
+                path: net_path.clone(),
+                source: err,
+            })?;
+        debug!(router = %self.name, "Starting LLDP agent");
+        Self::detach(&self.name, "lldp", self.limits, || {
+            if setns(netns_file.as_fd(), CloneFlags::CLONE_NEWNET).is_ok() {
+                lldp.run(&self.name, &ifaces);
+            }
+        })
+    }
+
+    /// State of the router's probe sessions, as last reported by its end.
+    pub fn probe_status(&self) -> Vec<ProbeState> {
+        self.probes
+            .iter()
+            .filter_map(|probe| probe.state(&self.name))
+            .collect()
+    }
+
+    /// Makes the ports listed under `expose:`, and the gRPC northbound when
+    /// proxied, reachable from the calling process' network namespace,
+    /// normally the host's.
+    ///
+    /// The listening sockets are bound here, then handed over to a forwarder
+    /// running in the router's namespaces, which connects to the router's
+    /// loopback for every accepted connection.
+    pub fn expose_ports(&self) -> NetResult<()> {
+        if self.expose.is_empty() && self.grpc.is_none() {
+            return Ok(());
+        }
+
+        let mut listeners = vec![];
+        for forward in self.expose.iter().chain(&self.grpc) {
+            let listener =
+                TcpListener::bind(forward.host_addr()).map_err(|err| {
+                    NetError::BasicError(format!(
+                        "Unable to expose {}:{} on {}: {err:?}",
+                        self.name,
+                        forward.port,
+                        forward.host_addr()
+                    ))
+                })?;
+            debug!(router = %self.name, host = %forward.host_addr(),
+                port = forward.port, "exposing port");
+            listeners.push((listener, forward.target_addr()));
+        }
+
+        let namespace = NamespaceHandle::new(Some(&self.name));
+        let net_path = namespace.net_path().to_string();
+        let netns_file =
+            File::open(&net_path).map_err(|err| NamespaceError::FileOpen {
+                path: net_path,
+                source: err,
+            })?;
+
+        Self::detach(&self.name, "expose", self.limits, move || {
+            // The listeners stay in the namespace they were bound in, the
+            // connections to the router are opened from its namespace.
+            if setns(netns_file.as_fd(), CloneFlags::CLONE_NEWNET).is_ok() {
+                let _ = expose::forward(listeners);
+            }
+        })
+    }
+
+    /// A new file descriptor of the router's network namespace, for other
+    /// tooling to `setns` into without knowing where netgen mounts it. The
+    /// namespace stays around for as long as the descriptor is open.
+    pub fn netns_fd(&self) -> NetResult<OwnedFd> {
+        let namespace = NamespaceHandle::new(Some(&self.name));
+        if !namespace.is_mounted() {
+            return Err(NetError::BasicError(format!(
+                "{} is not running",
+                self.name
+            )));
+        }
+        let netns_file = File::open(namespace.net_path()).map_err(|err| {
+            NamespaceError::FileOpen {
+                path: namespace.net_path().to_string(),
+                source: err,
+            }
+        })?;
+        Ok(OwnedFd::from(netns_file))
+    }
+
+    /// Where `path`, as the router's processes see it in their private mount
+    /// namespace, lives on the host: under the router's directories for its
+    /// volumes, persisted directories, files and FRR runtime directory.
+    /// Along with whether it is private to the router, other paths being
+    /// shared with the host.
+    pub fn host_path(&self, path: &str) -> (PathBuf, bool) {
+        let path = Path::new(path);
+        // Mounted last, so shadowing the volumes.
+        if let Some(file) =
+            self.files.iter().find(|file| Path::new(&file.dst) == path)
+        {
+            return (
+                PathBuf::from(crate::file_staging_path(&self.name, file)),
+                true,
+            );
+        }
+        if self.frr.is_some()
+            && let Ok(rest) = path.strip_prefix(frr::RUN_DIR)
+        {
+            let run_dir = format!("{}/{}/frr", devices_ns_dir(), self.name);
+            return (Path::new(&run_dir).join(rest), true);
+        }
+        // Mounted over the volumes.
+        if let Some(dir) = self
+            .persist
+            .iter()
+            .filter(|dir| path.starts_with(dir.as_str()))
+            .max_by_key(|dir| dir.len())
+        {
+            let persisted = crate::persisted_path(&self.name, dir);
+            let rest = path.strip_prefix(dir.as_str()).unwrap_or(path);
+            return (Path::new(&persisted).join(rest), true);
+        }
+        // The innermost volume, as mounted over the outer ones.
+        let volume = self
+            .volumes
+            .iter()
+            .filter_map(|volume| {
+                let rest = path.strip_prefix(&volume.dst).ok()?;
+                Some((volume, rest))
+            })
+            .max_by_key(|(volume, _)| volume.dst.len());
+        match volume {
+            Some((volume, rest)) => {
+                let staging = crate::volume_staging_path(&self.name, volume);
+                match rest.as_os_str().is_empty() {
+                    true => (staging, true),
+                    false => (staging.join(rest), true),
+                }
+            }
+            None => (path.to_path_buf(), false),
+        }
+    }
+
+    /// Opens a packet socket on one of the router's interfaces, to inject
+    /// and capture crafted frames from the router's point of view.
+    pub fn raw_socket(&self, iface: &str) -> NetResult<RawSocket> {
+        RawSocket::open(&self.name, iface)
+    }
+
+    /// Sends `frame`, Ethernet header included, out of `iface` as is.
+    pub fn send_raw(&self, iface: &str, frame: &[u8]) -> NetResult<()> {
+        self.raw_socket(iface)?.send(frame)
+    }
+
+    /// Waits up to `timeout` for the next frame seen on `iface`.
+    ///
+    /// Only frames arriving once this is called are seen, open a
+    /// `raw_socket` first to capture the answer to a frame being sent.
+    pub fn recv_raw(
+        &self,
+        iface: &str,
+        timeout: Duration,
+    ) -> NetResult<Option<Vec<u8>>> {
+        self.raw_socket(iface)?.recv(timeout)
+    }
+
+    /// Waits up to `timeout` for a packet matching `matcher` on `iface`,
+    /// failing if none came. Same as `recv_raw` as to which frames are seen.
+    pub fn expect_packet(
+        &self,
+        iface: &str,
+        matcher: &Matcher,
+        timeout: Duration,
+    ) -> NetResult<Packet> {
+        self.raw_socket(iface)?.expect(matcher, timeout)
+    }
+
+    /// Runs `cmd` as a daemon with the router's `env` added to ours, and
+    /// records it in the topology state so that `stop` can terminate it.
+    fn spawn_detached(
+        router_name: &str,
+        env: &BTreeMap<String, String>,
+        limits: ProcessLimits,
+        name: &str,
+        cmd: &str,
+        args: &[&str],
+    ) -> NetResult<()> {
+        let cmd = cmd.to_string();
+        let args: Vec<String> =
+            args.iter().map(|arg| arg.to_string()).collect();
+        let env = env.clone();
+        Self::detach(router_name, name, limits, move || {
+            // Execute command (no extra process layer!)
+            let _ = Command::new(cmd).args(args).envs(env).exec();
+        })
+    }
+
+    /// Runs `run` in a daemon living in the router's PID namespace, under
+    /// the router's `limits`, and records it in the topology state so that
+    /// `stop` can terminate it.
+    ///
+    /// The recorded PID is the one seen through `/proc`, which still belongs
+    /// to our own PID namespace.
+    fn detach(
+        router_name: &str,
+        name: &str,
+        limits: ProcessLimits,
+        run: impl FnOnce(),
+    ) -> NetResult<()> {
+        let namespace = NamespaceHandle::new(Some(router_name));
+        let pid_path = namespace.pid_path();
+        let pidns_file =
+            File::open(pid_path).map_err(|err| NamespaceError::FileOpen {
+                path: pid_path.to_string(),
+                source: err,
+            })?;
+
+        // The grandchild's PID is sent back through this pipe.
+        let (pid_reader, pid_writer) =
+            pipe2(OFlag::O_CLOEXEC).map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to create pipe for {name}: {err:?}"
+                ))
+            })?;
+
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { child }) => {
+                drop(pid_writer);
+                let mut pid = String::new();
+                let _ = File::from(pid_reader).read_to_string(&mut pid);
+                let _ = waitpid(child, None);
+
+                let Ok(pid) = pid.trim().parse::<i32>() else {
+                    // What was sent back is then why the limits of the
+                    // router could not be applied, if anything.
+                    let mut message =
+                        format!("Unable to start {name} on {router_name}");
+                    if !pid.trim().is_empty() {
+                        message += &format!(": {}", pid.trim());
+                    }
+                    return Err(NetError::BasicError(message));
+                };
+                // The process may already have exited, in which case there
+                // is nothing to stop later on.
+                let Some(start_time) = process_start_time(pid) else {
+                    return Ok(());
+                };
+                TopologyState::record_process(ProcessRecord {
+                    node: router_name.to_string(),
+                    name: name.to_string(),
+                    pid,
+                    start_time,
+                })
+            }
+
+            Ok(ForkResult::Child) => {
+                drop(pid_reader);
+                // Detach from terminal & session.
+                setsid().map_err(|err| {
+                    NetError::BasicError(format!(
+                        "Unable to detach from terminal for {name} -> {err:?}"
+                    ))
+                })?;
+
+                // Our children are to be part of the router, whichever
+                // namespace we are called from.
+                setns(pidns_file.as_fd(), CloneFlags::CLONE_NEWPID).map_err(
+                    |err| NamespaceError::Entry {
+                        device: router_name.to_string(),
+                        source: err,
+                    },
+                )?;
+
+                match unsafe { fork() } {
+                    Ok(ForkResult::Parent { .. }) => {
+                        // Exits to prevent zombies.
+                        std::process::exit(0);
+                    }
+
+                    Ok(ForkResult::Child) => {
+                        cancel::reset_interrupts();
+                        let mut pid_writer = File::from(pid_writer);
+                        if let Err(err) = limits.apply() {
+                            let _ = pid_writer.write_all(err.as_bytes());
+                            std::process::exit(1);
+                        }
+                        if let Ok(pid) = std::fs::read_link("/proc/self") {
+                            let _ = pid_writer
+                                .write_all(pid.to_string_lossy().as_bytes());
+                        }
+                        drop(pid_writer);
+
+                        // Redirect stdio → /dev/null
+                        let devnull: OwnedFd =
+                            open("/dev/null", OFlag::O_RDWR, Mode::empty())
+                                .unwrap();
+
+                        let _ = dup2_stdin(&devnull);
+                        let _ = dup2_stdout(&devnull);
+                        let _ = dup2_stderr(&devnull);
+
+                        coredump::enable_cores();
+                        run();
+                        std::process::exit(1);
+                    }
+                    Err(err) => Err(NetError::BasicError(format!(
+                        "Problem creating detached process {name} : {err:?}"
+                    ))),
+                }
+            }
+            Err(err) => Err(NetError::BasicError(format!(
+                "Problem creating spawn_detached {err:?}"
+            ))),
+        }
+    }
+}
+
+impl Device for Router {
+    /// Creates a namespace representing the router.
+    fn power_on(
+        &self,
+        backend: &dyn Backend,
+        _runtime: &Runtime,
+    ) -> NetResult<()> {
+        backend.create_namespace(Some(&self.name))?;
+        let namespace = Box::new(NamespaceHandle::new(Some(&self.name)));
+        // Powering on again is harmless, as long as the namespace is the
+        // same one.
+        if let Err(namespace) = self.namespace.set(namespace)
+            && let Some(current) = self.namespace.get()
+            && *current != namespace
+        {
+            return Err(NetError::BasicError(format!(
+                "Router {} is already powered on in {}, not {}.",
+                self.name,
+                current.net_path(),
+                namespace.net_path()
+            )));
+        }
+
+        debug!(router=%self.name, "Powered on");
+        Ok(())
+    }
+
+    /// Deletes the namespace created by the Router (if it exists)
+    fn power_off(&self, backend: &dyn Backend) -> NetResult<()> {
+        backend.destroy_namespace(Some(&self.name))
+    }
+
+    /// Runs the router's scripts.
+    fn run(&self, runtime: &Runtime) -> NetResult<()> {
+        self.run_scripts(runtime)
+    }
+
+    /// Status of the processes started by the router's scripts and route
+    /// injector, as recorded in the state file.
+    fn status(&self) -> NetResult<Vec<DaemonStatus>> {
+        let Some(state) = TopologyState::load()? else {
+            return Ok(vec![]);
+        };
+        Ok(state
+            .processes
+            .iter()
+            .filter(|process| process.node == self.name)
+            .map(ProcessRecord::status)
+            .collect())
+    }
+}
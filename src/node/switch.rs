@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use nix::net::if_::if_nametoindex;
+use rtnetlink::{LinkBridge, new_connection};
+use tokio::runtime::Runtime;
+use tracing::debug;
+
+use super::{Device, Interface};
+use crate::NetResult;
+use crate::backend::Backend;
+use crate::error::{LinkError, NetError};
+use crate::flow::FlowExport;
+use crate::limits::ProcessLimits;
+use crate::state::{DaemonStatus, ProcessRecord, TopologyState};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Switch {
+    pub(crate) name: String,
+    // Name of the bridge backing the switch, prefixed with the session
+    // identifier so that bridges from different sessions never clash.
+    pub(crate) bridge: String,
+    // Index of the bridge, set once powered on.
+    pub(crate) ifindex: OnceLock<u32>,
+    pub(crate) interfaces: Vec<Interface>,
+    pub(crate) metadata: BTreeMap<String, String>,
+    pub(crate) flow_export: Option<FlowExport>,
+    // Bridge options of the ports, by interface.
+    pub(crate) ports: BTreeMap<String, PortOptions>,
+    // Niceness and limits of the flow exporter.
+    pub(crate) limits: ProcessLimits,
+}
+
+// ==== struct PortOptions ====
+
+// Options of a bridge port, the kernel defaults where None.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct PortOptions {
+    // Only forwards to and from ports which are not isolated.
+    pub(crate) isolated: Option<bool>,
+    // Forwards frames back out of the port they came in through.
+    pub(crate) hairpin: Option<bool>,
+    // Learns the source addresses of the frames it receives.
+    pub(crate) learning: Option<bool>,
+    // Floods frames to unknown destinations out of the port.
+    pub(crate) flood: Option<bool>,
+    // Drops what the port receives beyond a rate.
+    pub(crate) police: Option<Police>,
+}
+
+impl PortOptions {
+    pub(crate) const KEYS: [&str; 5] =
+        ["isolated", "hairpin", "learning", "flood", "police"];
+
+    // Arguments of `bridge link set dev <iface>` setting the options.
+    pub(crate) fn bridge_args(&self) -> Vec<&'static str> {
+        let mut args = vec![];
+        for (option, value) in [
+            ("isolated", self.isolated),
+            ("hairpin", self.hairpin),
+            ("learning", self.learning),
+            ("flood", self.flood),
+        ] {
+            if let Some(value) = value {
+                args.push(option);
+                args.push(if value { "on" } else { "off" });
+            }
+        }
+        args
+    }
+
+    // tc commands policing what the port receives, if it does.
+    pub(crate) fn tc_commands(&self, iface: &str) -> Vec<Vec<String>> {
+        let Some(police) = &self.police else {
+            return vec![];
+        };
+        let command = |args: &[&str]| -> Vec<String> {
+            args.iter().map(|arg| arg.to_string()).collect()
+        };
+        let mut filter =
+            command(&["filter", "add", "dev", iface, "ingress", "protocol"]);
+        match police.broadcast_only {
+            // The group bit of the destination is set on broadcast and
+            // multicast frames alike.
+            true => filter.extend(command(&[
+                "all",
+                "flower",
+                "dst_mac",
+                "01:00:00:00:00:00/01:00:00:00:00:00",
+            ])),
+            false => filter.extend(command(&["all", "matchall"])),
+        }
+        filter.extend(command(&[
+            "action",
+            "police",
+            "rate",
+            &format!("{}kbit", police.rate),
+            "burst",
+            &police.burst().to_string(),
+            "conform-exceed",
+            "drop",
+        ]));
+        vec![
+            command(&["qdisc", "replace", "dev", iface, "ingress"]),
+            filter,
+        ]
+    }
+}
+
+// ==== struct Police ====
+
+// Ingress policing of a switch port.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Police {
+    // In kbit/s, like link rate limits.
+    pub(crate) rate: u32,
+    // In bytes, enough for ten full frames or 10ms at the rate if None.
+    pub(crate) burst: Option<u32>,
+    // Only polices broadcast and multicast frames, i.e. storms.
+    pub(crate) broadcast_only: bool,
+}
+
+impl Police {
+    fn burst(&self) -> u32 {
+        self.burst
+            .unwrap_or_else(|| (self.rate.saturating_mul(10) / 8).max(15140))
+    }
+}
+
+// ==== impl Switch ====
+
+impl Switch {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bridge: name.to_string(),
+            ifindex: OnceLock::new(),
+            interfaces: vec![],
+            metadata: BTreeMap::new(),
+            flow_export: None,
+            ports: BTreeMap::new(),
+            limits: ProcessLimits::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Name of the bridge backing the switch.
+    pub fn bridge(&self) -> &str {
+        &self.bridge
+    }
+
+    pub fn interfaces(&self) -> &[Interface] {
+        &self.interfaces
+    }
+}
+
+impl Device for Switch {
+    /// Initializes a network bridge representing the switch.
+    fn power_on(
+        &self,
+        backend: &dyn Backend,
+        runtime: &Runtime,
+    ) -> NetResult<()> {
+        let name = self.bridge.as_str();
+
+        runtime.block_on(async {
+            let (connection, handle, _) = new_connection()
+                .map_err(|err| LinkError::ConnectionFailed { source: err })?;
+            tokio::spawn(connection);
+
+            let message = LinkBridge::new(name).up().build();
+            let request = handle.link().add(message);
+
+            request.execute().await.map_err(|e| {
+                NetError::BasicError(format!(
+                    "Failed to create bridge {name}: {e}",
+                ))
+            })?;
+
+            if let Ok(ifindex) = if_nametoindex(name) {
+                let _ = self.ifindex.set(ifindex);
+                debug!(switch = %self.name, "Powered on");
+            }
+
+            Ok::<_, NetError>(())
+        })?;
+
+        // The address flows are exported from.
+        if let Some(source) =
+            self.flow_export.as_ref().and_then(|export| export.source)
+        {
+            let output = backend.exec(
+                None,
+                "ip",
+                &["addr", "add", &source.to_string(), "dev", name],
+            )?;
+            if !output.status.success() {
+                return Err(NetError::BasicError(format!(
+                    "Unable to add {source} to bridge {name}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Bridges are cleaned up along with the main namespace.
+    fn power_off(&self, _backend: &dyn Backend) -> NetResult<()> {
+        Ok(())
+    }
+
+    /// Starts exporting flows, if configured to.
+    fn run(&self, _runtime: &Runtime) -> NetResult<()> {
+        match &self.flow_export {
+            Some(export) => export.start(&self.name, &self.bridge, self.limits),
+            None => Ok(()),
+        }
+    }
+
+    /// Status of the flow exporter, as recorded in the state file.
+    fn status(&self) -> NetResult<Vec<DaemonStatus>> {
+        let Some(state) = TopologyState::load()? else {
+            return Ok(vec![]);
+        };
+        Ok(state
+            .processes
+            .iter()
+            .filter(|process| process.node == self.name)
+            .map(ProcessRecord::status)
+            .collect())
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use tracing::{debug, warn};
+
+use crate::events::unix_millis;
+use crate::report::json_string;
+
+// Longest a webhook is given to answer, in seconds.
+const WEBHOOK_TIMEOUT: &str = "10";
+
+// ==== enum Trigger ====
+
+/// Events a `NotifyRule` can be triggered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// A daemon started by netgen dumped core.
+    DaemonCrashed,
+    /// A daemon started by netgen exited without dumping core.
+    DaemonExited,
+    /// A router interface went down, e.g. its link flapped.
+    LinkDown,
+    /// A liveness probe session went down.
+    ProbeDown,
+    /// An assertion or churn step of a test suite failed.
+    TestFailed,
+}
+
+impl Trigger {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "daemon-crashed" => Some(Trigger::DaemonCrashed),
+            "daemon-exited" => Some(Trigger::DaemonExited),
+            "link-down" => Some(Trigger::LinkDown),
+            "probe-down" => Some(Trigger::ProbeDown),
+            "test-failed" => Some(Trigger::TestFailed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::DaemonCrashed => write!(f, "daemon-crashed"),
+            Trigger::DaemonExited => write!(f, "daemon-exited"),
+            Trigger::LinkDown => write!(f, "link-down"),
+            Trigger::ProbeDown => write!(f, "probe-down"),
+            Trigger::TestFailed => write!(f, "test-failed"),
+        }
+    }
+}
+
+// ==== enum NotifyAction ====
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotifyAction {
+    /// The notification is POSTed as JSON to the URL.
+    Webhook(String),
+    /// The command is run on the host through `sh -c`, with the
+    /// notification in its environment.
+    Exec(String),
+}
+
+// ==== struct NotifyRule ====
+
+/// Who to tell when some events happen in a topology:
+///
+/// ```yaml
+/// notify:
+///   - on: [ daemon-crashed, link-down, test-failed ]
+///     webhook: https://hooks.example.com/netgen
+///   - on: [ probe-down ]
+///     nodes: [ rt1, rt2 ]
+///     exec: "logger -t netgen \"$NETGEN_SUMMARY\""
+/// ```
+///
+/// Without `nodes`, the rule applies to every node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotifyRule {
+    pub on: Vec<Trigger>,
+    pub nodes: Vec<String>,
+    pub action: NotifyAction,
+}
+
+impl NotifyRule {
+    pub fn matches(&self, notification: &Notification) -> bool {
+        self.on.contains(&notification.trigger)
+            && (self.nodes.is_empty()
+                || self.nodes.contains(&notification.subject))
+    }
+}
+
+// ==== struct Notification ====
+
+/// An event worth telling the owner of a topology about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub trigger: Trigger,
+    // The node the event happened on, or the suite for failed tests.
+    pub subject: String,
+    pub summary: String,
+}
+
+impl Notification {
+    fn to_json(&self, session: &str) -> String {
+        format!(
+            "{{\"session\": {}, \"event\": {}, \"subject\": {}, \
+                \"summary\": {}, \"time\": {}}}\n",
+            json_string(session),
+            json_string(&self.trigger.to_string()),
+            json_string(&self.subject),
+            json_string(&self.summary),
+            unix_millis()
+        )
+    }
+}
+
+/// Runs the actions of the `rules` matching `notification`, one after the
+/// other. Notifications are best-effort, failing to deliver one is only
+/// logged.
+pub(crate) fn dispatch(
+    rules: &[NotifyRule],
+    session: &str,
+    notification: &Notification,
+) {
+    for rule in rules.iter().filter(|rule| rule.matches(notification)) {
+        debug!(trigger = %notification.trigger, subject = %notification.subject,
+            action = ?rule.action, "notifying");
+        let result = match &rule.action {
+            NotifyAction::Webhook(url) => {
+                post(url, &notification.to_json(session))
+            }
+            NotifyAction::Exec(cmd) => Command::new("sh")
+                .args(["-c", cmd])
+                .env("NETGEN_SESSION", session)
+                .env("NETGEN_EVENT", notification.trigger.to_string())
+                .env("NETGEN_SUBJECT", &notification.subject)
+                .env("NETGEN_SUMMARY", &notification.summary)
+                .stdin(Stdio::null())
+                .status()
+                .map_err(|err| err.to_string())
+                .and_then(|status| match status.success() {
+                    true => Ok(()),
+                    false => Err(format!("exited with {status}")),
+                }),
+        };
+        if let Err(err) = result {
+            warn!(action = ?rule.action, error = %err,
+                "unable to deliver notification");
+        }
+    }
+}
+
+// POSTs `body` to `url` with curl, which handles proxies and TLS for us.
+fn post(url: &str, body: &str) -> Result<(), String> {
+    let mut child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            WEBHOOK_TIMEOUT,
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("unable to run curl: {err}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(body.as_bytes())
+            .map_err(|err| err.to_string())?;
+    }
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+    match output.status.success() {
+        true => Ok(()),
+        false => {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+}
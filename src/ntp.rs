@@ -0,0 +1,92 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::net::IpAddr;
+
+use crate::error::NetError;
+use crate::{NetResult, devices_ns_dir};
+
+const CONFIG_FILE: &str = "chrony.conf";
+
+// ==== struct NtpService ====
+
+/// Lab time service: one router serves time with chronyd, the others run
+/// chrony clients synchronizing with it.
+///
+/// ```yaml
+/// ntp:
+///   server: rt1
+///   address: 10.0.0.1
+///
+/// routers:
+///   rt3:
+///     ntp: false
+/// ```
+///
+/// `ntp: rt1` is short for only naming the server. Clients reach it on
+/// `address`, its loopback or first address by default.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct NtpService {
+    pub(crate) server: String,
+    pub(crate) address: Option<IpAddr>,
+}
+
+// ==== enum NtpRole ====
+
+/// What a router runs of the lab time service.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum NtpRole {
+    Server,
+    Client(IpAddr),
+    // Opted out with `ntp: false`.
+    Disabled,
+}
+
+impl NtpRole {
+    /// Writes the chronyd configuration into the router's device directory
+    /// and returns the arguments to start chronyd with, if the router runs
+    /// it.
+    ///
+    /// The namespaces share the host's clock, which chronyd is told to
+    /// leave alone: the clients only track how far they are from the
+    /// server.
+    pub(crate) fn write_config(
+        &self,
+        router_name: &str,
+    ) -> NetResult<Option<Vec<String>>> {
+        if *self == Self::Disabled {
+            return Ok(None);
+        }
+        let dir = format!("{}/{router_name}/chrony", devices_ns_dir());
+        let io_err = |path: &str, err: std::io::Error| {
+            NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+        };
+        fs::create_dir_all(&dir).map_err(|err| io_err(&dir, err))?;
+        let config_path = format!("{dir}/{CONFIG_FILE}");
+        fs::write(&config_path, self.config(&dir))
+            .map_err(|err| io_err(&config_path, err))?;
+
+        let args = ["-n", "-x", "-u", "root", "-f", &config_path];
+        Ok(Some(args.map(str::to_string).to_vec()))
+    }
+
+    // Runtime files are kept in `dir` so that the routers do not step on
+    // each other's.
+    fn config(&self, dir: &str) -> String {
+        let mut config = String::from("# Written by netgen\n");
+        match self {
+            Self::Server => {
+                let _ = writeln!(config, "local stratum 10");
+                let _ = writeln!(config, "allow all");
+            }
+            Self::Client(server) => {
+                let _ = writeln!(config, "server {server} iburst");
+            }
+            Self::Disabled => {}
+        }
+        let _ = writeln!(config, "driftfile {dir}/chrony.drift");
+        let _ = writeln!(config, "pidfile {dir}/chronyd.pid");
+        let _ = writeln!(config, "bindcmdaddress {dir}/chronyd.sock");
+        let _ = writeln!(config, "bindcmdaddress 127.0.0.1");
+        config
+    }
+}
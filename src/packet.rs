@@ -0,0 +1,590 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub use pnet_base::MacAddr;
+use pnet_packet::Packet as _;
+use pnet_packet::ethernet::{
+    EtherType, EtherTypes, EthernetPacket, MutableEthernetPacket,
+};
+use pnet_packet::icmp::{self, IcmpPacket, MutableIcmpPacket};
+use pnet_packet::icmpv6::{self, Icmpv6Packet, MutableIcmpv6Packet};
+use pnet_packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet_packet::ipv4::{self, Ipv4Packet, MutableIpv4Packet};
+use pnet_packet::ipv6::{Ipv6Packet, MutableIpv6Packet};
+use pnet_packet::tcp::{self, MutableTcpPacket, TcpPacket};
+use pnet_packet::udp::{self, MutableUdpPacket, UdpPacket};
+
+pub use pnet_packet::tcp::TcpFlags;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const UDP_HEADER_LEN: usize = 8;
+const TCP_HEADER_LEN: usize = 20;
+const ICMP_ECHO_HEADER_LEN: usize = 8;
+
+// ICMP and ICMPv6 echo types.
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+// ==== struct PacketBuilder ====
+
+/// Builds Ethernet frames layer by layer, ready for `Router::send_raw`.
+///
+/// Lengths and checksums are filled in when building, unless set otherwise
+/// on purpose to craft a malformed packet.
+///
+/// ```ignore
+/// let frame = PacketBuilder::ethernet(src_mac, MacAddr::broadcast())
+///     .ipv4(Ipv4Addr::new(10, 0, 1, 1), Ipv4Addr::new(10, 0, 1, 2))
+///     .udp(4000, 53)
+///     .payload(b"hello")
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct PacketBuilder {
+    src_mac: MacAddr,
+    dst_mac: MacAddr,
+    // Only used when there is no network layer.
+    ethertype: u16,
+    network: Option<Network>,
+    ttl: u8,
+    transport: Option<Transport>,
+    payload: Vec<u8>,
+    // Overrides the computed checksum of the innermost header.
+    checksum: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Network {
+    Ipv4(Ipv4Addr, Ipv4Addr),
+    Ipv6(Ipv6Addr, Ipv6Addr),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Transport {
+    Udp {
+        src_port: u16,
+        dst_port: u16,
+    },
+    Tcp {
+        src_port: u16,
+        dst_port: u16,
+        flags: u8,
+        seq: u32,
+        ack: u32,
+    },
+    Echo {
+        reply: bool,
+        id: u16,
+        seq: u16,
+    },
+}
+
+impl PacketBuilder {
+    pub fn ethernet(src_mac: MacAddr, dst_mac: MacAddr) -> Self {
+        Self {
+            src_mac,
+            dst_mac,
+            ethertype: 0,
+            network: None,
+            ttl: 64,
+            transport: None,
+            payload: vec![],
+            checksum: None,
+        }
+    }
+
+    /// EtherType of a frame carrying no IP packet.
+    pub fn ethertype(mut self, ethertype: u16) -> Self {
+        self.ethertype = ethertype;
+        self
+    }
+
+    pub fn ipv4(mut self, src: Ipv4Addr, dst: Ipv4Addr) -> Self {
+        self.network = Some(Network::Ipv4(src, dst));
+        self
+    }
+
+    pub fn ipv6(mut self, src: Ipv6Addr, dst: Ipv6Addr) -> Self {
+        self.network = Some(Network::Ipv6(src, dst));
+        self
+    }
+
+    /// TTL, or hop limit for IPv6. 64 by default.
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn udp(mut self, src_port: u16, dst_port: u16) -> Self {
+        self.transport = Some(Transport::Udp { src_port, dst_port });
+        self
+    }
+
+    /// TCP segment with the `TcpFlags` given, e.g. `TcpFlags::SYN`.
+    pub fn tcp(mut self, src_port: u16, dst_port: u16, flags: u8) -> Self {
+        self.transport = Some(Transport::Tcp {
+            src_port,
+            dst_port,
+            flags,
+            seq: 0,
+            ack: 0,
+        });
+        self
+    }
+
+    /// Sequence and acknowledgement numbers of the TCP segment.
+    pub fn tcp_seq(mut self, seq: u32, ack: u32) -> Self {
+        if let Some(Transport::Tcp {
+            seq: tcp_seq,
+            ack: tcp_ack,
+            ..
+        }) = &mut self.transport
+        {
+            *tcp_seq = seq;
+            *tcp_ack = ack;
+        }
+        self
+    }
+
+    /// ICMP echo request, ICMPv6 over IPv6.
+    pub fn echo_request(mut self, id: u16, seq: u16) -> Self {
+        self.transport = Some(Transport::Echo {
+            reply: false,
+            id,
+            seq,
+        });
+        self
+    }
+
+    /// ICMP echo reply, ICMPv6 over IPv6.
+    pub fn echo_reply(mut self, id: u16, seq: u16) -> Self {
+        self.transport = Some(Transport::Echo {
+            reply: true,
+            id,
+            seq,
+        });
+        self
+    }
+
+    pub fn payload(mut self, payload: &[u8]) -> Self {
+        self.payload = payload.to_vec();
+        self
+    }
+
+    /// Checksum of the innermost header, in place of the computed one.
+    pub fn checksum(mut self, checksum: u16) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    /// The frame, Ethernet header included.
+    pub fn build(&self) -> Vec<u8> {
+        let segment = match self.transport {
+            Some(transport) => self.build_transport(transport),
+            None => self.payload.clone(),
+        };
+        let (ethertype, packet) = match self.network {
+            Some(network) => self.build_network(network, &segment),
+            None => (EtherType(self.ethertype), segment),
+        };
+
+        let mut frame = vec![0u8; ETHERNET_HEADER_LEN + packet.len()];
+        let mut ethernet = MutableEthernetPacket::new(&mut frame)
+            .expect("buffer fits the ethernet header");
+        ethernet.set_destination(self.dst_mac);
+        ethernet.set_source(self.src_mac);
+        ethernet.set_ethertype(ethertype);
+        ethernet.set_payload(&packet);
+        frame
+    }
+
+    fn build_network(
+        &self,
+        network: Network,
+        segment: &[u8],
+    ) -> (EtherType, Vec<u8>) {
+        let protocol = match self.transport {
+            Some(Transport::Udp { .. }) => IpNextHeaderProtocols::Udp,
+            Some(Transport::Tcp { .. }) => IpNextHeaderProtocols::Tcp,
+            Some(Transport::Echo { .. }) => match network {
+                Network::Ipv4(..) => IpNextHeaderProtocols::Icmp,
+                Network::Ipv6(..) => IpNextHeaderProtocols::Icmpv6,
+            },
+            None => IpNextHeaderProtocol(0xfd),
+        };
+        // Only the innermost checksum is overridden.
+        let ip_checksum = match self.transport {
+            Some(_) => None,
+            None => self.checksum,
+        };
+
+        match network {
+            Network::Ipv4(src, dst) => {
+                let mut buf = vec![0u8; IPV4_HEADER_LEN + segment.len()];
+                let mut ip = MutableIpv4Packet::new(&mut buf)
+                    .expect("buffer fits the ipv4 header");
+                ip.set_version(4);
+                ip.set_header_length((IPV4_HEADER_LEN / 4) as u8);
+                ip.set_total_length((IPV4_HEADER_LEN + segment.len()) as u16);
+                ip.set_ttl(self.ttl);
+                ip.set_next_level_protocol(protocol);
+                ip.set_source(src);
+                ip.set_destination(dst);
+                ip.set_payload(segment);
+                let checksum = ip_checksum
+                    .unwrap_or_else(|| ipv4::checksum(&ip.to_immutable()));
+                ip.set_checksum(checksum);
+                (EtherTypes::Ipv4, buf)
+            }
+            Network::Ipv6(src, dst) => {
+                let mut buf = vec![0u8; IPV6_HEADER_LEN + segment.len()];
+                let mut ip = MutableIpv6Packet::new(&mut buf)
+                    .expect("buffer fits the ipv6 header");
+                ip.set_version(6);
+                ip.set_payload_length(segment.len() as u16);
+                ip.set_next_header(protocol);
+                ip.set_hop_limit(self.ttl);
+                ip.set_source(src);
+                ip.set_destination(dst);
+                ip.set_payload(segment);
+                (EtherTypes::Ipv6, buf)
+            }
+        }
+    }
+
+    fn build_transport(&self, transport: Transport) -> Vec<u8> {
+        match transport {
+            Transport::Udp { src_port, dst_port } => {
+                let mut buf = vec![0u8; UDP_HEADER_LEN + self.payload.len()];
+                let mut udp = MutableUdpPacket::new(&mut buf)
+                    .expect("buffer fits the udp header");
+                udp.set_source(src_port);
+                udp.set_destination(dst_port);
+                udp.set_length((UDP_HEADER_LEN + self.payload.len()) as u16);
+                udp.set_payload(&self.payload);
+                let checksum =
+                    self.checksum.unwrap_or_else(|| match self.network {
+                        Some(Network::Ipv4(src, dst)) => {
+                            udp::ipv4_checksum(&udp.to_immutable(), &src, &dst)
+                        }
+                        Some(Network::Ipv6(src, dst)) => {
+                            udp::ipv6_checksum(&udp.to_immutable(), &src, &dst)
+                        }
+                        None => 0,
+                    });
+                udp.set_checksum(checksum);
+                buf
+            }
+            Transport::Tcp {
+                src_port,
+                dst_port,
+                flags,
+                seq,
+                ack,
+            } => {
+                let mut buf = vec![0u8; TCP_HEADER_LEN + self.payload.len()];
+                let mut tcp = MutableTcpPacket::new(&mut buf)
+                    .expect("buffer fits the tcp header");
+                tcp.set_source(src_port);
+                tcp.set_destination(dst_port);
+                tcp.set_sequence(seq);
+                tcp.set_acknowledgement(ack);
+                tcp.set_data_offset((TCP_HEADER_LEN / 4) as u8);
+                tcp.set_flags(flags);
+                tcp.set_window(u16::MAX);
+                tcp.set_payload(&self.payload);
+                let checksum =
+                    self.checksum.unwrap_or_else(|| match self.network {
+                        Some(Network::Ipv4(src, dst)) => {
+                            tcp::ipv4_checksum(&tcp.to_immutable(), &src, &dst)
+                        }
+                        Some(Network::Ipv6(src, dst)) => {
+                            tcp::ipv6_checksum(&tcp.to_immutable(), &src, &dst)
+                        }
+                        None => 0,
+                    });
+                tcp.set_checksum(checksum);
+                buf
+            }
+            Transport::Echo { reply, id, seq } => {
+                let icmp_type = match (reply, self.network) {
+                    (false, Some(Network::Ipv6(..))) => ICMPV6_ECHO_REQUEST,
+                    (true, Some(Network::Ipv6(..))) => ICMPV6_ECHO_REPLY,
+                    (false, _) => ICMP_ECHO_REQUEST,
+                    (true, _) => ICMP_ECHO_REPLY,
+                };
+                // Type, code, checksum, identifier and sequence number.
+                let mut buf = Vec::with_capacity(
+                    ICMP_ECHO_HEADER_LEN + self.payload.len(),
+                );
+                buf.extend([icmp_type, 0, 0, 0]);
+                buf.extend(id.to_be_bytes());
+                buf.extend(seq.to_be_bytes());
+                buf.extend(&self.payload);
+
+                match self.network {
+                    Some(Network::Ipv6(src, dst)) => {
+                        let mut icmp = MutableIcmpv6Packet::new(&mut buf)
+                            .expect("buffer fits the icmpv6 header");
+                        let checksum = self.checksum.unwrap_or_else(|| {
+                            icmpv6::checksum(&icmp.to_immutable(), &src, &dst)
+                        });
+                        icmp.set_checksum(checksum);
+                    }
+                    _ => {
+                        let mut icmp = MutableIcmpPacket::new(&mut buf)
+                            .expect("buffer fits the icmp header");
+                        let checksum = self.checksum.unwrap_or_else(|| {
+                            icmp::checksum(&icmp.to_immutable())
+                        });
+                        icmp.set_checksum(checksum);
+                    }
+                }
+                buf
+            }
+        }
+    }
+}
+
+// ==== struct Packet ====
+
+/// The headers of a received frame netgen knows about, decoded.
+///
+/// Fields of layers the frame does not carry are None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    pub src_mac: MacAddr,
+    pub dst_mac: MacAddr,
+    pub ethertype: u16,
+    pub src_ip: Option<IpAddr>,
+    pub dst_ip: Option<IpAddr>,
+    // TTL, or hop limit for IPv6.
+    pub ttl: Option<u8>,
+    pub protocol: Option<u8>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+    pub tcp_flags: Option<u8>,
+    pub icmp_type: Option<u8>,
+    // Payload of the innermost layer decoded.
+    pub payload: Vec<u8>,
+    // The whole frame, as received.
+    pub frame: Vec<u8>,
+}
+
+impl Packet {
+    /// Decodes a frame, Ethernet header included. None if it is too short
+    /// to carry an Ethernet header.
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        let ethernet = EthernetPacket::new(frame)?;
+        let mut packet = Self {
+            src_mac: ethernet.get_source(),
+            dst_mac: ethernet.get_destination(),
+            ethertype: ethernet.get_ethertype().0,
+            src_ip: None,
+            dst_ip: None,
+            ttl: None,
+            protocol: None,
+            src_port: None,
+            dst_port: None,
+            tcp_flags: None,
+            icmp_type: None,
+            payload: ethernet.payload().to_vec(),
+            frame: frame.to_vec(),
+        };
+
+        let (protocol, segment) = match ethernet.get_ethertype() {
+            EtherTypes::Ipv4 => {
+                let Some(ip) = Ipv4Packet::new(ethernet.payload()) else {
+                    return Some(packet);
+                };
+                packet.src_ip = Some(ip.get_source().into());
+                packet.dst_ip = Some(ip.get_destination().into());
+                packet.ttl = Some(ip.get_ttl());
+                (ip.get_next_level_protocol(), ip.payload().to_vec())
+            }
+            EtherTypes::Ipv6 => {
+                let Some(ip) = Ipv6Packet::new(ethernet.payload()) else {
+                    return Some(packet);
+                };
+                packet.src_ip = Some(ip.get_source().into());
+                packet.dst_ip = Some(ip.get_destination().into());
+                packet.ttl = Some(ip.get_hop_limit());
+                (ip.get_next_header(), ip.payload().to_vec())
+            }
+            _ => return Some(packet),
+        };
+        packet.protocol = Some(protocol.0);
+        packet.payload.clone_from(&segment);
+
+        match protocol {
+            IpNextHeaderProtocols::Udp => {
+                if let Some(udp) = UdpPacket::new(&segment) {
+                    packet.src_port = Some(udp.get_source());
+                    packet.dst_port = Some(udp.get_destination());
+                    packet.payload = udp.payload().to_vec();
+                }
+            }
+            IpNextHeaderProtocols::Tcp => {
+                if let Some(tcp) = TcpPacket::new(&segment) {
+                    packet.src_port = Some(tcp.get_source());
+                    packet.dst_port = Some(tcp.get_destination());
+                    packet.tcp_flags = Some(tcp.get_flags());
+                    packet.payload = tcp.payload().to_vec();
+                }
+            }
+            IpNextHeaderProtocols::Icmp => {
+                if let Some(icmp) = IcmpPacket::new(&segment) {
+                    packet.icmp_type = Some(icmp.get_icmp_type().0);
+                    packet.payload = icmp.payload().to_vec();
+                }
+            }
+            IpNextHeaderProtocols::Icmpv6 => {
+                if let Some(icmp) = Icmpv6Packet::new(&segment) {
+                    packet.icmp_type = Some(icmp.get_icmpv6_type().0);
+                    packet.payload = icmp.payload().to_vec();
+                }
+            }
+            _ => {}
+        }
+        Some(packet)
+    }
+}
+
+// ==== struct Matcher ====
+
+/// Fields a received packet has to have, for `Router::expect_packet`.
+/// Fields left unset match anything.
+///
+/// ```ignore
+/// let dns_query = Matcher::new()
+///     .dst_ip(Ipv4Addr::new(10, 0, 1, 2).into())
+///     .protocol(Protocol::Udp)
+///     .dst_port(53);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Matcher {
+    src_mac: Option<MacAddr>,
+    dst_mac: Option<MacAddr>,
+    ethertype: Option<u16>,
+    src_ip: Option<IpAddr>,
+    dst_ip: Option<IpAddr>,
+    protocol: Option<Protocol>,
+    src_port: Option<u16>,
+    dst_port: Option<u16>,
+    // Flags which have to be set, others being ignored.
+    tcp_flags: Option<u8>,
+    icmp_type: Option<u8>,
+    // Bytes the innermost payload has to contain.
+    payload: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Udp,
+    Tcp,
+    // ICMP or ICMPv6.
+    Icmp,
+}
+
+impl Matcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn src_mac(mut self, mac: MacAddr) -> Self {
+        self.src_mac = Some(mac);
+        self
+    }
+
+    pub fn dst_mac(mut self, mac: MacAddr) -> Self {
+        self.dst_mac = Some(mac);
+        self
+    }
+
+    pub fn ethertype(mut self, ethertype: u16) -> Self {
+        self.ethertype = Some(ethertype);
+        self
+    }
+
+    pub fn src_ip(mut self, addr: IpAddr) -> Self {
+        self.src_ip = Some(addr);
+        self
+    }
+
+    pub fn dst_ip(mut self, addr: IpAddr) -> Self {
+        self.dst_ip = Some(addr);
+        self
+    }
+
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    pub fn src_port(mut self, port: u16) -> Self {
+        self.src_port = Some(port);
+        self
+    }
+
+    pub fn dst_port(mut self, port: u16) -> Self {
+        self.dst_port = Some(port);
+        self
+    }
+
+    /// TCP flags which have to be set, e.g. `TcpFlags::SYN | TcpFlags::ACK`.
+    pub fn tcp_flags(mut self, flags: u8) -> Self {
+        self.tcp_flags = Some(flags);
+        self
+    }
+
+    pub fn icmp_type(mut self, icmp_type: u8) -> Self {
+        self.icmp_type = Some(icmp_type);
+        self
+    }
+
+    /// Bytes the innermost payload has to contain.
+    pub fn payload_contains(mut self, bytes: &[u8]) -> Self {
+        self.payload = Some(bytes.to_vec());
+        self
+    }
+
+    pub fn matches(&self, packet: &Packet) -> bool {
+        fn field<T: PartialEq>(expected: Option<T>, actual: T) -> bool {
+            expected.is_none_or(|expected| expected == actual)
+        }
+
+        let protocol = match self.protocol {
+            None => true,
+            Some(Protocol::Udp) => packet.protocol == Some(17),
+            Some(Protocol::Tcp) => packet.protocol == Some(6),
+            Some(Protocol::Icmp) => matches!(packet.protocol, Some(1 | 58)),
+        };
+        let tcp_flags = self.tcp_flags.is_none_or(|flags| {
+            packet
+                .tcp_flags
+                .is_some_and(|actual| actual & flags == flags)
+        });
+        let payload = self.payload.as_ref().is_none_or(|bytes| {
+            bytes.is_empty()
+                || packet
+                    .payload
+                    .windows(bytes.len())
+                    .any(|window| window == bytes.as_slice())
+        });
+
+        field(self.src_mac, packet.src_mac)
+            && field(self.dst_mac, packet.dst_mac)
+            && field(self.ethertype, packet.ethertype)
+            && field(self.src_ip.map(Some), packet.src_ip)
+            && field(self.dst_ip.map(Some), packet.dst_ip)
+            && protocol
+            && field(self.src_port.map(Some), packet.src_port)
+            && field(self.dst_port.map(Some), packet.dst_port)
+            && tcp_flags
+            && field(self.icmp_type.map(Some), packet.icmp_type)
+            && payload
+    }
+}
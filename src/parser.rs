@@ -1,11 +1,43 @@
 use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::ops::RangeInclusive;
+use std::time::Duration;
 
 use ipnetwork::IpNetwork;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::{Marker, ScanError};
 use yaml_rust2::yaml::{Hash, Yaml};
 
 use crate::NetResult;
+use crate::dns::DnsConfig;
 use crate::error::{ConfigError, NetError, YamlPath};
-use crate::node::{Interface, Kind, Router, Switch, Volume};
+use crate::expose::GrpcProxy;
+use crate::fib::StaticRoute;
+use crate::flow::{FlowExport, FlowProtocol};
+use crate::frr::{self, Frr};
+use crate::injector::{
+    InjectorPeer, RouteInjector, UpdateSource, check_prefixes,
+};
+use crate::limits::{IoClass, ProcessLimits};
+use crate::lldp::LldpAgent;
+use crate::nat::{NatKind, NatRule};
+use crate::netem::{FlowImpairment, FlowMatch, Impairment};
+use crate::node::{
+    AddressFamily, Families, FileDrop, FileSource, Interface, Kind, Police,
+    PortForward, PortOptions, Router, Switch, Volume,
+};
+use crate::notify::{NotifyAction, NotifyRule, Trigger};
+use crate::ntp::{NtpRole, NtpService};
+use crate::pki::{DEFAULT_VALIDITY, Pki};
+use crate::pool::{AddressPool, POOL_ADDR_PREFIX};
+use crate::probe::{
+    DEFAULT_INTERVAL, DEFAULT_MULTIPLIER, DEFAULT_PORT, ProbeSession,
+};
+use crate::rule::PolicyRule;
+use crate::service::{
+    DEFAULT_READY_TIMEOUT, ReadyCheck, ReadyProbe, RestartPolicy, Service,
+};
+use crate::ssh::SshServer;
 
 // ==== trait FromYamlConfig ====
 
@@ -17,6 +49,478 @@ pub(crate) trait FromYamlConfig: Sized {
     ) -> NetResult<Self>;
 }
 
+// ==== struct SourceMap ====
+
+/// Where the keys and the sequence entries of YAML documents start in
+/// their source, for errors to point at the line at fault. Documents
+/// rendered from a template are mapped back to the template, see `new`.
+#[derive(Default)]
+pub(crate) struct SourceMap {
+    // Per document, by path from its root, sequence entries being keyed by
+    // their index.
+    docs: Vec<BTreeMap<Vec<String>, Marker>>,
+    // Per line of the rendered documents, the line of the template it comes
+    // from and the column of the first expression of that line, if any.
+    origins: Vec<(usize, Option<usize>)>,
+    // Whether rendering changed anything.
+    templated: bool,
+    // Mappings and sequences the parser is within, with the key of the
+    // value being parsed or the index of the entry.
+    frames: Vec<Frame>,
+}
+
+enum Frame {
+    Mapping(Option<String>),
+    Sequence(usize),
+}
+
+impl SourceMap {
+    /// Maps `rendered`, rendered from the template `source`, `origins`
+    /// holding the line of `source` every line of `rendered` comes from, as
+    /// `template::render_mapped` returns them.
+    ///
+    /// Lines map back to the template line they come from, repeated ones to
+    /// their loop body. Columns are kept up to the first expression of the
+    /// template line, what comes after it having moved along with the
+    /// rendered values.
+    pub(crate) fn new(rendered: &str, source: &str, origins: &[usize]) -> Self {
+        let source_lines: Vec<&str> = source.lines().collect();
+        let mut source_map = Self {
+            origins: origins
+                .iter()
+                .map(|&line| {
+                    let expr = source_lines
+                        .get(line.wrapping_sub(1))
+                        .and_then(|text| text.find("{{"))
+                        .map(|idx| idx + 1);
+                    (line, expr)
+                })
+                .collect(),
+            templated: rendered != source,
+            ..Self::default()
+        };
+        // Syntax errors are reported by the loader.
+        let _ = Parser::new_from_str(rendered).load(&mut source_map, true);
+        source_map
+    }
+
+    /// `err`, from loading the rendered documents, along with the line of
+    /// the template it comes from if the file was templated.
+    pub(crate) fn locate_syntax(&self, err: ScanError) -> ConfigError {
+        if !self.templated {
+            return ConfigError::YamlSyntax(err);
+        }
+        let (line, _) = self.origin((err.marker().line(), 1));
+        ConfigError::RenderedYamlSyntax { scan: err, line }
+    }
+
+    // Line and column of the template for a line and column of the
+    // rendered documents.
+    fn origin(&self, (line, column): (usize, usize)) -> (usize, usize) {
+        match self.origins.get(line.wrapping_sub(1)) {
+            Some((line, Some(expr))) => (*line, column.min(*expr)),
+            Some((line, None)) => (*line, column),
+            None => (line, column),
+        }
+    }
+
+    /// Sets the location of the path of `err`, if it has one, to where
+    /// its deepest key found in document `doc` is.
+    pub(crate) fn locate(&self, doc: usize, mut err: NetError) -> NetError {
+        let NetError::ConfigError(config_err) = &mut err else {
+            return err;
+        };
+        let (Some(path), Some(marks)) =
+            (config_err.path_mut(), self.docs.get(doc))
+        else {
+            return err;
+        };
+        let keys: Vec<String> =
+            path.keys().into_iter().map(str::to_string).collect();
+        if let Some(location) = position(marks, &keys) {
+            let (line, column) = self.origin(location);
+            path.set_location(line, column);
+        }
+        err
+    }
+
+    /// Line and column of the deepest of `keys` found in document `doc`.
+    pub(crate) fn position(
+        &self,
+        doc: usize,
+        keys: &[String],
+    ) -> Option<(usize, usize)> {
+        position(self.docs.get(doc)?, keys).map(|at| self.origin(at))
+    }
+
+    fn path(&self) -> Vec<String> {
+        self.frames
+            .iter()
+            .filter_map(|frame| match frame {
+                Frame::Mapping(key) => key.clone(),
+                Frame::Sequence(index) => Some(index.to_string()),
+            })
+            .collect()
+    }
+
+    fn record(&mut self, mark: Marker) {
+        let path = self.path();
+        if self.docs.is_empty() {
+            self.docs.push(BTreeMap::new());
+        }
+        if let Some(marks) = self.docs.last_mut() {
+            marks.entry(path).or_insert(mark);
+        }
+    }
+
+    fn node_start(&mut self, mark: Marker) {
+        if let Some(Frame::Sequence(_)) = self.frames.last() {
+            self.record(mark);
+        }
+    }
+
+    fn node_end(&mut self) {
+        match self.frames.last_mut() {
+            Some(Frame::Mapping(key)) => {
+                // A key which is not a scalar ends without being recorded.
+                *key = match key {
+                    Some(_) => None,
+                    None => Some(String::new()),
+                }
+            }
+            Some(Frame::Sequence(index)) => *index += 1,
+            None => {}
+        }
+    }
+}
+
+fn position(
+    marks: &BTreeMap<Vec<String>, Marker>,
+    keys: &[String],
+) -> Option<(usize, usize)> {
+    let mark = (1..=keys.len())
+        .rev()
+        .find_map(|depth| marks.get(&keys[..depth]))?;
+    // Columns are counted from 0 by the scanner.
+    Some((mark.line(), mark.col() + 1))
+}
+
+impl MarkedEventReceiver for SourceMap {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::DocumentStart => self.docs.push(BTreeMap::new()),
+            Event::MappingStart(..) => {
+                self.node_start(mark);
+                self.frames.push(Frame::Mapping(None));
+            }
+            Event::SequenceStart(..) => {
+                self.node_start(mark);
+                self.frames.push(Frame::Sequence(0));
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.frames.pop();
+                self.node_end();
+            }
+            Event::Scalar(value, ..) => {
+                if let Some(Frame::Mapping(key @ None)) = self.frames.last_mut()
+                {
+                    *key = Some(value);
+                    self.record(mark);
+                    return;
+                }
+                self.node_start(mark);
+                self.node_end();
+            }
+            Event::Alias(_) => {
+                self.node_start(mark);
+                self.node_end();
+            }
+            _ => {}
+        }
+    }
+}
+
+// ==== Lint ====
+
+// Keys the parser reads, any other being reported.
+const TOPOLOGY_KEYS: &[&str] = &[
+    "session",
+    "loopbacks",
+    "veth-prefix",
+    "iface-naming",
+    "ssh",
+    "lldp",
+    "pki",
+    "ntp",
+    "grpc",
+    "limits",
+    "pools",
+    "probes",
+    "notify",
+    "invariants",
+    "profiles",
+    "kinds",
+    "routers",
+    "switches",
+    "links",
+];
+const ROUTER_KEYS: &[&str] = &[
+    "kind",
+    "interfaces",
+    "volumes",
+    "persist",
+    "files",
+    "expose",
+    "scripts",
+    "ssh",
+    "dns",
+    "frr",
+    "ntp",
+    "lldp",
+    "metadata",
+    "dut",
+    "timezone",
+    "locale",
+    "env",
+    "injector",
+    "services",
+    "static-fib",
+    "rules",
+    "nat",
+    "families",
+    "dummies",
+    "limits",
+    "profiles",
+];
+const SWITCH_KEYS: &[&str] = &[
+    "interfaces",
+    "metadata",
+    "flow-export",
+    "ports",
+    "limits",
+    "profiles",
+];
+const LINK_KEYS: &[&str] = &[
+    "src-device",
+    "src-iface",
+    "dst-device",
+    "dst-iface",
+    "profile",
+    "delay",
+    "jitter",
+    "loss",
+    "rate",
+    "bandwidth",
+    "mtu",
+    "flows",
+    "enabled",
+    "expect",
+    "metadata",
+    "profiles",
+];
+
+/// Problems of a topology document which do not keep it from being
+/// brought up: keys netgen does not know, which it ignores, and interface
+/// addresses which do not parse, which are dropped from `doc`. Each comes
+/// with the path to what is at fault, sequence entries by index.
+pub(crate) fn lint(doc: &mut Yaml) -> Vec<(Vec<String>, String)> {
+    let mut issues = vec![];
+    let Yaml::Hash(config) = doc else {
+        return issues;
+    };
+    unknown_keys(config, TOPOLOGY_KEYS, &[], &mut issues);
+
+    for (kind, known) in [("routers", ROUTER_KEYS), ("switches", SWITCH_KEYS)] {
+        let Some(Yaml::Hash(nodes)) =
+            config.get_mut(&Yaml::String(kind.to_string()))
+        else {
+            continue;
+        };
+        for (name, node_config) in nodes.iter_mut() {
+            let (Some(name), Yaml::Hash(node_config)) =
+                (name.as_str(), node_config)
+            else {
+                continue;
+            };
+            let node_path = [kind.to_string(), name.to_string()];
+            unknown_keys(node_config, known, &node_path, &mut issues);
+
+            let Some(Yaml::Hash(ifaces)) =
+                node_config.get_mut(&Yaml::String("interfaces".to_string()))
+            else {
+                continue;
+            };
+            for (iface, addresses) in ifaces.iter_mut() {
+                let (Some(iface), Yaml::Array(addresses)) =
+                    (iface.as_str(), addresses)
+                else {
+                    continue;
+                };
+                let mut index = 0;
+                addresses.retain(|address| {
+                    index += 1;
+                    // Those which are not strings fail parsing.
+                    let Some(address) = address.as_str() else {
+                        return true;
+                    };
+                    if address.starts_with(POOL_ADDR_PREFIX)
+                        || address.parse::<IpNetwork>().is_ok()
+                    {
+                        return true;
+                    }
+                    let path = [
+                        kind.to_string(),
+                        name.to_string(),
+                        "interfaces".to_string(),
+                        iface.to_string(),
+                        (index - 1).to_string(),
+                    ];
+                    issues.push((
+                        path.to_vec(),
+                        format!(
+                            "{}: invalid address '{address}', ignored",
+                            path[..4].join(".")
+                        ),
+                    ));
+                    false
+                });
+            }
+        }
+    }
+
+    if let Some(Yaml::Array(links)) =
+        config.get(&Yaml::String("links".to_string()))
+    {
+        for (index, link_config) in links.iter().enumerate() {
+            if let Yaml::Hash(link_config) = link_config {
+                let path = ["links".to_string(), index.to_string()];
+                unknown_keys(link_config, LINK_KEYS, &path, &mut issues);
+            }
+        }
+    }
+    issues
+}
+
+fn unknown_keys(
+    config: &Hash,
+    known: &[&str],
+    path: &[String],
+    issues: &mut Vec<(Vec<String>, String)>,
+) {
+    for key in config.keys().filter_map(Yaml::as_str) {
+        if known.contains(&key) {
+            continue;
+        }
+        let mut key_path = path.to_vec();
+        key_path.push(key.to_string());
+        let issue = format!("{}: unknown key, ignored", key_path.join("."));
+        issues.push((key_path, issue));
+    }
+}
+
+// ==== Format ====
+
+/// `doc` in canonical form, for `netgen fmt`: keys in the order of the
+/// tables above, unknown ones last and sorted, routers, switches and kinds
+/// sorted by name, and interface addresses as the parser reads them back,
+/// e.g. `2001:DB8:0::1/64` as `2001:db8::1/64`.
+///
+/// Interfaces, links and lists keep their order, which the interfaces
+/// created depend on.
+pub(crate) fn canonicalize(doc: &Yaml) -> Yaml {
+    let Yaml::Hash(config) = doc else {
+        return doc.clone();
+    };
+    let mut config = order_keys(config, TOPOLOGY_KEYS);
+    for (kind, known) in [
+        ("routers", ROUTER_KEYS),
+        ("switches", SWITCH_KEYS),
+        ("kinds", ROUTER_KEYS),
+    ] {
+        let Some(Yaml::Hash(nodes)) =
+            config.get_mut(&Yaml::String(kind.to_string()))
+        else {
+            continue;
+        };
+        let mut sorted: Vec<(Yaml, Yaml)> = nodes
+            .iter()
+            .map(|(name, node_config)| {
+                let node_config = match node_config {
+                    Yaml::Hash(node_config) => {
+                        Yaml::Hash(canonical_node(node_config, known))
+                    }
+                    other => other.clone(),
+                };
+                (name.clone(), node_config)
+            })
+            .collect();
+        sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+        *nodes = sorted.into_iter().collect();
+    }
+    if let Some(Yaml::Array(links)) =
+        config.get_mut(&Yaml::String("links".to_string()))
+    {
+        for link in links.iter_mut() {
+            if let Yaml::Hash(link_config) = link {
+                *link = Yaml::Hash(order_keys(link_config, LINK_KEYS));
+            }
+        }
+    }
+    Yaml::Hash(config)
+}
+
+// A node with its keys ordered and the addresses of its interfaces
+// normalized.
+fn canonical_node(config: &Hash, known: &[&str]) -> Hash {
+    let mut config = order_keys(config, known);
+    if let Some(Yaml::Hash(ifaces)) =
+        config.get_mut(&Yaml::String("interfaces".to_string()))
+    {
+        for addresses in ifaces.values_mut() {
+            let Yaml::Array(addresses) = addresses else {
+                continue;
+            };
+            for address in addresses.iter_mut() {
+                if let Some(normalized) =
+                    address.as_str().and_then(normalize_address)
+                {
+                    *address = Yaml::String(normalized);
+                }
+            }
+        }
+    }
+    config
+}
+
+// The address as displayed once parsed, addresses from pools and those
+// which do not parse being left alone.
+fn normalize_address(address: &str) -> Option<String> {
+    if address.starts_with(POOL_ADDR_PREFIX) {
+        return None;
+    }
+    match address.contains('/') {
+        true => address.parse::<IpNetwork>().ok().map(|net| net.to_string()),
+        false => address.parse::<IpAddr>().ok().map(|addr| addr.to_string()),
+    }
+}
+
+// `config` with the `known` keys first, in that order, then the others
+// sorted.
+fn order_keys(config: &Hash, known: &[&str]) -> Hash {
+    let mut entries: Vec<(&Yaml, &Yaml)> = config.iter().collect();
+    entries.sort_by_key(|(key, _)| {
+        let rank = key
+            .as_str()
+            .and_then(|key| known.iter().position(|name| *name == key))
+            .unwrap_or(known.len());
+        (rank, *key)
+    });
+    entries
+        .into_iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
 // ==== impl Router ====
 
 impl FromYamlConfig for Router {
@@ -102,6 +606,40 @@ impl FromYamlConfig for Router {
             }
         }
 
+        // Dummy interfaces, e.g. to stress daemons with many interfaces.
+        if let Some(dummies) =
+            router_config.get(&Yaml::String(String::from("dummies")))
+            && !dummies.is_null()
+        {
+            let dummies_path =
+                YamlPath::new().key("routers").key(name).key("dummies");
+            for iface in parse_dummies(dummies, &dummies_path)? {
+                if router
+                    .interfaces
+                    .iter()
+                    .any(|other| other.name == iface.name)
+                {
+                    return Err(ConfigError::IncorrectType {
+                        path: dummies_path.clone().key("name").unknown(),
+                        expected: "names not used by other interfaces"
+                            .to_string(),
+                    }
+                    .into());
+                }
+                router.interfaces.push(iface);
+            }
+        }
+
+        // Address families of the interfaces which do not set theirs.
+        if let Some(families) =
+            router_config.get(&Yaml::String(String::from("families")))
+        {
+            router.families = parse_families(
+                families,
+                &YamlPath::new().key("routers").key(name).key("families"),
+            )?;
+        }
+
         // Router Volume Configurations.
         match router_config.get(&Yaml::String(String::from("volumes"))) {
             Some(Yaml::Array(volumes_configs)) => {
@@ -128,6 +666,101 @@ impl FromYamlConfig for Router {
             }
         }
 
+        // Directories kept across restarts of the router.
+        if let Some(persist) =
+            router_config.get(&Yaml::String(String::from("persist")))
+        {
+            let yaml_path = YamlPath::new().key("routers").key(name);
+            let dirs = match persist {
+                Yaml::Array(dirs) => dirs.as_slice(),
+                Yaml::Null => &[],
+                _ => {
+                    return Err(ConfigError::IncorrectType {
+                        path: yaml_path.key("persist").unknown(),
+                        expected: "array".to_string(),
+                    }
+                    .into());
+                }
+            };
+            for dir in dirs {
+                match dir.as_str() {
+                    Some(dir) if dir.starts_with('/') && dir != "/" => router
+                        .persist
+                        .push(dir.trim_end_matches('/').to_string()),
+                    _ => {
+                        return Err(ConfigError::IncorrectType {
+                            path: yaml_path.clone().key("persist").unknown(),
+                            expected: "absolute directory path".to_string(),
+                        }
+                        .into());
+                    }
+                }
+            }
+        }
+
+        // Router File Configurations.
+        match router_config.get(&Yaml::String(String::from("files"))) {
+            Some(Yaml::Hash(file_configs)) => {
+                for (dst, file_config) in file_configs {
+                    let Yaml::String(dst) = dst else {
+                        return Err(ConfigError::IncorrectType {
+                            path: YamlPath::new()
+                                .key("routers")
+                                .key(name)
+                                .key("files")
+                                .unknown(),
+                            expected: "string".to_string(),
+                        }
+                        .into());
+                    };
+                    let file = FileDrop::from_yaml_config(
+                        dst,
+                        file_config,
+                        BTreeMap::from([("device_name", name)]),
+                    )?;
+                    router.files.push(file);
+                }
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: YamlPath::new()
+                        .key("routers")
+                        .key(name)
+                        .key("files")
+                        .unknown(),
+                    expected: "hash".to_string(),
+                }
+                .into());
+            }
+        }
+
+        // Router Exposed Ports Configurations.
+        match router_config.get(&Yaml::String(String::from("expose"))) {
+            Some(Yaml::Array(expose_configs)) => {
+                for expose_config in expose_configs {
+                    let forward = PortForward::from_yaml_config(
+                        name,
+                        expose_config,
+                        BTreeMap::new(),
+                    )?;
+                    router.expose.push(forward);
+                }
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: YamlPath::new()
+                        .key("routers")
+                        .key(name)
+                        .key("expose")
+                        .unknown(),
+                    expected: "array".to_string(),
+                }
+                .into());
+            }
+        }
+
         // Router Scripts Configurations.
         match router_config.get(&Yaml::String(String::from("scripts"))) {
             Some(Yaml::Array(script_configs)) => {
@@ -151,108 +784,1061 @@ impl FromYamlConfig for Router {
                 .into());
             }
         }
-        Ok(router)
-    }
-}
 
-// ==== impl Switch ====
+        // Router SSH server configuration.
+        match router_config.get(&Yaml::String(String::from("ssh"))) {
+            Some(Yaml::Null) | None => {}
+            Some(ssh_config) => {
+                router.ssh = Some(Box::new(SshServer::from_yaml_config(
+                    name,
+                    ssh_config,
+                    BTreeMap::from([("device_name", name)]),
+                )?));
+            }
+        }
 
-impl FromYamlConfig for Switch {
-    /// Handles config that is in the form of:
-    ///
-    /// ```yaml
-    /// sw1:
-    ///   interfaces:
-    ///     eth0:
-    ///       ipv4:
-    ///         - 192.168.100.20/24
-    ///       ipv6:
-    ///         - 2001:db8::/96
-    /// ```
-    /// converted into a yaml_rust2::yaml::Hash;
-    fn from_yaml_config(
-        switch_name: &str,
-        switch_config: &Yaml,
-        _switch_ctx: BTreeMap<&str, &str>,
-    ) -> NetResult<Self> {
-        let switch_config = if let Yaml::Hash(switch_config) = switch_config {
-            switch_config
-        } else {
-            return Err(ConfigError::IncorrectType {
-                path: YamlPath::new()
-                    .key("switches")
-                    .key(switch_name)
-                    .unknown(),
-                expected: "hash".to_string(),
+        // Router resolver configuration, written after the files so that
+        // it takes precedence over a resolv.conf dropped there.
+        match router_config.get(&Yaml::String(String::from("dns"))) {
+            Some(Yaml::Null) | None => {}
+            Some(dns_config) => {
+                let dns = DnsConfig::from_yaml_config(
+                    name,
+                    dns_config,
+                    BTreeMap::new(),
+                )?;
+                router.files.push(dns.resolv_conf());
             }
-            .into());
-        };
+        }
 
-        let mut switch = Self::new(switch_name);
+        // Router FRR configuration, its files written the same way.
+        match router_config.get(&Yaml::String(String::from("frr"))) {
+            Some(Yaml::Null) | None => {}
+            Some(frr_config) => {
+                let frr =
+                    Frr::from_yaml_config(name, frr_config, BTreeMap::new())?;
+                router.files.extend(frr.files());
+                router.frr = Some(Box::new(frr));
+            }
+        }
 
-        match switch_config.get(&Yaml::String(String::from("interfaces"))) {
-            Some(Yaml::Hash(interfaces_config)) => {
-                for (iface_name, iface_config) in interfaces_config {
-                    match iface_name {
-                        Yaml::String(iface_name) => {
-                            let interface = Interface::from_yaml_config(
-                                iface_name,
-                                iface_config,
-                                BTreeMap::from([
-                                    ("device_name", switch_name),
-                                    ("device_type", "switch"),
-                                ]),
-                            )?;
-                            switch.interfaces.push(interface);
-                        }
-                        _ => {
-                            return Err(ConfigError::IncorrectType {
-                                path: YamlPath::new()
-                                    .key("switches")
-                                    .key(switch_name)
-                                    .key("interfaces")
-                                    .unknown(),
-                                expected: "string".to_string(),
-                            }
-                            .into());
-                        }
-                    }
+        // Routers run the topology's time service unless opting out.
+        match router_config.get(&Yaml::String(String::from("ntp"))) {
+            Some(Yaml::Null) | None => {}
+            Some(Yaml::Boolean(false)) => router.ntp = Some(NtpRole::Disabled),
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: YamlPath::new()
+                        .key("routers")
+                        .key(name)
+                        .key("ntp")
+                        .unknown(),
+                    expected: "false".to_string(),
                 }
-                Ok(switch)
+                .into());
             }
-            _ => Err(ConfigError::IncorrectType {
-                path: YamlPath::new()
-                    .key("switches")
-                    .key(switch_name)
-                    .unknown(),
-                expected: "hash".to_string(),
+        }
+
+        // Router LLDP agent configuration.
+        match router_config.get(&Yaml::String(String::from("lldp"))) {
+            Some(Yaml::Null) | None => {}
+            Some(lldp_config) => {
+                router.lldp = Some(LldpAgent::from_yaml_config(
+                    name,
+                    lldp_config,
+                    BTreeMap::from([("device_name", name)]),
+                )?);
             }
-            .into()),
         }
-    }
-}
 
-// ==== impl Interface ====
+        router.metadata = get_metadata_field(
+            router_config,
+            &YamlPath::new().key("routers").key(name),
+        )?;
 
-impl FromYamlConfig for Interface {
-    fn from_yaml_config(
-        iface_name: &str,
-        iface_config: &Yaml,
-        iface_ctx: BTreeMap<&str, &str>,
-    ) -> NetResult<Self> {
-        let yaml_path = match (
-            iface_ctx.get("device_name"),
-            iface_ctx.get("device_type"),
-        ) {
-            (Some(device_name), Some(device_type)) => {
-                if *device_type == "switch" {
-                    YamlPath::new()
-                        .key("switches")
-                        .key(*device_name)
-                        .key("interfaces")
-                        .key(iface_name)
-                } else if *device_type == "router" {
-                    YamlPath::new()
+        // Devices under test, which measurements and reports focus on.
+        match router_config.get(&Yaml::String(String::from("dut"))) {
+            Some(Yaml::Boolean(dut)) => router.dut = *dut,
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: YamlPath::new()
+                        .key("routers")
+                        .key(name)
+                        .key("dut")
+                        .unknown(),
+                    expected: "bool".to_string(),
+                }
+                .into());
+            }
+        }
+
+        // Environment of the processes started in the router, `timezone`
+        // and `locale` being short for the usual variables.
+        let router_path = YamlPath::new().key("routers").key(name);
+        for (field, vars) in
+            [("timezone", &["TZ"][..]), ("locale", &["LANG", "LC_ALL"])]
+        {
+            match router_config.get(&Yaml::String(field.to_string())) {
+                Some(Yaml::String(value)) => {
+                    for var in vars {
+                        router.env.insert(var.to_string(), value.clone());
+                    }
+                }
+                Some(Yaml::Null) | None => {}
+                Some(_) => {
+                    return Err(ConfigError::IncorrectType {
+                        path: router_path.clone().key(field).unknown(),
+                        expected: "string".to_string(),
+                    }
+                    .into());
+                }
+            }
+        }
+        router.env.extend(get_scalars_field(
+            router_config,
+            "env",
+            &router_path,
+        )?);
+
+        // Router route injector configuration.
+        match router_config.get(&Yaml::String(String::from("injector"))) {
+            Some(Yaml::Null) | None => {}
+            Some(injector_config) => {
+                router.injector =
+                    Some(Box::new(RouteInjector::from_yaml_config(
+                        name,
+                        injector_config,
+                        BTreeMap::new(),
+                    )?));
+            }
+        }
+
+        // Long-running processes beside the router's daemons.
+        let services_path = router_path.clone().key("services");
+        match router_config.get(&Yaml::String(String::from("services"))) {
+            Some(Yaml::Null) | None => {}
+            Some(Yaml::Hash(services)) => {
+                for (service_name, service_config) in services {
+                    let Yaml::String(service_name) = service_name else {
+                        return Err(ConfigError::IncorrectType {
+                            path: services_path.clone().unknown(),
+                            expected: "service name".to_string(),
+                        }
+                        .into());
+                    };
+                    router.services.push(parse_service(
+                        service_name,
+                        service_config,
+                        &services_path.clone().key(service_name),
+                    )?);
+                }
+            }
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: services_path.clone().unknown(),
+                    expected: "hash of services".to_string(),
+                }
+                .into());
+            }
+        }
+
+        // Routes programmed without any routing daemon.
+        let fib_path = router_path.clone().key("static-fib");
+        match router_config.get(&Yaml::String(String::from("static-fib"))) {
+            Some(Yaml::Null) | None => {}
+            Some(Yaml::Array(routes)) => {
+                for route in routes {
+                    let Yaml::Hash(route) = route else {
+                        return Err(ConfigError::IncorrectType {
+                            path: fib_path.clone().unknown(),
+                            expected: "hash".to_string(),
+                        }
+                        .into());
+                    };
+                    router.static_fib.push(StaticRoute {
+                        prefix: get_network_field(route, "prefix", &fib_path)?,
+                        via: get_addr_field(route, "via", &fib_path)?,
+                        table: get_table_field(route, "table", &fib_path)?,
+                    });
+                }
+            }
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: fib_path.clone().unknown(),
+                    expected: "list of routes".to_string(),
+                }
+                .into());
+            }
+        }
+
+        // Policy routing rules, e.g. for PBR or VRF-lite.
+        let rules_path = router_path.clone().key("rules");
+        match router_config.get(&Yaml::String(String::from("rules"))) {
+            Some(Yaml::Null) | None => {}
+            Some(Yaml::Array(rules)) => {
+                for rule in rules {
+                    router.rules.push(parse_policy_rule(rule, &rules_path)?);
+                }
+            }
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: rules_path.clone().unknown(),
+                    expected: "list of rules".to_string(),
+                }
+                .into());
+            }
+        }
+
+        // Address translation done by the router, e.g. as a middlebox.
+        let nat_path = router_path.clone().key("nat");
+        match router_config.get(&Yaml::String(String::from("nat"))) {
+            Some(Yaml::Null) | None => {}
+            Some(Yaml::Array(rules)) => {
+                for rule in rules {
+                    router.nat.push(parse_nat_rule(rule, &nat_path)?);
+                }
+            }
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: nat_path.clone().unknown(),
+                    expected: "list of rules".to_string(),
+                }
+                .into());
+            }
+        }
+
+        // Limits of the processes started in the router.
+        if let Some(limits) =
+            router_config.get(&Yaml::String(String::from("limits")))
+        {
+            router.limits =
+                parse_limits(limits, &router_path.clone().key("limits"))?;
+        }
+        Ok(router)
+    }
+}
+
+/// Expands the `dummies` of a router into its dummy interfaces, in the
+/// form of:
+///
+/// ```yaml
+/// dummies:
+///   count: 500
+///   name: dum       # dum0 to dum499, "dummy" by default
+///   pool: loopbacks # where each gets an address from, if any
+/// ```
+fn parse_dummies(
+    config: &Yaml,
+    dummies_path: &YamlPath,
+) -> NetResult<Vec<Interface>> {
+    let Yaml::Hash(config) = config else {
+        return Err(ConfigError::IncorrectType {
+            path: dummies_path.clone().unknown(),
+            expected: "hash".to_string(),
+        }
+        .into());
+    };
+    for key in config.keys() {
+        if !matches!(key.as_str(), Some("count" | "name" | "pool")) {
+            return Err(ConfigError::IncorrectType {
+                path: dummies_path.clone().unknown(),
+                expected: "count, name, pool".to_string(),
+            }
+            .into());
+        }
+    }
+
+    let count = get_u32_field(config, "count", dummies_path)?;
+    if count == 0 {
+        return Err(ConfigError::IncorrectType {
+            path: dummies_path.clone().key("count").unknown(),
+            expected: "positive integer".to_string(),
+        }
+        .into());
+    }
+    let optional_string =
+        |field: &str| match config.get(&Yaml::String(field.to_string())) {
+            Some(Yaml::String(value)) => Ok(Some(value.clone())),
+            Some(Yaml::Null) | None => Ok(None),
+            Some(_) => Err(ConfigError::IncorrectType {
+                path: dummies_path.clone().key(field).unknown(),
+                expected: "string".to_string(),
+            }),
+        };
+    let name = optional_string("name")?.unwrap_or("dummy".to_string());
+    let pool = optional_string("pool")?;
+
+    Ok((0..count)
+        .map(|index| {
+            let mut iface = Interface::new(format!("{name}{index}"));
+            iface.dummy = true;
+            iface.pools.extend(pool.clone());
+            iface
+        })
+        .collect())
+}
+
+/// Handles a rule of a router's `rules` list, in the form of:
+///
+/// ```yaml
+/// - priority: 100
+///   from: 10.0.1.0/24
+///   fwmark: 1
+///   iif: eth0
+///   table: 100
+/// ```
+/// Parses the `limits` of a node's processes, in the form of:
+///
+/// ```yaml
+/// limits:
+///   nofile: 65536        # RLIMIT_NOFILE of its daemons
+///   oom-score-adj: -500  # spared by the OOM killer before others
+///   nice: 10             # CPU niceness, from -20 to 19
+///   ionice: idle         # or best-effort[:level], realtime[:level]
+/// ```
+pub(crate) fn parse_limits(
+    config: &Yaml,
+    limits_path: &YamlPath,
+) -> NetResult<ProcessLimits> {
+    const KEYS: &[&str] = &["nofile", "oom-score-adj", "nice", "ionice"];
+    let config = match config {
+        Yaml::Null => return Ok(ProcessLimits::default()),
+        Yaml::Hash(config) => config,
+        _ => {
+            return Err(ConfigError::IncorrectType {
+                path: limits_path.clone().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        }
+    };
+    for key in config.keys() {
+        if !key.as_str().is_some_and(|key| KEYS.contains(&key)) {
+            return Err(ConfigError::IncorrectType {
+                path: limits_path.clone().unknown(),
+                expected: KEYS.join(", "),
+            }
+            .into());
+        }
+    }
+    let integer =
+        |field: &str, range: RangeInclusive<i64>, expected: &str| match config
+            .get(&Yaml::String(field.to_string()))
+        {
+            Some(Yaml::Integer(value)) if range.contains(value) => {
+                Ok(Some(*value))
+            }
+            Some(Yaml::Null) | None => Ok(None),
+            Some(_) => Err(ConfigError::IncorrectType {
+                path: limits_path.clone().key(field).unknown(),
+                expected: expected.to_string(),
+            }),
+        };
+    Ok(ProcessLimits {
+        nofile: integer("nofile", 1..=i64::MAX, "positive integer")?
+            .map(|value| value as u64),
+        oom_score_adj: integer(
+            "oom-score-adj",
+            -1000..=1000,
+            "integer from -1000 to 1000",
+        )?
+        .map(|value| value as i32),
+        nice: integer("nice", -20..=19, "integer from -20 to 19")?
+            .map(|value| value as i32),
+        ionice: match config.get(&Yaml::String("ionice".to_string())) {
+            Some(Yaml::Null) | None => None,
+            Some(class) => Some(
+                class.as_str().and_then(IoClass::from_name).ok_or_else(
+                    || ConfigError::IncorrectType {
+                        path: limits_path.clone().key("ionice").unknown(),
+                        expected: "idle, best-effort[:0-7] or realtime[:0-7]"
+                            .to_string(),
+                    },
+                )?,
+            ),
+        },
+    })
+}
+
+/// Parses a service of a router, in the form of:
+///
+/// ```yaml
+/// echo:
+///   command: socat TCP-LISTEN:8080,fork,reuseaddr EXEC:cat
+///   ready:
+///     tcp: 8080        # or exec: <command exiting 0 once ready>
+///     timeout: 10      # seconds
+///   restart: on-failure  # or never, always
+/// ```
+fn parse_service(
+    name: &str,
+    config: &Yaml,
+    service_path: &YamlPath,
+) -> NetResult<Service> {
+    const KEYS: &[&str] = &["command", "ready", "restart"];
+    const READY_KEYS: &[&str] = &["tcp", "exec", "timeout"];
+    let Yaml::Hash(config) = config else {
+        return Err(ConfigError::IncorrectType {
+            path: service_path.clone().unknown(),
+            expected: "hash".to_string(),
+        }
+        .into());
+    };
+    for key in config.keys() {
+        if !key.as_str().is_some_and(|key| KEYS.contains(&key)) {
+            return Err(ConfigError::IncorrectType {
+                path: service_path.clone().unknown(),
+                expected: KEYS.join(", "),
+            }
+            .into());
+        }
+    }
+    let command = match config.get(&Yaml::String(String::from("command"))) {
+        Some(Yaml::String(command)) if !command.trim().is_empty() => {
+            command.clone()
+        }
+        None => {
+            return Err(ConfigError::MissingField {
+                path: service_path.clone().key("command").unknown(),
+            }
+            .into());
+        }
+        Some(_) => {
+            return Err(ConfigError::IncorrectType {
+                path: service_path.clone().key("command").unknown(),
+                expected: "command line".to_string(),
+            }
+            .into());
+        }
+    };
+
+    let ready_path = service_path.clone().key("ready");
+    let ready = match config.get(&Yaml::String(String::from("ready"))) {
+        Some(Yaml::Null) | None => None,
+        Some(Yaml::Hash(ready)) => {
+            for key in ready.keys() {
+                if !key.as_str().is_some_and(|key| READY_KEYS.contains(&key)) {
+                    return Err(ConfigError::IncorrectType {
+                        path: ready_path.clone().unknown(),
+                        expected: READY_KEYS.join(", "),
+                    }
+                    .into());
+                }
+            }
+            let probe = match (
+                ready.get(&Yaml::String(String::from("tcp"))),
+                ready.get(&Yaml::String(String::from("exec"))),
+            ) {
+                (Some(Yaml::Integer(port)), None) => u16::try_from(*port)
+                    .ok()
+                    .filter(|port| *port != 0)
+                    .map(ReadyProbe::Tcp),
+                (None, Some(Yaml::String(check))) => {
+                    Some(ReadyProbe::Exec(check.clone()))
+                }
+                _ => None,
+            }
+            .ok_or_else(|| ConfigError::IncorrectType {
+                path: ready_path.clone().unknown(),
+                expected: "either tcp: <port> or exec: <command>".to_string(),
+            })?;
+            let timeout =
+                match ready.get(&Yaml::String(String::from("timeout"))) {
+                    Some(Yaml::Null) | None => DEFAULT_READY_TIMEOUT,
+                    Some(_) => Duration::from_secs(
+                        get_u32_field(ready, "timeout", &ready_path)?.into(),
+                    ),
+                };
+            Some(ReadyCheck { probe, timeout })
+        }
+        Some(_) => {
+            return Err(ConfigError::IncorrectType {
+                path: ready_path.unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        }
+    };
+
+    let restart = match config.get(&Yaml::String(String::from("restart"))) {
+        Some(Yaml::Null) | None => RestartPolicy::default(),
+        Some(policy) => policy
+            .as_str()
+            .and_then(RestartPolicy::from_name)
+            .ok_or_else(|| ConfigError::IncorrectType {
+                path: service_path.clone().key("restart").unknown(),
+                expected: "never, on-failure or always".to_string(),
+            })?,
+    };
+    Ok(Service {
+        name: name.to_string(),
+        command,
+        ready,
+        restart,
+    })
+}
+
+fn parse_policy_rule(
+    config: &Yaml,
+    rules_path: &YamlPath,
+) -> NetResult<PolicyRule> {
+    const KEYS: &[&str] =
+        &["priority", "from", "to", "fwmark", "iif", "oif", "table"];
+    let Yaml::Hash(config) = config else {
+        return Err(ConfigError::IncorrectType {
+            path: rules_path.clone().unknown(),
+            expected: "hash".to_string(),
+        }
+        .into());
+    };
+    for key in config.keys() {
+        if !key.as_str().is_some_and(|key| KEYS.contains(&key)) {
+            return Err(ConfigError::IncorrectType {
+                path: rules_path.clone().unknown(),
+                expected: KEYS.join(", "),
+            }
+            .into());
+        }
+    }
+    let has = |field: &str| config.contains_key(&Yaml::String(field.into()));
+    let string = |field: &str| -> NetResult<Option<String>> {
+        match config.get(&Yaml::String(field.to_string())) {
+            Some(Yaml::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(ConfigError::IncorrectType {
+                path: rules_path.clone().key(field).unknown(),
+                expected: "string".to_string(),
+            }
+            .into()),
+            None => Ok(None),
+        }
+    };
+
+    let rule = PolicyRule {
+        priority: has("priority")
+            .then(|| get_u32_field(config, "priority", rules_path))
+            .transpose()?,
+        from: has("from")
+            .then(|| get_network_field(config, "from", rules_path))
+            .transpose()?,
+        to: has("to")
+            .then(|| get_network_field(config, "to", rules_path))
+            .transpose()?,
+        fwmark: has("fwmark")
+            .then(|| get_u32_field(config, "fwmark", rules_path))
+            .transpose()?,
+        iif: string("iif")?,
+        oif: string("oif")?,
+        table: get_table_field(config, "table", rules_path)?.ok_or_else(
+            || ConfigError::MissingField {
+                path: rules_path.clone().key("table").unknown(),
+            },
+        )?,
+    };
+    if let (Some(from), Some(to)) = (rule.from, rule.to)
+        && from.is_ipv4() != to.is_ipv4()
+    {
+        return Err(ConfigError::IncorrectType {
+            path: rules_path.clone().key("to").unknown(),
+            expected: "prefix of the same family as from".to_string(),
+        }
+        .into());
+    }
+    Ok(rule)
+}
+
+/// Handles a rule of a router's `nat` list, in the form of:
+///
+/// ```yaml
+/// - type: dnat
+///   in-iface: eth0
+///   destination: 203.0.113.1/32
+///   protocol: tcp
+///   port: 80
+///   to: 10.0.1.10
+///   to-port: 8080
+/// ```
+fn parse_nat_rule(config: &Yaml, nat_path: &YamlPath) -> NetResult<NatRule> {
+    const KEYS: &[&str] = &[
+        "type",
+        "source",
+        "destination",
+        "protocol",
+        "port",
+        "in-iface",
+        "out-iface",
+        "to",
+        "to-port",
+    ];
+    let Yaml::Hash(config) = config else {
+        return Err(ConfigError::IncorrectType {
+            path: nat_path.clone().unknown(),
+            expected: "hash".to_string(),
+        }
+        .into());
+    };
+    let incorrect = |field: &str, expected: &str| -> NetError {
+        ConfigError::IncorrectType {
+            path: nat_path.clone().key(field).unknown(),
+            expected: expected.to_string(),
+        }
+        .into()
+    };
+    for key in config.keys() {
+        if !key.as_str().is_some_and(|key| KEYS.contains(&key)) {
+            return Err(ConfigError::IncorrectType {
+                path: nat_path.clone().unknown(),
+                expected: KEYS.join(", "),
+            }
+            .into());
+        }
+    }
+    let has = |field: &str| config.contains_key(&Yaml::String(field.into()));
+    let string = |field: &str| -> NetResult<Option<String>> {
+        match config.get(&Yaml::String(field.to_string())) {
+            Some(Yaml::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(incorrect(field, "string")),
+            None => Ok(None),
+        }
+    };
+    let port = |field: &str| -> NetResult<Option<u16>> {
+        match config.get(&Yaml::String(field.to_string())) {
+            Some(Yaml::Integer(value)) => u16::try_from(*value)
+                .ok()
+                .filter(|port| *port != 0)
+                .map(Some)
+                .ok_or_else(|| incorrect(field, "port number")),
+            Some(_) => Err(incorrect(field, "port number")),
+            None => Ok(None),
+        }
+    };
+
+    let kind = match string("type")? {
+        Some(name) => NatKind::from_name(&name)
+            .ok_or_else(|| incorrect("type", "snat, dnat or masquerade"))?,
+        None => {
+            return Err(ConfigError::MissingField {
+                path: nat_path.clone().key("type").unknown(),
+            }
+            .into());
+        }
+    };
+    let rule = NatRule {
+        kind,
+        source: has("source")
+            .then(|| get_network_field(config, "source", nat_path))
+            .transpose()?,
+        destination: has("destination")
+            .then(|| get_network_field(config, "destination", nat_path))
+            .transpose()?,
+        protocol: string("protocol")?,
+        port: port("port")?,
+        in_iface: string("in-iface")?,
+        out_iface: string("out-iface")?,
+        to: has("to")
+            .then(|| get_addr_field(config, "to", nat_path))
+            .transpose()?,
+        to_port: port("to-port")?,
+    };
+
+    if let Some(protocol) = &rule.protocol
+        && !["tcp", "udp"].contains(&protocol.as_str())
+    {
+        return Err(incorrect("protocol", "tcp or udp"));
+    }
+    if rule.protocol.is_none() {
+        for field in ["port", "to-port"] {
+            if has(field) {
+                return Err(ConfigError::MissingField {
+                    path: nat_path.clone().key("protocol").unknown(),
+                }
+                .into());
+            }
+        }
+    }
+    match kind {
+        NatKind::Masquerade if rule.to.is_some() => {
+            return Err(incorrect("to", "no address for masquerade"));
+        }
+        NatKind::Snat | NatKind::Dnat if rule.to.is_none() => {
+            return Err(ConfigError::MissingField {
+                path: nat_path.clone().key("to").unknown(),
+            }
+            .into());
+        }
+        NatKind::Dnat if rule.out_iface.is_some() => {
+            return Err(incorrect("out-iface", "in-iface, for dnat"));
+        }
+        NatKind::Snat | NatKind::Masquerade if rule.in_iface.is_some() => {
+            return Err(incorrect("in-iface", "out-iface, for snat"));
+        }
+        _ => {}
+    }
+    // Families of the matches and of the translation have to agree.
+    let families: Vec<bool> = [rule.source, rule.destination]
+        .into_iter()
+        .flatten()
+        .map(|network| network.is_ipv4())
+        .chain(rule.to.map(|to| to.is_ipv4()))
+        .collect();
+    if families.windows(2).any(|pair| pair[0] != pair[1]) {
+        return Err(incorrect(
+            "to",
+            "addresses of the same family as the matches",
+        ));
+    }
+    Ok(rule)
+}
+
+// ==== impl Switch ====
+
+impl FromYamlConfig for Switch {
+    /// Handles config that is in the form of:
+    ///
+    /// ```yaml
+    /// sw1:
+    ///   interfaces:
+    ///     eth0:
+    ///       ipv4:
+    ///         - 192.168.100.20/24
+    ///       ipv6:
+    ///         - 2001:db8::/96
+    ///   ports:
+    ///     eth0:
+    ///       isolated: true
+    /// ```
+    /// converted into a yaml_rust2::yaml::Hash;
+    fn from_yaml_config(
+        switch_name: &str,
+        switch_config: &Yaml,
+        _switch_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let switch_config = if let Yaml::Hash(switch_config) = switch_config {
+            switch_config
+        } else {
+            return Err(ConfigError::IncorrectType {
+                path: YamlPath::new()
+                    .key("switches")
+                    .key(switch_name)
+                    .unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+
+        let mut switch = Self::new(switch_name);
+        switch.metadata = get_metadata_field(
+            switch_config,
+            &YamlPath::new().key("switches").key(switch_name),
+        )?;
+        match switch_config.get(&Yaml::String(String::from("flow-export"))) {
+            Some(Yaml::Null) | None => {}
+            Some(export_config) => {
+                switch.flow_export = Some(FlowExport::from_yaml_config(
+                    switch_name,
+                    export_config,
+                    BTreeMap::new(),
+                )?);
+            }
+        }
+        if let Some(limits) =
+            switch_config.get(&Yaml::String(String::from("limits")))
+        {
+            switch.limits = parse_limits(
+                limits,
+                &YamlPath::new()
+                    .key("switches")
+                    .key(switch_name)
+                    .key("limits"),
+            )?;
+        }
+        match switch_config.get(&Yaml::String(String::from("ports"))) {
+            Some(Yaml::Null) | None => {}
+            Some(Yaml::Hash(ports_config)) => {
+                for (iface_name, port_config) in ports_config {
+                    let Yaml::String(iface_name) = iface_name else {
+                        return Err(ConfigError::IncorrectType {
+                            path: YamlPath::new()
+                                .key("switches")
+                                .key(switch_name)
+                                .key("ports")
+                                .unknown(),
+                            expected: "string".to_string(),
+                        }
+                        .into());
+                    };
+                    let port = PortOptions::from_yaml_config(
+                        iface_name,
+                        port_config,
+                        BTreeMap::from([("switch_name", switch_name)]),
+                    )?;
+                    switch.ports.insert(iface_name.clone(), port);
+                }
+            }
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: YamlPath::new()
+                        .key("switches")
+                        .key(switch_name)
+                        .key("ports")
+                        .unknown(),
+                    expected: "hash".to_string(),
+                }
+                .into());
+            }
+        }
+
+        match switch_config.get(&Yaml::String(String::from("interfaces"))) {
+            Some(Yaml::Hash(interfaces_config)) => {
+                for (iface_name, iface_config) in interfaces_config {
+                    match iface_name {
+                        Yaml::String(iface_name) => {
+                            let interface = Interface::from_yaml_config(
+                                iface_name,
+                                iface_config,
+                                BTreeMap::from([
+                                    ("device_name", switch_name),
+                                    ("device_type", "switch"),
+                                ]),
+                            )?;
+                            switch.interfaces.push(interface);
+                        }
+                        _ => {
+                            return Err(ConfigError::IncorrectType {
+                                path: YamlPath::new()
+                                    .key("switches")
+                                    .key(switch_name)
+                                    .key("interfaces")
+                                    .unknown(),
+                                expected: "string".to_string(),
+                            }
+                            .into());
+                        }
+                    }
+                }
+                Ok(switch)
+            }
+            _ => Err(ConfigError::IncorrectType {
+                path: YamlPath::new()
+                    .key("switches")
+                    .key(switch_name)
+                    .unknown(),
+                expected: "hash".to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+// ==== impl PortOptions ====
+
+impl FromYamlConfig for PortOptions {
+    /// Handles config that is in the form of:
+    ///
+    /// ```yaml
+    /// eth0:
+    ///   isolated: true
+    ///   hairpin: true
+    ///   learning: false
+    ///   flood: false
+    ///   police:
+    ///     rate: 1000
+    ///     burst: 20000
+    ///     broadcast-only: true
+    /// ```
+    fn from_yaml_config(
+        iface_name: &str,
+        port_config: &Yaml,
+        port_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new()
+            .key("switches")
+            .key(port_ctx.get("switch_name").copied().unwrap_or_default())
+            .key("ports")
+            .key(iface_name);
+        let Yaml::Hash(config) = port_config else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+
+        let mut port = PortOptions::default();
+        for (key, value) in config {
+            if key.as_str() == Some("police") {
+                port.police = Some(parse_police(value, &yaml_path)?);
+                continue;
+            }
+            let option = match key.as_str() {
+                Some("isolated") => &mut port.isolated,
+                Some("hairpin") => &mut port.hairpin,
+                Some("learning") => &mut port.learning,
+                Some("flood") => &mut port.flood,
+                _ => {
+                    return Err(ConfigError::IncorrectType {
+                        path: yaml_path.clone().unknown(),
+                        expected: PortOptions::KEYS.join(", "),
+                    }
+                    .into());
+                }
+            };
+            let Yaml::Boolean(value) = value else {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path
+                        .clone()
+                        .key(key.as_str().unwrap_or_default())
+                        .unknown(),
+                    expected: "bool".to_string(),
+                }
+                .into());
+            };
+            *option = Some(*value);
+        }
+        Ok(port)
+    }
+}
+
+fn parse_police(config: &Yaml, port_path: &YamlPath) -> NetResult<Police> {
+    let yaml_path = port_path.clone().key("police");
+    let Yaml::Hash(config) = config else {
+        return Err(ConfigError::IncorrectType {
+            path: yaml_path.clone().unknown(),
+            expected: "hash".to_string(),
+        }
+        .into());
+    };
+    let rate = get_u32_field(config, "rate", &yaml_path)?;
+    if rate == 0 {
+        return Err(ConfigError::IncorrectType {
+            path: yaml_path.clone().key("rate").unknown(),
+            expected: "positive integer".to_string(),
+        }
+        .into());
+    }
+    let burst = match config.get(&Yaml::String(String::from("burst"))) {
+        Some(_) => Some(get_u32_field(config, "burst", &yaml_path)?),
+        None => None,
+    };
+    let broadcast_only =
+        match config.get(&Yaml::String(String::from("broadcast-only"))) {
+            Some(Yaml::Boolean(value)) => *value,
+            None => false,
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("broadcast-only").unknown(),
+                    expected: "bool".to_string(),
+                }
+                .into());
+            }
+        };
+    Ok(Police {
+        rate,
+        burst,
+        broadcast_only,
+    })
+}
+
+// ==== impl FlowExport ====
+
+impl FromYamlConfig for FlowExport {
+    fn from_yaml_config(
+        switch_name: &str,
+        export_config: &Yaml,
+        _export_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new()
+            .key("switches")
+            .key(switch_name)
+            .key("flow-export");
+        let Yaml::Hash(config) = export_config else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+
+        let protocol =
+            match config.get(&Yaml::String(String::from("protocol"))) {
+                Some(Yaml::String(name)) => FlowProtocol::from_name(name),
+                Some(_) => None,
+                None => {
+                    return Err(ConfigError::MissingField {
+                        path: yaml_path.clone().key("protocol").unknown(),
+                    }
+                    .into());
+                }
+            }
+            .ok_or_else(|| ConfigError::IncorrectType {
+                path: yaml_path.clone().key("protocol").unknown(),
+                expected: "netflow, ipfix or sflow".to_string(),
+            })?;
+
+        let collector =
+            match config.get(&Yaml::String(String::from("collector"))) {
+                Some(Yaml::String(collector)) => collector.parse().ok(),
+                Some(_) => None,
+                None => {
+                    return Err(ConfigError::MissingField {
+                        path: yaml_path.clone().key("collector").unknown(),
+                    }
+                    .into());
+                }
+            }
+            .ok_or_else(|| ConfigError::IncorrectType {
+                path: yaml_path.clone().key("collector").unknown(),
+                expected: "address:port".to_string(),
+            })?;
+
+        let sampling = match config.get(&Yaml::String(String::from("sampling")))
+        {
+            Some(_) => get_u32_field(config, "sampling", &yaml_path)?,
+            None => 1,
+        };
+        if sampling == 0 {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().key("sampling").unknown(),
+                expected: "positive integer".to_string(),
+            }
+            .into());
+        }
+
+        let source = match config.get(&Yaml::String(String::from("source"))) {
+            Some(_) => Some(get_network_field(config, "source", &yaml_path)?),
+            None => None,
+        };
+
+        Ok(FlowExport {
+            protocol,
+            collector,
+            sampling,
+            source,
+        })
+    }
+}
+
+// ==== impl Interface ====
+
+impl FromYamlConfig for Interface {
+    fn from_yaml_config(
+        iface_name: &str,
+        iface_config: &Yaml,
+        iface_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = match (
+            iface_ctx.get("device_name"),
+            iface_ctx.get("device_type"),
+        ) {
+            (Some(device_name), Some(device_type)) => {
+                if *device_type == "switch" {
+                    YamlPath::new()
+                        .key("switches")
+                        .key(*device_name)
+                        .key("interfaces")
+                        .key(iface_name)
+                } else if *device_type == "router" {
+                    YamlPath::new()
                         .key("routers")
                         .key(*device_name)
                         .key("interfaces")
@@ -263,185 +1849,1691 @@ impl FromYamlConfig for Interface {
                     )));
                 }
             }
-            (_, _) => {
-                // TODO: Add more specified error checks.
-                return Err(NetError::BasicError(format!(
-                    "device_name or device_type not specified for iface {iface_name}"
-                )));
+            (_, _) => {
+                // TODO: Add more specified error checks.
+                return Err(NetError::BasicError(format!(
+                    "device_name or device_type not specified for iface {iface_name}"
+                )));
+            }
+        };
+
+        // Confirm Yaml config type.
+        let mut interface = Interface::new(iface_name.to_string());
+        let addr_array = match iface_config {
+            Yaml::Array(addr_array) => addr_array,
+            Yaml::Null => {
+                return Ok(interface);
+            }
+            // Long form of router interfaces, setting their families or
+            // type.
+            Yaml::Hash(config)
+                if iface_ctx.get("device_type") == Some(&"router") =>
+            {
+                for key in config.keys() {
+                    if !matches!(
+                        key.as_str(),
+                        Some("addresses" | "families" | "type")
+                    ) {
+                        return Err(ConfigError::IncorrectType {
+                            path: yaml_path.clone().unknown(),
+                            expected: "addresses, families, type".to_string(),
+                        }
+                        .into());
+                    }
+                }
+                match config.get(&Yaml::String(String::from("type"))) {
+                    Some(Yaml::String(kind)) if kind == "dummy" => {
+                        interface.dummy = true;
+                    }
+                    Some(Yaml::Null) | None => {}
+                    Some(_) => {
+                        return Err(ConfigError::IncorrectType {
+                            path: yaml_path.clone().key("type").unknown(),
+                            expected: "dummy".to_string(),
+                        }
+                        .into());
+                    }
+                }
+                if let Some(families) =
+                    config.get(&Yaml::String(String::from("families")))
+                {
+                    interface.families = Some(parse_families(
+                        families,
+                        &yaml_path.clone().key("families"),
+                    )?);
+                }
+                match config.get(&Yaml::String(String::from("addresses"))) {
+                    Some(Yaml::Array(addr_array)) => addr_array,
+                    Some(Yaml::Null) | None => return Ok(interface),
+                    Some(_) => {
+                        return Err(ConfigError::IncorrectType {
+                            path: yaml_path.clone().key("addresses").unknown(),
+                            expected: "array".to_string(),
+                        }
+                        .into());
+                    }
+                }
+            }
+            _ => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone(),
+                    expected: "array".to_string(),
+                }
+                .into());
+            }
+        };
+
+        for addr in addr_array {
+            if let Yaml::String(addr_str) = addr {
+                if let Some(pool) = addr_str.strip_prefix(POOL_ADDR_PREFIX) {
+                    interface.pools.push(pool.to_string());
+                    continue;
+                }
+                let ip: IpNetwork =
+                    addr_str.as_str().parse().map_err(|err| {
+                        ConfigError::InvalidAddress {
+                            address: addr_str.to_string(),
+                            path: yaml_path.clone(),
+                            source: err,
+                        }
+                    })?;
+                interface.addresses.push(ip);
+            } else {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path,
+                    expected: "string".to_string(),
+                }
+                .into());
+            }
+        }
+
+        Ok(interface)
+    }
+}
+
+/// Handles a list of address families, in order of preference:
+///
+/// ```yaml
+/// families: [ ipv6, ipv4 ]
+/// ```
+fn parse_families(config: &Yaml, yaml_path: &YamlPath) -> NetResult<Families> {
+    let invalid = || -> NetError {
+        ConfigError::IncorrectType {
+            path: yaml_path.clone().unknown(),
+            expected: "list of distinct families, ipv4 or ipv6".to_string(),
+        }
+        .into()
+    };
+    let Yaml::Array(names) = config else {
+        return Err(invalid());
+    };
+    let mut families = vec![];
+    for name in names {
+        let family = name
+            .as_str()
+            .and_then(AddressFamily::from_name)
+            .ok_or_else(invalid)?;
+        if families.contains(&family) {
+            return Err(invalid());
+        }
+        families.push(family);
+    }
+    if families.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Families::new(families))
+}
+
+// ==== impl Volume ====
+
+impl FromYamlConfig for Volume {
+    fn from_yaml_config(
+        router_name: &str,
+        volume_config: &Yaml,
+        _router_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        if let Yaml::Hash(map) = volume_config {
+            if let Some((Yaml::String(src), Yaml::String(dst))) =
+                map.iter().next()
+            {
+                Ok(Volume {
+                    src: src.to_string(),
+                    dst: dst.to_string(),
+                })
+            } else {
+                Err(ConfigError::IncorrectType {
+                    path: YamlPath::new()
+                        .key("routers")
+                        .key(router_name)
+                        .key("volumes")
+                        .unknown(),
+                    expected: "'string':'string'".to_string(),
+                }
+                .into())
+            }
+        } else {
+            Err(ConfigError::IncorrectType {
+                path: YamlPath::new()
+                    .key("routers")
+                    .key(router_name)
+                    .key("volumes")
+                    .unknown(),
+                expected: "array".to_string(),
+            }
+            .into())
+        }
+    }
+}
+
+// ==== impl FileDrop ====
+
+impl FromYamlConfig for FileDrop {
+    fn from_yaml_config(
+        dst: &str,
+        file_config: &Yaml,
+        file_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new()
+            .key("routers")
+            .key(file_ctx.get("device_name").copied().unwrap_or_default())
+            .key("files")
+            .key(dst);
+
+        if !dst.starts_with('/') {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: "absolute destination path".to_string(),
+            }
+            .into());
+        }
+
+        let mut file = FileDrop {
+            dst: dst.to_string(),
+            source: FileSource::Inline(String::new()),
+            mode: None,
+        };
+
+        match file_config {
+            // Inline content.
+            Yaml::String(content) => {
+                file.source = FileSource::Inline(content.clone());
+            }
+            Yaml::Hash(config) => {
+                let content =
+                    config.get(&Yaml::String(String::from("content")));
+                let source = config.get(&Yaml::String(String::from("source")));
+                file.source = match (content, source) {
+                    (Some(Yaml::String(content)), None) => {
+                        FileSource::Inline(content.clone())
+                    }
+                    (None, Some(Yaml::String(source))) => {
+                        FileSource::Path(source.clone())
+                    }
+                    _ => {
+                        return Err(ConfigError::IncorrectType {
+                            path: yaml_path.clone().unknown(),
+                            expected: "either 'content' or 'source' string"
+                                .to_string(),
+                        }
+                        .into());
+                    }
+                };
+
+                // Modes are written in octal, e.g. "0600".
+                file.mode = match config
+                    .get(&Yaml::String(String::from("mode")))
+                {
+                    Some(Yaml::String(mode)) => {
+                        Some(u32::from_str_radix(mode, 8).map_err(|_| {
+                            ConfigError::IncorrectType {
+                                path: yaml_path.clone().key("mode").unknown(),
+                                expected: "octal mode".to_string(),
+                            }
+                        })?)
+                    }
+                    Some(Yaml::Null) | None => None,
+                    Some(_) => {
+                        return Err(ConfigError::IncorrectType {
+                            path: yaml_path.clone().key("mode").unknown(),
+                            expected: "octal mode string".to_string(),
+                        }
+                        .into());
+                    }
+                };
+            }
+            _ => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().unknown(),
+                    expected: "string or hash".to_string(),
+                }
+                .into());
+            }
+        }
+        Ok(file)
+    }
+}
+
+// ==== impl PortForward ====
+
+impl FromYamlConfig for PortForward {
+    fn from_yaml_config(
+        router_name: &str,
+        expose_config: &Yaml,
+        _expose_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new()
+            .key("routers")
+            .key(router_name)
+            .key("expose");
+        let invalid = |expected: &str| -> NetError {
+            ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: expected.to_string(),
+            }
+            .into()
+        };
+        let port = |value: &Yaml| match value {
+            Yaml::Integer(port) => u16::try_from(*port).ok(),
+            Yaml::String(port) => port.parse().ok(),
+            _ => None,
+        };
+
+        match expose_config {
+            // Same port on both sides, e.g. `22`.
+            Yaml::Integer(_) => {
+                let port = port(expose_config)
+                    .ok_or_else(|| invalid("port number"))?;
+                Ok(PortForward::new(port))
+            }
+            // "<host-port>:<port>", e.g. "2222:22".
+            Yaml::String(mapping) => {
+                let (host_port, port) = mapping
+                    .split_once(':')
+                    .and_then(|(host_port, port)| {
+                        Some((host_port.parse().ok()?, port.parse().ok()?))
+                    })
+                    .ok_or_else(|| invalid("'<host-port>:<port>'"))?;
+                let mut forward = PortForward::new(port);
+                forward.host_port = host_port;
+                Ok(forward)
+            }
+            Yaml::Hash(config) => {
+                let field =
+                    |name: &str| config.get(&Yaml::String(name.to_string()));
+                let mut forward = PortForward::new(
+                    field("port")
+                        .and_then(port)
+                        .ok_or_else(|| invalid("'port' number"))?,
+                );
+                if let Some(host_port) = field("host-port") {
+                    forward.host_port = port(host_port)
+                        .ok_or_else(|| invalid("'host-port' number"))?;
+                }
+                if field("address").is_some() {
+                    forward.address =
+                        get_addr_field(config, "address", &yaml_path)?;
+                }
+                if field("host-address").is_some() {
+                    forward.host_address =
+                        get_addr_field(config, "host-address", &yaml_path)?;
+                }
+                Ok(forward)
+            }
+            _ => Err(invalid("port, string or hash")),
+        }
+    }
+}
+
+// ==== impl GrpcProxy ====
+
+impl FromYamlConfig for GrpcProxy {
+    fn from_yaml_config(
+        _name: &str,
+        grpc_config: &Yaml,
+        _grpc_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new().key("grpc");
+        let config = match grpc_config {
+            Yaml::Boolean(true) => return Ok(GrpcProxy::default()),
+            Yaml::Hash(config) => config,
+            _ => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().unknown(),
+                    expected: "true or hash".to_string(),
+                }
+                .into());
+            }
+        };
+
+        let mut grpc = GrpcProxy::default();
+        if let Some(kind) = config.get(&Yaml::String(String::from("kind"))) {
+            grpc.kind = kind
+                .as_str()
+                .ok_or_else(|| ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("kind").unknown(),
+                    expected: "string".to_string(),
+                })?
+                .to_string();
+        }
+        for (field, port) in
+            [("port", &mut grpc.port), ("host-port", &mut grpc.host_port)]
+        {
+            if let Some(value) = config.get(&Yaml::String(field.to_string())) {
+                *port = match value {
+                    Yaml::Integer(port) => u16::try_from(*port).ok(),
+                    _ => None,
+                }
+                .filter(|port| *port > 0)
+                .ok_or_else(|| ConfigError::IncorrectType {
+                    path: yaml_path.clone().key(field).unknown(),
+                    expected: "port number".to_string(),
+                })?;
+            }
+        }
+        Ok(grpc)
+    }
+}
+
+// ==== impl SshServer ====
+
+impl FromYamlConfig for SshServer {
+    fn from_yaml_config(
+        _name: &str,
+        ssh_config: &Yaml,
+        ssh_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        // Either at the top level, or under a router.
+        let yaml_path = match ssh_ctx.get("device_name") {
+            Some(router_name) => {
+                YamlPath::new().key("routers").key(*router_name).key("ssh")
+            }
+            None => YamlPath::new().key("ssh"),
+        };
+
+        let config = match ssh_config {
+            Yaml::Boolean(true) => return Ok(SshServer::default()),
+            Yaml::Boolean(false) => return Ok(SshServer::disabled()),
+            Yaml::Hash(config) => config,
+            _ => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().unknown(),
+                    expected: "boolean or hash".to_string(),
+                }
+                .into());
+            }
+        };
+
+        let mut ssh = SshServer::default();
+        if let Some(port) = config.get(&Yaml::String(String::from("port"))) {
+            ssh.port = match port {
+                Yaml::Integer(port) => u16::try_from(*port).ok(),
+                _ => None,
+            }
+            .ok_or_else(|| ConfigError::IncorrectType {
+                path: yaml_path.clone().key("port").unknown(),
+                expected: "port number".to_string(),
+            })?;
+        }
+        if config.get(&Yaml::String(String::from("address"))).is_some() {
+            ssh.address = Some(get_addr_field(config, "address", &yaml_path)?);
+        }
+        match config.get(&Yaml::String(String::from("authorized-keys"))) {
+            Some(Yaml::String(keys)) => {
+                ssh.authorized_keys = Some(keys.clone());
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("authorized-keys").unknown(),
+                    expected: "string".to_string(),
+                }
+                .into());
+            }
+        }
+        Ok(ssh)
+    }
+}
+
+// ==== impl DnsConfig ====
+
+impl FromYamlConfig for DnsConfig {
+    fn from_yaml_config(
+        router_name: &str,
+        dns_config: &Yaml,
+        _dns_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path =
+            YamlPath::new().key("routers").key(router_name).key("dns");
+        let strings = |field: &str, list: &Yaml| -> NetResult<Vec<String>> {
+            let invalid = || ConfigError::IncorrectType {
+                path: yaml_path.clone().key(field).unknown(),
+                expected: "list of strings".to_string(),
+            };
+            let Yaml::Array(list) = list else {
+                return Err(invalid().into());
+            };
+            list.iter()
+                .map(|item| {
+                    item.as_str().map(str::to_string).ok_or_else(invalid)
+                })
+                .collect::<Result<_, _>>()
+                .map_err(NetError::from)
+        };
+
+        let mut dns = DnsConfig::default();
+        let servers = match dns_config {
+            // Short for only listing servers.
+            Yaml::Array(_) => dns_config,
+            Yaml::Hash(config) => {
+                for (field, list) in
+                    [("search", &mut dns.search), ("options", &mut dns.options)]
+                {
+                    if let Some(config) =
+                        config.get(&Yaml::String(field.to_string()))
+                    {
+                        *list = strings(field, config)?;
+                    }
+                }
+                config
+                    .get(&Yaml::String(String::from("servers")))
+                    .ok_or_else(|| ConfigError::MissingField {
+                        path: yaml_path.clone().key("servers").unknown(),
+                    })?
+            }
+            _ => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().unknown(),
+                    expected: "list of addresses or hash".to_string(),
+                }
+                .into());
+            }
+        };
+        for server in strings("servers", servers)? {
+            let server =
+                server.parse().map_err(|_| ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("servers").unknown(),
+                    expected: "list of ip addresses".to_string(),
+                })?;
+            dns.servers.push(server);
+        }
+        Ok(dns)
+    }
+}
+
+// ==== impl Frr ====
+
+impl FromYamlConfig for Frr {
+    fn from_yaml_config(
+        router_name: &str,
+        frr_config: &Yaml,
+        _frr_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path =
+            YamlPath::new().key("routers").key(router_name).key("frr");
+        let Yaml::Hash(frr_config) = frr_config else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+
+        let mut frr = Frr::default();
+        match frr_config.get(&Yaml::String(String::from("daemons"))) {
+            Some(Yaml::Array(daemons)) => {
+                for daemon in daemons {
+                    let daemon = daemon
+                        .as_str()
+                        .filter(|daemon| frr::DAEMONS.contains(daemon))
+                        .ok_or_else(|| ConfigError::IncorrectType {
+                            path: yaml_path.clone().key("daemons").unknown(),
+                            expected: format!(
+                                "list of FRR daemons ({})",
+                                frr::DAEMONS.join(", ")
+                            ),
+                        })?;
+                    if !frr.daemons.iter().any(|known| known == daemon) {
+                        frr.daemons.push(daemon.to_string());
+                    }
+                }
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("daemons").unknown(),
+                    expected: "array".to_string(),
+                }
+                .into());
+            }
+        }
+
+        // Either a local file, or the config itself under `content`.
+        frr.startup_config = match frr_config
+            .get(&Yaml::String(String::from("startup-config")))
+        {
+            Some(Yaml::String(source)) => {
+                Some(FileSource::Path(source.clone()))
+            }
+            Some(Yaml::Hash(config)) => {
+                match config.get(&Yaml::String(String::from("content"))) {
+                    Some(Yaml::String(content)) => {
+                        Some(FileSource::Inline(content.clone()))
+                    }
+                    _ => {
+                        return Err(ConfigError::MissingField {
+                            path: yaml_path
+                                .clone()
+                                .key("startup-config")
+                                .key("content")
+                                .unknown(),
+                        }
+                        .into());
+                    }
+                }
+            }
+            Some(Yaml::Null) | None => None,
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("startup-config").unknown(),
+                    expected: "path or hash".to_string(),
+                }
+                .into());
+            }
+        };
+
+        if frr_config
+            .get(&Yaml::String(String::from("startup-retries")))
+            .is_some()
+        {
+            frr.retries =
+                get_u32_field(frr_config, "startup-retries", &yaml_path)?;
+        }
+
+        match frr_config.get(&Yaml::String(String::from("path"))) {
+            Some(Yaml::String(path)) => frr.path = Some(path.clone()),
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("path").unknown(),
+                    expected: "string".to_string(),
+                }
+                .into());
+            }
+        }
+
+        match frr_config.get(&Yaml::String(String::from("commands"))) {
+            Some(Yaml::Array(commands)) => {
+                for command in commands {
+                    let Yaml::String(command) = command else {
+                        return Err(ConfigError::IncorrectType {
+                            path: yaml_path.clone().key("commands").unknown(),
+                            expected: "list of strings".to_string(),
+                        }
+                        .into());
+                    };
+                    frr.commands.push(command.clone());
+                }
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("commands").unknown(),
+                    expected: "array".to_string(),
+                }
+                .into());
+            }
+        }
+
+        match frr_config.get(&Yaml::String(String::from("lazy"))) {
+            Some(Yaml::Boolean(lazy)) => frr.lazy = *lazy,
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("lazy").unknown(),
+                    expected: "bool".to_string(),
+                }
+                .into());
+            }
+        }
+        Ok(frr)
+    }
+}
+
+// ==== impl NtpService ====
+
+impl FromYamlConfig for NtpService {
+    fn from_yaml_config(
+        _name: &str,
+        ntp_config: &Yaml,
+        _ntp_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new().key("ntp");
+        let config = match ntp_config {
+            // Short for only naming the server.
+            Yaml::String(server) => {
+                return Ok(NtpService {
+                    server: server.clone(),
+                    address: None,
+                });
+            }
+            Yaml::Hash(config) => config,
+            _ => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().unknown(),
+                    expected: "router name or hash".to_string(),
+                }
+                .into());
+            }
+        };
+
+        let server = match config.get(&Yaml::String(String::from("server"))) {
+            Some(Yaml::String(server)) => server.clone(),
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("server").unknown(),
+                    expected: "router name".to_string(),
+                }
+                .into());
+            }
+            None => {
+                return Err(ConfigError::MissingField {
+                    path: yaml_path.clone().key("server").unknown(),
+                }
+                .into());
+            }
+        };
+        let address = match config.get(&Yaml::String(String::from("address"))) {
+            Some(_) => Some(get_addr_field(config, "address", &yaml_path)?),
+            None => None,
+        };
+        Ok(NtpService { server, address })
+    }
+}
+
+// ==== impl Pki ====
+
+impl FromYamlConfig for Pki {
+    fn from_yaml_config(
+        _name: &str,
+        pki_config: &Yaml,
+        _pki_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new().key("pki");
+        let config = match pki_config {
+            Yaml::Boolean(true) => return Ok(Pki::default()),
+            Yaml::Hash(config) => config,
+            _ => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().unknown(),
+                    expected: "true or hash".to_string(),
+                }
+                .into());
+            }
+        };
+
+        let mut pki = Pki::default();
+        if config
+            .get(&Yaml::String(String::from("validity")))
+            .is_some()
+        {
+            pki.validity = get_u32_field(config, "validity", &yaml_path)?;
+            if pki.validity == 0 {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("validity").unknown(),
+                    expected: format!(
+                        "positive number of days ({DEFAULT_VALIDITY} by \
+                            default)"
+                    ),
+                }
+                .into());
+            }
+        }
+        match config.get(&Yaml::String(String::from("routers"))) {
+            Some(Yaml::Array(routers)) => {
+                let routers = routers
+                    .iter()
+                    .map(|router| {
+                        router.as_str().map(str::to_string).ok_or_else(|| {
+                            ConfigError::IncorrectType {
+                                path: yaml_path
+                                    .clone()
+                                    .key("routers")
+                                    .unknown(),
+                                expected: "list of router names".to_string(),
+                            }
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                pki.routers = Some(routers);
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("routers").unknown(),
+                    expected: "array".to_string(),
+                }
+                .into());
+            }
+        }
+        Ok(pki)
+    }
+}
+
+// ==== impl LldpAgent ====
+
+impl FromYamlConfig for LldpAgent {
+    fn from_yaml_config(
+        _name: &str,
+        lldp_config: &Yaml,
+        lldp_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        // Either at the top level, or under a router.
+        let yaml_path = match lldp_ctx.get("device_name") {
+            Some(router_name) => {
+                YamlPath::new().key("routers").key(*router_name).key("lldp")
+            }
+            None => YamlPath::new().key("lldp"),
+        };
+
+        let config = match lldp_config {
+            Yaml::Boolean(true) => return Ok(LldpAgent::default()),
+            Yaml::Boolean(false) => return Ok(LldpAgent::disabled()),
+            Yaml::Hash(config) => config,
+            _ => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().unknown(),
+                    expected: "boolean or hash".to_string(),
+                }
+                .into());
+            }
+        };
+
+        let mut lldp = LldpAgent::default();
+        if config
+            .get(&Yaml::String(String::from("interval")))
+            .is_some()
+        {
+            let interval = get_u32_field(config, "interval", &yaml_path)?;
+            if interval == 0 {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("interval").unknown(),
+                    expected: "positive number of seconds".to_string(),
+                }
+                .into());
+            }
+            lldp.interval = Duration::from_secs(interval.into());
+        }
+        Ok(lldp)
+    }
+}
+
+// ==== impl Kind ====
+
+impl FromYamlConfig for Kind {
+    fn from_yaml_config(
+        name: &str,
+        kind_config: &Yaml,
+        _kind_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let kind_config = if let Yaml::Hash(kind_config) = kind_config {
+            kind_config
+        } else {
+            return Err(ConfigError::IncorrectType {
+                path: YamlPath::new().key("kinds").key(name).unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+
+        let mut kind = Kind::new(name);
+
+        // Kind volume configurations.
+        match kind_config.get(&Yaml::String(String::from("volumes"))) {
+            Some(Yaml::Array(volumes_configs)) => {
+                for volume_config in volumes_configs {
+                    let volume = Volume::from_yaml_config(
+                        kind.name.as_str(),
+                        volume_config,
+                        BTreeMap::new(),
+                    )?;
+                    kind.volumes.push(volume);
+                }
+            }
+            Some(Yaml::Null) | None => { /* Ignore volume configs. */ }
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: YamlPath::new()
+                        .key("kinds")
+                        .key(name)
+                        .key("volumes")
+                        .unknown(),
+                    expected: "hash".to_string(),
+                }
+                .into());
+            }
+        }
+
+        match kind_config.get(&Yaml::String(String::from("scripts"))) {
+            Some(Yaml::Array(script_configs)) => {
+                for script in script_configs {
+                    if let Yaml::String(path) = script {
+                        kind.scripts.push(path.clone());
+                    }
+                }
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: YamlPath::new()
+                        .key("kinds")
+                        .key(name)
+                        .key("scripts")
+                        .unknown(),
+
+                    expected: "array".to_string(),
+                }
+                .into());
+            }
+        }
+        Ok(kind)
+    }
+}
+
+// Get field value from Yaml Hash.
+// TODO: have this better positioned with other YANG configs methods.
+pub(crate) fn get_string_field(
+    config: &Hash,
+    field: &str,
+) -> NetResult<String> {
+    let field_value =
+        config
+            .get(&Yaml::String(field.to_string()))
+            .ok_or_else(|| ConfigError::MissingField {
+                path: YamlPath::new().key(field).unknown(),
+            })?;
+
+    match field_value {
+        Yaml::String(value) => Ok(value.to_string()),
+        _ => Err(ConfigError::IncorrectType {
+            path: YamlPath::new().key(field).unknown(),
+            expected: "string".to_string(),
+        }
+        .into()),
+    }
+}
+
+/// User-defined `metadata:` of a node or link, a hash of scalars kept as
+/// strings.
+pub(crate) fn get_metadata_field(
+    config: &Hash,
+    yaml_path: &YamlPath,
+) -> NetResult<BTreeMap<String, String>> {
+    get_scalars_field(config, "metadata", yaml_path)
+}
+
+// A hash of scalars under `field`, kept as strings.
+fn get_scalars_field(
+    config: &Hash,
+    field: &str,
+    yaml_path: &YamlPath,
+) -> NetResult<BTreeMap<String, String>> {
+    let mut scalars = BTreeMap::new();
+    let field_path = yaml_path.clone().key(field);
+    match config.get(&Yaml::String(field.to_string())) {
+        Some(Yaml::Hash(entries)) => {
+            for (key, value) in entries {
+                let Yaml::String(key) = key else {
+                    return Err(ConfigError::IncorrectType {
+                        path: field_path.clone().unknown(),
+                        expected: "string".to_string(),
+                    }
+                    .into());
+                };
+                let value = match value {
+                    Yaml::String(value) | Yaml::Real(value) => value.clone(),
+                    Yaml::Integer(value) => value.to_string(),
+                    Yaml::Boolean(value) => value.to_string(),
+                    _ => {
+                        return Err(ConfigError::IncorrectType {
+                            path: field_path.clone().key(key).unknown(),
+                            expected: "string".to_string(),
+                        }
+                        .into());
+                    }
+                };
+                scalars.insert(key.clone(), value);
+            }
+        }
+        Some(Yaml::Null) | None => {}
+        Some(_) => {
+            return Err(ConfigError::IncorrectType {
+                path: field_path.clone().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        }
+    }
+    Ok(scalars)
+}
+
+// ==== impl RouteInjector ====
+
+impl FromYamlConfig for RouteInjector {
+    fn from_yaml_config(
+        router_name: &str,
+        injector_config: &Yaml,
+        _injector_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new()
+            .key("routers")
+            .key(router_name)
+            .key("injector");
+
+        let injector_config = if let Yaml::Hash(config) = injector_config {
+            config
+        } else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+
+        let asn = get_u32_field(injector_config, "asn", &yaml_path)?;
+        let router_id = match injector_config
+            .get(&Yaml::String(String::from("router-id")))
+        {
+            Some(Yaml::Null) | None => None,
+            Some(_) => {
+                Some(get_addr_field(injector_config, "router-id", &yaml_path)?)
+            }
+        };
+
+        let update_source = match injector_config
+            .get(&Yaml::String(String::from("update-source")))
+        {
+            Some(Yaml::String(source)) if source == "loopback" => {
+                Some(UpdateSource::Loopback)
+            }
+            Some(Yaml::Null) | None => None,
+            Some(_) => Some(UpdateSource::Address(get_addr_field(
+                injector_config,
+                "update-source",
+                &yaml_path,
+            )?)),
+        };
+
+        let mut neighbors = vec![];
+        match injector_config.get(&Yaml::String(String::from("neighbors"))) {
+            Some(Yaml::Array(neighbor_configs)) => {
+                let neighbor_path = yaml_path.clone().key("neighbors");
+                for neighbor_config in neighbor_configs {
+                    let Yaml::Hash(neighbor_config) = neighbor_config else {
+                        return Err(ConfigError::IncorrectType {
+                            path: neighbor_path.clone().unknown(),
+                            expected: "hash".to_string(),
+                        }
+                        .into());
+                    };
+                    neighbors.push(InjectorPeer {
+                        address: get_addr_field(
+                            neighbor_config,
+                            "address",
+                            &neighbor_path,
+                        )?,
+                        remote_as: get_u32_field(
+                            neighbor_config,
+                            "remote-as",
+                            &neighbor_path,
+                        )?,
+                    });
+                }
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("neighbors").unknown(),
+                    expected: "array".to_string(),
+                }
+                .into());
+            }
+        }
+
+        let prefixes_path = yaml_path.clone().key("prefixes");
+        let Some(Yaml::Hash(prefixes_config)) =
+            injector_config.get(&Yaml::String(String::from("prefixes")))
+        else {
+            return Err(ConfigError::MissingField {
+                path: prefixes_path.clone().unknown(),
+            }
+            .into());
+        };
+        let start_str = get_string_field(prefixes_config, "start")?;
+        let start: IpNetwork =
+            start_str
+                .parse()
+                .map_err(|err| ConfigError::InvalidAddress {
+                    address: start_str.clone(),
+                    path: prefixes_path.clone().key("start"),
+                    source: err,
+                })?;
+        let count =
+            match prefixes_config.get(&Yaml::String(String::from("count"))) {
+                Some(Yaml::Integer(count)) if *count > 0 => *count as u64,
+                None => 1,
+                Some(_) => {
+                    return Err(ConfigError::IncorrectType {
+                        path: prefixes_path.clone().key("count").unknown(),
+                        expected: "positive integer".to_string(),
+                    }
+                    .into());
+                }
+            };
+        check_prefixes(start, count, &prefixes_path)?;
+
+        let flap_interval = match injector_config
+            .get(&Yaml::String(String::from("flap-interval")))
+        {
+            Some(Yaml::Integer(secs)) if *secs > 0 => Some(*secs as u64),
+            Some(Yaml::Null) | None => None,
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("flap-interval").unknown(),
+                    expected: "positive integer".to_string(),
+                }
+                .into());
+            }
+        };
+
+        Ok(RouteInjector {
+            asn,
+            router_id,
+            update_source,
+            neighbors,
+            start,
+            count,
+            flap_interval,
+        })
+    }
+}
+
+// ==== impl AddressPool ====
+
+impl FromYamlConfig for AddressPool {
+    fn from_yaml_config(
+        pool_name: &str,
+        pool_config: &Yaml,
+        _pool_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new().key("pools").key(pool_name);
+
+        let Yaml::Hash(pool_config) = pool_config else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: "hash".to_string(),
             }
+            .into());
         };
 
-        // Confirm Yaml config type.
-        let mut interface = Interface::new(iface_name.to_string());
-        let addr_array = match iface_config {
-            Yaml::Array(addr_array) => addr_array,
-            Yaml::Null => {
-                return Ok(interface);
+        let prefix = get_network_field(pool_config, "prefix", &yaml_path)?;
+        let length = get_u32_field(pool_config, "length", &yaml_path)?;
+        let length =
+            u8::try_from(length).map_err(|_| ConfigError::IncorrectType {
+                path: yaml_path.clone().key("length").unknown(),
+                expected: "prefix length".to_string(),
+            })?;
+        let mut pool = Self::new(pool_name, prefix, length);
+
+        match pool_config.get(&Yaml::String(String::from("exclude"))) {
+            Some(Yaml::Array(ranges)) => {
+                for range in ranges {
+                    let range = match range {
+                        Yaml::String(range) => range.parse().ok(),
+                        _ => None,
+                    };
+                    let Some(range) = range else {
+                        return Err(ConfigError::IncorrectType {
+                            path: yaml_path.clone().key("exclude").unknown(),
+                            expected: "ip network".to_string(),
+                        }
+                        .into());
+                    };
+                    pool.exclude.push(range);
+                }
             }
-            _ => {
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
                 return Err(ConfigError::IncorrectType {
-                    path: yaml_path.clone(),
+                    path: yaml_path.clone().key("exclude").unknown(),
                     expected: "array".to_string(),
                 }
                 .into());
             }
-        };
+        }
 
-        for addr in addr_array {
-            if let Yaml::String(addr_str) = addr {
-                let ip: IpNetwork =
-                    addr_str.as_str().parse().map_err(|err| {
-                        ConfigError::InvalidAddress {
-                            address: addr_str.to_string(),
-                            path: yaml_path.clone(),
-                            source: err,
+        match pool_config.get(&Yaml::String(String::from("reserve"))) {
+            Some(Yaml::Hash(reservations)) => {
+                let reserve_path = yaml_path.clone().key("reserve");
+                for (iface, addr) in reservations {
+                    let Yaml::String(iface) = iface else {
+                        return Err(ConfigError::IncorrectType {
+                            path: reserve_path.clone().unknown(),
+                            expected: "<node>:<iface> string".to_string(),
                         }
-                    })?;
-                interface.addresses.push(ip);
-            } else {
+                        .into());
+                    };
+                    // A bare address gets the pool's length.
+                    let addr = match addr {
+                        Yaml::String(addr) => addr
+                            .parse::<IpNetwork>()
+                            .ok()
+                            .filter(|_| addr.contains('/'))
+                            .or_else(|| {
+                                let addr: IpAddr = addr.parse().ok()?;
+                                IpNetwork::new(addr, length).ok()
+                            }),
+                        _ => None,
+                    };
+                    let Some(addr) = addr else {
+                        return Err(ConfigError::IncorrectType {
+                            path: reserve_path.clone().key(iface).unknown(),
+                            expected: "ip address".to_string(),
+                        }
+                        .into());
+                    };
+                    pool.reservations.insert(iface.clone(), addr);
+                }
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
                 return Err(ConfigError::IncorrectType {
-                    path: yaml_path,
-                    expected: "string".to_string(),
+                    path: yaml_path.clone().key("reserve").unknown(),
+                    expected: "hash".to_string(),
                 }
                 .into());
             }
         }
 
-        Ok(interface)
+        pool.validate()?;
+        Ok(pool)
     }
 }
 
-// ==== impl Volume ====
+// ==== impl ProbeSession ====
 
-impl FromYamlConfig for Volume {
+impl FromYamlConfig for ProbeSession {
     fn from_yaml_config(
-        router_name: &str,
-        volume_config: &Yaml,
-        _router_ctx: BTreeMap<&str, &str>,
+        index: &str,
+        probe_config: &Yaml,
+        _probe_ctx: BTreeMap<&str, &str>,
     ) -> NetResult<Self> {
-        if let Yaml::Hash(map) = volume_config {
-            if let Some((Yaml::String(src), Yaml::String(dst))) =
-                map.iter().next()
-            {
-                Ok(Volume {
-                    src: src.to_string(),
-                    dst: dst.to_string(),
-                })
-            } else {
-                Err(ConfigError::IncorrectType {
-                    path: YamlPath::new()
-                        .key("routers")
-                        .key(router_name)
-                        .key("volumes")
-                        .unknown(),
-                    expected: "'string':'string'".to_string(),
+        let yaml_path = YamlPath::new().key("probes").key(index);
+
+        let Yaml::Hash(probe_config) = probe_config else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+
+        let nodes =
+            match probe_config.get(&Yaml::String(String::from("between"))) {
+                Some(Yaml::Array(nodes)) => match nodes.as_slice() {
+                    [Yaml::String(node1), Yaml::String(node2)]
+                        if node1 != node2 =>
+                    {
+                        Some([node1.clone(), node2.clone()])
+                    }
+                    _ => None,
+                },
+                None => {
+                    return Err(ConfigError::MissingField {
+                        path: yaml_path.clone().key("between").unknown(),
+                    }
+                    .into());
                 }
-                .into())
+                Some(_) => None,
+            };
+        let Some(nodes) = nodes else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().key("between").unknown(),
+                expected: "two distinct node names".to_string(),
             }
-        } else {
-            Err(ConfigError::IncorrectType {
-                path: YamlPath::new()
-                    .key("routers")
-                    .key(router_name)
-                    .key("volumes")
-                    .unknown(),
-                expected: "array".to_string(),
+            .into());
+        };
+
+        let optional_u32 = |field: &str| -> NetResult<Option<u32>> {
+            match probe_config.get(&Yaml::String(field.to_string())) {
+                Some(Yaml::Null) | None => Ok(None),
+                Some(_) => {
+                    match get_u32_field(probe_config, field, &yaml_path)? {
+                        0 => Err(ConfigError::IncorrectType {
+                            path: yaml_path.clone().key(field).unknown(),
+                            expected: "positive integer".to_string(),
+                        }
+                        .into()),
+                        value => Ok(Some(value)),
+                    }
+                }
             }
-            .into())
-        }
+        };
+        let interval = optional_u32("interval")?
+            .map_or(DEFAULT_INTERVAL, |millis| {
+                Duration::from_millis(millis.into())
+            });
+        let multiplier =
+            optional_u32("multiplier")?.unwrap_or(DEFAULT_MULTIPLIER);
+        // Sessions of a router need ports of their own.
+        let port = match optional_u32("port")? {
+            None => index
+                .parse::<u16>()
+                .ok()
+                .and_then(|index| DEFAULT_PORT.checked_add(index))
+                .ok_or_else(|| ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("port").unknown(),
+                    expected: "port number, past the default ones".to_string(),
+                })?,
+            Some(port) => {
+                u16::try_from(port).map_err(|_| ConfigError::IncorrectType {
+                    path: yaml_path.clone().key("port").unknown(),
+                    expected: "port number".to_string(),
+                })?
+            }
+        };
+
+        Ok(ProbeSession {
+            nodes,
+            interval,
+            multiplier,
+            port,
+        })
     }
 }
 
-// ==== impl Kind ====
+// ==== impl NotifyRule ====
 
-impl FromYamlConfig for Kind {
+impl FromYamlConfig for NotifyRule {
     fn from_yaml_config(
-        name: &str,
-        kind_config: &Yaml,
-        _kind_ctx: BTreeMap<&str, &str>,
+        index: &str,
+        notify_config: &Yaml,
+        _notify_ctx: BTreeMap<&str, &str>,
     ) -> NetResult<Self> {
-        let kind_config = if let Yaml::Hash(kind_config) = kind_config {
-            kind_config
-        } else {
+        let yaml_path = YamlPath::new().key("notify").key(index);
+
+        let Yaml::Hash(notify_config) = notify_config else {
             return Err(ConfigError::IncorrectType {
-                path: YamlPath::new().key("kinds").key(name).unknown(),
+                path: yaml_path.clone().unknown(),
                 expected: "hash".to_string(),
             }
             .into());
         };
 
-        let mut kind = Kind::new(name);
+        // A single event or a list of them.
+        let on_path = yaml_path.clone().key("on");
+        let events = match notify_config.get(&Yaml::String(String::from("on")))
+        {
+            Some(Yaml::String(event)) => vec![event.as_str()],
+            Some(Yaml::Array(events)) => events
+                .iter()
+                .map(|event| event.as_str())
+                .collect::<Option<Vec<_>>>()
+                .unwrap_or_default(),
+            None => {
+                return Err(ConfigError::MissingField {
+                    path: on_path.unknown(),
+                }
+                .into());
+            }
+            Some(_) => vec![],
+        };
+        let on = events
+            .into_iter()
+            .map(Trigger::from_name)
+            .collect::<Option<Vec<_>>>()
+            .filter(|on| !on.is_empty())
+            .ok_or_else(|| ConfigError::IncorrectType {
+                path: on_path.clone().unknown(),
+                expected: "daemon-crashed, daemon-exited, link-down, \
+                    probe-down or test-failed"
+                    .to_string(),
+            })?;
 
-        // Kind volume configurations.
-        match kind_config.get(&Yaml::String(String::from("volumes"))) {
-            Some(Yaml::Array(volumes_configs)) => {
-                for volume_config in volumes_configs {
-                    let volume = Volume::from_yaml_config(
-                        kind.name.as_str(),
-                        volume_config,
-                        BTreeMap::new(),
-                    )?;
-                    kind.volumes.push(volume);
+        let nodes =
+            match notify_config.get(&Yaml::String(String::from("nodes"))) {
+                Some(Yaml::Array(nodes)) => nodes
+                    .iter()
+                    .map(|node| node.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| ConfigError::IncorrectType {
+                        path: yaml_path.clone().key("nodes").unknown(),
+                        expected: "list of node names".to_string(),
+                    })?,
+                Some(Yaml::Null) | None => vec![],
+                Some(_) => {
+                    return Err(ConfigError::IncorrectType {
+                        path: yaml_path.clone().key("nodes").unknown(),
+                        expected: "list of node names".to_string(),
+                    }
+                    .into());
                 }
+            };
+
+        let webhook = notify_config.get(&Yaml::String(String::from("webhook")));
+        let exec = notify_config.get(&Yaml::String(String::from("exec")));
+        let action = match (webhook, exec) {
+            (Some(Yaml::String(url)), None) => {
+                NotifyAction::Webhook(url.clone())
             }
-            Some(Yaml::Null) | None => { /* Ignore volume configs. */ }
-            Some(_) => {
+            (None, Some(Yaml::String(cmd))) => NotifyAction::Exec(cmd.clone()),
+            _ => {
                 return Err(ConfigError::IncorrectType {
-                    path: YamlPath::new()
-                        .key("kinds")
-                        .key(name)
-                        .key("volumes")
-                        .unknown(),
-                    expected: "hash".to_string(),
+                    path: yaml_path.clone().unknown(),
+                    expected: "either 'webhook' or 'exec' string".to_string(),
                 }
                 .into());
             }
-        }
+        };
 
-        match kind_config.get(&Yaml::String(String::from("scripts"))) {
-            Some(Yaml::Array(script_configs)) => {
-                for script in script_configs {
-                    if let Yaml::String(path) = script {
-                        kind.scripts.push(path.clone());
-                    }
+        Ok(NotifyRule { on, nodes, action })
+    }
+}
+
+// ==== impl Impairment ====
+
+impl FromYamlConfig for Impairment {
+    fn from_yaml_config(
+        name: &str,
+        profile_config: &Yaml,
+        profile_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        // Either a profile, or given inline by a link.
+        let parent = profile_ctx.get("parent").copied().unwrap_or("profiles");
+        let yaml_path = YamlPath::new().key(parent).key(name);
+
+        let Yaml::Hash(profile_config) = profile_config else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+
+        let optional_u32 = |field: &str| -> NetResult<Option<u32>> {
+            match profile_config.get(&Yaml::String(field.to_string())) {
+                Some(Yaml::Null) | None => Ok(None),
+                Some(_) => {
+                    get_u32_field(profile_config, field, &yaml_path).map(Some)
                 }
             }
-            Some(Yaml::Null) | None => {}
-            Some(_) => {
-                return Err(ConfigError::IncorrectType {
-                    path: YamlPath::new()
-                        .key("kinds")
-                        .key(name)
-                        .key("scripts")
-                        .unknown(),
+        };
+        let millis = |field: &str| -> NetResult<Duration> {
+            Ok(Duration::from_millis(
+                optional_u32(field)?.unwrap_or(0).into(),
+            ))
+        };
 
-                    expected: "array".to_string(),
+        let loss = match profile_config.get(&Yaml::String(String::from("loss")))
+        {
+            Some(Yaml::Null) | None => Some(0.0),
+            Some(Yaml::Integer(loss)) => Some(*loss as f64),
+            Some(Yaml::Real(loss)) => loss.parse().ok(),
+            Some(_) => None,
+        };
+        let Some(loss) = loss.filter(|loss| (0.0..=100.0).contains(loss))
+        else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().key("loss").unknown(),
+                expected: "percentage".to_string(),
+            }
+            .into());
+        };
+        // `bandwidth` is another name for the rate.
+        let rate_field = match profile_config
+            .contains_key(&Yaml::String(String::from("bandwidth")))
+        {
+            true => "bandwidth",
+            false => "rate",
+        };
+        let rate = match optional_u32(rate_field)? {
+            Some(0) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.clone().key(rate_field).unknown(),
+                    expected: "positive integer".to_string(),
                 }
                 .into());
             }
+            rate => rate,
+        };
+
+        Ok(Impairment {
+            delay: millis("delay")?,
+            jitter: millis("jitter")?,
+            loss,
+            rate,
+        })
+    }
+}
+
+// ==== impl FlowImpairment ====
+
+impl FromYamlConfig for FlowImpairment {
+    fn from_yaml_config(
+        index: &str,
+        flow_config: &Yaml,
+        _flow_ctx: BTreeMap<&str, &str>,
+    ) -> NetResult<Self> {
+        let yaml_path = YamlPath::new().key("links").key("flows").key(index);
+
+        let Yaml::Hash(flow_config) = flow_config else {
+            return Err(ConfigError::IncorrectType {
+                path: yaml_path.clone().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+        let profile = get_string_field(flow_config, "profile")?;
+
+        let match_path = yaml_path.clone().key("match");
+        let match_config =
+            match flow_config.get(&Yaml::String(String::from("match"))) {
+                Some(Yaml::Hash(match_config)) => match_config,
+                None => {
+                    return Err(ConfigError::MissingField {
+                        path: match_path.clone().unknown(),
+                    }
+                    .into());
+                }
+                Some(_) => {
+                    return Err(ConfigError::IncorrectType {
+                        path: match_path.clone().unknown(),
+                        expected: "hash".to_string(),
+                    }
+                    .into());
+                }
+            };
+        let invalid = |field: &str, expected: &str| -> NetError {
+            ConfigError::IncorrectType {
+                path: match_path.clone().key(field).unknown(),
+                expected: expected.to_string(),
+            }
+            .into()
+        };
+        let present = |field: &str| {
+            !matches!(
+                match_config.get(&Yaml::String(field.to_string())),
+                Some(Yaml::Null) | None
+            )
+        };
+
+        let network = |field: &str| -> NetResult<Option<IpNetwork>> {
+            match present(field) {
+                true => get_network_field(match_config, field, &match_path)
+                    .map(Some),
+                false => Ok(None),
+            }
+        };
+        let (src, dst) = (network("src")?, network("dst")?);
+        if let (Some(src), Some(dst)) = (src, dst)
+            && src.is_ipv4() != dst.is_ipv4()
+        {
+            return Err(invalid("dst", "network of the family of src"));
         }
-        Ok(kind)
+
+        let protocol = match present("protocol") {
+            true => Some(get_string_field(match_config, "protocol")?),
+            false => None,
+        };
+        let ipv4 = src.or(dst).map(|network| network.is_ipv4());
+        match (protocol.as_deref(), ipv4) {
+            (None | Some("tcp" | "udp" | "sctp"), _)
+            | (Some("icmp"), None | Some(true))
+            | (Some("icmpv6"), None | Some(false)) => {}
+            _ => {
+                return Err(invalid(
+                    "protocol",
+                    "tcp, udp, sctp, icmp or icmpv6 matching the addresses",
+                ));
+            }
+        }
+
+        let port = |field: &str| -> NetResult<Option<u16>> {
+            if !present(field) {
+                return Ok(None);
+            }
+            if !matches!(protocol.as_deref(), Some("tcp" | "udp" | "sctp")) {
+                return Err(invalid("protocol", "tcp, udp or sctp with ports"));
+            }
+            let port = get_u32_field(match_config, field, &match_path)?;
+            u16::try_from(port)
+                .map(Some)
+                .map_err(|_| invalid(field, "port number"))
+        };
+        let (src_port, dst_port, port) =
+            (port("src-port")?, port("dst-port")?, port("port")?);
+
+        let dscp = match present("dscp") {
+            true => match get_u32_field(match_config, "dscp", &match_path)? {
+                dscp @ 0..64 => Some(dscp as u8),
+                _ => return Err(invalid("dscp", "integer below 64")),
+            },
+            false => None,
+        };
+
+        Ok(FlowImpairment {
+            matching: FlowMatch {
+                src,
+                dst,
+                protocol,
+                src_port,
+                dst_port,
+                port,
+                dscp,
+            },
+            profile,
+            impairment: None,
+        })
     }
 }
 
-// Get field value from Yaml Hash.
-// TODO: have this better positioned with other YANG configs methods.
-pub(crate) fn get_string_field(
+fn get_u32_field(
     config: &Hash,
     field: &str,
-) -> NetResult<String> {
-    let field_value =
-        config
-            .get(&Yaml::String(field.to_string()))
-            .ok_or_else(|| ConfigError::MissingField {
-                path: YamlPath::new().key(field).unknown(),
-            })?;
+    yaml_path: &YamlPath,
+) -> NetResult<u32> {
+    match config.get(&Yaml::String(field.to_string())) {
+        Some(Yaml::Integer(value)) => u32::try_from(*value).map_err(|_| {
+            ConfigError::IncorrectType {
+                path: yaml_path.clone().key(field).unknown(),
+                expected: "u32".to_string(),
+            }
+            .into()
+        }),
+        Some(_) => Err(ConfigError::IncorrectType {
+            path: yaml_path.clone().key(field).unknown(),
+            expected: "u32".to_string(),
+        }
+        .into()),
+        None => Err(ConfigError::MissingField {
+            path: yaml_path.clone().key(field).unknown(),
+        }
+        .into()),
+    }
+}
 
-    match field_value {
-        Yaml::String(value) => Ok(value.to_string()),
-        _ => Err(ConfigError::IncorrectType {
-            path: YamlPath::new().key(field).unknown(),
-            expected: "string".to_string(),
+// A routing table, by number or as "main", "local" or "default".
+fn get_table_field(
+    config: &Hash,
+    field: &str,
+    yaml_path: &YamlPath,
+) -> NetResult<Option<u32>> {
+    let table = match config.get(&Yaml::String(field.to_string())) {
+        None => return Ok(None),
+        Some(Yaml::Integer(table)) => u32::try_from(*table).ok(),
+        Some(Yaml::String(name)) => match name.as_str() {
+            "default" => Some(253),
+            "main" => Some(254),
+            "local" => Some(255),
+            _ => None,
+        },
+        Some(_) => None,
+    };
+    // Table 0 stands for none to the kernel.
+    match table.filter(|table| *table != 0) {
+        Some(table) => Ok(Some(table)),
+        None => Err(ConfigError::IncorrectType {
+            path: yaml_path.clone().key(field).unknown(),
+            expected: "table number, main, local or default".to_string(),
+        }
+        .into()),
+    }
+}
+
+fn get_network_field(
+    config: &Hash,
+    field: &str,
+    yaml_path: &YamlPath,
+) -> NetResult<IpNetwork> {
+    match config.get(&Yaml::String(field.to_string())) {
+        Some(Yaml::String(value)) => value.parse().map_err(|_| {
+            ConfigError::IncorrectType {
+                path: yaml_path.clone().key(field).unknown(),
+                expected: "ip network".to_string(),
+            }
+            .into()
+        }),
+        Some(_) => Err(ConfigError::IncorrectType {
+            path: yaml_path.clone().key(field).unknown(),
+            expected: "ip network".to_string(),
+        }
+        .into()),
+        None => Err(ConfigError::MissingField {
+            path: yaml_path.clone().key(field).unknown(),
+        }
+        .into()),
+    }
+}
+
+fn get_addr_field(
+    config: &Hash,
+    field: &str,
+    yaml_path: &YamlPath,
+) -> NetResult<IpAddr> {
+    match config.get(&Yaml::String(field.to_string())) {
+        Some(Yaml::String(value)) => value.parse().map_err(|_| {
+            ConfigError::IncorrectType {
+                path: yaml_path.clone().key(field).unknown(),
+                expected: "ip address".to_string(),
+            }
+            .into()
+        }),
+        Some(_) => Err(ConfigError::IncorrectType {
+            path: yaml_path.clone().key(field).unknown(),
+            expected: "ip address".to_string(),
+        }
+        .into()),
+        None => Err(ConfigError::MissingField {
+            path: yaml_path.clone().key(field).unknown(),
         }
         .into()),
     }
@@ -0,0 +1,85 @@
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Duration;
+
+use nix::sys::signal::Signal;
+
+// ==== struct PidFd ====
+
+/// A file descriptor referring to a process (see pidfd_open(2)).
+///
+/// Unlike a bare PID, it keeps designating the same process once that one
+/// exits: signals sent through it never reach a process that reused the PID,
+/// and it becomes readable when the process terminates.
+#[derive(Debug)]
+pub(crate) struct PidFd {
+    pid: i32,
+    fd: OwnedFd,
+}
+
+impl PidFd {
+    pub(crate) fn open(pid: i32) -> io::Result<Self> {
+        // SAFETY: pidfd_open takes a PID and flags, and returns a new file
+        // descriptor we take ownership of.
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just returned by the kernel and is owned by no
+        // one else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+        Ok(Self { pid, fd })
+    }
+
+    pub(crate) fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    pub(crate) fn signal(&self, signal: Signal) -> io::Result<()> {
+        // SAFETY: the file descriptor is valid for the lifetime of `self`,
+        // no siginfo is passed.
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_pidfd_send_signal,
+                self.fd.as_raw_fd(),
+                signal as libc::c_int,
+                std::ptr::null::<libc::siginfo_t>(),
+                0,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Whether the process has terminated, without blocking.
+    pub(crate) fn has_exited(&self) -> bool {
+        self.wait_exit(Duration::ZERO)
+    }
+
+    /// Waits up to `timeout` for the process to terminate, returning whether
+    /// it did.
+    pub(crate) fn wait_exit(&self, timeout: Duration) -> bool {
+        let mut pollfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+        loop {
+            // SAFETY: `pollfd` points to a single valid pollfd structure.
+            let ret = unsafe { libc::poll(&mut pollfd, 1, timeout) };
+            match ret {
+                0 => return false,
+                ret if ret > 0 => return pollfd.revents & libc::POLLIN != 0,
+                _ if io::Error::last_os_error().kind()
+                    == io::ErrorKind::Interrupted =>
+                {
+                    continue;
+                }
+                _ => return false,
+            }
+        }
+    }
+}
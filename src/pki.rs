@@ -0,0 +1,206 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::NetError;
+use crate::node::{FileDrop, FileSource};
+use crate::{NetResult, devices_ns_dir, run_dir};
+
+/// Where the lab CA is kept on the host, for clients outside the topology
+/// to trust it.
+pub(crate) fn ca_dir() -> String {
+    format!("{}/pki", run_dir())
+}
+
+pub(crate) const DEFAULT_VALIDITY: u32 = 365;
+
+// Where the routers find their TLS material.
+const CA_CERT_DST: &str = "/etc/netgen/pki/ca.crt";
+const CERT_DST: &str = "/etc/netgen/pki/node.crt";
+const KEY_DST: &str = "/etc/netgen/pki/node.key";
+
+// ==== struct Pki ====
+
+/// Lab certificate authority, issuing a certificate to each router for TLS
+/// experiments (gNMI, BGP over TLS...):
+///
+/// ```yaml
+/// pki:
+///   validity: 30
+///   routers: [ rt1, rt2 ]
+/// ```
+///
+/// `validity` is in days, 365 by default, and every router gets a
+/// certificate unless `routers` lists them. `openssl` needs to be installed
+/// on the host.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Pki {
+    pub(crate) validity: u32,
+    pub(crate) routers: Option<Vec<String>>,
+}
+
+impl Default for Pki {
+    fn default() -> Self {
+        Self {
+            validity: DEFAULT_VALIDITY,
+            routers: None,
+        }
+    }
+}
+
+// ==== struct NodeCert ====
+
+/// Certificate issued to a router by the lab CA, valid for its name and
+/// addresses.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct NodeCert {
+    pub(crate) validity: u32,
+    pub(crate) addresses: Vec<IpAddr>,
+}
+
+impl NodeCert {
+    /// Placeholders of the router's scripts, along with the paths they are
+    /// replaced by.
+    pub(crate) const PLACEHOLDERS: [(&str, &str); 3] = [
+        ("<TLS-CA>", CA_CERT_DST),
+        ("<TLS-CERT>", CERT_DST),
+        ("<TLS-KEY>", KEY_DST),
+    ];
+
+    /// The CA certificate and the router's own certificate and key, as
+    /// files to materialize in the router. They only exist once issued.
+    pub(crate) fn files(router_name: &str) -> Vec<FileDrop> {
+        let dir = cert_dir(router_name);
+        [
+            (format!("{}/ca.crt", ca_dir()), CA_CERT_DST, 0o644),
+            (format!("{dir}/node.crt"), CERT_DST, 0o644),
+            (format!("{dir}/node.key"), KEY_DST, 0o600),
+        ]
+        .into_iter()
+        .map(|(src, dst, mode)| FileDrop {
+            dst: dst.to_string(),
+            source: FileSource::Path(src),
+            mode: Some(mode),
+        })
+        .collect()
+    }
+
+    /// Issues the router's certificate into its device directory, creating
+    /// the lab CA first if needed. The CA is kept across runs.
+    pub(crate) fn issue(&self, router_name: &str) -> NetResult<()> {
+        let ca_cert = format!("{}/ca.crt", ca_dir());
+        let ca_key = format!("{}/ca.key", ca_dir());
+        if !Path::new(&ca_cert).exists() {
+            create_dir(&ca_dir())?;
+            openssl(
+                &[
+                    "req",
+                    "-x509",
+                    "-newkey",
+                    "ec",
+                    "-pkeyopt",
+                    "ec_paramgen_curve:prime256v1",
+                    "-nodes",
+                    "-keyout",
+                    &ca_key,
+                    "-out",
+                    &ca_cert,
+                    "-days",
+                    "3650",
+                    "-subj",
+                    "/CN=netgen lab CA",
+                ],
+                "the lab CA",
+            )?;
+        }
+
+        let dir = cert_dir(router_name);
+        create_dir(&dir)?;
+        let (key, csr, cert, ext) = (
+            format!("{dir}/node.key"),
+            format!("{dir}/node.csr"),
+            format!("{dir}/node.crt"),
+            format!("{dir}/node.ext"),
+        );
+        let mut names = vec![format!("DNS:{router_name}")];
+        names.extend(self.addresses.iter().map(|addr| format!("IP:{addr}")));
+        fs::write(
+            &ext,
+            format!(
+                "subjectAltName = {}\nextendedKeyUsage = serverAuth, \
+                    clientAuth\n",
+                names.join(", ")
+            ),
+        )
+        .map_err(|err| {
+            NetError::BasicError(format!("Unable to write {ext}: {err:?}"))
+        })?;
+
+        let what = format!("the certificate of {router_name}");
+        openssl(
+            &[
+                "req",
+                "-new",
+                "-newkey",
+                "ec",
+                "-pkeyopt",
+                "ec_paramgen_curve:prime256v1",
+                "-nodes",
+                "-keyout",
+                &key,
+                "-out",
+                &csr,
+                "-subj",
+                &format!("/CN={router_name}"),
+            ],
+            &what,
+        )?;
+        openssl(
+            &[
+                "x509",
+                "-req",
+                "-in",
+                &csr,
+                "-CA",
+                &ca_cert,
+                "-CAkey",
+                &ca_key,
+                "-CAcreateserial",
+                "-out",
+                &cert,
+                "-days",
+                &self.validity.to_string(),
+                "-extfile",
+                &ext,
+            ],
+            &what,
+        )
+    }
+}
+
+fn cert_dir(router_name: &str) -> String {
+    format!("{}/{router_name}/pki", devices_ns_dir())
+}
+
+fn create_dir(dir: &str) -> NetResult<()> {
+    fs::create_dir_all(dir).map_err(|err| {
+        NetError::BasicError(format!("Unable to create {dir}: {err:?}"))
+    })
+}
+
+fn openssl(args: &[&str], what: &str) -> NetResult<()> {
+    let output =
+        Command::new("openssl").args(args).output().map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to run openssl for {what}: {err:?}"
+            ))
+        })?;
+    if !output.status.success() {
+        return Err(NetError::BasicError(format!(
+            "Unable to generate {what}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use std::fmt::{self, Write as _};
+
+use crate::link::Link;
+use crate::report::json_string;
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+pub(crate) const RESET: &str = "\x1b[0m";
+
+// ==== enum Action ====
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    // Applied by deleting and creating the component again.
+    Update,
+    Delete,
+}
+
+impl Action {
+    pub(crate) fn symbol(&self) -> char {
+        match self {
+            Action::Create => '+',
+            Action::Update => '~',
+            Action::Delete => '-',
+        }
+    }
+
+    pub(crate) fn color(&self) -> &'static str {
+        match self {
+            Action::Create => GREEN,
+            Action::Update => YELLOW,
+            Action::Delete => RED,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+        };
+        f.write_str(action)
+    }
+}
+
+// ==== struct Plan ====
+
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub action: Action,
+    // "router", "switch" or "link".
+    pub kind: String,
+    pub name: String,
+}
+
+/// Changes bringing the running topology in line with the topology file,
+/// nodes first and links last.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, action: Action, kind: &str, name: &str) {
+        self.changes.push(Change {
+            action,
+            kind: kind.to_string(),
+            name: name.to_string(),
+        });
+    }
+
+    pub fn count(&self, action: Action) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| change.action == action)
+            .count()
+    }
+
+    pub(crate) fn node_action(&self, name: &str) -> Option<Action> {
+        self.changes
+            .iter()
+            .find(|change| change.kind != "link" && change.name == name)
+            .map(|change| change.action)
+    }
+
+    pub(crate) fn link_action(&self, link: &Link) -> Option<Action> {
+        let name = link_name(link);
+        self.changes
+            .iter()
+            .find(|change| change.kind == "link" && change.name == name)
+            .map(|change| change.action)
+    }
+
+    /// Renders the plan as a list of changes followed by a summary, the
+    /// changes being colored when `color` is set.
+    pub fn render(&self, color: bool) -> String {
+        let mut out = String::new();
+        if self.is_empty() {
+            let _ = writeln!(out, "No changes.");
+            return out;
+        }
+
+        let _ = writeln!(out, "netgen will perform the following actions:");
+        let _ = writeln!(out);
+        for change in &self.changes {
+            let (start, end) = match color {
+                true => (change.action.color(), RESET),
+                false => ("", ""),
+            };
+            let _ = writeln!(
+                out,
+                "  {start}{} {} {}{end} ({})",
+                change.action.symbol(),
+                change.kind,
+                change.name,
+                change.action
+            );
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "Plan: {} to create, {} to update, {} to delete.",
+            self.count(Action::Create),
+            self.count(Action::Update),
+            self.count(Action::Delete)
+        );
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let changes: Vec<String> = self
+            .changes
+            .iter()
+            .map(|change| {
+                format!(
+                    r#"    {{"action": "{}", "kind": {}, "name": {}}}"#,
+                    change.action,
+                    json_string(&change.kind),
+                    json_string(&change.name)
+                )
+            })
+            .collect();
+        format!("{{\n  \"changes\": [\n{}\n  ]\n}}\n", changes.join(",\n"))
+    }
+}
+
+/// Name of the link in plans, the same whichever end is the source.
+pub(crate) fn link_name(link: &Link) -> String {
+    let (src, dst) = (link.src.to_string(), link.dst.to_string());
+    match src <= dst {
+        true => format!("{src} <-> {dst}"),
+        false => format!("{dst} <-> {src}"),
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use ipnetwork::IpNetwork;
+use tracing::debug;
+
+use crate::NetResult;
+use crate::error::{ConfigError, NetError};
+use crate::link::Link;
+use crate::node::Node;
+
+/// Prefix of the interface addresses asking for an address from a pool,
+/// e.g. `pool:p2p`.
+pub(crate) const POOL_ADDR_PREFIX: &str = "pool:";
+
+// ==== struct AddressPool ====
+
+/// A named range interface addresses are allocated from.
+///
+/// Subnets of `length` are carved out of `prefix` in order, skipping those
+/// overlapping an excluded range, a reservation or an address configured
+/// statically in the topology.
+#[derive(Debug, Clone)]
+pub(crate) struct AddressPool {
+    pub(crate) name: String,
+    pub(crate) prefix: IpNetwork,
+    pub(crate) length: u8,
+    pub(crate) exclude: Vec<IpNetwork>,
+    // "<node>:<iface>" -> address always given to that interface.
+    pub(crate) reservations: BTreeMap<String, IpNetwork>,
+    // Index of the next subnet to hand out.
+    next: u128,
+}
+
+// ==== impl AddressPool ====
+
+impl AddressPool {
+    pub(crate) fn new(name: &str, prefix: IpNetwork, length: u8) -> Self {
+        Self {
+            name: name.to_string(),
+            prefix,
+            length,
+            exclude: vec![],
+            reservations: BTreeMap::new(),
+            next: 0,
+        }
+    }
+
+    /// Checks that the pool is internally consistent.
+    pub(crate) fn validate(&self) -> NetResult<()> {
+        let max_len = max_prefix_len(&self.prefix);
+        if self.length < self.prefix.prefix() || self.length > max_len {
+            return Err(self.invalid(format!(
+                "length /{} does not fit in {}",
+                self.length, self.prefix
+            )));
+        }
+        for network in &self.exclude {
+            if network.is_ipv4() != self.prefix.is_ipv4() {
+                return Err(self.invalid(format!(
+                    "excluded range {network} is not of the pool's family"
+                )));
+            }
+        }
+        for (iface, addr) in &self.reservations {
+            if !self.prefix.contains(addr.ip()) {
+                return Err(self.invalid(format!(
+                    "reservation {addr} for {iface} is outside {}",
+                    self.prefix
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Next free subnet, `taken` holding the addresses used outside the pool
+    // and the subnets allocated so far, which it is added to.
+    fn allocate(&mut self, taken: &mut Vec<IpNetwork>) -> NetResult<IpNetwork> {
+        let (base, last) = bounds(&self.prefix);
+        let step = 1u128
+            .checked_shl(u32::from(max_prefix_len(&self.prefix) - self.length));
+
+        loop {
+            let start = match step {
+                Some(step) => self
+                    .next
+                    .checked_mul(step)
+                    .and_then(|offset| offset.checked_add(base)),
+                // An IPv6 /0 pool of /0 subnets holds a single one.
+                None => (self.next == 0).then_some(base),
+            };
+            let start = start
+                .filter(|start| *start <= last)
+                .ok_or_else(|| ConfigError::PoolExhausted(self.name.clone()))?;
+            self.next += 1;
+
+            // Single addresses never use the network or broadcast address
+            // of an IPv4 pool.
+            let max_len = max_prefix_len(&self.prefix);
+            if self.prefix.is_ipv4()
+                && self.length == max_len
+                && self.prefix.prefix() < max_len - 1
+                && (start == base || start == last)
+            {
+                continue;
+            }
+
+            let subnet = network(start, self.length, self.prefix.is_ipv4());
+            let in_use_until = self
+                .exclude
+                .iter()
+                .chain(self.reservations.values())
+                .chain(taken.iter())
+                .filter(|other| overlaps(&subnet, other))
+                .map(|other| bounds(other).1)
+                .max();
+            let Some(in_use_until) = in_use_until else {
+                taken.push(subnet);
+                return Ok(subnet);
+            };
+            // Straight past the ranges in use rather than a subnet at a time,
+            // which would never end for e.g. an excluded /64 in a pool of
+            // /128s.
+            if let Some(step) = step {
+                let past = ((in_use_until - base) / step).saturating_add(1);
+                self.next = self.next.max(past);
+            }
+        }
+    }
+
+    /// First host of the next free subnet, for an interface which is not at
+    /// the end of a link sharing the subnet.
+    pub(crate) fn allocate_host(
+        &mut self,
+        taken: &mut Vec<IpNetwork>,
+    ) -> NetResult<IpNetwork> {
+        let subnet = self.allocate(taken)?;
+        let offset = if host_count(&subnet) <= 2 { 0 } else { 1 };
+        nth_host(&subnet, offset)
+            .ok_or_else(|| ConfigError::PoolExhausted(self.name.clone()).into())
+    }
+
+    fn invalid(&self, reason: String) -> NetError {
+        ConfigError::InvalidPool {
+            name: self.name.clone(),
+            reason,
+        }
+        .into()
+    }
+}
+
+/// Gives an address to every interface asking for one from a pool.
+///
+/// Reservations are honored first. The two ends of a link asking for the
+/// same pool then share a subnet, links being walked in the order they are
+/// configured. Any other interface (e.g. a loopback) gets a subnet of its
+/// own.
+pub(crate) fn assign(
+    pools: &mut [AddressPool],
+    nodes: &mut BTreeMap<String, Node>,
+    links: &[Link],
+) -> NetResult<()> {
+    // Static addresses are left alone, and never handed out again, no more
+    // than the subnets allocated, pools possibly overlapping.
+    let mut taken: Vec<IpNetwork> = nodes
+        .values()
+        .flat_map(|node| node.interfaces())
+        .flat_map(|iface| iface.addresses.iter())
+        .map(|addr| host(addr.ip()))
+        .collect();
+
+    // (node, iface, pool) still waiting for an address.
+    let mut pending: Vec<(String, String, String)> = vec![];
+    for (node_name, node) in nodes.iter_mut() {
+        for iface in node.interfaces_mut() {
+            for pool_name in &iface.pools {
+                let pool = pools
+                    .iter()
+                    .find(|pool| &pool.name == pool_name)
+                    .ok_or_else(|| {
+                        ConfigError::UnknownPool(pool_name.clone())
+                    })?;
+                let key = format!("{node_name}:{}", iface.name);
+                match pool.reservations.get(&key) {
+                    Some(addr) => iface.addresses.push(*addr),
+                    None => pending.push((
+                        node_name.clone(),
+                        iface.name.clone(),
+                        pool_name.clone(),
+                    )),
+                }
+            }
+        }
+    }
+
+    let mut take = |node: &str, iface: &str, pool: &str| {
+        pending
+            .iter()
+            .position(|entry| {
+                entry.0 == node && entry.1 == iface && entry.2 == pool
+            })
+            .map(|idx| pending.remove(idx))
+            .is_some()
+    };
+
+    let mut assigned: Vec<(String, String, IpNetwork)> = vec![];
+    for link in links {
+        let src_key = link.src.to_string();
+        let dst_key = link.dst.to_string();
+
+        for pool in pools.iter_mut() {
+            let src = take(
+                link.src.node.as_str(),
+                link.src.iface.as_str(),
+                &pool.name,
+            );
+            let dst = take(
+                link.dst.node.as_str(),
+                link.dst.iface.as_str(),
+                &pool.name,
+            );
+            if !src && !dst {
+                continue;
+            }
+
+            // When the other end has a reservation, join its subnet.
+            let reserved = pool
+                .reservations
+                .get(&src_key)
+                .or_else(|| pool.reservations.get(&dst_key))
+                .copied();
+            let subnet = match reserved {
+                Some(addr) => IpNetwork::new(addr.network(), addr.prefix())
+                    .unwrap_or(addr),
+                None => pool.allocate(&mut taken)?,
+            };
+
+            // Point-to-point subnets have no network/broadcast addresses.
+            let first = if host_count(&subnet) <= 2 { 0 } else { 1 };
+            let mut hosts = (first..)
+                .map_while(|offset| nth_host(&subnet, offset))
+                .filter(|addr| Some(addr.ip()) != reserved.map(|r| r.ip()));
+            for (wanted, end) in [(src, &link.src), (dst, &link.dst)] {
+                if wanted {
+                    let addr = hosts.next().ok_or_else(|| {
+                        ConfigError::PoolExhausted(pool.name.clone())
+                    })?;
+                    assigned.push((
+                        end.node.to_string(),
+                        end.iface.to_string(),
+                        addr,
+                    ));
+                }
+            }
+        }
+    }
+
+    for (node, iface, pool_name) in pending {
+        let Some(pool) = pools.iter_mut().find(|pool| pool.name == pool_name)
+        else {
+            continue;
+        };
+        let addr = pool.allocate_host(&mut taken)?;
+        assigned.push((node, iface, addr));
+    }
+
+    for (node_name, iface_name, addr) in assigned {
+        debug!(node = %node_name, iface = %iface_name, %addr, "pool address");
+        if let Some(iface) = nodes.get_mut(&node_name).and_then(|node| {
+            node.interfaces_mut()
+                .iter_mut()
+                .find(|iface| iface.name == iface_name)
+        }) {
+            iface.addresses.push(addr);
+        }
+    }
+    Ok(())
+}
+
+/// First address after `addr` which is not `taken`, with the same prefix
+/// length.
+///
+/// When the subnet of `addr` holds more than a point-to-point pair, the
+/// address stays in it, leaving out the network and broadcast addresses of
+/// IPv4 subnets. Otherwise, e.g. for a /32 loopback, any following address
+/// does.
+pub(crate) fn next_free(
+    addr: &IpNetwork,
+    taken: &[IpNetwork],
+) -> Option<IpNetwork> {
+    let is_ipv4 = addr.is_ipv4();
+    let value = match addr.ip() {
+        IpAddr::V4(ip) => u128::from(u32::from(ip)),
+        IpAddr::V6(ip) => u128::from(ip),
+    };
+    let (first, last) = match host_count(addr) {
+        count if count > 2 && is_ipv4 => {
+            let (first, last) = bounds(addr);
+            (first + 1, last - 1)
+        }
+        count if count > 2 => bounds(addr),
+        _ if is_ipv4 => (0, u128::from(u32::MAX)),
+        _ => (0, u128::MAX),
+    };
+
+    // Wraps around to the start of the subnet.
+    let after = (value.saturating_add(1)..=last).filter(|_| value < last);
+    let before = first..value;
+    after
+        .chain(before)
+        .map(|candidate| to_addr(candidate, is_ipv4))
+        .find(|candidate| !taken.iter().any(|other| other.ip() == *candidate))
+        .and_then(|candidate| IpNetwork::new(candidate, addr.prefix()).ok())
+}
+
+fn max_prefix_len(network: &IpNetwork) -> u8 {
+    if network.is_ipv4() { 32 } else { 128 }
+}
+
+// First and last address of the network, as integers.
+fn bounds(network: &IpNetwork) -> (u128, u128) {
+    let host_bits = u32::from(max_prefix_len(network) - network.prefix());
+    let first = match network.network() {
+        IpAddr::V4(addr) => u128::from(u32::from(addr)),
+        IpAddr::V6(addr) => u128::from(addr),
+    };
+    let span = 1u128.checked_shl(host_bits).map_or(u128::MAX, |n| n - 1);
+    (first, first.saturating_add(span))
+}
+
+fn overlaps(a: &IpNetwork, b: &IpNetwork) -> bool {
+    if a.is_ipv4() != b.is_ipv4() {
+        return false;
+    }
+    let (a_first, a_last) = bounds(a);
+    let (b_first, b_last) = bounds(b);
+    a_first <= b_last && b_first <= a_last
+}
+
+fn host_count(network: &IpNetwork) -> u128 {
+    let (first, last) = bounds(network);
+    (last - first).saturating_add(1)
+}
+
+fn nth_host(network: &IpNetwork, n: u128) -> Option<IpNetwork> {
+    let (first, last) = bounds(network);
+    let addr = first.checked_add(n).filter(|addr| *addr <= last)?;
+    let addr = to_addr(addr, network.is_ipv4());
+    IpNetwork::new(addr, network.prefix()).ok()
+}
+
+fn network(start: u128, length: u8, is_ipv4: bool) -> IpNetwork {
+    // `length` has been validated against the family.
+    IpNetwork::new(to_addr(start, is_ipv4), length)
+        .unwrap_or_else(|_| host(to_addr(start, is_ipv4)))
+}
+
+fn host(addr: IpAddr) -> IpNetwork {
+    IpNetwork::from(addr)
+}
+
+fn to_addr(value: u128, is_ipv4: bool) -> IpAddr {
+    if is_ipv4 {
+        IpAddr::V4(Ipv4Addr::from(value as u32))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(value))
+    }
+}
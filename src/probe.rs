@@ -0,0 +1,136 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::{devices_ns_dir, events};
+
+pub(crate) const DEFAULT_INTERVAL: Duration = Duration::from_millis(300);
+pub(crate) const DEFAULT_MULTIPLIER: u32 = 3;
+// Of the first session, the next ones taking the next ports.
+pub(crate) const DEFAULT_PORT: u16 = 13784;
+
+// Prefixes every probe, followed by the name of the node sending it.
+const PROBE_MAGIC: &str = "netgen-probe";
+
+// ==== struct ProbeSession ====
+
+/// Lightweight liveness probes between two routers, for those which do not
+/// run BFD themselves.
+///
+/// ```yaml
+/// probes:
+///   - between: [ rt1, rt2 ]
+///     interval: 100
+///     multiplier: 3
+/// ```
+///
+/// Both ends send a UDP probe every `interval` milliseconds (300 by
+/// default) to the first address of the other, and declare the session
+/// down once `multiplier` intervals (3 by default) go by without hearing
+/// from it.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ProbeSession {
+    pub(crate) nodes: [String; 2],
+    pub(crate) interval: Duration,
+    pub(crate) multiplier: u32,
+    pub(crate) port: u16,
+}
+
+// ==== struct Probe ====
+
+/// One end of a `ProbeSession`, run by the router it belongs to.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Probe {
+    pub(crate) peer: String,
+    pub(crate) peer_address: IpAddr,
+    pub(crate) interval: Duration,
+    pub(crate) multiplier: u32,
+    pub(crate) port: u16,
+}
+
+/// State of a probe session as last reported by one of its ends.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProbeState {
+    pub peer: String,
+    pub up: bool,
+    // Unix time in milliseconds of the last transition.
+    pub since: u128,
+}
+
+impl Probe {
+    /// Probes the peer until killed, from within the router's namespaces.
+    ///
+    /// Transitions are recorded as events, and the current state is kept in
+    /// a file under the router's directory for `status` to read.
+    pub(crate) fn run(&self, node: &str) -> std::io::Result<()> {
+        let unspecified = match self.peer_address {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = UdpSocket::bind(SocketAddr::new(unspecified, self.port))?;
+        let peer_addr = SocketAddr::new(self.peer_address, self.port);
+        let probe = format!("{PROBE_MAGIC} {node}");
+        let expected = format!("{PROBE_MAGIC} {}", self.peer);
+        let detect_time = self.interval * self.multiplier;
+        let subject = format!("{node} -> {}", self.peer);
+
+        let mut up = false;
+        self.save_state(node, up);
+        let mut last_heard: Option<Instant> = None;
+        let mut next_probe = Instant::now();
+        let mut buf = [0u8; 256];
+        loop {
+            let now = Instant::now();
+            if now >= next_probe {
+                // The peer might not be reachable yet, keep on probing.
+                let _ = socket.send_to(probe.as_bytes(), peer_addr);
+                next_probe = now + self.interval;
+            }
+
+            let wait = next_probe.saturating_duration_since(Instant::now());
+            socket
+                .set_read_timeout(Some(wait.max(Duration::from_millis(1))))?;
+            // Probes come from whichever address routing picks on the peer,
+            // it is told apart by its name instead.
+            if let Ok((len, _)) = socket.recv_from(&mut buf)
+                && buf[..len] == *expected.as_bytes()
+            {
+                last_heard = Some(Instant::now());
+            }
+
+            let heard = last_heard
+                .is_some_and(|last_heard| last_heard.elapsed() < detect_time);
+            if heard != up {
+                up = heard;
+                let state = if up { "up" } else { "down" };
+                debug!(%node, peer = %self.peer, %state, "probe session");
+                events::record("probe", &subject, state);
+                self.save_state(node, up);
+            }
+        }
+    }
+
+    /// State last reported by the router's end of the session, if it ever
+    /// ran.
+    pub(crate) fn state(&self, node: &str) -> Option<ProbeState> {
+        let contents = fs::read_to_string(self.state_path(node)).ok()?;
+        let (state, since) = contents.trim().split_once(' ')?;
+        Some(ProbeState {
+            peer: self.peer.clone(),
+            up: state == "up",
+            since: since.parse().ok()?,
+        })
+    }
+
+    fn save_state(&self, node: &str, up: bool) {
+        let state = if up { "up" } else { "down" };
+        let contents = format!("{state} {}\n", events::unix_millis());
+        let _ = fs::write(self.state_path(node), contents);
+    }
+
+    fn state_path(&self, node: &str) -> String {
+        format!("{}/{node}/probe-{}", devices_ns_dir(), self.peer)
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt;
+
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::NetResult;
+use crate::events::unix_millis;
+use crate::report::json_string;
+use crate::state::TopologyState;
+use crate::topology::Topology;
+
+// Initial hash values and round constants of SHA-256, FIPS 180-4.
+const SHA256_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c,
+    0x1f83d9ab, 0x5be0cd19,
+];
+const SHA256_ROUNDS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1,
+    0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+    0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+    0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+    0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+    0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+    0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+    0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+    0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// ==== struct Provenance ====
+
+/// What an artifact was produced from: the topology, its exact contents,
+/// the netgen which ran it and when it was started. Recorded in the state
+/// file, and carried over to the reports, diffs and graphs, so that results
+/// can be traced back to the lab definition that produced them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    // Session of the topology, which names it.
+    pub topology: String,
+    // "sha256:<hex>" of the topology file, once rendered.
+    pub hash: String,
+    pub netgen: String,
+    // Milliseconds since the Unix epoch, for running topologies only.
+    pub started: Option<u128>,
+}
+
+impl Provenance {
+    /// Provenance of `topology`, as parsed.
+    pub fn of(topology: &Topology) -> Self {
+        Self {
+            topology: topology.session().to_string(),
+            hash: format!(
+                "sha256:{}",
+                sha256_hex(topology.source().as_bytes())
+            ),
+            netgen: env!("CARGO_PKG_VERSION").to_string(),
+            started: None,
+        }
+    }
+
+    /// Provenance of `topology`, started now.
+    pub(crate) fn started(topology: &Topology) -> Self {
+        Self {
+            started: Some(unix_millis()),
+            ..Self::of(topology)
+        }
+    }
+
+    /// Provenance of `topology` replacing the `recorded` one of the running
+    /// topology, which is still started when it was.
+    pub(crate) fn updated(recorded: Option<Self>, topology: &Topology) -> Self {
+        match recorded.and_then(|recorded| recorded.started) {
+            Some(started) => Self {
+                started: Some(started),
+                ..Self::of(topology)
+            },
+            None => Self::started(topology),
+        }
+    }
+
+    /// Provenance of the running topology, if any.
+    pub fn running() -> NetResult<Option<Self>> {
+        Ok(TopologyState::load()?.and_then(|state| state.provenance))
+    }
+
+    pub(crate) fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        let field = |name: &str| yaml[name].as_str().map(str::to_string);
+        Some(Self {
+            topology: field("topology")?,
+            hash: field("hash")?,
+            netgen: field("netgen")?,
+            started: yaml["started"]
+                .as_i64()
+                .and_then(|started| u128::try_from(started).ok()),
+        })
+    }
+
+    pub(crate) fn to_yaml(&self) -> Yaml {
+        let mut doc = Hash::new();
+        for (key, value) in [
+            ("topology", &self.topology),
+            ("hash", &self.hash),
+            ("netgen", &self.netgen),
+        ] {
+            doc.insert(
+                Yaml::String(key.to_string()),
+                Yaml::String(value.clone()),
+            );
+        }
+        if let Some(started) = self.started.and_then(|s| i64::try_from(s).ok())
+        {
+            doc.insert(
+                Yaml::String("started".to_string()),
+                Yaml::Integer(started),
+            );
+        }
+        Yaml::Hash(doc)
+    }
+
+    /// The provenance as a JSON object.
+    pub fn to_json(&self) -> String {
+        let started = self
+            .started
+            .map_or_else(|| "null".to_string(), |started| started.to_string());
+        format!(
+            "{{\"topology\": {}, \"hash\": {}, \"netgen\": {}, \
+                \"started\": {started}}}",
+            json_string(&self.topology),
+            json_string(&self.hash),
+            json_string(&self.netgen)
+        )
+    }
+
+    /// The provenance as `(name, value)` pairs, e.g. for JUnit properties.
+    pub fn properties(&self) -> Vec<(&'static str, String)> {
+        let mut properties = vec![
+            ("netgen.topology", self.topology.clone()),
+            ("netgen.hash", self.hash.clone()),
+            ("netgen.version", self.netgen.clone()),
+        ];
+        if let Some(started) = self.started {
+            properties.push(("netgen.started", started.to_string()));
+        }
+        properties
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "topology {} ({}), netgen {}",
+            self.topology, self.hash, self.netgen
+        )?;
+        if let Some(started) = self.started {
+            write!(f, ", started at {started}")?;
+        }
+        Ok(())
+    }
+}
+
+// SHA-256 of `data`, in lowercase hexadecimal as `sha256sum` prints it.
+pub(crate) fn sha256_hex(data: impl AsRef<[u8]>) -> String {
+    let data = data.as_ref();
+    let mut message = data.to_vec();
+    let bits = (data.len() as u64).wrapping_mul(8);
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend(bits.to_be_bytes());
+
+    let mut hash = SHA256_INIT;
+    for block in message.chunks_exact(64) {
+        let mut words = [0u32; 64];
+        for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+            *word =
+                u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..64 {
+            let s0 = words[i - 15].rotate_right(7)
+                ^ words[i - 15].rotate_right(18)
+                ^ (words[i - 15] >> 3);
+            let s1 = words[i - 2].rotate_right(17)
+                ^ words[i - 2].rotate_right(19)
+                ^ (words[i - 2] >> 10);
+            words[i] = words[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(words[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = hash;
+        for (round, word) in SHA256_ROUNDS.iter().zip(words) {
+            let s1 =
+                e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(*round)
+                .wrapping_add(word);
+            let s0 =
+                a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            (h, g, f, e) = (g, f, e, d.wrapping_add(t1));
+            (d, c, b, a) = (c, b, a, t1.wrapping_add(t2));
+        }
+        for (value, add) in hash.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+    hash.iter().map(|value| format!("{value:08x}")).collect()
+}
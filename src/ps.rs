@@ -0,0 +1,159 @@
+use std::fs;
+
+use crate::coredump::USER_HZ;
+use crate::state::TopologyState;
+use crate::table::{self, Align};
+use crate::{NetResult, process_in_device};
+
+// ==== struct NodeProcess ====
+
+/// A process running inside a node, as found in `/proc`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeProcess {
+    pub pid: i32,
+    pub ppid: i32,
+    pub command: String,
+    // Share of a CPU used since the process started, in percent, as `ps`
+    // computes it.
+    pub cpu: f64,
+    // Resident memory, in bytes.
+    pub rss: u64,
+    // Share of the host memory resident, in percent.
+    pub mem: f64,
+    // Name of the daemon netgen started and tracks as this process, if any.
+    pub daemon: Option<String>,
+}
+
+/// The processes running in the PID namespace of `node`, by PID, the
+/// daemons netgen started being named after them.
+pub fn list(node: &str) -> NetResult<Vec<NodeProcess>> {
+    let tracked = TopologyState::load()?
+        .map(|state| state.processes)
+        .unwrap_or_default();
+    let uptime = fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|uptime| uptime.split_whitespace().next()?.parse().ok())
+        .unwrap_or(0.0);
+    let mem_total = mem_total();
+    // SAFETY: sysconf has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64;
+
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return Ok(vec![]);
+    };
+    let mut processes: Vec<NodeProcess> = procs
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<i32>().ok())
+        .filter(|pid| process_in_device(node, *pid))
+        .filter_map(|pid| {
+            let stat = Stat::read(pid)?;
+            let elapsed = uptime - stat.start_time as f64 / USER_HZ as f64;
+            let cpu_time = (stat.utime + stat.stime) as f64 / USER_HZ as f64;
+            let rss = stat.rss_pages * page_size;
+            let daemon = tracked
+                .iter()
+                .find(|record| {
+                    record.node == node
+                        && record.pid == pid
+                        && record.start_time == stat.start_time
+                })
+                .map(|record| record.name.clone());
+            Some(NodeProcess {
+                pid,
+                ppid: stat.ppid,
+                command: command(pid)
+                    .unwrap_or_else(|| format!("[{}]", stat.comm)),
+                cpu: match elapsed > 0.0 {
+                    true => 100.0 * cpu_time / elapsed,
+                    false => 0.0,
+                },
+                rss,
+                mem: match mem_total {
+                    0 => 0.0,
+                    total => 100.0 * rss as f64 / total as f64,
+                },
+                daemon,
+            })
+        })
+        .collect();
+    processes.sort_by_key(|process| process.pid);
+    Ok(processes)
+}
+
+/// Renders the processes as an aligned table, resident memory in KiB.
+pub fn render(processes: &[NodeProcess]) -> String {
+    let headers = ["PID", "PPID", "%CPU", "%MEM", "RSS", "DAEMON", "COMMAND"];
+    let rows: Vec<[String; 7]> = processes
+        .iter()
+        .map(|process| {
+            [
+                process.pid.to_string(),
+                process.ppid.to_string(),
+                format!("{:.1}", process.cpu),
+                format!("{:.1}", process.mem),
+                (process.rss / 1024).to_string(),
+                process.daemon.clone().unwrap_or_else(|| "-".to_string()),
+                process.command.clone(),
+            ]
+        })
+        .collect();
+    table::render(headers, [Align::Left; 7], &rows)
+}
+
+// ==== struct Stat ====
+
+// The fields of `/proc/<pid>/stat` the listing needs.
+struct Stat {
+    comm: String,
+    ppid: i32,
+    utime: u64,
+    stime: u64,
+    start_time: u64,
+    rss_pages: u64,
+}
+
+impl Stat {
+    fn read(pid: i32) -> Option<Self> {
+        let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+        // The command name may contain spaces and parentheses, fields are
+        // counted from the last closing parenthesis: the state is the 3rd
+        // field overall.
+        let (head, fields) = stat.rsplit_once(')')?;
+        let (_, comm) = head.split_once('(')?;
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let field = |nth: usize| fields.get(nth - 3)?.parse::<u64>().ok();
+        Some(Self {
+            comm: comm.to_string(),
+            ppid: fields.get(1)?.parse().ok()?,
+            utime: field(14)?,
+            stime: field(15)?,
+            start_time: field(22)?,
+            rss_pages: field(24)?,
+        })
+    }
+}
+
+// The command line of the process, None for kernel threads which have none.
+fn command(pid: i32) -> Option<String> {
+    let cmdline = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let args: Vec<String> = cmdline
+        .split(|&byte| byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect();
+    (!args.is_empty()).then(|| args.join(" "))
+}
+
+// Memory of the host, in bytes, or 0 if unknown.
+fn mem_total() -> u64 {
+    let Ok(meminfo) = fs::read_to_string("/proc/meminfo") else {
+        return 0;
+    };
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))
+        .and_then(|total| total.trim().strip_suffix("kB"))
+        .and_then(|total| total.trim().parse::<u64>().ok())
+        .map_or(0, |total| total * 1024)
+}
@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+use tracing::{debug, debug_span};
+
+use crate::NetResult;
+use crate::cancel::CancelToken;
+use crate::netem::Impairment;
+use crate::topology::Topology;
+
+// ==== struct Ramp ====
+
+/// Scenario step changing the impairment of a link gradually, from one
+/// value to another, to emulate degrading physical media:
+///
+/// ```yaml
+/// scenario:
+///   - ramp:
+///       link: [rt1:eth0, rt2:eth0]
+///       loss: [0, 30]       # percent
+///       delay: [5, 200]     # milliseconds, as is jitter
+///       rate: [100000, 2000]  # kbit/s
+///       duration: 300
+///       interval: 5         # seconds between changes, 1 by default
+/// ```
+///
+/// What is not ramped keeps the value the link's profile gives it. Both
+/// ends of the link are changed in place every `interval`, linearly, and
+/// are left at the last values once `duration` elapsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ramp {
+    // Ends of the link, as "<node>:<iface>".
+    pub src: String,
+    pub dst: String,
+    pub delay: Option<(Duration, Duration)>,
+    pub jitter: Option<(Duration, Duration)>,
+    pub loss: Option<(f64, f64)>,
+    pub rate: Option<(u32, u32)>,
+    pub duration: Duration,
+    pub interval: Duration,
+}
+
+impl Ramp {
+    pub(crate) const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Plays the ramp against the running `topology`, until its duration
+    /// elapsed or `cancel` is cancelled, the link being left as it got.
+    pub fn run(
+        &self,
+        topology: &Topology,
+        cancel: &CancelToken,
+    ) -> NetResult<()> {
+        let ramp_span = debug_span!("ramp", src = %self.src, dst = %self.dst);
+        let _span_guard = ramp_span.enter();
+
+        let link = topology.link_between(&self.src, &self.dst)?;
+        let base = link.impairment.clone().unwrap_or_default();
+        let start = Instant::now();
+        loop {
+            let progress = match self.duration.is_zero() {
+                true => 1.0,
+                false => (start.elapsed().as_secs_f64()
+                    / self.duration.as_secs_f64())
+                .min(1.0),
+            };
+            let impairment = self.at(&base, progress);
+            debug!(progress, ?impairment, "ramping");
+            topology.reimpair_link(link, &impairment)?;
+
+            let left = self.duration.saturating_sub(start.elapsed());
+            if progress >= 1.0 || cancel.sleep(self.interval.min(left)).is_err()
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // The impairment `progress` (from 0 to 1) into the ramp, `base` giving
+    // what is not ramped.
+    fn at(&self, base: &Impairment, progress: f64) -> Impairment {
+        let lerp = |(from, to): (f64, f64)| from + (to - from) * progress;
+        // Between `from` and `to`, so never out of the range of a Duration.
+        let time = |(from, to): (Duration, Duration)| match to >= from {
+            true => from + (to - from).mul_f64(progress),
+            false => from - (from - to).mul_f64(progress),
+        };
+        Impairment {
+            delay: self.delay.map_or(base.delay, time),
+            jitter: self.jitter.map_or(base.jitter, time),
+            // Rounded to a thousandth of a percent.
+            loss: self.loss.map_or(base.loss, |loss| {
+                (lerp(loss) * 1000.0).round() / 1000.0
+            }),
+            rate: match self.rate {
+                Some((from, to)) => {
+                    Some(lerp((from.into(), to.into())).round() as u32)
+                }
+                None => base.rate,
+            },
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut changes = vec![];
+        let millis = |duration: Duration| duration.as_millis();
+        if let Some((from, to)) = self.delay {
+            changes.push(format!("delay {}ms->{}ms", millis(from), millis(to)));
+        }
+        if let Some((from, to)) = self.jitter {
+            changes.push(format!(
+                "jitter {}ms->{}ms",
+                millis(from),
+                millis(to)
+            ));
+        }
+        if let Some((from, to)) = self.loss {
+            changes.push(format!("loss {from}%->{to}%"));
+        }
+        if let Some((from, to)) = self.rate {
+            changes.push(format!("rate {from}kbit->{to}kbit"));
+        }
+        format!(
+            "ramp {} - {} {} over {:?}",
+            self.src,
+            self.dst,
+            changes.join(", "),
+            self.duration
+        )
+    }
+}
@@ -0,0 +1,234 @@
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+use nix::net::if_::if_nametoindex;
+use nix::sched::{CloneFlags, setns};
+
+use crate::error::{LinkError, NamespaceError, NetError};
+use crate::packet::{Matcher, Packet};
+use crate::{NamespaceHandle, NetResult, with_run_dir};
+
+// Largest frame read at once: a jumbo frame along with its headers.
+const MAX_FRAME_LEN: usize = 9216 + 18;
+
+// ==== struct RawSocket ====
+
+/// A packet socket bound to an interface of a node (see packet(7)).
+///
+/// Frames are sent and received whole, Ethernet header included, without
+/// the node's network stack getting in the way of crafted or malformed
+/// ones. Received frames include those the node sends itself.
+#[derive(Debug)]
+pub struct RawSocket {
+    device: String,
+    iface: String,
+    ifindex: u32,
+    fd: OwnedFd,
+}
+
+impl RawSocket {
+    /// Opens a packet socket on the `iface` interface of the device.
+    ///
+    /// The socket is created from a short-lived thread moved into the
+    /// device's network namespace, where it stays bound once the thread is
+    /// gone. The calling thread is left where it is.
+    pub fn open(device: &str, iface: &str) -> NetResult<Self> {
+        let namespace = NamespaceHandle::new(Some(device));
+        let net_file = File::open(namespace.net_path()).map_err(|err| {
+            NamespaceError::FileOpen {
+                path: namespace.net_path().to_string(),
+                source: err,
+            }
+        })?;
+
+        std::thread::scope(|scope| {
+            scope
+                .spawn(with_run_dir(|| -> NetResult<Self> {
+                    setns(net_file.as_fd(), CloneFlags::CLONE_NEWNET).map_err(
+                        |err| NamespaceError::Entry {
+                            device: device.to_string(),
+                            source: err,
+                        },
+                    )?;
+                    let ifindex = if_nametoindex(iface).map_err(|err| {
+                        LinkError::NoInterface {
+                            iface: iface.to_string(),
+                            source: err,
+                        }
+                    })?;
+                    let fd = bound_socket(ifindex)
+                        .map_err(|err| raw_error(device, iface, "bind", err))?;
+                    Ok(Self {
+                        device: device.to_string(),
+                        iface: iface.to_string(),
+                        ifindex,
+                        fd,
+                    })
+                }))
+                .join()
+                .unwrap_or_else(|_| {
+                    Err(raw_error(
+                        device,
+                        iface,
+                        "open",
+                        io::Error::other("socket thread panicked"),
+                    )
+                    .into())
+                })
+        })
+    }
+
+    /// Sends `frame` as is, starting with its Ethernet header.
+    pub fn send(&self, frame: &[u8]) -> NetResult<()> {
+        let addr = link_addr(self.ifindex);
+        // SAFETY: `frame` and `addr` are valid for the lengths passed, and
+        // the file descriptor is valid for the lifetime of `self`.
+        let ret = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                frame.as_ptr().cast(),
+                frame.len(),
+                0,
+                (&raw const addr).cast(),
+                size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(self.error("send", io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for the next frame, returning None if none
+    /// came.
+    pub fn recv(&self, timeout: Duration) -> NetResult<Option<Vec<u8>>> {
+        let deadline = Instant::now() + timeout;
+        let mut pollfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let remaining =
+                i32::try_from(remaining.as_millis()).unwrap_or(i32::MAX);
+            // SAFETY: `pollfd` points to a single valid pollfd structure.
+            match unsafe { libc::poll(&mut pollfd, 1, remaining) } {
+                0 => return Ok(None),
+                ret if ret > 0 => break,
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(self.error("poll", err));
+                    }
+                }
+            }
+        }
+
+        let mut frame = vec![0u8; MAX_FRAME_LEN];
+        // SAFETY: `frame` is valid for writes of its length, and the file
+        // descriptor is valid for the lifetime of `self`.
+        let len = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                frame.as_mut_ptr().cast(),
+                frame.len(),
+                0,
+            )
+        };
+        if len < 0 {
+            return Err(self.error("recv", io::Error::last_os_error()));
+        }
+        frame.truncate(len as usize);
+        Ok(Some(frame))
+    }
+
+    /// Waits up to `timeout` for a frame matching `matcher`, skipping the
+    /// others. Fails if none came.
+    pub fn expect(
+        &self,
+        matcher: &Matcher,
+        timeout: Duration,
+    ) -> NetResult<Packet> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Some(frame) = self.recv(remaining)? else {
+                return Err(NetError::BasicError(format!(
+                    "No matching packet on {}:{} within {timeout:?}",
+                    self.device, self.iface
+                )));
+            };
+            if let Some(packet) = Packet::parse(&frame)
+                && matcher.matches(&packet)
+            {
+                return Ok(packet);
+            }
+        }
+    }
+
+    fn error(&self, operation: &str, source: io::Error) -> NetError {
+        raw_error(&self.device, &self.iface, operation, source).into()
+    }
+}
+
+// Creates a packet socket receiving every protocol, bound to the interface.
+fn bound_socket(ifindex: u32) -> io::Result<OwnedFd> {
+    let protocol = (libc::ETH_P_ALL as u16).to_be();
+    // SAFETY: plain socket creation, the returned descriptor is checked.
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_PACKET,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            protocol.into(),
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just returned by the kernel and is owned by no one
+    // else.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let addr = link_addr(ifindex);
+    // SAFETY: `addr` is a valid sockaddr_ll for the length passed.
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            (&raw const addr).cast(),
+            size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(fd)
+}
+
+fn link_addr(ifindex: u32) -> libc::sockaddr_ll {
+    libc::sockaddr_ll {
+        sll_family: libc::AF_PACKET as u16,
+        sll_protocol: (libc::ETH_P_ALL as u16).to_be(),
+        sll_ifindex: ifindex as i32,
+        sll_hatype: 0,
+        sll_pkttype: 0,
+        sll_halen: 0,
+        sll_addr: [0; 8],
+    }
+}
+
+fn raw_error(
+    device: &str,
+    iface: &str,
+    operation: &str,
+    source: io::Error,
+) -> LinkError {
+    LinkError::RawSocket {
+        device: device.to_string(),
+        iface: iface.to_string(),
+        operation: operation.to_string(),
+        source,
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::time::Duration;
+
+use crate::NetResult;
+use crate::error::NetError;
+use crate::provenance::Provenance;
+
+// ==== struct TestCase ====
+
+/// Outcome of a single assertion or check.
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub name: String,
+    pub duration: Duration,
+    pub failure: Option<String>,
+    // Node the case is about, if a single one.
+    pub node: Option<String>,
+}
+
+impl TestCase {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+// ==== struct TestReport ====
+
+/// A named collection of test cases, which can be rendered into formats
+/// that CI dashboards understand.
+#[derive(Debug, Clone, Default)]
+pub struct TestReport {
+    pub name: String,
+    pub cases: Vec<TestCase>,
+    // What the topology tested was, recorded along with the results.
+    pub provenance: Option<Provenance>,
+}
+
+impl TestReport {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            cases: vec![],
+            provenance: None,
+        }
+    }
+
+    pub fn failures(&self) -> usize {
+        self.cases.iter().filter(|case| !case.passed()).count()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.cases.iter().map(|case| case.duration).sum()
+    }
+
+    /// Moves the cases about the devices under test `duts` first, the
+    /// others keeping their order.
+    pub fn lead_with(&mut self, duts: &[String]) {
+        self.cases.sort_by_key(|case| {
+            !case.node.as_ref().is_some_and(|node| duts.contains(node))
+        });
+    }
+
+    /// Renders the report as a JUnit XML document.
+    pub fn to_junit(&self) -> String {
+        let mut xml = String::new();
+        let _ = writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = writeln!(
+            xml,
+            r#"<testsuites name="netgen" tests="{}" failures="{}" time="{:.3}">"#,
+            self.cases.len(),
+            self.failures(),
+            self.duration().as_secs_f64()
+        );
+        let _ = writeln!(
+            xml,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" time="{:.3}">"#,
+            xml_escape(&self.name),
+            self.cases.len(),
+            self.failures(),
+            self.duration().as_secs_f64()
+        );
+        if let Some(provenance) = &self.provenance {
+            let _ = writeln!(xml, "    <properties>");
+            for (name, value) in provenance.properties() {
+                let _ = writeln!(
+                    xml,
+                    r#"      <property name="{name}" value="{}"/>"#,
+                    xml_escape(&value)
+                );
+            }
+            let _ = writeln!(xml, "    </properties>");
+        }
+
+        for case in &self.cases {
+            let _ = write!(
+                xml,
+                r#"    <testcase classname="{}" name="{}" time="{:.3}""#,
+                xml_escape(&self.name),
+                xml_escape(&case.name),
+                case.duration.as_secs_f64()
+            );
+            match &case.failure {
+                Some(message) => {
+                    let _ = writeln!(xml, ">");
+                    let _ = writeln!(
+                        xml,
+                        r#"      <failure message="{}"/>"#,
+                        xml_escape(message)
+                    );
+                    let _ = writeln!(xml, "    </testcase>");
+                }
+                None => {
+                    let _ = writeln!(xml, "/>");
+                }
+            }
+        }
+
+        let _ = writeln!(xml, "  </testsuite>");
+        let _ = writeln!(xml, "</testsuites>");
+        xml
+    }
+
+    /// Renders the report in the Test Anything Protocol (version 13).
+    pub fn to_tap(&self) -> String {
+        let mut tap = String::new();
+        let _ = writeln!(tap, "TAP version 13");
+        let _ = writeln!(tap, "1..{}", self.cases.len());
+        // Diagnostics, which TAP consumers ignore.
+        if let Some(provenance) = &self.provenance {
+            let _ = writeln!(tap, "# {provenance}");
+        }
+
+        for (idx, case) in self.cases.iter().enumerate() {
+            // '#' starts a directive in TAP, keep it out of descriptions.
+            let name = case.name.replace('#', "\\#");
+            match &case.failure {
+                None => {
+                    let _ = writeln!(tap, "ok {} - {name}", idx + 1);
+                }
+                Some(message) => {
+                    let _ = writeln!(tap, "not ok {} - {name}", idx + 1);
+                    let _ = writeln!(tap, "  ---");
+                    let _ = writeln!(tap, "  message: {message:?}");
+                    let _ = writeln!(
+                        tap,
+                        "  duration_ms: {}",
+                        case.duration.as_millis()
+                    );
+                    let _ = writeln!(tap, "  ...");
+                }
+            }
+        }
+        tap
+    }
+
+    /// Writes the report, rendered in `format`, to `path`.
+    pub fn write(&self, format: ReportFormat, path: &str) -> NetResult<()> {
+        let contents = match format {
+            ReportFormat::Junit => self.to_junit(),
+            ReportFormat::Tap => self.to_tap(),
+        };
+        fs::write(path, contents).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to write {format} report {path}: {err:?}"
+            ))
+        })
+    }
+}
+
+// ==== enum ReportFormat ====
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Junit,
+    Tap,
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Junit => write!(f, "JUnit"),
+            Self::Tap => write!(f, "TAP"),
+        }
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Quotes and escapes `value` as a JSON string.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                escaped.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
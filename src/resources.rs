@@ -0,0 +1,299 @@
+use std::fmt;
+use std::fs;
+
+use tracing::debug;
+
+use crate::NetResult;
+use crate::error::NetError;
+use crate::node::Node;
+use crate::topology::Topology;
+
+// Ports a bridge takes at most, BR_MAX_PORTS in the kernel.
+const BRIDGE_MAX_PORTS: u64 = 1024;
+// Rough footprints, erring on the generous side: a router's namespaces and
+// interfaces, and a daemon once it learned a few routes.
+const ROUTER_MEMORY: u64 = 2 << 20;
+const DAEMON_MEMORY: u64 = 16 << 20;
+// Files netgen holds open on top of those of each router, and per router:
+// its namespaces and netlink sockets.
+const BASE_FILES: u64 = 64;
+const ROUTER_FILES: u64 = 4;
+// Files each daemon opens, its sockets included.
+const DAEMON_FILES: u64 = 32;
+// Mounts of each router: its network and PID namespaces, and procfs.
+const ROUTER_MOUNTS: u64 = 3;
+
+// ==== struct Needs ====
+
+/// What bringing up a topology takes from the host, roughly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Needs {
+    pub routers: u64,
+    // Interfaces of the routers and ports of the switches, i.e. veth ends,
+    // and the dummies and bridges.
+    pub interfaces: u64,
+    // Processes started in the routers: those holding their namespaces,
+    // and the daemons.
+    pub processes: u64,
+    pub daemons: u64,
+    // Files netgen itself holds open while setting up the links.
+    pub netgen_files: u64,
+    // Files open host-wide once the daemons run.
+    pub files: u64,
+    pub mounts: u64,
+    // Neighbor entries, per address family, once every router heard from
+    // every other one on its links and LANs.
+    pub neighbors: u64,
+    // In bytes.
+    pub memory: u64,
+    // Switches with more ports than a bridge takes, with their ports.
+    pub oversized_switches: Vec<(String, u64)>,
+}
+
+impl Needs {
+    pub fn of(topology: &Topology) -> Self {
+        let mut needs = Self::default();
+        for node in topology.nodes() {
+            match node {
+                Node::Router(router) => {
+                    let daemons = router.frr_daemons().len() as u64
+                        + u64::from(router.ntp.is_some())
+                        + u64::from(router.ssh.is_some());
+                    needs.routers += 1;
+                    needs.daemons += daemons;
+                    needs.interfaces += router.interfaces.len() as u64;
+                    needs.mounts += ROUTER_MOUNTS + router.volumes.len() as u64;
+                }
+                Node::Switch(switch) => {
+                    let ports = switch.interfaces.len() as u64;
+                    needs.interfaces += ports + 1;
+                    // Each router on the LAN hears from all the others.
+                    needs.neighbors += ports * ports.saturating_sub(1);
+                    if ports > BRIDGE_MAX_PORTS {
+                        needs
+                            .oversized_switches
+                            .push((switch.name.clone(), ports));
+                    }
+                }
+            }
+        }
+        // Both ends of a point-to-point link hear from each other, those
+        // towards switches were counted with the LANs.
+        needs.neighbors += 2 * topology
+            .links()
+            .iter()
+            .filter(|link| {
+                link.ends().iter().all(|end| {
+                    matches!(
+                        topology.get_node(&end.node),
+                        Some(Node::Router(_))
+                    )
+                })
+            })
+            .count() as u64;
+        needs.processes = needs.routers + needs.daemons;
+        needs.netgen_files = BASE_FILES + needs.routers * ROUTER_FILES;
+        needs.files = needs.netgen_files + needs.daemons * DAEMON_FILES;
+        needs.memory =
+            needs.routers * ROUTER_MEMORY + needs.daemons * DAEMON_MEMORY;
+        needs
+    }
+}
+
+// ==== struct Shortfall ====
+
+/// A host limit the topology would run into, e.g. a sysctl set too low.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortfall {
+    // Sysctl or resource limit, e.g. "fs.inotify.max_user_instances".
+    pub limit: String,
+    pub current: u64,
+    pub needed: u64,
+    tune: Option<Tune>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tune {
+    // Written to /proc/sys.
+    Sysctl,
+    // The soft limit on open files of this process, raised up to the hard
+    // one, which the processes it starts inherit.
+    OpenFiles,
+}
+
+impl Shortfall {
+    /// Whether netgen can raise the limit itself, see `tune`.
+    pub fn is_tunable(&self) -> bool {
+        self.tune.is_some()
+    }
+
+    /// Raises the limit to what is needed, with a quarter more as
+    /// headroom.
+    pub fn tune(&self) -> NetResult<()> {
+        let target = self.needed + self.needed / 4;
+        let tune_err = |reason: String| {
+            NetError::BasicError(format!(
+                "Unable to raise {} to {target}: {reason}",
+                self.limit
+            ))
+        };
+        match self.tune {
+            Some(Tune::Sysctl) => {
+                fs::write(sysctl_path(&self.limit), target.to_string())
+                    .map_err(|err| tune_err(err.to_string()))
+            }
+            Some(Tune::OpenFiles) => {
+                let mut limit = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                // SAFETY: `limit` is a valid rlimit.
+                if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) }
+                    != 0
+                {
+                    return Err(tune_err("getrlimit failed".to_string()));
+                }
+                if limit.rlim_max < self.needed {
+                    return Err(tune_err(format!(
+                        "the hard limit is {}",
+                        limit.rlim_max
+                    )));
+                }
+                limit.rlim_cur = target.min(limit.rlim_max);
+                // SAFETY: `limit` is a valid rlimit.
+                if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0
+                {
+                    return Err(tune_err("setrlimit failed".to_string()));
+                }
+                Ok(())
+            }
+            None => Err(tune_err("not something netgen tunes".to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Shortfall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {}, the topology needs about {}",
+            self.limit, self.current, self.needed
+        )
+    }
+}
+
+/// The host limits the topology would run into once brought up, which
+/// otherwise make it fail halfway through with ENOSPC or ENFILE.
+///
+/// Limits which cannot be read are skipped.
+pub fn check(topology: &Topology) -> Vec<Shortfall> {
+    let needs = Needs::of(topology);
+    debug!(?needs, "resources needed");
+
+    let mut shortfalls = vec![];
+    let mut sysctl = |name: &str, needed: u64| {
+        if let Some(current) = read_sysctl(name)
+            && current < needed
+        {
+            shortfalls.push(Shortfall {
+                limit: name.to_string(),
+                current,
+                needed,
+                tune: Some(Tune::Sysctl),
+            });
+        }
+    };
+    // Daemons watching files take an inotify instance each, all of them
+    // counting against root's.
+    sysctl("fs.inotify.max_user_instances", needs.daemons + 8);
+    // The first field of fs.file-nr is the files open already.
+    sysctl(
+        "fs.file-max",
+        needs.files + read_sysctl("fs.file-nr").unwrap_or(0),
+    );
+    sysctl("fs.mount-max", needs.mounts + mount_count());
+    // Neighbor tables overflow past gc_thresh3, host-wide.
+    sysctl("net.ipv4.neigh.default.gc_thresh3", needs.neighbors);
+    sysctl("net.ipv6.neigh.default.gc_thresh3", needs.neighbors);
+    sysctl("kernel.pid_max", needs.processes + process_count());
+
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` is a valid rlimit.
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } == 0
+        && limit.rlim_cur < needs.netgen_files
+    {
+        shortfalls.push(Shortfall {
+            limit: "RLIMIT_NOFILE".to_string(),
+            current: limit.rlim_cur,
+            needed: needs.netgen_files,
+            tune: Some(Tune::OpenFiles),
+        });
+    }
+
+    if let Some(available) = mem_available()
+        && available < needs.memory
+    {
+        shortfalls.push(Shortfall {
+            limit: "MemAvailable".to_string(),
+            current: available,
+            needed: needs.memory,
+            tune: None,
+        });
+    }
+    for (switch, ports) in needs.oversized_switches {
+        shortfalls.push(Shortfall {
+            limit: format!("ports of bridge {switch}"),
+            current: BRIDGE_MAX_PORTS,
+            needed: ports,
+            tune: None,
+        });
+    }
+    shortfalls
+}
+
+fn sysctl_path(name: &str) -> String {
+    format!("/proc/sys/{}", name.replace('.', "/"))
+}
+
+// The value of a sysctl, the first one of those holding several.
+fn read_sysctl(name: &str) -> Option<u64> {
+    fs::read_to_string(sysctl_path(name))
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn mount_count() -> u64 {
+    fs::read_to_string("/proc/self/mounts")
+        .map_or(0, |mounts| mounts.lines().count() as u64)
+}
+
+fn process_count() -> u64 {
+    fs::read_dir("/proc").map_or(0, |entries| {
+        entries
+            .flatten()
+            .filter(|entry| {
+                entry.file_name().to_string_lossy().parse::<u32>().is_ok()
+            })
+            .count() as u64
+    })
+}
+
+// Memory available to new processes, in bytes.
+fn mem_available() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let kib: u64 = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use ipnetwork::IpNetwork;
+
+use crate::node::AddressFamily;
+
+// ==== struct PolicyRule ====
+
+// A policy routing rule of a router, sending the packets it selects to the
+// lookup of `table`, e.g. for PBR or VRF-lite.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PolicyRule {
+    // Rules are looked up by increasing priority, the kernel picks one
+    // below that of the previous rule without it.
+    pub(crate) priority: Option<u32>,
+    pub(crate) from: Option<IpNetwork>,
+    pub(crate) to: Option<IpNetwork>,
+    pub(crate) fwmark: Option<u32>,
+    pub(crate) iif: Option<String>,
+    pub(crate) oif: Option<String>,
+    pub(crate) table: u32,
+}
+
+impl PolicyRule {
+    // Family of the addresses the rule selects, if it selects any.
+    pub(crate) fn family(&self) -> Option<AddressFamily> {
+        self.from
+            .or(self.to)
+            .map(|network| AddressFamily::of(network.ip()))
+    }
+
+    // Arguments of `ip` adding the rule for `family`.
+    pub(crate) fn args(&self, family: AddressFamily) -> Vec<String> {
+        let family = match family {
+            AddressFamily::Ipv4 => "-4",
+            AddressFamily::Ipv6 => "-6",
+        };
+        let mut args: Vec<String> =
+            [family, "rule", "add"].map(str::to_string).into();
+        if let Some(priority) = self.priority {
+            args.extend(["priority".to_string(), priority.to_string()]);
+        }
+        for (selector, network) in [("from", self.from), ("to", self.to)] {
+            if let Some(network) = network {
+                args.extend([selector.to_string(), network.to_string()]);
+            }
+        }
+        if let Some(fwmark) = self.fwmark {
+            args.extend(["fwmark".to_string(), format!("{fwmark:#x}")]);
+        }
+        for (selector, iface) in [("iif", &self.iif), ("oif", &self.oif)] {
+            if let Some(iface) = iface {
+                args.extend([selector.to_string(), iface.clone()]);
+            }
+        }
+        args.extend(["table".to_string(), self.table.to_string()]);
+        args
+    }
+}
+
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let family = self.family().unwrap_or(AddressFamily::Ipv4);
+        // Without the family and the "rule add" verb.
+        write!(f, "{}", self.args(family)[3..].join(" "))
+    }
+}
@@ -0,0 +1,252 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use yaml_rust2::YamlLoader;
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::NetResult;
+use crate::error::NetError;
+use crate::lab::{config_dir, lock_file, write_yaml};
+use crate::report::json_string;
+use crate::table::{self, Align};
+
+const RUNS_FILE: &str = "runs.yml";
+
+/// Runs kept, the oldest being dropped first.
+pub const RUNS_LEN: usize = 1000;
+
+// ==== struct RunRecord ====
+
+/// A netgen invocation which brought a topology up, changed or tore it
+/// down, or ran a test suite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunRecord {
+    // Subcommand run, e.g. "start" or "test run".
+    pub command: String,
+    // Who ran it, through sudo if they did.
+    pub user: String,
+    // Session of the topology and "sha256:<hex>" of its file, when a
+    // topology was running before or after.
+    pub topology: Option<String>,
+    pub hash: Option<String>,
+    // Milliseconds since the Unix epoch.
+    pub started: u128,
+    pub duration: Duration,
+    // Why it failed, if it did.
+    pub failure: Option<String>,
+}
+
+impl RunRecord {
+    fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        let field = |name: &str| yaml[name].as_str().map(str::to_string);
+        Some(Self {
+            command: field("command")?,
+            user: field("user")?,
+            topology: field("topology"),
+            hash: field("hash"),
+            started: u128::try_from(yaml["started"].as_i64()?).ok()?,
+            duration: Duration::from_millis(
+                u64::try_from(yaml["duration-ms"].as_i64()?).ok()?,
+            ),
+            failure: field("failure"),
+        })
+    }
+
+    fn to_yaml(&self) -> Yaml {
+        let mut doc = Hash::new();
+        let mut insert = |key: &str, value: Yaml| {
+            doc.insert(Yaml::String(key.to_string()), value);
+        };
+        insert("command", Yaml::String(self.command.clone()));
+        insert("user", Yaml::String(self.user.clone()));
+        if let Some(topology) = &self.topology {
+            insert("topology", Yaml::String(topology.clone()));
+        }
+        if let Some(hash) = &self.hash {
+            insert("hash", Yaml::String(hash.clone()));
+        }
+        insert(
+            "started",
+            Yaml::Integer(i64::try_from(self.started).unwrap_or(i64::MAX)),
+        );
+        insert(
+            "duration-ms",
+            Yaml::Integer(
+                i64::try_from(self.duration.as_millis()).unwrap_or(i64::MAX),
+            ),
+        );
+        if let Some(failure) = &self.failure {
+            insert("failure", Yaml::String(failure.clone()));
+        }
+        Yaml::Hash(doc)
+    }
+
+    /// The run as a JSON object.
+    pub fn to_json(&self) -> String {
+        let optional = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map_or_else(|| "null".to_string(), json_string)
+        };
+        format!(
+            "{{\"command\": {}, \"user\": {}, \"topology\": {}, \
+                \"hash\": {}, \"started\": {}, \"duration_ms\": {}, \
+                \"failure\": {}}}",
+            json_string(&self.command),
+            json_string(&self.user),
+            optional(&self.topology),
+            optional(&self.hash),
+            self.started,
+            self.duration.as_millis(),
+            optional(&self.failure)
+        )
+    }
+}
+
+// ==== struct RunHistory ====
+
+/// The runs of netgen, oldest first, persisted to `runs.yml` in the netgen
+/// config directory. netgen runs as root, so on a shared lab server that
+/// is one history for everyone, telling who ran what and when:
+///
+/// ```yaml
+/// - command: start
+///   user: alice
+///   topology: ng1
+///   hash: "sha256:9f2c.."
+///   started: 1760000000000
+///   duration-ms: 5210
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RunHistory {
+    pub runs: Vec<RunRecord>,
+}
+
+impl RunHistory {
+    fn path() -> NetResult<PathBuf> {
+        Ok(config_dir("the run history")?.join(RUNS_FILE))
+    }
+
+    /// Appends `run` to the history on disk, other processes waiting in
+    /// between loading and saving it.
+    pub fn append(run: RunRecord) -> NetResult<()> {
+        let _lock = lock_file(&Self::path()?)?;
+        let mut history = Self::load()?;
+        history.record(run);
+        history.save()
+    }
+
+    /// Loads the history, empty if nothing was run yet.
+    pub fn load() -> NetResult<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to read {}: {err:?}",
+                path.display()
+            ))
+        })?;
+        let invalid = |reason: &str| {
+            NetError::BasicError(format!(
+                "Run history {} is invalid: {reason}",
+                path.display()
+            ))
+        };
+        let docs = YamlLoader::load_from_str(&contents)
+            .map_err(|err| invalid(&err.to_string()))?;
+        let runs = match docs.first() {
+            Some(Yaml::Array(runs)) => runs
+                .iter()
+                .map(RunRecord::from_yaml)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("expected runs"))?,
+            Some(Yaml::Null) | None => vec![],
+            Some(_) => return Err(invalid("expected a list")),
+        };
+        Ok(Self { runs })
+    }
+
+    fn save(&self) -> NetResult<()> {
+        let doc =
+            Yaml::Array(self.runs.iter().map(RunRecord::to_yaml).collect());
+        write_yaml(&Self::path()?, &doc, "the run history")
+    }
+
+    /// Appends `run`, dropping the oldest ones beyond `RUNS_LEN`.
+    pub fn record(&mut self, run: RunRecord) {
+        self.runs.push(run);
+        let excess = self.runs.len().saturating_sub(RUNS_LEN);
+        self.runs.drain(..excess);
+    }
+}
+
+/// Who runs netgen: the user who called sudo, if it was run through it.
+pub fn current_user() -> String {
+    ["SUDO_USER", "USER"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|user| !user.is_empty()))
+        // SAFETY: getuid has no preconditions and cannot fail.
+        .unwrap_or_else(|| format!("uid {}", unsafe { libc::getuid() }))
+}
+
+/// Renders the runs as an aligned table, times in UTC.
+pub fn render(runs: &[RunRecord]) -> String {
+    let headers = [
+        "STARTED", "USER", "COMMAND", "TOPOLOGY", "HASH", "TOOK", "RESULT",
+    ];
+    let rows: Vec<[String; 7]> = runs
+        .iter()
+        .map(|run| {
+            [
+                utc(run.started),
+                run.user.clone(),
+                run.command.clone(),
+                run.topology.clone().unwrap_or_else(|| "-".to_string()),
+                // Enough of the hash to tell topologies apart.
+                run.hash.as_deref().map_or_else(
+                    || "-".to_string(),
+                    |hash| {
+                        hash.trim_start_matches("sha256:")
+                            .chars()
+                            .take(12)
+                            .collect()
+                    },
+                ),
+                format!("{:.1}s", run.duration.as_secs_f64()),
+                match &run.failure {
+                    None => "ok".to_string(),
+                    Some(failure) => format!("failed: {failure}"),
+                },
+            ]
+        })
+        .collect();
+    table::render(headers, [Align::Left; 7], &rows)
+}
+
+// Milliseconds since the Unix epoch as "YYYY-MM-DD HH:MM:SS", in UTC.
+fn utc(millis: u128) -> String {
+    let secs = (millis / 1000) as i64;
+    let (days, time) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Civil date of a day count, after Howard Hinnant's civil_from_days.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, warn};
+use yaml_rust2::YamlLoader;
+use yaml_rust2::yaml::Yaml;
+
+use crate::cancel::CancelToken;
+use crate::error::{ConfigError, NetError, YamlPath};
+use crate::events::unix_millis;
+use crate::suite::{as_hash, get_seconds, get_str};
+use crate::topology::Topology;
+use crate::{NetResult, csv, exec_in_ns, latency, stats};
+
+// How long latencies and rates are sampled for, when the job does not say.
+const DEFAULT_SAMPLE: Duration = Duration::from_secs(1);
+
+/// Columns of the route counts, one row per router and run: when they were
+/// counted in milliseconds since the Unix epoch, and the number of IPv4 and
+/// IPv6 routes in the main table of the router.
+pub const ROUTES_CSV_COLUMNS: &[&str] =
+    &["time_ms", "node", "ipv4_routes", "ipv6_routes"];
+
+// ==== enum Measure ====
+
+/// What a job measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    /// The one-way latency of every enabled link, both ways, see
+    /// `latency::measure`.
+    Latency,
+    /// The number of routes of every router.
+    Routes,
+    /// The traffic rates of every router interface, see `stats::sample`.
+    Rates,
+}
+
+impl Measure {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "latency" => Some(Measure::Latency),
+            "routes" => Some(Measure::Routes),
+            "rates" => Some(Measure::Rates),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Measure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Measure::Latency => write!(f, "latency"),
+            Measure::Routes => write!(f, "routes"),
+            Measure::Rates => write!(f, "rates"),
+        }
+    }
+}
+
+// ==== struct Job ====
+
+/// A measurement taken every `every`, its samples being appended to a CSV
+/// file as a time series.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub measure: Measure,
+    pub every: Duration,
+    // How long each link or interface is sampled for, for latencies and
+    // rates.
+    pub sample: Duration,
+    // Resolved relative to the schedule file.
+    pub csv: PathBuf,
+}
+
+impl Job {
+    // Takes the measurement once, appending it to the CSV file.
+    fn run(&self, topology: &Topology) -> NetResult<()> {
+        let time_ms = unix_millis();
+        let (columns, rows) = match self.measure {
+            Measure::Latency => {
+                let mut rows = String::new();
+                for link in topology.enabled_links() {
+                    let (a, b) = (link.src.to_string(), link.dst.to_string());
+                    let latencies =
+                        latency::measure(topology, &a, &b, self.sample)?;
+                    // Prefixed with the time, as for the other measures.
+                    for row in latency::to_csv(&latencies).lines() {
+                        rows += &format!("{time_ms},{row}\n");
+                    }
+                }
+                let columns: Vec<&str> = ["time_ms"]
+                    .into_iter()
+                    .chain(latency::CSV_COLUMNS.iter().copied())
+                    .collect();
+                (columns, rows)
+            }
+            Measure::Routes => {
+                let mut rows = String::new();
+                for router in topology.router_names() {
+                    let count = |family: &str| -> NetResult<String> {
+                        let output = exec_in_ns(
+                            &router,
+                            "ip",
+                            &[family, "route", "show"],
+                        )?;
+                        let routes = String::from_utf8_lossy(&output.stdout)
+                            .lines()
+                            .filter(|line| !line.trim().is_empty())
+                            .count();
+                        Ok(routes.to_string())
+                    };
+                    rows += &csv::line(&[
+                        time_ms.to_string(),
+                        router.clone(),
+                        count("-4")?,
+                        count("-6")?,
+                    ]);
+                }
+                (ROUTES_CSV_COLUMNS.to_vec(), rows)
+            }
+            Measure::Rates => {
+                let routers = topology.router_names();
+                let rates = stats::sample(topology, &routers, self.sample)?;
+                (stats::CSV_COLUMNS.to_vec(), stats::to_csv(&rates, time_ms))
+            }
+        };
+        csv::write(&self.csv.to_string_lossy(), &columns, &rows, true)
+    }
+}
+
+// ==== struct Schedule ====
+
+/// Recurring measurements of the running topology, run by `netgen
+/// schedule`:
+///
+/// ```yaml
+/// jobs:
+///   - measure: latency
+///     every: 60
+///     csv: latency.csv
+///   - measure: routes
+///     every: 30
+///     csv: routes.csv
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schedule {
+    pub jobs: Vec<Job>,
+}
+
+impl Schedule {
+    pub fn from_yaml_file(path: &str) -> NetResult<Self> {
+        let contents = fs::read_to_string(path).map_err(|err| {
+            NetError::BasicError(format!("Unable to read {path}: {err:?}"))
+        })?;
+        let base_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        Self::from_yaml_str(&contents, base_dir)
+    }
+
+    /// `base_dir` is the directory the CSV files are resolved against.
+    pub fn from_yaml_str(yaml_str: &str, base_dir: &Path) -> NetResult<Self> {
+        let docs = YamlLoader::load_from_str(yaml_str).map_err(|err| {
+            NetError::ConfigError(ConfigError::YamlSyntax(err))
+        })?;
+        let config =
+            as_hash(docs.first().unwrap_or(&Yaml::Null), YamlPath::new())?;
+
+        let path = YamlPath::new().key("jobs");
+        let Some(Yaml::Array(job_configs)) =
+            config.get(&Yaml::String("jobs".to_string()))
+        else {
+            return Err(ConfigError::IncorrectType {
+                path: path.clone().unknown(),
+                expected: "array".to_string(),
+            }
+            .into());
+        };
+
+        let mut jobs = vec![];
+        for job_config in job_configs {
+            let job_config = as_hash(job_config, path.clone())?;
+            let measure = get_str(job_config, "measure", path.clone())?;
+            let measure = Measure::from_name(&measure).ok_or_else(|| {
+                ConfigError::IncorrectType {
+                    path: path.clone().key("measure").unknown(),
+                    expected: "latency, routes or rates".to_string(),
+                }
+            })?;
+            let every = get_seconds(job_config, "every", path.clone())?;
+            if every.is_zero() {
+                return Err(ConfigError::IncorrectType {
+                    path: path.clone().key("every").unknown(),
+                    expected: "positive seconds".to_string(),
+                }
+                .into());
+            }
+            let sample = match get_seconds(job_config, "sample", path.clone())?
+            {
+                Duration::ZERO => DEFAULT_SAMPLE,
+                sample => sample,
+            };
+            let csv = base_dir.join(get_str(job_config, "csv", path.clone())?);
+            jobs.push(Job {
+                measure,
+                every,
+                sample,
+                csv,
+            });
+        }
+        Ok(Self { jobs })
+    }
+
+    /// Runs the jobs against the running `topology` until cancelled, each
+    /// one first straight away. Jobs run one at a time, a long one delaying
+    /// those due meanwhile; runs missed that way are skipped rather than
+    /// caught up with. A failing run is logged, and the job tried again
+    /// when next due.
+    pub fn run(&self, topology: &Topology, cancel: &CancelToken) {
+        let start = Instant::now();
+        let mut due: Vec<Instant> = vec![start; self.jobs.len()];
+        loop {
+            let Some((index, next)) = due
+                .iter()
+                .copied()
+                .enumerate()
+                .min_by_key(|(_, next)| *next)
+            else {
+                return;
+            };
+            let wait = next.saturating_duration_since(Instant::now());
+            if cancel.sleep(wait).is_err() {
+                return;
+            }
+
+            let job = &self.jobs[index];
+            debug!(measure = %job.measure, csv = %job.csv.display(),
+                "measuring");
+            match job.run(topology) {
+                Ok(()) => {
+                    info!("{} measured into {}", job.measure, job.csv.display())
+                }
+                Err(err) => {
+                    warn!(measure = %job.measure, %err, "measurement failed")
+                }
+            }
+            let mut next = next + job.every;
+            while next <= Instant::now() {
+                next += job.every;
+            }
+            due[index] = next;
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{devices_ns_dir, events};
+
+pub(crate) const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Between two readiness checks.
+const READY_INTERVAL: Duration = Duration::from_millis(200);
+// Wait before the first restart of a service, doubled on every further one
+// up to MAX_BACKOFF, and reset once the service stayed up for as long.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// ==== struct Service ====
+
+/// Long-running process of a router beside its routing daemons, e.g. a
+/// telemetry agent or an HTTP server standing in for an end host:
+///
+/// ```yaml
+/// routers:
+///   h1:
+///     services:
+///       echo:
+///         command: socat TCP-LISTEN:8080,fork,reuseaddr EXEC:cat
+///         ready:
+///           tcp: 8080      # or exec: <command exiting 0 once ready>
+///           timeout: 10
+///         restart: on-failure
+/// ```
+///
+/// The command is run by `sh -c` in the router's namespaces, with the
+/// router's `env` and `limits`, its output going to `service-<name>.log`
+/// under the router's directory. A supervisor restarts it according to
+/// `restart` (never, on-failure, the default, or always), waiting longer
+/// after every restart in a row, and terminates it along with itself.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Service {
+    pub(crate) name: String,
+    pub(crate) command: String,
+    pub(crate) ready: Option<ReadyCheck>,
+    pub(crate) restart: RestartPolicy,
+}
+
+// ==== struct ReadyCheck ====
+
+// How `start` tells a service is ready, failing after `timeout`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ReadyCheck {
+    pub(crate) probe: ReadyProbe,
+    pub(crate) timeout: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ReadyProbe {
+    // Accepts connections on the router's loopback.
+    Tcp(u16),
+    // Exits successfully, run like the service.
+    Exec(String),
+}
+
+// ==== enum RestartPolicy ====
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum RestartPolicy {
+    Never,
+    #[default]
+    OnFailure,
+    Always,
+}
+
+impl RestartPolicy {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "never" => Some(RestartPolicy::Never),
+            "on-failure" => Some(RestartPolicy::OnFailure),
+            "always" => Some(RestartPolicy::Always),
+            _ => None,
+        }
+    }
+
+    fn restarts(&self, status: &ExitStatus) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !status.success(),
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+impl Service {
+    fn command(&self, env: &BTreeMap<String, String>) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", &self.command]).envs(env);
+        command
+    }
+
+    /// Runs the service from within the router's namespaces, restarting it
+    /// as its policy says, until killed.
+    ///
+    /// The service is sent SIGTERM once the supervisor is gone, whether
+    /// stopped by `stop` or killed. Exits and restarts are recorded as
+    /// events.
+    pub(crate) fn supervise(&self, node: &str, env: &BTreeMap<String, String>) {
+        let subject = format!("{node}/{}", self.name);
+        let log_path =
+            format!("{}/{node}/service-{}.log", devices_ns_dir(), self.name);
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let log = File::options().create(true).append(true).open(&log_path);
+            let (stdout, stderr) =
+                match log.and_then(|log| Ok((log.try_clone()?, log))) {
+                    Ok((stdout, stderr)) => (stdout.into(), stderr.into()),
+                    Err(_) => (Stdio::null(), Stdio::null()),
+                };
+            let mut command = self.command(env);
+            command.stdin(Stdio::null()).stdout(stdout).stderr(stderr);
+            // SAFETY: prctl is async-signal-safe.
+            unsafe {
+                command.pre_exec(|| {
+                    if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+
+            let started = Instant::now();
+            let status = match command.status() {
+                Ok(status) => status,
+                Err(err) => {
+                    events::record(
+                        "service",
+                        &subject,
+                        &format!("failed to start: {err}"),
+                    );
+                    return;
+                }
+            };
+            if !self.restart.restarts(&status) {
+                events::record(
+                    "service",
+                    &subject,
+                    &format!("exited, {status}"),
+                );
+                return;
+            }
+            if started.elapsed() >= MAX_BACKOFF {
+                backoff = MIN_BACKOFF;
+            }
+            events::record(
+                "service",
+                &subject,
+                &format!("exited, {status}, restarting in {backoff:?}"),
+            );
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Waits for the service to be ready, from within the router's
+    /// namespaces. Services without a readiness check are ready once
+    /// started.
+    pub(crate) fn wait_ready(
+        &self,
+        node: &str,
+        env: &BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        let Some(ready) = &self.ready else {
+            return Ok(());
+        };
+        let deadline = Instant::now() + ready.timeout;
+        loop {
+            let is_ready = match &ready.probe {
+                ReadyProbe::Tcp(port) => {
+                    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, *port));
+                    TcpStream::connect_timeout(&addr, READY_INTERVAL).is_ok()
+                }
+                ReadyProbe::Exec(check) => Command::new("sh")
+                    .args(["-c", check])
+                    .envs(env)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .is_ok_and(|status| status.success()),
+            };
+            if is_ready {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "service {} of {node} not ready after {:?}",
+                    self.name, ready.timeout
+                ));
+            }
+            thread::sleep(READY_INTERVAL);
+        }
+    }
+}
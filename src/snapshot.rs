@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::error::NetError;
+use crate::provenance::Provenance;
+use crate::report::json_string;
+use crate::{NetResult, exec_in_ns};
+
+// ==== struct NodeSnapshot ====
+
+/// Forwarding and neighbor state, plus interface counters, of a single node
+/// at a point in time.
+#[derive(Debug, Clone, Default)]
+pub struct NodeSnapshot {
+    pub routes: BTreeSet<String>,
+    pub neighbors: BTreeSet<String>,
+    pub counters: BTreeMap<String, Counters>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+}
+
+impl NodeSnapshot {
+    pub fn take(node: &str) -> NetResult<Self> {
+        let mut snapshot = Self::default();
+
+        for family in ["-4", "-6"] {
+            let output = exec_in_ns(node, "ip", &[family, "route", "show"])?;
+            snapshot.routes.extend(
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(|line| line.trim().to_string()),
+            );
+        }
+
+        // The neighbor state (REACHABLE, STALE...) changes on its own, only
+        // keep the address and link layer address.
+        let output = exec_in_ns(node, "ip", &["neigh", "show"])?;
+        snapshot.neighbors.extend(
+            String::from_utf8_lossy(&output.stdout).lines().map(|line| {
+                line.split_whitespace()
+                    .take(5)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }),
+        );
+
+        snapshot.counters = Counters::read(node)?;
+        Ok(snapshot)
+    }
+}
+
+impl Counters {
+    /// Counters of every interface of `node`, by interface name.
+    pub fn read(node: &str) -> NetResult<BTreeMap<String, Counters>> {
+        let mut counters = BTreeMap::new();
+
+        // procfs shows the network namespace of the reader.
+        let output = exec_in_ns(node, "cat", &["/proc/net/dev"])?;
+        for line in String::from_utf8_lossy(&output.stdout).lines().skip(2) {
+            let Some((iface, stats)) = line.split_once(':') else {
+                continue;
+            };
+            let stats: Vec<u64> = stats
+                .split_whitespace()
+                .filter_map(|stat| stat.parse().ok())
+                .collect();
+            if stats.len() < 10 {
+                continue;
+            }
+            counters.insert(
+                iface.trim().to_string(),
+                Counters {
+                    rx_bytes: stats[0],
+                    rx_packets: stats[1],
+                    tx_bytes: stats[8],
+                    tx_packets: stats[9],
+                },
+            );
+        }
+        Ok(counters)
+    }
+
+    /// Increase of the counters since `before`.
+    pub fn since(&self, before: &Counters) -> Counters {
+        Counters {
+            rx_bytes: self.rx_bytes.saturating_sub(before.rx_bytes),
+            rx_packets: self.rx_packets.saturating_sub(before.rx_packets),
+            tx_bytes: self.tx_bytes.saturating_sub(before.tx_bytes),
+            tx_packets: self.tx_packets.saturating_sub(before.tx_packets),
+        }
+    }
+}
+
+// ==== struct TopologySnapshot ====
+
+#[derive(Debug, Clone, Default)]
+pub struct TopologySnapshot {
+    pub nodes: BTreeMap<String, NodeSnapshot>,
+}
+
+impl TopologySnapshot {
+    pub fn take<'a>(
+        nodes: impl IntoIterator<Item = &'a String>,
+    ) -> NetResult<Self> {
+        let mut snapshot = Self::default();
+        for node in nodes {
+            snapshot
+                .nodes
+                .insert(node.clone(), NodeSnapshot::take(node)?);
+        }
+        Ok(snapshot)
+    }
+
+    /// What changed between `self` and the later snapshot `after`.
+    pub fn diff(&self, after: &TopologySnapshot) -> SnapshotDiff {
+        let empty = NodeSnapshot::default();
+        let mut diff = SnapshotDiff::default();
+
+        let names: BTreeSet<&String> =
+            self.nodes.keys().chain(after.nodes.keys()).collect();
+        for name in names {
+            let before = self.nodes.get(name).unwrap_or(&empty);
+            let after = after.nodes.get(name).unwrap_or(&empty);
+
+            let mut node_diff = NodeDiff {
+                routes_added: after
+                    .routes
+                    .difference(&before.routes)
+                    .cloned()
+                    .collect(),
+                routes_removed: before
+                    .routes
+                    .difference(&after.routes)
+                    .cloned()
+                    .collect(),
+                neighbors_added: after
+                    .neighbors
+                    .difference(&before.neighbors)
+                    .cloned()
+                    .collect(),
+                neighbors_removed: before
+                    .neighbors
+                    .difference(&after.neighbors)
+                    .cloned()
+                    .collect(),
+                counters: BTreeMap::new(),
+            };
+
+            for (iface, after_counters) in &after.counters {
+                let before_counters =
+                    before.counters.get(iface).copied().unwrap_or_default();
+                let delta = after_counters.since(&before_counters);
+                if delta != Counters::default() {
+                    node_diff.counters.insert(iface.clone(), delta);
+                }
+            }
+            diff.nodes.insert(name.clone(), node_diff);
+        }
+        diff
+    }
+}
+
+// ==== struct SnapshotDiff ====
+
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotDiff {
+    pub nodes: BTreeMap<String, NodeDiff>,
+    // User-defined metadata of the nodes, carried over to the JSON export.
+    pub metadata: BTreeMap<String, BTreeMap<String, String>>,
+    // What the topology was, carried over to both exports.
+    pub provenance: Option<Provenance>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NodeDiff {
+    pub routes_added: Vec<String>,
+    pub routes_removed: Vec<String>,
+    pub neighbors_added: Vec<String>,
+    pub neighbors_removed: Vec<String>,
+    // Counter increase per interface, for the interfaces that saw traffic.
+    pub counters: BTreeMap<String, Counters>,
+}
+
+impl NodeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.routes_added.is_empty()
+            && self.routes_removed.is_empty()
+            && self.neighbors_added.is_empty()
+            && self.neighbors_removed.is_empty()
+            && self.counters.is_empty()
+    }
+}
+
+impl SnapshotDiff {
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "# What changed");
+        if let Some(provenance) = &self.provenance {
+            let _ = writeln!(md);
+            let _ = writeln!(md, "Changes of {provenance}.");
+        }
+
+        for (name, node) in &self.nodes {
+            let _ = writeln!(md);
+            let _ = writeln!(md, "## {name}");
+            if node.is_empty() {
+                let _ = writeln!(md);
+                let _ = writeln!(md, "No changes.");
+                continue;
+            }
+
+            let sections = [
+                ("Routes added", &node.routes_added),
+                ("Routes removed", &node.routes_removed),
+                ("Neighbors added", &node.neighbors_added),
+                ("Neighbors removed", &node.neighbors_removed),
+            ];
+            for (title, entries) in sections {
+                if entries.is_empty() {
+                    continue;
+                }
+                let _ = writeln!(md);
+                let _ = writeln!(md, "### {title}");
+                let _ = writeln!(md);
+                for entry in entries {
+                    let _ = writeln!(md, "- `{entry}`");
+                }
+            }
+
+            if !node.counters.is_empty() {
+                let _ = writeln!(md);
+                let _ = writeln!(md, "### Interface counters");
+                let _ = writeln!(md);
+                let _ = writeln!(
+                    md,
+                    "| Interface | RX packets | RX bytes | TX packets | TX bytes |"
+                );
+                let _ = writeln!(md, "|---|---:|---:|---:|---:|");
+                for (iface, delta) in &node.counters {
+                    let _ = writeln!(
+                        md,
+                        "| {iface} | +{} | +{} | +{} | +{} |",
+                        delta.rx_packets,
+                        delta.rx_bytes,
+                        delta.tx_packets,
+                        delta.tx_bytes
+                    );
+                }
+            }
+        }
+        md
+    }
+
+    pub fn to_json(&self) -> String {
+        let list = |entries: &[String]| {
+            let entries: Vec<String> =
+                entries.iter().map(|entry| json_string(entry)).collect();
+            format!("[{}]", entries.join(", "))
+        };
+
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|(name, node)| {
+                let counters: Vec<String> = node
+                    .counters
+                    .iter()
+                    .map(|(iface, delta)| {
+                        format!(
+                            r#"{}: {{"rx_packets": {}, "rx_bytes": {}, "tx_packets": {}, "tx_bytes": {}}}"#,
+                            json_string(iface),
+                            delta.rx_packets,
+                            delta.rx_bytes,
+                            delta.tx_packets,
+                            delta.tx_bytes
+                        )
+                    })
+                    .collect();
+                let metadata: Vec<String> = self
+                    .metadata
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .map(|(key, value)| {
+                        format!("{}: {}", json_string(key), json_string(value))
+                    })
+                    .collect();
+                format!(
+                    r#"    {}: {{"metadata": {{{}}}, "routes_added": {}, "routes_removed": {}, "neighbors_added": {}, "neighbors_removed": {}, "counters": {{{}}}}}"#,
+                    json_string(name),
+                    metadata.join(", "),
+                    list(&node.routes_added),
+                    list(&node.routes_removed),
+                    list(&node.neighbors_added),
+                    list(&node.neighbors_removed),
+                    counters.join(", ")
+                )
+            })
+            .collect();
+
+        let provenance = match &self.provenance {
+            Some(provenance) => {
+                format!("  \"provenance\": {},\n", provenance.to_json())
+            }
+            None => String::new(),
+        };
+        format!(
+            "{{\n{provenance}  \"nodes\": {{\n{}\n  }}\n}}\n",
+            nodes.join(",\n")
+        )
+    }
+
+    /// Saves the diff as `changes.md` and `changes.json` under `dir`.
+    pub fn write(&self, dir: &str) -> NetResult<()> {
+        fs::create_dir_all(dir).map_err(|err| {
+            NetError::BasicError(format!("Unable to create {dir}: {err:?}"))
+        })?;
+        for (file, contents) in [
+            ("changes.md", self.to_markdown()),
+            ("changes.json", self.to_json()),
+        ] {
+            let path = format!("{dir}/{file}");
+            fs::write(&path, contents).map_err(|err| {
+                NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+
+use nix::sched::{CloneFlags, setns};
+
+use crate::error::{NamespaceError, NetError};
+use crate::table::{self, Align};
+use crate::{NamespaceHandle, NetResult, with_run_dir};
+
+// Message type of inet_diag requests, see sock_diag(7).
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+// Lengths of struct nlmsghdr, inet_diag_req_v2 and inet_diag_msg.
+const NLMSG_HDR_LEN: usize = 16;
+const DIAG_REQ_LEN: usize = 56;
+const DIAG_MSG_LEN: usize = 72;
+const RECV_BUF_LEN: usize = 32 * 1024;
+
+// TCP states, as numbered by the kernel. Unconnected UDP sockets are in
+// the closed state.
+const TCP_ESTABLISHED: u8 = 1;
+const TCP_CLOSE: u8 = 7;
+const TCP_LISTEN: u8 = 10;
+
+// ==== enum Protocol ====
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    fn number(&self) -> u8 {
+        match self {
+            Protocol::Tcp => libc::IPPROTO_TCP as u8,
+            Protocol::Udp => libc::IPPROTO_UDP as u8,
+        }
+    }
+
+    // The states to dump: listening and established TCP sockets, bound
+    // and connected UDP ones.
+    fn states(&self, listening_only: bool) -> u32 {
+        let listening = match self {
+            Protocol::Tcp => TCP_LISTEN,
+            Protocol::Udp => TCP_CLOSE,
+        };
+        match listening_only {
+            true => 1 << listening,
+            false => (1 << listening) | (1 << TCP_ESTABLISHED),
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protocol::Tcp => write!(f, "tcp"),
+            Protocol::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+// ==== struct Socket ====
+
+/// A TCP or UDP socket of a node, as reported by the kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socket {
+    pub protocol: Protocol,
+    pub listening: bool,
+    pub local: SocketAddr,
+    // None when not connected.
+    pub peer: Option<SocketAddr>,
+    pub inode: u32,
+    // Processes holding the socket, as "name(pid)".
+    pub processes: Vec<String>,
+}
+
+impl Socket {
+    /// The state as `ss` shows it.
+    pub fn state(&self) -> &'static str {
+        match (self.protocol, self.listening) {
+            (Protocol::Tcp, true) => "LISTEN",
+            (Protocol::Udp, true) => "UNCONN",
+            (_, false) => "ESTAB",
+        }
+    }
+}
+
+/// The listening and established sockets of the `device` namespace, or
+/// only the listening ones, sorted by protocol then local address.
+///
+/// They are dumped over a sock_diag netlink socket opened from a
+/// short-lived thread moved into the namespace, so `ss` need not be
+/// installed, and matched with the processes holding them through procfs.
+pub fn list(device: &str, listening_only: bool) -> NetResult<Vec<Socket>> {
+    let namespace = NamespaceHandle::new(Some(device));
+    let net_file = File::open(namespace.net_path()).map_err(|err| {
+        NamespaceError::FileOpen {
+            path: namespace.net_path().to_string(),
+            source: err,
+        }
+    })?;
+
+    let mut sockets = std::thread::scope(|scope| {
+        scope
+            .spawn(with_run_dir(|| -> NetResult<Vec<Socket>> {
+                setns(net_file.as_fd(), CloneFlags::CLONE_NEWNET).map_err(
+                    |err| NamespaceError::Entry {
+                        device: device.to_string(),
+                        source: err,
+                    },
+                )?;
+                let fd =
+                    diag_socket().map_err(|err| diag_error(device, err))?;
+                let mut sockets = vec![];
+                for protocol in [Protocol::Tcp, Protocol::Udp] {
+                    for family in [libc::AF_INET, libc::AF_INET6] {
+                        let states = protocol.states(listening_only);
+                        sockets.extend(
+                            dump(&fd, family as u8, protocol, states)
+                                .map_err(|err| diag_error(device, err))?,
+                        );
+                    }
+                }
+                Ok(sockets)
+            }))
+            .join()
+            .unwrap_or_else(|_| {
+                Err(diag_error(
+                    device,
+                    io::Error::other("socket thread panicked"),
+                ))
+            })
+    })?;
+
+    let owners = socket_owners();
+    for socket in &mut sockets {
+        socket.processes =
+            owners.get(&socket.inode).cloned().unwrap_or_default();
+    }
+    sockets.sort_by_key(|socket| (socket.protocol, socket.local, socket.peer));
+    Ok(sockets)
+}
+
+/// Renders the sockets as an aligned table.
+pub fn render(sockets: &[Socket]) -> String {
+    let headers = ["PROTO", "STATE", "LOCAL", "PEER", "PROCESS"];
+    let rows: Vec<[String; 5]> = sockets
+        .iter()
+        .map(|socket| {
+            [
+                socket.protocol.to_string(),
+                socket.state().to_string(),
+                socket.local.to_string(),
+                socket
+                    .peer
+                    .map_or_else(|| "*".to_string(), |peer| peer.to_string()),
+                socket.processes.join(","),
+            ]
+        })
+        .collect();
+    table::render(headers, [Align::Left; 5], &rows)
+}
+
+fn diag_socket() -> io::Result<OwnedFd> {
+    // SAFETY: plain socket creation, the returned descriptor is checked.
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_SOCK_DIAG,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just returned by the kernel and is owned by no one
+    // else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+// Dumps the sockets of a family and protocol in the given states.
+fn dump(
+    fd: &OwnedFd,
+    family: u8,
+    protocol: Protocol,
+    states: u32,
+) -> io::Result<Vec<Socket>> {
+    let mut request = Vec::with_capacity(NLMSG_HDR_LEN + DIAG_REQ_LEN);
+    request.extend(((NLMSG_HDR_LEN + DIAG_REQ_LEN) as u32).to_ne_bytes());
+    request.extend(SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+    request.extend(
+        ((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes(),
+    );
+    // Sequence number and port id, the kernel fills in the latter.
+    request.extend([0u8; 8]);
+    request.extend([family, protocol.number(), 0, 0]);
+    request.extend(states.to_ne_bytes());
+    // Any socket id, all zeroes.
+    request.resize(NLMSG_HDR_LEN + DIAG_REQ_LEN, 0);
+
+    // SAFETY: `request` is valid for reads of its length, and the file
+    // descriptor is valid for the duration of the call.
+    let ret = unsafe {
+        libc::send(fd.as_raw_fd(), request.as_ptr().cast(), request.len(), 0)
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut sockets = vec![];
+    let mut buf = vec![0u8; RECV_BUF_LEN];
+    loop {
+        // SAFETY: `buf` is valid for writes of its length, and the file
+        // descriptor is valid for the duration of the call.
+        let len = unsafe {
+            libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0)
+        };
+        if len < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+
+        let mut messages = &buf[..len as usize];
+        while messages.len() >= NLMSG_HDR_LEN {
+            let msg_len =
+                u32::from_ne_bytes(messages[0..4].try_into().unwrap()) as usize;
+            let msg_type = u16::from_ne_bytes([messages[4], messages[5]]);
+            if msg_len < NLMSG_HDR_LEN || msg_len > messages.len() {
+                return Err(io::Error::other("truncated netlink message"));
+            }
+            let payload = &messages[NLMSG_HDR_LEN..msg_len];
+            match i32::from(msg_type) {
+                libc::NLMSG_DONE => return Ok(sockets),
+                libc::NLMSG_ERROR => {
+                    let errno = payload
+                        .get(0..4)
+                        .map(|errno| {
+                            i32::from_ne_bytes(errno.try_into().unwrap())
+                        })
+                        .unwrap_or_default();
+                    // Without udp_diag loaded, UDP sockets cannot be
+                    // dumped, which is no reason to hide the TCP ones.
+                    if errno == -libc::ENOENT {
+                        return Ok(sockets);
+                    }
+                    return Err(io::Error::from_raw_os_error(-errno));
+                }
+                _ => sockets.extend(parse_diag_msg(protocol, payload)),
+            }
+            // Messages are aligned on 4 bytes.
+            let aligned = (msg_len + 3) & !3;
+            messages = messages.get(aligned..).unwrap_or_default();
+        }
+    }
+}
+
+// Decodes a struct inet_diag_msg: family, state, timer and retransmits,
+// then the socket id with ports and addresses in network order, then the
+// expiry, queues, uid and inode.
+fn parse_diag_msg(protocol: Protocol, msg: &[u8]) -> Option<Socket> {
+    if msg.len() < DIAG_MSG_LEN {
+        return None;
+    }
+    let (family, state) = (msg[0], msg[1]);
+    let sport = u16::from_be_bytes([msg[4], msg[5]]);
+    let dport = u16::from_be_bytes([msg[6], msg[7]]);
+    let address = |bytes: &[u8]| -> Option<IpAddr> {
+        match i32::from(family) {
+            libc::AF_INET => {
+                let octets: [u8; 4] = bytes[..4].try_into().ok()?;
+                Some(IpAddr::V4(Ipv4Addr::from(octets)))
+            }
+            libc::AF_INET6 => {
+                let octets: [u8; 16] = bytes.try_into().ok()?;
+                Some(IpAddr::V6(Ipv6Addr::from(octets)))
+            }
+            _ => None,
+        }
+    };
+    let src = address(&msg[8..24])?;
+    let dst = address(&msg[24..40])?;
+    let inode = u32::from_ne_bytes(msg[68..72].try_into().ok()?);
+
+    let listening = state != TCP_ESTABLISHED;
+    Some(Socket {
+        protocol,
+        listening,
+        local: SocketAddr::new(src, sport),
+        peer: (!listening).then(|| SocketAddr::new(dst, dport)),
+        inode,
+        processes: vec![],
+    })
+}
+
+// The processes holding each socket, by inode, from the file descriptors
+// listed in procfs. Processes which vanish meanwhile are skipped.
+fn socket_owners() -> BTreeMap<u32, Vec<String>> {
+    let mut owners: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return owners;
+    };
+    for entry in procs.flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let comm = fs::read_to_string(entry.path().join("comm"))
+            .map(|comm| comm.trim().to_string())
+            .unwrap_or_default();
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            let inode = target
+                .to_str()
+                .and_then(|target| target.strip_prefix("socket:["))
+                .and_then(|target| target.strip_suffix(']'))
+                .and_then(|inode| inode.parse::<u32>().ok());
+            if let Some(inode) = inode {
+                let owner = format!("{comm}({pid})");
+                let holders = owners.entry(inode).or_default();
+                if !holders.contains(&owner) {
+                    holders.push(owner);
+                }
+            }
+        }
+    }
+    owners
+}
+
+fn diag_error(device: &str, source: io::Error) -> NetError {
+    NetError::BasicError(format!(
+        "Unable to list the sockets of {device}: {source}"
+    ))
+}
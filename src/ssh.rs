@@ -0,0 +1,131 @@
+use std::net::IpAddr;
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::NetError;
+use crate::node::{FileDrop, FileSource};
+use crate::{NetResult, devices_ns_dir};
+
+// ==== struct SshServer ====
+
+/// SSH server sidecar, backed by dropbear.
+///
+/// Configured either for every router at the top level of the topology, or
+/// per router, the latter taking precedence:
+///
+/// ```yaml
+/// ssh:
+///   port: 22
+///   authorized-keys: ~/.ssh/id_ed25519.pub
+///
+/// routers:
+///   rt1:
+///     ssh: false
+/// ```
+///
+/// `dropbear` and `dropbearkey` need to be installed on the host.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SshServer {
+    pub(crate) enabled: bool,
+    pub(crate) port: u16,
+    // Only listen on this address, rather than on all of the router's.
+    pub(crate) address: Option<IpAddr>,
+    // Public keys allowed to log in as root, password logins are disabled
+    // when set.
+    pub(crate) authorized_keys: Option<String>,
+}
+
+impl Default for SshServer {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            port: 22,
+            address: None,
+            authorized_keys: None,
+        }
+    }
+}
+
+impl SshServer {
+    pub(crate) const HOST_KEY_FILE: &str = "ssh_host_ed25519_key";
+    const AUTHORIZED_KEYS_DST: &str = "/root/.ssh/authorized_keys";
+
+    pub(crate) fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// The authorized keys, as a file to materialize in the router.
+    pub(crate) fn authorized_keys_file(&self) -> Option<FileDrop> {
+        let source = self.authorized_keys.as_ref()?;
+        Some(FileDrop {
+            dst: Self::AUTHORIZED_KEYS_DST.to_string(),
+            source: FileSource::Path(expand_home(source)),
+            mode: Some(0o600),
+        })
+    }
+
+    /// Generates the router's host key if it does not have one yet.
+    ///
+    /// Returns its path.
+    pub(crate) fn ensure_host_key(
+        &self,
+        router_name: &str,
+    ) -> NetResult<String> {
+        let key_path = format!(
+            "{}/{router_name}/{}",
+            devices_ns_dir(),
+            Self::HOST_KEY_FILE
+        );
+        if Path::new(&key_path).exists() {
+            return Ok(key_path);
+        }
+
+        let output = Command::new("dropbearkey")
+            .args(["-t", "ed25519", "-f", &key_path])
+            .output()
+            .map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to run dropbearkey for {router_name}: {err:?}"
+                ))
+            })?;
+        if !output.status.success() {
+            return Err(NetError::BasicError(format!(
+                "Unable to generate the SSH host key of {router_name}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(key_path)
+    }
+
+    /// Arguments to run dropbear in the foreground with.
+    pub(crate) fn args(&self, key_path: &str) -> Vec<String> {
+        let listen = match self.address {
+            Some(IpAddr::V6(addr)) => format!("[{addr}]:{}", self.port),
+            Some(IpAddr::V4(addr)) => format!("{addr}:{}", self.port),
+            None => self.port.to_string(),
+        };
+        let mut args = vec![
+            "-F".to_string(),
+            "-E".to_string(),
+            "-r".to_string(),
+            key_path.to_string(),
+            "-p".to_string(),
+            listen,
+        ];
+        if self.authorized_keys.is_some() {
+            args.push("-s".to_string());
+        }
+        args
+    }
+}
+
+// `~/` is expanded against $HOME, as users tend to write key paths that way.
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{home}/{rest}"),
+        _ => path.to_string(),
+    }
+}
@@ -0,0 +1,691 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use yaml_rust2::yaml::{Hash, Yaml};
+use yaml_rust2::{YamlEmitter, YamlLoader};
+
+use nix::fcntl::Flock;
+use nix::sys::signal::Signal;
+use tracing::{debug, warn};
+
+use crate::coredump;
+use crate::error::{NetError, StateError};
+use crate::lab::lock_file;
+use crate::pidfd::PidFd;
+use crate::provenance::Provenance;
+use crate::{NetResult, process_in_device, process_start_time, state_file};
+
+const BOOT_ID_FILE: &str = "/proc/sys/kernel/random/boot_id";
+
+/// Version of the state file schema written by this netgen.
+///
+/// Bumped when a key changes meaning or is removed, which a netgen knowing
+/// an older schema would misread. Added keys are optional and need no bump:
+/// older netgen leave them as they are.
+pub const STATE_VERSION: u32 = 1;
+
+// Top-level keys of the schema, anything else being kept as is.
+const STATE_KEYS: &[&str] = &[
+    "version",
+    "session",
+    "bridges",
+    "processes",
+    "topology",
+    "profiles",
+    "metadata",
+    "boot-id",
+    "partitioned",
+    "config-failures",
+    "provenance",
+    "frr-dir",
+    "binaries",
+    "grpc",
+];
+
+// ==== struct TopologyState ====
+
+/// Runtime state of the running topology, persisted to `state_file()`.
+///
+/// Commands other than `start` consult it to find out what has actually
+/// been created on the host, rather than re-deriving it from the topology
+/// file.
+#[derive(Debug, Clone, Default)]
+pub struct TopologyState {
+    // Schema version the file was written with.
+    pub version: u32,
+    pub session: String,
+    // Logical switch name -> name of the bridge backing it.
+    pub bridges: BTreeMap<String, String>,
+    // Long running processes started inside the nodes.
+    pub processes: Vec<ProcessRecord>,
+    // Topology file the running topology was built from, once rendered.
+    pub topology: Option<String>,
+    // Profiles enabled when parsing the topology.
+    pub profiles: Vec<String>,
+    // User-defined metadata, by node or link name.
+    pub metadata: BTreeMap<String, BTreeMap<String, String>>,
+    // Links disabled by `netgen partition`, by link name.
+    pub partitioned: Vec<String>,
+    // Boot the topology was brought up during, see `is_stale`.
+    pub boot_id: Option<String>,
+    // Why the startup config of a node failed to load, by node name.
+    pub config_failures: BTreeMap<String, String>,
+    // What the running topology was built from, and when.
+    pub provenance: Option<Provenance>,
+    // Directory the FRR daemons were found in, for the routers which do not
+    // set theirs.
+    pub frr_dir: Option<String>,
+    // Binaries the daemons were started from, as they were then.
+    pub binaries: Vec<BinaryRecord>,
+    // Host address of the gRPC northbound of the proxied routers, by name.
+    pub grpc: BTreeMap<String, String>,
+    // Fields written by a newer netgen, saved back untouched.
+    extra: Hash,
+}
+
+impl TopologyState {
+    pub fn new(session: &str) -> Self {
+        Self {
+            version: STATE_VERSION,
+            session: session.to_string(),
+            boot_id: current_boot_id(),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the host rebooted since the topology was brought up, taking
+    /// down everything the state refers to.
+    pub fn is_stale(&self) -> bool {
+        self.boot_id.is_some() && self.boot_id != current_boot_id()
+    }
+
+    /// Removes the state file and what is left under `ns_dir()` when the host
+    /// rebooted since the topology was brought up. Returns whether it did.
+    pub fn clean_stale() -> NetResult<bool> {
+        let _state_lock = Self::lock()?;
+        match Self::load()? {
+            Some(state) if state.is_stale() => {}
+            _ => return Ok(false),
+        }
+
+        warn!("host rebooted since the topology was started, cleaning up");
+        crate::clean_stale_run()?;
+        Ok(true)
+    }
+
+    /// Loads the state file, if any.
+    ///
+    /// Files written with a newer schema are read as far as this one goes,
+    /// their unknown fields being left alone.
+    pub fn load() -> NetResult<Option<Self>> {
+        let path = state_file();
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+
+        let contents =
+            fs::read_to_string(&path).map_err(|err| StateError::Read {
+                path: path.clone(),
+                source: err,
+            })?;
+        let docs = YamlLoader::load_from_str(&contents).map_err(|err| {
+            StateError::Corrupted {
+                path: path.clone(),
+                reason: err.to_string(),
+            }
+        })?;
+        let Some(Yaml::Hash(doc)) = docs.first() else {
+            return Err(corrupted("expected a hash").into());
+        };
+
+        let version = match doc.get(&Yaml::String("version".to_string())) {
+            Some(Yaml::Integer(version)) if *version >= 1 => {
+                u32::try_from(*version)
+                    .map_err(|_| corrupted("invalid version"))?
+            }
+            Some(_) => return Err(corrupted("invalid version").into()),
+            None => return Err(corrupted("missing version").into()),
+        };
+        if version > STATE_VERSION {
+            warn!(
+                version,
+                supported = STATE_VERSION,
+                "state file written by a newer netgen, reading what is known"
+            );
+        }
+        let extra = doc
+            .iter()
+            .filter(|(key, _)| {
+                key.as_str().is_none_or(|key| !STATE_KEYS.contains(&key))
+            })
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        let mut state = Self {
+            version,
+            extra,
+            ..Default::default()
+        };
+        match doc.get(&Yaml::String("session".to_string())) {
+            Some(Yaml::String(session)) => state.session = session.clone(),
+            _ => return Err(corrupted("missing session").into()),
+        }
+
+        if let Some(Yaml::Hash(bridges)) =
+            doc.get(&Yaml::String("bridges".to_string()))
+        {
+            for (name, bridge) in bridges {
+                if let (Yaml::String(name), Yaml::String(bridge)) =
+                    (name, bridge)
+                {
+                    state.bridges.insert(name.clone(), bridge.clone());
+                }
+            }
+        }
+
+        if let Some(Yaml::Array(processes)) =
+            doc.get(&Yaml::String("processes".to_string()))
+        {
+            for process in processes {
+                state.processes.push(ProcessRecord::from_yaml(process)?);
+            }
+        }
+        if let Some(Yaml::String(topology)) =
+            doc.get(&Yaml::String("topology".to_string()))
+        {
+            state.topology = Some(topology.clone());
+        }
+        if let Some(Yaml::Array(profiles)) =
+            doc.get(&Yaml::String("profiles".to_string()))
+        {
+            state.profiles = profiles
+                .iter()
+                .filter_map(|profile| Some(profile.as_str()?.to_string()))
+                .collect();
+        }
+        if let Some(Yaml::Hash(metadata)) =
+            doc.get(&Yaml::String("metadata".to_string()))
+        {
+            for (name, entries) in metadata {
+                let (Yaml::String(name), Yaml::Hash(entries)) = (name, entries)
+                else {
+                    continue;
+                };
+                let entries = entries
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        Some((
+                            key.as_str()?.to_string(),
+                            value.as_str()?.to_string(),
+                        ))
+                    })
+                    .collect();
+                state.metadata.insert(name.clone(), entries);
+            }
+        }
+        if let Some(Yaml::String(boot_id)) =
+            doc.get(&Yaml::String("boot-id".to_string()))
+        {
+            state.boot_id = Some(boot_id.clone());
+        }
+        if let Some(Yaml::Array(partitioned)) =
+            doc.get(&Yaml::String("partitioned".to_string()))
+        {
+            state.partitioned = partitioned
+                .iter()
+                .filter_map(|link| Some(link.as_str()?.to_string()))
+                .collect();
+        }
+        if let Some(Yaml::Hash(failures)) =
+            doc.get(&Yaml::String("config-failures".to_string()))
+        {
+            state.config_failures = failures
+                .iter()
+                .filter_map(|(node, reason)| {
+                    Some((
+                        node.as_str()?.to_string(),
+                        reason.as_str()?.to_string(),
+                    ))
+                })
+                .collect();
+        }
+        state.provenance = doc
+            .get(&Yaml::String("provenance".to_string()))
+            .and_then(Provenance::from_yaml);
+        if let Some(Yaml::String(dir)) =
+            doc.get(&Yaml::String("frr-dir".to_string()))
+        {
+            state.frr_dir = Some(dir.clone());
+        }
+        if let Some(Yaml::Array(binaries)) =
+            doc.get(&Yaml::String("binaries".to_string()))
+        {
+            state.binaries = binaries
+                .iter()
+                .filter_map(BinaryRecord::from_yaml)
+                .collect();
+        }
+        if let Some(Yaml::Hash(grpc)) =
+            doc.get(&Yaml::String("grpc".to_string()))
+        {
+            state.grpc = grpc
+                .iter()
+                .filter_map(|(node, addr)| {
+                    Some((
+                        node.as_str()?.to_string(),
+                        addr.as_str()?.to_string(),
+                    ))
+                })
+                .collect();
+        }
+        Ok(Some(state))
+    }
+
+    /// Held while loading, modifying then saving the state, so that the
+    /// threads and netgen processes doing so concurrently, e.g. `link add`
+    /// and `session rename`, do not overwrite each other's changes.
+    pub fn lock() -> NetResult<Flock<File>> {
+        lock_file(Path::new(&state_file()))
+    }
+
+    /// Adds a process to the state file.
+    pub fn record_process(record: ProcessRecord) -> NetResult<()> {
+        let _state_lock = Self::lock()?;
+        let mut state = Self::load()?.unwrap_or_default();
+        state.processes.push(record);
+        state.save()
+    }
+
+    /// Records whether the startup config of `node` loaded, and why not if
+    /// it failed to.
+    pub fn record_config(node: &str, failure: Option<String>) -> NetResult<()> {
+        let _state_lock = Self::lock()?;
+        let mut state = Self::load()?.unwrap_or_default();
+        match failure {
+            Some(reason) => {
+                state.config_failures.insert(node.to_string(), reason)
+            }
+            None => state.config_failures.remove(node),
+        };
+        state.save()
+    }
+
+    /// Records where the gRPC northbound of the proxied routers listens on
+    /// the host.
+    pub fn record_grpc(
+        endpoints: &BTreeMap<String, SocketAddr>,
+    ) -> NetResult<()> {
+        let _state_lock = Self::lock()?;
+        let mut state = Self::load()?.unwrap_or_default();
+        for (node, addr) in endpoints {
+            state.grpc.insert(node.clone(), addr.to_string());
+        }
+        state.save()
+    }
+
+    /// Writes the state file atomically, so that a crash mid-write never
+    /// leaves a truncated file behind.
+    pub fn save(&self) -> NetResult<()> {
+        let mut doc = Hash::new();
+        // Never downgraded, unknown fields being saved back.
+        doc.insert(
+            Yaml::String("version".to_string()),
+            Yaml::Integer(self.version.max(STATE_VERSION).into()),
+        );
+        doc.insert(
+            Yaml::String("session".to_string()),
+            Yaml::String(self.session.clone()),
+        );
+
+        let mut bridges = Hash::new();
+        for (name, bridge) in &self.bridges {
+            bridges.insert(
+                Yaml::String(name.clone()),
+                Yaml::String(bridge.clone()),
+            );
+        }
+        doc.insert(Yaml::String("bridges".to_string()), Yaml::Hash(bridges));
+        doc.insert(
+            Yaml::String("processes".to_string()),
+            Yaml::Array(
+                self.processes.iter().map(ProcessRecord::to_yaml).collect(),
+            ),
+        );
+
+        if let Some(topology) = &self.topology {
+            doc.insert(
+                Yaml::String("topology".to_string()),
+                Yaml::String(topology.clone()),
+            );
+        }
+
+        if !self.profiles.is_empty() {
+            doc.insert(
+                Yaml::String("profiles".to_string()),
+                Yaml::Array(
+                    self.profiles.iter().cloned().map(Yaml::String).collect(),
+                ),
+            );
+        }
+
+        if !self.metadata.is_empty() {
+            let mut metadata = Hash::new();
+            for (name, entries) in &self.metadata {
+                let entries = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        (Yaml::String(key.clone()), Yaml::String(value.clone()))
+                    })
+                    .collect();
+                metadata
+                    .insert(Yaml::String(name.clone()), Yaml::Hash(entries));
+            }
+            doc.insert(
+                Yaml::String("metadata".to_string()),
+                Yaml::Hash(metadata),
+            );
+        }
+
+        if let Some(boot_id) = &self.boot_id {
+            doc.insert(
+                Yaml::String("boot-id".to_string()),
+                Yaml::String(boot_id.clone()),
+            );
+        }
+
+        if !self.partitioned.is_empty() {
+            doc.insert(
+                Yaml::String("partitioned".to_string()),
+                Yaml::Array(
+                    self.partitioned
+                        .iter()
+                        .cloned()
+                        .map(Yaml::String)
+                        .collect(),
+                ),
+            );
+        }
+
+        if !self.config_failures.is_empty() {
+            let failures = self
+                .config_failures
+                .iter()
+                .map(|(node, reason)| {
+                    (Yaml::String(node.clone()), Yaml::String(reason.clone()))
+                })
+                .collect();
+            doc.insert(
+                Yaml::String("config-failures".to_string()),
+                Yaml::Hash(failures),
+            );
+        }
+
+        if let Some(provenance) = &self.provenance {
+            doc.insert(
+                Yaml::String("provenance".to_string()),
+                provenance.to_yaml(),
+            );
+        }
+
+        if let Some(dir) = &self.frr_dir {
+            doc.insert(
+                Yaml::String("frr-dir".to_string()),
+                Yaml::String(dir.clone()),
+            );
+        }
+
+        if !self.binaries.is_empty() {
+            doc.insert(
+                Yaml::String("binaries".to_string()),
+                Yaml::Array(
+                    self.binaries.iter().map(BinaryRecord::to_yaml).collect(),
+                ),
+            );
+        }
+
+        if !self.grpc.is_empty() {
+            let grpc = self
+                .grpc
+                .iter()
+                .map(|(node, addr)| {
+                    (Yaml::String(node.clone()), Yaml::String(addr.clone()))
+                })
+                .collect();
+            doc.insert(Yaml::String("grpc".to_string()), Yaml::Hash(grpc));
+        }
+
+        doc.extend(self.extra.clone());
+
+        let mut contents = String::new();
+        let mut emitter = YamlEmitter::new(&mut contents);
+        // Keeps the recorded topology readable.
+        emitter.multiline_strings(true);
+        emitter
+            .dump(&Yaml::Hash(doc))
+            .map_err(|err| corrupted(&format!("{err:?}")))?;
+        contents.push('\n');
+
+        let path = state_file();
+        let tmp_path = format!("{path}.tmp");
+        fs::write(&tmp_path, contents).map_err(|err| StateError::Write {
+            path: tmp_path.clone(),
+            source: err,
+        })?;
+        fs::rename(&tmp_path, &path).map_err(|err| StateError::Write {
+            path: path.clone(),
+            source: err,
+        })?;
+        Ok(())
+    }
+
+    /// Deletes the state file once the topology has been torn down.
+    pub fn remove() -> NetResult<()> {
+        let path = state_file();
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(StateError::Write { path, source: err }.into()),
+        }
+    }
+}
+
+// ==== struct ProcessRecord ====
+
+/// A process started by netgen inside a node.
+///
+/// The start time (in clock ticks since boot, as found in `/proc/<pid>/stat`)
+/// is kept so that a PID reused by an unrelated process is never signalled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessRecord {
+    pub node: String,
+    pub name: String,
+    pub pid: i32,
+    pub start_time: u64,
+}
+
+impl ProcessRecord {
+    /// Sends SIGTERM to the process, provided it is still the one that was
+    /// recorded. Returns whether a signal was sent.
+    pub fn stop(&self) -> NetResult<bool> {
+        Ok(self.terminate()?.is_some())
+    }
+
+    /// Whether the process is still running, and if not, what was collected
+    /// of it crashing.
+    pub fn status(&self) -> DaemonStatus {
+        let running = self.is_running();
+        DaemonStatus {
+            name: self.name.clone(),
+            pid: self.pid,
+            running,
+            crash: match running {
+                true => None,
+                false => coredump::collect(self),
+            },
+        }
+    }
+
+    /// Whether the process is still the one recorded, and running.
+    pub fn is_running(&self) -> bool {
+        self.pidfd().is_some_and(|pidfd| !pidfd.has_exited())
+    }
+
+    // Sends SIGTERM to the process, returning its pidfd to wait on if it was
+    // still the one recorded.
+    pub(crate) fn terminate(&self) -> NetResult<Option<PidFd>> {
+        let Some(pidfd) = self.pidfd() else {
+            return Ok(None);
+        };
+
+        pidfd.signal(Signal::SIGTERM).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to stop {} (PID {}) on {}: {err:?}",
+                self.name, self.pid, self.node
+            ))
+        })?;
+        Ok(Some(pidfd))
+    }
+
+    // A pidfd on the process, provided it is still the one recorded.
+    fn pidfd(&self) -> Option<PidFd> {
+        let Ok(pidfd) = PidFd::open(self.pid) else {
+            debug!(node = %self.node, name = %self.name, pid = self.pid,
+                "process already gone");
+            return None;
+        };
+
+        // Once the pidfd is open it keeps designating the same process, so
+        // checking its identity now is race-free.
+        if process_start_time(self.pid) != Some(self.start_time)
+            || !process_in_device(&self.node, self.pid)
+        {
+            warn!(node = %self.node, name = %self.name, pid = self.pid,
+                "PID reused by another process, ignoring it");
+            return None;
+        }
+        Some(pidfd)
+    }
+
+    fn to_yaml(&self) -> Yaml {
+        let mut record = Hash::new();
+        record.insert(
+            Yaml::String("node".to_string()),
+            Yaml::String(self.node.clone()),
+        );
+        record.insert(
+            Yaml::String("name".to_string()),
+            Yaml::String(self.name.clone()),
+        );
+        record.insert(
+            Yaml::String("pid".to_string()),
+            Yaml::Integer(self.pid.into()),
+        );
+        record.insert(
+            Yaml::String("start-time".to_string()),
+            Yaml::Integer(self.start_time as i64),
+        );
+        Yaml::Hash(record)
+    }
+
+    fn from_yaml(yaml: &Yaml) -> Result<Self, StateError> {
+        let field = |name: &str| yaml[name].clone();
+        match (
+            field("node"),
+            field("name"),
+            field("pid"),
+            field("start-time"),
+        ) {
+            (
+                Yaml::String(node),
+                Yaml::String(name),
+                Yaml::Integer(pid),
+                Yaml::Integer(start_time),
+            ) => Ok(Self {
+                node,
+                name,
+                pid: i32::try_from(pid)
+                    .map_err(|_| corrupted("invalid process pid"))?,
+                start_time: u64::try_from(start_time)
+                    .map_err(|_| corrupted("invalid process start-time"))?,
+            }),
+            _ => Err(corrupted("invalid process record")),
+        }
+    }
+}
+
+// ==== struct BinaryRecord ====
+
+/// A binary daemons were started from, pinned when the topology was
+/// started so that changes to it while running can be told.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryRecord {
+    pub path: String,
+    // As reported by `--version`, if it answered.
+    pub version: Option<String>,
+    // Modification time, in seconds since the Unix epoch.
+    pub modified: Option<u64>,
+}
+
+impl BinaryRecord {
+    fn to_yaml(&self) -> Yaml {
+        let mut record = Hash::new();
+        record.insert(
+            Yaml::String("path".to_string()),
+            Yaml::String(self.path.clone()),
+        );
+        if let Some(version) = &self.version {
+            record.insert(
+                Yaml::String("version".to_string()),
+                Yaml::String(version.clone()),
+            );
+        }
+        if let Some(modified) = self
+            .modified
+            .and_then(|modified| i64::try_from(modified).ok())
+        {
+            record.insert(
+                Yaml::String("modified".to_string()),
+                Yaml::Integer(modified),
+            );
+        }
+        Yaml::Hash(record)
+    }
+
+    fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        Some(Self {
+            path: yaml["path"].as_str()?.to_string(),
+            version: yaml["version"].as_str().map(str::to_string),
+            modified: yaml["modified"]
+                .as_i64()
+                .and_then(|modified| u64::try_from(modified).ok()),
+        })
+    }
+}
+
+// ==== struct DaemonStatus ====
+
+/// Runtime status of a process started by netgen inside a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaemonStatus {
+    pub name: String,
+    pub pid: i32,
+    pub running: bool,
+    // Report of the crash, backtrace included, if it dumped core.
+    pub crash: Option<PathBuf>,
+}
+
+// Identifier the kernel picks anew on every boot.
+fn current_boot_id() -> Option<String> {
+    fs::read_to_string(BOOT_ID_FILE)
+        .ok()
+        .map(|boot_id| boot_id.trim().to_string())
+}
+
+fn corrupted(reason: &str) -> StateError {
+    StateError::Corrupted {
+        path: state_file(),
+        reason: reason.to_string(),
+    }
+}
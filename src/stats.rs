@@ -0,0 +1,177 @@
+use std::time::{Duration, Instant};
+
+use crate::NetResult;
+use crate::csv;
+use crate::snapshot::Counters;
+use crate::table::{self, Align};
+use crate::topology::Topology;
+
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+// Share of the rate limit above which a link is shown as busy, then as
+// congested.
+const BUSY_PERCENT: f64 = 70.0;
+const CONGESTED_PERCENT: f64 = 90.0;
+
+// ==== struct IfaceRate ====
+
+/// Traffic of an interface of a router, averaged over a sampling interval.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfaceRate {
+    pub node: String,
+    pub iface: String,
+    pub rx_pps: f64,
+    pub rx_bps: f64,
+    pub tx_pps: f64,
+    pub tx_bps: f64,
+    // Rate limit of the link, in kbit/s, if impaired with one.
+    pub limit: Option<u32>,
+}
+
+impl IfaceRate {
+    /// Share of the rate limit used by what the interface sends, in percent.
+    ///
+    /// Impairments apply to what each end sends, so the limit is compared
+    /// with the transmit rate.
+    pub fn utilization(&self) -> Option<f64> {
+        let limit = f64::from(self.limit?) * 1000.0;
+        (limit > 0.0).then(|| self.tx_bps / limit * 100.0)
+    }
+}
+
+/// Samples the counters of every interface of the `routers` of `topology`
+/// twice, `interval` apart, and returns their rates.
+pub fn sample(
+    topology: &Topology,
+    routers: &[String],
+    interval: Duration,
+) -> NetResult<Vec<IfaceRate>> {
+    let mut before = vec![];
+    for router in &routers {
+        before.push((Counters::read(router)?, Instant::now()));
+    }
+    std::thread::sleep(interval);
+
+    let mut rates = vec![];
+    for (router, (before, read_at)) in routers.iter().zip(before) {
+        let after = Counters::read(router)?;
+        // Reading the counters of every router takes a while, measure.
+        let elapsed = read_at.elapsed().as_secs_f64();
+        for (iface, counters) in after {
+            // Interfaces which appeared in the meantime have no rate yet.
+            let Some(before) = before.get(&iface) else {
+                continue;
+            };
+            let delta = counters.since(before);
+            rates.push(IfaceRate {
+                node: router.clone(),
+                limit: topology.rate_limit(router, &iface),
+                iface,
+                rx_pps: delta.rx_packets as f64 / elapsed,
+                rx_bps: delta.rx_bytes as f64 * 8.0 / elapsed,
+                tx_pps: delta.tx_packets as f64 / elapsed,
+                tx_bps: delta.tx_bytes as f64 * 8.0 / elapsed,
+            });
+        }
+    }
+    Ok(rates)
+}
+
+/// Columns of `to_csv`, one row per interface and sample: when it was
+/// sampled in milliseconds since the Unix epoch, the rates of the interface,
+/// its rate limit in bit/s and the share of it used in percent.
+pub const CSV_COLUMNS: &[&str] = &[
+    "time_ms",
+    "node",
+    "iface",
+    "rx_pps",
+    "rx_bps",
+    "tx_pps",
+    "tx_bps",
+    "limit_bps",
+    "utilization_pct",
+];
+
+/// The rates sampled at `time_ms` as CSV rows, see `CSV_COLUMNS`.
+pub fn to_csv(rates: &[IfaceRate], time_ms: u128) -> String {
+    rates
+        .iter()
+        .map(|rate| {
+            csv::line(&[
+                time_ms.to_string(),
+                rate.node.clone(),
+                rate.iface.clone(),
+                format!("{:.0}", rate.rx_pps),
+                format!("{:.0}", rate.rx_bps),
+                format!("{:.0}", rate.tx_pps),
+                format!("{:.0}", rate.tx_bps),
+                rate.limit.map_or(String::new(), |limit| {
+                    (u64::from(limit) * 1000).to_string()
+                }),
+                rate.utilization()
+                    .map_or(String::new(), |used| format!("{used:.1}")),
+            ])
+        })
+        .collect()
+}
+
+/// Renders the rates as an aligned table, links close to their rate limit
+/// being colored when `color` is set.
+pub fn render(rates: &[IfaceRate], color: bool) -> String {
+    let headers = [
+        "NODE", "IFACE", "RX PPS", "RX RATE", "TX PPS", "TX RATE", "LIMIT",
+        "USED",
+    ];
+    let rows: Vec<[String; 8]> = rates
+        .iter()
+        .map(|rate| {
+            let (start, end) = match rate.utilization() {
+                Some(used) if color && used >= CONGESTED_PERCENT => {
+                    (RED, RESET)
+                }
+                Some(used) if color && used >= BUSY_PERCENT => (YELLOW, RESET),
+                _ => ("", ""),
+            };
+            [
+                rate.node.clone(),
+                rate.iface.clone(),
+                format!("{:.0}", rate.rx_pps),
+                format_bps(rate.rx_bps),
+                format!("{:.0}", rate.tx_pps),
+                format_bps(rate.tx_bps),
+                rate.limit.map_or("-".to_string(), |limit| {
+                    format_bps(f64::from(limit) * 1000.0)
+                }),
+                rate.utilization().map_or("-".to_string(), |used| {
+                    format!("{start}{used:.0}%{end}")
+                }),
+            ]
+        })
+        .collect();
+    // Names are aligned left, numbers right.
+    let align = [
+        Align::Left,
+        Align::Left,
+        Align::Right,
+        Align::Right,
+        Align::Right,
+        Align::Right,
+        Align::Right,
+        Align::Left,
+    ];
+    table::render(headers, align, &rows)
+}
+
+// e.g. "12.5 Mbit/s".
+fn format_bps(bps: f64) -> String {
+    let units = ["bit/s", "kbit/s", "Mbit/s", "Gbit/s"];
+    let mut value = bps;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < units.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", units[unit])
+}
@@ -0,0 +1,376 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::time::Duration;
+
+use crate::NetResult;
+use crate::backend::Backend;
+use crate::report::{TestCase, TestReport, json_string};
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+// ==== enum Health ====
+
+/// How a component of the running topology compares to the topology file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    Ok,
+    // Declared in the topology, but not found at runtime.
+    Missing,
+    // Present, but not operationally up.
+    Down,
+    // A process netgen started that is no longer running.
+    Dead,
+    // Same, a core of it having been collected.
+    Crashed,
+    // A startup config which did not load.
+    Failed,
+}
+
+impl Health {
+    fn color(&self) -> &'static str {
+        match self {
+            Health::Ok => GREEN,
+            Health::Down => YELLOW,
+            Health::Missing
+            | Health::Dead
+            | Health::Crashed
+            | Health::Failed => RED,
+        }
+    }
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let health = match self {
+            Health::Ok => "ok",
+            Health::Missing => "missing",
+            Health::Down => "down",
+            Health::Dead => "dead",
+            Health::Crashed => "crashed",
+            Health::Failed => "failed",
+        };
+        // Padding is honored so that the status can be aligned.
+        f.pad(health)
+    }
+}
+
+// ==== enum NodeHealth ====
+
+/// Overall health of a node, summing up the health of its components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeHealth {
+    Ok,
+    // Up, but some of its links, probes or daemons are not.
+    Degraded,
+    // Not there, cut off from the others, or running none of its daemons.
+    Failed,
+}
+
+impl NodeHealth {
+    fn color(&self) -> &'static str {
+        match self {
+            NodeHealth::Ok => GREEN,
+            NodeHealth::Degraded => YELLOW,
+            NodeHealth::Failed => RED,
+        }
+    }
+}
+
+impl fmt::Display for NodeHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let health = match self {
+            NodeHealth::Ok => "ok",
+            NodeHealth::Degraded => "degraded",
+            NodeHealth::Failed => "failed",
+        };
+        f.pad(health)
+    }
+}
+
+// ==== struct NodeStatus ====
+
+#[derive(Debug, Clone)]
+pub struct NodeStatus {
+    pub node: String,
+    pub health: NodeHealth,
+    // The components which are not ok, e.g. "interface eth0 down".
+    pub reasons: Vec<String>,
+}
+
+// ==== struct TopologyStatus ====
+
+#[derive(Debug, Clone)]
+pub struct StatusEntry {
+    pub node: String,
+    // "namespace", "interface", "link", "bridge", "probe", "daemon" or
+    // "config".
+    pub component: String,
+    pub name: String,
+    pub health: Health,
+}
+
+/// Runtime state of every component declared in the topology file.
+#[derive(Debug, Clone, Default)]
+pub struct TopologyStatus {
+    pub entries: Vec<StatusEntry>,
+    // Host address of the gRPC northbound of the proxied routers, by name.
+    pub grpc: BTreeMap<String, String>,
+}
+
+impl TopologyStatus {
+    pub(crate) fn push(
+        &mut self,
+        node: &str,
+        component: &str,
+        name: &str,
+        health: Health,
+    ) {
+        self.entries.push(StatusEntry {
+            node: node.to_string(),
+            component: component.to_string(),
+            name: name.to_string(),
+            health,
+        });
+    }
+
+    /// Whether anything differs from the topology file.
+    pub fn diverges(&self) -> bool {
+        self.entries.iter().any(|entry| entry.health != Health::Ok)
+    }
+
+    /// The health of every node, by name.
+    ///
+    /// A node has failed when its namespace or bridge is missing, when none
+    /// of its interfaces other than the loopback is up, or when none of its
+    /// daemons runs. It is degraded when anything else of it is not ok.
+    pub fn nodes(&self) -> Vec<NodeStatus> {
+        let mut nodes: BTreeMap<&str, Vec<&StatusEntry>> = BTreeMap::new();
+        for entry in &self.entries {
+            nodes.entry(&entry.node).or_default().push(entry);
+        }
+
+        nodes
+            .into_iter()
+            .map(|(node, entries)| {
+                // Health of the node's components of a kind, but `skip`.
+                let healths = |component: &str, skip: &str| -> Vec<Health> {
+                    entries
+                        .iter()
+                        .filter(|entry| {
+                            entry.component == component && entry.name != skip
+                        })
+                        .map(|entry| entry.health)
+                        .collect()
+                };
+                let none_ok = |component: &str, skip: &str| {
+                    let healths = healths(component, skip);
+                    !healths.is_empty()
+                        && healths.iter().all(|health| *health != Health::Ok)
+                };
+                let absent = healths("namespace", "")
+                    .into_iter()
+                    .chain(healths("bridge", ""))
+                    .any(|health| health == Health::Missing);
+
+                let reasons: Vec<String> = entries
+                    .iter()
+                    .filter(|entry| entry.health != Health::Ok)
+                    .map(|entry| {
+                        format!(
+                            "{} {} {}",
+                            entry.component, entry.name, entry.health
+                        )
+                    })
+                    .collect();
+                let health = if absent
+                    || none_ok("interface", "lo")
+                    || none_ok("daemon", "")
+                {
+                    NodeHealth::Failed
+                } else if !reasons.is_empty() {
+                    NodeHealth::Degraded
+                } else {
+                    NodeHealth::Ok
+                };
+                NodeStatus {
+                    node: node.to_string(),
+                    health,
+                    reasons,
+                }
+            })
+            .collect()
+    }
+
+    /// Renders the entries as an aligned table, followed by the health of
+    /// every node, the states being colored when `color` is set.
+    pub fn render(&self, color: bool) -> String {
+        let headers = ["NODE", "COMPONENT", "NAME", "STATE"];
+        let width = |column: fn(&StatusEntry) -> &str, header: &str| {
+            self.entries
+                .iter()
+                .map(|entry| column(entry).len())
+                .chain([header.len()])
+                .max()
+                .unwrap_or_default()
+        };
+        let node_width = width(|entry| &entry.node, headers[0]);
+        let component_width = width(|entry| &entry.component, headers[1]);
+        let name_width = width(|entry| &entry.name, headers[2]);
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{:node_width$}  {:component_width$}  {:name_width$}  {}",
+            headers[0], headers[1], headers[2], headers[3]
+        );
+        for entry in &self.entries {
+            let (start, end) = match color {
+                true => (entry.health.color(), RESET),
+                false => ("", ""),
+            };
+            let _ = writeln!(
+                out,
+                "{:node_width$}  {:component_width$}  {:name_width$}  {start}{}{end}",
+                entry.node, entry.component, entry.name, entry.health
+            );
+        }
+
+        let nodes = self.nodes();
+        let _ =
+            writeln!(out, "\n{:node_width$}  {:8}  REASONS", "NODE", "STATE");
+        for node in &nodes {
+            let (start, end) = match color {
+                true => (node.health.color(), RESET),
+                false => ("", ""),
+            };
+            let _ = writeln!(
+                out,
+                "{:node_width$}  {start}{:8}{end}  {}",
+                node.node,
+                node.health,
+                node.reasons.join(", ")
+            );
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let entries: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    r#"    {{"node": {}, "component": {}, "name": {}, "state": "{}"}}"#,
+                    json_string(&entry.node),
+                    json_string(&entry.component),
+                    json_string(&entry.name),
+                    entry.health
+                )
+            })
+            .collect();
+        let nodes: Vec<String> = self
+            .nodes()
+            .iter()
+            .map(|node| {
+                let reasons: Vec<String> = node
+                    .reasons
+                    .iter()
+                    .map(|reason| json_string(reason))
+                    .collect();
+                format!(
+                    r#"    {{"node": {}, "state": "{}", "reasons": [{}]}}"#,
+                    json_string(&node.node),
+                    node.health,
+                    reasons.join(", ")
+                )
+            })
+            .collect();
+        let grpc: Vec<String> = self
+            .grpc
+            .iter()
+            .map(|(node, addr)| {
+                format!("    {}: {}", json_string(node), json_string(addr))
+            })
+            .collect();
+        format!(
+            "{{\n  \"entries\": [\n{}\n  ],\n  \"nodes\": [\n{}\n  ],\n  \
+                \"grpc\": {{\n{}\n  }}\n}}\n",
+            entries.join(",\n"),
+            nodes.join(",\n"),
+            grpc.join(",\n")
+        )
+    }
+
+    /// One test case per entry, failing unless the entry is healthy.
+    pub fn to_report(&self) -> TestReport {
+        let mut report = TestReport::new("status");
+        for entry in &self.entries {
+            report.cases.push(TestCase {
+                name: format!(
+                    "{} {} {}",
+                    entry.node, entry.component, entry.name
+                ),
+                duration: Duration::ZERO,
+                failure: (entry.health != Health::Ok)
+                    .then(|| entry.health.to_string()),
+                node: Some(entry.node.clone()),
+            });
+        }
+        report
+    }
+}
+
+// ==== struct LinkState ====
+
+/// State of a network interface, as reported by `ip link`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LinkState {
+    // Administratively up.
+    pub(crate) up: bool,
+    // The lower layer, e.g. the veth peer, is up too.
+    pub(crate) lower_up: bool,
+}
+
+impl LinkState {
+    pub(crate) fn health(&self) -> Health {
+        match self.up && self.lower_up {
+            true => Health::Ok,
+            false => Health::Down,
+        }
+    }
+}
+
+/// Interfaces present in the device's namespace, by name.
+pub(crate) fn link_states(
+    backend: &dyn Backend,
+    device: Option<&str>,
+) -> NetResult<BTreeMap<String, LinkState>> {
+    let output = backend.exec(device, "ip", &["-o", "link", "show"])?;
+
+    // e.g. "4: eth0@if5: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500 ..."
+    let mut links = BTreeMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut fields = line.split_whitespace().skip(1);
+        let (Some(name), Some(flags)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let name = name.trim_end_matches(':');
+        let name = name.split_once('@').map_or(name, |(name, _)| name);
+        let flags: Vec<&str> = flags
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .split(',')
+            .collect();
+        links.insert(
+            name.to_string(),
+            LinkState {
+                up: flags.contains(&"UP"),
+                lower_up: flags.contains(&"LOWER_UP"),
+            },
+        );
+    }
+    Ok(links)
+}
@@ -0,0 +1,1003 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use ipnetwork::IpNetwork;
+use tracing::{debug, debug_span, warn};
+use yaml_rust2::YamlLoader;
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::cancel::CancelToken;
+use crate::check;
+use crate::churn::{Churn, ChurnRun};
+use crate::error::{ConfigError, NamespaceError, NetError, YamlPath};
+use crate::events;
+use crate::injector::check_prefixes;
+use crate::link::LinkEndpoint;
+use crate::lldp;
+use crate::mgmt::MgmtOutage;
+use crate::ramp::Ramp;
+use crate::report::{TestCase, TestReport};
+use crate::topology::{Topology, parse_end};
+use crate::{NetResult, exec_in_ns};
+
+// ==== struct Suite ====
+
+/// A self-contained regression test: the topology to bring up, the
+/// scenario to play against it and the assertions to check afterwards.
+///
+/// ```yaml
+/// name: ospf-basic
+/// topology: topo.yml
+/// settle: 10
+/// scenario:
+///   - exec: { node: rt1, cmd: "ip link set eth-rt2 down" }
+///   - sleep: 5
+///   - churn:
+///       injector: inj1
+///       dut: rt1
+///       prefixes: { start: 200.0.0.0/24, count: 1000 }
+///       rate: 100
+///       duration: 60
+///   - ramp: { link: [rt1:eth0, rt2:eth0], loss: [0, 30], duration: 300 }
+/// assertions:
+///   - ping: { node: rt1, dst: 3.3.3.3 }
+///   - route: { node: rt1, prefix: 3.3.3.3/32, via: 10.0.1.2 }
+///     timeout: 30
+///   - neigh-entries: { node: rt1, count: 2 }
+///   - adjacencies: { node: rt1, protocol: ospf, count: 2 }
+///   - name: isis adjacency
+///     exec: { node: rt1, cmd: "vtysh -c 'show isis neighbor'" }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Suite {
+    pub name: String,
+    // Resolved relative to the suite file.
+    pub topology: PathBuf,
+    // Time given to the topology to converge before the scenario runs.
+    pub settle: Duration,
+    pub scenario: Vec<Step>,
+    pub assertions: Vec<Assertion>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    Sleep(Duration),
+    Exec { node: String, cmd: String },
+    Churn(Churn),
+    Ramp(Ramp),
+    MgmtOutage(MgmtOutage),
+}
+
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    pub name: String,
+    // The check is retried every second until it passes or the timeout
+    // expires.
+    pub timeout: Duration,
+    pub check: Check,
+}
+
+#[derive(Debug, Clone)]
+pub enum Check {
+    /// `dst` answers a single ping from `node`.
+    Ping { node: String, dst: String },
+    /// `node` has a route for exactly `prefix` in its main table, through
+    /// `via` if set.
+    Route {
+        node: String,
+        prefix: IpNetwork,
+        via: Option<IpAddr>,
+    },
+    /// `node` has at least `count` resolved entries in its kernel neighbor
+    /// table, as `ip neigh` lists them: ARP and NDP, not routing protocol
+    /// neighbors, which `Adjacencies` checks.
+    NeighEntries { node: String, count: usize },
+    /// The FRR daemons of `node` have at least `count` adjacencies of
+    /// `protocol` up.
+    Adjacencies {
+        node: String,
+        protocol: Protocol,
+        count: usize,
+    },
+    /// `cmd` run through `sh -c` inside `node` exits successfully.
+    Exec { node: String, cmd: String },
+    /// The LLDP neighbor `iface` of `node` last heard, and still remembers,
+    /// is `peer`.
+    LldpPeer {
+        node: String,
+        iface: String,
+        peer: LinkEndpoint,
+    },
+}
+
+impl Suite {
+    /// Plays the scenario steps, in order, against the running `topology`.
+    /// Once `cancel` is cancelled, the step being played is cut short where
+    /// it can be, and the following ones are skipped.
+    ///
+    /// Returns the measurements of the steps taking some, i.e. churn.
+    pub fn run_scenario(
+        &self,
+        topology: &Topology,
+        cancel: &CancelToken,
+    ) -> NetResult<Vec<ChurnRun>> {
+        let scenario_span = debug_span!("scenario", suite = %self.name);
+        let _span_guard = scenario_span.enter();
+
+        let mut measurements = vec![];
+        for (index, step) in self.scenario.iter().enumerate() {
+            if cancel.is_cancelled() {
+                events::record(
+                    "scenario",
+                    &self.name,
+                    &format!("cancelled before step {}", index + 1),
+                );
+                break;
+            }
+            match step {
+                Step::Sleep(duration) => {
+                    debug!(?duration, "sleeping");
+                    events::record(
+                        "scenario",
+                        &self.name,
+                        &format!("sleep {duration:?}"),
+                    );
+                    // Cancellation is reported before the next step.
+                    let _ = cancel.sleep(*duration);
+                }
+                Step::Exec { node, cmd } => {
+                    debug!(%node, %cmd, "executing");
+                    events::record("scenario", node, &format!("exec {cmd}"));
+                    let output = exec_in_ns(node, "sh", &["-c", cmd])?;
+                    if !output.status.success() {
+                        warn!(%node, %cmd, status = %output.status,
+                            "scenario command failed");
+                    }
+                }
+                Step::Churn(churn) => {
+                    events::record("scenario", &churn.dut, &churn.describe());
+                    let start = Instant::now();
+                    let stats = churn.run(cancel)?;
+                    debug!(churn = %churn.describe(), %stats, "churn done");
+                    measurements.push(ChurnRun {
+                        step: index,
+                        churn: churn.clone(),
+                        stats,
+                        duration: start.elapsed(),
+                    });
+                }
+                Step::Ramp(ramp) => {
+                    events::record("scenario", &ramp.src, &ramp.describe());
+                    ramp.run(topology, cancel)?;
+                }
+                Step::MgmtOutage(outage) => {
+                    events::record("scenario", &self.name, &outage.describe());
+                    topology.set_mgmt_reachable(
+                        &outage.nodes,
+                        &outage.ifaces,
+                        false,
+                    )?;
+                    // Restored even when cut short.
+                    let _ = cancel.sleep(outage.duration);
+                    topology.set_mgmt_reachable(
+                        &outage.nodes,
+                        &outage.ifaces,
+                        true,
+                    )?;
+                }
+            }
+        }
+        Ok(measurements)
+    }
+
+    /// Evaluates every assertion against the running topology. Those left
+    /// once `cancel` is cancelled fail without being checked.
+    pub fn check_assertions(&self, cancel: &CancelToken) -> TestReport {
+        let mut report = TestReport::new(&self.name);
+        for assertion in &self.assertions {
+            report.cases.push(assertion.evaluate(cancel));
+        }
+        report
+    }
+}
+
+// ==== impl Assertion ====
+
+impl Assertion {
+    pub fn evaluate(&self, cancel: &CancelToken) -> TestCase {
+        let start = Instant::now();
+        let failure = loop {
+            if cancel.is_cancelled() {
+                break Some("cancelled".to_string());
+            }
+            match self.check.run() {
+                Ok(()) => break None,
+                Err(reason) if start.elapsed() >= self.timeout => {
+                    break Some(reason);
+                }
+                Err(_) => {
+                    let _ = cancel.sleep(Duration::from_secs(1));
+                }
+            }
+        };
+
+        TestCase {
+            name: self.name.clone(),
+            duration: start.elapsed(),
+            failure,
+            node: Some(self.check.node().to_string()),
+        }
+    }
+}
+
+// ==== enum Protocol ====
+
+/// Routing protocol an `adjacencies` assertion counts the adjacencies of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// OSPFv2 neighbors in the Full state.
+    Ospf,
+    /// OSPFv3 neighbors in the Full state.
+    Ospf6,
+    /// BGP peers Established in any address family.
+    Bgp,
+}
+
+impl Protocol {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ospf" => Some(Self::Ospf),
+            "ospf6" => Some(Self::Ospf6),
+            "bgp" => Some(Self::Bgp),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Ospf => "ospf",
+            Self::Ospf6 => "ospf6",
+            Self::Bgp => "bgp",
+        }
+    }
+
+    // vtysh command listing the adjacencies, as JSON.
+    fn command(&self) -> &'static str {
+        match self {
+            Self::Ospf => "show ip ospf neighbor json",
+            Self::Ospf6 => "show ipv6 ospf6 neighbor json",
+            Self::Bgp => "show bgp summary json",
+        }
+    }
+
+    // Adjacencies up in `doc`, what `command` output. JSON being YAML, it
+    // is loaded as such.
+    fn count_up(&self, doc: &Yaml) -> usize {
+        // FRR names the state nbrState since 8.0, state before.
+        let full = |neighbor: &Yaml| {
+            neighbor["nbrState"]
+                .as_str()
+                .or(neighbor["state"].as_str())
+                .is_some_and(|state| state.starts_with("Full"))
+        };
+        match self {
+            // Neighbors by router-id, each with a list of interfaces.
+            Self::Ospf => doc["neighbors"].as_hash().map_or(0, |neighbors| {
+                neighbors
+                    .values()
+                    .filter_map(Yaml::as_vec)
+                    .flatten()
+                    .filter(|neighbor| full(neighbor))
+                    .count()
+            }),
+            Self::Ospf6 => doc["neighbors"].as_vec().map_or(0, |neighbors| {
+                neighbors.iter().filter(|neighbor| full(neighbor)).count()
+            }),
+            // Peers by address family, a peer counting once.
+            Self::Bgp => doc.as_hash().map_or(0, |families| {
+                families
+                    .values()
+                    .filter_map(|family| family["peers"].as_hash())
+                    .flatten()
+                    .filter(|(_, peer)| {
+                        peer["state"].as_str() == Some("Established")
+                    })
+                    .map(|(address, _)| address)
+                    .collect::<BTreeSet<_>>()
+                    .len()
+            }),
+        }
+    }
+}
+
+// ==== impl Check ====
+
+impl Check {
+    fn run(&self) -> Result<(), String> {
+        match self {
+            Self::Ping { node, dst } => {
+                let output =
+                    exec_in_ns(node, "ping", &["-c", "1", "-W", "1", dst])
+                        .map_err(|err| err.to_string())?;
+                if output.status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("{node} cannot reach {dst}"))
+                }
+            }
+            Self::Route { node, prefix, via } => {
+                let route = check::lookup_route(node, *prefix)
+                    .map_err(|err| err.to_string())?;
+                match (route, via) {
+                    (None, _) => {
+                        Err(format!("{node} has no route for {prefix}"))
+                    }
+                    (Some(route), Some(via))
+                        if !route.nexthops.contains(via) =>
+                    {
+                        Err(format!("{node} routes {route}, not via {via}"))
+                    }
+                    (Some(_), _) => Ok(()),
+                }
+            }
+            Self::NeighEntries { node, count } => {
+                let output = exec_in_ns(node, "ip", &["neigh", "show"])
+                    .map_err(|err| err.to_string())?;
+                let found = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter(|line| {
+                        !line.ends_with("FAILED")
+                            && !line.ends_with("INCOMPLETE")
+                    })
+                    .count();
+                if found >= *count {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "{node} has {found} neighbor entries, expected at \
+                            least {count}"
+                    ))
+                }
+            }
+            Self::Adjacencies {
+                node,
+                protocol,
+                count,
+            } => {
+                let output =
+                    exec_in_ns(node, "vtysh", &["-c", protocol.command()])
+                        .map_err(|err| err.to_string())?;
+                if !output.status.success() {
+                    return Err(format!(
+                        "'{}' on {node} exited with {}: {}",
+                        protocol.command(),
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+                let docs = YamlLoader::load_from_str(&String::from_utf8_lossy(
+                    &output.stdout,
+                ))
+                .map_err(|err| {
+                    format!(
+                        "unexpected output of '{}': {err}",
+                        protocol.command()
+                    )
+                })?;
+                let found =
+                    docs.first().map_or(0, |doc| protocol.count_up(doc));
+                if found >= *count {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "{node} has {found} {} adjacencies up, expected at \
+                            least {count}",
+                        protocol.name()
+                    ))
+                }
+            }
+            Self::Exec { node, cmd } => {
+                let output = exec_in_ns(node, "sh", &["-c", cmd])
+                    .map_err(|err| err.to_string())?;
+                if output.status.success() {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "'{cmd}' on {node} exited with {}: {}",
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ))
+                }
+            }
+            Self::LldpPeer { node, iface, peer } => {
+                let Some(neighbor) = lldp::neighbors(node)
+                    .into_iter()
+                    .find(|neighbor| neighbor.iface == *iface)
+                    .filter(|neighbor| !neighbor.expired())
+                else {
+                    return Err(format!(
+                        "{node}:{iface} has no LLDP neighbor, expected {peer}"
+                    ));
+                };
+                let heard = format!(
+                    "{}:{}",
+                    neighbor.system_name.as_deref().unwrap_or("?"),
+                    neighbor.port_id
+                );
+                if heard == peer.to_string() {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "{node}:{iface} hears {heard}, expected {peer}"
+                    ))
+                }
+            }
+        }
+    }
+
+    // The node the check runs in.
+    fn node(&self) -> &str {
+        match self {
+            Self::Ping { node, .. }
+            | Self::Route { node, .. }
+            | Self::NeighEntries { node, .. }
+            | Self::Adjacencies { node, .. }
+            | Self::Exec { node, .. }
+            | Self::LldpPeer { node, .. } => node,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Ping { node, dst } => format!("ping {node} -> {dst}"),
+            Self::Route {
+                node,
+                prefix,
+                via: None,
+            } => format!("route {node} {prefix}"),
+            Self::Route {
+                node,
+                prefix,
+                via: Some(via),
+            } => format!("route {node} {prefix} via {via}"),
+            Self::NeighEntries { node, count } => {
+                format!("neigh-entries {node} >= {count}")
+            }
+            Self::Adjacencies {
+                node,
+                protocol,
+                count,
+            } => format!("adjacencies {node} {} >= {count}", protocol.name()),
+            Self::Exec { node, cmd } => format!("exec {node}: {cmd}"),
+            Self::LldpPeer { node, iface, peer } => {
+                format!("lldp-peer {node}:{iface} = {peer}")
+            }
+        }
+    }
+}
+
+// ==== struct SuiteParser ====
+
+pub struct SuiteParser;
+
+impl SuiteParser {
+    pub fn from_yaml_file(path: &str) -> NetResult<Suite> {
+        let mut file =
+            File::open(path).map_err(|err| NamespaceError::FileOpen {
+                path: path.to_string(),
+                source: err,
+            })?;
+        let mut contents = String::new();
+        let _ = file.read_to_string(&mut contents);
+
+        let base_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        Self::from_yaml_str(&contents, base_dir)
+    }
+
+    /// `base_dir` is the directory relative paths in the suite (e.g the
+    /// topology file) are resolved against.
+    pub fn from_yaml_str(yaml_str: &str, base_dir: &Path) -> NetResult<Suite> {
+        let yaml_content =
+            YamlLoader::load_from_str(yaml_str).map_err(|err| {
+                NetError::ConfigError(ConfigError::YamlSyntax(err))
+            })?;
+
+        let Some(Yaml::Hash(suite_config)) = yaml_content.first() else {
+            return Err(ConfigError::IncorrectType {
+                path: YamlPath::new().unknown(),
+                expected: "hash".to_string(),
+            }
+            .into());
+        };
+
+        let name = match suite_config.get(&Yaml::String("name".to_string())) {
+            Some(Yaml::String(name)) => name.clone(),
+            _ => "netgen".to_string(),
+        };
+
+        let topology =
+            match suite_config.get(&Yaml::String("topology".to_string())) {
+                Some(Yaml::String(topology)) => base_dir.join(topology),
+                _ => return Err(ConfigError::TopologyFileMissing.into()),
+            };
+
+        let settle = get_seconds(suite_config, "settle", YamlPath::new())?;
+
+        let mut scenario = vec![];
+        match suite_config.get(&Yaml::String("scenario".to_string())) {
+            Some(Yaml::Array(steps)) => {
+                for step in steps {
+                    scenario.push(Self::parse_step(step)?);
+                }
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: YamlPath::new().key("scenario").unknown(),
+                    expected: "array".to_string(),
+                }
+                .into());
+            }
+        }
+
+        let mut assertions = vec![];
+        match suite_config.get(&Yaml::String("assertions".to_string())) {
+            Some(Yaml::Array(configs)) => {
+                for config in configs {
+                    assertions.push(Self::parse_assertion(config)?);
+                }
+            }
+            Some(Yaml::Null) | None => {}
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: YamlPath::new().key("assertions").unknown(),
+                    expected: "array".to_string(),
+                }
+                .into());
+            }
+        }
+
+        Ok(Suite {
+            name,
+            topology,
+            settle,
+            scenario,
+            assertions,
+        })
+    }
+
+    fn parse_step(step_config: &Yaml) -> NetResult<Step> {
+        let path = YamlPath::new().key("scenario");
+        let step_config = as_hash(step_config, path.clone())?;
+
+        if step_config.contains_key(&Yaml::String("sleep".to_string())) {
+            let duration = get_seconds(step_config, "sleep", path)?;
+            return Ok(Step::Sleep(duration));
+        }
+
+        if let Some(exec_config) =
+            step_config.get(&Yaml::String("exec".to_string()))
+        {
+            let exec_path = path.clone().key("exec");
+            let exec_config = as_hash(exec_config, exec_path.clone())?;
+            return Ok(Step::Exec {
+                node: get_str(exec_config, "node", exec_path.clone())?,
+                cmd: get_str(exec_config, "cmd", exec_path)?,
+            });
+        }
+
+        if let Some(churn_config) =
+            step_config.get(&Yaml::String("churn".to_string()))
+        {
+            return Self::parse_churn(churn_config).map(Step::Churn);
+        }
+
+        if let Some(ramp_config) =
+            step_config.get(&Yaml::String("ramp".to_string()))
+        {
+            return Self::parse_ramp(ramp_config).map(Step::Ramp);
+        }
+
+        if let Some(outage_config) =
+            step_config.get(&Yaml::String("mgmt-outage".to_string()))
+        {
+            return Self::parse_mgmt_outage(outage_config)
+                .map(Step::MgmtOutage);
+        }
+
+        Err(ConfigError::MissingField {
+            path: path.clone().unknown(),
+        }
+        .into())
+    }
+
+    fn parse_mgmt_outage(outage_config: &Yaml) -> NetResult<MgmtOutage> {
+        let path = YamlPath::new().key("scenario").key("mgmt-outage");
+        let outage_config = as_hash(outage_config, path.clone())?;
+
+        let strings = |field: &str| -> NetResult<Vec<String>> {
+            match outage_config.get(&Yaml::String(field.to_string())) {
+                Some(Yaml::Array(values)) => values
+                    .iter()
+                    .map(|value| {
+                        value.as_str().map(str::to_string).ok_or_else(|| {
+                            ConfigError::IncorrectType {
+                                path: path.clone().key(field).unknown(),
+                                expected: "string".to_string(),
+                            }
+                            .into()
+                        })
+                    })
+                    .collect(),
+                Some(Yaml::Null) | None => Ok(vec![]),
+                Some(_) => Err(ConfigError::IncorrectType {
+                    path: path.clone().key(field).unknown(),
+                    expected: "array".to_string(),
+                }
+                .into()),
+            }
+        };
+        let nodes = strings("nodes")?;
+        if nodes.is_empty() {
+            return Err(ConfigError::MissingField {
+                path: path.clone().key("nodes").unknown(),
+            }
+            .into());
+        }
+        Ok(MgmtOutage {
+            nodes,
+            ifaces: strings("ifaces")?,
+            duration: get_seconds(outage_config, "duration", path)?,
+        })
+    }
+
+    fn parse_churn(churn_config: &Yaml) -> NetResult<Churn> {
+        let path = YamlPath::new().key("scenario").key("churn");
+        let churn_config = as_hash(churn_config, path.clone())?;
+
+        let prefixes_path = path.clone().key("prefixes");
+        let prefixes_config = as_hash(
+            churn_config
+                .get(&Yaml::String("prefixes".to_string()))
+                .unwrap_or(&Yaml::Null),
+            prefixes_path.clone(),
+        )?;
+        let start_str =
+            get_str(prefixes_config, "start", prefixes_path.clone())?;
+        let start =
+            start_str
+                .parse()
+                .map_err(|err| ConfigError::InvalidAddress {
+                    address: start_str.clone(),
+                    path: prefixes_path.clone().key("start"),
+                    source: err,
+                })?;
+
+        let positive =
+            |config: &Hash, field: &str, mut path: YamlPath| match config
+                .get(&Yaml::String(field.to_string()))
+            {
+                Some(Yaml::Integer(value)) if *value > 0 => Ok(*value as u64),
+                _ => Err(NetError::from(ConfigError::IncorrectType {
+                    path: path.key(field).unknown(),
+                    expected: "positive integer".to_string(),
+                })),
+            };
+        let count = positive(prefixes_config, "count", prefixes_path.clone())?;
+        check_prefixes(start, count, &prefixes_path)?;
+        let rate = u32::try_from(positive(churn_config, "rate", path.clone())?)
+            .map_err(|_| ConfigError::IncorrectType {
+                path: path.clone().key("rate").unknown(),
+                expected: "updates per second".to_string(),
+            })?;
+
+        let timeout = match get_seconds(churn_config, "timeout", path.clone())?
+        {
+            Duration::ZERO => Churn::DEFAULT_TIMEOUT,
+            timeout => timeout,
+        };
+
+        Ok(Churn {
+            injector: get_str(churn_config, "injector", path.clone())?,
+            dut: get_str(churn_config, "dut", path.clone())?,
+            start,
+            count,
+            rate,
+            duration: get_seconds(churn_config, "duration", path)?,
+            timeout,
+        })
+    }
+
+    fn parse_ramp(ramp_config: &Yaml) -> NetResult<Ramp> {
+        let path = YamlPath::new().key("scenario").key("ramp");
+        let ramp_config = as_hash(ramp_config, path.clone())?;
+
+        let link_error = || {
+            NetError::from(ConfigError::IncorrectType {
+                path: path.clone().key("link").unknown(),
+                expected: "[<node>:<iface>, <node>:<iface>]".to_string(),
+            })
+        };
+        let ends = match ramp_config.get(&Yaml::String("link".to_string())) {
+            Some(Yaml::Array(ends)) if ends.len() == 2 => ends
+                .iter()
+                .map(|end| match end {
+                    Yaml::String(end) => Ok(parse_end(end)?.to_string()),
+                    _ => Err(link_error()),
+                })
+                .collect::<NetResult<Vec<String>>>()?,
+            Some(_) => return Err(link_error()),
+            None => {
+                return Err(ConfigError::MissingField {
+                    path: path.clone().key("link").unknown(),
+                }
+                .into());
+            }
+        };
+
+        // A [from, to] pair of positive numbers, if `field` is ramped.
+        let range = |field: &str| -> NetResult<Option<(f64, f64)>> {
+            let number = |value: &Yaml| match value {
+                Yaml::Integer(value) => Some(*value as f64),
+                Yaml::Real(value) => value.parse().ok(),
+                _ => None,
+            };
+            match ramp_config.get(&Yaml::String(field.to_string())) {
+                Some(Yaml::Null) | None => Ok(None),
+                Some(Yaml::Array(values))
+                    if values.len() == 2
+                        && values.iter().all(|value| {
+                            number(value).is_some_and(|value| value >= 0.0)
+                        }) =>
+                {
+                    Ok(number(&values[0]).zip(number(&values[1])))
+                }
+                Some(_) => Err(ConfigError::IncorrectType {
+                    path: path.clone().key(field).unknown(),
+                    expected: "[from, to]".to_string(),
+                }
+                .into()),
+            }
+        };
+        // A range of milliseconds, if `field` is ramped.
+        let millis = |field: &str| -> NetResult<Option<(Duration, Duration)>> {
+            let Some((from, to)) = range(field)? else {
+                return Ok(None);
+            };
+            let duration =
+                |millis: f64| Duration::try_from_secs_f64(millis / 1000.0);
+            match (duration(from), duration(to)) {
+                (Ok(from), Ok(to)) => Ok(Some((from, to))),
+                _ => Err(ConfigError::IncorrectType {
+                    path: path.clone().key(field).unknown(),
+                    expected: "milliseconds".to_string(),
+                }
+                .into()),
+            }
+        };
+        let delay = millis("delay")?;
+        let jitter = millis("jitter")?;
+        let loss = range("loss")?;
+        if loss.is_some_and(|(from, to)| from > 100.0 || to > 100.0) {
+            return Err(ConfigError::IncorrectType {
+                path: path.clone().key("loss").unknown(),
+                expected: "percentages".to_string(),
+            }
+            .into());
+        }
+        let rate = match range("rate")? {
+            Some((from, to)) if from >= 1.0 && to >= 1.0 => {
+                Some((from as u32, to as u32))
+            }
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: path.clone().key("rate").unknown(),
+                    expected: "kbit/s".to_string(),
+                }
+                .into());
+            }
+            None => None,
+        };
+        if delay.is_none()
+            && jitter.is_none()
+            && loss.is_none()
+            && rate.is_none()
+        {
+            return Err(ConfigError::IncorrectType {
+                path: path.clone().unknown(),
+                expected: "delay, jitter, loss or rate to ramp".to_string(),
+            }
+            .into());
+        }
+
+        let interval = match get_seconds(ramp_config, "interval", path.clone())?
+        {
+            Duration::ZERO => Ramp::DEFAULT_INTERVAL,
+            interval => interval,
+        };
+
+        Ok(Ramp {
+            src: ends[0].clone(),
+            dst: ends[1].clone(),
+            delay,
+            jitter,
+            loss,
+            rate,
+            duration: get_seconds(ramp_config, "duration", path)?,
+            interval,
+        })
+    }
+
+    fn parse_assertion(assertion_config: &Yaml) -> NetResult<Assertion> {
+        let path = YamlPath::new().key("assertions");
+        let assertion_config = as_hash(assertion_config, path.clone())?;
+
+        let timeout = get_seconds(assertion_config, "timeout", path.clone())?;
+
+        let check = if let Some(config) =
+            assertion_config.get(&Yaml::String("ping".to_string()))
+        {
+            let ping_path = path.clone().key("ping");
+            let config = as_hash(config, ping_path.clone())?;
+            Check::Ping {
+                node: get_str(config, "node", ping_path.clone())?,
+                dst: get_str(config, "dst", ping_path)?,
+            }
+        } else if let Some(config) =
+            assertion_config.get(&Yaml::String("route".to_string()))
+        {
+            let route_path = path.clone().key("route");
+            let config = as_hash(config, route_path.clone())?;
+            let node = get_str(config, "node", route_path.clone())?;
+            let prefix_str = get_str(config, "prefix", route_path.clone())?;
+            let prefix = prefix_str.parse().map_err(|err| {
+                ConfigError::InvalidAddress {
+                    address: prefix_str.clone(),
+                    path: route_path.clone().key("prefix"),
+                    source: err,
+                }
+            })?;
+            let via = match config.get(&Yaml::String("via".to_string())) {
+                Some(Yaml::Null) | None => None,
+                Some(_) => {
+                    let via = get_str(config, "via", route_path.clone())?;
+                    Some(via.parse().map_err(|_| {
+                        ConfigError::IncorrectType {
+                            path: route_path.clone().key("via").unknown(),
+                            expected: "ip address".to_string(),
+                        }
+                    })?)
+                }
+            };
+            Check::Route { node, prefix, via }
+        } else if let Some(config) =
+            assertion_config.get(&Yaml::String("neigh-entries".to_string()))
+        {
+            let entries_path = path.clone().key("neigh-entries");
+            let config = as_hash(config, entries_path.clone())?;
+            Check::NeighEntries {
+                node: get_str(config, "node", entries_path.clone())?,
+                count: get_count(config, entries_path)?,
+            }
+        } else if let Some(config) =
+            assertion_config.get(&Yaml::String("adjacencies".to_string()))
+        {
+            let adjacencies_path = path.clone().key("adjacencies");
+            let config = as_hash(config, adjacencies_path.clone())?;
+            let protocol =
+                get_str(config, "protocol", adjacencies_path.clone())?;
+            let protocol = Protocol::from_name(&protocol).ok_or_else(|| {
+                ConfigError::IncorrectType {
+                    path: adjacencies_path.clone().key("protocol").unknown(),
+                    expected: "ospf, ospf6 or bgp".to_string(),
+                }
+            })?;
+            Check::Adjacencies {
+                node: get_str(config, "node", adjacencies_path.clone())?,
+                protocol,
+                count: get_count(config, adjacencies_path)?,
+            }
+        } else if let Some(config) =
+            assertion_config.get(&Yaml::String("exec".to_string()))
+        {
+            let exec_path = path.clone().key("exec");
+            let config = as_hash(config, exec_path.clone())?;
+            Check::Exec {
+                node: get_str(config, "node", exec_path.clone())?,
+                cmd: get_str(config, "cmd", exec_path)?,
+            }
+        } else {
+            return Err(ConfigError::MissingField {
+                path: path.clone().unknown(),
+            }
+            .into());
+        };
+
+        let name = match assertion_config.get(&Yaml::String("name".to_string()))
+        {
+            Some(Yaml::String(name)) => name.clone(),
+            _ => check.describe(),
+        };
+
+        Ok(Assertion {
+            name,
+            timeout,
+            check,
+        })
+    }
+}
+
+// The `count` of an assertion, which is mandatory.
+fn get_count(config: &Hash, mut path: YamlPath) -> NetResult<usize> {
+    match config.get(&Yaml::String("count".to_string())) {
+        Some(Yaml::Integer(count)) if *count >= 0 => Ok(*count as usize),
+        _ => Err(ConfigError::IncorrectType {
+            path: path.key("count").unknown(),
+            expected: "positive integer".to_string(),
+        }
+        .into()),
+    }
+}
+
+pub(crate) fn as_hash(config: &Yaml, mut path: YamlPath) -> NetResult<&Hash> {
+    match config {
+        Yaml::Hash(config) => Ok(config),
+        _ => Err(ConfigError::IncorrectType {
+            path: path.unknown(),
+            expected: "hash".to_string(),
+        }
+        .into()),
+    }
+}
+
+pub(crate) fn get_str(
+    config: &Hash,
+    field: &str,
+    mut path: YamlPath,
+) -> NetResult<String> {
+    match config.get(&Yaml::String(field.to_string())) {
+        Some(Yaml::String(value)) => Ok(value.clone()),
+        Some(_) => Err(ConfigError::IncorrectType {
+            path: path.key(field).unknown(),
+            expected: "string".to_string(),
+        }
+        .into()),
+        None => Err(ConfigError::MissingField {
+            path: path.key(field).unknown(),
+        }
+        .into()),
+    }
+}
+
+pub(crate) fn get_seconds(
+    config: &Hash,
+    field: &str,
+    mut path: YamlPath,
+) -> NetResult<Duration> {
+    match config.get(&Yaml::String(field.to_string())) {
+        Some(Yaml::Integer(secs)) if *secs >= 0 => {
+            Ok(Duration::from_secs(*secs as u64))
+        }
+        Some(Yaml::Real(secs)) => secs
+            .parse::<f64>()
+            .ok()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .ok_or_else(|| {
+                ConfigError::IncorrectType {
+                    path: path.key(field).unknown(),
+                    expected: "seconds".to_string(),
+                }
+                .into()
+            }),
+        Some(Yaml::Null) | None => Ok(Duration::ZERO),
+        Some(_) => Err(ConfigError::IncorrectType {
+            path: path.key(field).unknown(),
+            expected: "seconds".to_string(),
+        }
+        .into()),
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::NetResult;
+use crate::error::NetError;
+use crate::report::{TestReport, json_string};
+use crate::template::Value;
+
+// ==== struct SweepVar ====
+
+/// A template variable of a sweep and the values it takes, parsed from
+/// `name=value,value,..`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SweepVar {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl std::str::FromStr for SweepVar {
+    type Err = NetError;
+
+    fn from_str(var: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            NetError::BasicError(format!(
+                "Invalid sweep variable '{var}', expected name=value,value,.."
+            ))
+        };
+        let (name, values) = var.split_once('=').ok_or_else(invalid)?;
+        let name = name.trim();
+        let values: Vec<String> = values
+            .split(',')
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .collect();
+        let valid_name = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+        if !valid_name || values.is_empty() {
+            return Err(invalid());
+        }
+        Ok(SweepVar {
+            name: name.to_string(),
+            values,
+        })
+    }
+}
+
+/// Every combination of the values of `vars`, the last variable varying
+/// fastest.
+pub fn grid(vars: &[SweepVar]) -> Vec<BTreeMap<String, String>> {
+    vars.iter()
+        .fold(vec![BTreeMap::new()], |combinations, var| {
+            combinations
+                .iter()
+                .flat_map(|combination| {
+                    var.values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.insert(var.name.clone(), value.clone());
+                        combination
+                    })
+                })
+                .collect()
+        })
+}
+
+/// `combination` as template variables, values which are integers being
+/// usable in arithmetic.
+pub fn template_vars(
+    combination: &BTreeMap<String, String>,
+) -> BTreeMap<String, Value> {
+    combination
+        .iter()
+        .map(|(name, value)| {
+            let value = match value.parse() {
+                Ok(value) => Value::Int(value),
+                Err(_) => Value::Str(value.clone()),
+            };
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+/// Short form of a combination, e.g. "delay=10ms loss=1".
+pub fn describe(combination: &BTreeMap<String, String>) -> String {
+    combination
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// ==== struct SweepReport ====
+
+/// Results of a test suite, for every combination of a sweep.
+#[derive(Debug, Clone, Default)]
+pub struct SweepReport {
+    pub runs: Vec<(BTreeMap<String, String>, TestReport)>,
+}
+
+impl SweepReport {
+    pub fn failures(&self) -> usize {
+        self.runs.iter().map(|(_, report)| report.failures()).sum()
+    }
+
+    /// The results of every run in a single report, the cases being
+    /// prefixed with their combination.
+    pub fn to_report(&self, name: &str) -> TestReport {
+        let mut merged = TestReport::new(name);
+        for (combination, report) in &self.runs {
+            let prefix = describe(combination);
+            merged.cases.extend(report.cases.iter().map(|case| {
+                let mut case = case.clone();
+                case.name = format!("[{prefix}] {}", case.name);
+                case
+            }));
+        }
+        merged
+    }
+
+    pub fn to_json(&self) -> String {
+        let runs: Vec<String> = self
+            .runs
+            .iter()
+            .map(|(combination, report)| {
+                let vars: Vec<String> = combination
+                    .iter()
+                    .map(|(name, value)| {
+                        format!("{}: {}", json_string(name), json_string(value))
+                    })
+                    .collect();
+                let cases: Vec<String> = report
+                    .cases
+                    .iter()
+                    .map(|case| {
+                        let failure = case
+                            .failure
+                            .as_deref()
+                            .map_or("null".to_string(), json_string);
+                        format!(
+                            r#"{{"name": {}, "duration": {:.3}, "failure": {failure}}}"#,
+                            json_string(&case.name),
+                            case.duration.as_secs_f64()
+                        )
+                    })
+                    .collect();
+                format!(
+                    r#"    {{"vars": {{{}}}, "tests": {}, "failures": {}, "cases": [{}]}}"#,
+                    vars.join(", "),
+                    report.cases.len(),
+                    report.failures(),
+                    cases.join(", ")
+                )
+            })
+            .collect();
+
+        format!("{{\n  \"runs\": [\n{}\n  ]\n}}\n", runs.join(",\n"))
+    }
+
+    pub fn write_json(&self, path: &str) -> NetResult<()> {
+        fs::write(path, self.to_json()).map_err(|err| {
+            NetError::BasicError(format!("Unable to write {path}: {err:?}"))
+        })
+    }
+}
@@ -0,0 +1,47 @@
+use std::fmt::Write as _;
+
+// ==== enum Align ====
+
+/// Side of its column a cell is aligned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Align {
+    Left,
+    Right,
+}
+
+/// Renders `rows` under `headers`, each column as wide as its widest cell
+/// and two spaces apart from the next, without trailing spaces.
+///
+/// Only the width of the last column may be thrown off by escape sequences
+/// in its cells, which is why colors go there.
+pub(crate) fn render<const N: usize>(
+    headers: [&str; N],
+    align: [Align; N],
+    rows: &[[String; N]],
+) -> String {
+    let widths: Vec<usize> = (0..N)
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].len())
+                .chain([headers[column].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut out = String::new();
+    let header_row = headers.map(str::to_string);
+    for row in [&header_row].into_iter().chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .zip(align)
+            .map(|((cell, width), align)| match align {
+                Align::Left => format!("{cell:width$}"),
+                Align::Right => format!("{cell:>width$}"),
+            })
+            .collect();
+        let _ = writeln!(out, "{}", line.join("  ").trim_end());
+    }
+    out
+}
@@ -0,0 +1,723 @@
+//! Minimal templating used to pre-process topology files.
+//!
+//! Two constructs are supported:
+//!
+//! - `{{ expr }}` anywhere in a line, replaced by the value of `expr`.
+//! - `{% for var in expr %}` / `{% endfor %}` on their own lines, repeating
+//!   the lines in between once per item of `expr`. Items that are lists can
+//!   be unpacked into several variables: `{% for i, name in enumerate(..) %}`.
+//!
+//! Expressions are made of integers, quoted strings, lists (`[a, b]`),
+//! variables, `+`, `-`, `*` and the following functions:
+//!
+//! - `range(end)`, `range(start, end)`, `range(start, end, step)`
+//! - `cidrhost(prefix, n)`: n-th address of `prefix`, counting from the end
+//!   when `n` is negative.
+//! - `zip(list, list, ..)`: lists of the items at the same position.
+//! - `enumerate(list)`: `[index, item]` pairs.
+//!
+//! Integers are 64-bit, and arithmetic overflowing them is an error, as is a
+//! list, from `range` or `+`, of more than 65536 items.
+//!
+//! Loops and expressions can only be nested so deep, loops can only run so
+//! many times in all, and the rendered file is capped in size, so that a
+//! malformed file fails instead of exhausting the stack, memory or time.
+//!
+//! ```yaml
+//! routers:
+//! {% for i in range(1, 4) %}
+//!   rt{{ i }}:
+//!     interfaces:
+//!       lo: [ {{ cidrhost("10.255.0.0/24", i) }}/32 ]
+//! {% endfor %}
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+
+use crate::NetResult;
+use crate::error::ConfigError;
+
+// How deep loops, and separately expressions, can be nested.
+const MAX_NESTING: usize = 32;
+
+// Most items of a list built by an expression.
+const MAX_LIST_LEN: usize = 1 << 16;
+
+// Most iterations of all the loops of a file together, empty ones included.
+const MAX_ITERATIONS: usize = 1 << 20;
+
+// Largest rendered file, in bytes.
+const MAX_RENDERED_LEN: usize = 16 << 20;
+
+/// Renders `source`, expanding loops and expressions.
+pub fn render(source: &str) -> NetResult<String> {
+    render_with(source, &BTreeMap::new())
+}
+
+/// Renders `source` with `vars` predefined, e.g. values describing the node
+/// a startup configuration is being generated for.
+pub fn render_with(
+    source: &str,
+    vars: &BTreeMap<String, Value>,
+) -> NetResult<String> {
+    render_mapped(source, vars).map(|(rendered, _)| rendered)
+}
+
+/// Renders `source` like `render_with`, along with the line of `source`
+/// every line of the result comes from, both counted from 1, for errors
+/// found in the result to point at the template.
+pub(crate) fn render_mapped(
+    source: &str,
+    vars: &BTreeMap<String, Value>,
+) -> NetResult<(String, Vec<usize>)> {
+    // Nothing to do for the (common) plain files.
+    if !source.contains("{{") && !source.contains("{%") {
+        let origins = (1..=source.lines().count()).collect();
+        return Ok((source.to_string(), origins));
+    }
+
+    let lines: Vec<(usize, &str)> = source
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line))
+        .collect();
+    let scope = Scope {
+        vars: vars.clone(),
+        parent: None,
+        depth: 0,
+    };
+    let mut output = Output {
+        text: String::with_capacity(source.len()),
+        origins: vec![],
+        iterations: 0,
+    };
+    render_lines(&lines, &scope, &mut output)?;
+    Ok((output.text, output.origins))
+}
+
+// ==== enum Value ====
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Str(String),
+    List(Vec<Value>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Str(value) => write!(f, "{value}"),
+            Self::List(values) => {
+                write!(f, "[")?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+// ==== struct Scope ====
+
+// Variables of a loop iteration, over those of the scope it runs in, which
+// are looked up rather than copied.
+#[derive(Debug)]
+struct Scope<'a> {
+    vars: BTreeMap<String, Value>,
+    parent: Option<&'a Scope<'a>>,
+    // Loops the scope is nested in.
+    depth: usize,
+}
+
+impl Scope<'_> {
+    fn get(&self, name: &str) -> Option<&Value> {
+        self.vars
+            .get(name)
+            .or_else(|| self.parent.and_then(|parent| parent.get(name)))
+    }
+}
+
+// ==== struct BoundedText ====
+
+// Text failing to grow past the size of a rendered file, for values to be
+// refused before they are built rather than after.
+#[derive(Debug, Default)]
+struct BoundedText(String);
+
+impl fmt::Write for BoundedText {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.0.len() + s.len() > MAX_RENDERED_LEN {
+            return Err(fmt::Error);
+        }
+        self.0.push_str(s);
+        Ok(())
+    }
+}
+
+// ==== struct Output ====
+
+#[derive(Debug)]
+struct Output {
+    text: String,
+    // Line of the source each line of `text` comes from.
+    origins: Vec<usize>,
+    // Loop iterations run so far, in the whole file.
+    iterations: usize,
+}
+
+fn render_lines(
+    lines: &[(usize, &str)],
+    scope: &Scope<'_>,
+    output: &mut Output,
+) -> NetResult<()> {
+    let mut idx = 0;
+    while idx < lines.len() {
+        let (line_no, line) = lines[idx];
+
+        match parse_statement(line) {
+            Some(Statement::For(header)) => {
+                if scope.depth == MAX_NESTING {
+                    return Err(template_error(
+                        line_no,
+                        "loops nested too deeply",
+                    ));
+                }
+                let (vars, iterable) = parse_for(&header, line_no)?;
+                let end = find_endfor(lines, idx, line_no)?;
+                let body = &lines[idx + 1..end];
+
+                let items = match eval(&iterable, line_no, scope)? {
+                    Value::List(items) => items,
+                    other => {
+                        return Err(template_error(
+                            line_no,
+                            &format!("cannot iterate over '{other}'"),
+                        ));
+                    }
+                };
+
+                for item in items {
+                    output.iterations += 1;
+                    if output.iterations > MAX_ITERATIONS {
+                        return Err(template_error(
+                            line_no,
+                            &format!(
+                                "loops run more than {MAX_ITERATIONS} times"
+                            ),
+                        ));
+                    }
+                    let mut inner = Scope {
+                        vars: BTreeMap::new(),
+                        parent: Some(scope),
+                        depth: scope.depth + 1,
+                    };
+                    bind(&mut inner, &vars, item, line_no)?;
+                    render_lines(body, &inner, output)?;
+                }
+                idx = end + 1;
+            }
+            Some(Statement::EndFor) => {
+                return Err(template_error(line_no, "unexpected endfor"));
+            }
+            None => {
+                let rendered = render_expressions(line, line_no, scope)?;
+                // Values may span several lines.
+                for _ in 0..=rendered.matches('\n').count() {
+                    output.origins.push(line_no);
+                }
+                output.text.push_str(&rendered);
+                output.text.push('\n');
+                if output.text.len() > MAX_RENDERED_LEN {
+                    return Err(template_error(
+                        line_no,
+                        "rendered file too large",
+                    ));
+                }
+                idx += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+enum Statement {
+    For(String),
+    EndFor,
+}
+
+fn parse_statement(line: &str) -> Option<Statement> {
+    let inner = line.trim().strip_prefix("{%")?.strip_suffix("%}")?.trim();
+
+    if inner == "endfor" {
+        Some(Statement::EndFor)
+    } else {
+        inner
+            .strip_prefix("for ")
+            .map(|header| Statement::For(header.to_string()))
+    }
+}
+
+// Splits `a, b in expr` into the loop variables and the iterated expression.
+fn parse_for(header: &str, line_no: usize) -> NetResult<(Vec<String>, String)> {
+    let (vars, iterable) = header
+        .split_once(" in ")
+        .ok_or_else(|| template_error(line_no, "expected 'for .. in ..'"))?;
+
+    let vars: Vec<String> =
+        vars.split(',').map(|var| var.trim().to_string()).collect();
+    if vars.iter().any(|var| !is_identifier(var)) {
+        return Err(template_error(line_no, "invalid loop variable"));
+    }
+    Ok((vars, iterable.trim().to_string()))
+}
+
+fn find_endfor(
+    lines: &[(usize, &str)],
+    start: usize,
+    line_no: usize,
+) -> NetResult<usize> {
+    let mut depth = 0;
+    for (idx, (_, line)) in lines.iter().enumerate().skip(start + 1) {
+        match parse_statement(line) {
+            Some(Statement::For(_)) => depth += 1,
+            Some(Statement::EndFor) if depth == 0 => return Ok(idx),
+            Some(Statement::EndFor) => depth -= 1,
+            None => {}
+        }
+    }
+    Err(template_error(line_no, "for without matching endfor"))
+}
+
+fn bind(
+    scope: &mut Scope<'_>,
+    vars: &[String],
+    item: Value,
+    line_no: usize,
+) -> NetResult<()> {
+    if let [var] = vars {
+        scope.vars.insert(var.clone(), item);
+        return Ok(());
+    }
+
+    match item {
+        Value::List(values) if values.len() == vars.len() => {
+            for (var, value) in vars.iter().zip(values) {
+                scope.vars.insert(var.clone(), value);
+            }
+            Ok(())
+        }
+        other => Err(template_error(
+            line_no,
+            &format!("cannot unpack '{other}' into {} variables", vars.len()),
+        )),
+    }
+}
+
+fn render_expressions(
+    line: &str,
+    line_no: usize,
+    scope: &Scope<'_>,
+) -> NetResult<String> {
+    let mut rendered = BoundedText(String::with_capacity(line.len()));
+    let mut rest = line;
+    let too_large = |_| template_error(line_no, "rendered file too large");
+
+    while let Some(start) = rest.find("{{") {
+        rendered.write_str(&rest[..start]).map_err(too_large)?;
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| template_error(line_no, "unclosed '{{'"))?;
+        let value = eval(after[..end].trim(), line_no, scope)?;
+        write!(rendered, "{value}").map_err(too_large)?;
+        rest = &after[end + 2..];
+    }
+    rendered.write_str(rest).map_err(too_large)?;
+    Ok(rendered.0)
+}
+
+// ==== Expressions ====
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Str(String),
+    Ident(String),
+    Symbol(char),
+}
+
+fn tokenize(expr: &str, line_no: usize) -> NetResult<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = expr.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut number = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                number.push(d);
+                chars.next();
+            }
+            let number = number
+                .parse()
+                .map_err(|_| template_error(line_no, "integer too large"))?;
+            tokens.push(Token::Int(number));
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    Some(q) if q == c => break,
+                    Some(other) => string.push(other),
+                    None => {
+                        return Err(template_error(
+                            line_no,
+                            "unterminated string",
+                        ));
+                    }
+                }
+            }
+            tokens.push(Token::Str(string));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&d) =
+                chars.peek().filter(|d| d.is_alphanumeric() || **d == '_')
+            {
+                ident.push(d);
+                chars.next();
+            }
+            tokens.push(Token::Ident(ident));
+        } else if "()[],+-*".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(template_error(
+                line_no,
+                &format!("unexpected character '{c}'"),
+            ));
+        }
+    }
+    Ok(tokens)
+}
+
+struct ExprParser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    line_no: usize,
+    scope: &'a Scope<'a>,
+    // Expressions the parser is nested in.
+    depth: usize,
+}
+
+fn eval(expr: &str, line_no: usize, scope: &Scope<'_>) -> NetResult<Value> {
+    let mut parser = ExprParser {
+        tokens: tokenize(expr, line_no)?,
+        pos: 0,
+        line_no,
+        scope,
+        depth: 0,
+    };
+    let value = parser.expr()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(parser.error(&format!("unexpected input in '{expr}'")));
+    }
+    Ok(value)
+}
+
+impl ExprParser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> NetResult<()> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{symbol}'")))
+        }
+    }
+
+    fn error(&self, reason: &str) -> crate::error::NetError {
+        template_error(self.line_no, reason)
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> NetResult<Value> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                let rhs = self.term()?;
+                value = match (value, rhs) {
+                    (Value::Int(a), Value::Int(b)) => Value::Int(
+                        a.checked_add(b)
+                            .ok_or_else(|| self.error("integer overflow"))?,
+                    ),
+                    (Value::List(mut a), Value::List(b)) => {
+                        if a.len() + b.len() > MAX_LIST_LEN {
+                            return Err(self.error(&format!(
+                                "list of more than {MAX_LIST_LEN} items"
+                            )));
+                        }
+                        a.extend(b);
+                        Value::List(a)
+                    }
+                    (a, b) => {
+                        let mut text = BoundedText::default();
+                        write!(text, "{a}{b}").map_err(|_| {
+                            self.error("string too large to render")
+                        })?;
+                        Value::Str(text.0)
+                    }
+                };
+            } else if self.eat('-') {
+                let rhs = self.term()?;
+                let (lhs, rhs) = (self.int(value)?, self.int(rhs)?);
+                value = Value::Int(
+                    lhs.checked_sub(rhs)
+                        .ok_or_else(|| self.error("integer overflow"))?,
+                );
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // term := unary ('*' unary)*
+    fn term(&mut self) -> NetResult<Value> {
+        let mut value = self.unary()?;
+        while self.eat('*') {
+            let rhs = self.unary()?;
+            let (lhs, rhs) = (self.int(value)?, self.int(rhs)?);
+            value = Value::Int(
+                lhs.checked_mul(rhs)
+                    .ok_or_else(|| self.error("integer overflow"))?,
+            );
+        }
+        Ok(value)
+    }
+
+    // unary := '-' unary | primary
+    //
+    // Every nested expression goes through here.
+    fn unary(&mut self) -> NetResult<Value> {
+        if self.depth == MAX_NESTING {
+            return Err(self.error("expression nested too deeply"));
+        }
+
+        self.depth += 1;
+        let value = match self.eat('-') {
+            true => self.unary().and_then(|value| {
+                self.int(value)?
+                    .checked_neg()
+                    .map(Value::Int)
+                    .ok_or_else(|| self.error("integer overflow"))
+            }),
+            false => self.primary(),
+        };
+        self.depth -= 1;
+        value
+    }
+
+    fn primary(&mut self) -> NetResult<Value> {
+        match self.next() {
+            Some(Token::Int(value)) => Ok(Value::Int(value)),
+            Some(Token::Str(value)) => Ok(Value::Str(value)),
+            Some(Token::Symbol('(')) => {
+                let value = self.expr()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Some(Token::Symbol('[')) => Ok(Value::List(self.args(']')?)),
+            Some(Token::Ident(name)) => {
+                if self.eat('(') {
+                    let args = self.args(')')?;
+                    self.call(&name, args)
+                } else {
+                    self.scope.get(&name).cloned().ok_or_else(|| {
+                        self.error(&format!("unknown variable '{name}'"))
+                    })
+                }
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    // Comma separated expressions, up to the `close` symbol.
+    fn args(&mut self, close: char) -> NetResult<Vec<Value>> {
+        let mut args = vec![];
+        if self.eat(close) {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            if self.eat(close) {
+                return Ok(args);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn int(&self, value: Value) -> NetResult<i64> {
+        match value {
+            Value::Int(value) => Ok(value),
+            other => Err(self.error(&format!("'{other}' is not an integer"))),
+        }
+    }
+
+    fn list(&self, value: Value) -> NetResult<Vec<Value>> {
+        match value {
+            Value::List(values) => Ok(values),
+            other => Err(self.error(&format!("'{other}' is not a list"))),
+        }
+    }
+
+    fn call(&self, name: &str, args: Vec<Value>) -> NetResult<Value> {
+        match name {
+            "range" => {
+                let ints = args
+                    .into_iter()
+                    .map(|arg| self.int(arg))
+                    .collect::<NetResult<Vec<_>>>()?;
+                let (start, end, step) = match ints[..] {
+                    [end] => (0, end, 1),
+                    [start, end] => (start, end, 1),
+                    [_, _, 0] => return Err(self.error("range step is 0")),
+                    [start, end, step] => (start, end, step),
+                    _ => return Err(self.error("range takes 1 to 3 arguments")),
+                };
+                // Sized up front, in a type none of the bounds overflow.
+                let span = i128::from(end) - i128::from(start);
+                let len = match span.signum() == i128::from(step.signum()) {
+                    true => {
+                        let step = i128::from(step.unsigned_abs());
+                        (span.abs() + step - 1) / step
+                    }
+                    false => 0,
+                };
+                if len > MAX_LIST_LEN as i128 {
+                    return Err(self.error(&format!(
+                        "range of more than {MAX_LIST_LEN} values"
+                    )));
+                }
+                // Every value lies between start and end, so fits.
+                Ok(Value::List(
+                    (0..len)
+                        .map(|idx| i128::from(start) + idx * i128::from(step))
+                        .map(|value| Value::Int(value as i64))
+                        .collect(),
+                ))
+            }
+            "cidrhost" => {
+                let [prefix, n] = <[Value; 2]>::try_from(args)
+                    .map_err(|_| self.error("cidrhost takes 2 arguments"))?;
+                let Value::Str(prefix) = prefix else {
+                    return Err(self.error("cidrhost expects a prefix"));
+                };
+                let n = self.int(n)?;
+                self.cidrhost(&prefix, n).map(Value::Str)
+            }
+            "zip" => {
+                let lists = args
+                    .into_iter()
+                    .map(|arg| self.list(arg))
+                    .collect::<NetResult<Vec<_>>>()?;
+                let len = lists.iter().map(Vec::len).min().unwrap_or(0);
+                Ok(Value::List(
+                    (0..len)
+                        .map(|idx| {
+                            Value::List(
+                                lists.iter().map(|l| l[idx].clone()).collect(),
+                            )
+                        })
+                        .collect(),
+                ))
+            }
+            "enumerate" => {
+                let [list] = <[Value; 1]>::try_from(args)
+                    .map_err(|_| self.error("enumerate takes 1 argument"))?;
+                Ok(Value::List(
+                    self.list(list)?
+                        .into_iter()
+                        .enumerate()
+                        .map(|(idx, value)| {
+                            Value::List(vec![Value::Int(idx as i64), value])
+                        })
+                        .collect(),
+                ))
+            }
+            _ => Err(self.error(&format!("unknown function '{name}'"))),
+        }
+    }
+
+    fn cidrhost(&self, prefix: &str, n: i64) -> NetResult<String> {
+        let network: IpNetwork = prefix
+            .parse()
+            .map_err(|_| self.error(&format!("invalid prefix '{prefix}'")))?;
+
+        let (base, bits) = match network {
+            IpNetwork::V4(net) => (u128::from(u32::from(net.network())), 32),
+            IpNetwork::V6(net) => (u128::from(net.network()), 128),
+        };
+        let size = 1u128
+            .checked_shl(bits - u32::from(network.prefix()))
+            .unwrap_or(u128::MAX);
+
+        let offset = if n >= 0 {
+            u128::from(n.unsigned_abs())
+        } else {
+            size.checked_sub(u128::from(n.unsigned_abs()))
+                .ok_or_else(|| self.error("host number out of range"))?
+        };
+        if offset >= size {
+            return Err(self
+                .error(&format!("host number {n} out of range for {prefix}")));
+        }
+
+        let addr = base + offset;
+        let addr = match network {
+            IpNetwork::V4(_) => IpAddr::from((addr as u32).to_be_bytes()),
+            IpNetwork::V6(_) => IpAddr::from(addr.to_be_bytes()),
+        };
+        Ok(addr.to_string())
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn template_error(line: usize, reason: &str) -> crate::error::NetError {
+    ConfigError::Template {
+        line,
+        reason: reason.to_string(),
+    }
+    .into()
+}
@@ -0,0 +1,186 @@
+//! Helpers for the integration tests of crates built on netgen.
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use nix::sys::wait::{WaitStatus, waitpid};
+use nix::unistd::{ForkResult, Pid, fork};
+use tracing::{debug, error, warn};
+
+use crate::error::{NamespaceError, NetError};
+use crate::topology::Topology;
+use crate::{
+    NamespaceHandle, NetResult, default_run_dir, devices_ns_dir, enter_ns,
+    set_run_dir,
+};
+
+// Counts the topologies started by this process, for their IDs.
+static STARTED: AtomicU32 = AtomicU32::new(0);
+
+// ==== struct OwnedTopology ====
+
+/// A topology which is up for as long as it is in scope.
+///
+/// Dropping it, which also happens while a failed test unwinds, powers the
+/// topology off and removes what it created, so that test cases never leak
+/// namespaces, bridges or daemons to the next ones. Tests built with
+/// `panic = "abort"` do not unwind, and leave them behind until the next
+/// topology is started.
+///
+/// Each topology gets an ID of its own, which its session identifier and
+/// its run directory are named after, so that test cases running in
+/// parallel never share any of them. The thread which started it works on
+/// it until it is dropped: netgen functions called from other threads work
+/// on the topology `netgen start` runs instead.
+///
+/// ```no_run
+/// use netgen::exec_in_ns;
+/// use netgen::testing::OwnedTopology;
+/// use netgen::topology::TopologyParser;
+///
+/// # fn main() -> netgen::NetResult<()> {
+/// let topology = TopologyParser::from_yaml_str(
+///     "routers: { rt1: { interfaces: { lo: [ 10.0.0.1/32 ] } } }",
+/// )?;
+/// let lab = OwnedTopology::start(topology)?;
+/// let output = exec_in_ns("rt1", "ip", &["addr"])?;
+/// assert!(output.status.success());
+/// # drop(lab);
+/// # Ok(())
+/// # }
+/// ```
+pub struct OwnedTopology {
+    topology: Topology,
+    id: String,
+    // Run directory of the thread before the topology was started.
+    previous_run_dir: Option<String>,
+}
+
+impl OwnedTopology {
+    /// Brings `topology` up as `netgen start` does, from a forked process
+    /// so that the caller stays in its own namespaces. Whatever was brought
+    /// up is removed again if it fails halfway.
+    pub fn start(mut topology: Topology) -> NetResult<Self> {
+        clean_abandoned();
+
+        // Short enough for bridge names to stay within IFNAME_MAX_LEN, the
+        // count wrapping around well after the first topologies stopped.
+        let count = STARTED.fetch_add(1, Ordering::Relaxed) % 0x100;
+        let pid = Pid::this().as_raw();
+        let id = format!("t{pid:x}-{count:x}");
+        topology.isolate(&id)?;
+
+        let previous_run_dir = set_run_dir(Some(run_dir_of(pid, count)));
+        let owned = Self {
+            topology,
+            id,
+            previous_run_dir,
+        };
+        let _ = fs::create_dir_all(devices_ns_dir());
+
+        match unsafe { fork() } {
+            Ok(ForkResult::Child) => {
+                // Neither errors nor panics may return into the caller's
+                // code from here, nor may `owned` be dropped.
+                let result =
+                    panic::catch_unwind(AssertUnwindSafe(|| owned.bring_up()));
+                let code = match result {
+                    Ok(Ok(())) => 0,
+                    Ok(Err(err)) => {
+                        error!(%err, "unable to bring up the topology");
+                        1
+                    }
+                    Err(_) => 1,
+                };
+                std::process::exit(code);
+            }
+            Ok(ForkResult::Parent { child }) => {
+                let status = waitpid(child, None).map_err(|err| {
+                    NamespaceError::Fork {
+                        fork_function: String::from("OwnedTopology::start"),
+                        source: err,
+                    }
+                })?;
+                if !matches!(status, WaitStatus::Exited(_, 0)) {
+                    return Err(NetError::BasicError(
+                        "Unable to bring up the topology, see the logs."
+                            .to_string(),
+                    ));
+                }
+                owned.topology.expose_ports()?;
+                Ok(owned)
+            }
+            Err(err) => Err(NamespaceError::Fork {
+                fork_function: String::from("OwnedTopology::start"),
+                source: err,
+            }
+            .into()),
+        }
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    /// Prefixed to the bridges and interfaces the topology creates.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    // The routers first, each in its own namespaces, then the switches and
+    // links from the main namespace.
+    fn bring_up(&self) -> NetResult<()> {
+        crate::mount_device(None)?;
+        self.topology.power_routers_on()?;
+        enter_ns(None)?;
+        self.topology.power_switches_on()?;
+        self.topology.setup_links()
+    }
+}
+
+impl Drop for OwnedTopology {
+    fn drop(&mut self) {
+        if let Err(err) = self.topology.power_off() {
+            warn!(%err, "unable to power off the topology, cleaning up");
+            let _ = crate::clean_stale_run();
+        }
+        let _ = fs::remove_dir_all(crate::run_dir());
+        set_run_dir(self.previous_run_dir.take());
+    }
+}
+
+// Where the topologies started by tests are run from, one directory each.
+fn tests_dir() -> String {
+    format!("{}/tests", default_run_dir())
+}
+
+fn run_dir_of(pid: i32, count: u32) -> String {
+    format!("{}/{pid}-{count}", tests_dir())
+}
+
+// Tears down the topologies of the test processes which are gone without
+// dropping them, i.e. which aborted.
+fn clean_abandoned() {
+    let Ok(entries) = fs::read_dir(tests_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(pid) = name.split_once('-').map(|(pid, _)| pid) else {
+            continue;
+        };
+        if Path::new(&format!("/proc/{pid}")).exists() {
+            continue;
+        }
+        let dir = entry.path().to_string_lossy().to_string();
+        let previous = set_run_dir(Some(dir.clone()));
+        if !NamespaceHandle::new(None).is_running() {
+            debug!(%dir, "cleaning up abandoned topology");
+            let _ = crate::clean_stale_run();
+            let _ = fs::remove_dir_all(&dir);
+        }
+        set_run_dir(previous);
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use yaml_rust2::YamlLoader;
+use yaml_rust2::yaml::{Hash, Yaml};
+
+use crate::NetResult;
+use crate::error::NetError;
+use crate::lab::{config_dir, lock_file, write_yaml};
+
+const TOGGLES_FILE: &str = "link-toggles.yml";
+
+// ==== struct LinkToggles ====
+
+/// Links brought up or down at runtime against what the topology file
+/// says, by topology then link name, persisted to `link-toggles.yml` in the
+/// netgen config directory so that they outlive the topologies. Topologies
+/// are told apart by their run directory and session, see
+/// `Topology::identity`:
+///
+/// ```yaml
+/// /tmp/netgen-rs/ng1:
+///   "rt1:eth0 <-> rt2:eth0": false
+/// ```
+///
+/// Toggling a link back to what the topology file says forgets about it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LinkToggles {
+    topologies: BTreeMap<String, BTreeMap<String, bool>>,
+}
+
+impl LinkToggles {
+    fn path() -> NetResult<PathBuf> {
+        Ok(config_dir("the link toggles")?.join(TOGGLES_FILE))
+    }
+
+    /// Loads the toggles, hands them to `change` and saves them back, other
+    /// processes waiting in between.
+    pub(crate) fn update(change: impl FnOnce(&mut Self)) -> NetResult<()> {
+        let _lock = lock_file(&Self::path()?)?;
+        let mut toggles = Self::load()?;
+        change(&mut toggles);
+        toggles.save()
+    }
+
+    /// Loads the toggles, empty if no link was toggled yet.
+    pub(crate) fn load() -> NetResult<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path).map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to read {}: {err:?}",
+                path.display()
+            ))
+        })?;
+        let invalid = |reason: &str| {
+            NetError::BasicError(format!(
+                "Link toggles {} are invalid: {reason}",
+                path.display()
+            ))
+        };
+        let docs = YamlLoader::load_from_str(&contents)
+            .map_err(|err| invalid(&err.to_string()))?;
+
+        let mut toggles = Self::default();
+        let topologies = match docs.first() {
+            Some(Yaml::Hash(topologies)) => topologies,
+            Some(Yaml::Null) | None => return Ok(toggles),
+            Some(_) => return Err(invalid("expected a hash")),
+        };
+        for (topology, links) in topologies {
+            let (Yaml::String(topology), Yaml::Hash(links)) = (topology, links)
+            else {
+                return Err(invalid("expected links by topology"));
+            };
+            let links = links
+                .iter()
+                .map(|(link, enabled)| {
+                    Some((link.as_str()?.to_string(), enabled.as_bool()?))
+                })
+                .collect::<Option<BTreeMap<_, _>>>()
+                .ok_or_else(|| invalid("links are either true or false"))?;
+            toggles.topologies.insert(topology.clone(), links);
+        }
+        Ok(toggles)
+    }
+
+    fn save(&self) -> NetResult<()> {
+        let mut doc = Hash::new();
+        for (topology, links) in &self.topologies {
+            let links = links
+                .iter()
+                .map(|(link, enabled)| {
+                    (Yaml::String(link.clone()), Yaml::Boolean(*enabled))
+                })
+                .collect();
+            doc.insert(Yaml::String(topology.clone()), Yaml::Hash(links));
+        }
+        write_yaml(&Self::path()?, &Yaml::Hash(doc), "the link toggles")
+    }
+
+    /// Whether `link` of `topology` was toggled up or down, if it was.
+    pub(crate) fn get(&self, topology: &str, link: &str) -> Option<bool> {
+        self.topologies.get(topology)?.get(link).copied()
+    }
+
+    /// Records `link` of `topology` as toggled up or down, or forgets about
+    /// it when that is what the topology file `declared`.
+    pub(crate) fn set(
+        &mut self,
+        topology: &str,
+        link: &str,
+        enabled: bool,
+        declared: bool,
+    ) {
+        let links = self.topologies.entry(topology.to_string()).or_default();
+        match enabled == declared {
+            true => links.remove(link),
+            false => links.insert(link.to_string(), enabled),
+        };
+        if links.is_empty() {
+            self.topologies.remove(topology);
+        }
+    }
+
+    /// Moves the toggles of topology `from` over to `to`, e.g. once its
+    /// session was renamed.
+    pub(crate) fn rename(&mut self, from: &str, to: &str) {
+        if let Some(links) = self.topologies.remove(from) {
+            self.topologies.insert(to.to_string(), links);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use crate::frr;
+
+// ==== struct Tool ====
+
+/// A program netgen runs on the host or in the routers for some of its
+/// features. Namespaces, links, addresses, routes, probes, LLDP, exposed
+/// ports, raw captures and services are built in and need none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tool {
+    pub program: &'static str,
+    // What needs it, for humans.
+    pub needed_by: &'static str,
+}
+
+/// Every program netgen may run, by name.
+pub const TOOLS: &[Tool] = &[
+    Tool {
+        program: "bpftrace",
+        needed_by: "link latency",
+    },
+    Tool {
+        program: "bridge",
+        needed_by: "switch port options",
+    },
+    Tool {
+        program: "chronyd",
+        needed_by: "ntp",
+    },
+    Tool {
+        program: "coredumpctl",
+        needed_by: "cores of crashed daemons, with systemd-coredump",
+    },
+    Tool {
+        program: "criu",
+        needed_by: "node checkpoint and restore",
+    },
+    Tool {
+        program: "curl",
+        needed_by: "webhook notifications",
+    },
+    Tool {
+        program: "dropbear",
+        needed_by: "ssh",
+    },
+    Tool {
+        program: "dropbearkey",
+        needed_by: "ssh",
+    },
+    Tool {
+        program: "gobgpd",
+        needed_by: "injector",
+    },
+    Tool {
+        program: "ip",
+        needed_by: "status, watch, rules, link enable/disable and removal",
+    },
+    Tool {
+        program: "modprobe",
+        needed_by: "link bench",
+    },
+    Tool {
+        program: "nft",
+        needed_by: "nat, mgmt outages",
+    },
+    Tool {
+        program: "openssl",
+        needed_by: "pki",
+    },
+    Tool {
+        program: "ping",
+        needed_by: "check pmtu and path, ping assertions, calibrate",
+    },
+    Tool {
+        program: "pmacctd",
+        needed_by: "switch flow-export",
+    },
+    Tool {
+        program: "sh",
+        needed_by: "injector, services",
+    },
+    Tool {
+        program: "sysctl",
+        needed_by: "address families, nat",
+    },
+    Tool {
+        program: "tar",
+        needed_by: "bundle",
+    },
+    Tool {
+        program: "tc",
+        needed_by: "link impairments, switch port police",
+    },
+    Tool {
+        program: "vtysh",
+        needed_by: "frr startup configs and commands",
+    },
+    Tool {
+        program: "zcat",
+        needed_by: "kernel checks, with /proc/config.gz",
+    },
+    Tool {
+        program: "zebra",
+        needed_by: "frr, along with the other daemons of its directory",
+    },
+];
+
+/// Whether `program` exists, as a path or on the PATH.
+pub fn installed(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| dir.join(program).is_file())
+    })
+}
+
+/// Every tool, along with whether it is installed. The FRR daemons are
+/// looked up where FRR is found, as when starting a router.
+pub fn audit() -> Vec<(Tool, bool)> {
+    TOOLS
+        .iter()
+        .map(|tool| {
+            let found = match tool.program {
+                "zebra" => installed(&format!("{}/zebra", frr::default_dir())),
+                program => installed(program),
+            };
+            (*tool, found)
+        })
+        .collect()
+}
@@ -1,18 +1,75 @@
-use std::collections::BTreeMap;
-use std::fs::File;
-use std::io::Read;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::process::{ExitStatus, Output};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use ipnetwork::IpNetwork;
+use nix::net::if_::if_nametoindex;
+use nix::sys::signal::Signal;
 use tokio;
 use tokio::runtime::Runtime;
-use tracing::debug_span;
-use yaml_rust2::YamlLoader;
-use yaml_rust2::yaml::Yaml;
+use tracing::{debug, debug_span, warn};
+use yaml_rust2::yaml::{Hash, Yaml};
+use yaml_rust2::{YamlEmitter, YamlLoader};
 
-use crate::NetResult;
+use crate::backend::{Backend, LinuxBackend};
+use crate::cancel::CancelToken;
+use crate::checkpoint;
+use crate::clone::{self, RouterCopy};
+use crate::edit;
 use crate::error::{ConfigError, NetError, YamlPath};
-use crate::link::{Link, LinkManager};
-use crate::node::{Kind, Node, Router, Switch};
-use crate::parser::{FromYamlConfig, get_string_field};
+use crate::events;
+use crate::expose::GrpcProxy;
+use crate::fib::StaticRoute;
+use crate::frr;
+use crate::graph::TopologyGraph;
+use crate::hooks::Hooks;
+use crate::import;
+use crate::invariant::{self, Invariant};
+use crate::limits::ProcessLimits;
+use crate::link::{
+    IfaceId, Link, LinkEndpoint, LinkExpectations, LinkManager, MAX_LINKS,
+    NodeId,
+};
+use crate::lldp::LldpAgent;
+use crate::mgmt;
+use crate::nat;
+use crate::netem::{self, FlowImpairment, Impairment, MAX_FLOWS};
+use crate::node::{
+    AddressFamily, Device, FileSource, Interface, Kind, Node, PortForward,
+    Router, Switch,
+};
+use crate::notify::{self, Notification, NotifyRule};
+use crate::ntp::{NtpRole, NtpService};
+use crate::parser::{
+    self, FromYamlConfig, SourceMap, get_metadata_field, get_string_field,
+};
+use crate::pki::{NodeCert, Pki};
+use crate::plan::{Action, Plan, link_name};
+use crate::pool::{self, AddressPool};
+use crate::probe::{Probe, ProbeSession};
+use crate::provenance::Provenance;
+use crate::report::{TestCase, TestReport};
+use crate::ssh::SshServer;
+use crate::state::{DaemonStatus, ProcessRecord, TopologyState};
+use crate::status::{Health, TopologyStatus, link_states};
+use crate::template;
+use crate::toggles::LinkToggles;
+use crate::tools::installed;
+use crate::validator;
+use crate::{
+    DEFAULT_PARALLELISM, IFNAME_MAX_LEN, NamespaceHandle, NetResult,
+    default_session, devices_ns_dir, persist_dir, run_dir, with_run_dir,
+};
+
+// How long the processes started in the nodes are given to exit on SIGTERM
+// before their namespaces are torn down.
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(2);
 
 // struct TopologyParser ====
 
@@ -26,24 +83,403 @@ impl TopologyParser {
     }
 
     pub fn from_yaml_str(yaml_str: &str) -> NetResult<Topology> {
+        Self::from_yaml_str_with(yaml_str, &BTreeMap::new(), &BTreeSet::new())
+    }
+
+    /// Parses `yaml_str` with the template variables `vars` predefined, e.g.
+    /// the parameters of a sweep, and the nodes and links of the `profiles`
+    /// given enabled.
+    pub fn from_yaml_str_with(
+        yaml_str: &str,
+        vars: &BTreeMap<String, template::Value>,
+        profiles: &BTreeSet<String>,
+    ) -> NetResult<Topology> {
         let mut topology = Topology::new()?;
+        let (rendered, origins) =
+            template::render_mapped(yaml_str, &file_vars(vars))?;
+        // Errors point at the line of the file at fault, template included.
+        let source_map = SourceMap::new(&rendered, yaml_str, &origins);
+        let yaml_str = rendered;
+        topology.session = default_session(&yaml_str);
         let yaml_content =
-            YamlLoader::load_from_str(yaml_str).map_err(|err| {
-                NetError::ConfigError(ConfigError::YamlSyntax(err))
+            YamlLoader::load_from_str(&yaml_str).map_err(|err| {
+                NetError::ConfigError(source_map.locate_syntax(err))
             })?;
 
-        for yaml_group in yaml_content {
-            Self::parse_topology_config(&yaml_group, &mut topology)?;
+        let mut used_profiles = BTreeSet::new();
+        for (doc, mut yaml_group) in yaml_content.into_iter().enumerate() {
+            for (keys, issue) in parser::lint(&mut yaml_group) {
+                topology.issues.push(match source_map.position(doc, &keys) {
+                    Some((line, column)) => {
+                        format!("{issue} (line {line}, column {column})")
+                    }
+                    None => issue,
+                });
+            }
+            Self::select_profiles(
+                &mut yaml_group,
+                profiles,
+                &mut used_profiles,
+            )
+            .and_then(|()| {
+                Self::parse_topology_config(&yaml_group, &mut topology)
+            })
+            .map_err(|err| source_map.locate(doc, err))?;
+        }
+        for profile in profiles.difference(&used_profiles) {
+            warn!(%profile, "no node or link belongs to profile");
+        }
+        topology.source = yaml_str;
+        topology.active_profiles = profiles.clone();
+
+        // Rather than have the kernel reject them halfway through `start`.
+        if topology.links.len() > MAX_LINKS {
+            return Err(NetError::BasicError(format!(
+                "The topology has {} links, netgen supports {MAX_LINKS} at \
+                    most",
+                topology.links.len()
+            )));
         }
+        for link in &topology.links {
+            for end in link.ends() {
+                check_iface_name(&end.iface)?;
+            }
+        }
+        for node in topology.nodes.values() {
+            for iface in node.interfaces() {
+                check_iface_name(&iface.name)?;
+            }
+        }
+
+        // Addresses from pools are only known once every node and link has
+        // been parsed.
+        topology.request_loopbacks();
+        pool::assign(
+            &mut topology.pools,
+            &mut topology.nodes,
+            &topology.links,
+        )?;
+        topology.check_families()?;
+        topology.resolve_router_ids()?;
+        topology.resolve_ssh();
+        topology.resolve_grpc()?;
+        topology.resolve_lldp();
+        topology.resolve_limits();
+        topology.resolve_ntp()?;
+        topology.resolve_pki()?;
+        topology.resolve_probes()?;
+        topology.check_notify()?;
+        topology.resolve_profiles()?;
+        topology.sort();
+        let unlinked = topology.unlinked_ports();
+        topology.issues.extend(unlinked);
+        let missing = topology.missing_programs();
+        topology.issues.extend(missing);
+        let mismatches = topology.startup_config_mismatches();
+        topology.issues.extend(mismatches);
         Ok(topology)
     }
 
+    /// `yaml_str` in canonical form, see `netgen fmt`. The topology has to
+    /// parse first; templated files are not formatted, their rendered form
+    /// being all that could be.
+    ///
+    /// Comments are not kept.
+    pub fn format(yaml_str: &str) -> NetResult<String> {
+        if template::render_with(yaml_str, &file_vars(&BTreeMap::new()))?
+            != yaml_str
+        {
+            return Err(NetError::BasicError(
+                "Templated topologies are not formatted.".to_string(),
+            ));
+        }
+        Self::from_yaml_str(yaml_str)?;
+
+        let docs = YamlLoader::load_from_str(yaml_str).map_err(|err| {
+            NetError::ConfigError(ConfigError::YamlSyntax(err))
+        })?;
+        let mut formatted = String::new();
+        for doc in &docs {
+            let mut emitter = YamlEmitter::new(&mut formatted);
+            emitter.multiline_strings(true);
+            emitter.dump(&parser::canonicalize(doc)).map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to write the formatted topology: {err:?}"
+                ))
+            })?;
+            formatted.push('\n');
+        }
+        Ok(formatted)
+    }
+
+    // Drops the routers, switches and links which belong to profiles, none
+    // of which is enabled, along with the links to the nodes dropped.
+    // Profiles nodes and links belong to are added to `used`.
+    fn select_profiles(
+        yaml_data: &mut Yaml,
+        enabled: &BTreeSet<String>,
+        used: &mut BTreeSet<String>,
+    ) -> NetResult<()> {
+        let Yaml::Hash(topo_config_group) = yaml_data else {
+            return Ok(());
+        };
+        let mut selected = |config: &Yaml, path: YamlPath| {
+            let profiles = match &config["profiles"] {
+                Yaml::BadValue | Yaml::Null => return Ok(true),
+                Yaml::Array(profiles) => profiles
+                    .iter()
+                    .map(|profile| profile.as_str().map(str::to_string))
+                    .collect::<Option<BTreeSet<String>>>(),
+                _ => None,
+            };
+            let Some(profiles) = profiles else {
+                return Err(NetError::from(ConfigError::IncorrectType {
+                    path,
+                    expected: "list of profile names".to_string(),
+                }));
+            };
+            used.extend(profiles.iter().cloned());
+            Ok(profiles.is_empty() || !profiles.is_disjoint(enabled))
+        };
+
+        let mut dropped = BTreeSet::new();
+        for kind in ["routers", "switches"] {
+            let Some(Yaml::Hash(nodes)) =
+                topo_config_group.get_mut(&Yaml::String(kind.to_string()))
+            else {
+                continue;
+            };
+            let mut kept = Hash::new();
+            for (name, config) in std::mem::take(nodes) {
+                let name_str = name.as_str().unwrap_or_default().to_string();
+                let path =
+                    YamlPath::new().key(kind).key(&name_str).key("profiles");
+                match selected(&config, path)? {
+                    true => {
+                        kept.insert(name, config);
+                    }
+                    false => {
+                        dropped.insert(name_str);
+                    }
+                }
+            }
+            *nodes = kept;
+        }
+
+        if let Some(Yaml::Array(links)) =
+            topo_config_group.get_mut(&Yaml::String("links".to_string()))
+        {
+            let mut kept = vec![];
+            for (index, config) in std::mem::take(links).into_iter().enumerate()
+            {
+                let path = YamlPath::new()
+                    .key("links")
+                    .key(index.to_string())
+                    .key("profiles");
+                let to_dropped =
+                    ["src-device", "dst-device"].iter().any(|end| {
+                        config[*end]
+                            .as_str()
+                            .is_some_and(|node| dropped.contains(node))
+                    });
+                if selected(&config, path)? && !to_dropped {
+                    kept.push(config);
+                }
+            }
+            *links = kept;
+        }
+        Ok(())
+    }
+
     fn parse_topology_config(
         yaml_data: &Yaml,
         topology: &mut Topology,
     ) -> NetResult<()> {
         // Fetch routers' configurations.
         if let Yaml::Hash(topo_config_group) = yaml_data {
+            // Fetch the session identifier.
+            match topo_config_group.get(&Yaml::String(String::from("session")))
+            {
+                Some(Yaml::String(session)) => {
+                    // Prefixes bridge names, and veth names by default.
+                    check_iface_name(&format!("{session}-XXXX"))?;
+                    topology.session = session.clone();
+                }
+                Some(Yaml::Null) | None => {}
+                Some(_) => {
+                    return Err(ConfigError::IncorrectType {
+                        path: YamlPath::new().key("session").unknown(),
+                        expected: "string".to_string(),
+                    }
+                    .into());
+                }
+            }
+
+            // Fetch the pool loopbacks are allocated from.
+            match topo_config_group
+                .get(&Yaml::String(String::from("loopbacks")))
+            {
+                Some(Yaml::String(pool)) => {
+                    topology.loopback_pool = Some(pool.clone());
+                }
+                Some(Yaml::Null) | None => {}
+                Some(_) => {
+                    return Err(ConfigError::IncorrectType {
+                        path: YamlPath::new().key("loopbacks").unknown(),
+                        expected: "string".to_string(),
+                    }
+                    .into());
+                }
+            }
+
+            // Fetch the prefix of the temporary names of veth pairs.
+            match topo_config_group
+                .get(&Yaml::String(String::from("veth-prefix")))
+            {
+                Some(Yaml::String(prefix)) => {
+                    // Leave room for the suffix of veth names.
+                    check_iface_name(&format!("{prefix}-XXXX"))?;
+                    topology.veth_prefix = Some(prefix.clone());
+                }
+                Some(Yaml::Null) | None => {}
+                Some(_) => {
+                    return Err(ConfigError::IncorrectType {
+                        path: YamlPath::new().key("veth-prefix").unknown(),
+                        expected: "string".to_string(),
+                    }
+                    .into());
+                }
+            }
+
+            // Fetch the policy naming the interfaces links leave out.
+            match topo_config_group
+                .get(&Yaml::String(String::from("iface-naming")))
+            {
+                Some(Yaml::String(policy)) if valid_naming_policy(policy) => {
+                    topology.iface_naming = Some(policy.clone());
+                }
+                Some(Yaml::Null) | None => {}
+                Some(_) => {
+                    return Err(ConfigError::IncorrectType {
+                        path: YamlPath::new().key("iface-naming").unknown(),
+                        expected: "string using {node}, {peer} and {index}"
+                            .to_string(),
+                    }
+                    .into());
+                }
+            }
+
+            // Fetch the SSH server every router runs by default.
+            if let Some(ssh_config) =
+                topo_config_group.get(&Yaml::String(String::from("ssh")))
+                && !ssh_config.is_null()
+            {
+                topology.ssh = Some(SshServer::from_yaml_config(
+                    "ssh",
+                    ssh_config,
+                    BTreeMap::new(),
+                )?);
+            }
+
+            // Fetch the gRPC northbound proxied to the host.
+            if let Some(grpc_config) =
+                topo_config_group.get(&Yaml::String(String::from("grpc")))
+                && !matches!(grpc_config, Yaml::Null | Yaml::Boolean(false))
+            {
+                topology.grpc = Some(GrpcProxy::from_yaml_config(
+                    "grpc",
+                    grpc_config,
+                    BTreeMap::new(),
+                )?);
+            }
+
+            // Fetch the LLDP agent every router runs by default.
+            if let Some(lldp_config) =
+                topo_config_group.get(&Yaml::String(String::from("lldp")))
+                && !lldp_config.is_null()
+            {
+                topology.lldp = Some(LldpAgent::from_yaml_config(
+                    "lldp",
+                    lldp_config,
+                    BTreeMap::new(),
+                )?);
+            }
+
+            // Fetch the limits of every node's processes.
+            if let Some(limits_config) =
+                topo_config_group.get(&Yaml::String(String::from("limits")))
+            {
+                topology.limits = parser::parse_limits(
+                    limits_config,
+                    &YamlPath::new().key("limits"),
+                )?;
+            }
+
+            // Fetch the lab CA.
+            if let Some(pki_config) =
+                topo_config_group.get(&Yaml::String(String::from("pki")))
+                && !pki_config.is_null()
+            {
+                topology.pki = Some(Pki::from_yaml_config(
+                    "pki",
+                    pki_config,
+                    BTreeMap::new(),
+                )?);
+            }
+
+            // Fetch the router serving time to the others.
+            if let Some(ntp_config) =
+                topo_config_group.get(&Yaml::String(String::from("ntp")))
+                && !ntp_config.is_null()
+            {
+                topology.ntp = Some(NtpService::from_yaml_config(
+                    "ntp",
+                    ntp_config,
+                    BTreeMap::new(),
+                )?);
+            }
+
+            // Fetch the address pools.
+            if let Some(pools_config) =
+                topo_config_group.get(&Yaml::String(String::from("pools")))
+            {
+                let pools = Self::parse_pool_configs(pools_config)?;
+                topology.pools.extend(pools);
+            }
+
+            // Fetch the liveness probes.
+            if let Some(probes_config) =
+                topo_config_group.get(&Yaml::String(String::from("probes")))
+            {
+                let probes = Self::parse_probe_configs(probes_config)?;
+                topology.probes.extend(probes);
+            }
+
+            // Fetch the notification rules.
+            if let Some(notify_config) =
+                topo_config_group.get(&Yaml::String(String::from("notify")))
+            {
+                let rules = Self::parse_notify_configs(notify_config)?;
+                topology.notify.extend(rules);
+            }
+
+            // Fetch the invariants.
+            if let Some(invariants_config) =
+                topo_config_group.get(&Yaml::String(String::from("invariants")))
+            {
+                let invariants =
+                    Self::parse_invariant_configs(invariants_config)?;
+                topology.invariants.extend(invariants);
+            }
+
+            // Fetch the link impairment profiles.
+            if let Some(profiles_config) =
+                topo_config_group.get(&Yaml::String(String::from("profiles")))
+            {
+                let profiles = Self::parse_profile_configs(profiles_config)?;
+                topology.profiles.extend(profiles);
+            }
+
             // Fetch the Kinds created
             let mut kinds: Vec<Kind> = vec![];
             if let Some(kinds_config) =
@@ -103,7 +539,10 @@ impl TopologyParser {
                 topo_config_group.get(&Yaml::String(String::from("switches")))
             {
                 let switches = Self::parse_switch_configs(switches_configs)?;
-                for switch in switches {
+                for mut switch in switches {
+                    switch.bridge =
+                        format!("{}-{}", topology.session, switch.name);
+                    check_iface_name(&switch.bridge)?;
                     if topology.nodes.contains_key(&switch.name) {
                         return Err(
                             ConfigError::DuplicateNode(switch.name).into()
@@ -119,32 +558,37 @@ impl TopologyParser {
             if let Some(links_configs) =
                 topo_config_group.get(&Yaml::String(String::from("links")))
             {
-                let yaml_links = Self::parse_links_configs(links_configs)?;
+                let yaml_links =
+                    Self::parse_links_configs(links_configs, topology)?;
 
                 for link in yaml_links {
-                    if !topology.nodes.contains_key(&link.src_device) {
-                        return Err(
-                            ConfigError::UnknownNode(link.src_device).into()
-                        );
-                    }
-
-                    if !topology.nodes.contains_key(&link.dst_device) {
-                        return Err(
-                            ConfigError::UnknownNode(link.dst_device).into()
-                        );
+                    // Dummy interfaces are not created by links.
+                    for end in link.ends() {
+                        let dummy = topology
+                            .nodes
+                            .get(end.node.as_str())
+                            .into_iter()
+                            .flat_map(|node| node.interfaces())
+                            .any(|iface| {
+                                iface.dummy && end.iface == iface.name
+                            });
+                        if dummy {
+                            return Err(NetError::BasicError(format!(
+                                "{end} is a dummy interface, no link can \
+                                    attach to it"
+                            )));
+                        }
                     }
 
                     // Check if link has already been added to the links vector.
                     for link2 in topology.links.as_slice() {
-                        if (link.src() == link2.src())
-                            && (link.dst() == link2.dst())
-                            || ((link.src() == link2.dst())
-                                && (link.dst() == link2.src()))
+                        if (link.src == link2.src && link.dst == link2.dst)
+                            || (link.src == link2.dst && link.dst == link2.src)
                         {
                             // Link exists.
                             return Err(ConfigError::DuplicateLink {
-                                src: link.src(),
-                                dst: link.dst(),
+                                src: link.src.to_string(),
+                                dst: link.dst.to_string(),
                             }
                             .into());
                         }
@@ -191,6 +635,152 @@ impl TopologyParser {
         }
     }
 
+    fn parse_pool_configs(pools_config: &Yaml) -> NetResult<Vec<AddressPool>> {
+        let mut pools: Vec<AddressPool> = vec![];
+
+        match pools_config {
+            Yaml::Hash(configs) => {
+                for (pool_name, pool_config) in configs {
+                    let pool_name = match pool_name {
+                        Yaml::String(name) => name,
+                        _ => {
+                            return Err(ConfigError::IncorrectType {
+                                path: YamlPath::new().key("pools").unknown(),
+                                expected: "string".to_string(),
+                            }
+                            .into());
+                        }
+                    };
+
+                    let pool = AddressPool::from_yaml_config(
+                        pool_name,
+                        pool_config,
+                        BTreeMap::new(),
+                    )?;
+                    pools.push(pool);
+                }
+                Ok(pools)
+            }
+            Yaml::Null => Ok(pools),
+            _ => Err(ConfigError::IncorrectType {
+                path: YamlPath::new().key("pools").unknown(),
+                expected: "hash".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    fn parse_probe_configs(
+        probes_config: &Yaml,
+    ) -> NetResult<Vec<ProbeSession>> {
+        match probes_config {
+            Yaml::Array(configs) => configs
+                .iter()
+                .enumerate()
+                .map(|(index, probe_config)| {
+                    ProbeSession::from_yaml_config(
+                        &index.to_string(),
+                        probe_config,
+                        BTreeMap::new(),
+                    )
+                })
+                .collect(),
+            Yaml::Null => Ok(vec![]),
+            _ => Err(ConfigError::IncorrectType {
+                path: YamlPath::new().key("probes").unknown(),
+                expected: "array".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    fn parse_notify_configs(
+        notify_config: &Yaml,
+    ) -> NetResult<Vec<NotifyRule>> {
+        match notify_config {
+            Yaml::Array(configs) => configs
+                .iter()
+                .enumerate()
+                .map(|(index, rule_config)| {
+                    NotifyRule::from_yaml_config(
+                        &index.to_string(),
+                        rule_config,
+                        BTreeMap::new(),
+                    )
+                })
+                .collect(),
+            Yaml::Null => Ok(vec![]),
+            _ => Err(ConfigError::IncorrectType {
+                path: YamlPath::new().key("notify").unknown(),
+                expected: "array".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    fn parse_invariant_configs(
+        invariants_config: &Yaml,
+    ) -> NetResult<Vec<Invariant>> {
+        match invariants_config {
+            Yaml::Array(configs) => configs
+                .iter()
+                .enumerate()
+                .map(|(index, config)| {
+                    let invariant =
+                        config.as_str().and_then(Invariant::from_name);
+                    invariant.ok_or_else(|| {
+                        NetError::from(ConfigError::IncorrectType {
+                            path: YamlPath::new()
+                                .key("invariants")
+                                .key(index.to_string())
+                                .unknown(),
+                            expected: "adjacencies, reachability or \
+                                unique-router-ids"
+                                .to_string(),
+                        })
+                    })
+                })
+                .collect(),
+            Yaml::Null => Ok(vec![]),
+            _ => Err(ConfigError::IncorrectType {
+                path: YamlPath::new().key("invariants").unknown(),
+                expected: "array".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    fn parse_profile_configs(
+        profiles_config: &Yaml,
+    ) -> NetResult<BTreeMap<String, Impairment>> {
+        match profiles_config {
+            Yaml::Hash(configs) => configs
+                .iter()
+                .map(|(profile_name, profile_config)| {
+                    let Yaml::String(profile_name) = profile_name else {
+                        return Err(ConfigError::IncorrectType {
+                            path: YamlPath::new().key("profiles").unknown(),
+                            expected: "string".to_string(),
+                        }
+                        .into());
+                    };
+                    let profile = Impairment::from_yaml_config(
+                        profile_name,
+                        profile_config,
+                        BTreeMap::new(),
+                    )?;
+                    Ok((profile_name.clone(), profile))
+                })
+                .collect(),
+            Yaml::Null => Ok(BTreeMap::new()),
+            _ => Err(ConfigError::IncorrectType {
+                path: YamlPath::new().key("profiles").unknown(),
+                expected: "hash".to_string(),
+            }
+            .into()),
+        }
+    }
+
     fn parse_kind_configs(kinds_config: &Yaml) -> NetResult<Vec<Kind>> {
         let mut kinds: Vec<Kind> = vec![];
 
@@ -264,22 +854,53 @@ impl TopologyParser {
         Ok(switches)
     }
 
-    fn parse_links_configs(links_configs: &Yaml) -> NetResult<Vec<Link>> {
+    /// Parses the links, resolving their ends against the nodes parsed
+    /// already.
+    fn parse_links_configs(
+        links_configs: &Yaml,
+        topology: &mut Topology,
+    ) -> NetResult<Vec<Link>> {
         let mut links: Vec<Link> = vec![];
         if let Yaml::Array(configs) = links_configs {
-            for link_config in configs {
-                if let Yaml::Hash(link_config) = link_config {
+            for (index, config) in configs.iter().enumerate() {
+                if let Yaml::Hash(link_config) = config {
+                    let src_device =
+                        get_string_field(link_config, "src-device")?;
+                    let dst_device =
+                        get_string_field(link_config, "dst-device")?;
+                    let src_iface = Self::link_iface(
+                        topology,
+                        &links,
+                        link_config,
+                        "src-iface",
+                        [&src_device, &dst_device],
+                    )?;
+                    let dst_iface = Self::link_iface(
+                        topology,
+                        &links,
+                        link_config,
+                        "dst-iface",
+                        [&dst_device, &src_device],
+                    )?;
+                    let (profile, impairment) =
+                        Self::link_impairment(index, config, link_config)?;
                     let link = Link {
-                        src_device: get_string_field(
-                            link_config,
-                            "src-device",
+                        src: Self::resolve_endpoint(
+                            topology, src_device, src_iface,
+                        )?,
+                        dst: Self::resolve_endpoint(
+                            topology, dst_device, dst_iface,
                         )?,
-                        src_iface: get_string_field(link_config, "src-iface")?,
-                        dst_device: get_string_field(
+                        metadata: get_metadata_field(
                             link_config,
-                            "dst-device",
+                            &YamlPath::new().key("links"),
                         )?,
-                        dst_iface: get_string_field(link_config, "dst-iface")?,
+                        profile,
+                        impairment,
+                        mtu: Self::link_mtu(index, link_config)?,
+                        flows: Self::parse_flow_configs(link_config)?,
+                        enabled: Self::link_enabled(index, link_config)?,
+                        expect: Self::link_expectations(index, link_config)?,
                     };
                     links.push(link);
                 }
@@ -293,107 +914,3406 @@ impl TopologyParser {
         }
         Ok(links)
     }
+
+    // The impairment profile of a link, or the impairment given inline with
+    // the same fields as a profile, e.g. `delay: 40`. Bandwidth is the
+    // rate, in kbit/s.
+    fn link_impairment(
+        index: usize,
+        config: &Yaml,
+        link_config: &Hash,
+    ) -> NetResult<(Option<String>, Option<Impairment>)> {
+        let profile =
+            match link_config.get(&Yaml::String(String::from("profile"))) {
+                Some(Yaml::Null) | None => None,
+                Some(_) => Some(get_string_field(link_config, "profile")?),
+            };
+        let inline = ["delay", "jitter", "loss", "rate", "bandwidth"]
+            .into_iter()
+            .any(|field| link_config.contains_key(&Yaml::String(field.into())));
+        match (profile, inline) {
+            (Some(_), true) => Err(ConfigError::IncorrectType {
+                path: YamlPath::new()
+                    .key("links")
+                    .key(index.to_string())
+                    .key("profile")
+                    .unknown(),
+                expected: "either a profile or inline delay, jitter, loss \
+                    and bandwidth"
+                    .to_string(),
+            }
+            .into()),
+            (profile, false) => Ok((profile, None)),
+            (None, true) => {
+                let impairment = Impairment::from_yaml_config(
+                    &index.to_string(),
+                    config,
+                    BTreeMap::from([("parent", "links")]),
+                )?;
+                Ok((None, Some(impairment)))
+            }
+        }
+    }
+
+    fn link_mtu(index: usize, link_config: &Hash) -> NetResult<Option<u32>> {
+        let mtu = match link_config.get(&Yaml::String(String::from("mtu"))) {
+            Some(Yaml::Null) | None => return Ok(None),
+            Some(Yaml::Integer(mtu)) => u32::try_from(*mtu).ok(),
+            Some(_) => None,
+        };
+        // From the smallest MTU an IPv4 host must accept to the largest
+        // one a veth takes.
+        match mtu {
+            Some(mtu @ 68..=65535) => Ok(Some(mtu)),
+            _ => Err(ConfigError::IncorrectType {
+                path: YamlPath::new()
+                    .key("links")
+                    .key(index.to_string())
+                    .key("mtu")
+                    .unknown(),
+                expected: "MTU between 68 and 65535".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    // Whether a link is up once created, `enabled: false` holding it down.
+    fn link_enabled(index: usize, link_config: &Hash) -> NetResult<bool> {
+        match link_config.get(&Yaml::String(String::from("enabled"))) {
+            Some(Yaml::Null) | None => Ok(true),
+            Some(Yaml::Boolean(enabled)) => Ok(*enabled),
+            Some(_) => Err(ConfigError::IncorrectType {
+                path: YamlPath::new()
+                    .key("links")
+                    .key(index.to_string())
+                    .key("enabled")
+                    .unknown(),
+                expected: "bool".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    // What `verify` expects of a link, see `LinkExpectations`.
+    fn link_expectations(
+        index: usize,
+        link_config: &Hash,
+    ) -> NetResult<LinkExpectations> {
+        let path = YamlPath::new()
+            .key("links")
+            .key(index.to_string())
+            .key("expect");
+        let config = match link_config.get(&Yaml::String("expect".into())) {
+            Some(Yaml::Null) | None => return Ok(LinkExpectations::default()),
+            Some(Yaml::Hash(config)) => config,
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: path.clone().unknown(),
+                    expected: "hash".to_string(),
+                }
+                .into());
+            }
+        };
+        let incorrect = |field: &str, expected: &str| -> NetError {
+            ConfigError::IncorrectType {
+                path: path.clone().key(field).unknown(),
+                expected: expected.to_string(),
+            }
+            .into()
+        };
+
+        let mut expect = LinkExpectations::default();
+        for (field, value) in config {
+            match (field.as_str(), value) {
+                (Some("up-within"), Yaml::Integer(secs)) => {
+                    let secs = u64::try_from(*secs)
+                        .map_err(|_| incorrect("up-within", "seconds"))?;
+                    expect.up_within = Some(Duration::from_secs(secs));
+                }
+                (Some("up-within"), _) => {
+                    return Err(incorrect("up-within", "seconds"));
+                }
+                (Some("carrier"), Yaml::Boolean(carrier)) => {
+                    expect.carrier = *carrier;
+                }
+                (Some("lldp-peer"), Yaml::Boolean(lldp_peer)) => {
+                    expect.lldp_peer = *lldp_peer;
+                }
+                (Some(field @ ("carrier" | "lldp-peer")), _) => {
+                    return Err(incorrect(field, "bool"));
+                }
+                _ => {
+                    return Err(ConfigError::IncorrectType {
+                        path: path.clone().unknown(),
+                        expected: "up-within, carrier or lldp-peer".to_string(),
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(expect)
+    }
+
+    // The interface `field` of a link between `node` and `peer`, derived
+    // from the naming policy when the link leaves it out.
+    fn link_iface(
+        topology: &Topology,
+        parsed: &[Link],
+        link_config: &Hash,
+        field: &str,
+        [node, peer]: [&str; 2],
+    ) -> NetResult<String> {
+        let policy = match &topology.iface_naming {
+            Some(policy)
+                if !link_config
+                    .contains_key(&Yaml::String(field.to_string())) =>
+            {
+                policy
+            }
+            _ => return get_string_field(link_config, field),
+        };
+
+        let links = || topology.links.iter().chain(parsed);
+        // Parallel links to the same peer are told apart by their index.
+        let index = links()
+            .filter(|link| {
+                link.end_on(node).is_some() && link.end_on(peer).is_some()
+            })
+            .count();
+        let iface = policy
+            .replace("{node}", node)
+            .replace("{peer}", peer)
+            .replace("{index}", &index.to_string());
+
+        check_iface_name(&iface)?;
+        if links()
+            .any(|link| link.ends().iter().any(|end| end.is(node, &iface)))
+        {
+            return Err(ConfigError::InterfaceNameClash {
+                node: node.to_string(),
+                iface,
+            }
+            .into());
+        }
+        Ok(iface)
+    }
+
+    fn parse_flow_configs(
+        link_config: &Hash,
+    ) -> NetResult<Vec<FlowImpairment>> {
+        match link_config.get(&Yaml::String(String::from("flows"))) {
+            Some(Yaml::Array(configs)) if configs.len() <= MAX_FLOWS => configs
+                .iter()
+                .enumerate()
+                .map(|(index, flow_config)| {
+                    FlowImpairment::from_yaml_config(
+                        &index.to_string(),
+                        flow_config,
+                        BTreeMap::new(),
+                    )
+                })
+                .collect(),
+            Some(Yaml::Null) | None => Ok(vec![]),
+            Some(_) => Err(ConfigError::IncorrectType {
+                path: YamlPath::new().key("links").key("flows").unknown(),
+                expected: format!("array of at most {MAX_FLOWS} flows"),
+            }
+            .into()),
+        }
+    }
+
+    // The interfaces links are attached to need not be declared on their
+    // node when they have no addresses, they are declared here then.
+    fn resolve_endpoint(
+        topology: &mut Topology,
+        node_name: String,
+        iface_name: String,
+    ) -> NetResult<LinkEndpoint> {
+        let Some(node) = topology.nodes.get_mut(&node_name) else {
+            return Err(ConfigError::UnknownNode(node_name).into());
+        };
+        let interfaces = node.interfaces_mut();
+        if !interfaces.iter().any(|iface| iface.name == iface_name) {
+            interfaces.push(Interface::new(iface_name.clone()));
+        }
+        Ok(LinkEndpoint {
+            node: NodeId::new(&node_name),
+            iface: IfaceId::new(&iface_name),
+        })
+    }
+}
+
+// The links toggled at runtime, none when they cannot be read, the links
+// then being as the topology file says.
+fn link_toggles() -> LinkToggles {
+    LinkToggles::load().unwrap_or_else(|err| {
+        warn!(%err, "link toggles ignored");
+        LinkToggles::default()
+    })
+}
+
+// Variable the scripts of a router get its router-id in.
+const ROUTER_ID_VAR: &str = "router_id";
+
+// Variables of the whole topology file, `vars` and `router_id`: known only
+// once the addresses are assigned, the latter is left as is for the scripts
+// to be rendered with, see `resolve_router_ids`.
+fn file_vars(
+    vars: &BTreeMap<String, template::Value>,
+) -> BTreeMap<String, template::Value> {
+    let mut vars = vars.clone();
+    vars.entry(ROUTER_ID_VAR.to_string()).or_insert_with(|| {
+        template::Value::Str(format!("{{{{ {ROUTER_ID_VAR} }}}}"))
+    });
+    vars
+}
+
+// A runtime for the netlink requests of one operation. Each operation gets
+// its own, so that threads sharing a topology do not queue behind a single
+// one, and blocks on it: from within an async context, where blocking would
+// panic, it fails instead.
+fn runtime() -> NetResult<Runtime> {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(NetError::BasicError(
+            "Topology operations block, call them from a plain thread or \
+             spawn_blocking rather than from async code"
+                .to_string(),
+        ));
+    }
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| {
+            NetError::BasicError(format!(
+                "Failed to create tokio runtime: {err:?}"
+            ))
+        })
+}
+
+/// Parses a link end given as "<node>:<iface>".
+pub(crate) fn parse_end(end: &str) -> NetResult<LinkEndpoint> {
+    match end.split_once(':') {
+        Some((node, iface)) if !node.is_empty() && !iface.is_empty() => {
+            check_iface_name(iface)?;
+            Ok(LinkEndpoint {
+                node: NodeId::new(node),
+                iface: IfaceId::new(iface),
+            })
+        }
+        _ => Err(NetError::BasicError(format!(
+            "Invalid link end '{end}', expected '<node>:<iface>'."
+        ))),
+    }
+}
+
+/// Checks that the kernel accepts `name` as an interface name.
+pub(crate) fn check_iface_name(name: &str) -> NetResult<()> {
+    if name.len() > IFNAME_MAX_LEN {
+        return Err(ConfigError::InterfaceNameTooLong {
+            name: name.to_string(),
+        }
+        .into());
+    }
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name
+            .chars()
+            .any(|c| c == '/' || c == ':' || c.is_whitespace())
+    {
+        return Err(ConfigError::InvalidInterfaceName {
+            name: name.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+// Whether the placeholders of an interface naming policy are all known.
+fn valid_naming_policy(policy: &str) -> bool {
+    let mut rest = policy;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return false;
+        };
+        if !matches!(
+            &rest[start..=start + end],
+            "{node}" | "{peer}" | "{index}"
+        ) {
+            return false;
+        }
+        rest = &rest[start + end + 1..];
+    }
+    !policy.is_empty()
 }
 
 // ==== struct Topology ====
 
-#[derive(Debug)]
+/// A parsed topology, and the handles to it once powered on.
+///
+/// Every operation on a topology takes `&self` once it is configured, so it
+/// can be shared behind an `Arc` between threads. The namespace handles and
+/// bridge indexes are set once, when the nodes are powered on, and updates
+/// of the state file are serialized by `TopologyState::lock`.
+///
+/// Operations block until done, each on a runtime of its own, so threads
+/// run them concurrently. They cannot be called from async code, where they
+/// fail: wrap them in `tokio::task::spawn_blocking` there.
+#[derive(Debug, Clone)]
 pub struct Topology {
+    // Prefixed to every interface created in the shared namespace.
+    session: String,
     // String holds the nodename(),
     // Node holds the node object.
     links: Vec<Link>,
     nodes: BTreeMap<String, Node>,
-    runtime: Runtime,
+    pools: Vec<AddressPool>,
+    // Pool every router without a loopback address gets one from.
+    loopback_pool: Option<String>,
+    // Prefixed to the temporary names of veth pairs instead of the session.
+    veth_prefix: Option<String>,
+    // Names the interfaces of the links which leave them out, e.g.
+    // "to-{peer}".
+    iface_naming: Option<String>,
+    // SSH server run by the routers which do not configure one.
+    ssh: Option<SshServer>,
+    // LLDP agent run by the routers which do not configure one.
+    lldp: Option<LldpAgent>,
+    // gRPC northbound of the routers of a kind, exposed on the host.
+    grpc: Option<GrpcProxy>,
+    // Niceness and limits of the processes of the nodes which leave them
+    // out, e.g. to keep a lab from starving the rest of a laptop.
+    limits: ProcessLimits,
+    // Lab CA issuing certificates to the routers.
+    pki: Option<Pki>,
+    // Time service the routers take part in.
+    ntp: Option<NtpService>,
+    // Liveness probes, handed over to the routers at both of their ends.
+    probes: Vec<ProbeSession>,
+    // Who to tell about crashes, link flaps and failed tests.
+    notify: Vec<NotifyRule>,
+    // What `verify` checks once the topology is started.
+    invariants: Vec<Invariant>,
+    // Link impairment profiles declared in the topology, by name.
+    profiles: BTreeMap<String, Impairment>,
+    // Profiles whose nodes and links are part of the topology.
+    active_profiles: BTreeSet<String>,
+    // Maximum netlink requests in flight while setting up the links.
+    parallelism: usize,
+    // The topology file, once rendered.
+    source: String,
+    // Problems found in the topology file or on the host which netgen works
+    // around, e.g. unknown keys or programs not installed.
+    issues: Vec<String>,
+    // Callbacks of the application embedding netgen.
+    hooks: Hooks,
+    // Stops setting the topology up between two steps, see `set_cancel`.
+    cancel: CancelToken,
+    // Where the namespaces are created and the commands run.
+    backend: Arc<dyn Backend>,
 }
 
+// Topology is shared between threads, keep it that way.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Topology>();
+};
+
 impl Topology {
     fn new() -> NetResult<Self> {
         Ok(Self {
+            session: String::new(),
             links: vec![],
             nodes: BTreeMap::new(),
-            runtime: tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .map_err(|err| {
-                    NetError::BasicError(format!(
-                        "Failed to create tokio runtime: {err:?}"
-                    ))
-                })?,
+            pools: vec![],
+            loopback_pool: None,
+            veth_prefix: None,
+            iface_naming: None,
+            ssh: None,
+            lldp: None,
+            grpc: None,
+            limits: ProcessLimits::default(),
+            pki: None,
+            ntp: None,
+            probes: vec![],
+            notify: vec![],
+            invariants: vec![],
+            profiles: BTreeMap::new(),
+            active_profiles: BTreeSet::new(),
+            parallelism: DEFAULT_PARALLELISM,
+            source: String::new(),
+            issues: vec![],
+            hooks: Hooks::default(),
+            cancel: CancelToken::new(),
+            backend: Arc::new(LinuxBackend),
         })
     }
 
-    pub fn print_nodes(&self) {
-        println!("{:#?}", self.nodes);
+    pub fn session(&self) -> &str {
+        &self.session
     }
 
-    fn get_node(&self, device_name: &str) -> Option<Node> {
-        self.nodes.get(device_name).cloned()
+    // What the state of the topology outliving its run, e.g. its link
+    // toggles, is kept by: its run directory and session, only one topology
+    // running in a run directory at a time.
+    pub(crate) fn identity(&self) -> String {
+        format!("{}/{}", run_dir(), self.session)
     }
 
-    pub fn get_router(&self, router_name: &str) -> Option<Router> {
-        if let Some(Node::Router(router)) = self.get_node(router_name) {
-            Some(router)
-        } else {
-            None
-        }
+    // The topology file, once rendered.
+    pub(crate) fn source(&self) -> &str {
+        &self.source
     }
 
-    /// We power on the switches.
-    ///
-    /// This is by creating a bridged device and making sure its administrative
-    ///     state is "up".This is done in the main namespace.
-    pub fn power_switches_on(&mut self) -> NetResult<()> {
-        let power_on_span = debug_span!("switch-power-on");
-        let _span_guard = power_on_span.enter();
+    /// Runs the actions of the `notify` rules of the topology matching
+    /// `notification`.
+    pub fn notify(&self, notification: &Notification) {
+        notify::dispatch(&self.notify, &self.session, notification);
+    }
 
-        for node in self.nodes.values_mut() {
-            if let Node::Switch(switch) = node {
-                switch.power_on(&self.runtime)?;
+    // Prefix of the temporary names of veth pairs.
+    fn veth_prefix(&self) -> &str {
+        self.veth_prefix.as_deref().unwrap_or(&self.session)
+    }
+
+    /// Router-id of the node, i.e. the address of its loopback interface.
+    pub fn router_id(&self, node_name: &str) -> Option<IpAddr> {
+        match self.nodes.get(node_name) {
+            Some(Node::Router(router)) => router.router_id,
+            _ => None,
+        }
+    }
+
+    // Asks for a loopback address from the loopback pool on every router
+    // that does not configure one.
+    fn request_loopbacks(&mut self) {
+        let Some(pool) = &self.loopback_pool else {
+            return;
+        };
+        for node in self.nodes.values_mut() {
+            let Node::Router(router) = node else {
+                continue;
+            };
+            let lo = match router
+                .interfaces
+                .iter_mut()
+                .position(|iface| iface.name == "lo")
+            {
+                Some(idx) => &mut router.interfaces[idx],
+                None => {
+                    router.interfaces.push(Interface::new("lo".to_string()));
+                    router.interfaces.last_mut().unwrap()
+                }
+            };
+            let family = self
+                .pools
+                .iter()
+                .find(|candidate| candidate.name == *pool)
+                .map(|pool| AddressFamily::of(pool.prefix.ip()));
+            let enabled = lo
+                .families
+                .as_ref()
+                .unwrap_or(&router.families)
+                .contains(family.unwrap_or(AddressFamily::Ipv4));
+            if lo.addresses.is_empty() && lo.pools.is_empty() && enabled {
+                lo.pools.push(pool.clone());
+            }
+        }
+    }
+
+    // Rejects the addresses, static or from pools, of the families disabled
+    // on the router interfaces they are configured on.
+    fn check_families(&self) -> NetResult<()> {
+        for node in self.nodes.values() {
+            let Node::Router(router) = node else {
+                continue;
+            };
+            for iface in &router.interfaces {
+                let families = router.families_of(&iface.name);
+                if let Some(address) = iface
+                    .addresses
+                    .iter()
+                    .find(|address| !families.allows(address.ip()))
+                {
+                    return Err(ConfigError::DisabledFamily {
+                        node: router.name.clone(),
+                        iface: iface.name.clone(),
+                        address: *address,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Makes the IPv4 address of the routers' loopback their router-id, the
+    // one BGP and OSPF take, and hands it to their route injector and to the
+    // `router_id` variable of their scripts.
+    fn resolve_router_ids(&mut self) -> NetResult<()> {
+        for node in self.nodes.values_mut() {
+            let Node::Router(router) = node else {
+                continue;
+            };
+            router.router_id = router.loopback_v4().map(IpAddr::V4);
+
+            let mut vars = BTreeMap::new();
+            if let Some(router_id) = router.router_id {
+                vars.insert(
+                    ROUTER_ID_VAR.to_string(),
+                    template::Value::Str(router_id.to_string()),
+                );
+            }
+            for script in &mut router.scripts {
+                *script = template::render_with(script, &vars).map_err(
+                    |err| match router.router_id {
+                        Some(_) => err,
+                        None => NetError::BasicError(format!(
+                            "Scripts of {}: {err}, the router having no IPv4 \
+                                loopback address to take its router-id from",
+                            router.name
+                        )),
+                    },
+                )?;
+            }
+            if let Some(injector) = &mut router.injector {
+                injector.resolve(
+                    &router.name,
+                    router.router_id,
+                    router.loopback_address(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // Applies the topology-wide SSH server to the routers which do not
+    // configure their own, and drops the authorized keys in place.
+    fn resolve_ssh(&mut self) {
+        for node in self.nodes.values_mut() {
+            let Node::Router(router) = node else {
+                continue;
+            };
+            if router.ssh.is_none() {
+                router.ssh = self.ssh.clone().map(Box::new);
+            }
+            if let Some(keys) = router
+                .ssh
+                .as_ref()
+                .filter(|ssh| ssh.enabled)
+                .and_then(|ssh| ssh.authorized_keys_file())
+            {
+                router.files.push(keys);
+            }
+        }
+    }
+
+    // Proxies the gRPC northbound of the routers of the proxied kind, each
+    // on the next host port no `expose:` of a router takes, in name order.
+    fn resolve_grpc(&mut self) -> NetResult<()> {
+        let Some(grpc) = &self.grpc else {
+            return Ok(());
+        };
+        let taken: BTreeSet<u16> = self
+            .nodes
+            .values()
+            .filter_map(|node| match node {
+                Node::Router(router) => Some(router),
+                Node::Switch(_) => None,
+            })
+            .flat_map(|router| router.expose.iter())
+            .map(|forward| forward.host_port)
+            .collect();
+        // The ports past host-port ran out before the routers did.
+        let exhausted = || ConfigError::IncorrectType {
+            path: YamlPath::new().key("grpc").key("host-port").unknown(),
+            expected: format!(
+                "port leaving room for the {} routers",
+                grpc.kind
+            ),
+        };
+        let mut next = Some(grpc.host_port);
+        for node in self.nodes.values_mut() {
+            let Node::Router(router) = node else {
+                continue;
+            };
+            if router.kind.as_deref() != Some(grpc.kind.as_str()) {
+                continue;
+            }
+            let mut host_port = next.ok_or_else(exhausted)?;
+            while taken.contains(&host_port) {
+                host_port = host_port.checked_add(1).ok_or_else(exhausted)?;
+            }
+            let mut forward = PortForward::new(grpc.port);
+            forward.host_port = host_port;
+            router.grpc = Some(forward);
+            next = host_port.checked_add(1);
+        }
+        Ok(())
+    }
+
+    /// Where the gRPC northbound of every proxied router is reachable from
+    /// the host, by router name.
+    pub fn grpc_endpoints(&self) -> BTreeMap<String, SocketAddr> {
+        self.nodes
+            .values()
+            .filter_map(|node| match node {
+                Node::Router(router) => {
+                    let forward = router.grpc.as_ref()?;
+                    Some((router.name.clone(), forward.host_addr()))
+                }
+                Node::Switch(_) => None,
+            })
+            .collect()
+    }
+
+    // Applies the topology-wide LLDP agent to the routers which do not
+    // configure their own.
+    fn resolve_lldp(&mut self) {
+        for node in self.nodes.values_mut() {
+            if let Node::Router(router) = node
+                && router.lldp.is_none()
+            {
+                router.lldp.clone_from(&self.lldp);
+            }
+        }
+    }
+
+    // Applies the topology-wide limits to the processes of the nodes, where
+    // they leave them out.
+    fn resolve_limits(&mut self) {
+        for node in self.nodes.values_mut() {
+            match node {
+                Node::Router(router) => {
+                    router.limits = router.limits.or(self.limits);
+                }
+                Node::Switch(switch) => {
+                    switch.limits = switch.limits.or(self.limits);
+                }
+            }
+        }
+    }
+
+    // Hands a certificate over to the routers the lab CA issues one to,
+    // valid for their addresses, and fills in the `<TLS-*>` placeholders
+    // of their scripts.
+    fn resolve_pki(&mut self) -> NetResult<()> {
+        let Some(pki) = &self.pki else {
+            return Ok(());
+        };
+        let names: Vec<String> = match &pki.routers {
+            Some(routers) => {
+                for router in routers {
+                    if !matches!(self.nodes.get(router), Some(Node::Router(_)))
+                    {
+                        return Err(
+                            ConfigError::UnknownNode(router.clone()).into()
+                        );
+                    }
+                }
+                routers.clone()
+            }
+            None => self.router_names(),
+        };
+
+        for name in names {
+            let cert = NodeCert {
+                validity: pki.validity,
+                addresses: self.addresses_of(&name),
+            };
+            let Some(Node::Router(router)) = self.nodes.get_mut(&name) else {
+                continue;
+            };
+            for script in &mut router.scripts {
+                for (placeholder, path) in NodeCert::PLACEHOLDERS {
+                    *script = script.replace(placeholder, path);
+                }
+            }
+            router.files.extend(NodeCert::files(&name));
+            router.cert = Some(cert);
+        }
+        Ok(())
+    }
+
+    // Makes the time server's router serve time, and the routers which do
+    // not opt out its clients.
+    fn resolve_ntp(&mut self) -> NetResult<()> {
+        let Some(ntp) = &self.ntp else {
+            return Ok(());
+        };
+        if !matches!(self.nodes.get(&ntp.server), Some(Node::Router(_))) {
+            return Err(ConfigError::UnknownNode(ntp.server.clone()).into());
+        }
+        let loopback = match self.nodes.get(&ntp.server) {
+            Some(Node::Router(router)) => router.loopback_address(),
+            _ => None,
+        };
+        let address = ntp
+            .address
+            .or(loopback)
+            .or_else(|| self.addresses_of(&ntp.server).first().copied())
+            .ok_or_else(|| {
+                NetError::BasicError(format!(
+                    "NTP server {} has no addresses to serve time on.",
+                    ntp.server
+                ))
+            })?;
+
+        for node in self.nodes.values_mut() {
+            if let Node::Router(router) = node {
+                if router.name == ntp.server {
+                    router.ntp = Some(NtpRole::Server);
+                } else if router.ntp.is_none() {
+                    router.ntp = Some(NtpRole::Client(address));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Hands each probe session over to the routers at both of its ends,
+    // each probing the first address of the other.
+    fn resolve_probes(&mut self) -> NetResult<()> {
+        // By router and port, the peer of the session bound to it.
+        let mut bound: BTreeMap<(&str, u16), &str> = BTreeMap::new();
+        for session in &self.probes {
+            for (node, peer) in [
+                (&session.nodes[0], &session.nodes[1]),
+                (&session.nodes[1], &session.nodes[0]),
+            ] {
+                if let Some(other) =
+                    bound.insert((node.as_str(), session.port), peer.as_str())
+                {
+                    return Err(NetError::BasicError(format!(
+                        "Probe sessions of {node} with {other} and {peer} \
+                            both use port {}.",
+                        session.port
+                    )));
+                }
+            }
+        }
+        for session in &self.probes {
+            for (node, peer) in [
+                (&session.nodes[0], &session.nodes[1]),
+                (&session.nodes[1], &session.nodes[0]),
+            ] {
+                if !matches!(self.nodes.get(peer), Some(Node::Router(_))) {
+                    return Err(ConfigError::UnknownNode(peer.clone()).into());
+                }
+                let peer_address =
+                    *self.addresses_of(peer).first().ok_or_else(|| {
+                        NetError::BasicError(format!(
+                            "Node {peer} has no addresses to probe."
+                        ))
+                    })?;
+                if let Some(Node::Router(router)) = self.nodes.get_mut(node) {
+                    router.probes.push(Probe {
+                        peer: peer.clone(),
+                        peer_address,
+                        interval: session.interval,
+                        multiplier: session.multiplier,
+                        port: session.port,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Notification rules only name nodes of the topology.
+    fn check_notify(&self) -> NetResult<()> {
+        for node in self.notify.iter().flat_map(|rule| &rule.nodes) {
+            if !self.nodes.contains_key(node) {
+                return Err(ConfigError::UnknownNode(node.clone()).into());
+            }
+        }
+        Ok(())
+    }
+
+    // Resolves the profile of every link, those declared in the topology
+    // taking precedence over the presets.
+    fn resolve_profiles(&mut self) -> NetResult<()> {
+        let resolve = |profile: &String| {
+            self.profiles
+                .get(profile)
+                .cloned()
+                .or_else(|| Impairment::preset(profile))
+                .ok_or_else(|| ConfigError::UnknownProfile(profile.clone()))
+        };
+        for link in &mut self.links {
+            if let Some(profile) = &link.profile {
+                link.impairment = Some(resolve(profile)?);
+            }
+            for flow in &mut link.flows {
+                flow.impairment = Some(resolve(&flow.profile)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Problems netgen works around rather than failing: keys of the
+    /// topology file it does not know, addresses which do not parse,
+    /// programs the nodes need which are not installed and startup configs
+    /// giving interfaces other addresses than the topology. Callers decide
+    /// whether to go ahead with the topology.
+    pub fn issues(&self) -> &[String] {
+        &self.issues
+    }
+
+    // Options given to switch ports which no link attaches to.
+    fn unlinked_ports(&self) -> Vec<String> {
+        let mut issues = vec![];
+        for node in self.nodes.values() {
+            let Node::Switch(switch) = node else {
+                continue;
+            };
+            for iface in switch.ports.keys() {
+                let linked = self.links.iter().any(|link| {
+                    link.ends().iter().any(|end| end.is(&switch.name, iface))
+                });
+                if !linked {
+                    issues.push(format!(
+                        "switches.{}.ports.{iface}: no link attaches to the \
+                            port, ignored",
+                        switch.name
+                    ));
+                }
+            }
+        }
+        issues
+    }
+
+    /// Checks that the files and volumes the routers are given from the host
+    /// (startup configs among them) can be read, before anything is brought
+    /// up. Fails listing every one which cannot, by node.
+    pub fn check_files(&self) -> NetResult<()> {
+        let mut unreadable = vec![];
+        for (name, node) in &self.nodes {
+            let Node::Router(router) = node else {
+                continue;
+            };
+            for file in &router.files {
+                let FileSource::Path(src) = &file.source else {
+                    continue;
+                };
+                let readable = File::open(src).and_then(|file| {
+                    match file.metadata()?.is_file() {
+                        true => Ok(()),
+                        false => Err(io::Error::other("not a file")),
+                    }
+                });
+                if let Err(err) = readable {
+                    unreadable
+                        .push(format!("{name}: {src} ({}): {err}", file.dst));
+                }
+            }
+            for volume in &router.volumes {
+                if let Err(err) = fs::metadata(&volume.src) {
+                    unreadable.push(format!(
+                        "{name}: volume {} ({}): {err}",
+                        volume.src, volume.dst
+                    ));
+                }
+            }
+        }
+        match unreadable.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigError::UnreadableFiles(unreadable).into()),
+        }
+    }
+
+    // Interfaces the FRR startup configs give other addresses than the
+    // topology does. Interfaces the configs set no address on are left to
+    // the topology, and configs which cannot be read to `check_files`.
+    fn startup_config_mismatches(&self) -> Vec<String> {
+        let mut issues = vec![];
+        for router in self.nodes.values().filter_map(|node| match node {
+            Node::Router(router) => Some(router),
+            Node::Switch(_) => None,
+        }) {
+            let contents = match router
+                .frr
+                .as_ref()
+                .and_then(|frr| frr.startup_config.as_ref())
+            {
+                Some(FileSource::Inline(contents)) => contents.clone(),
+                Some(FileSource::Path(path)) => {
+                    match fs::read_to_string(path) {
+                        Ok(contents) => contents,
+                        Err(_) => continue,
+                    }
+                }
+                None => continue,
+            };
+
+            let path = format!("routers.{}.frr.startup-config", router.name);
+            for (iface, configured) in import::config_addresses(&contents) {
+                if configured.is_empty() {
+                    continue;
+                }
+                let configured: BTreeSet<_> = configured.into_iter().collect();
+                let declared: BTreeSet<_> = router
+                    .interfaces
+                    .iter()
+                    .find(|declared| declared.name == iface)
+                    .map(|declared| declared.addresses.iter().copied())
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                if configured == declared {
+                    continue;
+                }
+                let list = |addresses: &BTreeSet<IpNetwork>| {
+                    addresses
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                issues.push(match declared.is_empty() {
+                    true => format!(
+                        "{path}: {iface} has {} in the startup config, \
+                            none in the topology",
+                        list(&configured)
+                    ),
+                    false => format!(
+                        "{path}: {iface} has {} in the startup config, {} \
+                            in the topology",
+                        list(&configured),
+                        list(&declared)
+                    ),
+                });
+            }
+        }
+        issues
+    }
+
+    // Programs the services of the nodes run which are not installed, those
+    // services failing to start otherwise.
+    fn missing_programs(&self) -> Vec<String> {
+        // By program, the nodes needing it.
+        let mut needed: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+        for node in self.nodes.values() {
+            let mut programs = vec![];
+            match node {
+                Node::Router(router) => {
+                    if let Some(frr) = &router.frr {
+                        programs.extend(
+                            frr.daemons()
+                                .into_iter()
+                                .map(|daemon| frr.binary(daemon)),
+                        );
+                        if frr.startup_config.is_some() {
+                            programs.push("vtysh".to_string());
+                        }
+                    }
+                    if router
+                        .ntp
+                        .as_ref()
+                        .is_some_and(|ntp| *ntp != NtpRole::Disabled)
+                    {
+                        programs.push("chronyd".to_string());
+                    }
+                    if router.ssh.as_ref().is_some_and(|ssh| ssh.enabled) {
+                        programs.push("dropbear".to_string());
+                        programs.push("dropbearkey".to_string());
+                    }
+                    if router.injector.is_some() {
+                        programs.push("gobgpd".to_string());
+                    }
+                    if !router.nat.is_empty() {
+                        programs.push("nft".to_string());
+                        programs.push("sysctl".to_string());
+                    }
+                    let restricted = std::iter::once(&router.families)
+                        .chain(
+                            router
+                                .interfaces
+                                .iter()
+                                .filter_map(|iface| iface.families.as_ref()),
+                        )
+                        .any(|families| families.iter().count() < 2);
+                    if restricted {
+                        programs.push("sysctl".to_string());
+                    }
+                    programs.sort();
+                    programs.dedup();
+                }
+                Node::Switch(switch) => {
+                    if switch.flow_export.is_some() {
+                        programs.push("pmacctd".to_string());
+                    }
+                    for port in switch.ports.values() {
+                        if !port.bridge_args().is_empty() {
+                            programs.push("bridge".to_string());
+                        }
+                        if port.police.is_some() {
+                            programs.push("tc".to_string());
+                        }
+                    }
+                    programs.sort();
+                    programs.dedup();
+                }
+            }
+            for program in programs {
+                needed.entry(program).or_default().push(node.name());
+            }
+        }
+        let impaired = self
+            .links
+            .iter()
+            .any(|link| link.impairment.is_some() || !link.flows.is_empty());
+        if impaired {
+            needed
+                .entry("tc".to_string())
+                .or_default()
+                .push("the links");
+        }
+
+        needed
+            .into_iter()
+            .filter(|(program, _)| !installed(program))
+            .map(|(program, nodes)| {
+                format!(
+                    "{program} is not installed (needed by {})",
+                    nodes.join(", ")
+                )
+            })
+            .collect()
+    }
+
+    /// Caps the number of concurrent netlink operations during setup, for
+    /// hosts that throttle heavy netlink usage.
+    pub fn set_parallelism(&mut self, parallelism: usize) {
+        self.parallelism = parallelism.max(1);
+    }
+
+    /// Replaces the session identifier the topology file set, renaming the
+    /// bridges of its switches along with it.
+    pub fn set_session(&mut self, session: &str) -> NetResult<()> {
+        check_iface_name(&format!("{session}-XXXX"))?;
+        for node in self.nodes.values() {
+            if let Node::Switch(switch) = node {
+                check_iface_name(&format!("{session}-{}", switch.name))?;
+            }
+        }
+        for node in self.nodes.values_mut() {
+            if let Node::Switch(switch) = node {
+                switch.bridge = format!("{session}-{}", switch.name);
+            }
+        }
+        self.session = session.to_string();
+        Ok(())
+    }
+
+    // Gives the topology `id` as its session and veth prefix, so that
+    // neither its bridges nor its temporary veths clash with those of
+    // another run, whatever its topology file set.
+    pub(crate) fn isolate(&mut self, id: &str) -> NetResult<()> {
+        self.set_session(id)?;
+        self.veth_prefix = None;
+        Ok(())
+    }
+
+    /// Makes setting the topology up stop once `cancel` is cancelled, e.g.
+    /// on Ctrl-C, between two of its steps: what was brought up until then
+    /// is recorded in the state file, for `netgen stop` to tear it down.
+    pub fn set_cancel(&mut self, cancel: CancelToken) {
+        self.cancel = cancel;
+    }
+
+    /// Calls `hook` with the name of every node once powered on, i.e. once
+    /// the namespace of a router or the bridge of a switch exists, before
+    /// any link is attached to it.
+    ///
+    /// Hooks are called from the process bringing the topology up, which
+    /// `netgen start` and `OwnedTopology` fork: they should report through
+    /// files, sockets or channels to other processes rather than memory.
+    pub fn on_node_up(&mut self, hook: impl Fn(&str) + Send + Sync + 'static) {
+        self.hooks.add_node_up(Arc::new(hook));
+    }
+
+    /// Calls `hook` with every link once both of its ends are attached and
+    /// addressed, before the routers' scripts and daemons are started.
+    pub fn on_link_up(&mut self, hook: impl Fn(&Link) + Send + Sync + 'static) {
+        self.hooks.add_link_up(Arc::new(hook));
+    }
+
+    /// Calls `hook` with the name of the router and of the FRR daemon once
+    /// each daemon is started and the router's startup config is loaded.
+    pub fn on_daemon_ready(
+        &mut self,
+        hook: impl Fn(&str, &str) + Send + Sync + 'static,
+    ) {
+        self.hooks.add_daemon_ready(Arc::new(hook));
+    }
+
+    pub fn backend(&self) -> &dyn Backend {
+        self.backend.as_ref()
+    }
+
+    /// Replaces the Linux namespaces the topology runs on, e.g. with a
+    /// `MockBackend` to exercise it without privileges.
+    pub fn set_backend(&mut self, backend: Arc<dyn Backend>) {
+        self.backend = backend;
+    }
+
+    /// Names of the routers, i.e. of the nodes with their own namespace.
+    pub fn router_names(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| matches!(node, Node::Router(_)))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Names of the devices under test (`dut: true`), which measurements
+    /// and reports focus on.
+    pub fn duts(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| {
+                matches!(node, Node::Router(router) if router.is_dut())
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    pub fn print_nodes(&self) {
+        println!("{:#?}", self.nodes);
+    }
+
+    /// The nodes of the topology, ordered by name.
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.values()
+    }
+
+    /// The links of the topology, in the order they were declared in.
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    pub fn get_node(&self, device_name: &str) -> Option<&Node> {
+        self.nodes.get(device_name)
+    }
+
+    /// The links attached to the node.
+    pub fn links_of<'a>(
+        &'a self,
+        node_name: &'a str,
+    ) -> impl Iterator<Item = &'a Link> + 'a {
+        self.links
+            .iter()
+            .filter(move |link| link.end_on(node_name).is_some())
+    }
+
+    fn graph(&self) -> TopologyGraph<'_> {
+        TopologyGraph::new(self.nodes.keys().map(String::as_str), &self.links)
+    }
+
+    /// Nodes sharing a link with the node, ordered by name. Nodes attached
+    /// to the same switch are neighbors of the switch, not of each other.
+    pub fn neighbors(&self, node_name: &str) -> Vec<&str> {
+        self.graph().neighbors(node_name)
+    }
+
+    /// Whether the two nodes are connected, directly or through others.
+    pub fn path_exists(&self, from: &str, to: &str) -> bool {
+        self.graph().path_exists(from, to)
+    }
+
+    /// Groups of nodes connected to one another. A single one unless the
+    /// topology is made of separate parts.
+    pub fn segments(&self) -> Vec<Vec<&str>> {
+        self.graph().segments()
+    }
+
+    /// Whether cutting the link would leave its ends unable to reach each
+    /// other, e.g. because it is the last link to one of them.
+    pub fn disconnects(&self, link: &Link) -> bool {
+        self.graph().disconnects(link)
+    }
+
+    /// Links to cut so that the nodes of each group can no longer reach
+    /// those of the other groups. See `partition`.
+    pub fn cut_set(&self, groups: &[Vec<String>]) -> NetResult<Vec<&Link>> {
+        if groups.len() < 2 {
+            return Err(NetError::BasicError(
+                "A partition needs at least two groups.".to_string(),
+            ));
+        }
+        let mut grouped = BTreeMap::new();
+        for (position, group) in groups.iter().enumerate() {
+            for name in group {
+                if !self.nodes.contains_key(name) {
+                    return Err(ConfigError::UnknownNode(name.clone()).into());
+                }
+                if grouped.insert(name.as_str(), position).is_some() {
+                    return Err(NetError::BasicError(format!(
+                        "Node {name} is in more than one group."
+                    )));
+                }
+            }
+        }
+
+        let groups: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|group| group.iter().map(String::as_str).collect())
+            .collect();
+        let mut cut = self.graph().cut_set(&groups);
+        cut.sort_by_cached_key(|link| link_name(link));
+        Ok(cut)
+    }
+
+    /// Splits the running topology into `groups` by bringing down both
+    /// ends of the fewest links possible. The links are recorded so that
+    /// `heal` can bring them back, and returned.
+    pub fn partition(&self, groups: &[Vec<String>]) -> NetResult<Vec<Link>> {
+        let partition_span = debug_span!("partition");
+        let _span_guard = partition_span.enter();
+
+        let _state_lock = TopologyState::lock()?;
+        let mut state = TopologyState::load()?.ok_or_else(|| {
+            NetError::BasicError(
+                "No topology instance currently running.".to_string(),
+            )
+        })?;
+        if !state.partitioned.is_empty() {
+            return Err(NetError::BasicError(
+                "Topology is partitioned already. Consider running \
+                    'netgen heal' first."
+                    .to_string(),
+            ));
+        }
+
+        let cut = self.cut_set(groups)?;
+        let result = cut
+            .iter()
+            .try_for_each(|link| {
+                // Recorded first, so that a link cut halfway is restored.
+                state.partitioned.push(link_name(link));
+                self.set_link_state(link, false)?;
+                debug!(link = %link_name(link), "cut");
+                Ok(())
+            })
+            .and_then(|()| state.save());
+
+        // Rather than leave links down without a record for `heal` to find,
+        // those cut already are brought back up, except the links meant to
+        // be down anyway.
+        if let Err(err) = result {
+            let toggles = link_toggles();
+            for link in cut.iter().rev() {
+                if !state.partitioned.contains(&link_name(link))
+                    || !self.link_enabled_in(link, &toggles)
+                {
+                    continue;
+                }
+                if let Err(err) = self.set_link_state(link, true) {
+                    warn!(link = %link_name(link), %err,
+                        "cut link not brought back up");
+                }
+            }
+            return Err(err);
+        }
+        Ok(cut.into_iter().cloned().collect())
+    }
+
+    /// Brings back up the links cut by `partition`, returning them.
+    pub fn heal(&self) -> NetResult<Vec<Link>> {
+        let heal_span = debug_span!("heal");
+        let _span_guard = heal_span.enter();
+
+        let _state_lock = TopologyState::lock()?;
+        let Some(mut state) = TopologyState::load()? else {
+            return Ok(vec![]);
+        };
+
+        // Links meant to be down stay down.
+        let toggles = link_toggles();
+        let healed: Vec<Link> = self
+            .links
+            .iter()
+            .filter(|link| state.partitioned.contains(&link_name(link)))
+            .filter(|link| self.link_enabled_in(link, &toggles))
+            .cloned()
+            .collect();
+        for link in &healed {
+            self.set_link_state(link, true)?;
+            debug!(link = %link_name(link), "healed");
+        }
+        state.partitioned.clear();
+        state.save()?;
+        Ok(healed)
+    }
+
+    /// Programs `route` into the FIB of the running router `node` over
+    /// netlink, replacing any route to the same prefix. Unlike those of
+    /// `static-fib`, the route is gone once the router is recreated.
+    pub fn add_route(&self, node: &str, route: &StaticRoute) -> NetResult<()> {
+        let runtime = runtime()?;
+        let Some(Node::Router(router)) = self.nodes.get(node) else {
+            return Err(NetError::BasicError(format!(
+                "{node} is not a router of the topology"
+            )));
+        };
+        let namespace = NamespaceHandle::new(Some(node));
+        if !namespace.is_active() {
+            return Err(NetError::BasicError(format!("{node} is not running")));
+        }
+        let _ = router.namespace.set(Box::new(namespace));
+        router.add_routes(&runtime, std::slice::from_ref(route))
+    }
+
+    /// Adds `addresses`, as (interface, address) pairs, to the router `node`
+    /// of the running topology, all at once. The addresses are not recorded
+    /// in the topology and are gone once it is restarted.
+    pub fn add_addresses_bulk(
+        &self,
+        node: &str,
+        addresses: Vec<(String, IpNetwork)>,
+    ) -> NetResult<()> {
+        let runtime = runtime()?;
+        let Some(Node::Router(router)) = self.nodes.get(node) else {
+            return Err(NetError::BasicError(format!(
+                "{node} is not a router of the topology"
+            )));
+        };
+        for (iface, address) in &addresses {
+            if !router.families_of(iface).allows(address.ip()) {
+                return Err(ConfigError::DisabledFamily {
+                    node: node.to_string(),
+                    iface: iface.clone(),
+                    address: *address,
+                }
+                .into());
+            }
+        }
+        let namespace = NamespaceHandle::new(Some(node));
+        if !namespace.is_active() {
+            return Err(NetError::BasicError(format!("{node} is not running")));
+        }
+        let _ = router.namespace.set(Box::new(namespace));
+        router.add_addresses(&runtime, &addresses, false)
+    }
+
+    // Brings both ends of the link up or down. Switch ends live in the main
+    // namespace.
+    fn set_link_state(&self, link: &Link, up: bool) -> NetResult<()> {
+        let state = if up { "up" } else { "down" };
+        for end in link.ends() {
+            let node = self.nodes.get(end.node.as_str());
+            let device = match node {
+                Some(Node::Router(_)) => Some(end.node.as_str()),
+                _ => None,
+            };
+            let iface = end.iface.as_str();
+            let output = self.backend.exec(
+                device,
+                "ip",
+                &["link", "set", iface, state],
+            )?;
+            if !output.status.success() {
+                return Err(NetError::BasicError(format!(
+                    "Unable to bring {end} {state}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+
+            // IPv6 addresses are flushed when the interface goes down, put
+            // them back.
+            if let (true, Some(node @ Node::Router(_))) = (up, node) {
+                let addresses = node
+                    .interfaces()
+                    .iter()
+                    .filter(|node_iface| node_iface.name == iface)
+                    .flat_map(|node_iface| node_iface.addresses.iter());
+                for address in addresses {
+                    let address = address.to_string();
+                    self.backend.exec(
+                        device,
+                        "ip",
+                        &["addr", "replace", &address, "dev", iface],
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_router(&self, router_name: &str) -> Option<Router> {
+        self.get_node(router_name)?.as_router().cloned()
+    }
+
+    /// Copies the file or directory `src` to `dst`, each a host path or
+    /// "<node>:<path>" of a running router, the path as the router's
+    /// processes see it. Returns where it was copied to on the host.
+    pub fn copy_files(&self, src: &str, dst: &str) -> NetResult<PathBuf> {
+        let (src, dst) = (self.copy_path(src)?, self.copy_path(dst)?);
+        if !src.exists() {
+            return Err(NetError::BasicError(format!(
+                "{} does not exist.",
+                src.display()
+            )));
+        }
+        // Into directories, as cp does.
+        let dst = match (dst.is_dir(), src.file_name()) {
+            (true, Some(name)) => dst.join(name),
+            _ => dst,
+        };
+        match src.is_dir() {
+            true => crate::copy_dir_all(&src, &dst),
+            false => fs::copy(&src, &dst).map(|_| ()),
+        }
+        .map_err(|err| {
+            NetError::BasicError(format!(
+                "Unable to copy {} to {}: {err:?}",
+                src.display(),
+                dst.display()
+            ))
+        })?;
+        debug!(src = %src.display(), dst = %dst.display(), "copied");
+        Ok(dst)
+    }
+
+    // Where `spec`, a host path or "<node>:<path>", lives on the host.
+    fn copy_path(&self, spec: &str) -> NetResult<PathBuf> {
+        let Some((node, path)) = spec
+            .split_once(':')
+            .filter(|(node, _)| !node.is_empty() && !node.contains('/'))
+        else {
+            return Ok(PathBuf::from(spec));
+        };
+        let router = match self.nodes.get(node) {
+            Some(Node::Router(router)) => router,
+            Some(Node::Switch(_)) => {
+                return Err(NetError::BasicError(format!(
+                    "{node} is a switch, it has no files of its own"
+                )));
+            }
+            None => {
+                return Err(ConfigError::UnknownNode(node.to_string()).into());
+            }
+        };
+        if !NamespaceHandle::new(Some(node)).is_mounted() {
+            return Err(NetError::BasicError(format!("{node} is not running")));
+        }
+        if !path.starts_with('/') {
+            return Err(NetError::BasicError(format!(
+                "Paths in {node} must be absolute, got '{path}'."
+            )));
+        }
+        let (host_path, private) = router.host_path(path);
+        if !private {
+            warn!(%node, %path,
+                "not private to the router, shared with the host");
+        }
+        Ok(host_path)
+    }
+
+    /// Runs `cmd` with `args` in the network namespace of the running router
+    /// `node_name`, its standard input and outputs being ours, and returns
+    /// its exit status once it is done.
+    pub fn exec_in_node(
+        &self,
+        node_name: &str,
+        cmd: &str,
+        args: &[&str],
+    ) -> NetResult<ExitStatus> {
+        self.exec_namespace(node_name)?.run(cmd, args)
+    }
+
+    /// Runs `cmd` with `args` in every running router, at most `parallel`
+    /// of them at once, capturing their outputs. Returns what each router
+    /// output, or why the command could not run there, in the order of
+    /// `router_names`.
+    pub fn exec_in_routers(
+        &self,
+        cmd: &str,
+        args: &[&str],
+        parallel: usize,
+    ) -> Vec<(String, NetResult<Output>)> {
+        let routers = self.router_names();
+        let next = AtomicUsize::new(0);
+        let workers = parallel.clamp(1, routers.len().max(1));
+        let mut outputs: BTreeMap<usize, NetResult<Output>> =
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..workers)
+                    .map(|_| {
+                        scope.spawn(with_run_dir(|| {
+                            let mut outputs = vec![];
+                            loop {
+                                let index = next.fetch_add(1, Ordering::SeqCst);
+                                let Some(router) = routers.get(index) else {
+                                    return outputs;
+                                };
+                                let output =
+                                    self.exec_namespace(router).and_then(
+                                        |namespace| namespace.exec(cmd, args),
+                                    );
+                                outputs.push((index, output));
+                            }
+                        }))
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .filter_map(|handle| handle.join().ok())
+                    .flatten()
+                    .collect()
+            });
+
+        routers
+            .iter()
+            .enumerate()
+            .map(|(index, router)| {
+                let output = outputs.remove(&index).unwrap_or_else(|| {
+                    Err(NetError::BasicError(format!(
+                        "Running '{cmd}' in {router} panicked"
+                    )))
+                });
+                (router.clone(), output)
+            })
+            .collect()
+    }
+
+    // The namespace of the running router `node_name` to run commands in,
+    // its lazy daemons being started first.
+    fn exec_namespace(&self, node_name: &str) -> NetResult<NamespaceHandle> {
+        match self.nodes.get(node_name) {
+            Some(Node::Router(_)) => {}
+            Some(Node::Switch(_)) => {
+                return Err(NetError::BasicError(format!(
+                    "{node_name} is a switch, it has no namespace of its own"
+                )));
+            }
+            None => {
+                return Err(
+                    ConfigError::UnknownNode(node_name.to_string()).into()
+                );
+            }
+        }
+
+        let namespace = NamespaceHandle::new(Some(node_name));
+        if !namespace.is_mounted() {
+            return Err(NetError::BasicError(format!(
+                "{node_name} is not running"
+            )));
+        }
+        self.start_lazy_daemons(node_name)?;
+        Ok(namespace)
+    }
+
+    /// Checkpoints the daemons of the running router `node_name` with CRIU
+    /// as `name`, returning them. They keep running with `leave_running`,
+    /// else they are gone until restored.
+    pub fn checkpoint_node(
+        &self,
+        node_name: &str,
+        name: &str,
+        leave_running: bool,
+    ) -> NetResult<Vec<String>> {
+        self.running_router(node_name)?;
+        let state = TopologyState::load()?.unwrap_or_default();
+        let records: Vec<&ProcessRecord> = state
+            .processes
+            .iter()
+            .filter(|process| {
+                process.node == node_name
+                    && process.status().running
+                    && checkpoint::checkpointable(process)
+            })
+            .collect();
+        if records.is_empty() {
+            return Err(NetError::BasicError(format!(
+                "No daemon to checkpoint on {node_name}"
+            )));
+        }
+
+        let dir = checkpoint::checkpoint_dir(node_name, name);
+        checkpoint::dump(&records, &dir, leave_running)?;
+        debug!(node = %node_name, %dir, "checkpointed");
+        Ok(records.iter().map(|record| record.name.clone()).collect())
+    }
+
+    /// Restores the daemons of the running router `node_name` from its
+    /// checkpoint `name`, returning them. Those of its daemons the
+    /// checkpoint holds are stopped first, the restored ones taking their
+    /// PIDs back.
+    pub fn restore_node(
+        &self,
+        node_name: &str,
+        name: &str,
+    ) -> NetResult<Vec<String>> {
+        let runtime = runtime()?;
+        let router = self.running_router(node_name)?;
+        let dir = checkpoint::checkpoint_dir(node_name, name);
+        let daemons = checkpoint::daemons(&dir)?;
+
+        let replaced = |process: &ProcessRecord| {
+            process.node == node_name && daemons.contains(&process.name)
+        };
+        let state_lock = TopologyState::lock()?;
+        if let Some(mut state) = TopologyState::load()? {
+            for process in &state.processes {
+                if replaced(process)
+                    && let Some(pidfd) = process.terminate()?
+                {
+                    pidfd.wait_exit(STOP_GRACE_PERIOD);
+                }
+            }
+            state.processes.retain(|process| !replaced(process));
+            state.save()?;
+        }
+        drop(state_lock);
+
+        for daemon in &daemons {
+            router.restore_daemon(&runtime, &dir, daemon)?;
+        }
+        Ok(daemons)
+    }
+
+    /// Starts the FRR daemons of the running router `node_name` if they were
+    /// left for its first use (`lazy` under `frr`) and are not running yet.
+    /// Returns whether they were started.
+    pub fn start_lazy_daemons(&self, node_name: &str) -> NetResult<bool> {
+        let runtime = runtime()?;
+        if !matches!(
+            self.nodes.get(node_name),
+            Some(Node::Router(router)) if router.frr_is_lazy()
+        ) {
+            return Ok(false);
+        }
+        let router = self.running_router(node_name)?;
+
+        // Two commands using the router at once would start them twice.
+        let _lock = frr::start_lock(node_name)?;
+        let daemons = router.frr_daemons();
+        let state = TopologyState::load()?.unwrap_or_default();
+        frr::check_pinned(&state);
+        if state.processes.iter().any(|process| {
+            process.node == node_name
+                && daemons.contains(&process.name.as_str())
+                && process.is_running()
+        }) {
+            return Ok(false);
+        }
+
+        debug!(router = %node_name, "Starting FRR on first use");
+        router.run_frr(&runtime)?;
+        events::record("daemon", node_name, "FRR started on first use");
+        Ok(true)
+    }
+
+    // The router `node_name`, provided it is running.
+    fn running_router(&self, node_name: &str) -> NetResult<Router> {
+        let router = match self.nodes.get(node_name) {
+            Some(Node::Router(router)) => router,
+            Some(Node::Switch(_)) => {
+                return Err(NetError::BasicError(format!(
+                    "{node_name} is a switch, it runs no daemons"
+                )));
+            }
+            None => {
+                return Err(
+                    ConfigError::UnknownNode(node_name.to_string()).into()
+                );
+            }
+        };
+        let namespace = NamespaceHandle::new(Some(node_name));
+        if !namespace.is_mounted() {
+            return Err(NetError::BasicError(format!(
+                "{node_name} is not running"
+            )));
+        }
+        // Not powered on by us, the topology being the recorded one.
+        let router = router.clone();
+        let _ = router.namespace.set(Box::new(namespace));
+        Ok(router)
+    }
+
+    /// Addresses configured on the node's interfaces, those of the family
+    /// a router prefers first, and within each family those on loopback
+    /// interfaces last.
+    pub fn addresses_of(&self, node_name: &str) -> Vec<IpAddr> {
+        let Some(node) = self.nodes.get(node_name) else {
+            return vec![];
+        };
+
+        let (loopbacks, others): (Vec<_>, Vec<_>) = node
+            .interfaces()
+            .iter()
+            .partition(|iface| iface.name == "lo");
+        let mut addresses: Vec<IpAddr> = others
+            .iter()
+            .chain(loopbacks.iter())
+            .flat_map(|iface| iface.addresses.iter().map(|addr| addr.ip()))
+            .collect();
+        if let Node::Router(router) = node {
+            addresses.sort_by_key(|addr| router.families.rank(*addr));
+        }
+        addresses
+    }
+
+    /// Node one of whose interfaces has the address configured.
+    pub fn node_with_address(&self, address: IpAddr) -> Option<&str> {
+        self.nodes
+            .iter()
+            .find(|(_, node)| {
+                node.interfaces().iter().any(|iface| {
+                    iface.addresses.iter().any(|addr| addr.ip() == address)
+                })
+            })
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// We power on the switches.
+    ///
+    /// This is by creating a bridged device and making sure its administrative
+    ///     state is "up".This is done in the main namespace.
+    pub fn power_switches_on(&self) -> NetResult<()> {
+        let runtime = runtime()?;
+        let power_on_span = debug_span!("switch-power-on");
+        let _span_guard = power_on_span.enter();
+
+        let _state_lock = TopologyState::lock()?;
+        let mut state = TopologyState::new(&self.session);
+        state.topology = Some(self.source.clone());
+        state.provenance = Some(Provenance::started(self));
+        state.profiles = self.active_profiles.iter().cloned().collect();
+        state.metadata = self.metadata();
+        self.pin_binaries(&mut state);
+        for (index, node) in self.nodes.values().enumerate() {
+            if let Node::Switch(switch) = node {
+                let _span_guard = debug_span!("switch", index).entered();
+                switch.power_on(self.backend(), &runtime)?;
+                self.hooks.node_up(&switch.name);
+                state
+                    .bridges
+                    .insert(switch.name.clone(), switch.bridge.clone());
+            }
+        }
+
+        state.save()
+    }
+
+    // Records in `state` where the FRR daemons are to be started from and
+    // their versions, for the whole run to use the same ones.
+    fn pin_binaries(&self, state: &mut TopologyState) {
+        let mut dirs = BTreeSet::new();
+        for node in self.nodes.values() {
+            if let Node::Router(router) = node
+                && let Some(frr) = &router.frr
+            {
+                let dir = frr.path.clone().unwrap_or_else(|| {
+                    state.frr_dir.get_or_insert_with(frr::default_dir).clone()
+                });
+                dirs.insert(dir);
+            }
+        }
+        state.binaries = dirs.iter().map(|dir| frr::pin(dir)).collect();
+        for pinned in &state.binaries {
+            debug!(path = %pinned.path, version = ?pinned.version, "pinned");
+        }
+    }
+
+    /// Powers on Routers.
+    ///
+    /// This is done by creating a new namespace. The adding of the relevant
+    ///     interfaces is done elsewhere .
+    pub fn power_routers_on(&self) -> NetResult<()> {
+        let runtime = runtime()?;
+        let power_on_span = debug_span!("router-power-on");
+        let _span_guard = power_on_span.enter();
+
+        for (index, node) in self.nodes.values().enumerate() {
+            if let Node::Router(router) = node {
+                self.cancel.check()?;
+                let _span_guard = debug_span!("router", index).entered();
+                router.power_on(self.backend(), &runtime)?;
+                self.hooks.node_up(&router.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// "Powers off" all the devices in the network.
+    ///
+    /// For switches, this means deleting the bridged interface
+    ///
+    /// For routers, this is deleting the respective namespaces
+    pub fn power_off(&self) -> NetResult<()> {
+        let power_off_span = debug_span!("power-off");
+        let _span_guard = power_off_span.enter();
+
+        // Give the processes started in the nodes a chance to exit cleanly,
+        // they are killed along with their namespace otherwise.
+        if let Err(err) = self.stop_daemons() {
+            warn!(%err, "processes left to die with their namespaces");
+        }
+
+        // Powers off all the nodes
+        for node in self.nodes.values() {
+            node.power_off(self.backend())?;
+        }
+
+        // Kill main namespace process and unomunts it.
+        self.backend.destroy_namespace(None)?;
+        TopologyState::remove()
+    }
+
+    /// Removes what the routers kept of their `persist:` directories, for
+    /// the next start to begin afresh.
+    pub fn purge_persisted(&self) -> NetResult<()> {
+        for router in self.nodes.values().filter_map(Node::as_router) {
+            let dir = format!("{}/{}", persist_dir(), router.name);
+            match fs::remove_dir_all(&dir) {
+                Ok(()) => {
+                    debug!(router = %router.name, "persisted data purged")
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(NetError::BasicError(format!(
+                        "Unable to remove {dir}: {err:?}"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops the processes netgen started in the nodes, e.g. the routing
+    /// daemons, killing those still running after `STOP_GRACE_PERIOD`. The
+    /// nodes and their links are left as they are, e.g. for post-mortem
+    /// inspection.
+    ///
+    /// A process failing to be signaled is logged and left to die with its
+    /// namespace, rather than keeping the others running.
+    pub fn stop_daemons(&self) -> NetResult<()> {
+        let Some(state) = TopologyState::load()? else {
+            return Ok(());
+        };
+        let mut stopping = vec![];
+        for process in &state.processes {
+            match process.terminate() {
+                Ok(pidfd) => stopping.extend(pidfd),
+                Err(err) => warn!(%err, "process left running"),
+            }
+        }
+        let deadline = Instant::now() + STOP_GRACE_PERIOD;
+        for pidfd in stopping {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if !pidfd.wait_exit(timeout) {
+                debug!(pid = pidfd.pid(), "process still running, killing it");
+                let _ = pidfd.signal(Signal::SIGKILL);
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the ports the routers expose reachable from the calling
+    /// process' network namespace.
+    pub fn expose_ports(&self) -> NetResult<()> {
+        self.expose_ports_of(|_| true)
+    }
+
+    fn expose_ports_of(&self, filter: impl Fn(&str) -> bool) -> NetResult<()> {
+        for (name, node) in &self.nodes {
+            if let Node::Router(router) = node
+                && filter(name)
+            {
+                router.expose_ports()?;
+            }
+        }
+        // For automation to find the routers' northbound without parsing
+        // the topology.
+        let endpoints = self.grpc_endpoints();
+        if !endpoints.is_empty() {
+            TopologyState::record_grpc(&endpoints)?;
+        }
+        Ok(())
+    }
+
+    /// The topology currently running, as recorded in the state file.
+    ///
+    /// None when nothing is running, or when it was started before
+    /// topologies were recorded.
+    pub fn recorded() -> NetResult<Option<Topology>> {
+        let Some(state) = TopologyState::load()? else {
+            return Ok(None);
+        };
+        let Some(source) = state.topology else {
+            return Ok(None);
+        };
+        let profiles = state.profiles.into_iter().collect();
+        let mut topology = TopologyParser::from_yaml_str_with(
+            &source,
+            &BTreeMap::new(),
+            &profiles,
+        )?;
+        // Renamed since it was started, see `rename_session`.
+        topology.session = state.session;
+        for node in topology.nodes.values_mut() {
+            if let Node::Switch(switch) = node
+                && let Some(bridge) = state.bridges.get(&switch.name)
+            {
+                switch.bridge = bridge.clone();
+            }
+        }
+        Ok(Some(topology))
+    }
+
+    /// Rate limit, in kbit/s, of what the interface `iface` of `node` sends
+    /// over its link, if impaired with one.
+    pub fn rate_limit(&self, node: &str, iface: &str) -> Option<u32> {
+        self.links_of(node)
+            .find(|link| link.ends().iter().any(|end| end.is(node, iface)))
+            .and_then(|link| link.impairment.as_ref()?.rate)
+    }
+
+    /// Links with a rate limit, along with the highest one applying to any
+    /// of their traffic, in kbit/s.
+    pub fn rate_limited_links(&self) -> Vec<(&Link, u32)> {
+        self.links
+            .iter()
+            .filter_map(|link| {
+                let flows = link.flows.iter().map(|flow| &flow.impairment);
+                let rate = [&link.impairment]
+                    .into_iter()
+                    .chain(flows)
+                    .filter_map(|impairment| impairment.as_ref()?.rate)
+                    .max()?;
+                Some((link, rate))
+            })
+            .collect()
+    }
+
+    /// Puts links and interfaces in the order everything is processed in,
+    /// nodes being ordered by name already: links by name, then interfaces
+    /// by name.
+    ///
+    /// Done once pool addresses are assigned, as those are handed out in
+    /// the order links appear in the topology file.
+    fn sort(&mut self) {
+        self.links.sort_by_cached_key(link_name);
+        for node in self.nodes.values_mut() {
+            node.interfaces_mut()
+                .sort_by(|iface1, iface2| iface1.name.cmp(&iface2.name));
+        }
+    }
+
+    /// User-defined metadata of the nodes and links which have some, by
+    /// node name and link name.
+    pub fn metadata(&self) -> BTreeMap<String, BTreeMap<String, String>> {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(name, node)| (name.clone(), node.metadata().clone()));
+        let links = self
+            .links
+            .iter()
+            .map(|link| (link_name(link), link.metadata.clone()));
+        nodes
+            .chain(links)
+            .filter(|(_, metadata)| !metadata.is_empty())
+            .collect()
+    }
+
+    /// Renames the session of the running topology. Bridges named after the
+    /// session, see `set_session`, are renamed along with it, each being
+    /// down for a moment while it is.
+    ///
+    /// The bridges renamed are renamed back if one fails to be, and the
+    /// state file is only updated once all of them were.
+    pub fn rename_session(&mut self, session: &str) -> NetResult<()> {
+        check_iface_name(&format!("{session}-XXXX"))?;
+        let old_identity = self.identity();
+        let old_session = std::mem::replace(&mut self.session, session.into());
+
+        let mut renames = vec![];
+        for node in self.nodes.values() {
+            if let Node::Switch(switch) = node
+                && switch.bridge == format!("{old_session}-{}", switch.name)
+            {
+                let bridge = format!("{session}-{}", switch.name);
+                check_iface_name(&bridge)?;
+                renames.push((
+                    switch.name.clone(),
+                    switch.bridge.clone(),
+                    bridge,
+                ));
+            }
+        }
+        for (index, (_, old, new)) in renames.iter().enumerate() {
+            if let Err(err) = self.rename_bridge(old, new) {
+                for (_, old, new) in renames[..index].iter().rev() {
+                    let _ = self.rename_bridge(new, old);
+                }
+                self.session = old_session;
+                return Err(err);
+            }
+        }
+        for (name, _, bridge) in &renames {
+            if let Some(Node::Switch(switch)) = self.nodes.get_mut(name) {
+                switch.bridge = bridge.clone();
+            }
+        }
+
+        let _state_lock = TopologyState::lock()?;
+        let mut state = TopologyState::load()?
+            .unwrap_or_else(|| TopologyState::new(&self.session));
+        state.session = self.session.clone();
+        for (name, _, bridge) in renames {
+            state.bridges.insert(name, bridge);
+        }
+        state.provenance =
+            Some(Provenance::updated(state.provenance.take(), self));
+        state.save()?;
+        // The bridges are renamed already, the links toggled are what is
+        // left to lose.
+        if let Err(err) = LinkToggles::update(|toggles| {
+            toggles.rename(&old_identity, &self.identity())
+        }) {
+            warn!(%err, "link toggles not carried over to the new session");
+        }
+        events::record(
+            "session",
+            &old_session,
+            &format!("renamed to {}", self.session),
+        );
+        Ok(())
+    }
+
+    // Renames the bridge `old` of the main namespace to `new`.
+    fn rename_bridge(&self, old: &str, new: &str) -> NetResult<()> {
+        let commands: [&[&str]; 3] = [
+            &["link", "set", "dev", old, "down"],
+            &["link", "set", "dev", old, "name", new],
+            &["link", "set", "dev", new, "up"],
+        ];
+        for args in commands {
+            let output = self.backend.exec(None, "ip", args)?;
+            if !output.status.success() {
+                return Err(NetError::BasicError(format!(
+                    "Unable to rename bridge {old} to {new}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+        debug!(%old, %new, "bridge renamed");
+        Ok(())
+    }
+
+    /// Records this topology as the running one, without changing anything
+    /// else. Used when only its metadata changed, or once a change was made
+    /// live.
+    pub fn record(&self) -> NetResult<()> {
+        let _state_lock = TopologyState::lock()?;
+        let Some(mut state) = TopologyState::load()? else {
+            return Ok(());
+        };
+        state.topology = Some(self.source.clone());
+        state.provenance =
+            Some(Provenance::updated(state.provenance.take(), self));
+        state.profiles = self.active_profiles.iter().cloned().collect();
+        state.metadata = self.metadata();
+        state.save()
+    }
+
+    /// This topology with a copy of the router `original` named `name`,
+    /// linked to the `link_to` ends ("<node>:<iface>").
+    ///
+    /// The copy is linked through the interface of the original which is
+    /// linked to the same node, and keeps its loopback. Its addresses come
+    /// from the pools of the original's, or are the next free ones in the
+    /// same subnets, so that nothing else in the topology changes.
+    pub fn clone_router(
+        &self,
+        original: &str,
+        name: &str,
+        link_to: &[String],
+    ) -> NetResult<Topology> {
+        let Some(Node::Router(router)) = self.nodes.get(original) else {
+            return Err(ConfigError::UnknownNode(original.to_string()).into());
+        };
+        if self.nodes.contains_key(name) {
+            return Err(ConfigError::DuplicateNode(name.to_string()).into());
+        }
+
+        let mut links = vec![];
+        for target in link_to {
+            let target = self.free_end(target)?;
+            let (node, iface) = (target.node.as_str(), target.iface.as_str());
+
+            let ifaces: Vec<&str> = self
+                .links_of(original)
+                .filter(|link| link.end_on(node).is_some())
+                .filter_map(|link| link.end_on(original))
+                .map(|end| end.iface.as_str())
+                .collect();
+            let [original_iface] = ifaces[..] else {
+                return Err(NetError::BasicError(format!(
+                    "{original} needs exactly one link to {node} to be \
+                        linked to {target}, it has {}.",
+                    ifaces.len()
+                )));
+            };
+            links.push((original_iface.to_string(), target));
+        }
+
+        // Addresses are picked among those nothing in the topology uses.
+        let mut taken: Vec<IpNetwork> = self
+            .nodes
+            .values()
+            .flat_map(|node| node.interfaces())
+            .flat_map(|iface| iface.addresses.iter())
+            .map(|addr| IpNetwork::from(addr.ip()))
+            .collect();
+        let mut pools = self.pools.clone();
+        let mut interfaces = vec![];
+        let mut replacements = vec![(original.to_string(), name.to_string())];
+        let kept = router.interfaces.iter().filter(|iface| {
+            iface.name == "lo"
+                || links.iter().any(|(linked, _)| *linked == iface.name)
+        });
+        for iface in kept {
+            let mut addresses = vec![];
+            for addr in &iface.addresses {
+                let pool = pools.iter_mut().find(|pool| {
+                    iface.pools.contains(&pool.name)
+                        && pool.prefix.contains(addr.ip())
+                });
+                let copy = match pool {
+                    Some(pool) => pool.allocate_host(&mut taken)?,
+                    None => pool::next_free(addr, &taken).ok_or_else(|| {
+                        NetError::BasicError(format!(
+                            "No free address next to {addr} for {name}:{}.",
+                            iface.name
+                        ))
+                    })?,
+                };
+                taken.push(IpNetwork::from(copy.ip()));
+                replacements
+                    .push((addr.ip().to_string(), copy.ip().to_string()));
+                addresses.push(copy.to_string());
+            }
+            interfaces.push((iface.name.clone(), addresses));
+        }
+
+        let source = clone::add_router_copy(
+            &self.source,
+            &RouterCopy {
+                original,
+                name,
+                interfaces,
+                links,
+                replacements,
+            },
+        )?;
+        self.with_source(&source)
+    }
+
+    /// This topology with a link between the `src` and `dst` ends
+    /// ("<node>:<iface>"), which have to be free.
+    pub fn add_link(&self, src: &str, dst: &str) -> NetResult<Topology> {
+        let (src, dst) = (self.free_end(src)?, self.free_end(dst)?);
+        if src == dst {
+            return Err(NetError::BasicError(format!(
+                "{src} cannot be linked to itself."
+            )));
+        }
+        self.with_source(&edit::add_link(&self.source, &src, &dst)?)
+    }
+
+    /// This topology without the link between the `a` and `b` ends
+    /// ("<node>:<iface>").
+    pub fn remove_link(&self, a: &str, b: &str) -> NetResult<Topology> {
+        self.link_between(a, b)?;
+        let (a, b) = (parse_end(a)?, parse_end(b)?);
+        self.with_source(&edit::remove_link(&self.source, &a, &b)?)
+    }
+
+    /// The link between the `a` and `b` ends ("<node>:<iface>"), whichever
+    /// way round.
+    pub fn link_between(&self, a: &str, b: &str) -> NetResult<&Link> {
+        let (a, b) = (parse_end(a)?, parse_end(b)?);
+        self.links
+            .iter()
+            .find(|link| {
+                let [src, dst] = link.ends();
+                (*src == a && *dst == b) || (*src == b && *dst == a)
+            })
+            .ok_or_else(|| {
+                NetError::BasicError(format!(
+                    "No link between {a} and {b} in the topology."
+                ))
+            })
+    }
+
+    /// This topology without the node `name`, its links and the probe
+    /// sessions it takes part in.
+    pub fn remove_node(&self, name: &str) -> NetResult<Topology> {
+        if !self.nodes.contains_key(name) {
+            return Err(ConfigError::UnknownNode(name.to_string()).into());
+        }
+        self.with_source(&edit::remove_node(&self.source, name)?)
+    }
+
+    /// Moves the link end `end` ("<node>:<iface>") of the running topology
+    /// to the router `to`, its interface keeping its name and taking its
+    /// addresses along, without recreating the link. Returns the topology
+    /// as it is afterwards.
+    pub fn move_iface(&self, end: &str, to: &str) -> NetResult<Topology> {
+        let runtime = runtime()?;
+        let end = parse_end(end)?;
+        if !self
+            .links
+            .iter()
+            .any(|link| link.ends().iter().any(|linked| **linked == end))
+        {
+            return Err(NetError::BasicError(format!("{end} is not linked.")));
+        }
+        let (Some(Node::Router(from)), Some(Node::Router(dest))) =
+            (self.nodes.get(end.node.as_str()), self.nodes.get(to))
+        else {
+            return Err(NetError::BasicError(format!(
+                "Both {} and {to} have to be routers of the topology.",
+                end.node
+            )));
+        };
+        if from.name == dest.name {
+            return Err(NetError::BasicError(format!(
+                "{end} is already on {to}."
+            )));
+        }
+        if dest
+            .interfaces()
+            .iter()
+            .any(|iface| iface.name == end.iface)
+        {
+            return Err(NetError::BasicError(format!(
+                "{to} already has an interface {}.",
+                end.iface
+            )));
+        }
+
+        // Checked before anything changes, e.g. the addresses against the
+        // families enabled on `to`.
+        let moved =
+            self.with_source(&edit::move_iface(&self.source, &end, to)?)?;
+        let addresses: Vec<(String, IpNetwork)> = match moved.get_node(to) {
+            Some(Node::Router(router)) => router
+                .interfaces()
+                .iter()
+                .filter(|iface| iface.name == end.iface)
+                .flat_map(|iface| {
+                    iface
+                        .addresses
+                        .iter()
+                        .map(|addr| (iface.name.clone(), *addr))
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        for router in [from, dest] {
+            let namespace = NamespaceHandle::new(Some(&router.name));
+            if !namespace.is_active() {
+                return Err(NetError::BasicError(format!(
+                    "{} is not running",
+                    router.name
+                )));
             }
+            let _ = router.namespace.set(Box::new(namespace));
+        }
+        from.move_iface(&runtime, &end.iface, dest)?;
+        dest.add_addresses(&runtime, &addresses, false)?;
+        events::record("iface", &end.to_string(), &format!("moved to {to}"));
+        Ok(moved)
+    }
+
+    /// This topology reduced to the nodes in `only` (all of them when
+    /// empty) but those in `skip`. The links to the nodes left out are
+    /// dropped, to be brought up by applying the whole topology later on.
+    pub fn subset(
+        &self,
+        only: &[String],
+        skip: &[String],
+    ) -> NetResult<Topology> {
+        if let Some(unknown) = only
+            .iter()
+            .chain(skip)
+            .find(|name| !self.nodes.contains_key(name.as_str()))
+        {
+            return Err(ConfigError::UnknownNode(unknown.clone()).into());
+        }
+        let left_out: Vec<&String> = self
+            .nodes
+            .keys()
+            .filter(|name| {
+                (!only.is_empty() && !only.contains(name))
+                    || skip.contains(name)
+            })
+            .collect();
+        if left_out.len() == self.nodes.len() {
+            return Err(NetError::BasicError(
+                "No node of the topology is left to start.".to_string(),
+            ));
         }
 
-        Ok(())
+        let mut source = self.source.clone();
+        for name in left_out {
+            source = edit::remove_node(&source, name)?;
+        }
+        self.with_source(&source)
     }
 
-    /// Powers on Routers.
-    ///
-    /// This is done by creating a new namespace. The adding of the relevant
-    ///     interfaces is done elsewhere .
-    pub fn power_routers_on(&mut self) -> NetResult<()> {
-        let power_on_span = debug_span!("router-power-on");
-        let _span_guard = power_on_span.enter();
+    // The end "<node>:<iface>" of a node of the topology, which no link
+    // uses yet.
+    fn free_end(&self, end: &str) -> NetResult<LinkEndpoint> {
+        let end = parse_end(end)?;
+        if !self.nodes.contains_key(end.node.as_str()) {
+            return Err(ConfigError::UnknownNode(
+                end.node.as_str().to_string(),
+            )
+            .into());
+        }
+        if self
+            .links
+            .iter()
+            .any(|link| link.ends().iter().any(|linked| **linked == end))
+        {
+            return Err(NetError::BasicError(format!(
+                "{end} is already linked."
+            )));
+        }
+        Ok(end)
+    }
 
-        for node in self.nodes.values_mut() {
-            if let Node::Router(router) = node {
-                router.power_on()?;
+    // This topology as rewritten into `source`, run the same way.
+    fn with_source(&self, source: &str) -> NetResult<Topology> {
+        let mut topology = TopologyParser::from_yaml_str_with(
+            source,
+            &BTreeMap::new(),
+            &self.active_profiles,
+        )?;
+        topology.parallelism = self.parallelism;
+        topology.backend = self.backend.clone();
+        Ok(topology)
+    }
+
+    /// Changes turning the `running` topology (None when nothing runs) into
+    /// this one. Nodes whose configuration changed are updated, and so are
+    /// the links attached to them.
+    pub fn plan(&self, running: Option<&Topology>) -> Plan {
+        let mut plan = Plan::default();
+        let no_nodes = BTreeMap::new();
+        let running_nodes = running.map_or(&no_nodes, |running| &running.nodes);
+        let running_links = running.map_or(&[][..], |running| &running.links);
+        let kind = |node: &Node| match node {
+            Node::Router(_) => "router",
+            Node::Switch(_) => "switch",
+        };
+
+        // Metadata is not applied to anything, a change to it alone does not
+        // warrant recreating the node. Neither do the interfaces without
+        // addresses, which come and go with their links.
+        let without_metadata = |node: &Node| {
+            let mut node = node.clone();
+            node.metadata_mut().clear();
+            node.interfaces_mut()
+                .retain(|iface| !iface.addresses.is_empty());
+            node
+        };
+
+        for (name, node) in &self.nodes {
+            match running_nodes.get(name) {
+                None => plan.push(Action::Create, kind(node), name),
+                Some(running_node)
+                    if without_metadata(running_node)
+                        != without_metadata(node) =>
+                {
+                    plan.push(Action::Update, kind(node), name)
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, node) in running_nodes {
+            if !self.nodes.contains_key(name) {
+                plan.push(Action::Delete, kind(node), name);
+            }
+        }
+
+        for link in &self.links {
+            let name = link_name(link);
+            let running = running_links
+                .iter()
+                .find(|running_link| link_name(running_link) == name);
+            let replaced = link.ends().iter().any(|end| {
+                plan.node_action(end.node.as_str()) == Some(Action::Update)
+            }) || running.is_some_and(|running| {
+                running.impairment != link.impairment
+                    || running.flows != link.flows
+            });
+            if running.is_none() {
+                plan.push(Action::Create, "link", &name);
+            } else if replaced {
+                plan.push(Action::Update, "link", &name);
             }
         }
+        for link in running_links {
+            let name = link_name(link);
+            if !self.links.iter().any(|desired| link_name(desired) == name) {
+                plan.push(Action::Delete, "link", &name);
+            }
+        }
+        plan
+    }
+
+    /// Removes from this running topology what `plan` deletes or updates:
+    /// the processes of those nodes first, then the links and the nodes.
+    pub fn tear_down(&self, plan: &Plan) -> NetResult<()> {
+        let tear_down_span = debug_span!("tear-down");
+        let _span_guard = tear_down_span.enter();
+
+        let replaced = |name: &str| {
+            matches!(
+                plan.node_action(name),
+                Some(Action::Update | Action::Delete)
+            )
+        };
+
+        let state_lock = TopologyState::lock()?;
+        if let Some(mut state) = TopologyState::load()? {
+            for process in &state.processes {
+                if replaced(&process.node)
+                    && let Some(pidfd) = process.terminate()?
+                {
+                    pidfd.wait_exit(STOP_GRACE_PERIOD);
+                }
+            }
+            state.processes.retain(|process| !replaced(&process.node));
+            state.bridges.retain(|name, _| !replaced(name));
+            state.save()?;
+        }
+        drop(state_lock);
+
+        // Deleting either end of a veth pair deletes the whole pair.
+        for link in &self.links {
+            if !matches!(
+                plan.link_action(link),
+                Some(Action::Update | Action::Delete)
+            ) {
+                continue;
+            }
+            let router_end = link.ends().into_iter().find(|end| {
+                matches!(
+                    self.nodes.get(end.node.as_str()),
+                    Some(Node::Router(_))
+                )
+            });
+            let (device, iface) = match router_end {
+                Some(end) => (Some(end.node.as_str()), &end.iface),
+                None => (None, &link.src.iface),
+            };
+            let output =
+                self.backend.exec(device, "ip", &["link", "del", iface])?;
+            if !output.status.success() {
+                debug!(link = %link_name(link), "link already gone");
+            }
+        }
+
+        for (name, node) in &self.nodes {
+            if !replaced(name) {
+                continue;
+            }
+            match node {
+                Node::Router(router) => router.power_off(self.backend())?,
+                Node::Switch(switch) => {
+                    self.backend.exec(
+                        None,
+                        "ip",
+                        &["link", "del", &switch.bridge],
+                    )?;
+                }
+            }
+            debug!(node = %name, "torn down");
+        }
         Ok(())
     }
 
-    /// "Powers off" all the devices in the network.
-    ///
-    /// For switches, this means deleting the bridged interface
-    ///
-    /// For routers, this is deleting the respective namespaces
-    pub fn power_off(&self) -> NetResult<()> {
-        let power_off_span = debug_span!("power-off");
-        let _span_guard = power_off_span.enter();
-        // Powers off all the nodes
+    /// Creates what `plan` creates or updates, the rest of the topology
+    /// being up already. Has to be called from the main namespace.
+    pub fn build(&self, plan: &Plan) -> NetResult<()> {
+        let runtime = runtime()?;
+        let build_span = debug_span!("build");
+        let _span_guard = build_span.enter();
+        if let Some(state) = TopologyState::load()? {
+            frr::check_pinned(&state);
+        }
+
+        let created = |name: &str| {
+            matches!(
+                plan.node_action(name),
+                Some(Action::Create | Action::Update)
+            )
+        };
+
+        for (name, node) in &self.nodes {
+            match node {
+                _ if created(name) => {
+                    node.power_on(self.backend(), &runtime)?;
+                    self.hooks.node_up(name);
+                }
+                Node::Router(router) => {
+                    let _ = router
+                        .namespace
+                        .set(Box::new(NamespaceHandle::new(Some(name))));
+                }
+                Node::Switch(switch) => {
+                    if let Ok(ifindex) = if_nametoindex(switch.bridge.as_str())
+                    {
+                        let _ = switch.ifindex.set(ifindex);
+                    }
+                }
+            }
+        }
+
+        let links: Vec<Link> = self
+            .links
+            .iter()
+            .filter(|link| {
+                matches!(
+                    plan.link_action(link),
+                    Some(Action::Create | Action::Update)
+                )
+            })
+            .cloned()
+            .collect();
+        let routers = || {
+            self.nodes.iter().filter_map(|(name, node)| match node {
+                Node::Router(router) => Some((name, router)),
+                Node::Switch(_) => None,
+            })
+        };
+
+        // Whatever was brought up is recorded, even when cancelled or failing
+        // halfway, for `netgen stop` to tear it down.
+        let result = (|| -> NetResult<()> {
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                router.iface_up(1, &runtime)?;
+            }
+            LinkManager::connect(
+                &runtime,
+                self.veth_prefix(),
+                &self.nodes,
+                &links,
+                self.parallelism,
+            )?;
+            self.configure_ports(&links)?;
+            self.impair_links(&links)?;
+            self.cancel.check()?;
+
+            for (name, router) in routers() {
+                if created(name) {
+                    router.add_dummies(&runtime)?;
+                    router.add_iface_addresses(&runtime)?;
+                    continue;
+                }
+                // Existing routers only need addresses on their new links.
+                let mut new_ifaces = router.clone();
+                new_ifaces.interfaces.retain(|iface| {
+                    links.iter().any(|link| {
+                        link.ends().iter().any(|end| end.is(name, &iface.name))
+                    })
+                });
+                if !new_ifaces.interfaces.is_empty() {
+                    new_ifaces.add_iface_addresses(&runtime)?;
+                }
+            }
+            for link in &links {
+                self.hooks.link_up(link);
+            }
+            self.hold_disabled_links(&links)?;
+
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                self.apply_families(router)?;
+                router.add_static_routes(&runtime)?;
+                self.apply_rules(router)?;
+                self.apply_nat(router)?;
+            }
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                router.issue_cert()?;
+            }
+            for (_, node) in self.nodes.iter().filter(|(name, _)| created(name))
+            {
+                self.cancel.check()?;
+                node.run(&runtime)?;
+            }
+            self.cancel.check()?;
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                router.run_ssh(&runtime)?;
+            }
+            self.cancel.check()?;
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                router.run_ntp(&runtime)?;
+            }
+            self.cancel.check()?;
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                router.run_probes()?;
+            }
+            self.cancel.check()?;
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                router.run_lldp()?;
+            }
+            self.cancel.check()?;
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                if router.frr_is_lazy() {
+                    debug!(router = %router.name, "FRR left for first use");
+                    continue;
+                }
+                router.run_frr(&runtime)?;
+                for daemon in router.frr_daemons() {
+                    self.hooks.daemon_ready(&router.name, daemon);
+                }
+            }
+            self.cancel.check()?;
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                router.run_injector(&runtime)?;
+            }
+            self.cancel.check()?;
+            for (_, router) in routers().filter(|(name, _)| created(name)) {
+                router.run_services(&runtime)?;
+                for service in &router.services {
+                    self.hooks.daemon_ready(&router.name, &service.name);
+                }
+            }
+            Ok(())
+        })();
+
+        let _state_lock = TopologyState::lock()?;
+        let mut state = TopologyState::load()?
+            .unwrap_or_else(|| TopologyState::new(&self.session));
+        state.topology = Some(self.source.clone());
+        state.provenance =
+            Some(Provenance::updated(state.provenance.take(), self));
+        state.profiles = self.active_profiles.iter().cloned().collect();
+        state.metadata = self.metadata();
         for node in self.nodes.values() {
-            node.power_off()?;
+            if let Node::Switch(switch) = node {
+                state
+                    .bridges
+                    .insert(switch.name.clone(), switch.bridge.clone());
+            }
+        }
+        state.save()?;
+        result
+    }
+
+    /// Makes the ports of the routers `plan` creates or updates reachable
+    /// from the calling process' network namespace.
+    pub fn expose_changed_ports(&self, plan: &Plan) -> NetResult<()> {
+        self.expose_ports_of(|name| {
+            matches!(
+                plan.node_action(name),
+                Some(Action::Create | Action::Update)
+            )
+        })
+    }
+
+    /// Status of the processes netgen started in every node.
+    pub fn daemon_status(
+        &self,
+    ) -> NetResult<BTreeMap<String, Vec<DaemonStatus>>> {
+        let mut status = BTreeMap::new();
+        for (name, node) in &self.nodes {
+            status.insert(name.clone(), node.status()?);
         }
+        Ok(status)
+    }
 
-        // Kill main namespace process and unomunts it.
-        crate::destroy_ns(None)?;
-        Ok(())
+    /// The invariants declared by the topology, in order.
+    pub fn invariants(&self) -> &[Invariant] {
+        &self.invariants
+    }
+
+    /// Checks the invariants of the running topology, each check being
+    /// retried until it passes or `timeout` expires, then runs the custom
+    /// validators of `validator::validators_dir()` once each. Those left
+    /// once `cancel` is cancelled fail without being run.
+    pub fn verify(
+        &self,
+        timeout: Duration,
+        cancel: &CancelToken,
+    ) -> NetResult<TestReport> {
+        let mut report = TestReport::new(&self.session);
+        for invariant in &self.invariants {
+            debug!(%invariant, "verifying");
+            report
+                .cases
+                .extend(invariant.evaluate(self, timeout, cancel));
+        }
+        for link in self.enabled_links() {
+            for assertion in invariant::expectations(self, link, timeout) {
+                report.cases.push(assertion.evaluate(cancel));
+            }
+        }
+
+        let validators = validator::discover()?;
+        if !validators.is_empty() {
+            let input = validator::input(self)?;
+            for path in validators {
+                let case = match cancel.is_cancelled() {
+                    true => TestCase {
+                        name: validator::name(&path),
+                        duration: Duration::ZERO,
+                        failure: Some("cancelled".to_string()),
+                        node: None,
+                    },
+                    false => validator::run(&path, &self.session, &input),
+                };
+                report.cases.push(case);
+            }
+        }
+        report.lead_with(&self.duts());
+        Ok(report)
+    }
+
+    /// Compares the runtime state with the topology: missing namespaces,
+    /// links and interfaces, interfaces which are down, probe sessions which
+    /// are down, dead daemons and startup configs which failed to load.
+    pub fn status(&self) -> NetResult<TopologyStatus> {
+        let mut status = TopologyStatus::default();
+        let state = TopologyState::load()?.unwrap_or_default();
+        frr::check_pinned(&state);
+        let config_failures = state.config_failures;
+
+        let main_links = match self.backend.namespace_exists(None) {
+            true => Some(link_states(self.backend(), None)?),
+            false => None,
+        };
+
+        let toggles = link_toggles();
+        let held_down = |node: &str, iface: &str| {
+            self.links_of(node).any(|link| {
+                !self.link_enabled_in(link, &toggles)
+                    && link.ends().iter().any(|end| end.is(node, iface))
+            })
+        };
+
+        // Interfaces are only created for link endpoints, loopbacks and
+        // dummies.
+        let linked = |node: &str, iface: &str| {
+            iface == "lo"
+                || self.links_of(node).any(|link| {
+                    link.ends().iter().any(|end| end.is(node, iface))
+                })
+        };
+
+        let mut node_links = BTreeMap::new();
+        for (name, node) in &self.nodes {
+            let links = match node {
+                Node::Router(_) => {
+                    if main_links.is_none()
+                        || !self.backend.namespace_exists(Some(name))
+                    {
+                        status.push(name, "namespace", name, Health::Missing);
+                        continue;
+                    }
+                    status.push(name, "namespace", name, Health::Ok);
+                    link_states(self.backend(), Some(name))?
+                }
+                Node::Switch(switch) => {
+                    let Some(links) = &main_links else {
+                        status.push(
+                            name,
+                            "bridge",
+                            &switch.bridge,
+                            Health::Missing,
+                        );
+                        continue;
+                    };
+                    // A bridge only has carrier once a port is up.
+                    let health = match links.get(&switch.bridge) {
+                        Some(link) if link.up => Health::Ok,
+                        Some(_) => Health::Down,
+                        None => Health::Missing,
+                    };
+                    status.push(name, "bridge", &switch.bridge, health);
+                    links.clone()
+                }
+            };
+
+            for iface in node.interfaces() {
+                if !iface.dummy && !linked(name, &iface.name) {
+                    continue;
+                }
+                let health = match links.get(&iface.name) {
+                    None => Health::Missing,
+                    // Down as it is meant to be.
+                    Some(link) if !link.up && held_down(name, &iface.name) => {
+                        Health::Ok
+                    }
+                    Some(link) => link.health(),
+                };
+                status.push(name, "interface", &iface.name, health);
+            }
+            if let Node::Router(router) = node {
+                for probe in &router.probes {
+                    let health = match probe.state(name) {
+                        Some(state) if state.up => Health::Ok,
+                        Some(_) => Health::Down,
+                        None => Health::Missing,
+                    };
+                    let session = format!("{name} -> {}", probe.peer);
+                    status.push(name, "probe", &session, health);
+                }
+                if router.has_startup_config() {
+                    let health = match config_failures.contains_key(name) {
+                        true => Health::Failed,
+                        false => Health::Ok,
+                    };
+                    status.push(name, "config", "startup-config", health);
+                }
+                if let Some(addr) = state.grpc.get(name) {
+                    let health = match addr.parse().is_ok_and(|addr| {
+                        TcpStream::connect_timeout(
+                            &addr,
+                            Duration::from_millis(500),
+                        )
+                        .is_ok()
+                    }) {
+                        true => Health::Ok,
+                        false => Health::Missing,
+                    };
+                    status.push(name, "grpc", addr, health);
+                }
+            }
+            for daemon in node.status()? {
+                let health = match (daemon.running, &daemon.crash) {
+                    (true, _) => Health::Ok,
+                    (false, Some(_)) => Health::Crashed,
+                    (false, None) => Health::Dead,
+                };
+                let daemon = format!("{} ({})", daemon.name, daemon.pid);
+                status.push(name, "daemon", &daemon, health);
+            }
+            node_links.insert(name.clone(), links);
+        }
+
+        for link in &self.links {
+            let present = |end: &LinkEndpoint| {
+                node_links
+                    .get(end.node.as_str())
+                    .is_some_and(|links| links.contains_key(end.iface.as_str()))
+            };
+            let health = match link.ends().into_iter().all(present) {
+                true => Health::Ok,
+                false => Health::Missing,
+            };
+            let name = format!("{} <-> {}", link.src, link.dst);
+            status.push(link.src.node.as_str(), "link", &name, health);
+        }
+        status.grpc = state.grpc;
+        Ok(status)
     }
 
     pub fn setup_links(&self) -> NetResult<()> {
+        let runtime = runtime()?;
         LinkManager::setup_all(
-            &self.runtime,
+            &runtime,
+            self.veth_prefix(),
             &self.nodes,
             self.links.as_slice(),
-        )
+            self.parallelism,
+            &self.hooks,
+            &self.cancel,
+        )?;
+        self.cancel.check()?;
+        for node in self.nodes.values() {
+            if let Node::Router(router) = node {
+                self.apply_families(router)?;
+            }
+        }
+        self.configure_ports(&self.links)?;
+        self.impair_links(&self.links)?;
+        self.cancel.check()?;
+        for node in self.nodes.values() {
+            if let Node::Router(router) = node {
+                self.apply_rules(router)?;
+                self.apply_nat(router)?;
+            }
+        }
+        self.hold_disabled_links(&self.links)
+    }
+
+    // Brings down those of `links` meant to be, as toggled at runtime or
+    // else as the topology file says.
+    fn hold_disabled_links(&self, links: &[Link]) -> NetResult<()> {
+        let toggles = link_toggles();
+        for link in links {
+            if !self.link_enabled_in(link, &toggles) {
+                self.set_link_state(link, false)?;
+                debug!(link = %link_name(link), "held down");
+            }
+        }
+        Ok(())
+    }
+
+    // The links meant to be up, i.e. neither declared nor toggled down.
+    pub(crate) fn enabled_links(&self) -> Vec<&Link> {
+        let toggles = link_toggles();
+        self.links
+            .iter()
+            .filter(|link| self.link_enabled_in(link, &toggles))
+            .collect()
+    }
+
+    // Whether `link` is meant to be up.
+    fn link_enabled_in(&self, link: &Link, toggles: &LinkToggles) -> bool {
+        toggles
+            .get(&self.identity(), &link_name(link))
+            .unwrap_or(link.enabled)
+    }
+
+    /// Brings the link between the `a` and `b` ends ("<node>:<iface>") of
+    /// the running topology up or down, and remembers it so that applying
+    /// the topology or starting it again keeps it that way.
+    pub fn set_link_enabled(
+        &self,
+        a: &str,
+        b: &str,
+        enabled: bool,
+    ) -> NetResult<&Link> {
+        let link = self.link_between(a, b)?;
+        self.set_link_state(link, enabled)?;
+
+        LinkToggles::update(|toggles| {
+            toggles.set(
+                &self.identity(),
+                &link_name(link),
+                enabled,
+                link.enabled,
+            )
+        })?;
+        let event = if enabled { "enabled" } else { "disabled" };
+        events::record("link", &link_name(link), event);
+        Ok(link)
+    }
+
+    // Applies the impairment profiles of `links` at both of their ends.
+    fn impair_links(&self, links: &[Link]) -> NetResult<()> {
+        for link in links {
+            for end in link.ends() {
+                self.impair_end(end, link.impairment.as_ref(), &link.flows)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn impair_end(
+        &self,
+        end: &LinkEndpoint,
+        impairment: Option<&Impairment>,
+        flows: &[FlowImpairment],
+    ) -> NetResult<()> {
+        for args in netem::tc_commands(end.iface.as_str(), impairment, flows) {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let output =
+                self.backend.exec(self.end_device(end), "tc", &args)?;
+            if !output.status.success() {
+                return Err(NetError::BasicError(format!(
+                    "Unable to impair {end}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Changes the impairment of both ends of `link` in place, e.g. step by
+    // step along a ramp, the flows keeping theirs.
+    pub(crate) fn reimpair_link(
+        &self,
+        link: &Link,
+        impairment: &Impairment,
+    ) -> NetResult<()> {
+        for end in link.ends() {
+            let args = netem::replace_args(
+                end.iface.as_str(),
+                impairment,
+                !link.flows.is_empty(),
+            );
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let output =
+                self.backend.exec(self.end_device(end), "tc", &args)?;
+            if !output.status.success() {
+                return Err(NetError::BasicError(format!(
+                    "Unable to impair {end}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Keeps the kernel from using the families disabled on the router's
+    // interfaces, and on those it gets later on.
+    fn apply_families(&self, router: &Router) -> NetResult<()> {
+        let mut ifaces: Vec<&str> = router
+            .interfaces
+            .iter()
+            .map(|iface| iface.name.as_str())
+            .collect();
+        if !ifaces.contains(&"lo") {
+            ifaces.push("lo");
+        }
+        let mut sysctls = router.families.sysctls("default");
+        for iface in ifaces {
+            sysctls.extend(router.families_of(iface).sysctls(iface));
+        }
+        if sysctls.is_empty() {
+            return Ok(());
+        }
+
+        let mut args = vec!["-w"];
+        args.extend(sysctls.iter().map(String::as_str));
+        let output =
+            self.backend
+                .exec(Some(router.name.as_str()), "sysctl", &args)?;
+        if !output.status.success() {
+            return Err(NetError::BasicError(format!(
+                "Unable to disable address families on {}: {}",
+                router.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    // Adds the policy routing rules of the router, those selecting no
+    // addresses for each of its families.
+    fn apply_rules(&self, router: &Router) -> NetResult<()> {
+        let device = Some(router.name.as_str());
+        for rule in &router.rules {
+            let families = match rule.family() {
+                Some(family) => vec![family],
+                None => router.families.iter().collect(),
+            };
+            for family in families {
+                let args = rule.args(family);
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                let output = self.backend.exec(device, "ip", &args)?;
+                if !output.status.success() {
+                    return Err(NetError::BasicError(format!(
+                        "Unable to add rule '{rule}' to {}: {}",
+                        router.name,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Loads the NAT rules of the router into nftables, replacing those
+    // loaded before, and has it forward packets for them to apply to.
+    fn apply_nat(&self, router: &Router) -> NetResult<()> {
+        if router.nat.is_empty() {
+            return Ok(());
+        }
+        let dir = format!("{}/{}", devices_ns_dir(), router.name);
+        let path = format!("{dir}/nat.nft");
+        fs::create_dir_all(&dir)
+            .and_then(|_| fs::write(&path, nat::ruleset(&router.nat)))
+            .map_err(|err| {
+                NetError::BasicError(format!("Unable to write {path}: {err}"))
+            })?;
+
+        let device = Some(router.name.as_str());
+        let forwarding = [
+            "-w",
+            "net.ipv4.ip_forward=1",
+            "net.ipv6.conf.all.forwarding=1",
+        ];
+        for (program, args) in
+            [("sysctl", &forwarding[..]), ("nft", &["-f", path.as_str()])]
+        {
+            let output = self.backend.exec(device, program, args)?;
+            if !output.status.success() {
+                return Err(NetError::BasicError(format!(
+                    "Unable to apply the NAT rules of {}: {}",
+                    router.name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Cuts the running routers `nodes` off from their controllers, or makes
+    /// them reachable again when `reachable`. What the host reaches them
+    /// through, their exposed ports and gRPC northbound, is blocked, along
+    /// with everything through their management `ifaces`. Their data plane
+    /// is left alone.
+    pub fn set_mgmt_reachable(
+        &self,
+        nodes: &[String],
+        ifaces: &[String],
+        reachable: bool,
+    ) -> NetResult<()> {
+        let mut rulesets = vec![];
+        for node in nodes {
+            let router = match self.nodes.get(node.as_str()) {
+                Some(Node::Router(router)) => router,
+                Some(Node::Switch(_)) => {
+                    return Err(NetError::BasicError(format!(
+                        "{node} is a switch, it has no management plane"
+                    )));
+                }
+                None => {
+                    return Err(ConfigError::UnknownNode(node.clone()).into());
+                }
+            };
+            let targets: Vec<SocketAddr> = router
+                .expose
+                .iter()
+                .chain(&router.grpc)
+                .map(PortForward::target_addr)
+                .collect();
+            if let Some(iface) = ifaces.iter().find(|iface| {
+                !router.interfaces.iter().any(|other| other.name == **iface)
+            }) {
+                return Err(NetError::BasicError(format!(
+                    "{node} has no interface {iface}"
+                )));
+            }
+            if !reachable && targets.is_empty() && ifaces.is_empty() {
+                return Err(NetError::BasicError(format!(
+                    "{node} exposes no port, name its management interfaces"
+                )));
+            }
+            rulesets.push((
+                router,
+                match reachable {
+                    true => mgmt::restore(),
+                    false => mgmt::ruleset(&targets, ifaces),
+                },
+            ));
+        }
+
+        for (router, ruleset) in rulesets {
+            let dir = format!("{}/{}", devices_ns_dir(), router.name);
+            let path = format!("{dir}/mgmt.nft");
+            fs::create_dir_all(&dir)
+                .and_then(|_| fs::write(&path, ruleset))
+                .map_err(|err| {
+                    NetError::BasicError(format!(
+                        "Unable to write {path}: {err}"
+                    ))
+                })?;
+            let output = self.backend.exec(
+                Some(router.name.as_str()),
+                "nft",
+                &["-f", path.as_str()],
+            )?;
+            if !output.status.success() {
+                return Err(NetError::BasicError(format!(
+                    "Unable to change the management plane of {}: {}",
+                    router.name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            let event = match reachable {
+                true => "management restored",
+                false => "management cut off",
+            };
+            events::record("mgmt", &router.name, event);
+        }
+        Ok(())
+    }
+
+    // Applies the bridge options of the switch ports `links` attach to.
+    fn configure_ports(&self, links: &[Link]) -> NetResult<()> {
+        for end in links.iter().flat_map(Link::ends) {
+            let Some(Node::Switch(switch)) = self.nodes.get(end.node.as_str())
+            else {
+                continue;
+            };
+            let Some(port) = switch.ports.get(end.iface.as_str()) else {
+                continue;
+            };
+            let mut commands = vec![];
+            let options = port.bridge_args();
+            if !options.is_empty() {
+                let mut args = vec!["link", "set", "dev", end.iface.as_str()];
+                args.extend(options);
+                commands.push(("bridge", args));
+            }
+            let tc_commands = port.tc_commands(end.iface.as_str());
+            for args in &tc_commands {
+                commands
+                    .push(("tc", args.iter().map(String::as_str).collect()));
+            }
+            for (program, args) in commands {
+                let output = self.backend.exec(None, program, &args)?;
+                if !output.status.success() {
+                    return Err(NetError::BasicError(format!(
+                        "Unable to set the options of port {end}: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Namespace of the end, switch ends living in the main one.
+    fn end_device<'a>(&self, end: &'a LinkEndpoint) -> Option<&'a str> {
+        match self.nodes.get(end.node.as_str()) {
+            Some(Node::Router(_)) => Some(end.node.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The impairments applied to every link end, as read back from their
+    /// qdiscs, in the format `import_impairments` takes. Whatever was
+    /// changed with `tc` since the links were set up is captured as well.
+    ///
+    /// ```yaml
+    /// impairments:
+    ///   rt1:eth0:
+    ///     delay: 50
+    ///     jitter: 15
+    ///     loss: 0.5
+    ///     rate: 20000
+    ///   rt2:eth0: ~
+    /// ```
+    pub fn export_impairments(&self) -> NetResult<String> {
+        let mut ends = Hash::new();
+        for end in self.links.iter().flat_map(Link::ends) {
+            let output = self.backend.exec(
+                self.end_device(end),
+                "tc",
+                &["qdisc", "show", "dev", end.iface.as_str()],
+            )?;
+            if !output.status.success() {
+                return Err(NetError::BasicError(format!(
+                    "Unable to read the qdiscs of {end}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            let impairment = Impairment::from_qdiscs(&String::from_utf8_lossy(
+                &output.stdout,
+            ));
+            let value = match impairment {
+                Some(impairment) => {
+                    let mut fields = Hash::new();
+                    let millis = |duration: Duration| {
+                        Yaml::Integer(duration.as_millis() as i64)
+                    };
+                    for (field, value) in [
+                        ("delay", millis(impairment.delay)),
+                        ("jitter", millis(impairment.jitter)),
+                        ("loss", Yaml::Real(impairment.loss.to_string())),
+                    ] {
+                        fields.insert(Yaml::String(field.to_string()), value);
+                    }
+                    if let Some(rate) = impairment.rate {
+                        fields.insert(
+                            Yaml::String("rate".to_string()),
+                            Yaml::Integer(rate.into()),
+                        );
+                    }
+                    Yaml::Hash(fields)
+                }
+                None => Yaml::Null,
+            };
+            ends.insert(Yaml::String(end.to_string()), value);
+        }
+
+        let mut doc = Hash::new();
+        doc.insert(Yaml::String("impairments".to_string()), Yaml::Hash(ends));
+        let mut out = String::new();
+        YamlEmitter::new(&mut out)
+            .dump(&Yaml::Hash(doc))
+            .map_err(|err| {
+                NetError::BasicError(format!(
+                    "Unable to write the impairments: {err:?}"
+                ))
+            })?;
+        out.push('\n');
+        Ok(out)
+    }
+
+    /// Applies the impairments of `source`, as written by
+    /// `export_impairments`, to the link ends it lists, the others being
+    /// left alone. Ends without any (`~`) are cleared. The flows of the
+    /// links keep their own impairments. Returns the ends impaired.
+    pub fn import_impairments(&self, source: &str) -> NetResult<Vec<String>> {
+        let yaml_path = YamlPath::new().key("impairments");
+        let docs = YamlLoader::load_from_str(source).map_err(|err| {
+            NetError::BasicError(format!("Invalid impairments: {err}"))
+        })?;
+        let ends = match docs.first().map(|doc| &doc["impairments"]) {
+            Some(Yaml::Hash(ends)) => ends,
+            Some(Yaml::BadValue) | None => {
+                return Err(ConfigError::MissingField {
+                    path: yaml_path.unknown(),
+                }
+                .into());
+            }
+            Some(_) => {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.unknown(),
+                    expected: "hash".to_string(),
+                }
+                .into());
+            }
+        };
+
+        // Everything is checked before any end is touched.
+        let mut impairments = vec![];
+        for (name, config) in ends {
+            let Some(name) = name.as_str() else {
+                return Err(ConfigError::IncorrectType {
+                    path: yaml_path.unknown(),
+                    expected: "hash of <node>:<iface>".to_string(),
+                }
+                .into());
+            };
+            let end = parse_end(name)?;
+            let link = self
+                .links
+                .iter()
+                .find(|link| link.ends().contains(&&end))
+                .ok_or_else(|| {
+                    NetError::BasicError(format!(
+                        "No link ends at {end} in the topology."
+                    ))
+                })?;
+            let impairment = match config {
+                Yaml::Null => None,
+                config => Some(Impairment::from_yaml_config(
+                    name,
+                    config,
+                    BTreeMap::from([("parent", "impairments")]),
+                )?),
+            };
+            impairments.push((end, impairment, link));
+        }
+
+        let mut impaired = vec![];
+        for (end, impairment, link) in impairments {
+            // The qdiscs are set up from scratch, there may be none yet.
+            let _ = self.backend.exec(
+                self.end_device(&end),
+                "tc",
+                &["qdisc", "del", "dev", end.iface.as_str(), "root"],
+            );
+            self.impair_end(&end, impairment.as_ref(), &link.flows)?;
+            impaired.push(end.to_string());
+        }
+        Ok(impaired)
     }
 }
@@ -0,0 +1,167 @@
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use tracing::debug;
+use yaml_rust2::YamlLoader;
+use yaml_rust2::yaml::Yaml;
+
+use crate::error::NetError;
+use crate::lab::config_dir;
+use crate::report::{TestCase, json_string};
+use crate::topology::Topology;
+use crate::{NetResult, state_file};
+
+const VALIDATORS_DIR: &str = "validators";
+
+/// Where the custom validators are dropped, `validators` in the netgen
+/// config directory. Every executable in there is run by `netgen verify`
+/// along with the invariants of the topology.
+pub fn validators_dir() -> NetResult<PathBuf> {
+    Ok(config_dir("the validators")?.join(VALIDATORS_DIR))
+}
+
+/// The executables of `validators_dir()`, by file name.
+pub(crate) fn discover() -> NetResult<Vec<PathBuf>> {
+    let dir = validators_dir()?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => {
+            return Err(NetError::BasicError(format!(
+                "Unable to read {}: {err:?}",
+                dir.display()
+            )));
+        }
+    };
+    let mut validators: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let executable = fs::metadata(path).is_ok_and(|meta| {
+                meta.is_file() && meta.permissions().mode() & 0o111 != 0
+            });
+            if !executable {
+                debug!(path = %path.display(), "not a validator, skipped");
+            }
+            executable
+        })
+        .collect();
+    validators.sort();
+    Ok(validators)
+}
+
+/// What validators get on their standard input: the running topology and
+/// its state file, both as JSON.
+///
+/// ```json
+/// {"session": "ng1", "topology": {"routers": ..}, "state": {"pids": ..}}
+/// ```
+pub(crate) fn input(topology: &Topology) -> NetResult<String> {
+    let to_json = |source: &str, what: &str| -> NetResult<String> {
+        let docs = YamlLoader::load_from_str(source).map_err(|err| {
+            NetError::BasicError(format!("Unable to parse {what}: {err}"))
+        })?;
+        Ok(docs
+            .first()
+            .map_or_else(|| "null".to_string(), yaml_to_json))
+    };
+    let state = match fs::read_to_string(state_file()) {
+        Ok(state) => to_json(&state, "the state file")?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => "null".to_string(),
+        Err(err) => {
+            return Err(NetError::BasicError(format!(
+                "Unable to read {}: {err:?}",
+                state_file()
+            )));
+        }
+    };
+    Ok(format!(
+        "{{\"session\": {}, \"topology\": {}, \"state\": {state}}}\n",
+        json_string(topology.session()),
+        to_json(topology.source(), "the topology")?,
+    ))
+}
+
+/// Runs the validator at `path` with `input` on its standard input. It
+/// passes by exiting successfully, and otherwise fails with what it wrote
+/// to its standard error.
+pub(crate) fn run(path: &Path, session: &str, input: &str) -> TestCase {
+    let start = Instant::now();
+    debug!(validator = %path.display(), "validating");
+    let failure = execute(path, session, input).err();
+    TestCase {
+        name: name(path),
+        duration: start.elapsed(),
+        failure,
+        node: None,
+    }
+}
+
+/// Name of the test case of the validator at `path`.
+pub(crate) fn name(path: &Path) -> String {
+    let file_name = path.file_name().unwrap_or(path.as_os_str());
+    format!("validator {}", file_name.to_string_lossy())
+}
+
+fn execute(path: &Path, session: &str, input: &str) -> Result<(), String> {
+    let mut child = Command::new(path)
+        .env("NETGEN_SESSION", session)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("unable to run: {err}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A validator which does not read its input is free not to.
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    match (output.status.success(), stderr.is_empty()) {
+        (true, _) => Ok(()),
+        (false, true) => Err(format!("exited with {}", output.status)),
+        (false, false) => Err(stderr),
+    }
+}
+
+// Renders YAML as JSON, keys which are not strings being quoted as such.
+fn yaml_to_json(yaml: &Yaml) -> String {
+    match yaml {
+        Yaml::String(value) => json_string(value),
+        Yaml::Integer(value) => value.to_string(),
+        Yaml::Real(value) => match value.parse::<f64>() {
+            Ok(real) if real.is_finite() => real.to_string(),
+            _ => json_string(value),
+        },
+        Yaml::Boolean(value) => value.to_string(),
+        Yaml::Array(values) => format!(
+            "[{}]",
+            values
+                .iter()
+                .map(yaml_to_json)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Yaml::Hash(entries) => format!(
+            "{{{}}}",
+            entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        Yaml::String(key) => key.clone(),
+                        Yaml::Integer(key) => key.to_string(),
+                        Yaml::Real(key) => key.clone(),
+                        Yaml::Boolean(key) => key.to_string(),
+                        _ => "null".to_string(),
+                    };
+                    format!("{}: {}", json_string(&key), yaml_to_json(value))
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Yaml::Null | Yaml::Alias(_) | Yaml::BadValue => "null".to_string(),
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use crate::events::{Event, unix_millis};
+use crate::notify::{Notification, Trigger};
+use crate::state::TopologyState;
+use crate::topology::Topology;
+use crate::{NamespaceHandle, NetResult, events_file, with_run_dir};
+
+const RESET: &str = "\x1b[0m";
+
+// Colors the nodes are told apart with, picked by name.
+const NODE_COLORS: &[&str] = &[
+    "\x1b[36m", "\x1b[35m", "\x1b[33m", "\x1b[32m", "\x1b[34m", "\x1b[96m",
+    "\x1b[95m", "\x1b[93m",
+];
+
+// How often the state file and the events log are read again.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// ==== enum Source ====
+
+/// What reported a `WatchEvent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Links, addresses and routes changing in a router's namespace.
+    Kernel,
+    /// Processes netgen started in the nodes starting and exiting.
+    Daemon,
+    /// Events recorded by netgen itself, e.g. probes and scenario steps.
+    Netgen,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self {
+            Source::Kernel => "kernel",
+            Source::Daemon => "daemon",
+            Source::Netgen => "netgen",
+        };
+        // Padding is honored so that the sources can be aligned.
+        f.pad(source)
+    }
+}
+
+// ==== struct WatchEvent ====
+
+/// A change of the running topology, summarized in a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchEvent {
+    // Milliseconds since the Unix epoch.
+    pub time: u128,
+    pub node: String,
+    pub source: Source,
+    pub summary: String,
+}
+
+impl WatchEvent {
+    /// The notification this event triggers, if any.
+    pub fn notification(&self) -> Option<Notification> {
+        // Told apart by the summaries written below.
+        let trigger = match self.source {
+            Source::Daemon if self.summary.contains(" crashed, see ") => {
+                Trigger::DaemonCrashed
+            }
+            Source::Daemon if self.summary.ends_with(" exited") => {
+                Trigger::DaemonExited
+            }
+            Source::Kernel
+                if self.summary.starts_with("link ")
+                    && (self.summary.ends_with(" DOWN")
+                        || self.summary.ends_with(" LOWERLAYERDOWN")) =>
+            {
+                Trigger::LinkDown
+            }
+            Source::Netgen
+                if self.summary.starts_with("probe ")
+                    && self.summary.ends_with(" down") =>
+            {
+                Trigger::ProbeDown
+            }
+            _ => return None,
+        };
+        Some(Notification {
+            trigger,
+            subject: self.node.clone(),
+            summary: self.summary.clone(),
+        })
+    }
+}
+
+// ==== struct Watcher ====
+
+/// Gathers what happens in the running topology into a single stream of
+/// events, in the order they happen:
+///
+/// * the `ip monitor` output of every router, summarized
+/// * daemons started by netgen starting, exiting or crashing
+/// * the events netgen records, e.g. probe transitions and scenario steps
+///
+/// Events from before the watcher was started are not replayed.
+pub struct Watcher {
+    start: u128,
+    // Width of the longest node name, to align the events.
+    node_width: usize,
+    receiver: Receiver<WatchEvent>,
+}
+
+impl Watcher {
+    pub fn start(topology: &Topology) -> NetResult<Self> {
+        let start = unix_millis();
+        let (sender, receiver) = mpsc::channel();
+        let routers = topology.router_names();
+
+        for router in &routers {
+            let namespace = NamespaceHandle::new(Some(router));
+            if !namespace.is_active() {
+                warn!(%router, "router not running, not watching it");
+                continue;
+            }
+            let mut child = namespace.spawn(
+                "ip",
+                &["-o", "monitor", "label", "link", "address", "route"],
+            )?;
+            let Some(stdout) = child.stdout.take() else {
+                continue;
+            };
+            let (router, sender) = (router.clone(), sender.clone());
+            thread::spawn(with_run_dir(move || {
+                watch_kernel(&router, BufReader::new(stdout), &sender);
+                let _ = child.kill();
+                let _ = child.wait();
+            }));
+        }
+
+        let events = events_file();
+        let daemon_sender = sender.clone();
+        thread::spawn(with_run_dir(move || watch_daemons(&daemon_sender)));
+        thread::spawn(with_run_dir(move || watch_events(&events, &sender)));
+
+        let node_width = topology
+            .nodes()
+            .map(|node| node.name().len())
+            .max()
+            .unwrap_or_default();
+        Ok(Self {
+            start,
+            node_width,
+            receiver,
+        })
+    }
+
+    /// Waits for the next event.
+    pub fn next(&self) -> Option<WatchEvent> {
+        self.receiver.recv().ok()
+    }
+
+    /// The event as a line, prefixed with the seconds elapsed since the
+    /// watcher was started, its node being colored when `color` is set.
+    pub fn render(&self, event: &WatchEvent, color: bool) -> String {
+        let elapsed = event.time.saturating_sub(self.start);
+        let node = format!("{:<width$}", event.node, width = self.node_width);
+        let node = match color {
+            true => format!("{}{node}{RESET}", node_color(&event.node)),
+            false => node,
+        };
+        format!(
+            "+{}.{:03}  {node}  {:<6}  {}",
+            elapsed / 1000,
+            elapsed % 1000,
+            event.source,
+            event.summary
+        )
+    }
+}
+
+fn node_color(node: &str) -> &'static str {
+    let hash = node.bytes().fold(0usize, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(usize::from(byte))
+    });
+    NODE_COLORS[hash % NODE_COLORS.len()]
+}
+
+fn send(
+    sender: &Sender<WatchEvent>,
+    node: &str,
+    source: Source,
+    summary: String,
+) {
+    let _ = sender.send(WatchEvent {
+        time: unix_millis(),
+        node: node.to_string(),
+        source,
+        summary,
+    });
+}
+
+// Summarizes the `ip -o monitor label` lines of a router until it is gone.
+fn watch_kernel(
+    router: &str,
+    output: impl BufRead,
+    sender: &Sender<WatchEvent>,
+) {
+    // Link messages are sent on any change, only state changes are shown.
+    let mut link_states = BTreeMap::new();
+    for line in output.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let Some(summary) = summarize_kernel(&line, &mut link_states) else {
+            continue;
+        };
+        send(sender, router, Source::Kernel, summary);
+    }
+    debug!(%router, "ip monitor stopped");
+}
+
+// Summary of a line of `ip -o monitor label link address route`, e.g.:
+//
+// [LINK]3: eth0@if2: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500 ... state UP
+// [ADDR]3: eth0    inet 10.0.1.1/24 scope global eth0 ...
+// [ROUTE]Deleted 10.0.2.0/24 via 10.0.1.2 dev eth0 proto bgp metric 20
+fn summarize_kernel(
+    line: &str,
+    link_states: &mut BTreeMap<String, String>,
+) -> Option<String> {
+    let (label, message) = line.strip_prefix('[')?.split_once(']')?;
+    let (deleted, message) = match message.strip_prefix("Deleted ") {
+        Some(message) => (true, message),
+        None => (false, message),
+    };
+    let tokens: Vec<&str> = message.split_whitespace().collect();
+    // Value of the attribute following `name`, e.g. "dev eth0".
+    let attribute = |name: &str| {
+        tokens
+            .windows(2)
+            .find(|pair| pair[0] == name)
+            .map(|pair| pair[1])
+    };
+
+    match label {
+        "LINK" => {
+            let iface = tokens.get(1)?.trim_end_matches(':');
+            let iface = iface.split('@').next().unwrap_or(iface).to_string();
+            if deleted {
+                link_states.remove(&iface);
+                return Some(format!("link {iface} removed"));
+            }
+            let state = attribute("state").unwrap_or("UNKNOWN").to_string();
+            if link_states.get(&iface) == Some(&state) {
+                return None;
+            }
+            link_states.insert(iface.clone(), state.clone());
+            Some(format!("link {iface} {state}"))
+        }
+        "ADDR" => {
+            let iface = tokens.get(1)?;
+            let address = attribute("inet").or_else(|| attribute("inet6"))?;
+            let change = if deleted { "removed" } else { "added" };
+            Some(format!("address {address} {change} on {iface}"))
+        }
+        "ROUTE" => {
+            // Routes the kernel adds along with addresses are noise.
+            let prefix = tokens.first()?;
+            if ["local", "broadcast", "multicast", "anycast"].contains(prefix) {
+                return None;
+            }
+            let mut route = format!("route {prefix}");
+            for name in ["via", "dev", "proto"] {
+                if let Some(value) = attribute(name) {
+                    route += &format!(" {name} {value}");
+                }
+            }
+            route += if deleted { " removed" } else { " added" };
+            Some(route)
+        }
+        _ => None,
+    }
+}
+
+// Reports the processes of the state file starting and exiting.
+fn watch_daemons(sender: &Sender<WatchEvent>) {
+    // By node, name and PID, whether the process is running. Processes
+    // found on the first read are not reported as started.
+    let mut known: Option<BTreeMap<(String, String, i32), bool>> = None;
+    loop {
+        let processes = match TopologyState::load() {
+            Ok(state) => state.map(|state| state.processes).unwrap_or_default(),
+            Err(err) => {
+                debug!(%err, "unable to read the state file");
+                vec![]
+            }
+        };
+
+        let mut current = BTreeMap::new();
+        for process in processes {
+            let key = (process.node.clone(), process.name.clone(), process.pid);
+            let previous = known.as_ref().map(|known| known.get(&key).copied());
+            // Dead for good, without looking for its core again.
+            if previous == Some(Some(false)) {
+                current.insert(key, false);
+                continue;
+            }
+            let status = process.status();
+            let summary = match (previous, status.running) {
+                // First read.
+                (None, _) => None,
+                (Some(None), true) => Some(format!(
+                    "{} started (PID {})",
+                    status.name, status.pid
+                )),
+                (Some(None | Some(true)), false) => match &status.crash {
+                    Some(report) => Some(format!(
+                        "{} crashed, see {}",
+                        status.name,
+                        report.display()
+                    )),
+                    None => Some(format!("{} exited", status.name)),
+                },
+                (Some(Some(_)), _) => None,
+            };
+            if let Some(summary) = summary {
+                send(sender, &process.node, Source::Daemon, summary);
+            }
+            current.insert(key, status.running);
+        }
+        known = Some(current);
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// Follows the events log, from its end, and across its rotations.
+fn watch_events(path: &str, sender: &Sender<WatchEvent>) {
+    let rotated = format!("{path}.1");
+    let (mut inode, mut position) = File::open(path)
+        .and_then(|mut file| {
+            Ok((file.metadata()?.ino(), file.seek(SeekFrom::End(0))?))
+        })
+        .unwrap_or((0, 0));
+    // Part of a line written without its end yet.
+    let mut pending = String::new();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let Ok(file) = File::open(path) else {
+            continue;
+        };
+        let Ok(metadata) = file.metadata() else {
+            continue;
+        };
+        if metadata.ino() != inode || metadata.len() < position {
+            // Rotated, the rest of the log followed until now being there.
+            if let Ok(rotated) = File::open(&rotated)
+                && rotated.metadata().is_ok_and(|meta| meta.ino() == inode)
+            {
+                read_events(rotated, &mut position, &mut pending, sender);
+            }
+            // Or started over, by a new topology.
+            (inode, position) = (metadata.ino(), 0);
+            pending.clear();
+        }
+        read_events(file, &mut position, &mut pending, sender);
+    }
+}
+
+// Sends the events of the complete lines of `file` from `position` on.
+fn read_events(
+    mut file: File,
+    position: &mut u64,
+    pending: &mut String,
+    sender: &Sender<WatchEvent>,
+) {
+    if file.seek(SeekFrom::Start(*position)).is_err() {
+        return;
+    }
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    while let Ok(read) = reader.read_line(&mut line) {
+        if read == 0 {
+            break;
+        }
+        *position += read as u64;
+        pending.push_str(&line);
+        line.clear();
+        if !pending.ends_with('\n') {
+            continue;
+        }
+        if let Some(event) = parse_event(pending.trim_end()) {
+            let _ = sender.send(event);
+        }
+        pending.clear();
+    }
+}
+
+// An events log line, e.g. "1760612345123 42 probe rt1 -> rt2 down".
+fn parse_event(line: &str) -> Option<WatchEvent> {
+    let event = Event::parse(line)?;
+    // The subject starts with the node, e.g. "rt1:eth0" for LLDP.
+    let subject = event.text.split_whitespace().next()?;
+    let node = subject.split(':').next().unwrap_or(subject);
+    Some(WatchEvent {
+        time: event.time,
+        node: node.to_string(),
+        source: Source::Netgen,
+        summary: format!("{} {}", event.kind, event.text),
+    })
+}